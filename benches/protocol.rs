@@ -0,0 +1,42 @@
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use lightstreamer_rs::protocol::{ServerMessage, split_frame};
+use std::hint::black_box;
+
+/// A frame as sent at a market open: a burst of updates of a 12-field quote schema.
+fn update_frame() -> String {
+    (1..=100)
+        .map(|item| {
+            format!(
+                "U,1,{},{}.25|{}.50|^3|#|$|EUR|2025-05-16T09%3A00%3A00Z|^P%5B%5D|1200|caf%C3%A9\r\n",
+                item, item, item
+            )
+        })
+        .collect()
+}
+
+/// Decodes the frame as the session loop does, notification by notification.
+fn bench_updates(c: &mut Criterion) {
+    let frame = update_frame();
+    let mut group = c.benchmark_group("updates");
+    group.throughput(Throughput::Bytes(frame.len() as u64));
+    group.bench_function("parse", |b| {
+        b.iter(|| {
+            for line in split_frame(black_box(&frame)) {
+                black_box(ServerMessage::parse(line).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_subscription_notifications(c: &mut Criterion) {
+    c.bench_function("parse_subcmd", |b| {
+        b.iter(|| ServerMessage::parse(black_box("SUBCMD,12,1,4,1,2")).unwrap())
+    });
+    c.bench_function("parse_eos", |b| {
+        b.iter(|| ServerMessage::parse(black_box("EOS,12,3")).unwrap())
+    });
+}
+
+criterion_group!(benches, bench_updates, bench_subscription_notifications);
+criterion_main!(benches);
@@ -46,7 +46,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     my_subscription.set_data_adapter(None)?;
     my_subscription.set_requested_snapshot(Some(Snapshot::Yes))?;
-    my_subscription.add_listener(Box::new(MySubscriptionListener {}));
+    my_subscription.add_listener(Box::new(MySubscriptionListener {}))?;
     
     let config = Config {
         cst: std::env::var("CST")?,
@@ -69,7 +69,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
     //
     {
         let mut client = client.lock().await;
-        LightstreamerClient::subscribe(client.subscription_sender.clone(), my_subscription).await;
+        let subscribed =
+            LightstreamerClient::subscribe(client.subscription_sender.clone(), my_subscription);
+        tokio::spawn(async move {
+            match subscribed.await {
+                Ok(confirmation) => info!(
+                    "Subscribed to {} items with {} fields",
+                    confirmation.items, confirmation.fields
+                ),
+                Err(e) => error!("Subscription failed: {:?}", e),
+            }
+        });
         client
             .connection_options
             .set_forced_transport(Some(Transport::WsStreaming));
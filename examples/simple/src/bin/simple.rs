@@ -81,7 +81,7 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     my_subscription.set_data_adapter(Some(String::from("QUOTE_ADAPTER")))?;
     my_subscription.set_requested_snapshot(Some(Snapshot::Yes))?;
-    my_subscription.add_listener(Box::new(MySubscriptionListener {}));
+    my_subscription.add_listener(Box::new(MySubscriptionListener {}))?;
 
     // Create a new Lightstreamer client instance and wrap it in an Arc<Mutex<>> so it can be shared across threads.
     let client = Arc::new(Mutex::new(LightstreamerClient::new(
@@ -96,7 +96,17 @@ async fn main() -> Result<(), Box<dyn Error>> {
     //
     {
         let mut client = client.lock().await;
-        LightstreamerClient::subscribe(client.subscription_sender.clone(), my_subscription).await;
+        let subscribed =
+            LightstreamerClient::subscribe(client.subscription_sender.clone(), my_subscription);
+        tokio::spawn(async move {
+            match subscribed.await {
+                Ok(confirmation) => info!(
+                    "Subscribed to {} items with {} fields",
+                    confirmation.items, confirmation.fields
+                ),
+                Err(e) => info!("Subscription failed: {:?}", e),
+            }
+        });
         client
             .connection_options
             .set_forced_transport(Some(Transport::WsStreaming));
@@ -0,0 +1,330 @@
+//! # TLCP debug proxy
+//!
+//! A small WebSocket relay that sits between an application and a Lightstreamer Server and
+//! prints every TLCP frame exchanged in both directions, each one timestamped and tagged with
+//! its direction and connection number. It is meant for protocol-level troubleshooting and is
+//! only built when the `debug-proxy` feature is enabled:
+//!
+//! ```text
+//! cargo run --features debug-proxy --bin tlcp-proxy -- 127.0.0.1:8080 https://push.lightstreamer.com
+//! ```
+//!
+//! The application is then pointed at `http://127.0.0.1:8080/...` instead of the real server.
+//! The path and query string of each incoming WebSocket request are appended to the upstream
+//! address, and the requested WebSocket sub-protocol is forwarded unchanged.
+
+use futures_util::{SinkExt, StreamExt};
+use std::error::Error;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{Request, Response};
+use tokio_tungstenite::tungstenite::http::HeaderValue;
+use tokio_tungstenite::{accept_hdr_async, connect_async};
+use url::Url;
+
+/// Address the proxy listens on when none is given on the command line.
+const DEFAULT_LISTEN_ADDRESS: &str = "127.0.0.1:8080";
+
+/// Upstream server used when none is given on the command line.
+const DEFAULT_UPSTREAM: &str = "https://push.lightstreamer.com";
+
+/// Indentation used for the decoded parameters of a client request.
+const PARAM_INDENT: &str = "        ";
+
+/// Direction of a relayed frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Direction {
+    /// Frame sent by the application towards the server.
+    ClientToServer,
+    /// Frame sent by the server towards the application.
+    ServerToClient,
+}
+
+impl std::fmt::Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Direction::ClientToServer => write!(f, "C->S"),
+            Direction::ServerToClient => write!(f, "S->C"),
+        }
+    }
+}
+
+/// Formats a wall-clock time as `HH:MM:SS.mmm` (UTC).
+fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let millis = since_epoch.as_millis() % 1000;
+    let seconds_of_day = since_epoch.as_secs() % 86_400;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds_of_day / 3600,
+        (seconds_of_day % 3600) / 60,
+        seconds_of_day % 60,
+        millis
+    )
+}
+
+/// Pretty-prints a TLCP text frame.
+///
+/// Server frames may carry several notifications separated by CRLF; each one is printed on its
+/// own line. Client frames consist of a request name followed by URL-encoded parameters, which
+/// are decoded and printed one per line.
+///
+/// # Parameters
+///
+/// * `direction`: the direction of the frame.
+/// * `connection_id`: the sequential number of the proxied connection.
+/// * `timestamp`: the wall-clock time, already formatted.
+/// * `elapsed`: seconds elapsed since the connection was opened.
+/// * `text`: the raw frame.
+fn format_frame(
+    direction: Direction,
+    connection_id: usize,
+    timestamp: &str,
+    elapsed: f64,
+    text: &str,
+) -> String {
+    let header = format!(
+        "[{} +{:.3}s] #{} {}",
+        timestamp, elapsed, connection_id, direction
+    );
+    let lines: Vec<&str> = text
+        .split("\r\n")
+        .flat_map(|line| line.split('\n'))
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    let mut output = String::new();
+    match direction {
+        Direction::ServerToClient => {
+            for line in lines {
+                output.push_str(&format!("{} {}\n", header, line));
+            }
+        }
+        Direction::ClientToServer => {
+            let mut lines = lines.into_iter();
+            let request_name = lines.next().unwrap_or("");
+            output.push_str(&format!("{} {}\n", header, request_name));
+            for line in lines {
+                match serde_urlencoded::from_str::<Vec<(String, String)>>(line) {
+                    Ok(params) if !params.is_empty() => {
+                        for (name, value) in params {
+                            output.push_str(&format!("{}{} = {}\n", PARAM_INDENT, name, value));
+                        }
+                    }
+                    _ => output.push_str(&format!("{}{}\n", PARAM_INDENT, line)),
+                }
+            }
+        }
+    }
+    output
+}
+
+/// Converts an `http(s)` or `ws(s)` base address into the WebSocket URL for the given
+/// path and query.
+fn build_upstream_url(
+    upstream: &str,
+    path_and_query: &str,
+) -> Result<Url, Box<dyn Error + Send + Sync>> {
+    let mut url = Url::parse(upstream)?;
+    match url.scheme() {
+        "http" | "ws" => url
+            .set_scheme("ws")
+            .map_err(|_| "Failed to set scheme to ws for upstream URL.")?,
+        "https" | "wss" => url
+            .set_scheme("wss")
+            .map_err(|_| "Failed to set scheme to wss for upstream URL.")?,
+        invalid_scheme => {
+            return Err(format!("Unsupported upstream scheme '{}'.", invalid_scheme).into());
+        }
+    }
+    let base_path = url.path().trim_end_matches('/').to_string();
+    let (path, query) = match path_and_query.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path_and_query, None),
+    };
+    url.set_path(&format!("{}{}", base_path, path));
+    url.set_query(query);
+    Ok(url)
+}
+
+/// Prints a relayed message, if it carries text or a close frame.
+fn log_message(direction: Direction, connection_id: usize, opened_at: Instant, message: &Message) {
+    let timestamp = format_timestamp(SystemTime::now());
+    let elapsed = opened_at.elapsed().as_secs_f64();
+    match message {
+        Message::Text(text) => print!(
+            "{}",
+            format_frame(direction, connection_id, &timestamp, elapsed, text)
+        ),
+        Message::Close(frame) => println!(
+            "[{} +{:.3}s] #{} {} <close> {:?}",
+            timestamp, elapsed, connection_id, direction, frame
+        ),
+        Message::Binary(data) => println!(
+            "[{} +{:.3}s] #{} {} <binary {} bytes>",
+            timestamp,
+            elapsed,
+            connection_id,
+            direction,
+            data.len()
+        ),
+        _ => {}
+    }
+}
+
+/// Accepts one application connection, opens the matching upstream connection and relays
+/// frames in both directions until either side closes.
+// The handshake callback's error type is dictated by tungstenite.
+#[allow(clippy::result_large_err)]
+async fn handle_connection(
+    stream: TcpStream,
+    upstream: String,
+    connection_id: usize,
+) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut path_and_query = String::from("/");
+    let mut sub_protocol: Option<HeaderValue> = None;
+    let callback = |request: &Request, mut response: Response| {
+        if let Some(path) = request.uri().path_and_query() {
+            path_and_query = path.to_string();
+        }
+        if let Some(protocol) = request.headers().get("sec-websocket-protocol") {
+            sub_protocol = Some(protocol.clone());
+            response
+                .headers_mut()
+                .insert("sec-websocket-protocol", protocol.clone());
+        }
+        Ok(response)
+    };
+    let client_stream = accept_hdr_async(stream, callback).await?;
+
+    let upstream_url = build_upstream_url(&upstream, &path_and_query)?;
+    let mut request = upstream_url.as_str().into_client_request()?;
+    if let Some(protocol) = sub_protocol {
+        request
+            .headers_mut()
+            .insert("sec-websocket-protocol", protocol);
+    }
+    let (server_stream, _) = connect_async(request).await?;
+    println!("#{} connected to {}", connection_id, upstream_url);
+
+    let opened_at = Instant::now();
+    let (mut client_write, mut client_read) = client_stream.split();
+    let (mut server_write, mut server_read) = server_stream.split();
+    loop {
+        tokio::select! {
+            message = client_read.next() => match message {
+                Some(Ok(message)) => {
+                    log_message(Direction::ClientToServer, connection_id, opened_at, &message);
+                    let is_close = message.is_close();
+                    server_write.send(message).await?;
+                    if is_close {
+                        break;
+                    }
+                }
+                Some(Err(err)) => return Err(Box::new(err)),
+                None => break,
+            },
+            message = server_read.next() => match message {
+                Some(Ok(message)) => {
+                    log_message(Direction::ServerToClient, connection_id, opened_at, &message);
+                    let is_close = message.is_close();
+                    client_write.send(message).await?;
+                    if is_close {
+                        break;
+                    }
+                }
+                Some(Err(err)) => return Err(Box::new(err)),
+                None => break,
+            },
+        }
+    }
+    println!("#{} closed", connection_id);
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let mut args = std::env::args().skip(1);
+    let listen_address = args
+        .next()
+        .unwrap_or_else(|| DEFAULT_LISTEN_ADDRESS.to_string());
+    let upstream = args.next().unwrap_or_else(|| DEFAULT_UPSTREAM.to_string());
+    // Fail early on a malformed upstream address.
+    build_upstream_url(&upstream, "/")?;
+
+    let listener = TcpListener::bind(&listen_address).await?;
+    println!(
+        "TLCP debug proxy listening on {} -> {}",
+        listen_address, upstream
+    );
+
+    let connection_counter = AtomicUsize::new(0);
+    loop {
+        let (stream, peer) = listener.accept().await?;
+        let connection_id = connection_counter.fetch_add(1, Ordering::Relaxed) + 1;
+        println!("#{} accepted connection from {}", connection_id, peer);
+        let upstream = upstream.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle_connection(stream, upstream, connection_id).await {
+                eprintln!("#{} proxy error: {}", connection_id, err);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_timestamp() {
+        let time = UNIX_EPOCH + Duration::from_millis(3_723_004);
+        assert_eq!(format_timestamp(time), "01:02:03.004");
+    }
+
+    #[test]
+    fn test_format_server_frame_splits_notifications() {
+        let output = format_frame(
+            Direction::ServerToClient,
+            1,
+            "00:00:00.000",
+            0.5,
+            "PROBE\r\nU,1,1,a|b\r\n",
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], "[00:00:00.000 +0.500s] #1 S->C PROBE");
+        assert!(lines[1].ends_with("S->C U,1,1,a|b"));
+    }
+
+    #[test]
+    fn test_format_client_frame_decodes_parameters() {
+        let output = format_frame(
+            Direction::ClientToServer,
+            2,
+            "00:00:00.000",
+            0.0,
+            "control\r\nLS_reqId=1&LS_op=add&LS_group=item1+item2",
+        );
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "[00:00:00.000 +0.000s] #2 C->S control");
+        assert_eq!(lines[1].trim(), "LS_reqId = 1");
+        assert_eq!(lines[2].trim(), "LS_op = add");
+        assert_eq!(lines[3].trim(), "LS_group = item1 item2");
+    }
+
+    #[test]
+    fn test_build_upstream_url() {
+        let url = build_upstream_url("https://push.example.com", "/lightstreamer?x=1").unwrap();
+        assert_eq!(url.as_str(), "wss://push.example.com/lightstreamer?x=1");
+
+        let url = build_upstream_url("http://localhost:8080/base/", "/lightstreamer").unwrap();
+        assert_eq!(url.as_str(), "ws://localhost:8080/base/lightstreamer");
+
+        assert!(build_upstream_url("ftp://example.com", "/").is_err());
+    }
+}
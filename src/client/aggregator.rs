@@ -0,0 +1,376 @@
+use crate::client::{ClientListener, LightstreamerClient};
+use crate::subscription::{ItemUpdate, Subscription, SubscriptionListener};
+use crate::utils::logging::warn;
+use crate::utils::{IllegalArgumentException, LightstreamerError};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// An update received by a `FeedAggregator`, tagged with the name of the source it came from.
+#[derive(Debug, Clone)]
+pub struct TaggedUpdate {
+    /// The name of the source, as given to `FeedAggregator::add_source()`.
+    pub source: String,
+    /// The update.
+    pub update: ItemUpdate,
+}
+
+/// The health of a source of a `FeedAggregator`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceHealth {
+    /// The last status of the client of the source, as returned by
+    /// `LightstreamerClient::get_status()`.
+    pub status: String,
+    /// The number of updates received from the source.
+    pub updates: u64,
+    /// When the last update was received from the source, if any.
+    pub last_update: Option<Instant>,
+    /// The last error notified by the Server or returned by `LightstreamerClient::connect()`,
+    /// if any.
+    pub last_error: Option<String>,
+}
+
+impl SourceHealth {
+    /// Returns whether the client of the source is connected.
+    pub fn is_connected(&self) -> bool {
+        self.status.starts_with("CONNECTED:")
+    }
+}
+
+impl Default for SourceHealth {
+    fn default() -> Self {
+        SourceHealth {
+            status: "DISCONNECTED".to_string(),
+            updates: 0,
+            last_update: None,
+            last_error: None,
+        }
+    }
+}
+
+/// Shared handle on the health of the sources of a `FeedAggregator`, which can be kept to
+/// inspect them while the aggregator runs.
+#[derive(Debug, Clone, Default)]
+pub struct AggregatorHealth {
+    sources: Arc<Mutex<HashMap<String, SourceHealth>>>,
+}
+
+impl AggregatorHealth {
+    /// Returns the health of the given source, if known.
+    pub fn get_source(&self, source: &str) -> Option<SourceHealth> {
+        self.lock().get(source).cloned()
+    }
+
+    /// Returns the health of all the sources, by name.
+    pub fn get_sources(&self) -> HashMap<String, SourceHealth> {
+        self.lock().clone()
+    }
+
+    /// Returns the names of the sources whose client is connected.
+    pub fn get_connected_sources(&self) -> Vec<String> {
+        let mut connected: Vec<String> = self
+            .lock()
+            .iter()
+            .filter(|(_, health)| health.is_connected())
+            .map(|(source, _)| source.clone())
+            .collect();
+        connected.sort();
+        connected
+    }
+
+    /// Applies a change to the health of the given source.
+    fn update(&self, source: &str, change: impl FnOnce(&mut SourceHealth)) {
+        change(self.lock().entry(source.to_string()).or_default());
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, SourceHealth>> {
+        self.sources.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// `ClientListener` tracking the status and errors of the client of a source.
+#[derive(Debug)]
+struct SourceListener {
+    source: String,
+    health: AggregatorHealth,
+}
+
+impl ClientListener for SourceListener {
+    fn on_server_error(&self, code: i32, message: &str) {
+        let error = format!("{}: {}", code, message);
+        self.health
+            .update(&self.source, |health| health.last_error = Some(error));
+    }
+
+    fn on_status_change(&self, status: &str) {
+        self.health
+            .update(&self.source, |health| health.status = status.to_string());
+    }
+}
+
+/// `SubscriptionListener` forwarding the updates of a source to the merged stream.
+struct ForwardingListener {
+    source: String,
+    sender: UnboundedSender<TaggedUpdate>,
+    health: AggregatorHealth,
+}
+
+impl SubscriptionListener for ForwardingListener {
+    fn on_item_update(&self, update: &ItemUpdate) {
+        self.health.update(&self.source, |health| {
+            health.updates += 1;
+            health.last_update = Some(Instant::now());
+        });
+        // Updates are dropped once the consumer has gone away.
+        let _ = self.sender.send(TaggedUpdate {
+            source: self.source.clone(),
+            update: update.clone(),
+        });
+    }
+}
+
+/// Client consolidating several feeds, such as different regions or Adapter Sets, by managing a
+/// `LightstreamerClient` for each of them and merging their updates in a single stream, tagged
+/// with the name of their source.
+///
+/// Sources and their subscriptions are registered before the aggregator runs; the health of
+/// each source can be followed through `get_health()`.
+///
+/// ```ignore
+/// let mut aggregator = FeedAggregator::new();
+/// aggregator.add_source("eu", eu_client)?;
+/// aggregator.add_source("us", us_client)?;
+/// aggregator.add_subscription("eu", eu_quotes)?;
+/// aggregator.add_subscription("us", us_quotes)?;
+/// let mut updates = aggregator.take_updates().unwrap();
+/// tokio::spawn(aggregator.run(shutdown));
+/// while let Some(TaggedUpdate { source, update }) = updates.recv().await { ... }
+/// ```
+#[derive(Debug)]
+pub struct FeedAggregator {
+    /// The clients of the sources, in registration order.
+    sources: Vec<(String, LightstreamerClient)>,
+    /// The health of the sources.
+    health: AggregatorHealth,
+    /// The sending end of the merged stream, given to the subscriptions.
+    sender: UnboundedSender<TaggedUpdate>,
+    /// The receiving end of the merged stream, until taken.
+    receiver: Option<UnboundedReceiver<TaggedUpdate>>,
+    /// The runtime the sessions of the sources run on, if not the current one.
+    runtime_handle: Option<Handle>,
+}
+
+impl Default for FeedAggregator {
+    fn default() -> Self {
+        let (sender, receiver) = unbounded_channel();
+        FeedAggregator {
+            sources: Vec::new(),
+            health: AggregatorHealth::default(),
+            sender,
+            receiver: Some(receiver),
+            runtime_handle: None,
+        }
+    }
+}
+
+impl FeedAggregator {
+    /// Creates an aggregator with no sources.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a source, whose client will be connected when the aggregator runs.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`: The name tagging the updates of the source.
+    /// * `client`: The client connecting to the source, already configured.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if a source with the same name was already added.
+    pub fn add_source(
+        &mut self,
+        name: &str,
+        mut client: LightstreamerClient,
+    ) -> Result<(), LightstreamerError> {
+        if self.sources.iter().any(|(source, _)| source == name) {
+            return Err(IllegalArgumentException::new(&format!(
+                "Source '{}' was already added",
+                name
+            ))
+            .into());
+        }
+        client.add_listener(Box::new(SourceListener {
+            source: name.to_string(),
+            health: self.health.clone(),
+        }));
+        let status = client.get_status().to_string();
+        self.health.update(name, |health| health.status = status);
+        self.sources.push((name.to_string(), client));
+        Ok(())
+    }
+
+    /// Adds a subscription to a source, forwarding its updates to the merged stream. The
+    /// listeners of the subscription are still notified as usual.
+    ///
+    /// # Parameters
+    ///
+    /// * `source`: The name of the source.
+    /// * `subscription`: The subscription.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if no source has the given name.
+    /// * `SubscriptionStateError`: if the subscription is active or was unsubscribed.
+    pub fn add_subscription(
+        &mut self,
+        source: &str,
+        mut subscription: Subscription,
+    ) -> Result<(), LightstreamerError> {
+        let Some((_, client)) = self.sources.iter_mut().find(|(name, _)| name == source) else {
+            return Err(
+                IllegalArgumentException::new(&format!("Unknown source '{}'", source)).into(),
+            );
+        };
+        subscription.add_listener(Box::new(ForwardingListener {
+            source: source.to_string(),
+            sender: self.sender.clone(),
+            health: self.health.clone(),
+        }))?;
+        client.add_subscription(subscription)?;
+        Ok(())
+    }
+
+    /// Takes the merged stream of the updates of all the sources. The stream is unbounded, so
+    /// that a slow consumer never stalls the sessions; it can be taken only once.
+    pub fn take_updates(&mut self) -> Option<UnboundedReceiver<TaggedUpdate>> {
+        self.receiver.take()
+    }
+
+    /// Returns a handle on the health of the sources.
+    pub fn get_health(&self) -> AggregatorHealth {
+        self.health.clone()
+    }
+
+    /// Sets the Tokio runtime the sessions of the sources run on, instead of the one `run()` is
+    /// called from.
+    ///
+    /// # Parameters
+    ///
+    /// * `handle`: The handle of the runtime, or `None` to use the current one.
+    pub fn set_runtime_handle(&mut self, handle: Option<Handle>) {
+        self.runtime_handle = handle;
+    }
+
+    /// Returns the names of the sources, in registration order.
+    pub fn get_sources(&self) -> Vec<String> {
+        self.sources.iter().map(|(name, _)| name.clone()).collect()
+    }
+
+    /// Connects all the sources and runs their sessions concurrently, until all of them end or
+    /// the shutdown signal is notified. A source failing does not affect the others; its error
+    /// is recorded in its health.
+    ///
+    /// # Parameters
+    ///
+    /// * `shutdown_signal`: The signal disconnecting all the sources, through `notify_waiters()`.
+    pub async fn run(self, shutdown_signal: Arc<Notify>) {
+        let runtime = self.runtime_handle.unwrap_or_else(Handle::current);
+        let mut tasks = Vec::new();
+        for (source, mut client) in self.sources {
+            let health = self.health.clone();
+            let shutdown_signal = shutdown_signal.clone();
+            tasks.push(runtime.spawn(async move {
+                let result = client.connect(shutdown_signal).await;
+                let status = client.get_status().to_string();
+                health.update(&source, |health| {
+                    health.status = status;
+                    if let Err(err) = result {
+                        warn!("Source '{}' ended with an error: {}", source, err);
+                        health.last_error = Some(err.to_string());
+                    }
+                });
+            }));
+        }
+        for task in tasks {
+            let _ = task.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::SubscriptionMode;
+
+    fn client() -> LightstreamerClient {
+        LightstreamerClient::new(Some("http://localhost:8080"), None, None, None).unwrap()
+    }
+
+    fn subscription() -> Subscription {
+        Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_sources_are_registered_once() {
+        let mut aggregator = FeedAggregator::new();
+        aggregator.add_source("eu", client()).unwrap();
+        aggregator.add_source("us", client()).unwrap();
+        assert!(aggregator.add_source("eu", client()).is_err());
+        assert!(aggregator.add_subscription("eu", subscription()).is_ok());
+        assert!(aggregator.add_subscription("asia", subscription()).is_err());
+        assert_eq!(aggregator.get_sources(), vec!["eu", "us"]);
+        assert!(aggregator.take_updates().is_some());
+        assert!(aggregator.take_updates().is_none());
+    }
+
+    #[test]
+    fn test_updates_are_tagged_and_counted() {
+        let mut aggregator = FeedAggregator::new();
+        let mut updates = aggregator.take_updates().unwrap();
+        let health = aggregator.get_health();
+        let listener = ForwardingListener {
+            source: "eu".to_string(),
+            sender: aggregator.sender.clone(),
+            health: health.clone(),
+        };
+        let source_listener = SourceListener {
+            source: "eu".to_string(),
+            health: health.clone(),
+        };
+
+        source_listener.on_status_change("CONNECTED:WS-STREAMING");
+        listener.on_item_update(&ItemUpdate {
+            item_name: Some("item1".to_string()),
+            item_pos: 1,
+            fields: HashMap::new(),
+            changed_fields: HashMap::new(),
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        });
+
+        let tagged = updates.try_recv().unwrap();
+        assert_eq!(tagged.source, "eu");
+        assert_eq!(tagged.update.item_pos, 1);
+        let eu = health.get_source("eu").unwrap();
+        assert_eq!(eu.updates, 1);
+        assert!(eu.last_update.is_some());
+        assert_eq!(health.get_connected_sources(), vec!["eu"]);
+
+        source_listener.on_server_error(41, "License");
+        source_listener.on_status_change("DISCONNECTED");
+        let eu = health.get_source("eu").unwrap();
+        assert_eq!(eu.last_error.as_deref(), Some("41: License"));
+        assert!(health.get_connected_sources().is_empty());
+    }
+}
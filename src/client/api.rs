@@ -0,0 +1,93 @@
+use crate::client::LightstreamerClient;
+use crate::client::model::ClientStatus;
+use crate::client::request::{MessageRequest, SubscriptionRequest};
+use crate::subscription::Subscription;
+use crate::utils::{IllegalStateException, LightstreamerError};
+use tokio::sync::mpsc::error::TrySendError;
+
+/// The surface of a client used by the feed logic of an application: subscriptions, messages
+/// and status.
+///
+/// It is implemented by `LightstreamerClient` and by `FakeClient`, an in-memory client with no
+/// network, so that the feed logic can be written against the trait and unit-tested by pushing
+/// updates to its Subscriptions and inspecting the messages it sends.
+///
+/// ```ignore
+/// fn start_feed(client: &mut impl StreamingClient) -> Result<(), LightstreamerError> {
+///     let subscription = Subscription::new(...)?;
+///     client.subscribe(subscription)
+/// }
+/// ```
+pub trait StreamingClient {
+    /// Adds a subscription, to be subscribed to as soon as a session is available.
+    ///
+    /// The ID of the subscription, needed by `unsubscribe()`, is not known until then; see
+    /// `Subscription::await_subscribed()` to wait for it.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription`: the subscription.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the subscription cannot be handed to the client.
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), LightstreamerError>;
+
+    /// Removes a subscription.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription_id`: the ID of the subscription.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the request cannot be handed to the client.
+    fn unsubscribe(&mut self, subscription_id: usize) -> Result<(), LightstreamerError>;
+
+    /// Sends a message. If it cannot be handed to the client, it is aborted, notifying its
+    /// listener, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `message_request`: the message, with its options.
+    fn send_message(&mut self, message_request: MessageRequest);
+
+    /// Returns the current status of the client.
+    fn get_status(&self) -> ClientStatus;
+}
+
+impl StreamingClient for LightstreamerClient {
+    /// Hands the subscription to the session through `subscription_sender`, without waiting.
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), LightstreamerError> {
+        subscription.check_subscribable()?;
+        self.subscription_sender
+            .try_send(SubscriptionRequest {
+                subscription: Some(subscription),
+                subscription_id: None,
+                responder: None,
+            })
+            .map_err(|_| IllegalStateException::new("Subscription queue unavailable").into())
+    }
+
+    fn unsubscribe(&mut self, subscription_id: usize) -> Result<(), LightstreamerError> {
+        self.subscription_sender
+            .try_send(SubscriptionRequest {
+                subscription: None,
+                subscription_id: Some(subscription_id),
+                responder: None,
+            })
+            .map_err(|_| IllegalStateException::new("Subscription queue unavailable").into())
+    }
+
+    fn send_message(&mut self, message_request: MessageRequest) {
+        match self.message_sender.try_send(message_request) {
+            Ok(()) => {}
+            Err(TrySendError::Full(message_request))
+            | Err(TrySendError::Closed(message_request)) => message_request.abort(false),
+        }
+    }
+
+    fn get_status(&self) -> ClientStatus {
+        LightstreamerClient::get_status(self).clone()
+    }
+}
@@ -0,0 +1,173 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Collects control requests issued within a time window so that they can be sent together in
+/// a single `control` frame.
+///
+/// With a zero window every request is sent on its own, as soon as it is issued.
+#[derive(Debug)]
+pub(crate) struct ControlBatch {
+    /// The time window during which requests are collected.
+    window: Duration,
+    /// The encoded parameters of the collected requests, in issue order.
+    requests: Vec<String>,
+    /// The instant the collected requests are due to be sent, if any request is collected.
+    deadline: Option<Instant>,
+    /// The maximum length, in bytes, of the frames, as granted by the Server; zero means no
+    /// limit.
+    request_limit: usize,
+}
+
+impl ControlBatch {
+    /// Creates an empty batch.
+    ///
+    /// # Parameters
+    ///
+    /// * `window`: the time window during which requests are collected.
+    pub(crate) fn new(window: Duration) -> Self {
+        ControlBatch {
+            window,
+            requests: Vec::new(),
+            deadline: None,
+            request_limit: 0,
+        }
+    }
+
+    /// Sets the maximum length, in bytes, of the frames, as received through CONOK, so that the
+    /// collected requests are split over several frames when they would exceed it.
+    ///
+    /// # Parameters
+    ///
+    /// * `request_limit`: the maximum length of the frames; zero means no limit.
+    pub(crate) fn set_request_limit(&mut self, request_limit: usize) {
+        self.request_limit = request_limit;
+    }
+
+    /// Adds a request to the batch. If batching is disabled, the frame carrying the request alone
+    /// is returned, to be sent right away; otherwise the request is collected and `None` is
+    /// returned.
+    ///
+    /// # Parameters
+    ///
+    /// * `encoded_params`: the encoded parameters of the request.
+    pub(crate) fn push(&mut self, encoded_params: String) -> Option<String> {
+        if self.window.is_zero() {
+            return Some(format!("control\r\n{}", encoded_params));
+        }
+        self.requests.push(encoded_params);
+        self.deadline
+            .get_or_insert_with(|| Instant::now() + self.window);
+        None
+    }
+
+    /// Returns the instant the collected requests are due to be sent, if any.
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Removes the collected requests, returning the frames carrying them, as few as the request
+    /// limit allows.
+    pub(crate) fn take_frames(&mut self) -> Vec<String> {
+        self.deadline = None;
+        pack_control_frames(std::mem::take(&mut self.requests), self.request_limit)
+    }
+}
+
+/// Packs control requests, in order, into as few `control` frames as possible, none of them
+/// longer than the request limit of the session unless it carries a single request.
+///
+/// # Parameters
+///
+/// * `requests`: the encoded parameters of the requests.
+/// * `request_limit`: the maximum length, in bytes, of the frames; zero means no limit.
+pub(crate) fn pack_control_frames(requests: Vec<String>, request_limit: usize) -> Vec<String> {
+    const HEADER: &str = "control";
+    let mut frames = Vec::new();
+    let mut frame = String::new();
+    for request in requests {
+        let fits = request_limit == 0 || frame.len() + 2 + request.len() <= request_limit;
+        if !frame.is_empty() && !fits {
+            frames.push(std::mem::take(&mut frame));
+        }
+        if frame.is_empty() {
+            frame.push_str(HEADER);
+        }
+        frame.push_str("\r\n");
+        frame.push_str(&request);
+    }
+    if !frame.is_empty() {
+        frames.push(frame);
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_window_sends_right_away() {
+        let mut batch = ControlBatch::new(Duration::ZERO);
+        assert_eq!(
+            batch.push("LS_reqId=1".to_string()),
+            Some("control\r\nLS_reqId=1".to_string())
+        );
+        assert_eq!(batch.deadline(), None);
+        assert!(batch.take_frames().is_empty());
+    }
+
+    #[test]
+    fn test_requests_are_coalesced() {
+        let mut batch = ControlBatch::new(Duration::from_millis(10));
+        assert_eq!(batch.push("LS_reqId=1".to_string()), None);
+        let deadline = batch.deadline().unwrap();
+        assert_eq!(batch.push("LS_reqId=2".to_string()), None);
+        // The window starts with the first request.
+        assert_eq!(batch.deadline(), Some(deadline));
+
+        assert_eq!(
+            batch.take_frames(),
+            vec!["control\r\nLS_reqId=1\r\nLS_reqId=2".to_string()]
+        );
+        assert_eq!(batch.deadline(), None);
+        assert!(batch.take_frames().is_empty());
+    }
+
+    #[test]
+    fn test_collected_requests_are_split_on_request_limit() {
+        let mut batch = ControlBatch::new(Duration::from_millis(10));
+        batch.set_request_limit(31);
+        for request_id in 1..=3 {
+            batch.push(format!("LS_reqId={}", request_id));
+        }
+        assert_eq!(
+            batch.take_frames(),
+            vec![
+                "control\r\nLS_reqId=1\r\nLS_reqId=2".to_string(),
+                "control\r\nLS_reqId=3".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn test_frames_are_packed_within_request_limit() {
+        let requests = vec![
+            "LS_reqId=1".to_string(),
+            "LS_reqId=2".to_string(),
+            "LS_reqId=3".to_string(),
+        ];
+        assert_eq!(
+            pack_control_frames(requests.clone(), 0),
+            vec!["control\r\nLS_reqId=1\r\nLS_reqId=2\r\nLS_reqId=3".to_string()]
+        );
+        // Each request takes 12 bytes, the header 7.
+        assert_eq!(
+            pack_control_frames(requests, 31),
+            vec![
+                "control\r\nLS_reqId=1\r\nLS_reqId=2".to_string(),
+                "control\r\nLS_reqId=3".to_string()
+            ]
+        );
+        assert!(pack_control_frames(Vec::new(), 0).is_empty());
+    }
+}
@@ -0,0 +1,191 @@
+use crate::protocol::{ItemNotification, Overflow, ServerMessage, Update};
+use std::collections::{HashMap, HashSet};
+
+/// What the Server confirmed about a subscription, as far as the checks are concerned.
+#[derive(Debug, Default)]
+struct CheckedSubscription {
+    /// The number of items, as confirmed by SUBOK/SUBCMD.
+    item_count: usize,
+    /// Whether UNSUB was received.
+    unsubscribed: bool,
+    /// The positions of the items whose snapshot ended, through EOS.
+    ended_snapshots: HashSet<usize>,
+}
+
+/// Checks the subscription notifications of a session against the ones received before,
+/// reporting the ones that are out of the expected order or that reference unknown
+/// subscriptions or items, which hint at bugs in the protocol handling.
+///
+/// Only available with the `protocol-checks` feature.
+#[derive(Debug, Default)]
+pub(crate) struct ProtocolChecker {
+    /// The subscriptions confirmed in the session, by ID.
+    subscriptions: HashMap<usize, CheckedSubscription>,
+}
+
+impl ProtocolChecker {
+    /// Checks a notification.
+    ///
+    /// # Parameters
+    ///
+    /// * `notification`: the notification, as parsed from the stream.
+    ///
+    /// # Returns
+    ///
+    /// The description of the violation, if the notification is not the expected one.
+    pub(crate) fn check(&mut self, notification: &ServerMessage) -> Result<(), String> {
+        match notification {
+            ServerMessage::Subok(confirmation) => {
+                let name = match confirmation.command_pos {
+                    Some(_) => "SUBCMD",
+                    None => "SUBOK",
+                };
+                let previous = self.subscriptions.insert(
+                    confirmation.subscription_id,
+                    CheckedSubscription {
+                        item_count: confirmation.items,
+                        ..Default::default()
+                    },
+                );
+                match previous {
+                    Some(previous) if !previous.unsubscribed => Err(format!(
+                        "{} for subscription {} already confirmed",
+                        name, confirmation.subscription_id
+                    )),
+                    _ => Ok(()),
+                }
+            }
+            ServerMessage::Unsub(subscription_id) => {
+                let subscription = self.get_subscription("UNSUB", *subscription_id)?;
+                subscription.unsubscribed = true;
+                Ok(())
+            }
+            ServerMessage::Conf(configuration) => self
+                .get_subscription("CONF", configuration.subscription_id)
+                .map(|_| ()),
+            ServerMessage::Update(Update {
+                subscription_id,
+                item_pos,
+                ..
+            }) => self.get_item("U", *subscription_id, *item_pos).map(|_| ()),
+            ServerMessage::Ov(Overflow {
+                subscription_id,
+                item_pos,
+                ..
+            }) => self.get_item("OV", *subscription_id, *item_pos).map(|_| ()),
+            ServerMessage::Eos(ItemNotification {
+                subscription_id,
+                item_pos,
+            }) => {
+                let subscription = self.get_item("EOS", *subscription_id, *item_pos)?;
+                if subscription.ended_snapshots.insert(*item_pos) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "EOS for item {} of subscription {} already received",
+                        item_pos, subscription_id
+                    ))
+                }
+            }
+            ServerMessage::Cs(ItemNotification {
+                subscription_id,
+                item_pos,
+            }) => {
+                let subscription = self.get_item("CS", *subscription_id, *item_pos)?;
+                subscription.ended_snapshots.remove(item_pos);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the subscription of the given item, if it is confirmed, still active, and has
+    /// the item.
+    fn get_item(
+        &mut self,
+        notification: &str,
+        subscription_id: usize,
+        item_pos: usize,
+    ) -> Result<&mut CheckedSubscription, String> {
+        let subscription = self.get_subscription(notification, subscription_id)?;
+        if item_pos == 0 || item_pos > subscription.item_count {
+            return Err(format!(
+                "{} for item {} of subscription {}, which has {} items",
+                notification, item_pos, subscription_id, subscription.item_count
+            ));
+        }
+        Ok(subscription)
+    }
+
+    /// Returns the given subscription, if it is confirmed and still active.
+    fn get_subscription(
+        &mut self,
+        notification: &str,
+        subscription_id: usize,
+    ) -> Result<&mut CheckedSubscription, String> {
+        match self.subscriptions.get_mut(&subscription_id) {
+            Some(subscription) if subscription.unsubscribed => Err(format!(
+                "{} for subscription {} after UNSUB",
+                notification, subscription_id
+            )),
+            Some(subscription) => Ok(subscription),
+            None => Err(format!(
+                "{} for unknown subscription {}",
+                notification, subscription_id
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(checker: &mut ProtocolChecker, notification: &str) -> Result<(), String> {
+        checker.check(&ServerMessage::parse(notification).unwrap())
+    }
+
+    #[test]
+    fn test_expected_sequence() {
+        let mut checker = ProtocolChecker::default();
+        assert!(check(&mut checker, "SUBOK,1,2,3").is_ok());
+        assert!(check(&mut checker, "U,1,1,a|b|c").is_ok());
+        assert!(check(&mut checker, "EOS,1,1").is_ok());
+        assert!(check(&mut checker, "CS,1,1").is_ok());
+        assert!(check(&mut checker, "EOS,1,1").is_ok());
+        assert!(check(&mut checker, "OV,1,2,5").is_ok());
+        assert!(check(&mut checker, "CONF,1,unlimited,filtered").is_ok());
+        assert!(check(&mut checker, "UNSUB,1").is_ok());
+        // IDs can be reused after UNSUB.
+        assert!(check(&mut checker, "SUBOK,1,1,1").is_ok());
+        assert!(check(&mut checker, "PROBE").is_ok());
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut checker = ProtocolChecker::default();
+        assert_eq!(
+            check(&mut checker, "U,7,1,a"),
+            Err("U for unknown subscription 7".to_string())
+        );
+        check(&mut checker, "SUBOK,1,2,3").unwrap();
+        assert_eq!(
+            check(&mut checker, "U,1,3,a"),
+            Err("U for item 3 of subscription 1, which has 2 items".to_string())
+        );
+        check(&mut checker, "EOS,1,2").unwrap();
+        assert_eq!(
+            check(&mut checker, "EOS,1,2"),
+            Err("EOS for item 2 of subscription 1 already received".to_string())
+        );
+        assert_eq!(
+            check(&mut checker, "SUBOK,1,2,3"),
+            Err("SUBOK for subscription 1 already confirmed".to_string())
+        );
+        check(&mut checker, "UNSUB,1").unwrap();
+        assert_eq!(
+            check(&mut checker, "U,1,1,a"),
+            Err("U for subscription 1 after UNSUB".to_string())
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use flate2::{Decompress, FlushDecompress, Status};
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// The value of the `Sec-WebSocket-Extensions` header offering permessage-deflate (RFC 7692).
+///
+/// The client never compresses its own messages, so it lets the Server forget its compression
+/// context between them.
+pub(crate) const PERMESSAGE_DEFLATE_OFFER: &str = "permessage-deflate; client_no_context_takeover";
+
+/// The maximum length of a message, compressed or decompressed, the same as the default limit
+/// of the WebSocket layer. Decompression stops as soon as a message exceeds it, so that a small
+/// compressed message cannot exhaust the memory, and so do frames announcing, or fragments
+/// adding up to, a longer message, before they are buffered.
+const MAX_INFLATED_LENGTH: usize = 64 << 20;
+
+/// The bytes removed by the sender from the end of each compressed message.
+const MESSAGE_TRAILER: [u8; 4] = [0x00, 0x00, 0xff, 0xff];
+
+/// The stream of a WebSocket connection which decompresses the messages sent by the Server with
+/// permessage-deflate.
+///
+/// The WebSocket layer does not support extensions, and refuses the frames flagged as
+/// compressed: this stream rewrites them, before they reach it, as plain frames carrying the
+/// decompressed payload. Everything else, such as the handshake response, control frames and
+/// the requests of the client, passes through untouched.
+pub(crate) struct InflatingStream<S> {
+    inner: S,
+    enabled: bool,
+    /// Whether the handshake response has been passed through, so that frames follow.
+    handshake_done: bool,
+    /// The bytes read from the inner stream and not processed yet.
+    input: Vec<u8>,
+    /// The bytes ready to be read, from `output_pos` on.
+    output: Vec<u8>,
+    output_pos: usize,
+    /// The opcode and the payload collected so far of a compressed message split over several
+    /// frames.
+    fragments: Option<(u8, Vec<u8>)>,
+    inflater: Inflater,
+    /// The maximum length of a message, compressed or decompressed.
+    max_message_length: usize,
+}
+
+impl<S> InflatingStream<S> {
+    /// Wraps a stream.
+    ///
+    /// # Parameters
+    ///
+    /// * `inner`: the stream of the connection, after the TLS layer, if any.
+    /// * `enabled`: whether permessage-deflate was offered in the handshake; otherwise all the
+    ///   bytes pass through.
+    pub(crate) fn new(inner: S, enabled: bool) -> Self {
+        InflatingStream {
+            inner,
+            enabled,
+            handshake_done: false,
+            input: Vec::new(),
+            output: Vec::new(),
+            output_pos: 0,
+            fragments: None,
+            inflater: Inflater::default(),
+            max_message_length: MAX_INFLATED_LENGTH,
+        }
+    }
+
+    /// Moves as many bytes as possible from the input to the output, returning whether any
+    /// progress was made.
+    fn process(&mut self) -> io::Result<bool> {
+        if !self.handshake_done {
+            let Some(end) = self
+                .input
+                .windows(4)
+                .position(|window| window == b"\r\n\r\n")
+            else {
+                return Ok(false);
+            };
+            self.output.extend(self.input.drain(..end + 4));
+            self.handshake_done = true;
+            return Ok(true);
+        }
+        let Some(frame) = parse_frame(&self.input) else {
+            return Ok(false);
+        };
+        // The frame is refused from its header, without buffering it.
+        if frame.payload_len > self.max_message_length {
+            return Err(invalid_data("message too long"));
+        }
+        if self.input.len() < frame.end {
+            return Ok(false);
+        }
+        let payload = &self.input[frame.payload_start..frame.end];
+        let unmasked = |payload: &[u8]| match frame.mask {
+            Some(mask) => payload
+                .iter()
+                .enumerate()
+                .map(|(i, byte)| byte ^ mask[i % 4])
+                .collect(),
+            None => payload.to_vec(),
+        };
+        let is_data = matches!(frame.opcode, 0x1 | 0x2);
+        if frame.rsv1 && is_data {
+            let mut compressed = unmasked(payload);
+            if frame.fin {
+                self.emit_inflated(frame.opcode, &mut compressed)?;
+            } else {
+                self.fragments = Some((frame.opcode, compressed));
+            }
+        } else if frame.opcode == 0x0 && self.fragments.is_some() {
+            let payload = unmasked(payload);
+            if let Some((_, compressed)) = self.fragments.as_mut() {
+                if compressed.len() + payload.len() > self.max_message_length {
+                    return Err(invalid_data("message too long"));
+                }
+                compressed.extend_from_slice(&payload);
+            }
+            if frame.fin
+                && let Some((opcode, mut compressed)) = self.fragments.take()
+            {
+                self.emit_inflated(opcode, &mut compressed)?;
+            }
+        } else {
+            self.output.extend_from_slice(&self.input[..frame.end]);
+        }
+        self.input.drain(..frame.end);
+        Ok(true)
+    }
+
+    /// Decompresses a message, writing to the output a single frame carrying it.
+    fn emit_inflated(&mut self, opcode: u8, compressed: &mut Vec<u8>) -> io::Result<()> {
+        compressed.extend_from_slice(&MESSAGE_TRAILER);
+        let payload = self.inflater.inflate(compressed, self.max_message_length)?;
+        self.output.push(0x80 | opcode);
+        match payload.len() {
+            len @ 0..=125 => self.output.push(len as u8),
+            len @ 126..=0xffff => {
+                self.output.push(126);
+                self.output.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                self.output.push(127);
+                self.output.extend_from_slice(&(len as u64).to_be_bytes());
+            }
+        }
+        self.output.extend_from_slice(&payload);
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for InflatingStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if !this.enabled {
+            return Pin::new(&mut this.inner).poll_read(cx, buf);
+        }
+        loop {
+            if this.output_pos < this.output.len() {
+                let available = &this.output[this.output_pos..];
+                let len = available.len().min(buf.remaining());
+                buf.put_slice(&available[..len]);
+                this.output_pos += len;
+                if this.output_pos == this.output.len() {
+                    this.output.clear();
+                    this.output_pos = 0;
+                }
+                return Poll::Ready(Ok(()));
+            }
+            if this.process()? {
+                continue;
+            }
+            let mut chunk = [0u8; 8192];
+            let mut chunk_buf = ReadBuf::new(&mut chunk);
+            match Pin::new(&mut this.inner).poll_read(cx, &mut chunk_buf) {
+                Poll::Ready(Ok(())) if chunk_buf.filled().is_empty() => {
+                    // Let the WebSocket layer report an incomplete frame, if any.
+                    this.output.append(&mut this.input);
+                    if this.output.is_empty() {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Ready(Ok(())) => this.input.extend_from_slice(chunk_buf.filled()),
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for InflatingStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.get_mut().inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+}
+
+/// The header of a WebSocket frame, with the position of its payload.
+struct FrameHeader {
+    fin: bool,
+    rsv1: bool,
+    opcode: u8,
+    mask: Option<[u8; 4]>,
+    /// The length of the payload, as announced.
+    payload_len: usize,
+    payload_start: usize,
+    /// The end of the frame, possibly past the end of the buffer.
+    end: usize,
+}
+
+/// Parses the header of the frame at the start of the buffer, returning `None` until the
+/// header is complete.
+fn parse_frame(buffer: &[u8]) -> Option<FrameHeader> {
+    let [first, second, ..] = *buffer else {
+        return None;
+    };
+    let (len, mut pos) = match second & 0x7f {
+        126 => (
+            u16::from_be_bytes(buffer.get(2..4)?.try_into().ok()?) as usize,
+            4,
+        ),
+        127 => (
+            usize::try_from(u64::from_be_bytes(buffer.get(2..10)?.try_into().ok()?))
+                .unwrap_or(usize::MAX),
+            10,
+        ),
+        len => (len as usize, 2),
+    };
+    let mask = if second & 0x80 != 0 {
+        let mask = buffer.get(pos..pos + 4)?.try_into().ok()?;
+        pos += 4;
+        Some(mask)
+    } else {
+        None
+    };
+    Some(FrameHeader {
+        fin: first & 0x80 != 0,
+        rsv1: first & 0x40 != 0,
+        opcode: first & 0x0f,
+        mask,
+        payload_len: len,
+        payload_start: pos,
+        end: pos.saturating_add(len),
+    })
+}
+
+/// A DEFLATE (RFC 1951) decompressor keeping the window of the previous messages, which the
+/// Server may refer back to.
+pub(crate) struct Inflater {
+    decompress: Decompress,
+}
+
+impl Default for Inflater {
+    fn default() -> Self {
+        Inflater {
+            decompress: Decompress::new(false),
+        }
+    }
+}
+
+/// The length the output of `Inflater::inflate()` grows by at a time.
+const OUTPUT_CHUNK: usize = 16 << 10;
+
+impl Inflater {
+    /// Decompresses a message, failing as soon as the decompressed data exceeds `max_length`,
+    /// so that no more than `max_length` bytes, plus a chunk, are ever allocated for it.
+    ///
+    /// # Parameters
+    ///
+    /// * `input`: the compressed data, ending with an empty stored block.
+    /// * `max_length`: the maximum length of the decompressed data.
+    pub(crate) fn inflate(&mut self, input: &[u8], max_length: usize) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        let mut consumed = 0;
+        loop {
+            output.reserve_exact(OUTPUT_CHUNK.min(max_length + 1 - output.len()));
+            let total_in = self.decompress.total_in();
+            let status = self
+                .decompress
+                .decompress_vec(&input[consumed..], &mut output, FlushDecompress::Sync)
+                .map_err(|err| invalid_data(&err.to_string()))?;
+            consumed += (self.decompress.total_in() - total_in) as usize;
+            if output.len() > max_length {
+                return Err(invalid_data("decompressed message too long"));
+            }
+            match status {
+                // A final block ends the stream: the next message starts a new one.
+                Status::StreamEnd => {
+                    self.decompress.reset(false);
+                    return Ok(output);
+                }
+                // The output is not full, so the decompressor has nothing left to write.
+                _ if consumed == input.len() && output.len() < output.capacity() => {
+                    return Ok(output);
+                }
+                Status::BufError if output.len() < output.capacity() => {
+                    return Err(invalid_data("truncated data"));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn invalid_data(reason: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Malformed compressed message: {}", reason),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::io::AsyncWriteExt;
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+
+    fn hex(text: &str) -> Vec<u8> {
+        (0..text.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&text[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn inflate_message(inflater: &mut Inflater, compressed: &str) -> String {
+        let mut compressed = hex(compressed);
+        compressed.extend_from_slice(&MESSAGE_TRAILER);
+        String::from_utf8(inflater.inflate(&compressed, MAX_INFLATED_LENGTH).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_inflate_keeps_the_window_across_messages() {
+        let mut inflater = Inflater::default();
+        assert_eq!(
+            inflate_message(
+                &mut inflater,
+                "0ad5310441033dd31a23033d23d31ae51a155e2e0000"
+            ),
+            "U,1,1,10.5|20.25|#|$\r\n"
+        );
+        // The second message refers back to the first one.
+        assert_eq!(
+            inflate_message(&mut inflater, "0ac5296a84210a00"),
+            "U,1,1,10.5|20.25|#|$\r\nU,1,2,10.5|20.25|#|$\r\n"
+        );
+    }
+
+    #[test]
+    fn test_inflate_block_types() {
+        let mut inflater = Inflater::default();
+        assert_eq!(
+            inflate_message(&mut inflater, "000700f8ff50524f42450d0a00"),
+            "PROBE\r\n"
+        );
+        let expected: String = (0..11)
+            .map(|i| {
+                let value = ["x", "y"][i % 2].repeat(i % 4 + 1);
+                format!("U,1,{},{}|{}\r\n", i % 3, i * 7 % 10, value)
+            })
+            .collect();
+        assert_eq!(
+            inflate_message(
+                &mut Inflater::default(),
+                "4cccc909c0300c44d17b209dfc83a4c4590a712f32a8f818bce536f07993510409dfb75ca772\
+                 47296d1b67b8f720680d2329cf10469a42b02594f7278c2be6d511eb489af800"
+            ),
+            expected
+        );
+        assert!(
+            inflater
+                .inflate(&[0xff, 0xff], MAX_INFLATED_LENGTH)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_inflate_stops_at_the_maximum_length() {
+        // 1 MiB of zeros, compressed to about a kilobyte.
+        let mut compress = flate2::Compress::new(flate2::Compression::best(), false);
+        let mut compressed = Vec::with_capacity(4096);
+        compress
+            .compress_vec(&[0; 1 << 20], &mut compressed, flate2::FlushCompress::Sync)
+            .unwrap();
+        assert!(compressed.len() < 4096);
+        let err = Inflater::default()
+            .inflate(&compressed, 64 << 10)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            Inflater::default()
+                .inflate(&compressed, 1 << 20)
+                .unwrap()
+                .len(),
+            1 << 20
+        );
+    }
+
+    #[test]
+    fn test_oversized_messages_are_refused_before_buffering() {
+        let stream = || {
+            let mut stream = InflatingStream::new(tokio::io::empty(), true);
+            stream.handshake_done = true;
+            stream.max_message_length = 16;
+            stream
+        };
+        // Just the header of a frame announcing a terabyte.
+        let mut oversized = stream();
+        oversized.input = vec![0xc1, 127, 0, 0, 1, 0, 0, 0, 0, 0];
+        assert_eq!(
+            oversized.process().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        // Two fragments of a compressed message, short enough alone but not together.
+        let mut fragmented = stream();
+        fragmented.input = [0x41, 10].into_iter().chain([0; 10]).collect();
+        fragmented
+            .input
+            .extend([0x00, 10].into_iter().chain([0; 10]));
+        assert!(fragmented.process().unwrap());
+        assert_eq!(
+            fragmented.process().unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compressed_frames_reach_the_websocket_decompressed() {
+        let (client, server) = tokio::io::duplex(4096);
+        let server_task = tokio::spawn(async move {
+            let mut ws = tokio_tungstenite::accept_async(server).await.unwrap();
+            let compressed = hex("0ad5310441033dd31a23033d23d31ae51a155e2e0000");
+            // A compressed text frame, split over a first frame and a continuation.
+            let mut frames = vec![0x41, 10];
+            frames.extend_from_slice(&compressed[..10]);
+            frames.extend_from_slice(&[0x80, (compressed.len() - 10) as u8]);
+            frames.extend_from_slice(&compressed[10..]);
+            ws.get_mut().write_all(&frames).await.unwrap();
+            ws.send(Message::Text("PROBE\r\n".into())).await.unwrap();
+            ws.next().await
+        });
+        let request = "ws://localhost/lightstreamer"
+            .into_client_request()
+            .unwrap();
+        let (mut ws, _) =
+            tokio_tungstenite::client_async(request, InflatingStream::new(client, true))
+                .await
+                .unwrap();
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            Message::Text("U,1,1,10.5|20.25|#|$\r\n".into())
+        );
+        assert_eq!(
+            ws.next().await.unwrap().unwrap(),
+            Message::Text("PROBE\r\n".into())
+        );
+        ws.send(Message::Text("wsok".into())).await.unwrap();
+        assert_eq!(
+            server_task.await.unwrap().unwrap().unwrap(),
+            Message::Text("wsok".into())
+        );
+    }
+}
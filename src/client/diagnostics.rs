@@ -0,0 +1,119 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Counters of the current session, for monitoring and troubleshooting.
+#[derive(Debug, Default)]
+struct Counters {
+    /// The number of data notifications received in the current session (the PROG of TLCP).
+    prog: AtomicU64,
+    /// The number of data notifications discarded as duplicates since the client was created.
+    duplicates: AtomicU64,
+    /// The number of data notifications detected as lost since the client was created.
+    lost: AtomicU64,
+    /// How far behind the Server the client is, in milliseconds, as of the last SYNC
+    /// notification.
+    sync_delay: AtomicU64,
+    /// The number of notifications out of the expected order since the client was created.
+    #[cfg(feature = "protocol-checks")]
+    protocol_violations: AtomicU64,
+}
+
+/// Live diagnostics of a `LightstreamerClient`.
+///
+/// Handles are cheap to clone and share the same counters, so they can be read from another
+/// task while the client is connected.
+///
+/// See also `LightstreamerClient::get_diagnostics()`
+#[derive(Debug, Clone, Default)]
+pub struct SessionDiagnostics {
+    counters: Arc<Counters>,
+}
+
+impl SessionDiagnostics {
+    /// Returns the number of data notifications (updates, snapshot and subscription events,
+    /// message outcomes) received in the current session, which is the progressive used by the
+    /// Server for session recovery.
+    pub fn get_prog(&self) -> u64 {
+        self.counters.prog.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of data notifications discarded as duplicates, because the Server
+    /// resent notifications already received.
+    pub fn get_duplicate_notifications(&self) -> u64 {
+        self.counters.duplicates.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of data notifications detected as lost, because the Server reported
+    /// more notifications sent than the ones received.
+    pub fn get_lost_notifications(&self) -> u64 {
+        self.counters.lost.load(Ordering::Relaxed)
+    }
+
+    /// Returns how far behind the Server the client is, in milliseconds, measured on the last
+    /// SYNC notification as the difference between the time elapsed locally since the stream
+    /// connection was opened and the time the Server reports. A steadily growing value means
+    /// that the client cannot keep up with the notifications.
+    ///
+    /// Always 0 when the SYNC notifications are disabled (see
+    /// `ConnectionOptions::set_send_sync()`).
+    pub fn get_sync_delay(&self) -> u64 {
+        self.counters.sync_delay.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of subscription notifications received out of the expected order, or
+    /// referencing unknown subscriptions or items, which hint at bugs in the protocol handling.
+    ///
+    /// Only available with the `protocol-checks` feature.
+    #[cfg(feature = "protocol-checks")]
+    pub fn get_protocol_violations(&self) -> u64 {
+        self.counters.protocol_violations.load(Ordering::Relaxed)
+    }
+
+    /// Sets the number of data notifications received in the current session.
+    pub(crate) fn set_prog(&self, prog: u64) {
+        self.counters.prog.store(prog, Ordering::Relaxed);
+    }
+
+    /// Counts data notifications discarded as duplicates.
+    pub(crate) fn add_duplicate_notifications(&self, count: u64) {
+        self.counters.duplicates.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Counts data notifications detected as lost.
+    pub(crate) fn add_lost_notifications(&self, count: u64) {
+        self.counters.lost.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Sets how far behind the Server the client is.
+    pub(crate) fn set_sync_delay(&self, delay: u64) {
+        self.counters.sync_delay.store(delay, Ordering::Relaxed);
+    }
+
+    /// Counts a notification out of the expected order.
+    #[cfg(feature = "protocol-checks")]
+    pub(crate) fn add_protocol_violation(&self) {
+        self.counters
+            .protocol_violations
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_the_counters() {
+        let diagnostics = SessionDiagnostics::default();
+        let handle = diagnostics.clone();
+        diagnostics.set_prog(42);
+        diagnostics.add_duplicate_notifications(2);
+        diagnostics.add_lost_notifications(1);
+        diagnostics.set_sync_delay(250);
+
+        assert_eq!(handle.get_prog(), 42);
+        assert_eq!(handle.get_duplicate_notifications(), 2);
+        assert_eq!(handle.get_lost_notifications(), 1);
+        assert_eq!(handle.get_sync_delay(), 250);
+    }
+}
@@ -0,0 +1,93 @@
+use crate::client::session::SessionInfo;
+use crate::subscription::Subscription;
+use serde::Serialize;
+
+/// Snapshot of the internal state of a `LightstreamerClient`, for attaching to bug reports or
+/// exposing on admin endpoints.
+///
+/// Credentials are never included.
+///
+/// See also `LightstreamerClient::dump_state()`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClientStateDump {
+    /// The current status of the client, as returned by `LightstreamerClient::get_status()`.
+    pub status: String,
+    /// The configured Server address, if any.
+    pub server_address: Option<String>,
+    /// The configured Adapter Set, if any.
+    pub adapter_set: Option<String>,
+    /// The transport forced through `ConnectionOptions::set_forced_transport()`, if any.
+    pub forced_transport: Option<String>,
+    /// The details of the current session, if any.
+    pub session: Option<SessionInfo>,
+    /// The subscriptions of the client.
+    pub subscriptions: Vec<SubscriptionDump>,
+    /// The subscription and unsubscription requests issued and not yet taken in charge by the
+    /// session.
+    pub queued_subscription_requests: usize,
+    /// The messages sent and not yet taken in charge by the session.
+    pub queued_message_requests: usize,
+    /// The number of data notifications received in the current session.
+    pub prog: u64,
+    /// The number of data notifications discarded as duplicates.
+    pub duplicate_notifications: u64,
+    /// The number of data notifications detected as lost.
+    pub lost_notifications: u64,
+    /// The last status transitions, oldest first.
+    pub status_history: Vec<String>,
+}
+
+impl ClientStateDump {
+    /// Serializes the snapshot as a JSON object.
+    ///
+    /// # Returns
+    /// The JSON representation of the snapshot, or an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+}
+
+/// Snapshot of a `Subscription`, as part of a `ClientStateDump`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubscriptionDump {
+    /// The ID of the subscription in the current session, or 0 if not sent yet.
+    pub id: usize,
+    /// The subscription mode.
+    pub mode: String,
+    /// The items, if given as a list.
+    pub items: Option<Vec<String>>,
+    /// The item group, if given as a group name.
+    pub item_group: Option<String>,
+    /// The fields, if given as a list.
+    pub fields: Option<Vec<String>>,
+    /// The field schema, if given as a schema name.
+    pub field_schema: Option<String>,
+    /// The Data Adapter, if not the default one.
+    pub data_adapter: Option<String>,
+    /// Whether the subscription is active.
+    pub active: bool,
+    /// Whether the subscription is confirmed by the Server.
+    pub subscribed: bool,
+    /// The time from the last subscription request to the first update or end-of-snapshot, in
+    /// milliseconds, if received.
+    pub time_to_first_update_ms: Option<u64>,
+}
+
+impl From<&Subscription> for SubscriptionDump {
+    fn from(subscription: &Subscription) -> Self {
+        SubscriptionDump {
+            id: subscription.id,
+            mode: subscription.get_mode().to_string(),
+            items: subscription.get_items().cloned(),
+            item_group: subscription.get_item_group().cloned(),
+            fields: subscription.get_fields().cloned(),
+            field_schema: subscription.get_field_schema().cloned(),
+            data_adapter: subscription.get_data_adapter().cloned(),
+            active: subscription.is_active(),
+            subscribed: subscription.is_subscribed(),
+            time_to_first_update_ms: subscription
+                .get_time_to_first_update()
+                .map(|time| time.as_millis() as u64),
+        }
+    }
+}
@@ -0,0 +1,334 @@
+use crate::client::api::StreamingClient;
+use crate::client::model::{ClientStatus, ConnectionType};
+use crate::client::request::MessageRequest;
+use crate::protocol::SubscriptionOk;
+use crate::subscription::{ItemUpdate, Subscription};
+use crate::utils::{IllegalArgumentException, IllegalStateException, LightstreamerError};
+use std::collections::HashMap;
+
+/// In-memory `StreamingClient` with no network, to unit-test the feed logic of an application.
+///
+/// Subscriptions are confirmed as soon as they are added, as if the Server had accepted them,
+/// and receive the updates pushed by the test through `push_update()` and `push_snapshot()`,
+/// which go through their codecs, caches and listeners as real updates do. Messages are kept
+/// for the test to inspect, and to process or deny.
+///
+/// ```ignore
+/// let mut client = FakeClient::new();
+/// start_feed(&mut client)?;
+/// client.push_update(1, 1, &[("last_price", "10.5")])?;
+/// assert_eq!(client.get_messages()[0].get_message(), "BUY 100");
+/// ```
+#[derive(Debug)]
+pub struct FakeClient {
+    status: ClientStatus,
+    subscriptions: Vec<Subscription>,
+    /// The ID given to the last subscription.
+    subscription_id: usize,
+    /// The current values of the items, by subscription ID and item position.
+    values: HashMap<(usize, usize), HashMap<String, Option<String>>>,
+    messages: Vec<MessageRequest>,
+}
+
+impl FakeClient {
+    /// Creates a client connected through WebSocket streaming, with no subscriptions.
+    pub fn new() -> FakeClient {
+        FakeClient {
+            status: ClientStatus::Connected(ConnectionType::WsStreaming),
+            subscriptions: Vec::new(),
+            subscription_id: 0,
+            values: HashMap::new(),
+            messages: Vec::new(),
+        }
+    }
+
+    /// Sets the status returned by `get_status()`, e.g. to test the reaction to a disconnection.
+    pub fn set_status(&mut self, status: ClientStatus) {
+        self.status = status;
+    }
+
+    /// Returns the subscriptions, in the order they were added. Their IDs start at 1.
+    pub fn get_subscriptions(&self) -> &[Subscription] {
+        &self.subscriptions
+    }
+
+    /// Pushes a real-time update to an item of a subscription.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription_id`: the ID of the subscription.
+    /// * `item_pos`: the 1-based position of the item.
+    /// * `fields`: the names and values of the fields changed by the update; the other fields
+    ///   keep their previous values.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if no subscription has the given ID.
+    pub fn push_update(
+        &mut self,
+        subscription_id: usize,
+        item_pos: usize,
+        fields: &[(&str, &str)],
+    ) -> Result<(), LightstreamerError> {
+        self.dispatch(subscription_id, item_pos, fields, false)
+    }
+
+    /// Pushes a snapshot update to an item of a subscription, as `push_update()` does.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if no subscription has the given ID.
+    pub fn push_snapshot(
+        &mut self,
+        subscription_id: usize,
+        item_pos: usize,
+        fields: &[(&str, &str)],
+    ) -> Result<(), LightstreamerError> {
+        self.dispatch(subscription_id, item_pos, fields, true)
+    }
+
+    /// Returns the messages sent and not yet processed or denied, in the order they were sent.
+    pub fn get_messages(&self) -> &[MessageRequest] {
+        &self.messages
+    }
+
+    /// Processes the pending messages, as if the Server had, notifying their listeners.
+    ///
+    /// # Parameters
+    ///
+    /// * `response`: the response of the Metadata Adapter passed to the listeners, if any.
+    ///
+    /// # Returns
+    ///
+    /// The processed messages, in the order they were sent.
+    pub fn process_messages(&mut self, response: Option<&str>) -> Vec<String> {
+        self.messages
+            .drain(..)
+            .map(|message_request| {
+                if let Some(listener) = &message_request.listener {
+                    listener.on_processed(&message_request.message, response);
+                }
+                message_request.message
+            })
+            .collect()
+    }
+
+    /// Denies the pending messages, as if the Metadata Adapter had, notifying their listeners.
+    ///
+    /// # Parameters
+    ///
+    /// * `code`: the error code passed to the listeners.
+    /// * `error`: the error message passed to the listeners.
+    ///
+    /// # Returns
+    ///
+    /// The denied messages, in the order they were sent.
+    pub fn deny_messages(&mut self, code: i32, error: &str) -> Vec<String> {
+        self.messages
+            .drain(..)
+            .map(|message_request| {
+                if let Some(listener) = &message_request.listener {
+                    listener.on_deny(&message_request.message, code, error);
+                }
+                message_request.message
+            })
+            .collect()
+    }
+
+    fn dispatch(
+        &mut self,
+        subscription_id: usize,
+        item_pos: usize,
+        fields: &[(&str, &str)],
+        is_snapshot: bool,
+    ) -> Result<(), LightstreamerError> {
+        let subscription = self
+            .subscriptions
+            .iter_mut()
+            .find(|subscription| subscription.id == subscription_id)
+            .ok_or_else(|| {
+                IllegalArgumentException::new(&format!(
+                    "No subscription with ID {}",
+                    subscription_id
+                ))
+            })?;
+        let values = self
+            .values
+            .entry((subscription_id, item_pos))
+            .or_insert_with(|| {
+                subscription
+                    .get_fields()
+                    .into_iter()
+                    .flatten()
+                    .map(|field| (field.clone(), None))
+                    .collect()
+            });
+        let mut changed_fields = HashMap::new();
+        for (field, value) in fields {
+            values.insert(field.to_string(), Some(value.to_string()));
+            changed_fields.insert(field.to_string(), value.to_string());
+        }
+        let mut update = ItemUpdate {
+            item_name: subscription
+                .get_item_name(item_pos)
+                .map(|name| name.to_string()),
+            item_pos,
+            fields: values.clone(),
+            changed_fields,
+            is_snapshot,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        };
+        // Values failing to decode are delivered without decoded value, as by the real client.
+        let _ = subscription.decode_fields(&mut update);
+        subscription.on_item_update(&update);
+        Ok(())
+    }
+}
+
+impl Default for FakeClient {
+    fn default() -> Self {
+        FakeClient::new()
+    }
+}
+
+impl StreamingClient for FakeClient {
+    fn subscribe(&mut self, mut subscription: Subscription) -> Result<(), LightstreamerError> {
+        subscription.check_subscribable()?;
+        self.subscription_id += 1;
+        subscription.id = self.subscription_id;
+        let _ = subscription.id_sender.try_send(self.subscription_id);
+        subscription.on_subscription_request();
+        subscription.on_subscription(SubscriptionOk {
+            subscription_id: self.subscription_id,
+            items: subscription.get_items().map_or(1, |items| items.len()),
+            fields: subscription.get_fields().map_or(1, |fields| fields.len()),
+            key_pos: None,
+            command_pos: None,
+        });
+        self.subscriptions.push(subscription);
+        Ok(())
+    }
+
+    fn unsubscribe(&mut self, subscription_id: usize) -> Result<(), LightstreamerError> {
+        let index = self
+            .subscriptions
+            .iter()
+            .position(|subscription| subscription.id == subscription_id)
+            .ok_or_else(|| {
+                IllegalStateException::new(&format!("No subscription with ID {}", subscription_id))
+            })?;
+        self.subscriptions.remove(index);
+        self.values
+            .retain(|(values_id, _), _| *values_id != subscription_id);
+        Ok(())
+    }
+
+    fn send_message(&mut self, message_request: MessageRequest) {
+        self.messages.push(message_request);
+    }
+
+    fn get_status(&self) -> ClientStatus {
+        self.status.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::ClientMessageListener;
+    use crate::subscription::{SubscriptionListener, SubscriptionMode};
+    use std::sync::{Arc, Mutex};
+
+    struct PriceRecorder(Arc<Mutex<Vec<(String, String)>>>);
+
+    impl SubscriptionListener for PriceRecorder {
+        fn on_item_update(&self, update: &ItemUpdate) {
+            self.0.lock().unwrap().push((
+                update
+                    .get_value("last_price")
+                    .unwrap_or_default()
+                    .to_string(),
+                update.get_value("status").unwrap_or_default().to_string(),
+            ));
+        }
+    }
+
+    struct OutcomeRecorder(Arc<Mutex<Vec<String>>>);
+
+    impl ClientMessageListener for OutcomeRecorder {
+        fn on_processed(&self, msg: &str, response: Option<&str>) {
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{} -> {}", msg, response.unwrap_or_default()));
+        }
+    }
+
+    /// Feed logic written against the trait, buying whenever the price drops below 10.
+    fn on_price(client: &mut impl StreamingClient, price: f64) {
+        if price < 10.0 && client.get_status().to_string().starts_with("CONNECTED") {
+            StreamingClient::send_message(
+                client,
+                MessageRequest::new("BUY 100").with_sequence("orders"),
+            );
+        }
+    }
+
+    #[test]
+    fn test_fake_client_drives_the_subscriptions() {
+        let mut client = FakeClient::new();
+        let prices = Arc::new(Mutex::new(Vec::new()));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string(), "status".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .add_listener(Box::new(PriceRecorder(prices.clone())))
+            .unwrap();
+        client.subscribe(subscription).unwrap();
+        assert!(client.get_subscriptions()[0].is_subscribed());
+
+        client
+            .push_snapshot(1, 1, &[("last_price", "10"), ("status", "open")])
+            .unwrap();
+        client.push_update(1, 1, &[("last_price", "11")]).unwrap();
+        assert!(client.push_update(2, 1, &[]).is_err());
+        assert_eq!(
+            *prices.lock().unwrap(),
+            vec![
+                ("10".to_string(), "open".to_string()),
+                ("11".to_string(), "open".to_string())
+            ]
+        );
+
+        client.unsubscribe(1).unwrap();
+        assert!(client.get_subscriptions().is_empty());
+        assert!(client.unsubscribe(1).is_err());
+    }
+
+    #[test]
+    fn test_fake_client_keeps_the_messages() {
+        let mut client = FakeClient::new();
+        on_price(&mut client, 9.5);
+        client.set_status(ClientStatus::Stalled);
+        on_price(&mut client, 9.0);
+        assert_eq!(client.get_messages().len(), 1);
+        assert_eq!(client.get_messages()[0].get_sequence(), "orders");
+
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        StreamingClient::send_message(
+            &mut client,
+            MessageRequest::new("SELL 50")
+                .with_listener(Box::new(OutcomeRecorder(outcomes.clone()))),
+        );
+        assert_eq!(
+            client.process_messages(Some("done")),
+            vec!["BUY 100", "SELL 50"]
+        );
+        assert_eq!(*outcomes.lock().unwrap(), vec!["SELL 50 -> done"]);
+        assert!(client.get_messages().is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+use crate::client::api::StreamingClient;
+use crate::client::model::ClientStatus;
+use crate::client::request::{MessageRequest, SubscriptionRequest};
+use crate::subscription::Subscription;
+use crate::utils::{IllegalStateException, LightstreamerError};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+
+/// Handle to a `LightstreamerClient`, obtained through `LightstreamerClient::get_handle()`, to
+/// call back into the client from its own listeners.
+///
+/// Listeners are called by the task running the session, which cannot process requests until
+/// they return: a listener waiting for the client, e.g. through `subscribe()` with a full
+/// request queue, would wait forever. The methods of the handle never wait: requests are queued
+/// without bound and processed, in order, once the listener returns. Hence a listener may
+/// subscribe, unsubscribe, even its own Subscription, and send messages, from any callback.
+///
+/// The handle can be cloned and used from any thread, also while the client is not connected;
+/// requests are then processed by the next session, as for `subscribe()`.
+#[derive(Debug, Clone)]
+pub struct ClientHandle {
+    subscription_sender: UnboundedSender<SubscriptionRequest>,
+    message_sender: UnboundedSender<MessageRequest>,
+    status: watch::Receiver<ClientStatus>,
+}
+
+impl ClientHandle {
+    pub(crate) fn new(
+        subscription_sender: UnboundedSender<SubscriptionRequest>,
+        message_sender: UnboundedSender<MessageRequest>,
+        status: watch::Receiver<ClientStatus>,
+    ) -> ClientHandle {
+        ClientHandle {
+            subscription_sender,
+            message_sender,
+            status,
+        }
+    }
+
+    /// Adds a subscription to the client, without waiting.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription`: the subscription.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the subscription is already active, or the client is gone.
+    pub fn subscribe(&self, subscription: Subscription) -> Result<(), LightstreamerError> {
+        subscription.check_subscribable()?;
+        self.subscription_sender
+            .send(SubscriptionRequest {
+                subscription: Some(subscription),
+                subscription_id: None,
+                responder: None,
+            })
+            .map_err(|_| IllegalStateException::new("The client is gone").into())
+    }
+
+    /// Removes a subscription from the client, without waiting.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription_id`: the ID of the subscription.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the client is gone.
+    pub fn unsubscribe(&self, subscription_id: usize) -> Result<(), LightstreamerError> {
+        self.subscription_sender
+            .send(SubscriptionRequest {
+                subscription: None,
+                subscription_id: Some(subscription_id),
+                responder: None,
+            })
+            .map_err(|_| IllegalStateException::new("The client is gone").into())
+    }
+
+    /// Sends a message through the client, without waiting. If the client is gone, the message
+    /// is aborted right away.
+    ///
+    /// # Parameters
+    ///
+    /// * `message_request`: the message, with its options.
+    pub fn send_message(&self, message_request: MessageRequest) {
+        if let Err(err) = self.message_sender.send(message_request) {
+            err.0.abort(false);
+        }
+    }
+
+    /// Returns the current status of the client, or its last status if it is gone.
+    pub fn get_status(&self) -> ClientStatus {
+        self.status.borrow().clone()
+    }
+}
+
+impl StreamingClient for ClientHandle {
+    fn subscribe(&mut self, subscription: Subscription) -> Result<(), LightstreamerError> {
+        ClientHandle::subscribe(self, subscription)
+    }
+
+    fn unsubscribe(&mut self, subscription_id: usize) -> Result<(), LightstreamerError> {
+        ClientHandle::unsubscribe(self, subscription_id)
+    }
+
+    fn send_message(&mut self, message_request: MessageRequest) {
+        ClientHandle::send_message(self, message_request)
+    }
+
+    fn get_status(&self) -> ClientStatus {
+        ClientHandle::get_status(self)
+    }
+}
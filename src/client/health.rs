@@ -0,0 +1,241 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The health of a `LightstreamerClient`, as reported by `HealthProbe::get_report()`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealthReport {
+    /// The status of the client, as returned by `LightstreamerClient::get_status()`.
+    pub status: String,
+    /// Whether the client is connected, i.e. ready to deliver updates.
+    pub ready: bool,
+    /// Whether data or keepalives were received from the Server recently enough.
+    pub live: bool,
+    /// The time since the last data or keepalive was received from the Server, in milliseconds,
+    /// or `None` if nothing was received yet.
+    pub last_activity_age_ms: Option<u64>,
+}
+
+/// The state shared by the handles of a `HealthProbe`.
+#[derive(Debug)]
+struct ProbeState {
+    /// The reference for the activity times, and the start of the idle time when nothing was
+    /// received yet.
+    created_at: Instant,
+    /// The time of the last activity, in milliseconds since `created_at`, plus one; zero if
+    /// nothing was received yet.
+    last_activity: AtomicU64,
+    /// The status of the client.
+    status: Mutex<String>,
+}
+
+/// Probe of the health of a `LightstreamerClient`, suitable for the readiness and liveness
+/// probes of Kubernetes, so that pods with dead feed connections get restarted.
+///
+/// The client is ready when connected, and live when something, even a keepalive, was received
+/// from the Server within the given idle time. Since the Server sends keepalives on idle
+/// sessions, a connected client is always live, unless its connection silently died; a client
+/// which keeps failing to connect is not live either.
+///
+/// The probe is a shared handle, obtained through `LightstreamerClient::get_health_probe()`,
+/// whose report can be returned by the handlers of an HTTP framework, such as axum or warp.
+/// With the `health-endpoint` feature, `serve()` runs a tiny HTTP endpoint instead.
+#[derive(Debug, Clone)]
+pub struct HealthProbe {
+    state: Arc<ProbeState>,
+}
+
+impl Default for HealthProbe {
+    fn default() -> Self {
+        HealthProbe {
+            state: Arc::new(ProbeState {
+                created_at: Instant::now(),
+                last_activity: AtomicU64::new(0),
+                status: Mutex::new("DISCONNECTED".to_string()),
+            }),
+        }
+    }
+}
+
+impl HealthProbe {
+    /// Returns the health of the client.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_idle`: The longest time without anything received from the Server for the client
+    ///   to be considered live; it should exceed the keepalive interval of the sessions.
+    pub fn get_report(&self, max_idle: Duration) -> HealthReport {
+        let status = self.lock_status().clone();
+        let last_activity_age = self.get_last_activity_age();
+        let idle = last_activity_age.unwrap_or_else(|| self.state.created_at.elapsed());
+        HealthReport {
+            ready: status.starts_with("CONNECTED:"),
+            live: idle <= max_idle,
+            last_activity_age_ms: last_activity_age.map(|age| age.as_millis() as u64),
+            status,
+        }
+    }
+
+    /// Returns the time since the last data or keepalive was received from the Server, if any.
+    pub fn get_last_activity_age(&self) -> Option<Duration> {
+        match self.state.last_activity.load(Ordering::Relaxed) {
+            0 => None,
+            millis => Some((self.state.created_at + Duration::from_millis(millis - 1)).elapsed()),
+        }
+    }
+
+    /// Records that something was received from the Server.
+    pub(crate) fn record_activity(&self) {
+        let millis = self.state.created_at.elapsed().as_millis() as u64;
+        self.state
+            .last_activity
+            .store(millis + 1, Ordering::Relaxed);
+    }
+
+    /// Records the status of the client.
+    pub(crate) fn set_status(&self, status: &str) {
+        *self.lock_status() = status.to_string();
+    }
+
+    fn lock_status(&self) -> std::sync::MutexGuard<'_, String> {
+        self.state
+            .status
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Serves the health of the client over HTTP, until the listener fails: `GET /readyz`
+    /// answers 200 when the client is ready and `GET /livez` (or `/healthz`) when it is live,
+    /// 503 otherwise, both with the JSON report as body.
+    ///
+    /// Only available with the `health-endpoint` feature.
+    ///
+    /// ```ignore
+    /// let listener = tokio::net::TcpListener::bind("0.0.0.0:8086").await?;
+    /// tokio::spawn(client.get_health_probe().serve(listener, Duration::from_secs(30)));
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// * `listener`: The listener accepting the connections of the probes.
+    /// * `max_idle`: The longest time without anything received from the Server for the client
+    ///   to be considered live.
+    #[cfg(feature = "health-endpoint")]
+    pub async fn serve(
+        self,
+        listener: tokio::net::TcpListener,
+        max_idle: Duration,
+    ) -> std::io::Result<()> {
+        loop {
+            let (stream, _) = listener.accept().await?;
+            let probe = self.clone();
+            tokio::spawn(async move {
+                // A probe disconnecting early is not an error of the endpoint.
+                let _ = probe.answer(stream, max_idle).await;
+            });
+        }
+    }
+
+    /// Answers a single HTTP request of a probe.
+    #[cfg(feature = "health-endpoint")]
+    async fn answer(
+        &self,
+        mut stream: tokio::net::TcpStream,
+        max_idle: Duration,
+    ) -> std::io::Result<()> {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        // Only the request line matters; the rest of the request is ignored.
+        let mut request = [0u8; 1024];
+        let read = stream.read(&mut request).await?;
+        let request = String::from_utf8_lossy(&request[..read]);
+        let path = request.split_whitespace().nth(1).unwrap_or("");
+        let report = self.get_report(max_idle);
+        let (status_line, body) = match path {
+            "/readyz" | "/livez" | "/healthz" => {
+                let healthy = match path {
+                    "/readyz" => report.ready,
+                    _ => report.live,
+                };
+                let status_line = match healthy {
+                    true => "200 OK",
+                    false => "503 Service Unavailable",
+                };
+                let body = serde_json::to_string(&report).unwrap_or_default();
+                (status_line, body)
+            }
+            _ => ("404 Not Found", String::new()),
+        };
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status_line,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_follows_status_and_activity() {
+        let probe = HealthProbe::default();
+        let report = probe.get_report(Duration::from_secs(60));
+        assert!(!report.ready);
+        // Within the idle time since the probe was created.
+        assert!(report.live);
+        assert_eq!(report.last_activity_age_ms, None);
+
+        let handle = probe.clone();
+        handle.set_status("CONNECTED:WS-STREAMING");
+        handle.record_activity();
+        let report = probe.get_report(Duration::from_secs(60));
+        assert!(report.ready);
+        assert!(report.live);
+        assert!(report.last_activity_age_ms.is_some());
+        assert!(probe.get_last_activity_age().unwrap() < Duration::from_secs(60));
+    }
+
+    #[cfg(feature = "health-endpoint")]
+    #[tokio::test]
+    async fn test_endpoint_answers_the_probes() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let probe = HealthProbe::default();
+        probe.set_status("CONNECTED:WS-STREAMING");
+        probe.record_activity();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(probe.clone().serve(listener, Duration::from_secs(60)));
+
+        let mut answers = Vec::new();
+        for path in ["/readyz", "/livez", "/other"] {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            let request = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path);
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            answers.push(response);
+        }
+        assert!(answers[0].starts_with("HTTP/1.1 200 OK"));
+        assert!(answers[0].contains("\"ready\":true"));
+        assert!(answers[1].starts_with("HTTP/1.1 200 OK"));
+        assert!(answers[2].starts_with("HTTP/1.1 404 Not Found"));
+
+        probe.set_status("DISCONNECTED:WILL-RETRY");
+        let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /readyz HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 503 Service Unavailable"));
+    }
+}
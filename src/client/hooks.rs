@@ -0,0 +1,81 @@
+use crate::client::session::SessionInfo;
+use crate::client::status::StatusChangeCause;
+use std::fmt::{self, Debug, Formatter};
+
+/// A closure called with the details of a session.
+pub(crate) type SessionHook = Box<dyn FnMut(&SessionInfo) + Send>;
+
+/// A closure called with the cause of a disconnection.
+pub(crate) type DisconnectHook = Box<dyn FnMut(&StatusChangeCause) + Send>;
+
+/// A closure choosing the address of the Server to connect to.
+pub(crate) type ServerSelector = Box<dyn FnMut(&ServerSelection) -> Option<String> + Send>;
+
+/// The details of a connection about to be opened, passed to the closure registered through
+/// `LightstreamerClient::set_server_selector()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerSelection {
+    /// The address configured through `ConnectionDetails.setServerAddress()`, if any.
+    pub configured_address: Option<String>,
+    /// The address of the previous connection, if any.
+    pub previous_address: Option<String>,
+    /// Whether the connection is meant to recover the current session, which only the Server
+    /// hosting it can do.
+    pub recovering: bool,
+}
+
+/// Closures registered for the main lifecycle events of a client, as a lightweight alternative
+/// to a `ClientListener`.
+#[derive(Default)]
+pub(crate) struct LifecycleHooks {
+    /// Called when a new session is created.
+    pub(crate) on_connect: Vec<SessionHook>,
+    /// Called when the client leaves the CONNECTED status.
+    pub(crate) on_disconnect: Vec<DisconnectHook>,
+    /// Called when a session is recovered on a new connection.
+    pub(crate) on_session_recovered: Vec<SessionHook>,
+    /// Called before each connection to choose the Server to connect to.
+    pub(crate) server_selector: Option<ServerSelector>,
+}
+
+impl LifecycleHooks {
+    /// Calls the hooks registered for a session created.
+    pub(crate) fn connected(&mut self, session_info: &SessionInfo) {
+        for hook in &mut self.on_connect {
+            hook(session_info);
+        }
+    }
+
+    /// Calls the hooks registered for a disconnection.
+    pub(crate) fn disconnected(&mut self, cause: &StatusChangeCause) {
+        for hook in &mut self.on_disconnect {
+            hook(cause);
+        }
+    }
+
+    /// Asks the server selector, if any, for the address to connect to; `None` means the
+    /// configured one.
+    pub(crate) fn select_server(&mut self, selection: &ServerSelection) -> Option<String> {
+        self.server_selector
+            .as_mut()
+            .and_then(|selector| selector(selection))
+    }
+
+    /// Calls the hooks registered for a session recovered.
+    pub(crate) fn session_recovered(&mut self, session_info: &SessionInfo) {
+        for hook in &mut self.on_session_recovered {
+            hook(session_info);
+        }
+    }
+}
+
+impl Debug for LifecycleHooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LifecycleHooks")
+            .field("on_connect", &self.on_connect.len())
+            .field("on_disconnect", &self.on_disconnect.len())
+            .field("on_session_recovered", &self.on_session_recovered.len())
+            .field("server_selector", &self.server_selector.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,618 @@
+use crate::client::socket::connect_stream;
+use crate::connection::CookieStore;
+use crate::protocol::ServerMessage;
+use crate::utils::Proxy;
+use futures_util::{Sink, Stream};
+use std::io;
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::Connector;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use url::Url;
+
+/// The Server to send HTTP requests to, with the settings of the connections.
+pub(crate) struct HttpEndpoint {
+    /// The address of the Server, such as `https://push.example.com/lightstreamer`.
+    pub(crate) url: Url,
+    /// The version of TLCP, sent in the query string of every request.
+    pub(crate) protocol: &'static str,
+    pub(crate) local_address: Option<IpAddr>,
+    pub(crate) connector: Option<Connector>,
+    pub(crate) server_name: Option<String>,
+    pub(crate) proxy: Option<Proxy>,
+    /// The extra headers of every request.
+    pub(crate) headers: Vec<(String, String)>,
+    /// The cookies sent with every request, to which the ones set by the responses are added.
+    pub(crate) cookie_store: CookieStore,
+    /// The parameters added to the requests opening a stream connection, such as
+    /// `LS_content_length`.
+    pub(crate) stream_params: Vec<(&'static str, String)>,
+}
+
+/// The session the control requests refer to, as notified by CONOK.
+#[derive(Clone)]
+struct SessionAddress {
+    session_id: String,
+    /// The host of the control requests, if not the one of the Server address.
+    control_link: Option<String>,
+}
+
+/// A connection to the Server over HTTP, which the session handles as a WebSocket: the frames
+/// written to it become HTTP requests, and the notifications of their responses are read from
+/// it.
+///
+/// `create_session` and `bind_session` open a stream connection, whose response carries the
+/// notifications of the session until the Server closes it, after `LOOP`, to be rebound. When
+/// polling, through the `LS_polling` parameter of the endpoint, every such response is a poll,
+/// returning the notifications available and `LOOP` as soon as possible; the
+/// other requests, such as `control` and `msg`, are sent in order on connections of their own,
+/// with the ID of the session. `wsok` is answered locally, as there is no WebSocket to confirm.
+pub(crate) struct HttpConnection {
+    endpoint: Arc<HttpEndpoint>,
+    session: Arc<Mutex<Option<SessionAddress>>>,
+    notification_sender: UnboundedSender<Result<Message, WsError>>,
+    notifications: UnboundedReceiver<Result<Message, WsError>>,
+    requests: UnboundedSender<String>,
+    stream_task: Option<JoinHandle<()>>,
+    request_task: JoinHandle<()>,
+}
+
+impl HttpConnection {
+    /// Creates a connection to the given endpoint. No HTTP request is sent until the session is
+    /// created or bound.
+    pub(crate) fn new(endpoint: HttpEndpoint) -> Self {
+        let endpoint = Arc::new(endpoint);
+        let session = Arc::new(Mutex::new(None));
+        let (notification_sender, notifications) = unbounded_channel();
+        let (requests, request_receiver) = unbounded_channel();
+        let request_task = tokio::spawn(send_requests(
+            endpoint.clone(),
+            session.clone(),
+            notification_sender.clone(),
+            request_receiver,
+        ));
+        HttpConnection {
+            endpoint,
+            session,
+            notification_sender,
+            notifications,
+            requests,
+            stream_task: None,
+            request_task,
+        }
+    }
+
+    /// Sends a frame written by the session.
+    fn send_frame(&mut self, frame: &str) {
+        let name = frame.split("\r\n").next().unwrap_or("").trim();
+        match name.to_ascii_lowercase().as_str() {
+            "wsok" => {
+                let _ = self
+                    .notification_sender
+                    .send(Ok(Message::Text("WSOK\r\n".into())));
+            }
+            "create_session" | "bind_session" => {
+                // A new stream connection replaces the previous one, if still open.
+                if let Some(stream_task) = self.stream_task.take() {
+                    stream_task.abort();
+                }
+                self.stream_task = Some(tokio::spawn(open_stream(
+                    self.endpoint.clone(),
+                    self.session.clone(),
+                    self.notification_sender.clone(),
+                    frame.to_string(),
+                )));
+            }
+            _ => {
+                let _ = self.requests.send(frame.to_string());
+            }
+        }
+    }
+}
+
+impl Drop for HttpConnection {
+    fn drop(&mut self) {
+        if let Some(stream_task) = self.stream_task.take() {
+            stream_task.abort();
+        }
+        self.request_task.abort();
+    }
+}
+
+impl Stream for HttpConnection {
+    type Item = Result<Message, WsError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().notifications.poll_recv(cx)
+    }
+}
+
+impl Sink<Message> for HttpConnection {
+    type Error = WsError;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Message) -> Result<(), WsError> {
+        let this = self.get_mut();
+        match message {
+            Message::Text(frame) => this.send_frame(&frame),
+            Message::Close(_) => {
+                if let Some(stream_task) = this.stream_task.take() {
+                    stream_task.abort();
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        if let Some(stream_task) = self.get_mut().stream_task.take() {
+            stream_task.abort();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Sends a `create_session` or `bind_session` request, forwarding the notifications of its
+/// response as they arrive. The end of the response is reported as an error, unless it was
+/// announced by `LOOP`.
+async fn open_stream(
+    endpoint: Arc<HttpEndpoint>,
+    session: Arc<Mutex<Option<SessionAddress>>>,
+    notifications: UnboundedSender<Result<Message, WsError>>,
+    frame: String,
+) {
+    let result = async {
+        let (name, mut body) = split_request(&frame);
+        for (param, value) in &endpoint.stream_params {
+            if !body.is_empty() {
+                body.push('&');
+            }
+            body.push_str(&serde_urlencoded::to_string([(param, value)]).unwrap_or_default());
+        }
+        let mut response = send_request(&endpoint, None, name, &body).await?;
+        let mut pending = Vec::new();
+        let mut buffer = [0u8; 8192];
+        let mut looped = false;
+        loop {
+            let len = response.read(&mut buffer).await?;
+            if len == 0 {
+                break;
+            }
+            pending.extend_from_slice(&buffer[..len]);
+            // Only complete notifications are forwarded.
+            let Some(end) = pending.windows(2).rposition(|window| window == b"\r\n") else {
+                continue;
+            };
+            let text = String::from_utf8_lossy(&pending[..end + 2]).into_owned();
+            pending.drain(..end + 2);
+            for line in text.split("\r\n") {
+                match ServerMessage::parse(line) {
+                    Ok(ServerMessage::Conok {
+                        session_id,
+                        control_link,
+                        ..
+                    }) => {
+                        *session.lock().unwrap_or_else(|err| err.into_inner()) =
+                            Some(SessionAddress {
+                                session_id: session_id.into_owned(),
+                                control_link: control_link.map(|link| link.into_owned()),
+                            });
+                    }
+                    Ok(ServerMessage::Loop(_)) => looped = true,
+                    _ => {}
+                }
+            }
+            if notifications.send(Ok(Message::Text(text.into()))).is_err() {
+                return Ok(());
+            }
+        }
+        if looped {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "The Server closed the stream connection",
+            ))
+        }
+    }
+    .await;
+    if let Err(err) = result {
+        let _ = notifications.send(Err(WsError::Io(err)));
+    }
+}
+
+/// Sends the requests other than those opening a stream connection, one at a time and in
+/// order, forwarding the notifications of their responses, such as REQOK.
+async fn send_requests(
+    endpoint: Arc<HttpEndpoint>,
+    session: Arc<Mutex<Option<SessionAddress>>>,
+    notifications: UnboundedSender<Result<Message, WsError>>,
+    mut requests: UnboundedReceiver<String>,
+) {
+    while let Some(frame) = requests.recv().await {
+        let result = async {
+            let Some(session) = session
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone()
+            else {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotConnected,
+                    "No session to send the request to",
+                ));
+            };
+            let (name, body) = split_request(&frame);
+            // Each request of the frame is on its own line, and refers to the session.
+            let session_param = serde_urlencoded::to_string([("LS_session", &session.session_id)])
+                .unwrap_or_default();
+            let body = match body.is_empty() {
+                true => session_param,
+                false => body
+                    .split("\r\n")
+                    .map(|line| format!("{}&{}", line, session_param))
+                    .collect::<Vec<_>>()
+                    .join("\r\n"),
+            };
+            let mut response =
+                send_request(&endpoint, session.control_link.as_deref(), name, &body).await?;
+            let mut text = String::new();
+            response.read_to_string(&mut text).await?;
+            Ok(text)
+        }
+        .await;
+        let notification = match result {
+            Ok(text) if text.trim().is_empty() => continue,
+            Ok(text) => Ok(Message::Text(text.into())),
+            Err(err) => Err(WsError::Io(err)),
+        };
+        if notifications.send(notification).is_err() {
+            break;
+        }
+    }
+}
+
+/// Splits a frame into the name of the request and its parameters, one request per line,
+/// leaving out `LS_protocol`, which is sent in the query string.
+fn split_request(frame: &str) -> (&str, String) {
+    let mut lines = frame.split("\r\n").map(str::trim);
+    let name = lines.next().unwrap_or("");
+    let body = lines
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.split('&')
+                .filter(|param| !param.starts_with("LS_protocol="))
+                .collect::<Vec<_>>()
+                .join("&")
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    (name, body)
+}
+
+/// Splits a control link, as notified by CONOK, into its host and its port, if any.
+fn split_host_port(link: &str) -> (&str, Option<u16>) {
+    match link.rsplit_once(':') {
+        // A colon inside an IPv6 address does not introduce a port.
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => match port.parse() {
+            Ok(port) => (host, Some(port)),
+            Err(_) => (link, None),
+        },
+        _ => (link, None),
+    }
+}
+
+/// Sends a TLCP request over HTTP, returning the body of the response.
+///
+/// # Parameters
+///
+/// * `endpoint`: the Server.
+/// * `control_link`: the host to send the request to, if not the one of the Server.
+/// * `name`: the name of the request, such as `control`.
+/// * `body`: the URL-encoded parameters of the request.
+async fn send_request(
+    endpoint: &HttpEndpoint,
+    control_link: Option<&str>,
+    name: &str,
+    body: &str,
+) -> io::Result<HttpBody<impl AsyncRead + Unpin>> {
+    let url = &endpoint.url;
+    let is_secure = url.scheme() == "https";
+    // A control link without a port of its own keeps the port of the Server address.
+    let (host, explicit_port) = match control_link {
+        Some(link) => match split_host_port(link) {
+            (host, None) => (host, url.port()),
+            address => address,
+        },
+        None => (
+            url.host_str().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "No host in server address")
+            })?,
+            url.port(),
+        ),
+    };
+    let port = explicit_port.or(url.port_or_known_default()).unwrap_or(80);
+    let path = format!("{}/{}.txt", url.path().trim_end_matches('/'), name);
+    // The URL of the request, to select the cookies to send and to keep the ones received.
+    let mut request_url = url.clone();
+    if control_link.is_some() {
+        request_url
+            .set_host(Some(host))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let _ = request_url.set_port(explicit_port);
+    }
+    request_url.set_path(&path);
+    let stream = connect_stream(
+        host,
+        port,
+        is_secure,
+        endpoint.local_address,
+        endpoint.connector.clone(),
+        endpoint.server_name.as_deref(),
+        endpoint.proxy.as_ref(),
+    )
+    .await
+    .map_err(|err| match err {
+        WsError::Io(err) => err,
+        err => io::Error::other(err),
+    })?;
+    let mut request = format!(
+        "POST {}?LS_protocol={} HTTP/1.1\r\nHost: {}\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n",
+        path,
+        endpoint.protocol,
+        match explicit_port {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        },
+        body.len()
+    );
+    if let Some(cookie_header) = endpoint.cookie_store.get_cookie_header(&request_url) {
+        request.push_str(&format!("Cookie: {}\r\n", cookie_header));
+    }
+    for (name, value) in &endpoint.headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    request.push_str("\r\n");
+    request.push_str(body);
+    let mut stream = BufReader::new(stream);
+    stream.get_mut().write_all(request.as_bytes()).await?;
+    let response = HttpBody::read_head(stream).await?;
+    // Keep the cookies set by the Server, e.g. for load-balancer affinity.
+    endpoint.cookie_store.add_set_cookie_headers(
+        &request_url,
+        response.set_cookies.iter().map(String::as_str),
+    );
+    Ok(response)
+}
+
+/// How the end of the body of a response is found.
+enum Framing {
+    /// The body is sent in chunks, the current one having `remaining` bytes left to read.
+    Chunked { remaining: u64 },
+    /// The body has the given number of bytes left to read.
+    Length(u64),
+    /// The body ends when the connection is closed.
+    Close,
+    /// The body has been read.
+    Done,
+}
+
+/// The body of an HTTP response, read as it arrives.
+struct HttpBody<R> {
+    reader: BufReader<R>,
+    framing: Framing,
+    /// The values of the `Set-Cookie` headers of the response.
+    set_cookies: Vec<String>,
+}
+
+impl<R: AsyncRead + Unpin> HttpBody<R> {
+    /// Reads the status line and the headers of a response, leaving the body to be read.
+    ///
+    /// # Raises
+    ///
+    /// * `io::Error`: if the response is malformed or its status is not 200.
+    async fn read_head(mut reader: BufReader<R>) -> io::Result<Self> {
+        let mut status_line = String::new();
+        reader.read_line(&mut status_line).await?;
+        let status_line = status_line.trim_end().to_string();
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("Unexpected HTTP response: '{}'", status_line),
+            ));
+        }
+        let mut framing = Framing::Close;
+        let mut set_cookies = Vec::new();
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).await? == 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            let header = header.trim_end();
+            if header.is_empty() {
+                break;
+            }
+            let Some((name, value)) = header.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("set-cookie") {
+                set_cookies.push(value.to_string());
+            } else if name.eq_ignore_ascii_case("transfer-encoding")
+                && value.to_ascii_lowercase().contains("chunked")
+            {
+                framing = Framing::Chunked { remaining: 0 };
+            } else if name.eq_ignore_ascii_case("content-length")
+                && !matches!(framing, Framing::Chunked { .. })
+            {
+                let length = value.parse().map_err(|_| {
+                    io::Error::new(io::ErrorKind::InvalidData, "Bad Content-Length")
+                })?;
+                framing = Framing::Length(length);
+            }
+        }
+        Ok(HttpBody {
+            reader,
+            framing,
+            set_cookies,
+        })
+    }
+
+    /// Reads some bytes of the body, returning 0 at its end.
+    async fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let limit = match self.framing {
+                Framing::Done => return Ok(0),
+                Framing::Close => buffer.len(),
+                Framing::Length(0) => {
+                    self.framing = Framing::Done;
+                    continue;
+                }
+                Framing::Length(remaining) => buffer.len().min(remaining as usize),
+                Framing::Chunked { remaining: 0 } => {
+                    let mut size_line = String::new();
+                    if self.reader.read_line(&mut size_line).await? == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                    let size = size_line.split(';').next().unwrap_or("").trim();
+                    // The line ending a chunk precedes the size of the next one.
+                    if size.is_empty() {
+                        continue;
+                    }
+                    let size = u64::from_str_radix(size, 16).map_err(|_| {
+                        io::Error::new(io::ErrorKind::InvalidData, "Bad chunk size")
+                    })?;
+                    self.framing = match size {
+                        0 => Framing::Done,
+                        size => Framing::Chunked { remaining: size },
+                    };
+                    continue;
+                }
+                Framing::Chunked { remaining } => buffer.len().min(remaining as usize),
+            };
+            let len = self.reader.read(&mut buffer[..limit]).await?;
+            match &mut self.framing {
+                Framing::Close => {}
+                _ if len == 0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                Framing::Length(remaining) | Framing::Chunked { remaining } => {
+                    *remaining -= len as u64
+                }
+                Framing::Done => {}
+            }
+            return Ok(len);
+        }
+    }
+
+    /// Reads the rest of the body as text.
+    async fn read_to_string(&mut self, text: &mut String) -> io::Result<()> {
+        let mut body = Vec::new();
+        let mut buffer = [0u8; 4096];
+        loop {
+            let len = self.read(&mut buffer).await?;
+            if len == 0 {
+                break;
+            }
+            body.extend_from_slice(&buffer[..len]);
+        }
+        text.push_str(&String::from_utf8_lossy(&body));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read_body(response: &'static str) -> io::Result<String> {
+        let mut body = HttpBody::read_head(BufReader::new(response.as_bytes())).await?;
+        let mut text = String::new();
+        body.read_to_string(&mut text).await?;
+        Ok(text)
+    }
+
+    #[tokio::test]
+    async fn test_read_response_bodies() {
+        assert_eq!(
+            read_body(
+                "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                 7\r\nPROBE\r\n\r\nA;ext=1\r\nLOOP,0\r\n\r\n\r\n0\r\n\r\n"
+            )
+            .await
+            .unwrap(),
+            "PROBE\r\nLOOP,0\r\n\r\n"
+        );
+        assert_eq!(
+            read_body("HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nREQOK,1\r\nignored")
+                .await
+                .unwrap(),
+            "REQOK,1\r\n"
+        );
+        assert_eq!(
+            read_body("HTTP/1.0 200 OK\r\n\r\nREQOK,1\r\n")
+                .await
+                .unwrap(),
+            "REQOK,1\r\n"
+        );
+        assert_eq!(
+            read_body("HTTP/1.1 503 Service Unavailable\r\n\r\n")
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::ConnectionRefused
+        );
+        let body = HttpBody::read_head(BufReader::new(
+            "HTTP/1.1 200 OK\r\nSet-Cookie: AWSALB=node-2; Path=/\r\nset-cookie: B=1\r\n\r\n"
+                .as_bytes(),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(body.set_cookies, ["AWSALB=node-2; Path=/", "B=1"]);
+        assert_eq!(
+            read_body("HTTP/1.1 200 OK\r\nContent-Length: 20\r\n\r\nREQOK")
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+
+    #[test]
+    fn test_split_host_port() {
+        assert_eq!(
+            split_host_port("push1.example.com:8443"),
+            ("push1.example.com", Some(8443))
+        );
+        assert_eq!(
+            split_host_port("push1.example.com"),
+            ("push1.example.com", None)
+        );
+        assert_eq!(split_host_port("[::1]:8080"), ("[::1]", Some(8080)));
+        assert_eq!(split_host_port("::1"), ("::1", None));
+    }
+
+    #[test]
+    fn test_split_request() {
+        assert_eq!(
+            split_request("create_session\r\nLS_adapter_set=DEMO&LS_protocol=TLCP-2.5.0\n"),
+            ("create_session", "LS_adapter_set=DEMO".to_string())
+        );
+        assert_eq!(
+            split_request("control\r\nLS_reqId=1\r\nLS_reqId=2"),
+            ("control", "LS_reqId=1\r\nLS_reqId=2".to_string())
+        );
+        assert_eq!(split_request("heartbeat\r\n"), ("heartbeat", String::new()));
+    }
+}
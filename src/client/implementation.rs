@@ -1,34 +1,84 @@
-use crate::subscription::{ItemUpdate, Snapshot, Subscription, SubscriptionMode};
+use crate::subscription::{
+    DataGap, DataGapCause, FieldList, ItemUpdate, Snapshot, Subscription, SubscriptionMode,
+};
 
 use crate::client::Transport;
+use crate::client::batch::{ControlBatch, pack_control_frames};
+use crate::client::compression::PERMESSAGE_DEFLATE_OFFER;
+use crate::client::diagnostics::SessionDiagnostics;
+use crate::client::dump::{ClientStateDump, SubscriptionDump};
+use crate::client::handle::ClientHandle;
+use crate::client::health::HealthProbe;
+use crate::client::hooks::{LifecycleHooks, ServerSelection};
+use crate::client::http::{HttpConnection, HttpEndpoint};
+use crate::client::interceptor::{RequestInterceptor, intercept_request};
+use crate::client::journal::MessageJournal;
+use crate::client::limiter::ReconnectLimiter;
 pub(crate) use crate::client::listener::ClientListener;
 use crate::client::message_listener::ClientMessageListener;
-use crate::client::model::{ClientStatus, DisconnectionType, LogType};
-use crate::client::request::SubscriptionRequest;
+use crate::client::messages::{InFlightMessages, MessageQueue, PendingMessages};
+use crate::client::model::{
+    ClientStatus, ConnectionType, DisconnectionType, EndCauseReaction, LogType,
+};
+use crate::client::ordered::{OrderedDispatch, OrderedUpdate};
+use crate::client::probe::{
+    TransportProbeResult, cache_probe, clear_probe_cache, get_cached_probe, probe_websocket,
+};
+use crate::client::prog::ProgCheck;
+use crate::client::recorder::{FlightRecorder, RecordedEventKind, redact_credentials};
+use crate::client::recovery::{RecoveryBudget, SessionState, is_retired_table};
+use crate::client::request::{
+    MessageRequest, PendingRequest, PendingRequests, RequestResponder, SubscriptionRequest,
+    request_response,
+};
+use crate::client::sampling::{LogSampler, LogSampling};
+use crate::client::session::{SessionInfo, SessionReplacement};
+use crate::client::socket::{ServerConnection, ServerWebSocket, connect_websocket};
+use crate::client::status::{
+    ErrorSeverity, LastError, LastErrorHandle, StatusChangeCause, StatusHistory,
+};
 use crate::client::utils::get_subscription_by_id;
-use crate::connection::{ConnectionDetails, ConnectionOptions};
-use crate::utils::{IllegalStateException, clean_message, parse_arguments};
+use crate::connection::{ConnectionDetails, ConnectionOptions, CookieStore};
+use crate::protocol::{
+    self, FieldValue, ItemNotification, RequestError, ServerMessage, SubscriptionOk,
+};
+use crate::utils::logging::{Level, debug, error, info, trace, warn};
+use crate::utils::{
+    IllegalArgumentException, IllegalStateException, LightstreamerError, OversizedMessageError,
+    ServerException, SlowStartWarning, TimeoutError,
+};
+use bumpalo::Bump;
 use cookie::Cookie;
-use futures_util::{SinkExt, StreamExt};
-use std::collections::HashMap;
+use futures_util::{FutureExt, Sink, SinkExt, StreamExt};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fmt::{self, Debug, Formatter};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime};
+use tokio::runtime::Handle;
 use tokio::sync::mpsc::channel;
+use tokio::sync::mpsc::error::TrySendError;
 use tokio::sync::{
-    Notify,
-    mpsc::{Receiver, Sender},
+    Notify, OwnedSemaphorePermit,
+    mpsc::{Receiver, Sender, UnboundedReceiver, UnboundedSender, unbounded_channel},
+    watch,
 };
-use tokio_tungstenite::{
-    connect_async,
-    tungstenite::{
-        Message,
-        http::{HeaderName, HeaderValue, Request},
-    },
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, sleep_until};
+use tokio_tungstenite::Connector;
+use tokio_tungstenite::tungstenite::{
+    Message,
+    http::{HeaderName, HeaderValue, Request},
 };
-use tracing::{Level, debug, error, info, instrument, trace, warn};
+#[cfg(feature = "logging")]
+use tracing::instrument;
 use url::Url;
 
+/// Handle of the task running a client, as returned by `LightstreamerClient::spawn()`, which
+/// completes with the client and the outcome of `connect()`.
+pub type ClientTask = JoinHandle<(LightstreamerClient, Result<(), LightstreamerError>)>;
+
 /// Facade class for the management of the communication to Lightstreamer Server. Used to provide
 /// configuration settings, event handlers, operations for the control of the connection lifecycle,
 /// Subscription handling and to send messages.
@@ -52,6 +102,12 @@ use url::Url;
 /// instances of `LightstreamerClient`, they will all use a single event thread, that is shared
 /// among them.
 ///
+/// The event thread is the task running `connect()`, which calls the listeners with no lock held.
+/// A listener may therefore call back into the client, but not through `subscription_sender` or
+/// `message_sender`, whose bounded queues are served by that same task: use the `ClientHandle`
+/// returned by `get_handle()` instead, whose methods never wait, and whose requests are processed
+/// as soon as the listener returns.
+///
 /// # Parameters
 ///
 /// * `server_address`: the address of the Lightstreamer Server to which this `LightstreamerClient`
@@ -82,6 +138,8 @@ pub struct LightstreamerClient {
     pub connection_options: ConnectionOptions,
     /// A list of listeners that will receive events from the `LightstreamerClient` instance.
     listeners: Vec<Box<dyn ClientListener>>,
+    /// The listeners owned by the application, removed once dropped.
+    weak_listeners: Vec<Weak<dyn ClientListener + Sync>>,
     /// A list containing all the `Subscription` instances that are currently "active" on this
     /// `LightstreamerClient`.
     subscriptions: Vec<Subscription>,
@@ -93,6 +151,60 @@ pub struct LightstreamerClient {
     pub subscription_sender: Sender<SubscriptionRequest>,
     /// The receiver used for subscribe/unsubsribe
     subscription_receiver: Receiver<SubscriptionRequest>,
+    /// The sender that can be used to send messages
+    pub message_sender: Sender<MessageRequest>,
+    /// The receiver used for sending messages
+    message_receiver: Receiver<MessageRequest>,
+    /// The unbounded sender of subscribe/unsubscribe requests shared by the client handles.
+    handle_subscription_sender: UnboundedSender<SubscriptionRequest>,
+    /// The receiver of the subscribe/unsubscribe requests made through the client handles.
+    handle_subscription_receiver: UnboundedReceiver<SubscriptionRequest>,
+    /// The unbounded sender of messages shared by the client handles.
+    handle_message_sender: UnboundedSender<MessageRequest>,
+    /// The receiver of the messages sent through the client handles.
+    handle_message_receiver: UnboundedReceiver<MessageRequest>,
+    /// The status of the client, as seen by the client handles.
+    status_sender: watch::Sender<ClientStatus>,
+    /// The signal of the snapshot refreshes requested for the subscriptions.
+    snapshot_refresh: Arc<Notify>,
+    /// The recorder of the last frames and state changes, if enabled.
+    flight_recorder: Option<FlightRecorder>,
+    /// The queue all the updates are delivered through in arrival order, if enabled.
+    ordered_dispatch: Option<OrderedDispatch>,
+    /// The last status transitions of the client.
+    status_history: StatusHistory,
+    /// The most recent error of the client, if any.
+    last_error: LastErrorHandle,
+    /// The cookies used to access the Server, possibly shared with other clients.
+    cookie_store: CookieStore,
+    /// The details of the current session provided by the Server, if any.
+    session_info: watch::Sender<Option<SessionInfo>>,
+    /// The live diagnostics of the client.
+    diagnostics: SessionDiagnostics,
+    /// The probe reporting the health of the client.
+    health_probe: HealthProbe,
+    /// The messages sent with a listener whose outcome was not received yet.
+    in_flight_messages: InFlightMessages,
+    /// The messages waiting for a session, kept across connection attempts.
+    message_queue: MessageQueue,
+    /// The interceptors the requests to the Server are passed through, in order.
+    request_interceptors: Vec<Box<dyn RequestInterceptor>>,
+    /// The sampler of the log lines emitted for each update.
+    update_log_sampler: LogSampler,
+    /// The closures called on lifecycle events.
+    hooks: LifecycleHooks,
+    /// The address chosen by the server selector for the current connection, if any.
+    selected_server_address: Option<String>,
+    /// The address of the previous connection, if any.
+    previous_server_address: Option<String>,
+    /// The limiter coordinating the reconnections with other clients, if any.
+    reconnect_limiter: Option<ReconnectLimiter>,
+    /// The disk-backed queue of the messages to deliver at least once, if any.
+    message_journal: Option<MessageJournal>,
+    /// The runtime the client is spawned on by `spawn()`, if not the current one.
+    runtime_handle: Option<Handle>,
+    /// The permit of the limiter held while the current connection is being opened.
+    connect_permit: Option<OwnedSemaphorePermit>,
 }
 
 impl Debug for LightstreamerClient {
@@ -103,7 +215,25 @@ impl Debug for LightstreamerClient {
             .field("connection_details", &self.connection_details)
             .field("connection_options", &self.connection_options)
             .field("listeners", &self.listeners)
+            .field("weak_listeners", &self.get_weak_listener_count())
             .field("subscriptions", &self.subscriptions)
+            .field("flight_recorder", &self.flight_recorder)
+            .field("ordered_dispatch", &self.ordered_dispatch.is_some())
+            .field("status_history", &self.status_history)
+            .field("last_error", &self.last_error.get())
+            .field("cookie_store", &self.cookie_store)
+            .field("session_info", &*self.session_info.borrow())
+            .field("diagnostics", &self.diagnostics)
+            .field("health_probe", &self.health_probe)
+            .field("in_flight_messages", &self.in_flight_messages)
+            .field("message_queue", &self.message_queue.len())
+            .field("request_interceptors", &self.request_interceptors)
+            .field("update_log_sampling", &self.update_log_sampler.sampling())
+            .field("hooks", &self.hooks)
+            .field("selected_server_address", &self.selected_server_address)
+            .field("reconnect_limiter", &self.reconnect_limiter)
+            .field("message_journal", &self.message_journal)
+            .field("runtime_handle", &self.runtime_handle)
             .finish()
     }
 }
@@ -137,6 +267,15 @@ impl LightstreamerClient {
     /// A constant string representing the version of the TLCP protocol used by the library.
     pub const TLCP_VERSION: &'static str = "TLCP-2.4.0";
 
+    /// The maximum delay before opening a new session after an END notification.
+    const MAX_BACKOFF_DELAY: Duration = Duration::from_secs(60);
+
+    /// The maximum number of frames already received processed in a row by the read loop.
+    const MAX_READ_BATCH: usize = 64;
+
+    /// The time allowed to `probe_transport()` when no probe timeout is configured.
+    const DEFAULT_TRANSPORT_PROBE_TIMEOUT: Duration = Duration::from_secs(4);
+
     /// Static method that can be used to share cookies between connections to the Server (performed by
     /// this library) and connections to other sites that are performed by the application. With this
     /// method, cookies received by the application can be added (or replaced if already present) to
@@ -174,6 +313,515 @@ impl LightstreamerClient {
         self.listeners.push(listener);
     }
 
+    /// Adds a listener held through a weak reference, so that it is removed automatically once
+    /// its owner drops it, without any call to remove it.
+    ///
+    /// Weak listeners receive the same events as the other listeners, after them, but they are
+    /// not returned by `get_listeners()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `listener`: The listener, whose strong references are kept by the application.
+    ///
+    /// See also `addListener()`
+    pub fn add_listener_weak<L: ClientListener + Sync + 'static>(&mut self, listener: &Arc<L>) {
+        let listener: Weak<L> = Arc::downgrade(listener);
+        self.weak_listeners.push(listener);
+    }
+
+    /// Returns the number of weak listeners whose owner did not drop them yet.
+    ///
+    /// See also `add_listener_weak()`
+    pub fn get_weak_listener_count(&self) -> usize {
+        self.weak_listeners
+            .iter()
+            .filter(|listener| listener.strong_count() > 0)
+            .count()
+    }
+
+    /// Notifies an event to all the listeners, removing the weak listeners dropped by their owner.
+    fn dispatch_to_listeners(&mut self, event: impl Fn(&dyn ClientListener)) {
+        for listener in &self.listeners {
+            event(listener.as_ref());
+        }
+        self.weak_listeners
+            .retain(|listener| match listener.upgrade() {
+                Some(listener) => {
+                    event(&*listener);
+                    true
+                }
+                None => false,
+            });
+    }
+
+    /// Dispatches the updates deferred because of the priorities of the subscriptions, highest
+    /// priority first, keeping the order of the updates of the same priority.
+    fn dispatch_deferred_updates(&mut self, deferred_updates: &mut Vec<(i32, usize, ItemUpdate)>) {
+        deferred_updates.sort_by_key(|(priority, _, _)| Reverse(*priority));
+        for (_, subscription_id, item_update) in deferred_updates.drain(..) {
+            if let Some(subscription) = self
+                .subscriptions
+                .iter_mut()
+                .find(|s| s.id == subscription_id)
+            {
+                subscription.on_item_update(&item_update);
+                subscription.get_consumer_lag().record_delivered(1);
+            }
+        }
+    }
+
+    /// Notifies the subscribed subscriptions that their updates may have been missed.
+    fn notify_data_gap(
+        &mut self,
+        cause: DataGapCause,
+        lost_updates: Option<u64>,
+        since: Option<SystemTime>,
+    ) {
+        let gap = DataGap::new(cause, None, lost_updates, since);
+        for subscription in self.subscriptions.iter_mut() {
+            if subscription.is_subscribed() {
+                subscription.on_data_gap(&gap);
+            }
+        }
+    }
+
+    /// Adds an interceptor that will see, and may modify, every request sent to the Server from
+    /// now on: session creation and recovery, subscription and unsubscription requests, and
+    /// messages. Interceptors are called in the order they were added.
+    ///
+    /// This allows, for instance, to append the parameters expected by a custom Metadata Adapter,
+    /// to sign the requests or to log them.
+    ///
+    /// # Parameters
+    ///
+    /// * `interceptor`: An object that will receive the requests about to be sent.
+    ///
+    /// See also `RequestInterceptor`
+    pub fn add_request_interceptor(&mut self, interceptor: Box<dyn RequestInterceptor>) {
+        self.request_interceptors.push(interceptor);
+    }
+
+    /// Removes all the request interceptors added through `add_request_interceptor()`.
+    pub fn clear_request_interceptors(&mut self) {
+        self.request_interceptors.clear();
+    }
+
+    /// Enables the flight recorder, which keeps the last protocol frames exchanged with the Server
+    /// and the last connection state changes, with their timestamps.
+    ///
+    /// The recorder is dumped to the log when `connect()` fails, and can be dumped on demand
+    /// through the returned handle, even while the client is connected. If the recorder is
+    /// already enabled, it is replaced by an empty one.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity`: the maximum number of events kept; older events are discarded first.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the recorder.
+    ///
+    /// See also `FlightRecorder::dump()`
+    pub fn enable_flight_recorder(&mut self, capacity: usize) -> FlightRecorder {
+        let recorder = FlightRecorder::new(capacity);
+        self.flight_recorder = Some(recorder.clone());
+        recorder
+    }
+
+    /// Disables the flight recorder, discarding its content.
+    pub fn disable_flight_recorder(&mut self) {
+        self.flight_recorder = None;
+    }
+
+    /// Inquiry method that gets the flight recorder, if enabled.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the recorder, or `None` if it is not enabled.
+    pub fn get_flight_recorder(&self) -> Option<&FlightRecorder> {
+        self.flight_recorder.as_ref()
+    }
+
+    /// Enables the ordered dispatch, which delivers the updates of all the subscriptions through
+    /// a single queue, in the order they were received from the network, for consumers that
+    /// rebuild a state consistent across several tables. The listeners of the subscriptions are
+    /// still called as usual, possibly in a different order, as with `Subscription::set_priority()`.
+    ///
+    /// The queue is unbounded, so that a slow consumer never stalls the session. If the ordered
+    /// dispatch is already enabled, the previous queue is closed. Dropping the returned receiver
+    /// disables the ordered dispatch.
+    ///
+    /// # Returns
+    ///
+    /// The receiving end of the queue.
+    ///
+    /// See also `OrderedUpdate`
+    pub fn enable_ordered_dispatch(&mut self) -> UnboundedReceiver<OrderedUpdate> {
+        let (dispatch, receiver) = OrderedDispatch::new();
+        self.ordered_dispatch = Some(dispatch);
+        receiver
+    }
+
+    /// Disables the ordered dispatch, closing its queue.
+    pub fn disable_ordered_dispatch(&mut self) {
+        self.ordered_dispatch = None;
+    }
+
+    /// Setter method that sets the limiter coordinating the reconnections of the client with the
+    /// other clients of the process sharing it, to avoid a thundering herd of reconnections after
+    /// a network outage.
+    ///
+    /// The limiter applies to every connection opened after the first one, including the ones
+    /// recovering a session.
+    ///
+    /// # Parameters
+    ///
+    /// * `limiter`: the limiter, shared with other clients, or `None` to reconnect freely.
+    ///
+    /// See also `ReconnectLimiter`
+    pub fn set_reconnect_limiter(&mut self, limiter: Option<ReconnectLimiter>) {
+        self.reconnect_limiter = limiter;
+    }
+
+    /// Inquiry method that gets the limiter coordinating the reconnections of the client, if any.
+    ///
+    /// See also `setReconnectLimiter()`
+    pub fn get_reconnect_limiter(&self) -> Option<&ReconnectLimiter> {
+        self.reconnect_limiter.as_ref()
+    }
+
+    /// Setter method that sets the journal the client takes messages from, for their delivery
+    /// to survive process restarts.
+    ///
+    /// The messages pushed to the journal and still without outcome are sent as soon as a session
+    /// is available, on each new session, until the Server reports their outcome. A journal
+    /// should only be given to one client at a time.
+    ///
+    /// # Parameters
+    ///
+    /// * `journal`: the journal, or `None` to send messages through `sendMessage()` only.
+    ///
+    /// See also `MessageJournal`
+    pub fn set_message_journal(&mut self, journal: Option<MessageJournal>) {
+        self.message_journal = journal;
+    }
+
+    /// Inquiry method that gets the journal the client takes messages from, if any.
+    ///
+    /// See also `setMessageJournal()`
+    pub fn get_message_journal(&self) -> Option<&MessageJournal> {
+        self.message_journal.as_ref()
+    }
+
+    /// Returns a handle to the client, to subscribe, unsubscribe and send messages from the
+    /// listeners of the client and of its subscriptions without risk of deadlock.
+    ///
+    /// The listeners are called by the task running the session, which cannot serve the bounded
+    /// `subscription_sender` and `message_sender` queues until they return: a listener waiting
+    /// on a full queue would wait forever. The methods of the handle never wait, and the requests
+    /// made through it are processed, in order, as soon as the current callback returns.
+    ///
+    /// # Returns
+    ///
+    /// A handle that can be cloned and moved into the listeners.
+    pub fn get_handle(&self) -> ClientHandle {
+        ClientHandle::new(
+            self.handle_subscription_sender.clone(),
+            self.handle_message_sender.clone(),
+            self.status_sender.subscribe(),
+        )
+    }
+
+    /// Setter method that sets the Tokio runtime the client is spawned on by `spawn()`, so that
+    /// hosts running several runtimes, or custom schedulers, can place the session of the client
+    /// deliberately instead of on the runtime `spawn()` is called from.
+    ///
+    /// # Parameters
+    ///
+    /// * `handle`: the handle of the runtime, or `None` to use the current one.
+    ///
+    /// See also `spawn()`
+    pub fn set_runtime_handle(&mut self, handle: Option<Handle>) {
+        self.runtime_handle = handle;
+    }
+
+    /// Inquiry method that gets the Tokio runtime the client is spawned on, if set.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the runtime, or `None` if `spawn()` uses the current one.
+    ///
+    /// See also `setRuntimeHandle()`
+    pub fn get_runtime_handle(&self) -> Option<&Handle> {
+        self.runtime_handle.as_ref()
+    }
+
+    /// Runs `connect()` in a task spawned on the runtime set through `setRuntimeHandle()`, or
+    /// on the current one, handing the client back with the outcome once it returns.
+    ///
+    /// # Parameters
+    ///
+    /// * `shutdown_signal`: the signal disconnecting the client, as for `connect()`.
+    ///
+    /// # Returns
+    ///
+    /// The handle of the task.
+    ///
+    /// # Panics
+    ///
+    /// If no runtime handle was set and the method is not called from within a Tokio runtime.
+    ///
+    /// See also `connect()`
+    pub fn spawn(mut self, shutdown_signal: Arc<Notify>) -> ClientTask {
+        let runtime = self.runtime_handle.clone().unwrap_or_else(Handle::current);
+        runtime.spawn(async move {
+            let result = self.connect(shutdown_signal).await;
+            (self, result)
+        })
+    }
+
+    /// Setter method that sets the cookie store used to access the Server, replacing the one
+    /// created with the client.
+    ///
+    /// Giving the same store to several clients targeting the same cluster makes the cookies
+    /// obtained by one of them, such as load-balancer affinity cookies, apply to all of them.
+    ///
+    /// This method should be invoked before calling `connect()`: the supplied store will be used
+    /// on the next WebSocket establishment.
+    ///
+    /// # Parameters
+    ///
+    /// * `cookie_store`: the cookie store.
+    ///
+    /// See also `getCookieStore()`
+    pub fn set_cookie_store(&mut self, cookie_store: CookieStore) {
+        self.cookie_store = cookie_store;
+    }
+
+    /// Inquiry method that gets the cookie store used to access the Server.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the store, which can be given to sibling clients through
+    /// `set_cookie_store()`.
+    pub fn get_cookie_store(&self) -> CookieStore {
+        self.cookie_store.clone()
+    }
+
+    /// Inquiry method that gets the details of the current session provided by the Server, such
+    /// as the keepalive interval, the request limit, the control link and the server name.
+    ///
+    /// # Returns
+    ///
+    /// The details of the current session, or `None` if no session is active.
+    ///
+    /// See also `subscribeSessionInfo()`
+    pub fn get_session_info(&self) -> Option<SessionInfo> {
+        self.session_info.borrow().clone()
+    }
+
+    /// Operation method that returns a receiver notified whenever the details of the current
+    /// session change: when a session is created, when the Server sends its name or the client
+    /// IP, and when the session ends (the value becomes `None`).
+    ///
+    /// Unlike `get_session_info()`, the receiver can be used while `connect()` is running.
+    pub fn subscribe_session_info(&self) -> watch::Receiver<Option<SessionInfo>> {
+        self.session_info.subscribe()
+    }
+
+    /// Inquiry method that gets the probe reporting the health of the client, for the readiness
+    /// and liveness probes of orchestrators such as Kubernetes.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the probe, which can be kept to read the health while the client is connected.
+    ///
+    /// See also `HealthProbe::serve()`
+    pub fn get_health_probe(&self) -> HealthProbe {
+        self.health_probe.clone()
+    }
+
+    /// Inquiry method that gets the list of the messages sent with a listener whose outcome was
+    /// not received yet, with their sequence, progressive number and age, as well as those whose
+    /// outcome was lost when the last session ended.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the list, which can be kept to read it while the client is connected.
+    ///
+    /// See also `sendMessage()`
+    pub fn get_in_flight_messages(&self) -> InFlightMessages {
+        self.in_flight_messages.clone()
+    }
+
+    /// Inquiry method that gets the live diagnostics of the client, such as the progressive
+    /// count of the data notifications received in the current session.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the diagnostics, which can be kept to read them while the client is connected.
+    pub fn get_diagnostics(&self) -> SessionDiagnostics {
+        self.diagnostics.clone()
+    }
+
+    /// Inquiry method that gets the history of the status transitions of the client, with their
+    /// timestamps and causes, such as the END cause code sent by the Server.
+    ///
+    /// The last `StatusHistory::DEFAULT_CAPACITY` transitions are kept, unless configured
+    /// otherwise through `set_status_history_capacity()`.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the history, which can be kept to inspect it while the client is connected.
+    ///
+    /// See also `getStatus()`
+    pub fn get_status_history(&self) -> StatusHistory {
+        self.status_history.clone()
+    }
+
+    /// Inquiry method that gets the most recent error of the client, such as a refused session
+    /// or a lost connection, so that callers polling `getStatus()` can tell why the client got
+    /// disconnected.
+    ///
+    /// The error is kept until the next one, even after the client reconnected; it is fatal when
+    /// the client gave up after it, transient otherwise.
+    ///
+    /// # Returns
+    ///
+    /// The most recent error, with its timestamp and severity, or `None` if there was none.
+    ///
+    /// See also `get_last_error_handle()`
+    pub fn last_error(&self) -> Option<LastError> {
+        self.last_error.get()
+    }
+
+    /// Inquiry method that gets a handle on the most recent error of the client, updated by the
+    /// session as errors happen.
+    ///
+    /// # Returns
+    ///
+    /// A handle on the error, which can be kept to read it while the client is connected.
+    ///
+    /// See also `last_error()`
+    pub fn get_last_error_handle(&self) -> LastErrorHandle {
+        self.last_error.clone()
+    }
+
+    /// Setter method that sets the maximum number of status transitions kept by the client,
+    /// discarding the current history.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity`: the maximum number of transitions kept; older ones are discarded first.
+    ///
+    /// See also `getStatusHistory()`
+    pub fn set_status_history_capacity(&mut self, capacity: usize) {
+        self.status_history = StatusHistory::new(capacity);
+    }
+
+    /// Updates the status of the client, recording the transition in the status history and
+    /// calling the lifecycle hooks.
+    fn set_status(&mut self, status: ClientStatus, cause: StatusChangeCause) {
+        self.make_log(
+            Level::DEBUG,
+            &format!("Client status changed to {} ({})", status, cause),
+        );
+        let was_connected = matches!(self.status, ClientStatus::Connected(_));
+        let is_connected = matches!(status, ClientStatus::Connected(_));
+        if was_connected && !is_connected {
+            self.hooks.disconnected(&cause);
+        }
+        if let Some(session_info) = self.get_session_info() {
+            match cause {
+                StatusChangeCause::SessionCreated { .. } => self.hooks.connected(&session_info),
+                StatusChangeCause::SessionRecovered { .. } => {
+                    self.hooks.session_recovered(&session_info)
+                }
+                _ => {}
+            }
+        }
+        self.health_probe.set_status(&status.to_string());
+        if cause.is_error() {
+            let severity = match status {
+                ClientStatus::Disconnected(DisconnectionType::NoRetry) => ErrorSeverity::Fatal,
+                _ => ErrorSeverity::Transient,
+            };
+            self.last_error.record(LastError {
+                timestamp: SystemTime::now(),
+                severity,
+                cause: cause.clone(),
+            });
+        }
+        self.status_history.record(status.clone(), cause);
+        self.status_sender.send_replace(status.clone());
+        self.status = status;
+    }
+
+    /// Registers a closure called each time a new session is created, with its details.
+    ///
+    /// This is a lightweight alternative to a `ClientListener` for simple side effects, such as
+    /// updating a metric. Like listeners, the closure is called by the task running the session,
+    /// so it should be fast and never block.
+    ///
+    /// # Parameters
+    ///
+    /// * `hook`: The closure to call.
+    ///
+    /// See also `on_disconnect()`
+    pub fn on_connect(&mut self, hook: impl FnMut(&SessionInfo) + Send + 'static) {
+        self.hooks.on_connect.push(Box::new(hook));
+    }
+
+    /// Registers a closure called each time the client leaves the `CONNECTED` status, with the
+    /// cause, e.g. to invalidate a cache fed by the subscriptions.
+    ///
+    /// # Parameters
+    ///
+    /// * `hook`: The closure to call.
+    ///
+    /// See also `on_connect()`
+    pub fn on_disconnect(&mut self, hook: impl FnMut(&StatusChangeCause) + Send + 'static) {
+        self.hooks.on_disconnect.push(Box::new(hook));
+    }
+
+    /// Registers a closure called each time the session is recovered on a new connection after
+    /// a network drop, with its details. In this case no update was lost and `on_connect()`
+    /// hooks are not called.
+    ///
+    /// # Parameters
+    ///
+    /// * `hook`: The closure to call.
+    ///
+    /// See also `ConnectionOptions.setSessionRecoveryTimeout()`
+    pub fn on_session_recovered(&mut self, hook: impl FnMut(&SessionInfo) + Send + 'static) {
+        self.hooks.on_session_recovered.push(Box::new(hook));
+    }
+
+    /// Registers a closure called before each connection, including the reconnections and the
+    /// session recoveries, that can return the address of the Server to connect to, overriding
+    /// the one configured through `ConnectionDetails.setServerAddress()`. This allows choosing
+    /// the Server at run time, e.g. from a service registry or by probing the latency of the
+    /// Servers of several regions.
+    ///
+    /// Returning `None` uses the configured address. Since a session can only be recovered by
+    /// the Server hosting it, the closure should return the previous address when
+    /// `ServerSelection::recovering` is set, unless the Servers share their sessions. Like the
+    /// other hooks, the closure is called by the task running the connection, so it should be
+    /// fast and never block.
+    ///
+    /// # Parameters
+    ///
+    /// * `selector`: The closure choosing the address, which must start with "http://" or
+    ///   "https://"; it replaces the one registered before, if any.
+    ///
+    /// See also `ServerSelection`
+    pub fn set_server_selector(
+        &mut self,
+        selector: impl FnMut(&ServerSelection) -> Option<String> + Send + 'static,
+    ) {
+        self.hooks.server_selector = Some(Box::new(selector));
+    }
+
     /// Packs s string with the necessary parameters for a subscription request.
     ///
     /// # Parameters
@@ -262,65 +910,658 @@ impl LightstreamerClient {
         Ok(serde_urlencoded::to_string(&params)?)
     }
 
-    /// Operation method that requests to open a Session against the configured Lightstreamer Server.
-    ///
-    /// When `connect()` is called, unless a single transport was forced through `ConnectionOptions.setForcedTransport()`,
-    /// the so called "Stream-Sense" mechanism is started: if the client does not receive any answer
-    /// for some seconds from the streaming connection, then it will automatically open a polling
-    /// connection.
-    ///
-    /// A polling connection may also be opened if the environment is not suitable for a streaming
-    /// connection.
-    ///
-    /// Note that as "polling connection" we mean a loop of polling requests, each of which requires
-    /// opening a synchronous (i.e. not streaming) connection to Lightstreamer Server.
-    ///
-    /// Note that the request to connect is accomplished by the client in a separate thread; this
-    /// means that an invocation to `getStatus()` right after `connect()` might not reflect the change
-    /// yet.
+    /// Moves the subscription at the given index under a new ID, returning the requests which
+    /// unsubscribe the old ID and subscribe the new one, so that the Server sends a fresh
+    /// snapshot.
     ///
-    /// When the request to connect is finally being executed, if the current status of the client
-    /// is not `DISCONNECTED`, then nothing will be done.
+    /// # Parameters
     ///
-    /// # Raises
+    /// * `index`: The index of the subscription.
+    /// * `request_id`: The last request ID used, advanced for the two requests.
+    /// * `subscription_id`: The last subscription ID used, advanced for the new ID.
+    /// * `pending_requests`: The requests awaiting an answer, where the two requests are added.
+    fn get_resubscription_requests(
+        &mut self,
+        index: usize,
+        request_id: &mut usize,
+        subscription_id: &mut usize,
+        pending_requests: &mut PendingRequests,
+    ) -> Result<[String; 2], Box<dyn Error + Send + Sync>> {
+        let old_id = self.subscriptions[index].id;
+        *request_id += 1;
+        pending_requests.insert(
+            *request_id,
+            PendingRequest::Unsubscription {
+                subscription_id: old_id,
+            },
+        );
+        let unsubscription = intercept_request(
+            &self.request_interceptors,
+            "control",
+            Self::get_unsubscription_params(old_id, *request_id)?,
+        )?;
+        *request_id += 1;
+        *subscription_id += 1;
+        let subscription = &mut self.subscriptions[index];
+        subscription.id = *subscription_id;
+        let _ = subscription.id_sender.try_send(*subscription_id);
+        subscription.on_subscription_request();
+        pending_requests.insert(
+            *request_id,
+            PendingRequest::subscription(subscription, *subscription_id),
+        );
+        let resubscription = intercept_request(
+            &self.request_interceptors,
+            "control",
+            Self::get_subscription_params(subscription, *request_id)?,
+        )?;
+        Ok([unsubscription, resubscription])
+    }
+
+    /// Packs a string with the necessary parameters for a message request.
     ///
-    /// * `IllegalStateException`: if no server address was configured.
+    /// # Parameters
     ///
-    /// See also `getStatus()`
+    /// * `message_request`: The message to be sent.
+    /// * `request_id`: The request ID to use in the parameters.
+    /// * `prog`: The progressive number of the message in its sequence, if needed.
+    fn get_message_params(
+        message_request: &MessageRequest,
+        request_id: usize,
+        prog: Option<usize>,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let ls_req_id = request_id.to_string();
+        let ls_msg_prog = prog.map(|prog| prog.to_string());
+        let ls_max_wait = message_request
+            .delay_timeout
+            .filter(|_| !message_request.is_unordered())
+            .map(|delay_timeout| delay_timeout.to_string());
+        let ls_outcome = message_request.listener.is_some().to_string();
+        //
+        // Prepare the message request.
+        //
+        let mut params: Vec<(&str, &str)> = vec![
+            ("LS_reqId", &ls_req_id),
+            ("LS_message", &message_request.message),
+            ("LS_sequence", &message_request.sequence),
+        ];
+        if let Some(ls_msg_prog) = &ls_msg_prog {
+            params.push(("LS_msg_prog", ls_msg_prog));
+        }
+        if let Some(ls_max_wait) = &ls_max_wait {
+            params.push(("LS_max_wait", ls_max_wait));
+        }
+        params.push(("LS_outcome", &ls_outcome));
+        params.push(("LS_ack", "false"));
+
+        Ok(serde_urlencoded::to_string(&params)?)
+    }
+
+    /// Sends a message on the current session, keeping track of it until its outcome is received
+    /// if a listener was supplied. Returns the encoded request parameters.
+    ///
+    /// # Parameters
+    ///
+    /// * `write_stream`: The stream the request is written to.
+    /// * `pending_messages`: The messages of the session waiting for an outcome.
+    /// * `pending_requests`: The requests awaiting an answer, where the request is added.
+    /// * `message_request`: The message to be sent.
+    /// * `request_id`: The request ID to use.
+    /// * `interceptors`: The interceptors the request is passed through.
+    async fn write_message_request<S>(
+        write_stream: &mut S,
+        pending_messages: &mut PendingMessages,
+        pending_requests: &mut PendingRequests,
+        mut message_request: MessageRequest,
+        request_id: usize,
+        interceptors: &[Box<dyn RequestInterceptor>],
+    ) -> Result<String, Box<dyn Error + Send + Sync>>
+    where
+        S: Sink<Message> + Unpin,
+        S::Error: Error + Send + Sync + 'static,
+    {
+        // Fire-and-forget messages need no progressive number.
+        let prog = if !message_request.is_unordered() || message_request.listener.is_some() {
+            Some(pending_messages.next_prog(&message_request.sequence))
+        } else {
+            None
+        };
+        let responder = message_request.responder.take();
+        pending_requests.insert_awaited(
+            request_id,
+            PendingRequest::message(&message_request, prog),
+            responder,
+        );
+        let encoded_params = Self::get_message_params(&message_request, request_id, prog)?;
+        let encoded_params = intercept_request(interceptors, "msg", encoded_params)?;
+        write_stream
+            .send(Message::Text(format!("msg\r\n{}", encoded_params).into()))
+            .await?;
+        if let Some(prog) = prog
+            && message_request.listener.is_some()
+        {
+            pending_messages.track(prog, message_request);
+        }
+        Ok(encoded_params)
+    }
+
+    /// Sends the journaled messages still without outcome that were not sent yet in the current
+    /// session. Returns the encoded request parameters of each message sent.
+    ///
+    /// # Parameters
+    ///
+    /// * `write_stream`: The stream the requests are written to.
+    /// * `pending_messages`: The messages of the session waiting for an outcome.
+    /// * `pending_requests`: The requests awaiting an answer, where the requests are added.
+    /// * `journal`: The journal the messages are taken from.
+    /// * `journal_sent`: The IDs of the journaled messages already sent in the session.
+    /// * `request_id`: The last request ID used, incremented for each message.
+    /// * `interceptors`: The interceptors the requests are passed through.
+    async fn write_journaled_messages<S>(
+        write_stream: &mut S,
+        pending_messages: &mut PendingMessages,
+        pending_requests: &mut PendingRequests,
+        journal: &MessageJournal,
+        journal_sent: &mut HashSet<u64>,
+        request_id: &mut usize,
+        interceptors: &[Box<dyn RequestInterceptor>],
+    ) -> Result<Vec<String>, Box<dyn Error + Send + Sync>>
+    where
+        S: Sink<Message> + Unpin,
+        S::Error: Error + Send + Sync + 'static,
+    {
+        let mut sent = Vec::new();
+        for journaled in journal.get_pending() {
+            if !journal_sent.insert(journaled.id) {
+                continue;
+            }
+            let message_request = journal.get_message_request(&journaled);
+            *request_id += 1;
+            sent.push(
+                Self::write_message_request(
+                    write_stream,
+                    pending_messages,
+                    pending_requests,
+                    message_request,
+                    *request_id,
+                    interceptors,
+                )
+                .await?,
+            );
+        }
+        Ok(sent)
+    }
+
+    /// Maps the sequence name found in MSGDONE/MSGFAIL notifications to the one of the request,
+    /// since the Server reports "UNORDERED_MESSAGES" as `*`.
+    fn get_message_sequence(sequence: &str) -> &str {
+        if sequence == "*" {
+            MessageRequest::UNORDERED_MESSAGES
+        } else {
+            sequence
+        }
+    }
+
+    /// Packs a string with the necessary parameters for a create_session request.
+    fn get_create_session_params(&self) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let ls_adapter_set = match self.connection_details.get_adapter_set() {
+            Some(adapter_set) => adapter_set,
+            None => {
+                return Err(Box::new(IllegalStateException::new(
+                    "No adapter set found in connection details.",
+                )));
+            }
+        };
+        let ls_send_sync = self.connection_options.get_send_sync().to_string();
+        let ls_keepalive_millis = self.connection_options.get_keepalive_interval().to_string();
+        let ls_inactivity_millis = self
+            .connection_options
+            .get_reverse_heartbeat_interval()
+            .to_string();
+        let mut params: Vec<(&str, &str)> = vec![
+            ("LS_adapter_set", ls_adapter_set),
+            ("LS_cid", "mgQkwtwdysogQz2BJ4Ji kOj2Bg"),
+            ("LS_send_sync", &ls_send_sync),
+        ];
+        if self.connection_options.get_reduce_head() {
+            params.push(("LS_reduce_head", "true"));
+        }
+        // With no keepalive interval configured, the Server decides it.
+        if self.connection_options.get_keepalive_interval() > 0 {
+            params.push(("LS_keepalive_millis", &ls_keepalive_millis));
+        }
+        // The Server can tell a silent client from a broken connection.
+        if self.connection_options.get_reverse_heartbeat_interval() > 0 {
+            params.push(("LS_inactivity_millis", &ls_inactivity_millis));
+        }
+        if let Some(user) = &self.connection_details.get_user() {
+            params.push(("LS_user", user));
+        }
+        if let Some(password) = &self.connection_details.get_password() {
+            params.push(("LS_password", password));
+        }
+        params.push(("LS_protocol", Self::TLCP_VERSION));
+        Ok(serde_urlencoded::to_string(&params)?)
+    }
+
+    /// Packs a string with the necessary parameters for a bind_session request recovering the
+    /// given session.
+    ///
+    /// # Parameters
+    ///
+    /// * `session_id`: the ID of the session to recover.
+    /// * `recovery_from`: the number of data notifications already received in the session, so
+    ///   that the Server resends the following ones.
+    fn get_bind_session_params(
+        &self,
+        session_id: &str,
+        recovery_from: u64,
+    ) -> Result<String, Box<dyn Error + Send + Sync>> {
+        let ls_recovery_from = recovery_from.to_string();
+        let ls_send_sync = self.connection_options.get_send_sync().to_string();
+        let ls_keepalive_millis = self.connection_options.get_keepalive_interval().to_string();
+        let ls_inactivity_millis = self
+            .connection_options
+            .get_reverse_heartbeat_interval()
+            .to_string();
+        let mut params: Vec<(&str, &str)> = vec![
+            ("LS_session", session_id),
+            ("LS_recovery_from", &ls_recovery_from),
+            ("LS_send_sync", &ls_send_sync),
+        ];
+        if self.connection_options.get_reduce_head() {
+            params.push(("LS_reduce_head", "true"));
+        }
+        if self.connection_options.get_keepalive_interval() > 0 {
+            params.push(("LS_keepalive_millis", &ls_keepalive_millis));
+        }
+        if self.connection_options.get_reverse_heartbeat_interval() > 0 {
+            params.push(("LS_inactivity_millis", &ls_inactivity_millis));
+        }
+        Ok(serde_urlencoded::to_string(&params)?)
+    }
+
+    /// Applies a JSON Patch (`^P` value) to the previous value of a field.
+    #[cfg(feature = "json-patch")]
+    fn apply_json_patch(prev_value: &str, diff_value: &str) -> String {
+        let patch: serde_json::Value =
+            serde_json::from_str(diff_value).unwrap_or(serde_json::Value::Null);
+        let mut prev_json: serde_json::Value =
+            serde_json::from_str(prev_value).unwrap_or(serde_json::Value::Null);
+        let patch_operations: Vec<json_patch::PatchOperation> =
+            serde_json::from_value(patch).unwrap_or_default();
+        let _ = json_patch::patch(&mut prev_json, &patch_operations);
+        prev_json.to_string()
+    }
+
+    /// Without the `json-patch` feature, JSON Patches cannot be applied: the previous value of
+    /// the field is kept.
+    #[cfg(not(feature = "json-patch"))]
+    fn apply_json_patch(prev_value: &str, _diff_value: &str) -> String {
+        warn!("JSON Patch ignored: the `json-patch` feature is disabled");
+        prev_value.to_string()
+    }
+
+    /// Operation method that requests to open a Session against the configured Lightstreamer Server.
+    ///
+    /// When `connect()` is called, unless a single transport was forced through `ConnectionOptions.setForcedTransport()`,
+    /// the so called "Stream-Sense" mechanism is started: if the client does not receive any answer
+    /// for some seconds from the streaming connection, then it will automatically open a polling
+    /// connection.
+    ///
+    /// A polling connection may also be opened if the environment is not suitable for a streaming
+    /// connection.
+    ///
+    /// Note that as "polling connection" we mean a loop of polling requests, each of which requires
+    /// opening a synchronous (i.e. not streaming) connection to Lightstreamer Server.
+    ///
+    /// Note that the request to connect is accomplished by the client in a separate thread; this
+    /// means that an invocation to `getStatus()` right after `connect()` might not reflect the change
+    /// yet.
+    ///
+    /// When the request to connect is finally being executed, if the current status of the client
+    /// is not `DISCONNECTED`, then nothing will be done.
+    ///
+    /// When the Server ends the session (END notification), a new session is opened or not
+    /// depending on the END cause, as configured through `ConnectionOptions.setEndCauseReaction()`.
+    /// The method returns once no new session is going to be opened.
+    ///
+    /// When the connection is lost while the session is still alive on the Server, the client
+    /// enters the `DISCONNECTED:TRYING-RECOVERY` status and tries to recover the session on new
+    /// connections, without losing any update. If the session cannot be recovered within
+    /// `ConnectionOptions.getSessionRecoveryTimeout()`, it is abandoned and a new session is
+    /// created, with all the subscriptions sent again.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if no server address was configured.
+    ///
+    /// See also `getStatus()`
     ///
     /// See also `disconnect()`
     ///
     /// See also `ClientListener.onStatusChange()`
     ///
     /// See also `ConnectionDetails.setServerAddress()`
-    #[instrument(level = "trace")]
+    ///
+    /// See also `enableFlightRecorder()`
+    ///
+    /// See also `ConnectionOptions.setEndCauseReaction()`
+    #[cfg_attr(feature = "logging", instrument(level = "trace"))]
     pub async fn connect(
         &mut self,
         shutdown_signal: Arc<Notify>,
-    ) -> Result<(), Box<dyn Error + Send + Sync>> {
-        // Check if the server address is configured.
-        if self.server_address.is_none() {
-            return Err(Box::new(IllegalStateException::new(
-                "No server address was configured.",
-            )));
+    ) -> Result<(), LightstreamerError> {
+        let result = self.open_sessions(shutdown_signal).await;
+        // No session is coming anymore for the messages waiting for one.
+        let queued = self.message_queue.take();
+        if !queued.is_empty() {
+            self.make_log(
+                Level::INFO,
+                &format!("Aborting {} queued messages", queued.len()),
+            );
         }
-        //
-        // Only WebSocket streaming transport is currently supported.
-        //
-        let forced_transport = self.connection_options.get_forced_transport();
-        if forced_transport.is_none()
-            || *forced_transport.unwrap() /* unwrap() is safe here */ != Transport::WsStreaming
+        for message_request in queued {
+            message_request.abort(false);
+        }
+        result
+    }
+
+    /// Opens sessions, and recovers them, until no new session is going to be opened. See
+    /// `connect()`.
+    async fn open_sessions(
+        &mut self,
+        shutdown_signal: Arc<Notify>,
+    ) -> Result<(), LightstreamerError> {
+        let mut attempt: u32 = 0;
+        // Consecutive backoffs without a session being created, to compute the next delay.
+        let mut backoffs: u32 = 0;
+        // The session to recover on a new connection, if any.
+        let mut state = SessionState::default();
+        // The time left to recover the session, while trying.
+        let mut recovery: Option<RecoveryBudget> = None;
+        // Whether a new session is replacing a dropped one, as the network may still be down.
+        let mut replacing = false;
+        let mut reconnecting = false;
+        loop {
+            if reconnecting && !self.acquire_connect_permit(&shutdown_signal).await {
+                return Ok(());
+            }
+            reconnecting = true;
+            if recovery.is_none() {
+                let cause = match attempt {
+                    0 => StatusChangeCause::ConnectRequested,
+                    attempt => StatusChangeCause::Retry { attempt },
+                };
+                self.set_status(ClientStatus::Connecting, cause);
+            }
+            let result = self.run_session(shutdown_signal.clone(), &mut state).await;
+            self.connect_permit = None;
+            let session_created = self.session_info.send_replace(None).is_some();
+            let session_end = match result {
+                Ok(session_end) => session_end,
+                // A failed recovery attempt is retried while the recovery timeout allows, and a
+                // failed replacement of a dropped session is retried after the backoff delay.
+                Err(err) if recovery.is_some() || replacing => {
+                    StatusChangeCause::ConnectionLost(err.to_string())
+                }
+                Err(err) => {
+                    self.set_status(
+                        ClientStatus::Disconnected(DisconnectionType::NoRetry),
+                        StatusChangeCause::Failure(err.to_string()),
+                    );
+                    if let Some(recorder) = self.flight_recorder.clone() {
+                        recorder.record(RecordedEventKind::StateChange(format!(
+                            "Session failed: {}",
+                            err
+                        )));
+                        self.make_log(
+                            Level::ERROR,
+                            &format!("Flight recorder dump:\n{}", recorder.dump()),
+                        );
+                    }
+                    return Err(err.into());
+                }
+            };
+            if let Some(recorder) = &self.flight_recorder {
+                recorder.record(RecordedEventKind::StateChange("Session closed".to_string()));
+            }
+            if session_created {
+                backoffs = 0;
+                replacing = false;
+            }
+
+            //
+            // A session dropped by the network is recovered on a new connection, until the
+            // recovery timeout expires.
+            //
+            let connection_dropped = matches!(
+                session_end,
+                StatusChangeCause::ConnectionClosed | StatusChangeCause::ConnectionLost(_)
+            );
+            if connection_dropped && state.is_recoverable() {
+                if session_created {
+                    recovery = Some(RecoveryBudget::new(Duration::from_millis(
+                        self.connection_options.get_session_recovery_timeout(),
+                    )));
+                    state.dropped_at = Some(SystemTime::now());
+                }
+                if let Some(remaining) = recovery.as_ref().and_then(RecoveryBudget::remaining) {
+                    // The first attempt is immediate, the next ones wait for the retry delay.
+                    let delay = if session_created {
+                        Duration::ZERO
+                    } else {
+                        Duration::from_millis(self.connection_options.get_retry_delay())
+                            .min(remaining)
+                    };
+                    self.set_status(
+                        ClientStatus::Disconnected(DisconnectionType::TryingRecovery),
+                        session_end,
+                    );
+                    self.make_log(
+                        Level::INFO,
+                        &format!("Recovering the session in {} ms", delay.as_millis()),
+                    );
+                    if !self.wait_before_retry(delay, &shutdown_signal).await {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                self.make_log(
+                    Level::WARN,
+                    "Session recovery timeout expired, opening a new session",
+                );
+                self.notify_data_gap(DataGapCause::RecoveryFailed, None, state.dropped_at);
+                state = state.renew();
+                recovery = None;
+                replacing = true;
+                self.set_status(
+                    ClientStatus::Disconnected(DisconnectionType::WillRetry),
+                    StatusChangeCause::RecoveryAbandoned,
+                );
+                attempt += 1;
+                continue;
+            }
+            // Any other end means that the session is gone.
+            let dropped_at = state.dropped_at;
+            state = state.renew();
+            if recovery.take().is_some()
+                && matches!(session_end, StatusChangeCause::ConnectionRefused { .. })
+            {
+                self.make_log(
+                    Level::WARN,
+                    "Session recovery refused by the server, opening a new session",
+                );
+                self.notify_data_gap(DataGapCause::RecoveryFailed, None, dropped_at);
+                replacing = true;
+                self.set_status(
+                    ClientStatus::Disconnected(DisconnectionType::WillRetry),
+                    session_end,
+                );
+                attempt += 1;
+                continue;
+            }
+
+            // Only sessions ended by the Server are reopened, as configured for the END cause, and
+            // the replacements of dropped sessions until the network is back.
+            let reaction = match &session_end {
+                StatusChangeCause::SessionEnded { code, .. } => {
+                    self.connection_options.get_end_cause_reaction(*code)
+                }
+                _ if replacing && connection_dropped => EndCauseReaction::Backoff,
+                _ => EndCauseReaction::GiveUp,
+            };
+            let delay = match reaction {
+                EndCauseReaction::GiveUp => {
+                    self.set_status(
+                        ClientStatus::Disconnected(DisconnectionType::NoRetry),
+                        session_end,
+                    );
+                    return Ok(());
+                }
+                EndCauseReaction::Reconnect => Duration::ZERO,
+                EndCauseReaction::Backoff => {
+                    let delay = Duration::from_millis(self.connection_options.get_retry_delay())
+                        .saturating_mul(2u32.saturating_pow(backoffs))
+                        .min(Self::MAX_BACKOFF_DELAY);
+                    backoffs += 1;
+                    delay
+                }
+            };
+            self.set_status(
+                ClientStatus::Disconnected(DisconnectionType::WillRetry),
+                session_end,
+            );
+            attempt += 1;
+            self.make_log(
+                Level::INFO,
+                &format!("Opening a new session in {} ms", delay.as_millis()),
+            );
+            if !self.wait_before_retry(delay, &shutdown_signal).await {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Same as `connect()`, stopping when the given future completes rather than on an
+    /// `Arc<Notify>`, so that the client composes with hosts that already own signal handling or
+    /// cancellation.
+    ///
+    /// ```ignore
+    /// // With tokio-util's CancellationToken, owned by the host.
+    /// client.connect_with_shutdown(token.cancelled()).await?;
+    /// // With the termination signals handled by tokio.
+    /// client.connect_with_shutdown(async { let _ = tokio::signal::ctrl_c().await; }).await?;
+    /// ```
+    ///
+    /// # Parameters
+    ///
+    /// * `shutdown`: The future triggering the shutdown when it completes.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if no server address was configured.
+    ///
+    /// See also `connect()`
+    pub async fn connect_with_shutdown<F>(&mut self, shutdown: F) -> Result<(), LightstreamerError>
+    where
+        F: Future<Output = ()> + Send,
+    {
+        let shutdown_signal = Arc::new(Notify::new());
+        let connection = self.connect(shutdown_signal.clone());
+        tokio::pin!(connection);
+        tokio::select! {
+            result = &mut connection => result,
+            _ = shutdown => {
+                // The permit is kept until the client waits for the signal.
+                shutdown_signal.notify_one();
+                connection.await
+            }
+        }
+    }
+
+    /// Waits for the reconnect limiter, if any, to allow a new connection, unless a shutdown is
+    /// requested meanwhile.
+    ///
+    /// # Returns
+    ///
+    /// `false` if a shutdown was requested, in which case the client is disconnected.
+    async fn acquire_connect_permit(&mut self, shutdown_signal: &Notify) -> bool {
+        let Some(limiter) = self.reconnect_limiter.clone() else {
+            return true;
+        };
+        tokio::select! {
+            permit = limiter.acquire() => {
+                self.connect_permit = Some(permit);
+                true
+            },
+            _ = shutdown_signal.notified() => {
+                self.set_status(
+                    ClientStatus::Disconnected(DisconnectionType::NoRetry),
+                    StatusChangeCause::ShutdownRequested,
+                );
+                false
+            },
+        }
+    }
+
+    /// Waits for the given delay before a new connection attempt, unless a shutdown is
+    /// requested meanwhile.
+    ///
+    /// # Returns
+    ///
+    /// `false` if a shutdown was requested, in which case the client is disconnected.
+    async fn wait_before_retry(&mut self, delay: Duration, shutdown_signal: &Notify) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => true,
+            _ = shutdown_signal.notified() => {
+                self.set_status(
+                    ClientStatus::Disconnected(DisconnectionType::NoRetry),
+                    StatusChangeCause::ShutdownRequested,
+                );
+                false
+            },
+        }
+    }
+
+    /// Builds the TLS connector of the connections to the Server, or returns `None` to use the
+    /// default one.
+    fn get_tls_connector(&mut self) -> Result<Option<Connector>, Box<dyn Error + Send + Sync>> {
+        #[cfg(feature = "dangerous-dev")]
+        if self
+            .connection_options
+            .is_dangerous_accept_invalid_certificates()
         {
+            self.make_log(
+                Level::WARN,
+                "DANGER: the certificate and host name of the Server are NOT verified; this is only acceptable with local test Servers",
+            );
+            let connector = native_tls::TlsConnector::builder()
+                .danger_accept_invalid_certs(true)
+                .danger_accept_invalid_hostnames(true)
+                .build()?;
+            return Ok(Some(Connector::NativeTls(connector)));
+        }
+        Ok(None)
+    }
+
+    /// Builds the WebSocket handshake request for the configured Server address.
+    fn get_websocket_request(&self) -> Result<Request<()>, Box<dyn Error + Send + Sync>> {
+        let Some(http_url) = self
+            .selected_server_address
+            .as_ref()
+            .or(self.connection_details.get_server_address())
+        else {
             return Err(Box::new(IllegalStateException::new(
-                "Only WebSocket streaming transport is currently supported.",
+                "No server address was configured.",
             )));
-        }
+        };
         //
         // Convert the HTTP URL to a WebSocket URL.
         //
-        let http_url = self.connection_details.get_server_address().unwrap(); // unwrap() is safe here.
-        let mut url = Url::parse(http_url)
-            .expect("Failed to parse server address URL from connection details.");
+        let mut url = Url::parse(http_url).map_err(|err| {
+            IllegalStateException::new(&format!("Invalid server address '{}': {}", http_url, err))
+        })?;
         match url.scheme() {
             "http" => url
                 .set_scheme("ws")
@@ -338,7 +1579,7 @@ impl LightstreamerClient {
         let ws_url = url.as_str();
 
         // Build the WebSocket request with the necessary headers.
-        let request = Request::builder()
+        let mut request = Request::builder()
             .uri(ws_url)
             .header(
                 HeaderName::from_static("connection"),
@@ -368,12 +1609,135 @@ impl LightstreamerClient {
             .header(
                 HeaderName::from_static("upgrade"),
                 HeaderValue::from_static(Self::SEC_WEBSOCKET_UPGRADE),
-            )
-            .body(())?;
+            );
+        if self.connection_options.is_compression_enabled() {
+            request = request.header(
+                HeaderName::from_static("sec-websocket-extensions"),
+                HeaderValue::from_static(PERMESSAGE_DEFLATE_OFFER),
+            );
+        }
+        if let Some(cookie_header) = self.cookie_store.get_cookie_header(&url) {
+            request = request.header(
+                HeaderName::from_static("cookie"),
+                HeaderValue::from_str(&cookie_header)?,
+            );
+        }
+        Ok(request.body(())?)
+    }
+
+    /// Operation method that tests whether WebSockets actually work through the current network
+    /// path, by opening a WebSocket to the configured Server and completing a WSOK handshake,
+    /// without creating a session.
+    ///
+    /// The outcome is cached for each Server address and shared by all the clients of the
+    /// process: when a recent outcome is available, it is returned without probing again. The
+    /// probe is allowed `ConnectionOptions.getTransportProbeTimeout()` milliseconds, or 4 seconds
+    /// if the probe is disabled there.
+    ///
+    /// `connect()` performs this probe itself when a timeout is configured through
+    /// `ConnectionOptions.setTransportProbeTimeout()`.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if no valid server address was configured.
+    pub async fn probe_transport(&mut self) -> Result<TransportProbeResult, LightstreamerError> {
+        let request = self.get_websocket_request()?;
+        let url = request.uri().to_string();
+        if let Some(result) = get_cached_probe(&url) {
+            self.make_log(
+                Level::DEBUG,
+                &format!("Using cached transport probe for {}: {}", url, result),
+            );
+            return Ok(result);
+        }
+        let timeout = match self.connection_options.get_transport_probe_timeout() {
+            0 => Self::DEFAULT_TRANSPORT_PROBE_TIMEOUT,
+            timeout => Duration::from_millis(timeout),
+        };
+        let local_address = self.connection_options.get_local_address();
+        let connector = self.get_tls_connector()?;
+        let server_name = self.connection_options.get_tls_server_name().cloned();
+        let proxy = self.connection_options.resolve_proxy(&url)?;
+        let result = probe_websocket(
+            request,
+            local_address,
+            connector,
+            server_name.as_deref(),
+            proxy.as_ref(),
+            timeout,
+        )
+        .await;
+        self.make_log(
+            Level::INFO,
+            &format!("Transport probe for {}: {}", url, result),
+        );
+        cache_probe(&url, result.clone());
+        Ok(result)
+    }
+
+    /// Discards the cached outcomes of the transport probes of all the clients of the process,
+    /// so that the next probe tests the network path again, e.g. after a network change.
+    ///
+    /// See also `probeTransport()`
+    pub fn clear_transport_probe_cache() {
+        clear_probe_cache();
+    }
+
+    /// Opens a WebSocket to the Server, after probing the network path if required. Returns
+    /// the WebSocket and its address.
+    async fn open_websocket(
+        &mut self,
+    ) -> Result<(ServerWebSocket, String), Box<dyn Error + Send + Sync>> {
+        // Probe the network path first, if required, to fail fast when WebSockets are broken.
+        if self.connection_options.get_transport_probe_timeout() > 0
+            && let TransportProbeResult::WebSocketUnavailable(reason) =
+                self.probe_transport().await?
+        {
+            return Err(Box::new(IllegalStateException::new(&format!(
+                "WebSocket transport is not available through the current network path: {}",
+                reason
+            ))));
+        }
+
+        let request = self.get_websocket_request()?;
+        let ws_url = request.uri().to_string();
 
         // Connect to the Lightstreamer server using WebSocket.
-        let ws_stream = match connect_async(request).await {
+        let local_address = self.connection_options.get_local_address();
+        let connector = self.get_tls_connector()?;
+        let server_name = self.connection_options.get_tls_server_name().cloned();
+        let proxy = self.connection_options.resolve_proxy(&ws_url)?;
+        if let Some(proxy) = &proxy {
+            self.make_log(
+                Level::DEBUG,
+                &format!(
+                    "Connecting through proxy {}:{}",
+                    proxy.get_host(),
+                    proxy.get_port()
+                ),
+            );
+        }
+        match connect_websocket(
+            request,
+            local_address,
+            connector,
+            server_name.as_deref(),
+            proxy.as_ref(),
+        )
+        .await
+        {
             Ok((ws_stream, response)) => {
+                // Keep the cookies set by the Server, e.g. for load-balancer affinity.
+                if let Ok(url) = Url::parse(&ws_url) {
+                    self.cookie_store.add_set_cookie_headers(
+                        &url,
+                        response
+                            .headers()
+                            .get_all("set-cookie")
+                            .iter()
+                            .filter_map(|value| value.to_str().ok()),
+                    );
+                }
                 if let Some(server_header) = response.headers().get("server") {
                     self.make_log(
                         Level::INFO,
@@ -385,76 +1749,556 @@ impl LightstreamerClient {
                 } else {
                     self.make_log(Level::INFO, "Connected to Lightstreamer server");
                 }
-                ws_stream
-            }
-            Err(err) => {
-                return Err(Box::new(std::io::Error::new(
-                    std::io::ErrorKind::ConnectionRefused,
-                    format!(
-                        "Failed to connect to Lightstreamer server with WebSocket: {}",
-                        err
-                    ),
-                )));
+                Ok((ws_stream, ws_url))
             }
+            Err(err) => Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::ConnectionRefused,
+                format!(
+                    "Failed to connect to Lightstreamer server with WebSocket: {}",
+                    err
+                ),
+            ))),
+        }
+    }
+
+    /// Returns the Server to connect to over HTTP, with the settings of the connections.
+    ///
+    /// # Parameters
+    ///
+    /// * `polling`: whether the session is to be polled, rather than streamed: every request
+    ///   of the session then returns the notifications available, waiting at most the idle
+    ///   timeout, and the next one is sent after the polling interval.
+    fn get_http_endpoint(
+        &mut self,
+        polling: bool,
+    ) -> Result<HttpEndpoint, Box<dyn Error + Send + Sync>> {
+        let Some(http_url) = self
+            .selected_server_address
+            .as_ref()
+            .or(self.connection_details.get_server_address())
+        else {
+            return Err(Box::new(IllegalStateException::new(
+                "No server address was configured.",
+            )));
         };
+        let url = Url::parse(http_url).map_err(|err| {
+            IllegalStateException::new(&format!("Invalid server address '{}': {}", http_url, err))
+        })?;
+        let mut headers = Vec::new();
+        if let Some(extra_headers) = self.connection_options.get_http_extra_headers() {
+            headers.extend(
+                extra_headers
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.clone())),
+            );
+        }
+        let mut stream_params = Vec::new();
+        if polling {
+            stream_params.push(("LS_polling", "true".to_string()));
+            stream_params.push((
+                "LS_polling_millis",
+                self.connection_options.get_polling_interval().to_string(),
+            ));
+            stream_params.push((
+                "LS_idle_millis",
+                self.connection_options.get_idle_timeout().to_string(),
+            ));
+        } else if let Some(content_length) = self.connection_options.get_content_length() {
+            stream_params.push(("LS_content_length", content_length.to_string()));
+        }
+        let proxy = self.connection_options.resolve_proxy(url.as_str())?;
+        Ok(HttpEndpoint {
+            url,
+            protocol: Self::TLCP_VERSION,
+            local_address: self.connection_options.get_local_address(),
+            connector: self.get_tls_connector()?,
+            server_name: self.connection_options.get_tls_server_name().cloned(),
+            proxy,
+            headers,
+            cookie_store: self.cookie_store.clone(),
+            stream_params,
+        })
+    }
 
-        // Split the WebSocket stream into a write and a read stream.
-        let (mut write_stream, mut read_stream) = ws_stream.split();
+    /// Returns the transports to try in order, as allowed by the forced transport: with none, or
+    /// with just "WS" or "HTTP", the Stream-Sense algorithm falls back from streaming over
+    /// WebSocket to streaming over HTTP, then to polling over HTTP.
+    fn get_transport_chain(&self) -> VecDeque<ConnectionType> {
+        match self.connection_options.get_forced_transport() {
+            None => VecDeque::from([
+                ConnectionType::WsStreaming,
+                ConnectionType::HttpStreaming,
+                ConnectionType::HttpPolling,
+            ]),
+            Some(Transport::Ws) | Some(Transport::WsStreaming) => {
+                VecDeque::from([ConnectionType::WsStreaming])
+            }
+            Some(Transport::Http) => {
+                VecDeque::from([ConnectionType::HttpStreaming, ConnectionType::HttpPolling])
+            }
+            Some(Transport::HttpStreaming) => VecDeque::from([ConnectionType::HttpStreaming]),
+            Some(Transport::WsPolling) => VecDeque::from([ConnectionType::WsPolling]),
+            Some(Transport::HttpPolling) => VecDeque::from([ConnectionType::HttpPolling]),
+        }
+    }
 
-        //
-        // Initiate communication with the server by sending a 'wsok' message.
-        //
-        write_stream.send(Message::Text("wsok".into())).await?;
+    /// Returns when a connection of the given type is given up for the next transport, if it
+    /// has not confirmed the session yet, or `None` if it is never.
+    fn get_fallback_deadline(
+        &self,
+        connection_type: &ConnectionType,
+        transports: &VecDeque<ConnectionType>,
+    ) -> Option<Instant> {
+        let timeout = match connection_type {
+            ConnectionType::WsStreaming => self.connection_options.get_ws_streaming_timeout(),
+            ConnectionType::HttpStreaming => self.connection_options.get_http_streaming_timeout(),
+            _ => 0,
+        };
+        (timeout > 0 && !transports.is_empty())
+            .then(|| Instant::now() + Duration::from_millis(timeout))
+    }
 
-        //
-        // Start reading and processing messages from the server.
-        //
-        let mut is_connected = false;
-        let mut request_id: usize = 0;
-        let mut _session_id: Option<String> = None;
-        let mut subscription_id: usize = 0;
-        let mut subscription_item_updates: HashMap<usize, HashMap<usize, ItemUpdate>> =
-            HashMap::new();
-        loop {
-            tokio::select! {
-                message = read_stream.next() => {
-                    match message {
-                        Some(Ok(Message::Text(text))) => {
-                            // Messages could include multiple submessages separated by /r/n.
-                            // Split the message into submessages and process each one separately.
-                            let submessages: Vec<&str> = text.split("\r\n")
-                                .filter(|&line| !line.trim().is_empty()) // Filter out empty lines.
-                                .collect();
-                            for submessage in submessages {
-                                let clean_text = clean_message(submessage);
-                                let submessage_fields: Vec<&str> = clean_text.split(",").collect();
-                                match *submessage_fields.first().unwrap_or(&"") {
-                                    //
-                                    // Errors from server.
-                                    //
-                                    "conerr" | "reqerr" => {
-                                        self.make_log( Level::ERROR, &format!("Received connection error from Lightstreamer server: {}", clean_text) );
-                                        break;
-                                    },
-                                    //
-                                    // Session created successfully.
-                                    //
-                                    "conok" => {
-                                        is_connected = true;
-                                        if let Some(session_id) = submessage_fields.get(1) {
-                                            self.make_log( Level::DEBUG, &format!("Session creation confirmed by server: {}", clean_text) );
-                                            self.make_log( Level::DEBUG, &format!("Session created with ID: {:?}", session_id) );
-                                            //
-                                            // Subscribe to the desired items.
-                                            //
-                                            while let Some(subscription) = self.subscriptions.get_mut(subscription_id) {
-                                                //
+    /// Opens a connection with the given transport. Returns the connection and its address.
+    async fn open_connection(
+        &mut self,
+        connection_type: &ConnectionType,
+    ) -> Result<(ServerConnection, String), Box<dyn Error + Send + Sync>> {
+        match connection_type {
+            ConnectionType::WsStreaming => {
+                let (ws_stream, ws_url) = self.open_websocket().await?;
+                Ok((ServerConnection::WebSocket(Box::new(ws_stream)), ws_url))
+            }
+            ConnectionType::HttpStreaming | ConnectionType::HttpPolling => {
+                let endpoint =
+                    self.get_http_endpoint(*connection_type == ConnectionType::HttpPolling)?;
+                let url = endpoint.url.to_string();
+                Ok((ServerConnection::Http(HttpConnection::new(endpoint)), url))
+            }
+            _ => Err(Box::new(IllegalStateException::new(
+                "Only the WS-STREAMING, HTTP-STREAMING and HTTP-POLLING transports are currently supported.",
+            ))),
+        }
+    }
+
+    /// Opens a connection with the first of the given transports that succeeds, removing the
+    /// transports tried. Only the failure of the last transport is returned.
+    ///
+    /// The stage timeout only bounds the WebSocket handshake here: an HTTP connection sends no
+    /// request until the session is created, so opening it cannot time out, and the HTTP stage
+    /// timeout is enforced by the `fallback_deadline` of `run_session()`, which waits for the
+    /// session to be confirmed.
+    ///
+    /// # Returns
+    ///
+    /// The connection, its address and its type.
+    async fn open_first_connection(
+        &mut self,
+        transports: &mut VecDeque<ConnectionType>,
+    ) -> Result<(ServerConnection, String, ConnectionType), Box<dyn Error + Send + Sync>> {
+        loop {
+            let Some(connection_type) = transports.pop_front() else {
+                return Err(Box::new(IllegalStateException::new(
+                    "No transport is allowed by the forced transport.",
+                )));
+            };
+            let deadline = self.get_fallback_deadline(&connection_type, transports);
+            let opening = self.open_connection(&connection_type);
+            let result = match deadline {
+                Some(deadline) => tokio::time::timeout_at(deadline, opening)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(Box::new(std::io::Error::new(
+                            std::io::ErrorKind::TimedOut,
+                            "Timed out opening the connection",
+                        )))
+                    }),
+                None => opening.await,
+            };
+            match result {
+                Ok((connection, connection_url)) => {
+                    return Ok((connection, connection_url, connection_type));
+                }
+                Err(err) if !transports.is_empty() => {
+                    self.make_log(
+                        Level::WARN,
+                        &format!(
+                            "Cannot connect with {:?}, falling back to {:?}: {}",
+                            connection_type, transports[0], err
+                        ),
+                    );
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Opens a session, or recovers the one in `state` if any, and processes it until it ends.
+    /// See `connect()`.
+    ///
+    /// # Returns
+    ///
+    /// Why the session ended.
+    async fn run_session(
+        &mut self,
+        shutdown_signal: Arc<Notify>,
+        state: &mut SessionState,
+    ) -> Result<StatusChangeCause, Box<dyn Error + Send + Sync>> {
+        // Let the server selector, if any, choose the Server for this connection.
+        let configured_address = self.connection_details.get_server_address().cloned();
+        let selection = ServerSelection {
+            configured_address: configured_address.clone(),
+            previous_address: self.previous_server_address.clone(),
+            recovering: state.is_recoverable(),
+        };
+        self.selected_server_address = self.hooks.select_server(&selection);
+        if let Some(address) = &self.selected_server_address {
+            if !address.starts_with("http://") && !address.starts_with("https://") {
+                return Err(Box::new(IllegalStateException::new(&format!(
+                    "Invalid server address '{}' chosen by the server selector: must start with http:// or https://",
+                    address
+                ))));
+            }
+            self.make_log(
+                Level::INFO,
+                &format!("Server selector chose address: {}", address),
+            );
+        }
+        // Check if the server address is configured.
+        let Some(server_address) = self.selected_server_address.clone().or(configured_address)
+        else {
+            return Err(Box::new(IllegalStateException::new(
+                "No server address was configured.",
+            )));
+        };
+        self.previous_server_address = Some(server_address);
+        // Keep a copy of every frame sent in the flight recorder, if enabled.
+        let recorder = self.flight_recorder.clone();
+        // Splits a connection into a write and a read stream.
+        // When the client last wrote to the connection, to send reverse heartbeats when silent.
+        let last_sent_at = Arc::new(Mutex::new(Instant::now()));
+        let split_connection = |connection: ServerConnection, connection_url: &str| {
+            if let Some(recorder) = &recorder {
+                recorder.record(RecordedEventKind::StateChange(format!(
+                    "Connected to {}",
+                    connection_url
+                )));
+            }
+            let (write_stream, read_stream) = connection.split();
+            let sent_frames_recorder = recorder.clone();
+            let sent_at = last_sent_at.clone();
+            let write_stream = write_stream.with(move |message: Message| {
+                *sent_at.lock().unwrap_or_else(|err| err.into_inner()) = Instant::now();
+                if let (Some(recorder), Message::Text(text)) = (&sent_frames_recorder, &message) {
+                    recorder.record(RecordedEventKind::Sent(redact_credentials(text)));
+                }
+                futures_util::future::ready(Ok::<_, tokio_tungstenite::tungstenite::Error>(message))
+            });
+            (write_stream, read_stream)
+        };
+        // The transports still to try if the connection fails before the session is confirmed.
+        let mut transports = self.get_transport_chain();
+        let (connection, connection_url, mut connection_type) =
+            self.open_first_connection(&mut transports).await?;
+        let (mut write_stream, mut read_stream) = split_connection(connection, &connection_url);
+        let mut fallback_deadline = self.get_fallback_deadline(&connection_type, &transports);
+
+        //
+        // Initiate communication with the server by sending a 'wsok' message.
+        //
+        write_stream.send(Message::Text("wsok".into())).await?;
+
+        //
+        // Start reading and processing messages from the server.
+        //
+        let mut is_connected = false;
+        // Why the connection failed before the session was confirmed, to fall back to the next
+        // transport.
+        let mut transport_failure: Option<String> = None;
+        // When the stream connection was opened, to measure the delay reported by SYNC.
+        let mut stream_opened_at: Option<Instant> = None;
+        // A session created on a previous connection is recovered rather than created again.
+        let recovering = state.is_recoverable();
+        // Whether the session is being bound to a new stream connection, after LOOP.
+        let mut rebinding = false;
+        // When to bind the session to a new stream connection, after the delay requested by LOOP.
+        let mut rebind_deadline: Option<Instant> = None;
+        let mut request_id: usize = state.request_id;
+        // Updated in place, as table indexes are never reused, even after a failed connection.
+        let subscription_id = &mut state.subscription_id;
+        // The values of the items and the progressive count are updated in place, so that they
+        // survive a connection failing before the end of the session, for the next recovery
+        // attempt not to receive again what was already delivered.
+        let subscription_item_updates = &mut state.item_updates;
+        // Requests awaiting REQOK (or SUBOK/SUBCMD), by request ID, to resolve their futures and
+        // to route and explain REQERR notifications.
+        let mut pending_requests = PendingRequests::default();
+        // Unsubscriptions awaiting their UNSUB notification, by subscription ID.
+        let mut unsubscription_responders: HashMap<usize, Vec<RequestResponder>> = HashMap::new();
+        // Messages waiting for a session or for their outcome.
+        let mut pending_messages = PendingMessages::new(self.in_flight_messages.clone());
+        // Control requests waiting for the batching window to expire.
+        let mut control_batch = ControlBatch::new(Duration::from_millis(
+            self.connection_options.get_control_batching_window(),
+        ));
+        // Why the session ended, once it has.
+        let mut session_end: Option<StatusChangeCause> = None;
+        // Longer messages are discarded, possibly resubscribing the subscription they refer to.
+        let max_message_size = self.connection_options.get_max_message_size();
+        let resubscribe_on_oversized_message = self
+            .connection_options
+            .is_resubscribe_on_oversized_message();
+        // Progressive count of the data notifications of the session.
+        let prog = &mut state.prog;
+        self.diagnostics.set_prog(prog.prog());
+        // Subscriptions awaiting SUBOK or REQERR, with the deadline of the answer and the number
+        // of retries already made, in order of deadline.
+        let subscribe_timeout =
+            Duration::from_millis(self.connection_options.get_subscribe_timeout());
+        let subscribe_retries = self.connection_options.get_subscribe_retries();
+        let subscribe_deadline =
+            |now: Instant| (!subscribe_timeout.is_zero()).then(|| now + subscribe_timeout);
+        let mut subscribe_deadlines: VecDeque<(Instant, usize, u32)> = VecDeque::new();
+        // Updates of a batch of frames waiting to be dispatched in order of priority, with the
+        // priority and the ID of their subscription.
+        let mut deferred_updates: Vec<(i32, usize, ItemUpdate)> = Vec::new();
+        // Transient storage of the updates of a read, released once they are dispatched.
+        let mut arena = Bump::new();
+        // Journaled messages already sent in this session, awaiting their outcome.
+        let message_journal = self.message_journal.clone();
+        let snapshot_refresh = self.snapshot_refresh.clone();
+        let mut journal_sent: HashSet<u64> = HashSet::new();
+        let slow_start_threshold =
+            Duration::from_millis(self.connection_options.get_slow_start_threshold());
+        loop {
+            let next_message_deadline = pending_messages
+                .next_deadline()
+                .into_iter()
+                .chain(self.message_queue.next_deadline())
+                .min();
+            let control_batch_deadline = control_batch.deadline();
+            let next_subscribe_deadline = subscribe_deadlines
+                .front()
+                .map(|(deadline, _, _)| *deadline);
+            let next_slow_start_deadline = self
+                .subscriptions
+                .iter()
+                .filter(|_| !slow_start_threshold.is_zero())
+                .filter_map(|s| s.get_slow_start_deadline(slow_start_threshold))
+                .min()
+                .map(Instant::from_std);
+            // A reverse heartbeat is due once the client has been silent for the interval.
+            let reverse_heartbeat_interval =
+                self.connection_options.get_reverse_heartbeat_interval();
+            let next_heartbeat_deadline =
+                (is_connected && reverse_heartbeat_interval > 0).then(|| {
+                    *last_sent_at.lock().unwrap_or_else(|err| err.into_inner())
+                        + Duration::from_millis(reverse_heartbeat_interval)
+                });
+            tokio::select! {
+                message = read_stream.next() => {
+                    // The frames already received are processed in a batch, without going through
+                    // the select again, up to a limit so that the other events are not starved.
+                    let mut message = message;
+                    // Updates are only deferred when some subscription has a different priority.
+                    let prioritized = self.subscriptions.iter().any(|s| s.get_priority() != 0);
+                    for _ in 0..Self::MAX_READ_BATCH {
+                        match message {
+                            Some(Ok(Message::Text(text))) => {
+                                self.health_probe.record_activity();
+                                if let Some(recorder) = &recorder {
+                                    recorder.record(RecordedEventKind::Received(text.to_string()));
+                                }
+                                // Messages could include multiple notifications separated by CRLF, each
+                                // parsed into a ServerMessage and processed separately.
+                                for line in protocol::split_frame(&text) {
+                                    let notification = match ServerMessage::parse_in(line, &arena) {
+                                        Ok(notification) => notification,
+                                        // There is no session without a valid CONOK.
+                                        Err(err) if protocol::notification_name(line).eq_ignore_ascii_case("CONOK") => {
+                                            return Err(Box::new(err));
+                                        },
+                                        Err(err) => {
+                                            self.make_log( Level::WARN, &format!("Ignoring notification from server: {}", err) );
+                                            continue;
+                                        },
+                                    };
+                                    // Other notifications may change the state of the subscriptions,
+                                    // so the updates received before them are dispatched first.
+                                    if !matches!(notification, ServerMessage::Update(_)) && !deferred_updates.is_empty() {
+                                        self.dispatch_deferred_updates(&mut deferred_updates);
+                                    }
+                                    if notification.is_data_notification() {
+                                        if !prog.on_data_notification() {
+                                            self.diagnostics.add_duplicate_notifications(1);
+                                            self.make_log( Level::DEBUG, &format!("Discarded duplicate notification: {}", line) );
+                                            continue;
+                                        }
+                                        self.diagnostics.set_prog(prog.prog());
+                                    }
+                                    if let Some(limit) = max_message_size
+                                        && line.len() > limit
+                                    {
+                                        let oversized_id = notification.subscription_id();
+                                        let resubscribed_index = oversized_id
+                                            .filter(|_| resubscribe_on_oversized_message)
+                                            .and_then(|id| self.subscriptions.iter().position(|s| s.id == id));
+                                        if let Some(index) = resubscribed_index {
+                                            // Resubscribe under a new ID, to get a fresh snapshot.
+                                            for request in self.get_resubscription_requests(index, &mut request_id, subscription_id, &mut pending_requests)? {
+                                                if let Some(frame) = control_batch.push(request) {
+                                                    write_stream.send(Message::Text(frame.into())).await?;
+                                                }
+                                            }
+                                            if let Some(deadline) = subscribe_deadline(Instant::now()) {
+                                                subscribe_deadlines.push_back((deadline, *subscription_id, 0));
+                                            }
+                                        }
+                                        let error = OversizedMessageError::new(line.len(), limit, oversized_id, resubscribed_index.is_some());
+                                        self.make_log( Level::ERROR, &error.to_string() );
+                                        self.dispatch_to_listeners(|listener| listener.on_oversized_message(&error));
+                                        continue;
+                                    }
+                                    #[cfg(feature = "protocol-checks")]
+                                    if let Err(violation) = state.checker.check(&notification) {
+                                        self.diagnostics.add_protocol_violation();
+                                        if let Some(recorder) = &recorder {
+                                            recorder.record(RecordedEventKind::StateChange(format!("Protocol violation: {}", violation)));
+                                        }
+                                        self.make_log( Level::ERROR, &format!("Protocol violation: {}", violation) );
+                                    }
+                                    // Attribute the bytes of subscription notifications to their subscription.
+                                    if let Some(subscription_id) = notification.subscription_id()
+                                        && let Some(subscription) = self.subscriptions.iter().find(|s| s.id == subscription_id)
+                                    {
+                                        subscription.record_received_bytes(line.len() + 2);
+                                    }
+                                    match notification {
+                                        //
+                                        // Errors from server.
+                                        //
+                                        ServerMessage::Conerr { code, message } => {
+                                            self.make_log( Level::ERROR, &format!("Received connection error from Lightstreamer server: {}", line) );
+                                            self.dispatch_to_listeners(|listener| listener.on_server_error(code, &message));
+                                            session_end = Some(StatusChangeCause::ConnectionRefused { code, message: message.into_owned() });
+                                            break;
+                                        },
+                                        ServerMessage::End { code, message } => {
+                                            self.make_log( Level::WARN, &format!("Session closed by Lightstreamer server: {}", line) );
+                                            self.dispatch_to_listeners(|listener| listener.on_server_error(code, &message));
+                                            self.notify_data_gap(DataGapCause::SessionEnded, None, Some(SystemTime::now()));
+                                            session_end = Some(StatusChangeCause::SessionEnded { code, message: message.into_owned() });
+                                            break;
+                                        },
+                                        ServerMessage::Error { code, message } => {
+                                            self.make_log( Level::ERROR, &format!("Received error from Lightstreamer server: {}", line) );
+                                            return Err(Box::new(ServerException::new(code, &message)));
+                                        },
+                                        ServerMessage::Reqerr(RequestError { request_id: failed_request_id, code, message }) => {
+                                            match pending_requests.refuse(failed_request_id, ServerException::new(code, &message)) {
+                                                Some((request, error)) => {
+                                                    match request {
+                                                        PendingRequest::Subscription { subscription_id, .. } => {
+                                                            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == subscription_id) {
+                                                                subscription.on_subscription_error(error.clone());
+                                                            }
+                                                        },
+                                                        PendingRequest::Unsubscription { subscription_id } => {
+                                                            for responder in unsubscription_responders.remove(&subscription_id).unwrap_or_default() {
+                                                                let _ = responder.send(Err(error.clone()));
+                                                            }
+                                                        },
+                                                        PendingRequest::Message { sequence, prog: Some(prog) } => {
+                                                            if let Some(message_request) = pending_messages.complete(&sequence, prog)
+                                                                && let Some(listener) = &message_request.listener
+                                                            {
+                                                                listener.on_error(&message_request.message);
+                                                            }
+                                                        },
+                                                        PendingRequest::Message { prog: None, .. } => {},
+                                                    }
+                                                    self.make_log( Level::ERROR, &format!("Request {} refused by server: {}", failed_request_id, error) );
+                                                },
+                                                None => {
+                                                    self.make_log( Level::ERROR, &format!("Received request error from Lightstreamer server: {}", line) );
+                                                },
+                                            }
+                                        },
+                                        //
+                                        // Session created successfully.
+                                        //
+                                        ServerMessage::Conok { session_id: created_session_id, request_limit, keepalive, control_link } => {
+                                            is_connected = true;
+                                            stream_opened_at = Some(Instant::now());
+                                            // The connection is open, another client can reconnect.
+                                            self.connect_permit = None;
+                                            let mut session_info = SessionInfo::new(&created_session_id, request_limit, keepalive, control_link.as_deref());
+                                            let requested_keepalive = self.connection_options.get_keepalive_interval();
+                                            if requested_keepalive > 0 {
+                                                session_info.set_requested_keepalive(requested_keepalive);
+                                                if session_info.get_keepalive() != requested_keepalive {
+                                                    self.make_log( Level::WARN, &format!("Keepalive interval of {} ms requested, but the server granted {} ms", requested_keepalive, session_info.get_keepalive()) );
+                                                }
+                                            }
+                                            let created_session_id = created_session_id.into_owned();
+                                            let request_limit = usize::try_from(session_info.get_request_limit()).unwrap_or(usize::MAX);
+                                            control_batch.set_request_limit(request_limit);
+                                            self.session_info.send_replace(Some(session_info));
+                                            if rebinding {
+                                                // The session goes on, on a new stream connection.
+                                                rebinding = false;
+                                                self.make_log( Level::DEBUG, &format!("Session {} rebound", created_session_id) );
+                                            } else if recovering {
+                                                self.make_log( Level::INFO, &format!("Session {} recovered", created_session_id) );
+                                                if let Some(recorder) = &recorder {
+                                                    recorder.record(RecordedEventKind::StateChange(format!("Session {} recovered", created_session_id)));
+                                                }
+                                                self.set_status(
+                                                    ClientStatus::Connected(connection_type.clone()),
+                                                    StatusChangeCause::SessionRecovered { session_id: created_session_id },
+                                                );
+                                            } else {
+                                                state.session_id = Some(created_session_id.clone());
+                                                // The subscriptions of a session that could not be recovered are subscribed again.
+                                                if let Some(previous_session_id) = &state.previous_session_id
+                                                    && !self.subscriptions.is_empty()
+                                                {
+                                                    let replacement = SessionReplacement::new(previous_session_id, &created_session_id, self.subscriptions.len());
+                                                    self.make_log( Level::INFO, &replacement.to_string() );
+                                                    self.dispatch_to_listeners(|listener| listener.on_session_replaced(&replacement));
+                                                }
+                                                self.make_log( Level::DEBUG, &format!("Session creation confirmed by server: {}", line) );
+                                                self.make_log( Level::DEBUG, &format!("Session created with ID: {:?}", created_session_id) );
+                                                if let Some(recorder) = &recorder {
+                                                    recorder.record(RecordedEventKind::StateChange(format!("Session {} created", created_session_id)));
+                                                }
+                                                self.set_status(
+                                                    ClientStatus::Connected(connection_type.clone()),
+                                                    StatusChangeCause::SessionCreated { session_id: created_session_id },
+                                                );
+                                                // A new session starts with no subscriptions active.
+                                                for subscription in self.subscriptions.iter_mut() {
+                                                    subscription.id = 0;
+                                                }
+                                            }
+                                            //
+                                            // Subscribe to the desired items, except the ones still active on a
+                                            // recovered session, in as few frames as possible.
+                                            //
+                                            let mut subscription_params = Vec::new();
+                                            for subscription in self.subscriptions.iter_mut().filter(|subscription| subscription.id == 0) {
+                                                //
                                                 // Gather all the necessary subscription parameters.
                                                 //
-                                                subscription_id += 1;
+                                                *subscription_id += 1;
                                                 request_id += 1;
-                                                subscription.id = subscription_id;
-                                                subscription.id_sender.try_send(subscription_id)?;
+                                                subscription.id = *subscription_id;
+                                                // On re-subscription in a new session, nobody may be waiting for the ID anymore.
+                                                let _ = subscription.id_sender.try_send(*subscription_id);
+                                                subscription.on_subscription_request();
+                                                pending_requests.insert_awaited(request_id, PendingRequest::subscription(subscription, *subscription_id), subscription.take_request_responder());
+                                                if let Some(deadline) = subscribe_deadline(Instant::now()) {
+                                                    subscribe_deadlines.push_back((deadline, *subscription_id, 0));
+                                                }
 
                                                 let encoded_params = match Self::get_subscription_params(subscription, request_id)
                                                 {
@@ -463,364 +2307,477 @@ impl LightstreamerClient {
                                                         return Err(err);
                                                     },
                                                 };
-
-                                                write_stream
-                                                    .send(Message::Text(format!("control\r\n{}", encoded_params).into()))
-                                                    .await?;
-                                                debug!("Sent subscription request: '{}'", encoded_params);
+                                                let encoded_params = intercept_request(&self.request_interceptors, "control", encoded_params)?;
+                                                debug!("Sending subscription request: '{}'", encoded_params);
+                                                subscription_params.push(encoded_params);
                                             }
-                                        } else {
-                                            return Err(Box::new(std::io::Error::new(
-                                                std::io::ErrorKind::InvalidData,
-                                                "Session ID not found in 'conok' message from server",
-                                            )));
-                                        }
-                                    },
-                                    //
-                                    // Notifications from server.
-                                    //
-                                    "conf" | "cons" | "clientip" | "servname" | "prog" | "sync" | "eos" => {
-                                        self.make_log( Level::INFO, &format!("Received notification from server: {}", clean_text) );
-                                        // Don't do anything with these notifications for now.
-                                    },
-                                    "probe" => {
-                                        self.make_log( Level::DEBUG, &format!("Received probe message from server: {}", clean_text ) );
-                                    },
-                                    "reqok" => {
-                                        self.make_log( Level::DEBUG, &format!("Received reqok message from server: '{}'", clean_text ) );
-                                    },
-                                    //
-                                    // Subscription confirmation from server.
-                                    //
-                                    "subok" => {
-                                        self.make_log( Level::INFO, &format!("Subscription confirmed by server: '{}'", clean_text) );
-                                    },
-                                    //
-                                    // Usubscription confirmation from server.
-                                    //
-                                    "unsub" => {
-                                        self.make_log( Level::INFO, &format!("Unsubscription confirmed by server: '{}'", clean_text) );
-                                    },
-                                    //
-                                    // Data updates from server.
-                                    //
-                                    "u" => {
-                                        // Parse arguments from the received message.
-                                        let arguments = parse_arguments(&clean_text);
+                                            for frame in pack_control_frames(subscription_params, request_limit) {
+                                                write_stream.send(Message::Text(frame.into())).await?;
+                                            }
+                                            //
+                                            // Send the messages enqueued while waiting for the session.
+                                            //
+                                            for message_request in self.message_queue.take() {
+                                                if message_request.is_expired(Instant::now()) {
+                                                    message_request.abort(false);
+                                                    continue;
+                                                }
+                                                request_id += 1;
+                                                let encoded_params = Self::write_message_request(&mut write_stream, &mut pending_messages, &mut pending_requests, message_request, request_id, &self.request_interceptors).await?;
+                                                debug!("Sent message request: '{}'", encoded_params);
+                                            }
+                                            //
+                                            // Send the journaled messages still without outcome.
+                                            //
+                                            if let Some(journal) = &message_journal {
+                                                for encoded_params in Self::write_journaled_messages(&mut write_stream, &mut pending_messages, &mut pending_requests, journal, &mut journal_sent, &mut request_id, &self.request_interceptors).await? {
+                                                    self.make_log( Level::INFO, &format!("Sent journaled message request: '{}'", encoded_params) );
+                                                }
+                                            }
+                                        },
                                         //
-                                        // Extract the subscription from the first argument.
+                                        // Notifications from server.
                                         //
-                                        let subscription_index = arguments.get(1).unwrap_or(&"").parse::<usize>().unwrap_or(0);
-                                        let subscription = match get_subscription_by_id(self.get_subscriptions(), subscription_index) {
-                                            Some(subscription) => subscription,
-                                            None => {
-                                                self.make_log( Level::WARN, &format!("Subscription not found for index: {}", subscription_index) );
-                                                continue;
-
+                                        ServerMessage::Servname(server_name) => {
+                                            self.make_log( Level::INFO, &format!("Received notification from server: {}", line) );
+                                            self.session_info.send_if_modified(|info| match info {
+                                                Some(info) => { info.set_server_name(&server_name); true },
+                                                None => false,
+                                            });
+                                        },
+                                        ServerMessage::Clientip(client_ip) => {
+                                            self.make_log( Level::INFO, &format!("Received notification from server: {}", line) );
+                                            self.session_info.send_if_modified(|info| match info {
+                                                Some(info) => { info.set_client_ip(&client_ip); true },
+                                                None => false,
+                                            });
+                                        },
+                                        ServerMessage::Prog(server_prog) => {
+                                            match prog.on_prog(server_prog) {
+                                                ProgCheck::InSync => {
+                                                    self.make_log( Level::DEBUG, &format!("Progressive in sync with server: {}", server_prog) );
+                                                },
+                                                ProgCheck::Duplicates(count) => {
+                                                    self.make_log( Level::WARN, &format!("Server will resend {} notifications already received, which will be discarded", count) );
+                                                },
+                                                ProgCheck::Lost(count) => {
+                                                    self.diagnostics.add_lost_notifications(count);
+                                                    self.notify_data_gap(DataGapCause::LostNotifications, Some(count), state.dropped_at);
+                                                    self.make_log( Level::ERROR, &format!("{} notifications were lost: received {}, server sent {}", count, server_prog - count, server_prog) );
+                                                },
                                             }
-                                        };
+                                            self.diagnostics.set_prog(prog.prog());
+                                        },
+                                        ServerMessage::Sync(elapsed) => {
+                                            if let Some(opened_at) = stream_opened_at {
+                                                let local_elapsed = u64::try_from(opened_at.elapsed().as_millis()).unwrap_or(u64::MAX);
+                                                self.diagnostics.set_sync_delay(local_elapsed.saturating_sub(elapsed.saturating_mul(1000)));
+                                            }
+                                            self.make_log( Level::DEBUG, &format!("Received sync message from server: {}", line) );
+                                        },
+                                        ServerMessage::Conf(_) | ServerMessage::Cons(_) => {
+                                            self.make_log( Level::INFO, &format!("Received notification from server: {}", line) );
+                                            // Don't do anything with these notifications for now.
+                                        },
+                                        ServerMessage::Probe | ServerMessage::Noop => {
+                                            self.make_log( Level::DEBUG, &format!("Received probe message from server: {}", line ) );
+                                        },
                                         //
-                                        // Extract the item from the second argument.
+                                        // The stream connection is closed, or the poll is complete: bind the
+                                        // session to a new one, after the delay requested by the Server. When
+                                        // polling, each poll returns with LOOP, so this is the polling cycle.
                                         //
-                                        let item_index = arguments.get(2).unwrap_or(&"").parse::<usize>().unwrap_or(0);
-                                        let item = match subscription.get_items() {
-                                            Some(items) => items.get(item_index-1),
-                                            None => {
-                                                self.make_log( Level::WARN, &format!("No items found in subscription: {:?}", subscription) );
-                                                continue;
+                                        ServerMessage::Loop(delay) => {
+                                            if state.session_id.is_none() {
+                                                return Err(Box::new(std::io::Error::new(
+                                                    std::io::ErrorKind::InvalidData,
+                                                    "'loop' message received with no session",
+                                                )));
                                             }
-                                        };
+                                            self.make_log( Level::DEBUG, &format!("Stream connection closed by server, rebinding in {} ms", delay) );
+                                            rebind_deadline = Some(Instant::now() + Duration::from_millis(delay));
+                                        },
+                                        ServerMessage::Reqok(accepted_request_id) => {
+                                            self.make_log( Level::DEBUG, &format!("Received reqok message from server: '{}'", line ) );
+                                            if let Some(accepted_request_id) = accepted_request_id {
+                                                pending_requests.accept(accepted_request_id);
+                                            }
+                                        },
                                         //
-                                        // Determine if the update is a snapshot or real-time update based on the subscription parameters.
+                                        // Message outcomes from server.
                                         //
-                                        let is_snapshot = match subscription.get_requested_snapshot() {
-                                            Some(ls_snapshot) => {
-                                                match ls_snapshot {
-                                                    Snapshot::No => false,
-                                                    Snapshot::Yes => {
-                                                        match subscription.get_mode() {
-                                                            SubscriptionMode::Merge => {
-                                                                if arguments.len() == 4 && arguments[3] == "$" {
-                                                                    // EOS notification received
-                                                                    true
-                                                                } else {
-                                                                    // If item doesn't exist in item_updates yet, the first update
-                                                                    // is always a snapshot.
-                                                                    if let Some(item_updates) = subscription_item_updates.get(&(subscription_index)) {
-                                                                        if item_updates.get(&(item_index)).is_some() {
-                                                                            // Item update already exists in item_updates, so it's not a snapshot.
-                                                                            false
-                                                                        } else {
-                                                                            // Item update doesn't exist in item_updates, so the first update is always a snapshot.
-                                                                            true
-                                                                        }
-                                                                    } else {
-                                                                        // Item updates not found for subscription, so the first update is always a snapshot.
+                                        ServerMessage::Msgdone { sequence, prog: message_prog, response } => {
+                                            let sequence = Self::get_message_sequence(&sequence);
+                                            let message_prog = usize::try_from(message_prog).unwrap_or(usize::MAX);
+                                            let response = Some(response.as_ref()).filter(|response| !response.is_empty());
+                                            match pending_messages.complete(sequence, message_prog) {
+                                                Some(message_request) => {
+                                                    self.make_log( Level::DEBUG, &format!("Message processed by server: '{}'", line) );
+                                                    if let Some(listener) = &message_request.listener {
+                                                        listener.on_processed(&message_request.message, response);
+                                                    }
+                                                },
+                                                None => {
+                                                    self.make_log( Level::DEBUG, &format!("Ignoring outcome of message no longer pending: '{}'", line) );
+                                                },
+                                            }
+                                        },
+                                        ServerMessage::Msgfail { sequence, prog: message_prog, code, message: error } => {
+                                            let sequence = Self::get_message_sequence(&sequence);
+                                            let message_prog = usize::try_from(message_prog).unwrap_or(usize::MAX);
+                                            let failed = pending_messages.fail(sequence, message_prog, code, &error);
+                                            if failed.is_empty() {
+                                                self.make_log( Level::DEBUG, &format!("Ignoring outcome of message no longer pending: '{}'", line) );
+                                            }
+                                            for message_request in failed {
+                                                self.make_log( Level::WARN, &format!("Message refused by server: '{}'", line) );
+                                                message_request.fail(code, &error);
+                                            }
+                                        },
+                                        //
+                                        // Subscription confirmation from server.
+                                        //
+                                        ServerMessage::Subok(confirmation) => {
+                                            self.make_log( Level::INFO, &format!("Subscription confirmed by server: '{}'", line) );
+                                            pending_requests.accept_subscription(confirmation.subscription_id);
+                                            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == confirmation.subscription_id) {
+                                                subscription.on_subscription(confirmation);
+                                            }
+                                        },
+                                        //
+                                        // End of snapshot for an item.
+                                        //
+                                        ServerMessage::Eos(ItemNotification { subscription_id: eos_subscription_id, item_pos }) => {
+                                            self.make_log( Level::DEBUG, &format!("Received end of snapshot from server: '{}'", line) );
+                                            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == eos_subscription_id) {
+                                                subscription.on_end_of_snapshot(item_pos);
+                                            }
+                                        },
+                                        //
+                                        // Snapshot cleared for an item.
+                                        //
+                                        ServerMessage::Cs(ItemNotification { subscription_id: cs_subscription_id, item_pos }) => {
+                                            self.make_log( Level::DEBUG, &format!("Received clear snapshot from server: '{}'", line) );
+                                            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == cs_subscription_id) {
+                                                subscription.on_clear_snapshot(item_pos);
+                                            }
+                                        },
+                                        //
+                                        // Updates dropped by the Server for an item.
+                                        //
+                                        ServerMessage::Ov(overflow) => {
+                                            self.make_log( Level::WARN, &format!("Received overflow notification from server: '{}'", line) );
+                                            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == overflow.subscription_id) {
+                                                let gap = DataGap::new(DataGapCause::Overflow, Some(overflow.item_pos), Some(overflow.lost_updates), None);
+                                                subscription.on_data_gap(&gap);
+                                            }
+                                        },
+                                        //
+                                        // Usubscription confirmation from server.
+                                        //
+                                        ServerMessage::Unsub(unsubscribed_id) => {
+                                            self.make_log( Level::INFO, &format!("Unsubscription confirmed by server: '{}'", line) );
+                                            for responder in unsubscription_responders.remove(&unsubscribed_id).unwrap_or_default() {
+                                                let _ = responder.send(Ok(()));
+                                            }
+                                        },
+                                        //
+                                        // Data updates from server.
+                                        //
+                                        ServerMessage::Update(update) => {
+                                            if let Some(suppressed) = self.update_log_sampler.sample(Instant::now()) {
+                                                let log = match suppressed {
+                                                    0 => format!("Received update from server: {}", line),
+                                                    suppressed => format!("Received update from server: {} ({} similar lines suppressed)", line, suppressed),
+                                                };
+                                                self.make_log( Level::DEBUG, &log );
+                                            }
+                                            let subscription_index = update.subscription_id;
+                                            let subscription = match get_subscription_by_id(self.get_subscriptions(), subscription_index) {
+                                                Some(subscription) => subscription,
+                                                // Late updates for tables unsubscribed or superseded by a resubscription.
+                                                None if is_retired_table(*subscription_id, subscription_index) => {
+                                                    self.make_log( Level::DEBUG, &format!("Discarding update for retired table: {}", subscription_index) );
+                                                    continue;
+                                                },
+                                                None => {
+                                                    self.make_log( Level::WARN, &format!("Subscription not found for index: {}", subscription_index) );
+                                                    continue;
+                                                }
+                                            };
+                                            let item_index = update.item_pos;
+                                            let item = subscription.get_item_name(item_index).map(|name| name.to_string());
+                                            //
+                                            // Determine if the update is a snapshot or real-time update based on the subscription parameters.
+                                            //
+                                            let is_snapshot = match subscription.get_requested_snapshot() {
+                                                Some(ls_snapshot) => {
+                                                    match ls_snapshot {
+                                                        Snapshot::No => false,
+                                                        Snapshot::Yes => {
+                                                            match subscription.get_mode() {
+                                                                SubscriptionMode::Merge => {
+                                                                    if matches!(update.values.as_slice(), [FieldValue::Empty]) {
+                                                                        // EOS notification received
                                                                         true
+                                                                    } else {
+                                                                        // The first update of each item is the snapshot.
+                                                                        !subscription.is_snapshot_complete(item_index)
                                                                     }
-                                                                }
-                                                            },
-                                                            SubscriptionMode::Distinct | SubscriptionMode::Command => {
-                                                                !subscription.is_subscribed()
-                                                            },
-                                                            _ => false,
-                                                        }
-                                                    },
-                                                    _ => false,
-                                                }
-                                            },
-                                            None => false,
-                                        };
+                                                                },
+                                                                SubscriptionMode::Distinct | SubscriptionMode::Command => {
+                                                                    // Snapshot events precede the EOS notification for the item.
+                                                                    !subscription.is_snapshot_complete(item_index)
+                                                                },
+                                                                _ => false,
+                                                            }
+                                                        },
+                                                        _ => false,
+                                                    }
+                                                },
+                                                None => false,
+                                            };
 
-                                        // Extract the field values from the third argument.
-                                        let field_values: Vec<&str> = arguments.get(3).unwrap_or(&"").split('|').collect();
+                                            //
+                                            // Get the values carried by the update by field position, `None` for the unchanged fields.
+                                            //
+                                            let field_names: &[String] = subscription.get_fields().map(Vec::as_slice).unwrap_or_default();
+                                            let mut field_values: FieldList<Option<String>> = FieldList::from_elem(None, field_names.len());
 
-                                        //
-                                        // Get fields from subscription and create a HashMap of field names and values.
-                                        //
-                                        let subscription_fields = subscription.get_fields();
-                                        let mut field_map: HashMap<String, Option<String>> = subscription_fields
-                                            .map(|fields| fields.iter().map(|field_name| (field_name.to_string(), None)).collect())
-                                            .unwrap_or_default();
-
-                                        let mut field_index = 0;
-                                        for value in field_values {
-                                            match value {
-                                                "" => {
-                                                    // An empty value means the field is unchanged compared to the previous update of the same field.
-                                                    if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index)) {
-                                                        field_map.insert(field_name.to_string(), None);
+                                            for (value, field_value) in update.values.into_iter().zip(field_values.iter_mut()) {
+                                                match value {
+                                                    // The field is unchanged compared to the previous update of the same field.
+                                                    FieldValue::Unchanged => {}
+                                                    FieldValue::Null | FieldValue::Empty => {
+                                                        *field_value = Some("".to_string());
                                                     }
-                                                    field_index += 1;
-                                                }
-                                                "#" | "$" => {
-                                                    // A value corresponding to a hash sign "#" or dollar sign "$" means the field is null or empty.
-                                                    if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index)) {
-                                                        field_map.insert(field_name.to_string(), Some("".to_string()));
+                                                    FieldValue::Value(value) => {
+                                                        *field_value = Some(value.into_owned());
                                                     }
-                                                    field_index += 1;
-                                                }
-                                                value if value.starts_with('^') => {
-                                                    let command = value.chars().nth(1).unwrap_or(' ');
-                                                    match command {
-                                                        '0'..='9' => {
-                                                            let count = value[1..].parse().unwrap_or(0);
-                                                            for i in 0..count {
-                                                                if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index + i)) {
-                                                                    field_map.insert(field_name.to_string(), None);
-                                                                }
-                                                            }
-                                                            field_index += count;
-                                                        }
-                                                        'P' | 'T' => {
-                                                            let diff_value = serde_urlencoded::from_str(&value[2..]).unwrap_or_else(|_| value[2..].to_string());
-                                                            if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index)) {
-                                                                if let Some(prev_value) = field_map.get(field_name).and_then(|v| v.as_ref()) {
-                                                                    let new_value = match command {
-                                                                        'P' => {
-                                                                            // Apply JSON Patch
-                                                                            let patch: serde_json::Value = serde_json::from_str(&diff_value).unwrap_or(serde_json::Value::Null);
-                                                                            let mut prev_json: serde_json::Value = serde_json::from_str(prev_value).unwrap_or(serde_json::Value::Null);
-                                                                            let patch_operations: Vec<json_patch::PatchOperation> = serde_json::from_value(patch).unwrap_or_default();
-                                                                            let _ = json_patch::patch(&mut prev_json, &patch_operations);
-                                                                            prev_json.to_string()
-                                                                        }
-                                                                        'T' => {
-                                                                            // Apply TLCP-diff
-                                                                            //tlcp_diff::apply_diff(prev_value, &diff_value).unwrap_or_else(|_| prev_value.to_string())
-                                                                            unimplemented!("Implement TLCP-diff");
-                                                                        }
-                                                                        _ => unreachable!(),
-                                                                    };
-                                                                    field_map.insert(field_name.to_string(), Some(new_value.to_string()));
-                                                                }
-                                                            }
-                                                            field_index += 1;
-                                                        }
-                                                        _ => {
-                                                            let decoded_value = serde_urlencoded::from_str(value).unwrap_or_else(|_| value.to_string());
-                                                            if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index)) {
-                                                                field_map.insert(field_name.to_string(), Some(decoded_value));
-                                                            }
-                                                            field_index += 1;
+                                                    FieldValue::JsonPatch(patch) => {
+                                                        if let Some(prev_value) = field_value.as_ref() {
+                                                            *field_value = Some(Self::apply_json_patch(prev_value, &patch));
                                                         }
                                                     }
-                                                }
-                                                value if value.starts_with('{') => {
-                                                    // in this case it is a json payload that we will let the consumer handle. In this case, it is important
-                                                    // to preserve casing for parsing.
-                                                    let original_json = parse_arguments(submessage).get(3).unwrap_or(&"").split('|').collect::<Vec<&str>>();
-                                                    let mut payload = "";
-                                                    for json in original_json.iter()
-                                                    {
-                                                        if json.is_empty() || *json == "#"
-                                                        {
-                                                            continue;
+                                                    FieldValue::TlcpDiff(_) => {
+                                                        if field_value.is_some() {
+                                                            // Apply TLCP-diff
+                                                            //tlcp_diff::apply_diff(prev_value, &diff_value).unwrap_or_else(|_| prev_value.to_string())
+                                                            unimplemented!("Implement TLCP-diff");
                                                         }
-
-                                                        payload = json;
-                                                    }
-
-                                                    if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index)) {
-                                                        field_map.insert(field_name.to_string(), Some(payload.to_string()));
                                                     }
-                                                    field_index += 1;
-                                                }
-                                                _ => {
-                                                    let decoded_value = serde_urlencoded::from_str(value).unwrap_or_else(|_| value.to_string());
-                                                    if let Some(field_name) = subscription_fields.and_then(|fields| fields.get(field_index)) {
-                                                        field_map.insert(field_name.to_string(), Some(decoded_value));
-                                                    }
-                                                    field_index += 1;
                                                 }
                                             }
-                                        }
 
-                                        // Store only item_update's changed fields.
-                                        let changed_fields: HashMap<String, String> = field_map.iter()
-                                            .filter_map(|(k, v)| v.as_ref().map(|v| (k.clone(), v.clone())))
-                                            .collect();
+                                            // Store only item_update's changed fields.
+                                            let changed_fields: FieldList<(usize, &String)> = field_values.iter()
+                                                .enumerate()
+                                                .filter_map(|(field_pos, value)| Some((field_pos, value.as_ref()?)))
+                                                .collect();
+                                            // The maps held by the ItemUpdate, keyed by field name.
+                                            let field_map = || -> HashMap<String, Option<String>> {
+                                                field_names.iter().cloned().zip(field_values.iter().cloned()).collect()
+                                            };
+                                            let changed_field_map = || -> HashMap<String, String> {
+                                                changed_fields.iter()
+                                                    .map(|(field_pos, value)| (field_names[*field_pos].clone(), (*value).clone()))
+                                                    .collect()
+                                            };
 
-                                        //
-                                        // Take the proper item_update from item_updates and update it with changed fields.
-                                        // If the item_update doesn't exist yet, create a new one.
-                                        //
-                                        let current_item_update: ItemUpdate;
-                                        match subscription_item_updates.get_mut(&(subscription_index)) {
-                                            Some(item_updates) => match item_updates.get_mut(&(item_index)) {
-                                                Some(item_update) => {
-                                                    //
-                                                    // Iterate changed_fields and update existing item_update.fields assigning the new values.
-                                                    //
-                                                    for (field_name, new_value) in &changed_fields {
-                                                        if item_update.fields.contains_key(field_name) {
-                                                            item_update.fields.insert((*field_name).clone(), Some(new_value.clone()));
-                                                        }
-                                                    }
-                                                    item_update.changed_fields = changed_fields.clone();
-                                                    item_update.is_snapshot = is_snapshot;
-                                                    current_item_update = item_update.clone();
-                                                },
-                                                None => {
-                                                    // Create a new item_update and add it to item_updates.
-                                                    let item_update = ItemUpdate {
-                                                        item_name: item.cloned(),
-                                                        item_pos: item_index,
-                                                        fields: field_map.clone(),
-                                                        changed_fields: changed_fields.clone(),
-                                                        is_snapshot,
-                                                    };
-                                                    current_item_update = item_update.clone();
-                                                    item_updates.insert(item_index, item_update);
-                                                }
-                                            },
-                                            None => {
-                                                // Create a new item_update and add it to item_updates.
-                                                let item_update = ItemUpdate {
-                                                    item_name: item.cloned(),
+                                            //
+                                            // Take the proper item_update from item_updates and update it with changed fields.
+                                            // If the item_update doesn't exist yet, create a new one.
+                                            //
+                                            let mut current_item_update: ItemUpdate;
+                                            if !subscription.is_value_caching_enabled() {
+                                                // Pass-through: deliver only the values carried by this update.
+                                                current_item_update = ItemUpdate {
+                                                    item_name: item,
                                                     item_pos: item_index,
-                                                    fields: field_map,
-                                                    changed_fields,
+                                                    fields: field_map(),
+                                                    changed_fields: changed_field_map(),
                                                     is_snapshot,
+                                                    field_sources: HashMap::new(),
+                                                    decoded_fields: HashMap::new(),
+                                                };
+                                            } else {
+                                                match subscription_item_updates.get_mut(&(subscription_index)) {
+                                                    Some(item_updates) => match item_updates.get_mut(&(item_index)) {
+                                                        Some(item_update) => {
+                                                            // Drop the real-time updates changing no cached value, if required.
+                                                            if subscription.is_noop_update_suppression_enabled()
+                                                                && !is_snapshot
+                                                                && changed_fields.iter().all(|(field_pos, new_value)| {
+                                                                    item_update.fields.get(&field_names[*field_pos]).and_then(|value| value.as_ref()) == Some(*new_value)
+                                                                })
+                                                            {
+                                                                continue;
+                                                            }
+                                                            //
+                                                            // Iterate changed_fields and update existing item_update.fields assigning the new values.
+                                                            //
+                                                            for (field_pos, new_value) in &changed_fields {
+                                                                if let Some(value) = item_update.fields.get_mut(&field_names[*field_pos]) {
+                                                                    *value = Some((*new_value).clone());
+                                                                }
+                                                            }
+                                                            item_update.changed_fields = changed_field_map();
+                                                            item_update.is_snapshot = is_snapshot;
+                                                            current_item_update = item_update.clone();
+                                                        },
+                                                        None => {
+                                                            // Create a new item_update and add it to item_updates.
+                                                            let item_update = ItemUpdate {
+                                                                item_name: item,
+                                                                item_pos: item_index,
+                                                                fields: field_map(),
+                                                                changed_fields: changed_field_map(),
+                                                                is_snapshot,
+                                                                field_sources: HashMap::new(),
+                                                                decoded_fields: HashMap::new(),
+                                                            };
+                                                            current_item_update = item_update.clone();
+                                                            item_updates.insert(item_index, item_update);
+                                                        }
+                                                    },
+                                                    None => {
+                                                        // Create a new item_update and add it to item_updates.
+                                                        let item_update = ItemUpdate {
+                                                            item_name: item,
+                                                            item_pos: item_index,
+                                                            fields: field_map(),
+                                                            changed_fields: changed_field_map(),
+                                                            is_snapshot,
+                                                            field_sources: HashMap::new(),
+                                                            decoded_fields: HashMap::new(),
+                                                        };
+                                                        current_item_update = item_update.clone();
+                                                        let mut item_updates = HashMap::new();
+                                                        item_updates.insert(item_index, item_update);
+                                                        subscription_item_updates.insert(subscription_index, item_updates);
+                                                    }
                                                 };
-                                                current_item_update = item_update.clone();
-                                                let mut item_updates = HashMap::new();
-                                                item_updates.insert(item_index, item_update);
-                                                subscription_item_updates.insert(subscription_index, item_updates);
                                             }
-                                        };
-
-                                        // Get mutable subscription listeners directly.
-                                        let subscription_listeners = subscription.get_listeners();
 
-                                        // Iterate subscription listeners and call on_item_update for each listener.
-                                        for listener in subscription_listeners {
-                                            listener.on_item_update(&current_item_update);
+                                            // Decode the fields with the codecs of the subscription, and check them against its schema.
+                                            for (field, err) in subscription.decode_fields(&mut current_item_update) {
+                                                self.make_log( Level::WARN, &format!("Invalid value for field '{}' of subscription {}: {}", field, subscription_index, err) );
+                                            }
+                                            if let Some(ordered_dispatch) = &mut self.ordered_dispatch
+                                                && !ordered_dispatch.dispatch(subscription_index, &current_item_update)
+                                            {
+                                                self.ordered_dispatch = None;
+                                            }
+                                            // Dispatch the update to the subscription listeners, at the end of the
+                                            // batch if the subscriptions have different priorities.
+                                            if let Some(subscription) = self.subscriptions.iter_mut().find(|s| s.id == subscription_index) {
+                                                if prioritized {
+                                                    subscription.get_consumer_lag().record_enqueued(1);
+                                                    deferred_updates.push((subscription.get_priority(), subscription_index, current_item_update));
+                                                } else {
+                                                    subscription.on_item_update(&current_item_update);
+                                                }
+                                            }
                                         }
-                                    }
-                                    //
-                                    // Connection confirmation from server.
-                                    //
-                                    "wsok" => {
-                                        self.make_log( Level::INFO, &format!("Connection confirmed by server: '{}'", clean_text) );
                                         //
-                                        // Request session creation.
+                                        // Connection confirmation from server.
                                         //
-                                        let ls_adapter_set = match self.connection_details.get_adapter_set() {
-                                            Some(adapter_set) => adapter_set,
-                                            None => {
-                                                return Err(Box::new(IllegalStateException::new(
-                                                    "No adapter set found in connection details.",
-                                                )));
-                                            },
-                                        };
-                                        let ls_send_sync = self.connection_options.get_send_sync().to_string();
-                                        let mut params: Vec<(&str, &str)> = vec![
-                                            ("LS_adapter_set", ls_adapter_set),
-                                            ("LS_cid", "mgQkwtwdysogQz2BJ4Ji kOj2Bg"),
-                                            ("LS_send_sync", &ls_send_sync),
-                                        ];
-                                        if let Some(user) = &self.connection_details.get_user() {
-                                            params.push(("LS_user", user));
-                                        }
-                                        if let Some(password) = &self.connection_details.get_password() {
-                                            params.push(("LS_password", password));
-                                        }
-                                        params.push(("LS_protocol", Self::TLCP_VERSION));
-                                        let encoded_params = serde_urlencoded::to_string(&params)?;
-                                        write_stream
-                                            .send(Message::Text(format!("create_session\r\n{}\n", encoded_params).into()))
-                                            .await?;
-                                        self.make_log( Level::DEBUG, &format!("Sent create session request: '{}'", encoded_params) );
-                                    },
-                                    unexpected_message => {
-                                        return Err(Box::new(std::io::Error::new(
-                                            std::io::ErrorKind::InvalidData,
-                                            format!(
-                                                "Unexpected message received from server: '{:?}'",
-                                                unexpected_message
-                                            ),
-                                        )));
-                                    },
+                                        ServerMessage::Wsok => {
+                                            self.make_log( Level::INFO, &format!("Connection confirmed by server: '{}'", line) );
+                                            //
+                                            // Request session recovery or creation.
+                                            //
+                                            if let Some(session_id) = &state.session_id {
+                                                let encoded_params = self.get_bind_session_params(session_id, prog.prog())?;
+                                                let encoded_params = intercept_request(&self.request_interceptors, "bind_session", encoded_params)?;
+                                                write_stream
+                                                    .send(Message::Text(format!("bind_session\r\n{}\n", encoded_params).into()))
+                                                    .await?;
+                                                self.make_log( Level::DEBUG, &format!("Sent bind session request: '{}'", encoded_params) );
+                                            } else {
+                                                let encoded_params = self.get_create_session_params()?;
+                                                let encoded_params = intercept_request(&self.request_interceptors, "create_session", encoded_params)?;
+                                                write_stream
+                                                    .send(Message::Text(format!("create_session\r\n{}\n", encoded_params).into()))
+                                                    .await?;
+                                                self.make_log( Level::DEBUG, &format!("Sent create session request: '{}'", encoded_params) );
+                                            }
+                                        },
+                                    }
                                 }
-                            }
-                        },
-                        Some(Ok(non_text_message)) => {
-                            return Err(Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!(
-                                    "Unexpected non-text message from server: {:?}",
-                                    non_text_message
-                                ),
-                            )));
-                        },
-                        Some(Err(err)) => {
-                            return Err(Box::new(std::io::Error::new(
-                                std::io::ErrorKind::InvalidData,
-                                format!("Error reading message from server: {}", err),
-                            )));
-                        },
-                        None => {
-                            self.make_log( Level::DEBUG, "No more messages from server" );
-                            break;
-                        },
+                                arena.reset();
+                                if session_end.is_some() {
+                                    break;
+                                }
+                            },
+                            Some(Ok(non_text_message)) => {
+                                return Err(Box::new(std::io::Error::new(
+                                    std::io::ErrorKind::InvalidData,
+                                    format!(
+                                        "Unexpected non-text message from server: {:?}",
+                                        non_text_message
+                                    ),
+                                )));
+                            },
+                            // A connection failing before the session is confirmed is given up for the next transport, if any.
+                            Some(Err(err)) if !is_connected && !transports.is_empty() => {
+                                transport_failure = Some(err.to_string());
+                                break;
+                            },
+                            None if !is_connected && !transports.is_empty() => {
+                                transport_failure = Some("connection closed".to_string());
+                                break;
+                            },
+                            Some(Err(err)) => {
+                                self.make_log( Level::WARN, &format!("Error reading message from server: {}", err) );
+                                session_end = Some(StatusChangeCause::ConnectionLost(err.to_string()));
+                                break;
+                            },
+                            None => {
+                                self.make_log( Level::DEBUG, "No more messages from server" );
+                                session_end = Some(StatusChangeCause::ConnectionClosed);
+                                break;
+                            },
+                        }
+                        match read_stream.next().now_or_never() {
+                            Some(next_message) => message = next_message,
+                            None => break,
+                        }
+                    }
+                    self.dispatch_deferred_updates(&mut deferred_updates);
+                    if session_end.is_some() {
+                        break;
                     }
                 },
-                Some(subscription_request) = self.subscription_receiver.recv() => {
+                Some(subscription_request) = next_request(
+                    &mut self.subscription_receiver,
+                    &mut self.handle_subscription_receiver,
+                ) => {
                     request_id += 1;
                     // Process subscription requests.
-                    if subscription_request.subscription.is_some()
+                    if let Some(subscription) = subscription_request.subscription
                     {
-                        self.subscriptions.push(subscription_request.subscription.unwrap());
+                        if let Err(err) = subscription.check_subscribable() {
+                            self.make_log( Level::ERROR, &err.to_string() );
+                            continue;
+                        }
+                        subscription.get_snapshot_refresher().attach(self.snapshot_refresh.clone());
+                        self.subscriptions.push(subscription);
 
                         // if we are not connected yet, we will subscribe later
                         if !is_connected {
                             continue;
                         }
 
-                        subscription_id += 1;
-                        self.subscriptions.last_mut().unwrap().id = subscription_id;
-                        self.subscriptions.last().unwrap().id_sender.try_send(subscription_id)?;
+                        *subscription_id += 1;
+                        self.subscriptions.last_mut().unwrap().id = *subscription_id;
+                        let _ = self.subscriptions.last().unwrap().id_sender.try_send(*subscription_id);
+                        self.subscriptions.last_mut().unwrap().on_subscription_request();
+                        let responder = self.subscriptions.last_mut().unwrap().take_request_responder();
+                        pending_requests.insert_awaited(request_id, PendingRequest::subscription(self.subscriptions.last().unwrap(), *subscription_id), responder);
+                        if let Some(deadline) = subscribe_deadline(Instant::now()) {
+                            subscribe_deadlines.push_back((deadline, *subscription_id, 0));
+                        }
 
                         let encoded_params = match Self::get_subscription_params(self.subscriptions.last().unwrap(), request_id)
                         {
@@ -829,17 +2786,18 @@ impl LightstreamerClient {
                                 return Err(err);
                             },
                         };
+                        let encoded_params = intercept_request(&self.request_interceptors, "control", encoded_params)?;
 
-                        write_stream
-                            .send(Message::Text(format!("control\r\n{}", encoded_params).into()))
-                            .await?;
+                        if let Some(frame) = control_batch.push(encoded_params.clone()) {
+                            write_stream.send(Message::Text(frame.into())).await?;
+                        }
 
                         self.make_log( Level::INFO, &format!("Sent subscription request: '{}'", encoded_params) );
                     }
                     // Process unsubscription requests.
-                    else if subscription_request.subscription_id.is_some()
+                    else if let Some(unsubscription_id) = subscription_request.subscription_id
                     {
-                        let unsubscription_id = subscription_request.subscription_id.unwrap();
+                        pending_requests.insert(request_id, PendingRequest::Unsubscription { subscription_id: unsubscription_id });
                         let encoded_params = match Self::get_unsubscription_params(unsubscription_id, request_id)
                         {
                             Ok(params) => params,
@@ -847,14 +2805,30 @@ impl LightstreamerClient {
                                 return Err(err);
                             },
                         };
+                        let encoded_params = intercept_request(&self.request_interceptors, "control", encoded_params)?;
 
-                        write_stream
-                            .send(Message::Text(format!("control\r\n{}", encoded_params).into()))
-                            .await?;
+                        if let Some(frame) = control_batch.push(encoded_params.clone()) {
+                            write_stream.send(Message::Text(frame.into())).await?;
+                        }
 
                         self.make_log( Level::INFO, &format!("Sent unsubscription request: '{}'", encoded_params) );
 
-                        self.subscriptions.retain(|s| s.id != unsubscription_id);
+                        // Without a session, no UNSUB notification is coming.
+                        if let Some(responder) = subscription_request.responder {
+                            if is_connected {
+                                unsubscription_responders.entry(unsubscription_id).or_default().push(responder);
+                            } else {
+                                let _ = responder.send(Ok(()));
+                            }
+                        }
+
+                        self.subscriptions.retain_mut(|s| {
+                            let removed = s.id == unsubscription_id;
+                            if removed {
+                                s.on_unsubscription();
+                            }
+                            !removed
+                        });
 
                         if self.subscriptions.is_empty()
                         {
@@ -863,33 +2837,203 @@ impl LightstreamerClient {
                         }
                     }
                 },
-                _ = shutdown_signal.notified() => {
-                    self.make_log( Level::INFO, "Received shutdown signal" );
-                    break;
+                Some(message_request) = next_request(
+                    &mut self.message_receiver,
+                    &mut self.handle_message_receiver,
+                ) => {
+                    if message_request.is_expired(Instant::now()) {
+                        message_request.abort(false);
+                    }
+                    // If we are not connected yet, the message is either sent later or aborted.
+                    else if !is_connected {
+                        if !message_request.enqueue_while_disconnected {
+                            message_request.abort(false);
+                        } else if let Err(message_request) = self.message_queue.push(message_request, self.connection_options.get_message_queue_size()) {
+                            self.make_log( Level::WARN, &format!("Message '{}' aborted: the message queue is full", message_request.message) );
+                            message_request.abort(false);
+                        }
+                    } else {
+                        request_id += 1;
+                        let encoded_params = Self::write_message_request(&mut write_stream, &mut pending_messages, &mut pending_requests, message_request, request_id, &self.request_interceptors).await?;
+                        self.make_log( Level::INFO, &format!("Sent message request: '{}'", encoded_params) );
+                    }
                 },
-            }
-        }
-
-        Ok(())
-    }
-
-    /// Operation method that requests to close the Session opened against the configured Lightstreamer
-    /// Server (if any).
-    ///
-    /// When `disconnect()` is called, the "Stream-Sense" mechanism is stopped.
-    ///
-    /// Note that active `Subscription` instances, associated with this `LightstreamerClient` instance,
-    /// are preserved to be re-subscribed to on future Sessions.
-    ///
-    /// Note that the request to disconnect is accomplished by the client in a separate thread; this
-    /// means that an invocation to `getStatus()` right after `disconnect()` might not reflect the
-    /// change yet.
-    ///
+                _ = sleep_until(next_slow_start_deadline.unwrap_or_else(Instant::now)), if next_slow_start_deadline.is_some() => {
+                    let now = std::time::Instant::now();
+                    let mut warnings = Vec::new();
+                    for subscription in &mut self.subscriptions {
+                        if subscription.get_slow_start_deadline(slow_start_threshold).is_some_and(|deadline| deadline <= now) {
+                            subscription.on_slow_start();
+                            let description = PendingRequest::subscription(subscription, subscription.id).to_string();
+                            warnings.push(SlowStartWarning::new(&description, slow_start_threshold));
+                        }
+                    }
+                    for warning in warnings {
+                        self.make_log( Level::WARN, &warning.to_string() );
+                        self.dispatch_to_listeners(|listener| listener.on_slow_subscription_start(&warning));
+                    }
+                },
+                _ = snapshot_refresh.notified(), if is_connected => {
+                    for index in 0..self.subscriptions.len() {
+                        // Subscriptions not confirmed yet are already receiving a fresh snapshot.
+                        if !self.subscriptions[index].get_snapshot_refresher().take_request() || !self.subscriptions[index].is_subscribed() {
+                            continue;
+                        }
+                        self.make_log( Level::INFO, &format!("Refreshing the snapshot of subscription {}", self.subscriptions[index].id) );
+                        for request in self.get_resubscription_requests(index, &mut request_id, subscription_id, &mut pending_requests)? {
+                            if let Some(frame) = control_batch.push(request) {
+                                write_stream.send(Message::Text(frame.into())).await?;
+                            }
+                        }
+                        if let Some(deadline) = subscribe_deadline(Instant::now()) {
+                            subscribe_deadlines.push_back((deadline, *subscription_id, 0));
+                        }
+                    }
+                },
+                _ = async { message_journal.as_ref().unwrap().pushed().await }, if is_connected && message_journal.is_some() => {
+                    let journal = message_journal.as_ref().unwrap();
+                    for encoded_params in Self::write_journaled_messages(&mut write_stream, &mut pending_messages, &mut pending_requests, journal, &mut journal_sent, &mut request_id, &self.request_interceptors).await? {
+                        self.make_log( Level::INFO, &format!("Sent journaled message request: '{}'", encoded_params) );
+                    }
+                },
+                _ = sleep_until(next_message_deadline.unwrap_or_else(Instant::now)), if next_message_deadline.is_some() => {
+                    for message_request in self.message_queue.take_expired(Instant::now()) {
+                        self.make_log( Level::WARN, &format!("Message '{}' aborted: no session before its deadline", message_request.message) );
+                        message_request.abort(false);
+                    }
+                    for message_request in pending_messages.take_expired(Instant::now()) {
+                        self.make_log( Level::WARN, &format!("Message '{}' aborted: no outcome before its deadline", message_request.message) );
+                        message_request.abort(true);
+                    }
+                },
+                _ = sleep_until(next_subscribe_deadline.unwrap_or_else(Instant::now)), if next_subscribe_deadline.is_some() => {
+                    let now = Instant::now();
+                    while let Some(&(deadline, timed_out_id, retries)) = subscribe_deadlines.front()
+                        && deadline <= now
+                    {
+                        subscribe_deadlines.pop_front();
+                        // Subscriptions already answered, or removed, are no longer waiting.
+                        let Some(index) = self.subscriptions.iter().position(|s| s.id == timed_out_id && s.is_active() && !s.is_subscribed()) else {
+                            continue;
+                        };
+                        let request = PendingRequest::subscription(&self.subscriptions[index], timed_out_id);
+                        if retries < subscribe_retries {
+                            self.make_log( Level::WARN, &format!("No answer to {} within {} ms, subscribing again", request, subscribe_timeout.as_millis()) );
+                            for request in self.get_resubscription_requests(index, &mut request_id, subscription_id, &mut pending_requests)? {
+                                if let Some(frame) = control_batch.push(request) {
+                                    write_stream.send(Message::Text(frame.into())).await?;
+                                }
+                            }
+                            subscribe_deadlines.push_back((now + subscribe_timeout, *subscription_id, retries + 1));
+                            continue;
+                        }
+                        // Give up, making sure the Server drops the subscription if it ever answers.
+                        request_id += 1;
+                        pending_requests.insert(request_id, PendingRequest::Unsubscription { subscription_id: timed_out_id });
+                        let unsubscription = intercept_request(&self.request_interceptors, "control", Self::get_unsubscription_params(timed_out_id, request_id)?)?;
+                        if let Some(frame) = control_batch.push(unsubscription) {
+                            write_stream.send(Message::Text(frame.into())).await?;
+                        }
+                        let error = TimeoutError::new(&request.to_string(), subscribe_timeout);
+                        self.make_log( Level::ERROR, &error.to_string() );
+                        self.subscriptions[index].on_subscription_timeout(error);
+                    }
+                },
+                _ = sleep_until(control_batch_deadline.unwrap_or_else(Instant::now)), if control_batch_deadline.is_some() => {
+                    for frame in control_batch.take_frames() {
+                        write_stream.send(Message::Text(frame.into())).await?;
+                    }
+                },
+                _ = sleep_until(rebind_deadline.unwrap_or_else(Instant::now)), if rebind_deadline.is_some() => {
+                    rebind_deadline = None;
+                    if let Some(session_id) = &state.session_id {
+                        let encoded_params = self.get_bind_session_params(session_id, prog.prog())?;
+                        let encoded_params = intercept_request(&self.request_interceptors, "bind_session", encoded_params)?;
+                        write_stream
+                            .send(Message::Text(format!("bind_session\r\n{}\n", encoded_params).into()))
+                            .await?;
+                        rebinding = true;
+                    }
+                },
+                _ = sleep_until(next_heartbeat_deadline.unwrap_or_else(Instant::now)), if next_heartbeat_deadline.is_some() => {
+                    self.make_log( Level::DEBUG, "Sending reverse heartbeat" );
+                    write_stream.send(Message::Text("heartbeat\r\n".into())).await?;
+                },
+                _ = sleep_until(fallback_deadline.unwrap_or_else(Instant::now)), if fallback_deadline.is_some() && !is_connected => {
+                    transport_failure = Some("timed out waiting for the session".to_string());
+                },
+                _ = shutdown_signal.notified() => {
+                    self.make_log( Level::INFO, "Received shutdown signal" );
+                    if let Some(recorder) = &recorder {
+                        recorder.record(RecordedEventKind::StateChange("Shutdown requested".to_string()));
+                    }
+                    // Don't lose the control requests still waiting for the batching window.
+                    for frame in control_batch.take_frames() {
+                        write_stream.send(Message::Text(frame.into())).await?;
+                    }
+                    session_end = Some(StatusChangeCause::ShutdownRequested);
+                    break;
+                },
+            }
+
+            //
+            // Fall back to the next transport, on a new connection.
+            //
+            if let Some(reason) = transport_failure.take() {
+                self.make_log(
+                    Level::WARN,
+                    &format!(
+                        "Connection with {:?} failed ({}), falling back to {:?}",
+                        connection_type, reason, transports[0]
+                    ),
+                );
+                let (connection, connection_url, next_connection_type) =
+                    self.open_first_connection(&mut transports).await?;
+                (write_stream, read_stream) = split_connection(connection, &connection_url);
+                connection_type = next_connection_type;
+                fallback_deadline = self.get_fallback_deadline(&connection_type, &transports);
+                write_stream.send(Message::Text("wsok".into())).await?;
+            }
+        }
+
+        // The outcome of the messages still pending can no longer be received.
+        if pending_messages.len() > 0 {
+            self.make_log(
+                Level::INFO,
+                &format!("Aborting {} pending messages", pending_messages.len()),
+            );
+        }
+        for message_request in pending_messages.drain() {
+            message_request.abort(true);
+        }
+        // Nothing more is received for the subscriptions removed, whether UNSUB came or not.
+        for responder in unsubscription_responders.into_values().flatten() {
+            let _ = responder.send(Ok(()));
+        }
+
+        // Keep what is needed to recover the session on a new connection.
+        state.request_id = request_id;
+
+        Ok(session_end.unwrap_or(StatusChangeCause::ConnectionClosed))
+    }
+
+    /// Operation method that requests to close the Session opened against the configured Lightstreamer
+    /// Server (if any).
+    ///
+    /// When `disconnect()` is called, the "Stream-Sense" mechanism is stopped.
+    ///
+    /// Note that active `Subscription` instances, associated with this `LightstreamerClient` instance,
+    /// are preserved to be re-subscribed to on future Sessions.
+    ///
+    /// Note that the request to disconnect is accomplished by the client in a separate thread; this
+    /// means that an invocation to `getStatus()` right after `disconnect()` might not reflect the
+    /// change yet.
+    ///
     /// When the request to disconnect is finally being executed, if the status of the client is
     /// "DISCONNECTED", then nothing will be done.
     ///
     /// See also `connect()`
-    #[instrument(level = "trace")]
+    #[cfg_attr(feature = "logging", instrument(level = "trace"))]
     pub async fn disconnect(&mut self) {
         // Implementation for disconnect
         self.make_log(Level::INFO, "Disconnecting from Lightstreamer server");
@@ -911,7 +3055,7 @@ impl LightstreamerClient {
     ///
     /// A list with the various cookies that can be sent in a HTTP request for the specified URI.
     /// If a `None` URI was supplied, all available non-expired cookies will be returned.
-    pub fn get_cookies(_uri: Option<&str>) -> Cookie {
+    pub fn get_cookies(_uri: Option<&str>) -> Cookie<'_> {
         // Implementation for get_cookies
         unimplemented!()
     }
@@ -968,6 +3112,44 @@ impl LightstreamerClient {
         &self.subscriptions
     }
 
+    /// Inquiry method that takes a snapshot of the internal state of the client: status, session,
+    /// subscriptions, requests waiting to be taken in charge by the session, counters and recent
+    /// status transitions. The snapshot is serializable, e.g. through `ClientStateDump::to_json()`,
+    /// to be attached to bug reports or exposed on admin endpoints. Credentials are not included.
+    ///
+    /// # Returns
+    ///
+    /// The snapshot of the current state.
+    pub fn dump_state(&self) -> ClientStateDump {
+        let diagnostics = &self.diagnostics;
+        ClientStateDump {
+            status: self.status.to_string(),
+            server_address: self.connection_details.get_server_address().cloned(),
+            adapter_set: self.connection_details.get_adapter_set().cloned(),
+            forced_transport: self
+                .connection_options
+                .get_forced_transport()
+                .map(|transport| format!("{:?}", transport)),
+            session: self.get_session_info(),
+            subscriptions: self
+                .subscriptions
+                .iter()
+                .map(SubscriptionDump::from)
+                .collect(),
+            queued_subscription_requests: self.subscription_receiver.len(),
+            queued_message_requests: self.message_receiver.len(),
+            prog: diagnostics.get_prog(),
+            duplicate_notifications: diagnostics.get_duplicate_notifications(),
+            lost_notifications: diagnostics.get_lost_notifications(),
+            status_history: self
+                .status_history
+                .get_transitions()
+                .iter()
+                .map(|transition| transition.to_string())
+                .collect(),
+        }
+    }
+
     /// Creates a new instance of `LightstreamerClient`.
     ///
     /// The constructor initializes the client with the server address and adapter set, if provided.
@@ -995,11 +3177,15 @@ impl LightstreamerClient {
         adapter_set: Option<&str>,
         username: Option<&str>,
         password: Option<&str>,
-    ) -> Result<LightstreamerClient, Box<dyn Error>> {
+    ) -> Result<LightstreamerClient, LightstreamerError> {
         let connection_details =
             ConnectionDetails::new(server_address, adapter_set, username, password)?;
         let connection_options = ConnectionOptions::default();
         let (subscription_sender, subscription_receiver) = channel(100);
+        let (message_sender, message_receiver) = channel(100);
+        let (handle_subscription_sender, handle_subscription_receiver) = unbounded_channel();
+        let (handle_message_sender, handle_message_receiver) = unbounded_channel();
+        let status = ClientStatus::Disconnected(DisconnectionType::WillRetry);
 
         Ok(LightstreamerClient {
             server_address: server_address.map(|s| s.to_string()),
@@ -1007,11 +3193,39 @@ impl LightstreamerClient {
             connection_details,
             connection_options,
             listeners: Vec::new(),
+            weak_listeners: Vec::new(),
             subscriptions: Vec::new(),
-            status: ClientStatus::Disconnected(DisconnectionType::WillRetry),
+            status_sender: watch::Sender::new(status.clone()),
+            status,
             logging: LogType::StdLogs,
             subscription_sender,
             subscription_receiver,
+            message_sender,
+            message_receiver,
+            handle_subscription_sender,
+            handle_subscription_receiver,
+            handle_message_sender,
+            handle_message_receiver,
+            snapshot_refresh: Arc::new(Notify::new()),
+            flight_recorder: None,
+            ordered_dispatch: None,
+            status_history: StatusHistory::default(),
+            last_error: LastErrorHandle::default(),
+            cookie_store: CookieStore::new(),
+            session_info: watch::Sender::new(None),
+            diagnostics: SessionDiagnostics::default(),
+            health_probe: HealthProbe::default(),
+            in_flight_messages: InFlightMessages::default(),
+            message_queue: MessageQueue::default(),
+            request_interceptors: Vec::new(),
+            selected_server_address: None,
+            previous_server_address: None,
+            update_log_sampler: LogSampler::default(),
+            hooks: LifecycleHooks::default(),
+            reconnect_limiter: None,
+            message_journal: None,
+            runtime_handle: None,
+            connect_permit: None,
         })
     }
 
@@ -1079,8 +3293,10 @@ impl LightstreamerClient {
     /// event will be fired.
     ///
     /// Note that, in any case, as soon as the status switches again to "DISCONNECTED*", any message
-    /// still pending is aborted, including messages that were queued with the `enqueueWhileDisconnected`
-    /// flag set to `true`.
+    /// still waiting for its outcome is aborted. Messages queued with the `enqueueWhileDisconnected`
+    /// flag set to `true` are kept while the client retries, and sent in order on the next session;
+    /// they are aborted if the queue is full (see `ConnectionOptions.setMessageQueueSize()`) or when
+    /// the client stops trying to connect.
     ///
     /// Also note that forwarding of the message to the server is made in a separate thread, hence,
     /// if a message is sent while the connection is active, it could be aborted because of a subsequent
@@ -1095,9 +3311,9 @@ impl LightstreamerClient {
     ///   in sequence; underscore characters are also allowed. If the "UNORDERED_MESSAGES" identifier
     ///   is supplied, the message will be processed in the special way described above. The parameter
     ///   is optional; if set to `None`, "UNORDERED_MESSAGES" is used as the sequence name.
-    /// * `delay_timeout`: a timeout, expressed in milliseconds. If higher than the Server configured
-    ///   timeout on missing messages, the latter will be used instead. The parameter is optional; if
-    ///   a negative value is supplied, the Server configured timeout on missing messages will be applied.
+    /// * `delay_timeout`: a timeout, expressed in milliseconds, sent as `LS_max_wait`. If higher than
+    ///   the Server configured timeout on missing messages, the latter will be used instead. The
+    ///   parameter is optional; if `None`, the Server configured timeout on missing messages will be applied.
     ///   This timeout is ignored for the special "UNORDERED_MESSAGES" sequence, although a server-side
     ///   timeout on missing messages still applies.
     /// * `listener`: an object suitable for receiving notifications about the processing outcome. The
@@ -1106,39 +3322,59 @@ impl LightstreamerClient {
     ///   status when the provided message is handled, then the message is not aborted right away but
     ///   is queued waiting for a new session. Note that the message can still be aborted later when
     ///   a new session is established.
+    ///
+    /// The message is handed to the session through `message_sender`; see `send_message_request()`
+    /// to send messages while `connect()` is running, and to set a client-side delivery deadline.
     pub fn send_message(
         &mut self,
         message: &str,
         sequence: Option<&str>,
-        _delay_timeout: Option<u64>,
+        delay_timeout: Option<u64>,
         listener: Option<Box<dyn ClientMessageListener>>,
         enqueue_while_disconnected: bool,
     ) {
-        let _sequence = sequence.unwrap_or("UNORDERED_MESSAGES");
-
-        // Handle the message based on the current connection status
-        match &self.status {
-            ClientStatus::Connected(_connection_type) => {
-                // Send the message to the server in a separate thread
-                // ...
-            }
-            ClientStatus::Disconnected(_disconnection_type) => {
-                if enqueue_while_disconnected {
-                    // Enqueue the message to be sent when a connection is available
-                    // ...
-                } else {
-                    // Abort the message and notify the listener
-                    if let Some(listener) = listener {
-                        listener.on_abort(message, false);
-                    }
-                }
-            }
-            _ => {
-                // Enqueue the message to be sent when a connection is available
-                // ...
+        let mut message_request = MessageRequest::new(message)
+            .with_sequence(sequence.unwrap_or(MessageRequest::UNORDERED_MESSAGES))
+            .with_enqueue_while_disconnected(enqueue_while_disconnected);
+        if let Some(delay_timeout) = delay_timeout {
+            message_request = message_request.with_delay_timeout(delay_timeout);
+        }
+        if let Some(listener) = listener {
+            message_request = message_request.with_listener(listener);
+        }
+        match self.message_sender.try_send(message_request) {
+            Ok(()) => {}
+            Err(TrySendError::Full(message_request))
+            | Err(TrySendError::Closed(message_request)) => {
+                self.make_log(Level::WARN, "Message queue unavailable, aborting message");
+                message_request.abort(false);
             }
         }
-        unimplemented!("Complete mechanism to send message to LightstreamerClient.");
+    }
+
+    /// Sends a message described by a `MessageRequest` to the session of the `LightstreamerClient`
+    /// owning `message_sender`. This is the counterpart of `subscribe()` for messages: it can be
+    /// used while `connect()` is running.
+    ///
+    /// Besides the options of `send_message()`, the request can carry a client-side delivery
+    /// deadline (see `MessageRequest::with_timeout()`): if no outcome is received before the
+    /// deadline, the message is given up and `ClientMessageListener::on_abort()` is fired, so that
+    /// callers can enforce a latency budget. The Server-side wait for preceding messages of the
+    /// same sequence is bounded by `MessageRequest::with_delay_timeout()` (`LS_max_wait`).
+    ///
+    /// If the client is gone, the message is aborted right away.
+    ///
+    /// # Parameters
+    ///
+    /// * `message_sender`: A `Sender` object that sends a `MessageRequest` to the `LightstreamerClient`
+    /// * `message_request`: The message to be sent, with its options.
+    pub async fn send_message_request(
+        message_sender: Sender<MessageRequest>,
+        message_request: MessageRequest,
+    ) {
+        if let Err(err) = message_sender.send(message_request).await {
+            err.0.abort(false);
+        }
     }
 
     /// Static method that permits to configure the logging system used by the library. The logging
@@ -1190,11 +3426,11 @@ impl LightstreamerClient {
         unimplemented!("Implement mechanism to set trust manager factory for LightstreamerClient.");
     }
     /*
-    pub fn set_trust_manager_factory(factory: Option<SslContext>) -> Result<(), IllegalArgumentException> {
+    pub fn set_trust_manager_factory(factory: Option<SslContext>) -> Result<(), LightstreamerError> {
         if factory.is_none() {
             return Err(IllegalArgumentException::new(
                 "Factory cannot be None",
-            ));
+            ).into());
         }
 
         // Implementation for set_trust_manager_factory
@@ -1217,7 +3453,7 @@ impl LightstreamerClient {
     /// Also note that forwarding of the subscription to the server is made in a separate thread.
     ///
     /// A successful subscription to the server will be notified through a `SubscriptionListener.onSubscription()`
-    /// event.
+    /// event, and resolves the returned future.
     ///
     /// # Parameters
     ///
@@ -1225,18 +3461,156 @@ impl LightstreamerClient {
     /// * `subscription`: A `Subscription` object, carrying all the information needed to process real-time
     ///   values.
     ///
+    /// # Returns
+    ///
+    /// A future, as returned by `Subscription::await_subscribed()`, resolved by the SUBOK (or SUBCMD)
+    /// notification with the numbers of items and fields of the subscription, or failed by REQERR or
+    /// by the subscribe timeout. The request is queued before returning, so the future does not
+    /// need to be awaited for the subscription to proceed; it fails with an
+    /// `IllegalStateException` if the client is gone. If the subscription is already active, or
+    /// was unsubscribed, no request is sent and the future fails right away with a
+    /// `SubscriptionStateError`.
+    ///
     /// See also `unsubscribe()`
-    pub async fn subscribe(
+    pub fn subscribe(
         subscription_sender: Sender<SubscriptionRequest>,
         subscription: Subscription,
+    ) -> impl Future<Output = Result<SubscriptionOk, LightstreamerError>> + Send + 'static {
+        let subscribable = subscription.check_subscribable();
+        let subscribed = subscription.await_subscribed();
+        // If the client is gone, the subscription is dropped and the future fails.
+        if subscribable.is_ok() {
+            Self::queue_subscription_request(
+                subscription_sender,
+                SubscriptionRequest {
+                    subscription: Some(subscription),
+                    subscription_id: None,
+                    responder: None,
+                },
+            );
+        }
+        async move {
+            subscribable?;
+            subscribed.await
+        }
+    }
+
+    /// Queues a subscription or unsubscription request without waiting: when the queue is full,
+    /// the request is handed to a task waiting for room in it. If the client is gone, the request
+    /// is dropped.
+    fn queue_subscription_request(
+        subscription_sender: Sender<SubscriptionRequest>,
+        subscription_request: SubscriptionRequest,
     ) {
-        subscription_sender
-            .send(SubscriptionRequest {
-                subscription: Some(subscription),
-                subscription_id: None,
-            })
-            .await
-            .unwrap()
+        if let Err(TrySendError::Full(subscription_request)) =
+            subscription_sender.try_send(subscription_request)
+        {
+            tokio::spawn(async move {
+                let _ = subscription_sender.send(subscription_request).await;
+            });
+        }
+    }
+
+    /// Adds a subscription to a `LightstreamerClient` instance that is not connected yet.
+    ///
+    /// Unlike `subscribe()`, this method needs no running session and never waits, so any number
+    /// of subscriptions can be registered by the application startup code before calling
+    /// `connect()`. When the session is created, all the registered subscriptions are sent, in
+    /// registration order, batched in as few requests as the request limit of the session allows.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription`: A `Subscription` object, carrying all the information needed to process
+    ///   real-time values.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the client is connected; use `subscribe()` instead.
+    /// * `IllegalStateException`: if the subscription is already active.
+    ///
+    /// See also `subscribe()`
+    ///
+    /// See also `detach_subscription()`
+    pub fn add_subscription(
+        &mut self,
+        subscription: Subscription,
+    ) -> Result<(), LightstreamerError> {
+        subscription.check_subscribable()?;
+        if matches!(self.status, ClientStatus::Connected(_)) {
+            return Err(IllegalStateException::new(
+                "Subscriptions can only be added while the client is not connected",
+            )
+            .into());
+        }
+        subscription
+            .get_snapshot_refresher()
+            .attach(self.snapshot_refresh.clone());
+        self.subscriptions.push(subscription);
+        Ok(())
+    }
+
+    /// Removes a subscription from a `LightstreamerClient` instance that is not connected, to give
+    /// it to another client through `add_subscription()`, e.g. to fail over to another Server.
+    ///
+    /// The subscription keeps its configuration, its listeners and its cached values, which are
+    /// replaced by the ones of the new session once the other client subscribes to it. No
+    /// unsubscription event is notified.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscription_id`: The ID of the subscription in the last session of this client.
+    ///
+    /// # Returns
+    ///
+    /// The subscription, or `None` if no subscription has the given ID.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the client is connected.
+    ///
+    /// See also `detach_subscriptions()`
+    pub fn detach_subscription(
+        &mut self,
+        subscription_id: usize,
+    ) -> Result<Option<Subscription>, LightstreamerError> {
+        self.check_detachable()?;
+        let Some(index) = self
+            .subscriptions
+            .iter()
+            .position(|subscription| subscription.id == subscription_id)
+        else {
+            return Ok(None);
+        };
+        let mut subscription = self.subscriptions.remove(index);
+        subscription.on_detach();
+        Ok(Some(subscription))
+    }
+
+    /// Removes all the subscriptions from a `LightstreamerClient` instance that is not connected,
+    /// in the order they were added, to give them to another client through `add_subscription()`.
+    ///
+    /// See `detach_subscription()` for details.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the client is connected.
+    pub fn detach_subscriptions(&mut self) -> Result<Vec<Subscription>, LightstreamerError> {
+        self.check_detachable()?;
+        let mut subscriptions = std::mem::take(&mut self.subscriptions);
+        for subscription in &mut subscriptions {
+            subscription.on_detach();
+        }
+        Ok(subscriptions)
+    }
+
+    /// Checks that subscriptions can be detached, that is the client is not connected.
+    fn check_detachable(&self) -> Result<(), IllegalStateException> {
+        if matches!(self.status, ClientStatus::Connected(_)) {
+            return Err(IllegalStateException::new(
+                "Subscriptions can only be detached while the client is not connected",
+            ));
+        }
+        Ok(())
     }
 
     /// If you want to be able to unsubscribe from a subscription, you need to keep track of the id
@@ -1251,7 +3625,7 @@ impl LightstreamerClient {
     pub async fn subscribe_get_id(
         subscription_sender: Sender<SubscriptionRequest>,
         mut subscription: Subscription,
-    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+    ) -> Result<usize, LightstreamerError> {
         // Extract the id_receiver before sending the subscription
         let mut id_receiver = subscription.id_receiver;
 
@@ -1259,15 +3633,13 @@ impl LightstreamerClient {
         let (_new_sender, new_receiver) = channel(1);
         subscription.id_receiver = new_receiver;
 
-        // Send the subscription
-        LightstreamerClient::subscribe(subscription_sender, subscription).await;
+        // Send the subscription, without waiting for its confirmation.
+        let _subscribed = LightstreamerClient::subscribe(subscription_sender, subscription);
 
         // Wait for the ID to be updated through the channel
         match id_receiver.recv().await {
             Some(id) => Ok(id),
-            None => Err(Box::new(IllegalStateException::new(
-                "Failed to get subscription id",
-            ))),
+            None => Err(IllegalStateException::new("Failed to get subscription id").into()),
         }
     }
 
@@ -1306,17 +3678,30 @@ impl LightstreamerClient {
     /// * `subsrciption_sender`: A `Sender` object that sends a `SubscriptionRequest` to the `LightstreamerClient`
     /// * `subscription_id`: The id of the subscription to be unsubscribed from.
     ///   instance.
-    pub async fn unsubscribe(
+    ///
+    /// # Returns
+    ///
+    /// A future resolved by the UNSUB notification of the subscription, after which the Server sends
+    /// no more updates for it, or when the session ends first, or right away if no session is
+    /// available. It fails with a `ServerException` if the Server refuses the request through
+    /// REQERR, and with an `IllegalStateException` if the client is gone or the connection fails.
+    /// The request is queued before returning, so the future does not need to be awaited for the
+    /// unsubscription to proceed.
+    pub fn unsubscribe(
         subscription_sender: Sender<SubscriptionRequest>,
         subscription_id: usize,
-    ) {
-        subscription_sender
-            .send(SubscriptionRequest {
+    ) -> impl Future<Output = Result<(), LightstreamerError>> + Send + 'static {
+        let (responder, unsubscribed) = request_response();
+        // If the client is gone, the responder is dropped and the future fails.
+        Self::queue_subscription_request(
+            subscription_sender,
+            SubscriptionRequest {
                 subscription: None,
                 subscription_id: Some(subscription_id),
-            })
-            .await
-            .unwrap()
+                responder: Some(responder),
+            },
+        );
+        unsubscribed
     }
 
     /// Method setting enum for the logging of this instance.
@@ -1334,6 +3719,39 @@ impl LightstreamerClient {
         self.logging = logging;
     }
 
+    /// Method setting how the debug log lines emitted for each real-time update are sampled.
+    ///
+    /// Default is at most 10 lines per second, each reporting the lines suppressed before it.
+    ///
+    /// # Parameters
+    ///
+    /// * `sampling`: The sampling policy of the update log lines.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if `LogSampling::OneIn(0)` is passed.
+    pub fn set_update_log_sampling(
+        &mut self,
+        sampling: LogSampling,
+    ) -> Result<(), LightstreamerError> {
+        if sampling == LogSampling::OneIn(0) {
+            return Err(IllegalArgumentException::new(
+                "The sampling rate of the update log lines must be positive",
+            )
+            .into());
+        }
+        self.update_log_sampler = LogSampler::new(sampling);
+        Ok(())
+    }
+
+    /// Inquiry method that gets how the debug log lines emitted for each real-time update are
+    /// sampled.
+    ///
+    /// See also `set_update_log_sampling()`
+    pub fn get_update_log_sampling(&self) -> LogSampling {
+        self.update_log_sampler.sampling()
+    }
+
     /// Method for logging messages
     ///
     /// Match case wraps log types. `loglevel` param ignored in StdLogs case, all output to stdout.
@@ -1367,10 +3785,28 @@ impl LightstreamerClient {
     }
 }
 
+/// Receives the next request from the bounded queue of the client or from the unbounded queue of
+/// its handles, whichever has one first.
+async fn next_request<T>(
+    receiver: &mut Receiver<T>,
+    handle_receiver: &mut UnboundedReceiver<T>,
+) -> Option<T> {
+    tokio::select! {
+        Some(request) = receiver.recv() => Some(request),
+        Some(request) = handle_receiver.recv() => Some(request),
+        else => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::subscription::{Subscription, SubscriptionListener, SubscriptionMode};
+    use crate::client::{FeedAggregator, OutboundRequest};
+    use crate::subscription::{
+        Base64Codec, SnapshotRefresher, Subscription, SubscriptionListener, SubscriptionMode,
+        SubscriptionState,
+    };
+    use crate::utils::{Proxy, ProxyType};
     use std::error::Error;
     use std::fmt::Debug;
     use std::sync::{Arc, Mutex};
@@ -1548,68 +3984,2110 @@ mod tests {
         let mut client = result.unwrap();
         assert_eq!(client.get_listeners().len(), 0);
 
-        let listener = Box::new(MockClientListener::new());
-        client.add_listener(listener);
-        assert_eq!(client.get_listeners().len(), 1);
+        let listener = Box::new(MockClientListener::new());
+        client.add_listener(listener);
+        assert_eq!(client.get_listeners().len(), 1);
+    }
+
+    #[test]
+    fn test_get_status() {
+        let result = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let client = result.unwrap();
+        match client.get_status() {
+            ClientStatus::Disconnected(DisconnectionType::WillRetry) => {}
+            _ => panic!("Expected initial status to be DISCONNECTED:WILL-RETRY"),
+        }
+    }
+
+    #[test]
+    fn test_get_subscriptions() {
+        let result = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let client = result.unwrap();
+        assert_eq!(client.get_subscriptions().len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_dump_state() {
+        let client = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            Some("user"),
+            Some("secret"),
+        )
+        .unwrap();
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        let _subscribed =
+            LightstreamerClient::subscribe(client.subscription_sender.clone(), subscription);
+
+        let dump = client.dump_state();
+        assert_eq!(dump.status, client.get_status().to_string());
+        assert_eq!(dump.session, None);
+        assert_eq!(dump.queued_subscription_requests, 1);
+        assert_eq!(dump.queued_message_requests, 0);
+        let json = dump.to_json().unwrap();
+        assert!(json.contains("\"adapter_set\":\"DEMO\""));
+        assert!(!json.contains("secret"));
+    }
+
+    #[tokio::test]
+    async fn test_detach_and_attach_subscriptions() {
+        let (address, requests) =
+            spawn_recording_mock_server(vec!["CONOK,S1,50000,5000,*\r\n"]).await;
+        let mut blue = LightstreamerClient::new(None, None, None, None).unwrap();
+        let mut green = LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        green
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        for item in ["item1", "item2"] {
+            let subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            blue.add_subscription(subscription).unwrap();
+        }
+        // Subscribed in the session of blue, as the session loop does.
+        blue.subscriptions[0].id = 1;
+        blue.subscriptions[0].id_sender.try_send(1).unwrap();
+        blue.subscriptions[0].on_subscription_request();
+        blue.subscriptions[0].on_subscription(SubscriptionOk {
+            subscription_id: 1,
+            items: 1,
+            fields: 1,
+            key_pos: None,
+            command_pos: None,
+        });
+
+        let subscription = blue.detach_subscription(1).unwrap().unwrap();
+        assert_eq!(subscription.id, 0);
+        assert!(!subscription.is_active());
+        assert!(!subscription.is_subscribed());
+        assert!(blue.detach_subscription(1).unwrap().is_none());
+        for subscription in blue.detach_subscriptions().unwrap() {
+            green.add_subscription(subscription).unwrap();
+        }
+
+        assert!(blue.get_subscriptions().is_empty());
+        let items: Vec<&Vec<String>> = green
+            .get_subscriptions()
+            .iter()
+            .filter_map(|subscription| subscription.get_items())
+            .collect();
+        assert_eq!(items, vec![&vec!["item2".to_string()]]);
+
+        blue.status = ClientStatus::Connected(ConnectionType::WsStreaming);
+        assert!(blue.detach_subscriptions().is_err());
+
+        // The subscription detached after being subscribed can be subscribed again by green,
+        // without ending its session.
+        let subscription_sender = green.subscription_sender.clone();
+        let history = green.get_status_history();
+        let received = requests.clone();
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            green.connect_with_shutdown(async move {
+                while !history.get_last().is_some_and(|transition| {
+                    transition.status.to_string().starts_with("CONNECTED")
+                }) {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+                let _subscribed = LightstreamerClient::subscribe(subscription_sender, subscription);
+                while !received
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|request| request.starts_with("control") && request.contains("item1"))
+                {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }),
+        )
+        .await
+        .expect("the subscription was not sent");
+        assert!(result.is_ok());
+        assert_eq!(green.get_subscriptions().len(), 2);
+    }
+
+    #[test]
+    fn test_weak_listeners_are_removed_when_dropped() {
+        let mut client = LightstreamerClient::new(None, None, None, None).unwrap();
+        let server_errors = Arc::new(Mutex::new(Vec::new()));
+        let listener = Arc::new(MockClientListener::with_shared_data(
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(Mutex::new(Vec::new())),
+            server_errors.clone(),
+        ));
+        client.add_listener_weak(&listener);
+        assert_eq!(client.get_weak_listener_count(), 1);
+        assert!(client.get_listeners().is_empty());
+
+        client.dispatch_to_listeners(|listener| listener.on_server_error(41, "License"));
+        assert_eq!(server_errors.lock().unwrap().len(), 1);
+
+        drop(listener);
+        assert_eq!(client.get_weak_listener_count(), 0);
+        client.dispatch_to_listeners(|listener| listener.on_server_error(41, "License"));
+        assert_eq!(server_errors.lock().unwrap().len(), 1);
+        assert!(client.weak_listeners.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_no_server_address() {
+        let result = LightstreamerClient::new(None, Some("DEMO"), None, None);
+        assert!(result.is_ok());
+        let mut client = result.unwrap();
+        let shutdown_signal = Arc::new(Notify::new());
+        let result = client.connect(shutdown_signal).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_flight_recorder_records_failed_session() {
+        let mut client = LightstreamerClient::new(None, None, None, None).unwrap();
+        assert!(client.get_flight_recorder().is_none());
+        let recorder = client.enable_flight_recorder(16);
+
+        assert!(client.connect(Arc::new(Notify::new())).await.is_err());
+
+        let events = recorder.get_events();
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            &events[0].kind,
+            RecordedEventKind::StateChange(text) if text.starts_with("Session failed")
+        ));
+
+        client.disable_flight_recorder();
+        assert!(client.get_flight_recorder().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_status_history_records_failed_session() {
+        let mut client = LightstreamerClient::new(None, None, None, None).unwrap();
+        let history = client.get_status_history();
+
+        assert!(client.connect(Arc::new(Notify::new())).await.is_err());
+
+        let transitions = history.get_transitions();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[0].status, ClientStatus::Connecting);
+        assert_eq!(
+            transitions[1].status,
+            ClientStatus::Disconnected(DisconnectionType::NoRetry)
+        );
+        assert!(matches!(
+            transitions[1].cause,
+            StatusChangeCause::Failure(_)
+        ));
+        assert_eq!(client.get_status().to_string(), "DISCONNECTED");
+
+        client.set_status_history_capacity(1);
+        assert!(client.get_status_history().get_transitions().is_empty());
+    }
+
+    #[test]
+    fn test_create_session_params_request_keepalive() {
+        let mut client =
+            LightstreamerClient::new(Some("http://localhost:8080"), Some("DEMO"), None, None)
+                .unwrap();
+        let params = client.get_create_session_params().unwrap();
+        assert!(params.contains("LS_adapter_set=DEMO"));
+        assert!(!params.contains("LS_keepalive_millis"));
+
+        client
+            .connection_options
+            .set_keepalive_interval(5000)
+            .unwrap();
+        let params = client.get_create_session_params().unwrap();
+        assert!(params.contains("LS_keepalive_millis=5000"));
+    }
+
+    #[test]
+    fn test_session_params_request_inactivity_timeout() {
+        let mut client =
+            LightstreamerClient::new(Some("http://localhost:8080"), Some("DEMO"), None, None)
+                .unwrap();
+        assert!(
+            !client
+                .get_create_session_params()
+                .unwrap()
+                .contains("LS_inactivity_millis")
+        );
+
+        client
+            .connection_options
+            .set_reverse_heartbeat_interval(5000)
+            .unwrap();
+        assert!(
+            client
+                .get_create_session_params()
+                .unwrap()
+                .contains("LS_inactivity_millis=5000")
+        );
+        assert!(
+            client
+                .get_bind_session_params("S1", 0)
+                .unwrap()
+                .contains("LS_inactivity_millis=5000")
+        );
+    }
+
+    #[test]
+    fn test_session_params_forward_sync_and_head_options() {
+        let mut client =
+            LightstreamerClient::new(Some("http://localhost:8080"), Some("DEMO"), None, None)
+                .unwrap();
+        let params = client.get_create_session_params().unwrap();
+        assert!(params.contains("LS_send_sync=true"));
+        assert!(!params.contains("LS_reduce_head"));
+
+        client.connection_options.set_send_sync(false);
+        client.connection_options.set_reduce_head(true);
+        let params = client.get_create_session_params().unwrap();
+        assert!(params.contains("LS_send_sync=false"));
+        assert!(params.contains("LS_reduce_head=true"));
+        let params = client.get_bind_session_params("S1", 7).unwrap();
+        assert!(params.contains("LS_send_sync=false"));
+        assert!(params.contains("LS_reduce_head=true"));
+    }
+
+    /// Serves a session over HTTP streaming: `create_session.txt` and `bind_session.txt` are
+    /// answered with CONOK on a chunked response kept open, and each control request with REQOK,
+    /// the given notifications being pushed on the stream afterwards, which is then closed if they
+    /// end with DROP. When `polling`, every poll is answered at once with
+    /// CONOK, the notifications pending and LOOP instead, while the stream connections are left
+    /// unanswered, as by a buffering proxy; any other request, such as a WebSocket handshake, is
+    /// refused. The stream responses set an affinity cookie. Returns the address of the server
+    /// and the requests received, as request line, `Cookie` header, if any, and body.
+    async fn spawn_http_mock_server(
+        after_control: &'static str,
+        polling: bool,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}/lightstreamer", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        let chunk = |text: &str| format!("{:x}\r\n{}\r\n", text.len(), text);
+        tokio::spawn(async move {
+            let mut stream_connection = None;
+            let mut unanswered_connections = Vec::new();
+            let mut pending = "";
+            loop {
+                let (connection, _) = listener.accept().await.unwrap();
+                let mut connection = BufReader::new(connection);
+                let mut request_line = String::new();
+                connection.read_line(&mut request_line).await.unwrap();
+                let mut content_length = 0;
+                let mut cookie = String::new();
+                loop {
+                    let mut header = String::new();
+                    connection.read_line(&mut header).await.unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:")
+                    {
+                        content_length = value.trim().parse().unwrap();
+                    } else if header.to_ascii_lowercase().starts_with("cookie:") {
+                        cookie = header.clone();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                connection.read_exact(&mut body).await.unwrap();
+                let body = String::from_utf8(body).unwrap();
+                received.lock().unwrap().push(format!(
+                    "{}\n{}{}",
+                    request_line.trim(),
+                    cookie,
+                    body
+                ));
+                let mut connection = connection.into_inner();
+                let is_stream = request_line.contains("/create_session.txt")
+                    || request_line.contains("/bind_session.txt");
+                if polling && is_stream && !body.contains("LS_polling=true") {
+                    unanswered_connections.push(connection);
+                } else if polling && is_stream {
+                    let poll = format!(
+                        "CONOK,S1,50000,5000,*\r\n{}LOOP,0\r\n",
+                        std::mem::take(&mut pending)
+                    );
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}",
+                        poll.len(),
+                        poll
+                    );
+                    connection.write_all(response.as_bytes()).await.unwrap();
+                } else if is_stream {
+                    let response = format!(
+                        "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\
+                         Set-Cookie: AWSALB=node-1; Path=/\r\n\r\n{}",
+                        chunk("CONOK,S1,50000,5000,*\r\n")
+                    );
+                    connection.write_all(response.as_bytes()).await.unwrap();
+                    stream_connection = Some(connection);
+                } else if request_line.contains("/control.txt") {
+                    connection
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 9\r\n\r\nREQOK,1\r\n")
+                        .await
+                        .unwrap();
+                    if polling {
+                        pending = after_control;
+                    } else if let Some(connection) = &mut stream_connection {
+                        let notifications = after_control.trim_end_matches("DROP");
+                        let _ = connection.write_all(chunk(notifications).as_bytes()).await;
+                        if notifications.len() < after_control.len() {
+                            stream_connection = None;
+                        }
+                    }
+                } else {
+                    let _ = connection
+                        .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                        .await;
+                }
+            }
+        });
+        (address, requests)
+    }
+
+    #[tokio::test]
+    async fn test_http_streaming_session() {
+        let (address, requests) =
+            spawn_http_mock_server("SUBOK,1,1,1\r\nU,1,1,hello\r\n", false).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::HttpStreaming));
+        client.connection_options.set_content_length(50000).unwrap();
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let mut updates = subscription.updates(8).unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        let (update_sender, update_receiver) = tokio::sync::oneshot::channel();
+        client
+            .connect_with_shutdown(async move {
+                let _ = update_sender.send(updates.next().await);
+            })
+            .await
+            .unwrap();
+
+        let update = update_receiver.await.unwrap().unwrap();
+        assert_eq!(update.get_value("field1"), Some("hello"));
+        assert!(
+            client
+                .get_status_history()
+                .get_transitions()
+                .iter()
+                .any(|transition| {
+                    transition.status == ClientStatus::Connected(ConnectionType::HttpStreaming)
+                })
+        );
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with(
+            "POST /lightstreamer/create_session.txt?LS_protocol=TLCP-2.4.0 HTTP/1.1\n"
+        ));
+        assert!(requests[0].contains("LS_adapter_set=DEMO"));
+        assert!(requests[0].ends_with("&LS_content_length=50000"));
+        assert!(requests[1].starts_with("POST /lightstreamer/control.txt"));
+        // The affinity cookie set on the stream connection goes with the control requests.
+        assert!(requests[1].contains("\nCookie: AWSALB=node-1\r\n"));
+        assert!(requests[1].contains("LS_op=add"));
+        assert!(requests[1].ends_with("&LS_session=S1"));
+    }
+
+    #[tokio::test]
+    async fn test_transport_fallback_chain() {
+        let (address, requests) =
+            spawn_http_mock_server("SUBOK,1,1,1\r\nU,1,1,hello\r\n", true).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client.connection_options.set_http_streaming_timeout(100);
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let mut updates = subscription.updates(8).unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        let (update_sender, update_receiver) = tokio::sync::oneshot::channel();
+        client
+            .connect_with_shutdown(async move {
+                let _ = update_sender.send(updates.next().await);
+            })
+            .await
+            .unwrap();
+
+        let update = update_receiver.await.unwrap().unwrap();
+        assert_eq!(update.get_value("field1"), Some("hello"));
+        assert!(
+            client
+                .get_status_history()
+                .get_transitions()
+                .iter()
+                .any(|transition| {
+                    transition.status == ClientStatus::Connected(ConnectionType::HttpPolling)
+                })
+        );
+        // The WebSocket handshake is refused, and the stream connection never answered.
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("GET /lightstreamer"));
+        assert!(requests[1].starts_with("POST /lightstreamer/create_session.txt"));
+        assert!(!requests[1].contains("LS_polling"));
+        assert!(requests[2].starts_with("POST /lightstreamer/create_session.txt"));
+        assert!(requests[2].contains("LS_polling=true"));
+    }
+
+    #[tokio::test]
+    async fn test_http_polling_session() {
+        let (address, requests) =
+            spawn_http_mock_server("SUBOK,1,1,1\r\nU,1,1,hello\r\n", true).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::HttpPolling));
+        client.connection_options.set_idle_timeout(1000).unwrap();
+        client
+            .connection_options
+            .set_polling_interval(2000)
+            .unwrap();
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let mut updates = subscription.updates(8).unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        let (update_sender, update_receiver) = tokio::sync::oneshot::channel();
+        client
+            .connect_with_shutdown(async move {
+                let _ = update_sender.send(updates.next().await);
+            })
+            .await
+            .unwrap();
+
+        let update = update_receiver.await.unwrap().unwrap();
+        assert_eq!(update.get_value("field1"), Some("hello"));
+        assert!(
+            client
+                .get_status_history()
+                .get_transitions()
+                .iter()
+                .any(|transition| {
+                    transition.status == ClientStatus::Connected(ConnectionType::HttpPolling)
+                })
+        );
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("POST /lightstreamer/create_session.txt"));
+        assert!(
+            requests[0].ends_with("&LS_polling=true&LS_polling_millis=2000&LS_idle_millis=1000")
+        );
+        let bind_request = requests
+            .iter()
+            .find(|request| request.starts_with("POST /lightstreamer/bind_session.txt"))
+            .unwrap();
+        assert!(bind_request.contains("LS_session=S1"));
+        assert!(
+            bind_request.ends_with("&LS_polling=true&LS_polling_millis=2000&LS_idle_millis=1000")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_forced_transport_validation() {
+        let result = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        );
+        assert!(result.is_ok());
+        let mut client = result.unwrap();
+
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsPolling));
+        let shutdown_signal = Arc::new(Notify::new());
+        let result = client.connect(shutdown_signal).await;
+        assert!(result.is_err());
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+    }
+
+    #[test]
+    fn test_shared_cookie_store_applies_to_siblings() {
+        let server_address = Some("https://push.example.com/");
+        let mut client = LightstreamerClient::new(server_address, None, None, None).unwrap();
+        let mut sibling = LightstreamerClient::new(server_address, None, None, None).unwrap();
+        sibling.set_cookie_store(client.get_cookie_store());
+        assert!(
+            client
+                .get_websocket_request()
+                .unwrap()
+                .headers()
+                .get("cookie")
+                .is_none()
+        );
+
+        client.get_cookie_store().add_cookies(
+            &Url::parse("wss://push.example.com/").unwrap(),
+            &[Cookie::new("AWSALB", "node-2")],
+        );
+
+        let request = sibling.get_websocket_request().unwrap();
+        assert_eq!(request.headers().get("cookie").unwrap(), "AWSALB=node-2");
+        client.set_cookie_store(CookieStore::new());
+        assert!(
+            client
+                .get_websocket_request()
+                .unwrap()
+                .headers()
+                .get("cookie")
+                .is_none()
+        );
+    }
+
+    /// Accepts a WebSocket connection for each of the given sessions, answering the session
+    /// creation request with the given notifications. Returns the address of the server.
+    async fn spawn_mock_server(sessions: Vec<&'static str>) -> String {
+        spawn_recording_mock_server(sessions).await.0
+    }
+
+    /// Like `spawn_mock_server()`, also answering session recovery requests and unsubscriptions,
+    /// and dropping the connection after notifications ending with `DROP`. A `DOWN:<ms>` session
+    /// closes every connection at once for the given time. Returns the address of the server and
+    /// the requests received.
+    // The handshake callback has to return tungstenite's error response as is.
+    #[allow(clippy::result_large_err)]
+    async fn spawn_recording_mock_server(
+        sessions: Vec<&'static str>,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        use tokio_tungstenite::tungstenite::handshake::server::{
+            Request as HandshakeRequest, Response as HandshakeResponse,
+        };
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            for notifications in sessions {
+                if let Some(millis) = notifications.strip_prefix("DOWN:") {
+                    let until = Instant::now() + Duration::from_millis(millis.parse().unwrap());
+                    while let Ok(Ok((stream, _))) =
+                        tokio::time::timeout_at(until, listener.accept()).await
+                    {
+                        drop(stream);
+                    }
+                    continue;
+                }
+                let (stream, _) = listener.accept().await.unwrap();
+                let accept_protocol = |_: &HandshakeRequest, mut response: HandshakeResponse| {
+                    response.headers_mut().insert(
+                        "sec-websocket-protocol",
+                        HeaderValue::from_static(LightstreamerClient::SEC_WEBSOCKET_PROTOCOL),
+                    );
+                    Ok(response)
+                };
+                let mut ws_stream = tokio_tungstenite::accept_hdr_async(stream, accept_protocol)
+                    .await
+                    .unwrap();
+                while let Some(Ok(Message::Text(text))) = ws_stream.next().await {
+                    received.lock().unwrap().push(text.to_string());
+                    let answer = if text.starts_with("wsok") {
+                        "WSOK\r\n".to_string()
+                    } else if text.starts_with("create_session") || text.starts_with("bind_session")
+                    {
+                        notifications.to_string()
+                    } else if text.starts_with("control") && text.contains("LS_op=delete") {
+                        // Confirm the unsubscriptions, as the Server does.
+                        text.lines()
+                            .filter_map(|line| {
+                                line.split('&')
+                                    .find_map(|param| param.strip_prefix("LS_subId="))
+                            })
+                            .map(|id| format!("UNSUB,{}\r\n", id))
+                            .collect()
+                    } else {
+                        continue;
+                    };
+                    let (answer, drop) = match answer.strip_suffix("DROP") {
+                        Some(answer) => (answer, true),
+                        None => (answer.as_str(), false),
+                    };
+                    if ws_stream.send(Message::Text(answer.into())).await.is_err() || drop {
+                        break;
+                    }
+                }
+            }
+        });
+        (address, requests)
+    }
+
+    #[tokio::test]
+    async fn test_connect_stops_on_injected_shutdown() {
+        let address = spawn_mock_server(vec!["CONOK,S1,50000,5000,*\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let (trigger, shutdown) = tokio::sync::oneshot::channel::<()>();
+        let history = client.get_status_history();
+        tokio::spawn(async move {
+            // Shut down once the session is established.
+            while !history
+                .get_last()
+                .is_some_and(|transition| transition.status.to_string().starts_with("CONNECTED"))
+            {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            let _ = trigger.send(());
+        });
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_with_shutdown(async {
+                let _ = shutdown.await;
+            }),
+        )
+        .await
+        .expect("the client did not stop");
+        assert!(result.is_ok());
+        assert_eq!(client.get_status().to_string(), "DISCONNECTED");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_is_not_delayed_by_loop() {
+        let address = spawn_mock_server(vec!["CONOK,S1,50000,5000,*\r\nLOOP,60000\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let history = client.get_status_history();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_with_shutdown(async move {
+                while !history.get_last().is_some_and(|transition| {
+                    transition.status.to_string().starts_with("CONNECTED")
+                }) {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }),
+        )
+        .await
+        .expect("the client waited for the rebind delay");
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_end_cause_reactions() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nEND,48,Max duration\r\n",
+            "CONOK,S2,50000,5000,*\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let server_errors = Arc::new(Mutex::new(Vec::new()));
+        client.add_listener(Box::new(MockClientListener::with_shared_data(
+            Arc::new(Mutex::new(Vec::new())),
+            Arc::new(Mutex::new(Vec::new())),
+            server_errors.clone(),
+        )));
+
+        // 48 opens a new session right away, 41 gives up.
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(
+            *server_errors.lock().unwrap(),
+            vec![
+                (48, "Max duration".to_string()),
+                (41, "License".to_string())
+            ]
+        );
+        let statuses: Vec<String> = client
+            .get_status_history()
+            .get_transitions()
+            .iter()
+            .map(|transition| transition.to_string())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                "CONNECTING (connect requested)",
+                "CONNECTED:WS-STREAMING (session S1 created)",
+                "DISCONNECTED:WILL-RETRY (END 48: Max duration)",
+                "CONNECTING (retry attempt 1)",
+                "CONNECTED:WS-STREAMING (session S2 created)",
+                "DISCONNECTED (END 41: License)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prog_skips_resent_notifications() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\n\
+             CONF,1,unlimited,filtered\r\n\
+             CONF,1,unlimited,filtered\r\n\
+             PROG,1\r\n\
+             CONF,1,unlimited,filtered\r\n\
+             CONF,1,unlimited,filtered\r\n\
+             END,31,Closed\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let diagnostics = client.get_diagnostics();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(diagnostics.get_prog(), 3);
+        assert_eq!(diagnostics.get_duplicate_notifications(), 1);
+        assert_eq!(diagnostics.get_lost_notifications(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_recovery_delivers_resent_updates_once() {
+        let address = spawn_mock_server(vec![
+            "CONOK,Sa1,50000,5000,*\r\nSUBOK,1,1,1\r\nU,1,1,a\r\nU,1,1,b\r\nDROP",
+            "CONOK,Sa1,50000,5000,*\r\nPROG,1\r\nU,1,1,a\r\nU,1,1,b\r\nU,1,1,c\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Distinct,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["event".to_string()]),
+        )
+        .unwrap();
+        let mut changes = subscription.watch_field_changes(1, "event").unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(change) = changes.try_recv() {
+            events.push(change.new_value);
+        }
+        assert_eq!(events, vec!["a", "b", "c"]);
+        assert_eq!(client.get_diagnostics().get_duplicate_notifications(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_session_recovery() {
+        let (address, requests) = spawn_recording_mock_server(vec![
+            "CONOK,Sa1,50000,5000,*\r\nPROG,2\r\nDROP",
+            "CONOK,Sa1,50000,5000,*\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let connect_events = events.clone();
+        client.on_connect(move |info| {
+            connect_events
+                .lock()
+                .unwrap()
+                .push(format!("connect {}", info.get_session_id()))
+        });
+        let disconnect_events = events.clone();
+        client.on_disconnect(move |cause| {
+            disconnect_events
+                .lock()
+                .unwrap()
+                .push(format!("disconnect {}", cause))
+        });
+        let recovery_events = events.clone();
+        client.on_session_recovered(move |info| {
+            recovery_events
+                .lock()
+                .unwrap()
+                .push(format!("recovered {}", info.get_session_id()))
+        });
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let events = events.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0], "connect Sa1");
+        assert!(events[1].starts_with("disconnect "));
+        assert_eq!(events[2], "recovered Sa1");
+        assert_eq!(events[3], "disconnect END 41: License");
+        let bind_request = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|request| request.starts_with("bind_session"))
+            .cloned()
+            .unwrap();
+        assert!(bind_request.contains("LS_session=Sa1"));
+        assert!(bind_request.contains("LS_recovery_from=2"));
+        let statuses: Vec<String> = client
+            .get_status_history()
+            .get_transitions()
+            .iter()
+            .map(|transition| transition.status.to_string())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                "CONNECTING",
+                "CONNECTED:WS-STREAMING",
+                "DISCONNECTED:TRYING-RECOVERY",
+                "CONNECTED:WS-STREAMING",
+                "DISCONNECTED",
+            ]
+        );
+        assert_eq!(
+            client.get_status_history().get_transitions()[3].cause,
+            StatusChangeCause::SessionRecovered {
+                session_id: "Sa1".to_string()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_recovery_over_http_streaming() {
+        let (address, requests) =
+            spawn_http_mock_server("SUBOK,1,1,1\r\nU,1,1,hello\r\nDROP", false).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::HttpStreaming));
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        let status_history = client.get_status_history();
+        client
+            .connect_with_shutdown(async move {
+                while !status_history.get_transitions().iter().any(|transition| {
+                    matches!(transition.cause, StatusChangeCause::SessionRecovered { .. })
+                }) {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .unwrap();
+
+        let bind_request = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|request| request.starts_with("POST /lightstreamer/bind_session.txt"))
+            .cloned()
+            .unwrap();
+        assert!(bind_request.contains("LS_session=S1"));
+        assert!(bind_request.contains("LS_recovery_from=2"));
+        let statuses: Vec<String> = client
+            .get_status_history()
+            .get_transitions()
+            .iter()
+            .map(|transition| transition.status.to_string())
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                "CONNECTING",
+                "CONNECTED:HTTP-STREAMING",
+                "DISCONNECTED:TRYING-RECOVERY",
+                "CONNECTED:HTTP-STREAMING",
+                "DISCONNECTED",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_session_recovery_disabled_creates_new_session() {
+        let (address, requests) = spawn_recording_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nDROP",
+            "CONOK,S2,50000,5000,*\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client
+            .connection_options
+            .set_session_recovery_timeout(0)
+            .unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let session_requests: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| !request.starts_with("wsok"))
+            .map(|request| request.split("\r\n").next().unwrap().to_string())
+            .collect();
+        assert_eq!(session_requests, vec!["create_session", "create_session"]);
+        assert!(
+            client
+                .get_status_history()
+                .get_transitions()
+                .iter()
+                .any(|transition| transition.cause == StatusChangeCause::RecoveryAbandoned)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_new_session_is_retried_while_the_server_is_down() {
+        let (address, requests) = spawn_recording_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nDROP",
+            "DOWN:300",
+            "CONOK,S2,50000,5000,*\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_retry_delay(20).unwrap();
+        client
+            .connection_options
+            .set_session_recovery_timeout(50)
+            .unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let session_requests: Vec<String> = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| !request.starts_with("wsok"))
+            .map(|request| request.split("\r\n").next().unwrap().to_string())
+            .collect();
+        assert_eq!(session_requests, vec!["create_session", "create_session"]);
+        let transitions = client.get_status_history().get_transitions();
+        assert!(
+            transitions
+                .iter()
+                .any(|transition| transition.cause == StatusChangeCause::RecoveryAbandoned)
+        );
+        let connected = transitions
+            .iter()
+            .filter(|transition| matches!(transition.status, ClientStatus::Connected(_)))
+            .count();
+        assert_eq!(connected, 2);
+        assert!(matches!(
+            transitions.last().unwrap().cause,
+            StatusChangeCause::SessionEnded { code: 41, .. }
+        ));
+    }
+
+    #[derive(Debug, Default)]
+    struct SessionReplacementRecorder(Mutex<Vec<SessionReplacement>>);
+
+    impl ClientListener for SessionReplacementRecorder {
+        fn on_session_replaced(&self, replacement: &SessionReplacement) {
+            self.0.lock().unwrap().push(replacement.clone());
+        }
+
+        fn on_status_change(&self, _status: &str) {}
+    }
+
+    #[tokio::test]
+    async fn test_session_replacement_is_notified() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nDROP",
+            "CONOK,S2,50000,5000,*\r\nSUBOK,2,1,1\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client
+            .connection_options
+            .set_session_recovery_timeout(0)
+            .unwrap();
+        let recorder = Arc::new(SessionReplacementRecorder::default());
+        client.add_listener_weak(&recorder);
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![SessionReplacement::new("S1", "S2", 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_data_gaps_are_notified_to_subscriptions() {
+        struct GapRecorder(Arc<Mutex<Vec<DataGap>>>);
+
+        impl SubscriptionListener for GapRecorder {
+            fn on_data_gap(&mut self, gap: &DataGap) {
+                self.0.lock().unwrap().push(gap.clone());
+            }
+        }
+
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nOV,1,1,3\r\nDROP",
+            "CONOK,S2,50000,5000,*\r\nSUBOK,2,1,1\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client
+            .connection_options
+            .set_session_recovery_timeout(0)
+            .unwrap();
+        let gaps = Arc::new(Mutex::new(Vec::new()));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .add_listener(Box::new(GapRecorder(gaps.clone())))
+            .unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let gaps = gaps.lock().unwrap();
+        let causes: Vec<DataGapCause> = gaps.iter().map(|gap| gap.cause).collect();
+        assert_eq!(
+            causes,
+            vec![
+                DataGapCause::Overflow,
+                DataGapCause::RecoveryFailed,
+                DataGapCause::SessionEnded,
+            ]
+        );
+        assert_eq!(gaps[0].item_pos, Some(1));
+        assert_eq!(gaps[0].lost_updates, Some(3));
+        assert!(gaps[1].since.unwrap() <= gaps[1].detected_at);
+    }
+
+    #[tokio::test]
+    async fn test_updates_are_dispatched_by_priority() {
+        struct OrderRecorder(Arc<Mutex<Vec<String>>>);
+
+        impl SubscriptionListener for OrderRecorder {
+            fn on_item_update(&self, update: &ItemUpdate) {
+                let value = update.get_value("last_price").unwrap_or_default();
+                self.0.lock().unwrap().push(value.to_string());
+            }
+        }
+
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\nU,1,1,low1\r\nU,2,1,high1\r\nU,1,1,low2\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let order = Arc::new(Mutex::new(Vec::new()));
+        for (item, priority) in [("reference", 0), ("limits", 10)] {
+            let mut subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            subscription.set_priority(priority);
+            subscription
+                .add_listener(Box::new(OrderRecorder(order.clone())))
+                .unwrap();
+            client.add_subscription(subscription).unwrap();
+        }
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        // The updates of the same subscription keep their order.
+        assert_eq!(*order.lock().unwrap(), vec!["high1", "low1", "low2"]);
+    }
+
+    #[tokio::test]
+    async fn test_server_selector_overrides_the_configured_address() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nDROP",
+            "CONOK,S1,50000,5000,*\r\nEND,41,License\r\n",
+        ])
+        .await;
+        // Nothing listens on the configured address.
+        let mut client =
+            LightstreamerClient::new(Some("http://127.0.0.1:1/"), Some("DEMO"), None, None)
+                .unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let selections = Arc::new(Mutex::new(Vec::new()));
+        let recorded = selections.clone();
+        client.set_server_selector(move |selection| {
+            recorded.lock().unwrap().push(selection.clone());
+            Some(address.clone())
+        });
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let selections = selections.lock().unwrap();
+        assert_eq!(selections.len(), 2);
+        assert_eq!(
+            selections[0].configured_address.as_deref(),
+            Some("http://127.0.0.1:1/")
+        );
+        assert_eq!(selections[0].previous_address, None);
+        assert!(!selections[0].recovering);
+        assert!(selections[1].previous_address.is_some());
+        assert_ne!(
+            selections[1].previous_address,
+            selections[1].configured_address
+        );
+        assert!(selections[1].recovering);
+    }
+
+    #[test]
+    fn test_spawn_runs_on_the_injected_runtime() {
+        let caller = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        let injected = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("injected-runtime")
+            .enable_all()
+            .build()
+            .unwrap();
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        let recorded = threads.clone();
+        let handle = injected.handle().clone();
+        let (client, result) = caller.block_on(async move {
+            let address =
+                spawn_mock_server(vec!["CONOK,S1,50000,5000,*\r\nEND,41,License\r\n"]).await;
+            let mut client =
+                LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+            client
+                .connection_options
+                .set_forced_transport(Some(Transport::WsStreaming));
+            client.set_runtime_handle(Some(handle));
+            client.on_connect(move |_| {
+                let thread = std::thread::current().name().map(str::to_string);
+                recorded.lock().unwrap().push(thread);
+            });
+            client.spawn(Arc::new(Notify::new())).await.unwrap()
+        });
+
+        assert!(result.is_ok());
+        assert!(client.get_runtime_handle().is_some());
+        assert_eq!(
+            *threads.lock().unwrap(),
+            vec![Some("injected-runtime".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_ordered_dispatch_keeps_the_arrival_order() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\nU,1,1,low1\r\nU,2,1,high1\r\nU,1,1,low2\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        // The priorities change the order of the listeners, but not the one of the queue.
+        for (item, priority) in [("reference", 0), ("limits", 10)] {
+            let mut subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            subscription.set_priority(priority);
+            client.add_subscription(subscription).unwrap();
+        }
+        let mut updates = client.enable_ordered_dispatch();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let mut received = Vec::new();
+        while let Ok(ordered_update) = updates.try_recv() {
+            let value = ordered_update
+                .update
+                .get_value("last_price")
+                .unwrap_or_default();
+            received.push((
+                ordered_update.sequence,
+                ordered_update.subscription_id,
+                value.to_string(),
+            ));
+        }
+        assert_eq!(
+            received,
+            vec![
+                (0, 1, "low1".to_string()),
+                (1, 2, "high1".to_string()),
+                (2, 1, "low2".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_noop_updates_are_suppressed() {
+        struct PriceRecorder(Arc<Mutex<Vec<String>>>);
+
+        impl SubscriptionListener for PriceRecorder {
+            fn on_item_update(&self, update: &ItemUpdate) {
+                let value = update.get_value("last_price").unwrap_or_default();
+                self.0.lock().unwrap().push(value.to_string());
+            }
+        }
+
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,2\r\nU,1,1,10|a\r\nU,1,1,10|a\r\nU,1,1,|\r\nU,1,1,11|\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let prices = Arc::new(Mutex::new(Vec::new()));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string(), "status".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .set_requested_snapshot(Some(Snapshot::No))
+            .unwrap();
+        subscription
+            .set_noop_update_suppression_enabled(true)
+            .unwrap();
+        subscription
+            .add_listener(Box::new(PriceRecorder(prices.clone())))
+            .unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(*prices.lock().unwrap(), vec!["10", "11"]);
+    }
+
+    #[tokio::test]
+    async fn test_field_codecs_decode_the_updates() {
+        type Decoded = (Option<Vec<u8>>, Option<bool>);
+        struct DecodedRecorder(Arc<Mutex<Vec<Decoded>>>);
+
+        impl SubscriptionListener for DecodedRecorder {
+            fn on_item_update(&self, update: &ItemUpdate) {
+                let payload = update.get_decoded_value::<Vec<u8>>("payload").cloned();
+                let buy = update.get_decoded_value::<bool>("side").copied();
+                self.0.lock().unwrap().push((payload, buy));
+            }
+        }
+
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,2\r\nU,1,1,TWFu|B\r\nU,1,1,T|\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let decoded = Arc::new(Mutex::new(Vec::new()));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["payload".to_string(), "side".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .set_requested_snapshot(Some(Snapshot::No))
+            .unwrap();
+        subscription
+            .set_field_codec("payload", Base64Codec)
+            .unwrap();
+        subscription
+            .set_field_codec("side", |value: &str| Ok(value == "B"))
+            .unwrap();
+        subscription
+            .add_listener(Box::new(DecodedRecorder(decoded.clone())))
+            .unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        // The malformed payload is delivered without decoded value.
+        assert_eq!(
+            *decoded.lock().unwrap(),
+            vec![(Some(b"Man".to_vec()), Some(true)), (None, Some(true))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscriptions_added_before_connect_are_batched() {
+        let (address, requests) =
+            spawn_recording_mock_server(vec!["CONOK,S1,50000,5000,*\r\nEND,41,License\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        for item in ["item1", "item2", "item3"] {
+            let subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            client.add_subscription(subscription).unwrap();
+        }
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        // The server may still be reading the last frame.
+        let mut control_frames = Vec::new();
+        for _ in 0..100 {
+            control_frames = requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|request| request.starts_with("control"))
+                .cloned()
+                .collect();
+            if !control_frames.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(control_frames.len(), 1);
+        let lines: Vec<&str> = control_frames[0].split("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        for (index, item) in ["item1", "item2", "item3"].iter().enumerate() {
+            assert!(lines[index + 1].contains(&format!("LS_reqId={}", index + 1)));
+            assert!(lines[index + 1].contains(&format!("LS_group={}", item)));
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct OversizedMessageRecorder(Mutex<Vec<OversizedMessageError>>);
+
+    impl ClientListener for OversizedMessageRecorder {
+        fn on_oversized_message(&self, error: &OversizedMessageError) {
+            self.0.lock().unwrap().push(error.clone());
+        }
+
+        fn on_status_change(&self, _status: &str) {}
+    }
+
+    #[tokio::test]
+    async fn test_oversized_messages_are_discarded_and_resubscribed() {
+        let (address, requests) = spawn_recording_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nU,1,1,0123456789abcdefghij\r\nU,1,1,ok\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client
+            .connection_options
+            .set_max_message_size(Some(24))
+            .unwrap();
+        client
+            .connection_options
+            .set_resubscribe_on_oversized_message(true);
+        let recorder = Arc::new(OversizedMessageRecorder::default());
+        client.add_listener_weak(&recorder);
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        let meter = subscription.get_bandwidth_meter();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![OversizedMessageError::new(26, 24, Some(1), true)]
+        );
+        // The session went on, but the next update refers to the old ID.
+        assert_eq!(client.get_status().to_string(), "DISCONNECTED");
+        assert_eq!(meter.get_usage().notifications, 1);
+        let mut control_frames = Vec::new();
+        for _ in 0..100 {
+            control_frames = requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|request| request.starts_with("control"))
+                .cloned()
+                .collect();
+            if control_frames.len() == 3 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(control_frames[1].contains("LS_op=delete&LS_subId=1"));
+        assert!(control_frames[2].contains("LS_subId=2"));
+    }
+
+    #[tokio::test]
+    async fn test_health_probe_follows_the_session() {
+        let address = spawn_mock_server(vec!["CONOK,S1,50000,5000,*\r\nEND,41,License\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let probe = client.get_health_probe();
+        assert_eq!(probe.get_last_activity_age(), None);
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let report = probe.get_report(Duration::from_secs(60));
+        assert_eq!(report.status, "DISCONNECTED");
+        assert!(!report.ready);
+        assert!(report.live);
+        assert!(report.last_activity_age_ms.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_request_error_describes_the_refused_subscription() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nREQERR,1,21,bad Group name\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string(), "item2".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        let subscribed = subscription.await_subscribed();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let err = subscribed.await.unwrap_err();
+        let LightstreamerError::Server(server_error) = err else {
+            panic!("expected a server error, got {:?}", err);
+        };
+        assert_eq!(server_error.get_code(), 21);
+        assert_eq!(
+            server_error.get_request(),
+            Some("subscription 1 to items [item1, item2] in MERGE mode")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_subscription_confirmation_carries_the_counts() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBCMD,1,1,4,1,2\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let subscription = Subscription::new(
+            SubscriptionMode::Command,
+            Some(vec!["portfolio".to_string()]),
+            Some(
+                ["key", "command", "qty", "price"]
+                    .map(String::from)
+                    .to_vec(),
+            ),
+        )
+        .unwrap();
+        let subscribed = subscription.await_subscribed();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let confirmation = subscribed.await.unwrap();
+        assert_eq!(confirmation.subscription_id, 1);
+        assert_eq!(confirmation.items, 1);
+        assert_eq!(confirmation.fields, 4);
+        assert_eq!(confirmation.key_pos, Some(1));
+        assert_eq!(confirmation.command_pos, Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_fails_when_the_client_is_gone() {
+        let client = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        )
+        .unwrap();
+        let subscription_sender = client.subscription_sender.clone();
+        drop(client);
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+
+        let subscribed = LightstreamerClient::subscribe(subscription_sender, subscription);
+
+        assert!(matches!(
+            subscribed.await,
+            Err(LightstreamerError::IllegalState(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_refuses_active_subscriptions() {
+        let mut client = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        )
+        .unwrap();
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        subscription.on_subscription_request();
+
+        let subscribed =
+            LightstreamerClient::subscribe(client.subscription_sender.clone(), subscription);
+
+        let Err(LightstreamerError::SubscriptionState(error)) = subscribed.await else {
+            panic!("expected a subscription state error");
+        };
+        assert_eq!(error.get_operation(), "subscribe");
+        assert_eq!(error.get_state(), SubscriptionState::Activating);
+        assert!(client.subscription_receiver.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_resolves_on_unsub() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let mut confirmations = Vec::new();
+        for item in ["item1", "item2"] {
+            let subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            confirmations.push(subscription.await_subscribed());
+            client.add_subscription(subscription).unwrap();
+        }
+        let subscription_sender = client.subscription_sender.clone();
+        let shutdown_signal = Arc::new(Notify::new());
+        let shutdown = shutdown_signal.clone();
+        let unsubscribed = tokio::spawn(async move {
+            let confirmation = confirmations.remove(0).await.unwrap();
+            let unsubscribed = tokio::time::timeout(
+                Duration::from_secs(5),
+                LightstreamerClient::unsubscribe(subscription_sender, confirmation.subscription_id),
+            )
+            .await;
+            shutdown.notify_one();
+            unsubscribed
+        });
+
+        client.connect(shutdown_signal).await.unwrap();
+
+        // The other subscription keeps the session open: only UNSUB can resolve the future.
+        unsubscribed.await.unwrap().unwrap().unwrap();
+        assert_eq!(client.get_subscriptions().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribe_fails_when_the_client_is_gone() {
+        let client = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        )
+        .unwrap();
+        let unsubscribed = LightstreamerClient::unsubscribe(client.subscription_sender.clone(), 1);
+        drop(client);
+
+        assert!(matches!(
+            unsubscribed.await,
+            Err(LightstreamerError::IllegalState(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_resolved_by_their_answers() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nREQOK,1\r\nREQERR,2,21,bad Group name\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let mut responses = Vec::new();
+        for item in ["item1", "item2"] {
+            let mut subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            responses.push(subscription.await_response());
+            client.add_subscription(subscription).unwrap();
+        }
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let mut responses = responses.into_iter();
+        responses.next().unwrap().await.unwrap();
+        let err = responses.next().unwrap().await.unwrap_err();
+        let LightstreamerError::Server(server_error) = err else {
+            panic!("expected a server error, got {:?}", err);
+        };
+        assert_eq!(server_error.get_code(), 21);
+        assert_eq!(
+            server_error.get_request(),
+            Some("subscription 2 to items [item2] in MERGE mode")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_subscription_times_out_after_retries() {
+        let (address, requests) =
+            spawn_recording_mock_server(vec!["CONOK,S1,50000,5000,*\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_subscribe_timeout(50);
+        client.connection_options.set_subscribe_retries(1);
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        let subscribed = subscription.await_subscribed();
+        client.add_subscription(subscription).unwrap();
+
+        let (outcome_sender, outcome_receiver) = tokio::sync::oneshot::channel();
+        client
+            .connect_with_shutdown(async move {
+                let _ = outcome_sender.send(subscribed.await);
+            })
+            .await
+            .unwrap();
+
+        let err = outcome_receiver.await.unwrap().unwrap_err();
+        let LightstreamerError::Timeout(timeout_error) = err else {
+            panic!("expected a timeout error, got {:?}", err);
+        };
+        assert_eq!(
+            timeout_error.get_operation(),
+            "subscription 2 to items [item1] in MERGE mode"
+        );
+        assert_eq!(timeout_error.get_timeout(), Duration::from_millis(50));
+        // The last unsubscription may still be on its way to the server.
+        let mut control_frames = String::new();
+        for _ in 0..100 {
+            control_frames = requests
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|request| request.starts_with("control"))
+                .cloned()
+                .collect::<Vec<String>>()
+                .join("\n");
+            if control_frames.contains("LS_op=delete&LS_subId=2") {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(control_frames.matches("LS_op=add").count(), 2);
+        assert!(control_frames.contains("LS_op=delete&LS_subId=1"));
+        assert!(control_frames.contains("LS_op=delete&LS_subId=2"));
+    }
+
+    #[tokio::test]
+    async fn test_reverse_heartbeats_are_sent_when_silent() {
+        let (address, requests) =
+            spawn_recording_mock_server(vec!["CONOK,S1,50000,5000,*\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_retry_delay(10).unwrap();
+        client
+            .connection_options
+            .set_reverse_heartbeat_interval(20)
+            .unwrap();
+
+        let received = requests.clone();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            client.connect_with_shutdown(async move {
+                while received
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .filter(|request| request.starts_with("heartbeat"))
+                    .count()
+                    < 2
+                {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            }),
+        )
+        .await
+        .expect("no reverse heartbeats were sent")
+        .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[1].contains("LS_inactivity_millis=20"));
+        assert!(requests.iter().any(|request| request == "heartbeat\r\n"));
+    }
+
+    #[tokio::test]
+    async fn test_last_error_keeps_the_end_cause() {
+        let address = spawn_mock_server(vec!["CONOK,S1,50000,5000,*\r\nEND,41,License\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        assert!(client.last_error().is_none());
+        let last_error = client.get_last_error_handle();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(client.last_error(), last_error.get());
+        let last_error = last_error.get().unwrap();
+        assert_eq!(last_error.severity, ErrorSeverity::Fatal);
+        assert_eq!(
+            last_error.cause,
+            StatusChangeCause::SessionEnded {
+                code: 41,
+                message: "License".to_string(),
+            }
+        );
+        assert_eq!(last_error.to_string(), "fatal error: END 41: License");
+    }
+
+    #[tokio::test]
+    async fn test_bandwidth_is_attributed_to_subscriptions() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\nU,1,1,abc\r\nU,2,1,x\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let mut meters = Vec::new();
+        for item in ["item1", "item2"] {
+            let subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            meters.push(subscription.get_bandwidth_meter());
+            client.add_subscription(subscription).unwrap();
+        }
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        // SUBOK and U lines, each with its CRLF.
+        let first = meters[0].get_usage();
+        assert_eq!((first.bytes, first.notifications), (13 + 11, 2));
+        let second = meters[1].get_usage();
+        assert_eq!((second.bytes, second.notifications), (13 + 9, 2));
+        assert!(first.average_rate > 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_update_values_keep_their_case() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,2\r\nU,1,1,Open|NYSE\r\nu,1,1,Closed|\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["status".to_string(), "venue".to_string()]),
+        )
+        .unwrap();
+        let mut changes = subscription.watch_field_changes(1, "status").unwrap();
+        client.add_subscription(subscription).unwrap();
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(changes.try_recv().unwrap().new_value, "Open");
+        assert_eq!(changes.try_recv().unwrap().new_value, "Closed");
     }
 
-    #[test]
-    fn test_get_status() {
-        let result = LightstreamerClient::new(
-            Some("http://test.lightstreamer.com"),
-            Some("DEMO"),
-            None,
-            None,
+    #[tokio::test]
+    async fn test_feed_aggregator_merges_tagged_updates() {
+        let mut aggregator = FeedAggregator::new();
+        for (source, script) in [
+            (
+                "eu",
+                "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nU,1,1,1.5\r\nEND,41,License\r\n",
+            ),
+            (
+                "us",
+                "CONOK,S2,50000,5000,*\r\nSUBOK,1,1,1\r\nU,1,1,2.5\r\nEND,41,License\r\n",
+            ),
+        ] {
+            let address = spawn_mock_server(vec![script]).await;
+            let mut client =
+                LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+            client
+                .connection_options
+                .set_forced_transport(Some(Transport::WsStreaming));
+            aggregator.add_source(source, client).unwrap();
+            let subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec!["item1".to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            aggregator.add_subscription(source, subscription).unwrap();
+        }
+        let mut updates = aggregator.take_updates().unwrap();
+        let health = aggregator.get_health();
+
+        aggregator.run(Arc::new(Notify::new())).await;
+
+        let mut received = Vec::new();
+        while let Ok(tagged) = updates.try_recv() {
+            received.push((
+                tagged.source,
+                tagged.update.get_value("last_price").unwrap().to_string(),
+            ));
+        }
+        received.sort();
+        assert_eq!(
+            received,
+            vec![
+                ("eu".to_string(), "1.5".to_string()),
+                ("us".to_string(), "2.5".to_string())
+            ]
         );
-        assert!(result.is_ok());
-        let client = result.unwrap();
-        match client.get_status() {
-            ClientStatus::Disconnected(DisconnectionType::WillRetry) => {}
-            _ => panic!("Expected initial status to be DISCONNECTED:WILL-RETRY"),
+        for source in ["eu", "us"] {
+            let source_health = health.get_source(source).unwrap();
+            assert_eq!(source_health.updates, 1);
+            assert_eq!(source_health.last_error.as_deref(), Some("41: License"));
+            assert!(!source_health.is_connected());
         }
     }
 
-    #[test]
-    fn test_get_subscriptions() {
-        let result = LightstreamerClient::new(
-            Some("http://test.lightstreamer.com"),
-            Some("DEMO"),
-            None,
-            None,
+    #[tokio::test]
+    async fn test_reconnections_wait_for_the_limiter() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nEND,48,Max duration\r\n",
+            "CONOK,S2,50000,5000,*\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let limiter = ReconnectLimiter::new(1, Duration::ZERO).unwrap();
+        client.set_reconnect_limiter(Some(limiter.clone()));
+
+        // Another client is opening a connection: the reconnection waits for it.
+        let permit = limiter.acquire().await;
+        let connect = tokio::spawn(async move {
+            client.connect(Arc::new(Notify::new())).await.unwrap();
+            client
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!connect.is_finished());
+        drop(permit);
+
+        let client = connect.await.unwrap();
+        assert_eq!(
+            *client.get_status(),
+            ClientStatus::Disconnected(DisconnectionType::NoRetry)
         );
-        assert!(result.is_ok());
-        let client = result.unwrap();
-        assert_eq!(client.get_subscriptions().len(), 0);
+        // The permit is given back once the session is established.
+        assert_eq!(limiter.get_available_permits(), 1);
+    }
+
+    #[derive(Debug)]
+    struct TokenInterceptor;
+
+    impl RequestInterceptor for TokenInterceptor {
+        fn intercept(&self, request: &mut OutboundRequest) {
+            if request.get_name() == "create_session" {
+                request.set_param("LS_custom_token", "abc");
+            }
+        }
     }
 
     #[tokio::test]
-    async fn test_connect_with_no_server_address() {
-        let result = LightstreamerClient::new(None, Some("DEMO"), None, None);
-        assert!(result.is_ok());
-        let mut client = result.unwrap();
-        let shutdown_signal = Arc::new(Notify::new());
-        let result = client.connect(shutdown_signal).await;
-        assert!(result.is_err());
+    async fn test_request_interceptors_edit_requests() {
+        let (address, requests) =
+            spawn_recording_mock_server(vec!["CONOK,S1,50000,5000,*\r\nEND,41,License\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.add_request_interceptor(Box::new(TokenInterceptor));
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let create_request = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|request| request.starts_with("create_session"))
+            .cloned()
+            .unwrap();
+        assert!(create_request.contains("LS_protocol=TLCP-2.4.0&LS_custom_token=abc"));
     }
 
+    #[cfg(feature = "protocol-checks")]
     #[tokio::test]
-    async fn test_forced_transport_validation() {
-        let result = LightstreamerClient::new(
-            Some("http://test.lightstreamer.com"),
-            Some("DEMO"),
-            None,
-            None,
-        );
-        assert!(result.is_ok());
-        let mut client = result.unwrap();
+    async fn test_protocol_violations_are_counted() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nU,3,1,a|b\r\nEND,41,License\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
 
-        client.connection_options.set_forced_transport(None);
-        let shutdown_signal = Arc::new(Notify::new());
-        let result = client.connect(shutdown_signal).await;
-        assert!(result.is_err());
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        assert_eq!(client.get_diagnostics().get_protocol_violations(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_connect_fails_fast_when_probe_fails() {
+        let mut client =
+            LightstreamerClient::new(Some("http://127.0.0.1:1/"), Some("DEMO"), None, None)
+                .unwrap();
         client
             .connection_options
             .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_transport_probe_timeout(2000);
+
+        let result = client.connect(Arc::new(Notify::new())).await;
+        assert!(
+            result
+                .unwrap_err()
+                .to_string()
+                .contains("WebSocket transport is not available")
+        );
+        // The outcome is cached for the next attempts.
+        assert_eq!(
+            get_cached_probe("ws://127.0.0.1:1/").map(|result| result.is_websocket_available()),
+            Some(false)
+        );
     }
 
     #[test]
@@ -1644,6 +6122,157 @@ mod tests {
         assert!(params_str.contains("LS_subId=42"));
     }
 
+    struct MockMessageListener {
+        aborts: Arc<Mutex<Vec<(String, bool)>>>,
+    }
+
+    impl ClientMessageListener for MockMessageListener {
+        fn on_abort(&self, msg: &str, sent_on_network: bool) {
+            self.aborts
+                .lock()
+                .unwrap()
+                .push((msg.to_string(), sent_on_network));
+        }
+    }
+
+    #[test]
+    fn test_message_params_generation() {
+        let message_request = MessageRequest::new("BUY 100")
+            .with_sequence("orders")
+            .with_delay_timeout(500);
+        let params_str =
+            LightstreamerClient::get_message_params(&message_request, 7, Some(3)).unwrap();
+        assert_eq!(
+            params_str,
+            "LS_reqId=7&LS_message=BUY+100&LS_sequence=orders&LS_msg_prog=3&LS_max_wait=500&LS_outcome=false&LS_ack=false"
+        );
+
+        // LS_max_wait is meaningless for unordered messages.
+        let message_request = MessageRequest::new("ping").with_delay_timeout(500);
+        let params_str =
+            LightstreamerClient::get_message_params(&message_request, 8, None).unwrap();
+        assert!(params_str.contains("LS_sequence=UNORDERED_MESSAGES"));
+        assert!(!params_str.contains("LS_max_wait"));
+        assert!(!params_str.contains("LS_msg_prog"));
+    }
+
+    #[tokio::test]
+    async fn test_write_message_request_tracks_messages_with_listener() {
+        let aborts = Arc::new(Mutex::new(Vec::new()));
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let mut write_stream = Box::pin(futures_util::sink::unfold(
+            sent.clone(),
+            |sent, message: Message| async move {
+                sent.lock().unwrap().push(message.to_string());
+                Ok::<_, std::io::Error>(sent)
+            },
+        ));
+        let mut pending_messages = PendingMessages::default();
+        let mut pending_requests = PendingRequests::default();
+
+        LightstreamerClient::write_message_request(
+            &mut write_stream,
+            &mut pending_messages,
+            &mut pending_requests,
+            MessageRequest::new("fire and forget"),
+            1,
+            &[],
+        )
+        .await
+        .unwrap();
+        LightstreamerClient::write_message_request(
+            &mut write_stream,
+            &mut pending_messages,
+            &mut pending_requests,
+            MessageRequest::new("BUY 100")
+                .with_sequence("orders")
+                .with_listener(Box::new(MockMessageListener {
+                    aborts: aborts.clone(),
+                })),
+            2,
+            &[],
+        )
+        .await
+        .unwrap();
+
+        let sent = sent.lock().unwrap().clone();
+        assert_eq!(sent.len(), 2);
+        assert!(
+            sent[1].starts_with(
+                "msg\r\nLS_reqId=2&LS_message=BUY+100&LS_sequence=orders&LS_msg_prog=1"
+            )
+        );
+        assert_eq!(pending_messages.len(), 1);
+
+        for message_request in pending_messages.drain() {
+            message_request.abort(true);
+        }
+        assert_eq!(*aborts.lock().unwrap(), vec![("BUY 100".to_string(), true)]);
+    }
+
+    #[test]
+    fn test_send_message_is_handed_to_session() {
+        let mut client = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        )
+        .unwrap();
+
+        client.send_message("hello", Some("chat"), Some(100), None, true);
+
+        let message_request = client.message_receiver.try_recv().unwrap();
+        assert_eq!(message_request.get_message(), "hello");
+        assert_eq!(message_request.get_sequence(), "chat");
+        assert_eq!(message_request.get_delay_timeout(), Some(100));
+        assert!(message_request.enqueue_while_disconnected);
+    }
+
+    #[tokio::test]
+    async fn test_send_message_request_aborts_when_client_is_gone() {
+        let client = LightstreamerClient::new(
+            Some("http://test.lightstreamer.com"),
+            Some("DEMO"),
+            None,
+            None,
+        )
+        .unwrap();
+        let message_sender = client.message_sender.clone();
+        drop(client);
+
+        let aborts = Arc::new(Mutex::new(Vec::new()));
+        LightstreamerClient::send_message_request(
+            message_sender,
+            MessageRequest::new("late")
+                .with_timeout(std::time::Duration::from_millis(10))
+                .with_listener(Box::new(MockMessageListener {
+                    aborts: aborts.clone(),
+                })),
+        )
+        .await;
+
+        assert_eq!(*aborts.lock().unwrap(), vec![("late".to_string(), false)]);
+    }
+
+    #[test]
+    fn test_update_log_sampling() {
+        let mut client = LightstreamerClient::new(None, None, None, None).unwrap();
+        assert_eq!(
+            client.get_update_log_sampling(),
+            LogSampling::MaxPerSecond(10)
+        );
+        client
+            .set_update_log_sampling(LogSampling::OneIn(100))
+            .unwrap();
+        assert_eq!(client.get_update_log_sampling(), LogSampling::OneIn(100));
+        assert!(
+            client
+                .set_update_log_sampling(LogSampling::OneIn(0))
+                .is_err()
+        );
+    }
+
     #[test]
     fn test_logging_functions() {
         let result = LightstreamerClient::new(
@@ -1699,6 +6328,383 @@ mod tests {
         LightstreamerClient::add_cookies("http://test.lightstreamer.com", &cookie);
     }
 
+    #[tokio::test]
+    async fn test_queued_messages_are_aborted_when_full_or_given_up() {
+        // The session is never created.
+        let address = spawn_mock_server(vec!["PROBE\r\n"]).await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_message_queue_size(1);
+        let aborts = Arc::new(Mutex::new(Vec::new()));
+        let message_sender = client.message_sender.clone();
+        let shutdown_signal = Arc::new(Notify::new());
+        let shutdown = shutdown_signal.clone();
+        let recorded = aborts.clone();
+        tokio::spawn(async move {
+            for message in ["first", "second"] {
+                let message_request = MessageRequest::new(message)
+                    .with_enqueue_while_disconnected(true)
+                    .with_listener(Box::new(MockMessageListener {
+                        aborts: recorded.clone(),
+                    }));
+                LightstreamerClient::send_message_request(message_sender.clone(), message_request)
+                    .await;
+            }
+            while recorded.lock().unwrap().is_empty() {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+            shutdown.notify_one();
+        });
+
+        client.connect(shutdown_signal).await.unwrap();
+
+        assert_eq!(
+            *aborts.lock().unwrap(),
+            vec![("second".to_string(), false), ("first".to_string(), false)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_journaled_messages_are_sent_until_their_outcome() {
+        let (address, requests) =
+            spawn_recording_mock_server(vec!["CONOK,S1,50000,5000,*\r\nMSGDONE,orders,1,\r\n"])
+                .await;
+        let path = std::env::temp_dir().join(format!(
+            "lightstreamer-client-journal-{}.log",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let journal = MessageJournal::open(&path).unwrap();
+        // Pushed before the session, e.g. by a previous run.
+        journal.push("BUY 100", Some("orders")).unwrap();
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.set_message_journal(Some(journal.clone()));
+
+        let history = client.get_status_history();
+        let pusher = journal.clone();
+        let sent = requests.clone();
+        client
+            .connect_with_shutdown(async move {
+                while !history.get_last().is_some_and(|transition| {
+                    transition.status.to_string().starts_with("CONNECTED")
+                }) {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+                // Pushed while connected.
+                pusher.push("SELL 50", None).unwrap();
+                while !sent
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|request| request.contains("LS_message=SELL+50"))
+                {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .unwrap();
+
+        let messages = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.starts_with("msg"))
+            .count();
+        assert_eq!(messages, 2);
+        // Only the message without outcome is left, also after a restart.
+        drop(journal);
+        let pending = MessageJournal::open(&path).unwrap().get_pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].message, "SELL 50");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    /// Sends a message and unsubscribes its own subscription on the first update.
+    struct CallbackListener {
+        handle: ClientHandle,
+        statuses: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl SubscriptionListener for CallbackListener {
+        fn on_item_update(&self, update: &ItemUpdate) {
+            let mut statuses = self.statuses.lock().unwrap();
+            if statuses.is_empty() {
+                statuses.push(self.handle.get_status().to_string());
+                self.handle.send_message(MessageRequest::new(&format!(
+                    "SEEN {}",
+                    update.get_value("last_price").unwrap_or_default()
+                )));
+                self.handle.unsubscribe(1).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_listeners_call_back_into_the_client_through_a_handle() {
+        let (address, requests) = spawn_recording_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\nU,1,1,a\r\nU,1,1,b\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let statuses = Arc::new(Mutex::new(Vec::new()));
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .add_listener(Box::new(CallbackListener {
+                handle: client.get_handle(),
+                statuses: statuses.clone(),
+            }))
+            .unwrap();
+        client.add_subscription(subscription).unwrap();
+        // Kept, so that the session goes on after the unsubscription.
+        let other = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item2".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        client.add_subscription(other).unwrap();
+        assert!(
+            client
+                .get_handle()
+                .get_status()
+                .to_string()
+                .starts_with("DISCONNECTED")
+        );
+
+        let sent = requests.clone();
+        client
+            .connect_with_shutdown(async move {
+                while !sent
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|request| request.contains("LS_op=delete&LS_subId=1"))
+                    || !sent
+                        .lock()
+                        .unwrap()
+                        .iter()
+                        .any(|request| request.starts_with("msg"))
+                {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(statuses.lock().unwrap().len(), 1);
+        assert!(statuses.lock().unwrap()[0].starts_with("CONNECTED"));
+        let requests = requests.lock().unwrap();
+        let messages: Vec<_> = requests
+            .iter()
+            .filter(|request| request.starts_with("msg"))
+            .collect();
+        assert_eq!(messages.len(), 1);
+        assert!(messages[0].contains("LS_message=SEEN+a"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_http_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let address = spawn_mock_server(vec!["CONOK,S1,50000,5000,*\r\nEND,41,License\r\n"]).await;
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_port = listener.local_addr().unwrap().port();
+        // Relays the tunnel to the requested host, as an HTTP proxy does.
+        let proxy_task = tokio::spawn(async move {
+            let (mut client_stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(client_stream.read_u8().await.unwrap());
+            }
+            let request = String::from_utf8(request).unwrap();
+            let target = request.split_whitespace().nth(1).unwrap().to_string();
+            let mut server_stream = tokio::net::TcpStream::connect(&target).await.unwrap();
+            client_stream
+                .write_all(b"HTTP/1.1 200 Connection established\r\n\r\n")
+                .await
+                .unwrap();
+            let _ = tokio::io::copy_bidirectional(&mut client_stream, &mut server_stream).await;
+            request
+        });
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_proxy(Some(Proxy::new(
+            ProxyType::Http,
+            "127.0.0.1".to_string(),
+            proxy_port,
+            None,
+            None,
+        )));
+
+        client.connect(Arc::new(Notify::new())).await.unwrap();
+
+        let target = address
+            .trim_start_matches("http://")
+            .trim_end_matches('/')
+            .to_string();
+        assert!(
+            client
+                .get_status_history()
+                .get_transitions()
+                .iter()
+                .any(|transition| transition.status.to_string().starts_with("CONNECTED"))
+        );
+        assert!(
+            proxy_task
+                .await
+                .unwrap()
+                .starts_with(&format!("CONNECT {} HTTP/1.1\r\n", target))
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct SlowStartRecorder(Mutex<Vec<SlowStartWarning>>);
+
+    impl ClientListener for SlowStartRecorder {
+        fn on_slow_subscription_start(&self, warning: &SlowStartWarning) {
+            self.0.lock().unwrap().push(warning.clone());
+        }
+
+        fn on_status_change(&self, _status: &str) {}
+    }
+
+    #[tokio::test]
+    async fn test_slow_subscription_starts_are_reported() {
+        let address = spawn_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\nU,1,1,a\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        client.connection_options.set_slow_start_threshold(30);
+        let recorder = Arc::new(SlowStartRecorder::default());
+        client.add_listener_weak(&recorder);
+        for item in ["item1", "item2"] {
+            let subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            client.add_subscription(subscription).unwrap();
+        }
+
+        let warnings = recorder.clone();
+        client
+            .connect_with_shutdown(async move {
+                while warnings.0.lock().unwrap().is_empty() {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+                // No further warning for the same request.
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            })
+            .await
+            .unwrap();
+
+        let warnings = recorder.0.lock().unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].to_string(),
+            "No data for subscription 2 to items [item2] in MERGE mode within 30 ms of its request"
+        );
+        let dump = client.dump_state();
+        assert!(dump.subscriptions[0].time_to_first_update_ms.is_some());
+        assert_eq!(dump.subscriptions[1].time_to_first_update_ms, None);
+    }
+
+    /// Requests a fresh snapshot on the first update.
+    struct RefreshingListener(SnapshotRefresher);
+
+    impl SubscriptionListener for RefreshingListener {
+        fn on_item_update(&self, _update: &ItemUpdate) {
+            if !self.0.is_refresh_pending() {
+                let _ = self.0.refresh();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refresh_resubscribes_only_the_subscription() {
+        let (address, requests) = spawn_recording_mock_server(vec![
+            "CONOK,S1,50000,5000,*\r\nSUBOK,1,1,1\r\nSUBOK,2,1,1\r\nU,1,1,a\r\n",
+        ])
+        .await;
+        let mut client =
+            LightstreamerClient::new(Some(&address), Some("DEMO"), None, None).unwrap();
+        client
+            .connection_options
+            .set_forced_transport(Some(Transport::WsStreaming));
+        let mut refreshers = Vec::new();
+        for item in ["item1", "item2"] {
+            let mut subscription = Subscription::new(
+                SubscriptionMode::Merge,
+                Some(vec![item.to_string()]),
+                Some(vec!["last_price".to_string()]),
+            )
+            .unwrap();
+            assert!(subscription.refresh().is_err());
+            let refresher = subscription.get_snapshot_refresher();
+            subscription
+                .add_listener(Box::new(RefreshingListener(refresher.clone())))
+                .unwrap();
+            refreshers.push(refresher);
+            client.add_subscription(subscription).unwrap();
+        }
+
+        let sent = requests.clone();
+        client
+            .connect_with_shutdown(async move {
+                while !sent
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .any(|request| request.contains("LS_subId=3"))
+                {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            })
+            .await
+            .unwrap();
+
+        let control_frames = requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.starts_with("control"))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert!(control_frames.contains("LS_op=delete&LS_subId=1"));
+        assert!(!control_frames.contains("LS_op=delete&LS_subId=2"));
+        assert_eq!(control_frames.matches("LS_op=add").count(), 3);
+        assert!(!refreshers[0].is_refresh_pending());
+        // Removed subscriptions can no longer be refreshed.
+        client.detach_subscription(2).unwrap().unwrap();
+        assert!(refreshers[1].refresh().is_err());
+    }
+
     #[test]
     #[should_panic(expected = "not implemented")]
     fn test_get_cookies() {
@@ -0,0 +1,141 @@
+use std::error::Error;
+use std::fmt::Debug;
+
+/// A request about to be sent to the Server, as seen by a `RequestInterceptor`.
+///
+/// The request is made of its name, such as `create_session`, `bind_session`, `control` or
+/// `msg`, and of its parameters, in the order they are sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutboundRequest {
+    /// The name of the request.
+    name: String,
+    /// The parameters of the request, in order.
+    params: Vec<(String, String)>,
+}
+
+impl OutboundRequest {
+    /// Creates a request with the given name and parameters.
+    pub fn new(name: &str, params: Vec<(String, String)>) -> Self {
+        OutboundRequest {
+            name: name.to_string(),
+            params,
+        }
+    }
+
+    /// Returns the name of the request, such as `create_session`, `bind_session`, `control` or
+    /// `msg`.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the parameters of the request, in the order they are sent.
+    pub fn get_params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// Returns the value of the given parameter, if present.
+    pub fn get_param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of the given parameter, replacing the current value if present, or
+    /// appending the parameter otherwise.
+    pub fn set_param(&mut self, name: &str, value: &str) {
+        match self.params.iter_mut().find(|(param, _)| param == name) {
+            Some((_, current)) => *current = value.to_string(),
+            None => self.params.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// Removes the given parameter, returning its value if it was present.
+    pub fn remove_param(&mut self, name: &str) -> Option<String> {
+        let index = self.params.iter().position(|(param, _)| param == name)?;
+        Some(self.params.remove(index).1)
+    }
+}
+
+/// Interface for intercepting the requests sent to the Server, to add parameters, sign them or
+/// log them, e.g. to support a custom protocol with the Metadata Adapter.
+///
+/// Interceptors are called in the order they were added through
+/// `LightstreamerClient::add_request_interceptor()`, each one seeing the changes made by the
+/// previous ones, right before the request is sent. Like listeners, they are called by the task
+/// running the session, so they should be fast and never block.
+pub trait RequestInterceptor: Debug + Send + Sync {
+    /// Event handler called before a request is sent to the Server.
+    ///
+    /// # Parameters
+    ///
+    /// * `request`: the request, which can be modified.
+    fn intercept(&self, request: &mut OutboundRequest);
+}
+
+/// Passes the encoded parameters of a request through the given interceptors, returning the
+/// parameters to be sent.
+pub(crate) fn intercept_request(
+    interceptors: &[Box<dyn RequestInterceptor>],
+    name: &str,
+    encoded_params: String,
+) -> Result<String, Box<dyn Error + Send + Sync>> {
+    if interceptors.is_empty() {
+        return Ok(encoded_params);
+    }
+    let mut request = OutboundRequest::new(name, serde_urlencoded::from_str(&encoded_params)?);
+    for interceptor in interceptors {
+        interceptor.intercept(&mut request);
+    }
+    Ok(serde_urlencoded::to_string(request.get_params())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct SigningInterceptor;
+
+    impl RequestInterceptor for SigningInterceptor {
+        fn intercept(&self, request: &mut OutboundRequest) {
+            let signature = format!("{}:{}", request.get_name(), request.get_params().len());
+            request.set_param("LS_signature", &signature);
+        }
+    }
+
+    #[test]
+    fn test_params_are_edited_in_place() {
+        let mut request = OutboundRequest::new(
+            "control",
+            vec![
+                ("LS_reqId".to_string(), "1".to_string()),
+                ("LS_op".to_string(), "add".to_string()),
+            ],
+        );
+        request.set_param("LS_reqId", "2");
+        request.set_param("LS_custom", "x");
+        assert_eq!(request.get_param("LS_reqId"), Some("2"));
+        assert_eq!(request.remove_param("LS_op"), Some("add".to_string()));
+        assert_eq!(request.remove_param("LS_op"), None);
+        assert_eq!(
+            serde_urlencoded::to_string(request.get_params()).unwrap(),
+            "LS_reqId=2&LS_custom=x"
+        );
+    }
+
+    #[test]
+    fn test_interceptors_are_chained() {
+        let interceptors: Vec<Box<dyn RequestInterceptor>> =
+            vec![Box::new(SigningInterceptor), Box::new(SigningInterceptor)];
+        let encoded = intercept_request(&interceptors, "msg", "LS_message=BUY+100".to_string());
+        // The second interceptor sees the parameter added by the first one.
+        assert_eq!(encoded.unwrap(), "LS_message=BUY+100&LS_signature=msg%3A2");
+    }
+
+    #[test]
+    fn test_no_interceptors_leave_request_untouched() {
+        let encoded = intercept_request(&[], "control", "LS_op=add".to_string()).unwrap();
+        assert_eq!(encoded, "LS_op=add");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::client::message_listener::ClientMessageListener;
+use crate::client::request::MessageRequest;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::sync::Notify;
+
+/// A message kept by a `MessageJournal` until the Server reports its outcome.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournaledMessage {
+    /// The identifier of the message in the journal, increasing with each message.
+    pub id: u64,
+    /// The sequence the message belongs to.
+    pub sequence: String,
+    /// The text of the message.
+    pub message: String,
+}
+
+/// A record of the journal file, one per line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum JournalRecord {
+    /// A message was submitted.
+    Add(JournaledMessage),
+    /// The outcome of a message was received.
+    Done { id: u64 },
+}
+
+/// The state shared by the handles of a `MessageJournal`.
+#[derive(Debug)]
+struct JournalState {
+    path: PathBuf,
+    file: File,
+    next_id: u64,
+    /// The messages without outcome, by identifier, i.e. in submission order.
+    pending: BTreeMap<u64, JournaledMessage>,
+}
+
+/// Disk-backed queue of outbound messages, for an at-least-once delivery of messages to the
+/// Server which survives process restarts.
+///
+/// Messages pushed to the journal are appended to an append-only file before anything else, and
+/// sent by the client, through `LightstreamerClient::set_message_journal()`, as soon as a
+/// session is available: right away if connected, otherwise on the next session, possibly in
+/// another process opening the same file. A message leaves the journal once the Server reports
+/// its outcome, whether processed, denied, discarded or failed; a message aborted because the
+/// session ended stays in the journal and is sent again on the next session. Hence a message
+/// whose outcome was lost with the connection can be delivered twice, and the Metadata Adapter
+/// should be prepared to receive duplicates.
+///
+/// The journal is a shared handle, which can be cloned to push messages from anywhere. Pushes
+/// wait for the file to be synced to disk, so they should not be made from latency-critical
+/// code.
+#[derive(Debug, Clone)]
+pub struct MessageJournal {
+    state: Arc<Mutex<JournalState>>,
+    pushed: Arc<Notify>,
+}
+
+impl MessageJournal {
+    /// Opens the journal stored in the given file, creating it if needed, with the messages left
+    /// without outcome by previous runs. The file is compacted, keeping only those messages.
+    ///
+    /// A record truncated by a crash while being written is ignored.
+    ///
+    /// # Parameters
+    ///
+    /// * `path`: The path of the journal file.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the file system if the file cannot be read or written.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<MessageJournal> {
+        let path = path.as_ref().to_path_buf();
+        let mut next_id = 1;
+        let mut pending = BTreeMap::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                match serde_json::from_str::<JournalRecord>(&line?) {
+                    Ok(JournalRecord::Add(message)) => {
+                        next_id = next_id.max(message.id + 1);
+                        pending.insert(message.id, message);
+                    }
+                    Ok(JournalRecord::Done { id }) => {
+                        pending.remove(&id);
+                    }
+                    Err(_) => continue,
+                }
+            }
+        }
+        let file = Self::write_file(&path, pending.values())?;
+        Ok(MessageJournal {
+            state: Arc::new(Mutex::new(JournalState {
+                path,
+                file,
+                next_id,
+                pending,
+            })),
+            pushed: Arc::new(Notify::new()),
+        })
+    }
+
+    /// Appends a message to the journal, to be sent on the current or next session.
+    ///
+    /// # Parameters
+    ///
+    /// * `message`: The text of the message.
+    /// * `sequence`: The sequence of the message, or `None` for "UNORDERED_MESSAGES".
+    ///
+    /// # Returns
+    ///
+    /// The identifier of the message in the journal.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the file system if the message cannot be stored, in which case it
+    /// will not be sent.
+    pub fn push(&self, message: &str, sequence: Option<&str>) -> io::Result<u64> {
+        let mut state = self.lock();
+        let journaled = JournaledMessage {
+            id: state.next_id,
+            sequence: sequence
+                .unwrap_or(MessageRequest::UNORDERED_MESSAGES)
+                .to_string(),
+            message: message.to_string(),
+        };
+        Self::append(&mut state.file, &JournalRecord::Add(journaled.clone()))?;
+        state.file.sync_data()?;
+        state.next_id += 1;
+        let id = journaled.id;
+        state.pending.insert(id, journaled);
+        drop(state);
+        self.pushed.notify_one();
+        Ok(id)
+    }
+
+    /// Returns the messages without outcome, in submission order.
+    pub fn get_pending(&self) -> Vec<JournaledMessage> {
+        self.lock().pending.values().cloned().collect()
+    }
+
+    /// Rewrites the journal file with the messages without outcome only, reclaiming the space
+    /// of the others.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the file system if the file cannot be rewritten.
+    pub fn compact(&self) -> io::Result<()> {
+        let mut state = self.lock();
+        state.file = Self::write_file(&state.path, state.pending.values())?;
+        Ok(())
+    }
+
+    /// Removes a message whose outcome was received. A failure to record it only means that the
+    /// message may be sent again after a restart.
+    pub(crate) fn complete(&self, id: u64) {
+        let mut state = self.lock();
+        if state.pending.remove(&id).is_some() {
+            let _ = Self::append(&mut state.file, &JournalRecord::Done { id });
+        }
+    }
+
+    /// Waits for a message to be pushed.
+    pub(crate) async fn pushed(&self) {
+        self.pushed.notified().await;
+    }
+
+    /// Builds the request sending a journaled message, which completes it on its outcome.
+    pub(crate) fn get_message_request(&self, journaled: &JournaledMessage) -> MessageRequest {
+        MessageRequest::new(&journaled.message)
+            .with_sequence(&journaled.sequence)
+            .with_listener(Box::new(JournalOutcomeListener {
+                journal: self.clone(),
+                id: journaled.id,
+            }))
+    }
+
+    /// Writes the given messages to a new file replacing the one at the given path, returning
+    /// the new file open for appending.
+    fn write_file<'a>(
+        path: &Path,
+        messages: impl Iterator<Item = &'a JournaledMessage>,
+    ) -> io::Result<File> {
+        let temporary_path = path.with_extension("compacting");
+        let mut file = File::create(&temporary_path)?;
+        for message in messages {
+            Self::append(&mut file, &JournalRecord::Add(message.clone()))?;
+        }
+        file.sync_all()?;
+        fs::rename(&temporary_path, path)?;
+        OpenOptions::new().append(true).open(path)
+    }
+
+    fn append(file: &mut File, record: &JournalRecord) -> io::Result<()> {
+        let mut line = serde_json::to_string(record)?;
+        line.push('\n');
+        file.write_all(line.as_bytes())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, JournalState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// Listener of the outcome of a journaled message, removing it from the journal once the
+/// Server has handled it.
+#[derive(Debug)]
+struct JournalOutcomeListener {
+    journal: MessageJournal,
+    id: u64,
+}
+
+impl ClientMessageListener for JournalOutcomeListener {
+    fn on_abort(&self, _msg: &str, _sent_on_network: bool) {
+        // Kept in the journal, to be sent again on the next session.
+    }
+
+    fn on_deny(&self, _msg: &str, _code: i32, _error: &str) {
+        self.journal.complete(self.id);
+    }
+
+    fn on_discarded(&self, _msg: &str) {
+        self.journal.complete(self.id);
+    }
+
+    fn on_error(&self, _msg: &str) {
+        self.journal.complete(self.id);
+    }
+
+    fn on_processed(&self, _msg: &str, _response: Option<&str>) {
+        self.journal.complete(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "lightstreamer-journal-{}-{}.log",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_pending_messages_survive_a_restart() {
+        let path = journal_path("restart");
+        let journal = MessageJournal::open(&path).unwrap();
+        let first = journal.push("buy", Some("orders")).unwrap();
+        let second = journal.push("sell", None).unwrap();
+        journal
+            .get_message_request(&journal.get_pending()[0])
+            .listener
+            .unwrap()
+            .on_processed("buy", None);
+        drop(journal);
+
+        // A record truncated by a crash.
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"{\"op\":\"add\",\"id\":3,").unwrap();
+        drop(file);
+
+        let journal = MessageJournal::open(&path).unwrap();
+        let pending = journal.get_pending();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, second);
+        assert_eq!(pending[0].sequence, MessageRequest::UNORDERED_MESSAGES);
+        assert!(journal.push("hold", None).unwrap() > first.max(second));
+        // The file was compacted on opening.
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("buy"));
+        fs::remove_file(&path).unwrap();
+    }
+}
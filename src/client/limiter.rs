@@ -0,0 +1,152 @@
+use crate::utils::{IllegalArgumentException, LightstreamerError};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The shared state of a `ReconnectLimiter`.
+#[derive(Debug)]
+struct Limits {
+    /// The permits to open a connection.
+    permits: Arc<Semaphore>,
+    /// The maximum number of connections being opened at the same time.
+    max_concurrent: usize,
+    /// The upper bound of the random delay added before each reconnection.
+    max_jitter: Duration,
+    /// The number of reconnections coordinated so far, to vary the jitter.
+    reconnections: AtomicU64,
+    /// The seed of the jitter, random for each limiter.
+    seed: RandomState,
+}
+
+/// Limiter coordinating the reconnections of several `LightstreamerClient` instances of a
+/// process, so that a network outage hitting all of them does not turn into a thundering herd
+/// of reconnections hammering the Server when the network comes back.
+///
+/// Each reconnection first waits for a random delay up to the configured jitter, then for a
+/// permit, which is held until the session is established or the attempt fails. The first
+/// connection of each client is not limited.
+///
+/// The limiter is a shared handle: clones coordinate with each other, so the same limiter is
+/// given to all the clients through `LightstreamerClient::set_reconnect_limiter()`.
+#[derive(Debug, Clone)]
+pub struct ReconnectLimiter {
+    limits: Arc<Limits>,
+}
+
+impl ReconnectLimiter {
+    /// Creates a limiter.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_concurrent`: The maximum number of connections being opened at the same time.
+    /// * `max_jitter`: The upper bound of the random delay added before each reconnection.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if `max_concurrent` is zero.
+    pub fn new(
+        max_concurrent: usize,
+        max_jitter: Duration,
+    ) -> Result<ReconnectLimiter, LightstreamerError> {
+        if max_concurrent == 0 {
+            return Err(IllegalArgumentException::new(
+                "At least one concurrent connection must be allowed",
+            )
+            .into());
+        }
+        Ok(ReconnectLimiter {
+            limits: Arc::new(Limits {
+                permits: Arc::new(Semaphore::new(max_concurrent)),
+                max_concurrent,
+                max_jitter,
+                reconnections: AtomicU64::new(0),
+                seed: RandomState::new(),
+            }),
+        })
+    }
+
+    /// Returns the maximum number of connections being opened at the same time.
+    pub fn get_max_concurrent(&self) -> usize {
+        self.limits.max_concurrent
+    }
+
+    /// Returns the upper bound of the random delay added before each reconnection.
+    pub fn get_max_jitter(&self) -> Duration {
+        self.limits.max_jitter
+    }
+
+    /// Returns the number of connections that can be opened right now.
+    pub fn get_available_permits(&self) -> usize {
+        self.limits.permits.available_permits()
+    }
+
+    /// Returns a random delay up to the configured jitter.
+    pub(crate) fn next_jitter(&self) -> Duration {
+        let max_jitter = self.limits.max_jitter.as_millis() as u64;
+        if max_jitter == 0 {
+            return Duration::ZERO;
+        }
+        let mut hasher = self.limits.seed.build_hasher();
+        hasher.write_u64(self.limits.reconnections.fetch_add(1, Ordering::Relaxed));
+        Duration::from_millis(hasher.finish() % (max_jitter + 1))
+    }
+
+    /// Waits for the jitter and for a permit to open a connection, which is given back when
+    /// dropped.
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        tokio::time::sleep(self.next_jitter()).await;
+        self.limits
+            .permits
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("the semaphore of the limiter is never closed")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_concurrency_is_rejected() {
+        assert!(ReconnectLimiter::new(0, Duration::ZERO).is_err());
+    }
+
+    #[test]
+    fn test_jitter_is_bounded() {
+        let limiter = ReconnectLimiter::new(1, Duration::from_millis(50)).unwrap();
+        let jitters: Vec<Duration> = (0..100).map(|_| limiter.next_jitter()).collect();
+        assert!(
+            jitters
+                .iter()
+                .all(|jitter| *jitter <= Duration::from_millis(50))
+        );
+        // The delays are spread, not all the same.
+        assert!(jitters.iter().any(|jitter| *jitter != jitters[0]));
+        let no_jitter = ReconnectLimiter::new(1, Duration::ZERO).unwrap();
+        assert_eq!(no_jitter.next_jitter(), Duration::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_permits_are_shared_by_clones() {
+        let limiter = ReconnectLimiter::new(2, Duration::ZERO).unwrap();
+        let clone = limiter.clone();
+        let first = limiter.acquire().await;
+        let _second = clone.acquire().await;
+        assert_eq!(limiter.get_available_permits(), 0);
+        // A third reconnection waits for one of the others to complete.
+        let third = tokio::time::timeout(Duration::from_millis(20), clone.acquire()).await;
+        assert!(third.is_err());
+        drop(first);
+        assert_eq!(limiter.get_available_permits(), 1);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), clone.acquire())
+                .await
+                .is_ok()
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use crate::client::SessionReplacement;
+use crate::utils::{OversizedMessageError, SlowStartWarning};
 use std::fmt::Debug;
 
 /// Interface to be implemented to listen to `LightstreamerClient` events comprehending notifications
@@ -8,6 +10,12 @@ use std::fmt::Debug;
 /// has changed. On the other hand, all the notifications for a single `LightstreamerClient`,
 /// including notifications to `ClientListener`, `SubscriptionListener` and `ClientMessageListener`
 /// will be dispatched by the same thread.
+///
+/// The notifications are dispatched one at a time, with no lock of the client held, so a listener
+/// may call back into the client through a `ClientHandle`, obtained with
+/// `LightstreamerClient::get_handle()`: it can subscribe, unsubscribe, even its own Subscription,
+/// and send messages, and the requests are processed as soon as the notification returns. A
+/// listener must not block or wait for the client, as the client cannot make progress meanwhile.
 pub trait ClientListener: Debug + Send {
     /// Event handler that receives a notification when the `ClientListener` instance is removed
     /// from a `LightstreamerClient` through `LightstreamerClient.removeListener()`. This is the
@@ -74,6 +82,9 @@ pub trait ClientListener: Debug + Send {
     /// and no recovery attempt has been performed. By setting a custom handler, however, it is
     /// possible to override this and perform custom recovery actions.
     ///
+    /// When the Server ends a session (END notification), the client then reacts as configured
+    /// through `ConnectionOptions.setEndCauseReaction()`, possibly opening a new session.
+    ///
     /// # Parameters
     ///
     /// * `code`: The error code. It can be one of the following:
@@ -103,8 +114,43 @@ pub trait ClientListener: Debug + Send {
     ///
     /// See also `ConnectionDetails.setAdapterSet()`
     fn on_server_error(&self, _code: i32, _message: &str) {
-        // Implementation for on_server_error
-        unimplemented!("Implement on_server_error method for ClientListener");
+        // Default implementation does nothing.
+    }
+
+    /// Event handler that is called when the Server sends a message longer than the limit
+    /// configured through `ConnectionOptions.setMaxMessageSize()`. The message is discarded and
+    /// the session goes on; if the message referred to a subscription, its data may be out of
+    /// date, unless it was resubscribed (see `ConnectionOptions.setResubscribeOnOversizedMessage()`).
+    ///
+    /// # Parameters
+    ///
+    /// * `error`: The details of the discarded message.
+    fn on_oversized_message(&self, _error: &OversizedMessageError) {
+        // Default implementation does nothing.
+    }
+
+    /// Event handler that is called when a subscription receives no update, nor end-of-snapshot,
+    /// within the threshold configured through `ConnectionOptions.setSlowStartThreshold()` of
+    /// its request. The subscription goes on; the time it actually took is then available
+    /// through `Subscription.getTimeToFirstUpdate()`.
+    ///
+    /// # Parameters
+    ///
+    /// * `warning`: The details of the slow subscription.
+    fn on_slow_subscription_start(&self, _warning: &SlowStartWarning) {
+        // Default implementation does nothing.
+    }
+
+    /// Event handler that is called when a session is created to replace one that could not be
+    /// recovered (see `ConnectionOptions.setSessionRecoveryTimeout()`), right before the
+    /// subscriptions are subscribed again. Their snapshots, if requested, are then replayed, so
+    /// the application may have to reconcile them with the data it already received.
+    ///
+    /// # Parameters
+    ///
+    /// * `replacement`: The details of the replacement.
+    fn on_session_replaced(&self, _replacement: &SessionReplacement) {
+        // Default implementation does nothing.
     }
 
     /// Event handler that receives a notification each time the `LightstreamerClient` status has changed.
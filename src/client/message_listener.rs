@@ -3,7 +3,7 @@
 /// thread than the one that generates them. All the notifications for a single `LightstreamerClient`,
 /// including notifications to `ClientListener`, `SubscriptionListener` and `ClientMessageListener`
 /// will be dispatched by the same thread. Only one event per message is fired on this listener.
-pub trait ClientMessageListener {
+pub trait ClientMessageListener: Send {
     /// Event handler that is called by Lightstreamer when any notifications of the processing
     /// outcome of the related message haven't been received yet and can no longer be received.
     /// Typically, this happens after the session has been closed. In this case, the client has
@@ -0,0 +1,406 @@
+use crate::client::request::MessageRequest;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// A message sent to the Server whose outcome was not received, as listed by `InFlightMessages`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InFlightMessage {
+    /// The sequence of the message.
+    pub sequence: String,
+    /// The progressive number of the message in its sequence, within its session.
+    pub prog: usize,
+    /// The text of the message.
+    pub message: String,
+    /// The time since the message was sent.
+    pub age: Duration,
+}
+
+/// The messages listed by `InFlightMessages`, by sequence and progressive number.
+#[derive(Debug, Default)]
+struct InFlightState {
+    /// The messages of the current session waiting for an outcome, with the time they were sent.
+    current: BTreeMap<(String, usize), (String, Instant)>,
+    /// The messages whose outcome was lost when the last session ended.
+    lost: BTreeMap<(String, usize), (String, Instant)>,
+}
+
+/// Live list of the messages sent with a listener whose outcome was not received yet, so that
+/// order-routing layers can reconcile their in-flight orders, in particular after a reconnect.
+///
+/// When a session ends, its in-flight messages are aborted and listed as lost, until the next
+/// session ends, since the Server may or may not have processed them.
+///
+/// Handles are cheap to clone and share the same list, so it can be read from another task
+/// while the client is connected.
+///
+/// See also `LightstreamerClient::get_in_flight_messages()`
+#[derive(Debug, Clone, Default)]
+pub struct InFlightMessages {
+    state: Arc<Mutex<InFlightState>>,
+}
+
+impl InFlightMessages {
+    /// Returns the messages of the current session waiting for an outcome, ordered by sequence
+    /// and progressive number.
+    pub fn get_messages(&self) -> Vec<InFlightMessage> {
+        Self::list(&self.lock().current, None)
+    }
+
+    /// Returns the messages of the given sequence waiting for an outcome in the current
+    /// session, ordered by progressive number.
+    ///
+    /// # Parameters
+    ///
+    /// * `sequence`: the name of the sequence.
+    pub fn get_sequence_messages(&self, sequence: &str) -> Vec<InFlightMessage> {
+        Self::list(&self.lock().current, Some(sequence))
+    }
+
+    /// Returns the messages whose outcome was lost when the last session ended, ordered by
+    /// sequence and progressive number.
+    pub fn get_lost_messages(&self) -> Vec<InFlightMessage> {
+        Self::list(&self.lock().lost, None)
+    }
+
+    fn list(
+        messages: &BTreeMap<(String, usize), (String, Instant)>,
+        sequence: Option<&str>,
+    ) -> Vec<InFlightMessage> {
+        messages
+            .iter()
+            .filter(|((message_sequence, _), _)| sequence.is_none_or(|s| s == message_sequence))
+            .map(|((sequence, prog), (message, sent_at))| InFlightMessage {
+                sequence: sequence.clone(),
+                prog: *prog,
+                message: message.clone(),
+                age: sent_at.elapsed(),
+            })
+            .collect()
+    }
+
+    fn insert(&self, sequence: &str, prog: usize, message: &str) {
+        self.lock().current.insert(
+            (sequence.to_string(), prog),
+            (message.to_string(), Instant::now()),
+        );
+    }
+
+    fn remove(&self, sequence: &str, prog: usize) {
+        self.lock().current.remove(&(sequence.to_string(), prog));
+    }
+
+    /// Lists the messages still in flight as lost, as when the session ends.
+    fn end_session(&self) {
+        let mut state = self.lock();
+        state.lost = std::mem::take(&mut state.current);
+    }
+
+    fn lock(&self) -> MutexGuard<'_, InFlightState> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+/// The messages waiting for a session, as they were handled while no session was available
+/// and allow it, in submission order.
+///
+/// The queue outlives the sessions, so that the messages handled while the client is
+/// disconnected or retrying are sent on the next session.
+#[derive(Debug, Default)]
+pub(crate) struct MessageQueue {
+    messages: Vec<MessageRequest>,
+}
+
+impl MessageQueue {
+    /// Queues a message, unless the queue already holds `capacity` messages, in which case the
+    /// message is handed back.
+    pub(crate) fn push(
+        &mut self,
+        request: MessageRequest,
+        capacity: usize,
+    ) -> Result<(), MessageRequest> {
+        if self.messages.len() >= capacity {
+            return Err(request);
+        }
+        self.messages.push(request);
+        Ok(())
+    }
+
+    /// Removes and returns the queued messages, in submission order.
+    pub(crate) fn take(&mut self) -> Vec<MessageRequest> {
+        std::mem::take(&mut self.messages)
+    }
+
+    /// Returns the number of queued messages.
+    pub(crate) fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Returns the earliest deadline among the queued messages, if any.
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.messages
+            .iter()
+            .filter_map(|request| request.deadline)
+            .min()
+    }
+
+    /// Removes the messages whose deadline has expired at the given instant.
+    pub(crate) fn take_expired(&mut self, now: Instant) -> Vec<MessageRequest> {
+        let (expired, queued) = std::mem::take(&mut self.messages)
+            .into_iter()
+            .partition(|request| request.is_expired(now));
+        self.messages = queued;
+        expired
+    }
+}
+
+/// Book-keeping of the messages of a session which are still waiting for an outcome.
+///
+/// Messages sent with a listener are kept, keyed by sequence and progressive number, until
+/// their outcome is received, their deadline expires or the session ends.
+#[derive(Debug, Default)]
+pub(crate) struct PendingMessages {
+    /// Last progressive number assigned in each sequence.
+    progs: HashMap<String, usize>,
+    /// Messages sent and waiting for an outcome, by sequence and progressive number.
+    in_flight: HashMap<(String, usize), MessageRequest>,
+    /// The list of the messages in flight shared with the application.
+    in_flight_list: InFlightMessages,
+}
+
+impl PendingMessages {
+    /// Creates the book-keeping of a session, listing its messages in flight in the given list.
+    pub(crate) fn new(in_flight_list: InFlightMessages) -> Self {
+        PendingMessages {
+            in_flight_list,
+            ..Default::default()
+        }
+    }
+
+    /// Assigns the next progressive number of a sequence, starting from 1.
+    pub(crate) fn next_prog(&mut self, sequence: &str) -> usize {
+        let prog = self.progs.entry(sequence.to_string()).or_insert(0);
+        *prog += 1;
+        *prog
+    }
+
+    /// Keeps track of a sent message until its outcome is received.
+    pub(crate) fn track(&mut self, prog: usize, request: MessageRequest) {
+        self.in_flight_list
+            .insert(&request.sequence, prog, &request.message);
+        self.in_flight
+            .insert((request.sequence.clone(), prog), request);
+    }
+
+    /// Stops tracking a sent message, returning it if it was still waiting for an outcome.
+    pub(crate) fn complete(&mut self, sequence: &str, prog: usize) -> Option<MessageRequest> {
+        self.in_flight_list.remove(sequence, prog);
+        self.in_flight.remove(&(sequence.to_string(), prog))
+    }
+
+    /// Stops tracking the messages reported by a MSGFAIL notification, returning those that
+    /// were still waiting for an outcome. Code 39 reports a list of discarded messages, ending
+    /// with the given one, whose length is the error message.
+    pub(crate) fn fail(
+        &mut self,
+        sequence: &str,
+        prog: usize,
+        code: i32,
+        error: &str,
+    ) -> Vec<MessageRequest> {
+        let count = match code {
+            39 => error.parse::<usize>().unwrap_or(1).clamp(1, prog.max(1)),
+            _ => 1,
+        };
+        (prog + 1 - count..=prog)
+            .filter_map(|prog| self.complete(sequence, prog))
+            .collect()
+    }
+
+    /// Returns the number of messages waiting for an outcome.
+    pub(crate) fn len(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Returns the earliest deadline among the pending messages, if any.
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.in_flight
+            .values()
+            .filter_map(|request| request.deadline)
+            .min()
+    }
+
+    /// Removes the messages whose deadline has expired at the given instant.
+    pub(crate) fn take_expired(&mut self, now: Instant) -> Vec<MessageRequest> {
+        let mut expired = Vec::new();
+        let mut expired_keys: Vec<(String, usize)> = self
+            .in_flight
+            .iter()
+            .filter(|(_, request)| request.is_expired(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+        expired_keys.sort();
+        for key in expired_keys {
+            self.in_flight_list.remove(&key.0, key.1);
+            if let Some(request) = self.in_flight.remove(&key) {
+                expired.push(request);
+            }
+        }
+        expired
+    }
+
+    /// Removes all the pending messages, as when the session ends, ordered by sequence and
+    /// progressive number.
+    pub(crate) fn drain(&mut self) -> Vec<MessageRequest> {
+        self.in_flight_list.end_session();
+        let mut in_flight: Vec<_> = self.in_flight.drain().collect();
+        in_flight.sort_by(|(a, _), (b, _)| a.cmp(b));
+        in_flight.into_iter().map(|(_, request)| request).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_next_prog_is_per_sequence() {
+        let mut pending = PendingMessages::default();
+        assert_eq!(pending.next_prog("orders"), 1);
+        assert_eq!(pending.next_prog("orders"), 2);
+        assert_eq!(pending.next_prog("quotes"), 1);
+    }
+
+    #[test]
+    fn test_complete_removes_in_flight_message() {
+        let mut pending = PendingMessages::default();
+        pending.track(1, MessageRequest::new("buy").with_sequence("orders"));
+        assert_eq!(pending.len(), 1);
+
+        assert!(pending.complete("orders", 2).is_none());
+        let request = pending.complete("orders", 1).unwrap();
+        assert_eq!(request.get_message(), "buy");
+        assert_eq!(pending.len(), 0);
+    }
+
+    #[test]
+    fn test_fail_removes_the_discarded_list() {
+        let mut pending = PendingMessages::default();
+        for prog in 1..=4 {
+            pending.track(
+                prog,
+                MessageRequest::new(&format!("order {}", prog)).with_sequence("orders"),
+            );
+        }
+
+        let failed = pending.fail("orders", 1, 32, "error");
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].get_message(), "order 1");
+
+        // Messages 2 to 4 discarded, only the ones still pending are returned.
+        pending.complete("orders", 3);
+        let failed: Vec<String> = pending
+            .fail("orders", 4, 39, "3")
+            .iter()
+            .map(|request| request.get_message().to_string())
+            .collect();
+        assert_eq!(failed, vec!["order 2", "order 4"]);
+        assert_eq!(pending.len(), 0);
+        assert!(pending.fail("orders", 4, 39, "9").is_empty());
+    }
+
+    #[test]
+    fn test_take_expired() {
+        let mut pending = PendingMessages::default();
+        let sent = MessageRequest::new("sent").with_timeout(Duration::from_millis(200));
+        let deadline = sent.get_deadline().unwrap();
+        pending.track(1, sent);
+        pending.track(2, MessageRequest::new("no deadline"));
+
+        assert_eq!(pending.next_deadline(), Some(deadline));
+        assert!(
+            pending
+                .take_expired(deadline - Duration::from_millis(1))
+                .is_empty()
+        );
+
+        let expired = pending.take_expired(deadline);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].get_message(), "sent");
+
+        assert_eq!(pending.next_deadline(), None);
+        assert_eq!(pending.len(), 1);
+    }
+
+    #[test]
+    fn test_drain_orders_by_sequence_and_prog() {
+        let mut pending = PendingMessages::default();
+        pending.track(2, MessageRequest::new("second"));
+        pending.track(1, MessageRequest::new("first"));
+
+        let drained: Vec<String> = pending
+            .drain()
+            .iter()
+            .map(|request| request.get_message().to_string())
+            .collect();
+        assert_eq!(drained, vec!["first", "second"]);
+        assert_eq!(pending.len(), 0);
+    }
+
+    #[test]
+    fn test_message_queue_keeps_order_within_capacity() {
+        let mut queue = MessageQueue::default();
+        let expiring = MessageRequest::new("expiring").with_timeout(Duration::from_millis(100));
+        let deadline = expiring.get_deadline().unwrap();
+        queue.push(expiring, 3).unwrap();
+        queue.push(MessageRequest::new("first"), 3).unwrap();
+        queue.push(MessageRequest::new("second"), 3).unwrap();
+        let rejected = queue.push(MessageRequest::new("third"), 3).unwrap_err();
+        assert_eq!(rejected.get_message(), "third");
+        assert_eq!(queue.len(), 3);
+
+        assert_eq!(queue.next_deadline(), Some(deadline));
+        let expired = queue.take_expired(deadline);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].get_message(), "expiring");
+        assert_eq!(queue.next_deadline(), None);
+
+        let queued: Vec<String> = queue
+            .take()
+            .iter()
+            .map(|request| request.get_message().to_string())
+            .collect();
+        assert_eq!(queued, vec!["first", "second"]);
+        assert_eq!(queue.len(), 0);
+        assert!(queue.push(MessageRequest::new("none"), 0).is_err());
+    }
+
+    #[test]
+    fn test_in_flight_list_follows_the_messages() {
+        let list = InFlightMessages::default();
+        let mut pending = PendingMessages::new(list.clone());
+        pending.track(2, MessageRequest::new("sell").with_sequence("orders"));
+        pending.track(1, MessageRequest::new("buy").with_sequence("orders"));
+        pending.track(1, MessageRequest::new("quote").with_sequence("quotes"));
+
+        let messages = list.get_messages();
+        let keys: Vec<(&str, usize)> = messages
+            .iter()
+            .map(|message| (message.sequence.as_str(), message.prog))
+            .collect();
+        assert_eq!(keys, vec![("orders", 1), ("orders", 2), ("quotes", 1)]);
+        assert_eq!(list.get_sequence_messages("quotes")[0].message, "quote");
+
+        pending.complete("orders", 1);
+        assert_eq!(list.get_sequence_messages("orders").len(), 1);
+        assert!(list.get_lost_messages().is_empty());
+
+        pending.drain();
+        assert!(list.get_messages().is_empty());
+        let lost = list.get_lost_messages();
+        assert_eq!(lost.len(), 2);
+        assert_eq!(lost[0].message, "sell");
+    }
+}
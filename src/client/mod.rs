@@ -4,16 +4,65 @@
    Date: 16/5/25
 ******************************************************************************/
 
+mod aggregator;
+mod api;
+mod batch;
+#[cfg(feature = "protocol-checks")]
+mod checks;
+mod compression;
+mod diagnostics;
+mod dump;
+mod fake;
+mod handle;
+mod health;
+mod hooks;
+mod http;
 mod listener;
 mod message_listener;
 
 mod implementation;
+mod interceptor;
+mod journal;
+mod limiter;
+mod messages;
 mod model;
+mod ordered;
+mod probe;
+mod prog;
+mod recorder;
+mod recovery;
 mod request;
+mod sampling;
+mod session;
+mod socket;
+mod status;
 mod utils;
 
-pub use implementation::LightstreamerClient;
+pub use aggregator::{AggregatorHealth, FeedAggregator, SourceHealth, TaggedUpdate};
+pub use api::StreamingClient;
+pub use diagnostics::SessionDiagnostics;
+pub use dump::{ClientStateDump, SubscriptionDump};
+pub use fake::FakeClient;
+pub use handle::ClientHandle;
+pub use health::{HealthProbe, HealthReport};
+pub use hooks::ServerSelection;
+pub use implementation::{ClientTask, LightstreamerClient};
+pub use interceptor::{OutboundRequest, RequestInterceptor};
+pub use journal::{JournaledMessage, MessageJournal};
+pub use limiter::ReconnectLimiter;
 pub use listener::ClientListener;
 pub use message_listener::ClientMessageListener;
-pub use model::{ClientStatus, ConnectionType, DisconnectionType, LogType, Transport};
-pub use request::SubscriptionRequest;
+pub use messages::{InFlightMessage, InFlightMessages};
+pub use model::{
+    ClientStatus, ConnectionType, DisconnectionType, EndCauseReaction, LogType, Transport,
+};
+pub use ordered::OrderedUpdate;
+pub use probe::TransportProbeResult;
+pub use recorder::{FlightRecorder, RecordedEvent, RecordedEventKind};
+pub use request::{MessageRequest, SubscriptionRequest};
+pub(crate) use request::{RequestResponder, request_response};
+pub use sampling::LogSampling;
+pub use session::{SessionInfo, SessionReplacement};
+pub use status::{
+    ErrorSeverity, LastError, LastErrorHandle, StatusChangeCause, StatusHistory, StatusTransition,
+};
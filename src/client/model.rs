@@ -4,7 +4,10 @@
    Date: 16/5/25
 ******************************************************************************/
 
+use std::fmt::{self, Display, Formatter};
+
 /// Represents the current status of the `LightstreamerClient`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ClientStatus {
     /// The client is attempting to connect to the Lightstreamer Server.
     Connecting,
@@ -23,6 +26,7 @@ pub enum ClientStatus {
 ///
 /// This enum indicates the specific transport protocol and connection mode being used
 /// for communication with the server.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ConnectionType {
     /// Connection established using HTTP polling transport.
     HttpPolling,
@@ -41,6 +45,7 @@ pub enum ConnectionType {
 ///
 /// This enum provides information about the disconnection state and what actions
 /// the client will take following the disconnection.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum DisconnectionType {
     /// The client will automatically try to reconnect to the server.
     WillRetry,
@@ -48,6 +53,72 @@ pub enum DisconnectionType {
     /// This happens when a temporary disconnection is detected and the client
     /// is trying to restore the previous session without losing subscriptions.
     TryingRecovery,
+    /// No connection is currently active and none will be opened.
+    NoRetry,
+}
+
+impl Display for ClientStatus {
+    /// Formats the status as the string documented in `LightstreamerClient::get_status()`,
+    /// e.g. `CONNECTED:WS-STREAMING`.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientStatus::Connecting => write!(f, "CONNECTING"),
+            ClientStatus::Connected(ConnectionType::HttpPolling) => {
+                write!(f, "CONNECTED:HTTP-POLLING")
+            }
+            ClientStatus::Connected(ConnectionType::HttpStreaming) => {
+                write!(f, "CONNECTED:HTTP-STREAMING")
+            }
+            ClientStatus::Connected(ConnectionType::StreamSensing) => {
+                write!(f, "CONNECTED:STREAM-SENSING")
+            }
+            ClientStatus::Connected(ConnectionType::WsPolling) => {
+                write!(f, "CONNECTED:WS-POLLING")
+            }
+            ClientStatus::Connected(ConnectionType::WsStreaming) => {
+                write!(f, "CONNECTED:WS-STREAMING")
+            }
+            ClientStatus::Stalled => write!(f, "STALLED"),
+            ClientStatus::Disconnected(DisconnectionType::WillRetry) => {
+                write!(f, "DISCONNECTED:WILL-RETRY")
+            }
+            ClientStatus::Disconnected(DisconnectionType::TryingRecovery) => {
+                write!(f, "DISCONNECTED:TRYING-RECOVERY")
+            }
+            ClientStatus::Disconnected(DisconnectionType::NoRetry) => write!(f, "DISCONNECTED"),
+        }
+    }
+}
+
+/// The reaction of the client when the Server ends a session with an END notification.
+///
+/// See also `ConnectionOptions::set_end_cause_reaction()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EndCauseReaction {
+    /// Open a new session right away.
+    Reconnect,
+    /// Open a new session after a delay, starting from `ConnectionOptions::get_retry_delay()`
+    /// and doubling at every consecutive attempt that doesn't succeed in creating a session.
+    Backoff,
+    /// Don't open a new session: `LightstreamerClient::connect()` returns.
+    GiveUp,
+}
+
+impl EndCauseReaction {
+    /// Returns the reaction used for the given END cause code when none is configured:
+    /// - `31` (session closed through a client destroy request or by an administrator), `35`
+    ///   (session closed upon opening of a new session for the same user) and `41` (session
+    ///   closed because of license restrictions): `GiveUp`, since a new session would be closed
+    ///   for the same reason;
+    /// - `48` (maximum session duration reached): `Reconnect`;
+    /// - any other code: `Backoff`.
+    pub fn default_for(code: i32) -> EndCauseReaction {
+        match code {
+            31 | 35 | 41 => EndCauseReaction::GiveUp,
+            48 => EndCauseReaction::Reconnect,
+            _ => EndCauseReaction::Backoff,
+        }
+    }
 }
 
 /// Represents the type of logging to be used by the LightstreamerClient.
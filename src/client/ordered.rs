@@ -0,0 +1,50 @@
+use crate::subscription::ItemUpdate;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// An update delivered through the ordered queue of a client, as enabled by
+/// `LightstreamerClient::enable_ordered_dispatch()`.
+#[derive(Debug, Clone)]
+pub struct OrderedUpdate {
+    /// The position of the update in the queue, starting at 0 and increasing by one for each
+    /// update, across the subscriptions and the sessions of the client.
+    pub sequence: u64,
+    /// The ID of the subscription of the update in the session it was received in, as returned
+    /// by `LightstreamerClient::subscribe_get_id()`.
+    pub subscription_id: usize,
+    /// The update.
+    pub update: ItemUpdate,
+}
+
+/// The sending end of the ordered queue of a client.
+#[derive(Debug)]
+pub(crate) struct OrderedDispatch {
+    sender: UnboundedSender<OrderedUpdate>,
+    next_sequence: u64,
+}
+
+impl OrderedDispatch {
+    /// Creates a queue, returning its receiving end too.
+    pub(crate) fn new() -> (OrderedDispatch, UnboundedReceiver<OrderedUpdate>) {
+        let (sender, receiver) = unbounded_channel();
+        let dispatch = OrderedDispatch {
+            sender,
+            next_sequence: 0,
+        };
+        (dispatch, receiver)
+    }
+
+    /// Appends an update to the queue.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the receiving end was dropped, in which case the queue is useless.
+    pub(crate) fn dispatch(&mut self, subscription_id: usize, update: &ItemUpdate) -> bool {
+        let ordered_update = OrderedUpdate {
+            sequence: self.next_sequence,
+            subscription_id,
+            update: update.clone(),
+        };
+        self.next_sequence += 1;
+        self.sender.send(ordered_update).is_ok()
+    }
+}
@@ -0,0 +1,149 @@
+use crate::client::socket::connect_websocket;
+use crate::utils::Proxy;
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::net::IpAddr;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio_tungstenite::Connector;
+use tokio_tungstenite::tungstenite::{Message, http::Request};
+
+/// How long the outcome of a transport probe is reused before probing again.
+pub(crate) const PROBE_CACHE_TTL: Duration = Duration::from_secs(600);
+
+/// Outcome of a transport probe, see `LightstreamerClient::probe_transport()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransportProbeResult {
+    /// A WSOK handshake was completed with the Server: WebSockets work through the current
+    /// network path.
+    WebSocketAvailable,
+    /// The WSOK handshake could not be completed, for the given reason.
+    WebSocketUnavailable(String),
+}
+
+impl TransportProbeResult {
+    /// Returns whether WebSockets work through the current network path.
+    pub fn is_websocket_available(&self) -> bool {
+        matches!(self, TransportProbeResult::WebSocketAvailable)
+    }
+}
+
+impl Display for TransportProbeResult {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            TransportProbeResult::WebSocketAvailable => write!(f, "WebSocket available"),
+            TransportProbeResult::WebSocketUnavailable(reason) => {
+                write!(f, "WebSocket unavailable: {}", reason)
+            }
+        }
+    }
+}
+
+/// Outcomes of the last probes, by WebSocket URL, shared by all the clients of the process.
+static PROBE_CACHE: LazyLock<Mutex<HashMap<String, (Instant, TransportProbeResult)>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Returns the outcome of the last probe of the given URL, unless it is older than
+/// `PROBE_CACHE_TTL`.
+pub(crate) fn get_cached_probe(url: &str) -> Option<TransportProbeResult> {
+    let cache = PROBE_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    cache
+        .get(url)
+        .filter(|(probed_at, _)| probed_at.elapsed() < PROBE_CACHE_TTL)
+        .map(|(_, result)| result.clone())
+}
+
+/// Stores the outcome of a probe of the given URL.
+pub(crate) fn cache_probe(url: &str, result: TransportProbeResult) {
+    let mut cache = PROBE_CACHE.lock().unwrap_or_else(|err| err.into_inner());
+    cache.insert(url.to_string(), (Instant::now(), result));
+}
+
+/// Discards the outcomes of all the previous probes.
+pub(crate) fn clear_probe_cache() {
+    PROBE_CACHE
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clear();
+}
+
+/// Opens a WebSocket with the given request and waits for the Server to answer `wsok` with
+/// `WSOK`, closing the WebSocket afterwards.
+///
+/// # Parameters
+///
+/// * `request`: the WebSocket handshake request.
+/// * `local_address`: the local address the connection is bound to, if any.
+/// * `connector`: the TLS connector of `wss` connections, if not the default one.
+/// * `server_name`: the name sent through SNI on `wss` connections, if not the host of the
+///   request.
+/// * `proxy`: the HTTP proxy the connection is tunneled through, if any.
+/// * `timeout`: the time allowed to complete the handshake.
+pub(crate) async fn probe_websocket(
+    request: Request<()>,
+    local_address: Option<IpAddr>,
+    connector: Option<Connector>,
+    server_name: Option<&str>,
+    proxy: Option<&Proxy>,
+    timeout: Duration,
+) -> TransportProbeResult {
+    let handshake = async {
+        let (mut ws_stream, _) =
+            connect_websocket(request, local_address, connector, server_name, proxy)
+                .await
+                .map_err(|err| format!("WebSocket connection failed: {}", err))?;
+        ws_stream
+            .send(Message::Text("wsok".into()))
+            .await
+            .map_err(|err| format!("Sending wsok failed: {}", err))?;
+        while let Some(message) = ws_stream.next().await {
+            match message {
+                Ok(Message::Text(text)) if text.trim().eq_ignore_ascii_case("wsok") => {
+                    let _ = ws_stream.close(None).await;
+                    return Ok(());
+                }
+                Ok(_) => continue,
+                Err(err) => return Err(format!("WebSocket broken before WSOK: {}", err)),
+            }
+        }
+        Err("WebSocket closed before WSOK".to_string())
+    };
+    match tokio::time::timeout(timeout, handshake).await {
+        Ok(Ok(())) => TransportProbeResult::WebSocketAvailable,
+        Ok(Err(reason)) => TransportProbeResult::WebSocketUnavailable(reason),
+        Err(_) => TransportProbeResult::WebSocketUnavailable(format!(
+            "no WSOK within {} ms",
+            timeout.as_millis()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_cache() {
+        let url = "ws://probe-cache.test/lightstreamer";
+        assert_eq!(get_cached_probe(url), None);
+
+        cache_probe(url, TransportProbeResult::WebSocketAvailable);
+        assert_eq!(
+            get_cached_probe(url),
+            Some(TransportProbeResult::WebSocketAvailable)
+        );
+        assert_eq!(get_cached_probe("ws://other.test/lightstreamer"), None);
+    }
+
+    #[tokio::test]
+    async fn test_probe_unreachable_server() {
+        let request = Request::builder()
+            .uri("ws://127.0.0.1:1/lightstreamer")
+            .body(())
+            .unwrap();
+        let result = probe_websocket(request, None, None, None, None, Duration::from_secs(5)).await;
+        assert!(!result.is_websocket_available());
+        assert!(result.to_string().starts_with("WebSocket unavailable"));
+    }
+}
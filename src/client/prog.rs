@@ -0,0 +1,111 @@
+/// Outcome of the check of a PROG notification against the data notifications received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ProgCheck {
+    /// The Server and the client agree on the number of data notifications.
+    InSync,
+    /// The client received more data notifications than the Server reports as sent: the given
+    /// number of the next data notifications are duplicates, resent by the Server.
+    Duplicates(u64),
+    /// The Server sent more data notifications than the client received: the given number of
+    /// data notifications were lost.
+    Lost(u64),
+}
+
+/// Keeps the progressive count of the data notifications received in a session (the "PROG"
+/// of TLCP), which is the basis for session recovery and duplicate detection.
+///
+/// Data notifications are the ones carrying subscription data and message outcomes, such as
+/// U, CS, EOS, OV, CONF, SUBOK, SUBCMD, UNSUB, MSGDONE and MSGFAIL.
+///
+/// See also `ServerMessage::is_data_notification()`
+#[derive(Debug, Default)]
+pub(crate) struct ProgTracker {
+    /// The number of data notifications received and processed.
+    prog: u64,
+    /// The number of the next data notifications to be discarded as duplicates.
+    duplicates_to_skip: u64,
+}
+
+impl ProgTracker {
+    /// Returns the number of data notifications received and processed.
+    pub(crate) fn prog(&self) -> u64 {
+        self.prog
+    }
+
+    /// Counts a data notification.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the notification is a duplicate to be discarded, `true` if it has to be
+    /// processed.
+    pub(crate) fn on_data_notification(&mut self) -> bool {
+        if self.duplicates_to_skip > 0 {
+            self.duplicates_to_skip -= 1;
+            return false;
+        }
+        self.prog += 1;
+        true
+    }
+
+    /// Checks the count of data notifications reported by the Server through a PROG
+    /// notification, aligning the client count to it.
+    ///
+    /// # Parameters
+    ///
+    /// * `server_prog`: the number of data notifications the Server sent before the ones that
+    ///   follow.
+    pub(crate) fn on_prog(&mut self, server_prog: u64) -> ProgCheck {
+        if server_prog < self.prog {
+            self.duplicates_to_skip = self.prog - server_prog;
+            ProgCheck::Duplicates(self.duplicates_to_skip)
+        } else if server_prog > self.prog {
+            let lost = server_prog - self.prog;
+            self.prog = server_prog;
+            self.duplicates_to_skip = 0;
+            ProgCheck::Lost(lost)
+        } else {
+            self.duplicates_to_skip = 0;
+            ProgCheck::InSync
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_data_notifications() {
+        let mut tracker = ProgTracker::default();
+        assert!(tracker.on_data_notification());
+        assert!(tracker.on_data_notification());
+        assert_eq!(tracker.prog(), 2);
+        assert_eq!(tracker.on_prog(2), ProgCheck::InSync);
+    }
+
+    #[test]
+    fn test_duplicates_are_skipped() {
+        let mut tracker = ProgTracker::default();
+        for _ in 0..5 {
+            tracker.on_data_notification();
+        }
+
+        // The Server resends the last two notifications.
+        assert_eq!(tracker.on_prog(3), ProgCheck::Duplicates(2));
+        assert!(!tracker.on_data_notification());
+        assert!(!tracker.on_data_notification());
+        assert!(tracker.on_data_notification());
+        assert_eq!(tracker.prog(), 6);
+    }
+
+    #[test]
+    fn test_lost_notifications_are_reported() {
+        let mut tracker = ProgTracker::default();
+        tracker.on_data_notification();
+
+        assert_eq!(tracker.on_prog(4), ProgCheck::Lost(3));
+        assert_eq!(tracker.prog(), 4);
+        assert!(tracker.on_data_notification());
+        assert_eq!(tracker.prog(), 5);
+    }
+}
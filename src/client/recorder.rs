@@ -0,0 +1,211 @@
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Kind of an event kept by the `FlightRecorder`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordedEventKind {
+    /// A protocol frame received from the Server.
+    Received(String),
+    /// A protocol frame sent to the Server.
+    Sent(String),
+    /// A change in the state of the connection, such as the creation or the end of a session.
+    StateChange(String),
+}
+
+/// An event kept by the `FlightRecorder`, with the time it was recorded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedEvent {
+    /// The wall-clock time the event was recorded.
+    pub timestamp: SystemTime,
+    /// The event.
+    pub kind: RecordedEventKind,
+}
+
+impl Display for RecordedEvent {
+    /// Formats the event as a single line, with a `HH:MM:SS.mmm` (UTC) timestamp and the line
+    /// breaks of the frame escaped.
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let since_epoch = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let seconds_of_day = since_epoch.as_secs() % 86_400;
+        write!(
+            f,
+            "{:02}:{:02}:{:02}.{:03} ",
+            seconds_of_day / 3600,
+            (seconds_of_day % 3600) / 60,
+            seconds_of_day % 60,
+            since_epoch.subsec_millis()
+        )?;
+        let (tag, text) = match &self.kind {
+            RecordedEventKind::Received(text) => ("S->C", text),
+            RecordedEventKind::Sent(text) => ("C->S", text),
+            RecordedEventKind::StateChange(text) => ("STATE", text),
+        };
+        write!(
+            f,
+            "{} {}",
+            tag,
+            text.trim_end().replace('\r', "\\r").replace('\n', "\\n")
+        )
+    }
+}
+
+/// Bounded ring buffer of the last protocol frames and connection state changes, for post-mortem
+/// analysis of production incidents without enabling verbose logging beforehand.
+///
+/// The recorder is opt-in: it is enabled through `LightstreamerClient::enable_flight_recorder()`,
+/// which returns a handle. Handles are cheap to clone and share the same buffer, so the recorder
+/// can be dumped on demand from another task while the client is connected. The content is also
+/// logged automatically when `LightstreamerClient::connect()` fails.
+#[derive(Debug, Clone)]
+pub struct FlightRecorder {
+    /// The maximum number of events kept.
+    capacity: usize,
+    /// The recorded events, oldest first.
+    events: Arc<Mutex<VecDeque<RecordedEvent>>>,
+}
+
+impl FlightRecorder {
+    /// Creates an empty recorder.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity`: the maximum number of events kept; older events are discarded first.
+    pub fn new(capacity: usize) -> Self {
+        FlightRecorder {
+            capacity,
+            events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Returns the maximum number of events kept.
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Records an event, discarding the oldest one if the recorder is full.
+    pub fn record(&self, kind: RecordedEventKind) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.events.lock().unwrap_or_else(|err| err.into_inner());
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(RecordedEvent {
+            timestamp: SystemTime::now(),
+            kind,
+        });
+    }
+
+    /// Returns a copy of the recorded events, oldest first.
+    pub fn get_events(&self) -> Vec<RecordedEvent> {
+        let events = self.events.lock().unwrap_or_else(|err| err.into_inner());
+        events.iter().cloned().collect()
+    }
+
+    /// Returns the recorded events formatted one per line, oldest first.
+    pub fn dump(&self) -> String {
+        self.get_events()
+            .iter()
+            .map(|event| format!("{}\n", event))
+            .collect()
+    }
+
+    /// Discards all the recorded events.
+    pub fn clear(&self) {
+        self.events
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+}
+
+/// Masks the value of the `LS_password` parameter of a frame sent to the Server, so that
+/// credentials never end up in the recorder.
+pub(crate) fn redact_credentials(frame: &str) -> String {
+    const PASSWORD_PARAM: &str = "LS_password=";
+    let Some(start) = frame
+        .find(PASSWORD_PARAM)
+        .map(|pos| pos + PASSWORD_PARAM.len())
+    else {
+        return frame.to_string();
+    };
+    let end = frame[start..]
+        .find(['&', '\r', '\n'])
+        .map_or(frame.len(), |pos| start + pos);
+    format!("{}***{}", &frame[..start], &frame[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_keeps_last_events() {
+        let recorder = FlightRecorder::new(2);
+        recorder.record(RecordedEventKind::Sent("wsok".to_string()));
+        recorder.record(RecordedEventKind::Received("WSOK".to_string()));
+        recorder.record(RecordedEventKind::StateChange(
+            "Session created".to_string(),
+        ));
+
+        let kinds: Vec<RecordedEventKind> = recorder
+            .get_events()
+            .into_iter()
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                RecordedEventKind::Received("WSOK".to_string()),
+                RecordedEventKind::StateChange("Session created".to_string()),
+            ]
+        );
+
+        recorder.clear();
+        assert!(recorder.get_events().is_empty());
+    }
+
+    #[test]
+    fn test_clones_share_the_buffer() {
+        let recorder = FlightRecorder::new(10);
+        let handle = recorder.clone();
+        recorder.record(RecordedEventKind::Sent("wsok".to_string()));
+        assert_eq!(handle.get_events().len(), 1);
+    }
+
+    #[test]
+    fn test_zero_capacity_records_nothing() {
+        let recorder = FlightRecorder::new(0);
+        recorder.record(RecordedEventKind::Sent("wsok".to_string()));
+        assert!(recorder.get_events().is_empty());
+    }
+
+    #[test]
+    fn test_redact_credentials() {
+        assert_eq!(
+            redact_credentials("create_session\r\nLS_user=u&LS_password=secret&LS_cid=x"),
+            "create_session\r\nLS_user=u&LS_password=***&LS_cid=x"
+        );
+        assert_eq!(redact_credentials("LS_password=secret"), "LS_password=***");
+        assert_eq!(redact_credentials("wsok"), "wsok");
+    }
+
+    #[test]
+    fn test_event_display() {
+        let event = RecordedEvent {
+            timestamp: UNIX_EPOCH + Duration::from_millis(3_723_004),
+            kind: RecordedEventKind::Sent("control\r\nLS_reqId=1".to_string()),
+        };
+        assert_eq!(
+            event.to_string(),
+            "01:02:03.004 C->S control\\r\\nLS_reqId=1"
+        );
+    }
+}
@@ -0,0 +1,143 @@
+#[cfg(feature = "protocol-checks")]
+use crate::client::checks::ProtocolChecker;
+use crate::client::prog::ProgTracker;
+use crate::subscription::ItemUpdate;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+/// State of a session that outlives a single connection, so that the session can be recovered
+/// on a new connection after a network drop.
+#[derive(Debug, Default)]
+pub(crate) struct SessionState {
+    /// The ID of the session, once created.
+    pub(crate) session_id: Option<String>,
+    /// The ID of the last session created by the client before this one, if any.
+    pub(crate) previous_session_id: Option<String>,
+    /// The ID of the last request sent in the session.
+    pub(crate) request_id: usize,
+    /// The ID of the last subscription sent by the client. Subscription IDs are the table
+    /// indexes of the Server and are never reused, not even in a new session, so that a late
+    /// notification for an unsubscribed or superseded table cannot reach another subscription.
+    pub(crate) subscription_id: usize,
+    /// The number of sessions opened by the client before this one.
+    pub(crate) generation: u64,
+    /// The last values received for each item, by subscription and item position, needed to
+    /// decode the updates that only carry the changed fields.
+    pub(crate) item_updates: HashMap<usize, HashMap<usize, ItemUpdate>>,
+    /// The progressive count of the data notifications of the session.
+    pub(crate) prog: ProgTracker,
+    /// When the connection of the session dropped, while recovering it.
+    pub(crate) dropped_at: Option<SystemTime>,
+    /// The checks of the order of the subscription notifications of the session.
+    #[cfg(feature = "protocol-checks")]
+    pub(crate) checker: ProtocolChecker,
+}
+
+impl SessionState {
+    /// Returns whether the session exists and can be recovered on a new connection.
+    pub(crate) fn is_recoverable(&self) -> bool {
+        self.session_id.is_some()
+    }
+
+    /// Returns the state of the next session, which starts afresh but keeps assigning new
+    /// subscription IDs.
+    pub(crate) fn renew(&self) -> SessionState {
+        SessionState {
+            subscription_id: self.subscription_id,
+            generation: self.generation + 1,
+            previous_session_id: self
+                .session_id
+                .clone()
+                .or_else(|| self.previous_session_id.clone()),
+            ..SessionState::default()
+        }
+    }
+}
+
+/// Returns whether a table index, found by no subscription, refers to a subscription of the
+/// client that was unsubscribed or superseded by a new request, whose late notifications are to
+/// be discarded, rather than to a table never assigned.
+///
+/// # Parameters
+///
+/// * `last_subscription_id`: the ID of the last subscription sent by the client.
+/// * `table`: the table index, that is the subscription ID, of the notification.
+pub(crate) fn is_retired_table(last_subscription_id: usize, table: usize) -> bool {
+    table > 0 && table <= last_subscription_id
+}
+
+/// Time budget for the attempts to recover a session, after which the session is abandoned
+/// and a new one is created.
+#[derive(Debug)]
+pub(crate) struct RecoveryBudget {
+    /// When the attempts have to stop.
+    deadline: Instant,
+}
+
+impl RecoveryBudget {
+    /// Starts a budget.
+    ///
+    /// # Parameters
+    ///
+    /// * `timeout`: the time allowed to the recovery attempts; zero prevents any attempt.
+    pub(crate) fn new(timeout: Duration) -> Self {
+        RecoveryBudget {
+            deadline: Instant::now() + timeout,
+        }
+    }
+
+    /// Returns the time left for recovery attempts, or `None` if the budget is exhausted.
+    pub(crate) fn remaining(&self) -> Option<Duration> {
+        let remaining = self.deadline.saturating_duration_since(Instant::now());
+        (!remaining.is_zero()).then_some(remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zero_budget_is_exhausted() {
+        assert_eq!(RecoveryBudget::new(Duration::ZERO).remaining(), None);
+    }
+
+    #[test]
+    fn test_budget_remaining() {
+        let budget = RecoveryBudget::new(Duration::from_secs(15));
+        let remaining = budget.remaining().unwrap();
+        assert!(remaining <= Duration::from_secs(15));
+        assert!(remaining > Duration::from_secs(14));
+    }
+
+    #[test]
+    fn test_session_state_is_recoverable_once_created() {
+        let mut state = SessionState::default();
+        assert!(!state.is_recoverable());
+        state.session_id = Some("S1".to_string());
+        assert!(state.is_recoverable());
+    }
+
+    #[test]
+    fn test_renewed_session_state_keeps_the_subscription_ids() {
+        let state = SessionState {
+            session_id: Some("S1".to_string()),
+            request_id: 12,
+            subscription_id: 3,
+            ..SessionState::default()
+        };
+
+        let renewed = state.renew();
+        assert!(!renewed.is_recoverable());
+        assert_eq!(renewed.request_id, 0);
+        assert_eq!(renewed.subscription_id, 3);
+        assert_eq!(renewed.generation, 1);
+        assert_eq!(renewed.previous_session_id.as_deref(), Some("S1"));
+        // A session never created does not hide the previous one.
+        assert_eq!(renewed.renew().previous_session_id.as_deref(), Some("S1"));
+        assert!(is_retired_table(renewed.subscription_id, 3));
+        assert!(!is_retired_table(renewed.subscription_id, 4));
+        assert!(!is_retired_table(renewed.subscription_id, 0));
+    }
+}
@@ -3,7 +3,39 @@
    Email: jb@taunais.com
    Date: 16/5/25
 ******************************************************************************/
+use crate::client::message_listener::ClientMessageListener;
 use crate::subscription::Subscription;
+use crate::utils::{IllegalStateException, LightstreamerError, ServerException};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// The sending half of the answer of the Server to a request: `Ok` on REQOK, the error of the
+/// REQERR notification otherwise.
+pub(crate) type RequestResponder = oneshot::Sender<Result<(), ServerException>>;
+
+/// Creates a responder for a request, with the future resolved by its answer.
+///
+/// The future yields an `IllegalStateException` if the responder is dropped before the Server
+/// answers, as when the request is never sent or the session ends first.
+pub(crate) fn request_response() -> (
+    RequestResponder,
+    impl Future<Output = Result<(), LightstreamerError>> + Send + 'static,
+) {
+    let (responder, receiver) = oneshot::channel::<Result<(), ServerException>>();
+    let response = async move {
+        match receiver.await {
+            Ok(answer) => answer.map_err(LightstreamerError::from),
+            Err(_) => Err(IllegalStateException::new(
+                "Request was abandoned before being answered by the server.",
+            )
+            .into()),
+        }
+    };
+    (responder, response)
+}
 
 /// A request to subscribe or unsubscribe from a Lightstreamer data stream.
 ///
@@ -15,4 +47,468 @@ pub struct SubscriptionRequest {
     pub(crate) subscription: Option<Subscription>,
     /// The ID of the subscription to be removed. Set to None when subscribing.
     pub(crate) subscription_id: Option<usize>,
+    /// Whoever awaits the UNSUB notification confirming the unsubscription, if any.
+    pub(crate) responder: Option<RequestResponder>,
+}
+
+/// A request to send a message to the Metadata Adapter through the current session.
+///
+/// Created with `MessageRequest::new()` and configured through the `with_*` methods, then handed
+/// to `LightstreamerClient::send_message_request()`. `LightstreamerClient::send_message()` builds
+/// one of these from its arguments.
+pub struct MessageRequest {
+    /// The text of the message.
+    pub(crate) message: String,
+    /// The sequence the message belongs to.
+    pub(crate) sequence: String,
+    /// The longest time, in milliseconds, the Server can keep the message waiting for preceding
+    /// messages of the same sequence (`LS_max_wait`).
+    pub(crate) delay_timeout: Option<u64>,
+    /// The instant after which the message is aborted if no outcome has been received yet.
+    pub(crate) deadline: Option<Instant>,
+    /// The listener to be notified of the outcome.
+    pub(crate) listener: Option<Box<dyn ClientMessageListener>>,
+    /// Whether the message can wait for a session instead of being aborted right away.
+    pub(crate) enqueue_while_disconnected: bool,
+    /// Whoever awaits the answer of the Server to the request, see `await_response()`.
+    pub(crate) responder: Option<RequestResponder>,
+}
+
+impl MessageRequest {
+    /// Sequence name for messages which don't need to be processed in order.
+    pub const UNORDERED_MESSAGES: &'static str = "UNORDERED_MESSAGES";
+
+    /// Creates a request for the given message, in the "UNORDERED_MESSAGES" sequence, with no
+    /// listener and no timeouts.
+    ///
+    /// # Parameters
+    ///
+    /// * `message`: a text message, whose interpretation is entirely demanded to the Metadata
+    ///   Adapter associated to the current connection.
+    pub fn new(message: &str) -> Self {
+        MessageRequest {
+            message: message.to_string(),
+            sequence: Self::UNORDERED_MESSAGES.to_string(),
+            delay_timeout: None,
+            deadline: None,
+            listener: None,
+            enqueue_while_disconnected: false,
+            responder: None,
+        }
+    }
+
+    /// Sets the sequence the message belongs to. Messages of the same sequence are processed
+    /// by the Server in the order in which they were sent.
+    pub fn with_sequence(mut self, sequence: &str) -> Self {
+        self.sequence = sequence.to_string();
+        self
+    }
+
+    /// Sets the longest time, in milliseconds, the Server can keep the message waiting if one or
+    /// more preceding messages of the same sequence haven't been received yet. It is sent as
+    /// `LS_max_wait` and ignored for the "UNORDERED_MESSAGES" sequence. If not set, the Server
+    /// configured timeout on missing messages applies.
+    pub fn with_delay_timeout(mut self, delay_timeout: u64) -> Self {
+        self.delay_timeout = Some(delay_timeout);
+        self
+    }
+
+    /// Sets a client-side delivery deadline, measured from now. If no outcome for the message
+    /// has been received when the deadline expires, the message is given up and
+    /// `ClientMessageListener::on_abort()` is fired; an outcome arriving later is ignored.
+    ///
+    /// The deadline also covers the time spent waiting for a session when the message is
+    /// enqueued while disconnected.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    /// Sets the listener to be notified of the processing outcome.
+    pub fn with_listener(mut self, listener: Box<dyn ClientMessageListener>) -> Self {
+        self.listener = Some(listener);
+        self
+    }
+
+    /// Sets whether the message, if handled while no session is available, is queued waiting
+    /// for a new session instead of being aborted right away, as long as the queue has room, see
+    /// `ConnectionOptions::set_message_queue_size()`.
+    pub fn with_enqueue_while_disconnected(mut self, enqueue_while_disconnected: bool) -> Self {
+        self.enqueue_while_disconnected = enqueue_while_disconnected;
+        self
+    }
+
+    /// Returns a future that resolves when the Server answers the request of the message: with
+    /// `Ok` on REQOK, or with a `ServerException` carrying the code and message of the REQERR
+    /// notification.
+    ///
+    /// The answer only tells that the request was accepted; the processing outcome of the
+    /// message is notified to the listener, if any. Only the future obtained last is resolved.
+    ///
+    /// # Errors
+    /// - Returns an `IllegalStateException` if the message is aborted before being answered, or
+    ///   if it needs no answer, as a journaled message resent by the client on its own.
+    pub fn await_response(
+        &mut self,
+    ) -> impl Future<Output = Result<(), LightstreamerError>> + Send + 'static {
+        let (responder, response) = request_response();
+        self.responder = Some(responder);
+        response
+    }
+
+    /// Returns the text of the message.
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the sequence the message belongs to.
+    pub fn get_sequence(&self) -> &str {
+        &self.sequence
+    }
+
+    /// Returns the Server-side delay timeout, in milliseconds, if set.
+    pub fn get_delay_timeout(&self) -> Option<u64> {
+        self.delay_timeout
+    }
+
+    /// Returns the client-side delivery deadline, if set.
+    pub fn get_deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns whether the message belongs to the "UNORDERED_MESSAGES" sequence.
+    pub(crate) fn is_unordered(&self) -> bool {
+        self.sequence == Self::UNORDERED_MESSAGES
+    }
+
+    /// Returns whether the deadline of the message has expired at the given instant.
+    pub(crate) fn is_expired(&self, now: Instant) -> bool {
+        self.deadline.is_some_and(|deadline| deadline <= now)
+    }
+
+    /// Handles the failure of the message reported by MSGFAIL, notifying the listener, if any:
+    /// codes 38 and 39 mean the message was discarded before reaching the Metadata Adapter,
+    /// codes not greater than 0 that the Metadata Adapter refused it, and the others that its
+    /// processing failed.
+    pub(crate) fn fail(self, code: i32, error: &str) {
+        let Some(listener) = self.listener else {
+            return;
+        };
+        match code {
+            38 | 39 => listener.on_discarded(&self.message),
+            code if code <= 0 => listener.on_deny(&self.message, code, error),
+            _ => listener.on_error(&self.message),
+        }
+    }
+
+    /// Gives up the message, notifying the listener, if any, through `on_abort()`.
+    pub(crate) fn abort(self, sent_on_network: bool) {
+        if let Some(listener) = self.listener {
+            listener.on_abort(&self.message, sent_on_network);
+        }
+    }
+}
+
+impl Debug for MessageRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MessageRequest")
+            .field("message", &self.message)
+            .field("sequence", &self.sequence)
+            .field("delay_timeout", &self.delay_timeout)
+            .field("deadline", &self.deadline)
+            .field("listener", &self.listener.is_some())
+            .field(
+                "enqueue_while_disconnected",
+                &self.enqueue_while_disconnected,
+            )
+            .field("responder", &self.responder.is_some())
+            .finish()
+    }
+}
+
+/// A request sent to the Server and awaiting its REQOK or REQERR, described so that the errors
+/// reported by the Server tell what was being attempted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PendingRequest {
+    /// The subscription with the given ID.
+    Subscription {
+        /// The ID of the subscription.
+        subscription_id: usize,
+        /// The items, or the item group, of the subscription.
+        items: String,
+        /// The mode of the subscription.
+        mode: String,
+    },
+    /// The unsubscription of the subscription with the given ID.
+    Unsubscription {
+        /// The ID of the subscription.
+        subscription_id: usize,
+    },
+    /// A message of the given sequence.
+    Message {
+        /// The sequence of the message.
+        sequence: String,
+        /// The progressive number of the message in its sequence, if it needs one.
+        prog: Option<usize>,
+    },
+}
+
+impl PendingRequest {
+    /// Describes the request for the given subscription, under the given ID.
+    pub(crate) fn subscription(subscription: &Subscription, subscription_id: usize) -> Self {
+        let items = match (subscription.get_items(), subscription.get_item_group()) {
+            (Some(items), _) => format!("items [{}]", items.join(", ")),
+            (None, Some(group)) => format!("item group '{}'", group),
+            (None, None) => "no items".to_string(),
+        };
+        PendingRequest::Subscription {
+            subscription_id,
+            items,
+            mode: subscription.get_mode().to_string(),
+        }
+    }
+
+    /// Describes the request for the given message, sent with the given progressive number.
+    pub(crate) fn message(message_request: &MessageRequest, prog: Option<usize>) -> Self {
+        PendingRequest::Message {
+            sequence: message_request.sequence.clone(),
+            prog,
+        }
+    }
+}
+
+impl fmt::Display for PendingRequest {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PendingRequest::Subscription {
+                subscription_id,
+                items,
+                mode,
+            } => write!(
+                f,
+                "subscription {} to {} in {} mode",
+                subscription_id, items, mode
+            ),
+            PendingRequest::Unsubscription { subscription_id } => {
+                write!(f, "unsubscription of subscription {}", subscription_id)
+            }
+            PendingRequest::Message { sequence, .. } => {
+                write!(f, "message in sequence '{}'", sequence)
+            }
+        }
+    }
+}
+
+/// The requests sent in a session and awaiting their REQOK or REQERR, by request ID
+/// (`LS_reqId`), each with whoever awaits its answer.
+///
+/// Dropping the table, as when the session ends, abandons the requests still unanswered.
+#[derive(Debug, Default)]
+pub(crate) struct PendingRequests {
+    requests: HashMap<usize, (PendingRequest, Option<RequestResponder>)>,
+}
+
+impl PendingRequests {
+    /// Adds a request nobody awaits the answer of.
+    pub(crate) fn insert(&mut self, request_id: usize, request: PendingRequest) {
+        self.insert_awaited(request_id, request, None);
+    }
+
+    /// Adds a request, with whoever awaits its answer, if anyone.
+    pub(crate) fn insert_awaited(
+        &mut self,
+        request_id: usize,
+        request: PendingRequest,
+        responder: Option<RequestResponder>,
+    ) {
+        self.requests.insert(request_id, (request, responder));
+    }
+
+    /// Removes the request answered by REQOK, resolving its future.
+    pub(crate) fn accept(&mut self, request_id: usize) -> Option<PendingRequest> {
+        let (request, responder) = self.requests.remove(&request_id)?;
+        if let Some(responder) = responder {
+            let _ = responder.send(Ok(()));
+        }
+        Some(request)
+    }
+
+    /// Removes the request answered by REQERR, failing its future with the error, completed by
+    /// the description of the request.
+    pub(crate) fn refuse(
+        &mut self,
+        request_id: usize,
+        error: ServerException,
+    ) -> Option<(PendingRequest, ServerException)> {
+        let (request, responder) = self.requests.remove(&request_id)?;
+        let error = error.with_request(&request.to_string());
+        if let Some(responder) = responder {
+            let _ = responder.send(Err(error.clone()));
+        }
+        Some((request, error))
+    }
+
+    /// Accepts the requests of the given subscription, since SUBOK and SUBCMD may arrive
+    /// before their REQOK.
+    pub(crate) fn accept_subscription(&mut self, subscription_id: usize) {
+        let accepted: Vec<usize> = self
+            .requests
+            .iter()
+            .filter(|(_, (request, _))| {
+                matches!(request, PendingRequest::Subscription { subscription_id: id, .. } if *id == subscription_id)
+            })
+            .map(|(request_id, _)| *request_id)
+            .collect();
+        for request_id in accepted {
+            self.accept(request_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::SubscriptionMode;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_pending_request_descriptions() {
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string(), "item2".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        assert_eq!(
+            PendingRequest::subscription(&subscription, 3).to_string(),
+            "subscription 3 to items [item1, item2] in MERGE mode"
+        );
+        assert_eq!(
+            PendingRequest::Unsubscription { subscription_id: 3 }.to_string(),
+            "unsubscription of subscription 3"
+        );
+        let message_request = MessageRequest::new("BUY 100").with_sequence("orders");
+        assert_eq!(
+            PendingRequest::message(&message_request, None).to_string(),
+            "message in sequence 'orders'"
+        );
+    }
+
+    struct OutcomeRecorder {
+        outcomes: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl ClientMessageListener for OutcomeRecorder {
+        fn on_deny(&self, msg: &str, code: i32, error: &str) {
+            let outcome = format!("denied {}: {} {}", msg, code, error);
+            self.outcomes.lock().unwrap().push(outcome);
+        }
+
+        fn on_discarded(&self, msg: &str) {
+            self.outcomes
+                .lock()
+                .unwrap()
+                .push(format!("discarded {}", msg));
+        }
+
+        fn on_error(&self, msg: &str) {
+            self.outcomes.lock().unwrap().push(format!("error {}", msg));
+        }
+    }
+
+    #[test]
+    fn test_message_failures_are_told_apart() {
+        let outcomes = Arc::new(Mutex::new(Vec::new()));
+        for (message, code) in [
+            ("late", 38),
+            ("dropped", 39),
+            ("refused", -5),
+            ("broken", 32),
+        ] {
+            MessageRequest::new(message)
+                .with_listener(Box::new(OutcomeRecorder {
+                    outcomes: outcomes.clone(),
+                }))
+                .fail(code, "reason");
+        }
+        MessageRequest::new("unheard").fail(32, "reason");
+
+        assert_eq!(
+            *outcomes.lock().unwrap(),
+            vec![
+                "discarded late",
+                "discarded dropped",
+                "denied refused: -5 reason",
+                "error broken",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pending_requests_resolve_their_futures() {
+        let mut requests = PendingRequests::default();
+        let mut accepted = MessageRequest::new("BUY 100");
+        let accepted_response = accepted.await_response();
+        requests.insert_awaited(
+            1,
+            PendingRequest::message(&accepted, None),
+            accepted.responder.take(),
+        );
+        let mut refused = MessageRequest::new("SELL 100").with_sequence("orders");
+        let refused_response = refused.await_response();
+        requests.insert_awaited(
+            2,
+            PendingRequest::message(&refused, None),
+            refused.responder.take(),
+        );
+        let mut abandoned = MessageRequest::new("HOLD");
+        let abandoned_response = abandoned.await_response();
+        requests.insert_awaited(
+            3,
+            PendingRequest::message(&abandoned, None),
+            abandoned.responder.take(),
+        );
+
+        assert!(requests.accept(1).is_some());
+        assert!(requests.accept(1).is_none());
+        let (_, error) = requests
+            .refuse(2, ServerException::new(32, "sequence refused"))
+            .unwrap();
+        assert_eq!(error.get_request(), Some("message in sequence 'orders'"));
+        drop(requests);
+
+        accepted_response.await.unwrap();
+        let Err(LightstreamerError::Server(error)) = refused_response.await else {
+            panic!("expected a server error");
+        };
+        assert_eq!(error.get_code(), 32);
+        assert!(matches!(
+            abandoned_response.await,
+            Err(LightstreamerError::IllegalState(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_subscription_confirmation_accepts_its_requests() {
+        let mut requests = PendingRequests::default();
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["last_price".to_string()]),
+        )
+        .unwrap();
+        let response = subscription.await_response();
+        requests.insert_awaited(
+            1,
+            PendingRequest::subscription(&subscription, 4),
+            subscription.take_request_responder(),
+        );
+        requests.insert(2, PendingRequest::Unsubscription { subscription_id: 4 });
+
+        requests.accept_subscription(4);
+
+        response.await.unwrap();
+        assert!(requests.accept(1).is_none());
+        assert!(requests.accept(2).is_some());
+    }
 }
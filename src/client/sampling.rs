@@ -0,0 +1,132 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How the log lines emitted for each real-time update are thinned out, so that enabling
+/// debug logging on a fast feed does not overwhelm the logging pipeline.
+///
+/// When lines are dropped, the next line logged reports how many were suppressed.
+///
+/// See also `LightstreamerClient::set_update_log_sampling()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogSampling {
+    /// Every line is logged.
+    All,
+    /// One line in the given number is logged.
+    OneIn(u32),
+    /// At most the given number of lines is logged in each second; zero disables the lines.
+    MaxPerSecond(u32),
+}
+
+impl Default for LogSampling {
+    fn default() -> Self {
+        LogSampling::MaxPerSecond(10)
+    }
+}
+
+/// Decides which of a stream of high-frequency log lines are emitted, according to a
+/// `LogSampling`, counting the ones suppressed.
+#[derive(Debug, Default)]
+pub(crate) struct LogSampler {
+    /// The sampling policy.
+    sampling: LogSampling,
+    /// The number of lines seen, for `LogSampling::OneIn`.
+    seen: u64,
+    /// The start of the current one-second window, for `LogSampling::MaxPerSecond`.
+    window_start: Option<Instant>,
+    /// The number of lines logged in the current window.
+    logged_in_window: u32,
+    /// The number of lines suppressed since the last one logged.
+    suppressed: u64,
+}
+
+impl LogSampler {
+    /// The length of the window of `LogSampling::MaxPerSecond`.
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    /// Creates a sampler applying the given policy.
+    pub(crate) fn new(sampling: LogSampling) -> Self {
+        LogSampler {
+            sampling,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the sampling policy.
+    pub(crate) fn sampling(&self) -> LogSampling {
+        self.sampling
+    }
+
+    /// Decides whether the next line is logged.
+    ///
+    /// # Returns
+    ///
+    /// The number of lines suppressed since the last one logged if this line has to be logged,
+    /// `None` if it has to be suppressed.
+    pub(crate) fn sample(&mut self, now: Instant) -> Option<u64> {
+        let log = match self.sampling {
+            LogSampling::All => true,
+            LogSampling::OneIn(count) => {
+                self.seen += 1;
+                (self.seen - 1).is_multiple_of(u64::from(count.max(1)))
+            }
+            LogSampling::MaxPerSecond(max) => {
+                if self
+                    .window_start
+                    .is_none_or(|start| now.duration_since(start) >= Self::WINDOW)
+                {
+                    self.window_start = Some(now);
+                    self.logged_in_window = 0;
+                }
+                let log = self.logged_in_window < max;
+                if log {
+                    self.logged_in_window += 1;
+                }
+                log
+            }
+        };
+        if log {
+            Some(std::mem::take(&mut self.suppressed))
+        } else {
+            self.suppressed += 1;
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_one_in_n() {
+        let mut sampler = LogSampler::new(LogSampling::OneIn(3));
+        let now = Instant::now();
+        let sampled: Vec<Option<u64>> = (0..7).map(|_| sampler.sample(now)).collect();
+        assert_eq!(
+            sampled,
+            vec![Some(0), None, None, Some(2), None, None, Some(2)]
+        );
+    }
+
+    #[test]
+    fn test_max_per_second() {
+        let mut sampler = LogSampler::new(LogSampling::MaxPerSecond(2));
+        let start = Instant::now();
+        assert_eq!(sampler.sample(start), Some(0));
+        assert_eq!(sampler.sample(start), Some(0));
+        assert_eq!(sampler.sample(start), None);
+        assert_eq!(sampler.sample(start + Duration::from_millis(500)), None);
+        // A new window reports the lines suppressed in the previous one.
+        assert_eq!(sampler.sample(start + Duration::from_secs(1)), Some(2));
+    }
+
+    #[test]
+    fn test_all_and_disabled() {
+        let now = Instant::now();
+        let mut all = LogSampler::new(LogSampling::All);
+        assert!((0..100).all(|_| all.sample(now) == Some(0)));
+
+        let mut disabled = LogSampler::new(LogSampling::MaxPerSecond(0));
+        assert!((0..100).all(|_| disabled.sample(now).is_none()));
+    }
+}
@@ -0,0 +1,194 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Details of the current session provided by the Server when the session is created, through
+/// the CONOK notification and the SERVNAME and CLIENTIP notifications that follow it.
+///
+/// See also `LightstreamerClient::get_session_info()`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SessionInfo {
+    /// The ID of the session.
+    session_id: String,
+    /// The maximum length, in bytes, of the requests accepted by the Server.
+    request_limit: u64,
+    /// The interval, in milliseconds, between keepalives sent by the Server on idle streams.
+    keepalive: u64,
+    /// The keepalive interval, in milliseconds, requested by the client, if any.
+    requested_keepalive: Option<u64>,
+    /// The address to be used for control requests, if different from the Server address.
+    control_link: Option<String>,
+    /// The name of the Server, as configured on the Server.
+    server_name: Option<String>,
+    /// The IP address of the client as seen by the Server.
+    client_ip: Option<String>,
+}
+
+impl SessionInfo {
+    /// Creates the details of a session from the arguments of its CONOK notification.
+    pub(crate) fn new(
+        session_id: &str,
+        request_limit: u64,
+        keepalive: u64,
+        control_link: Option<&str>,
+    ) -> SessionInfo {
+        SessionInfo {
+            session_id: session_id.to_string(),
+            request_limit,
+            keepalive,
+            requested_keepalive: None,
+            control_link: control_link.map(str::to_string),
+            server_name: None,
+            client_ip: None,
+        }
+    }
+
+    /// Returns the ID of the session.
+    pub fn get_session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Returns the maximum length, in bytes, of the requests accepted by the Server. Longer
+    /// requests have to be split.
+    pub fn get_request_limit(&self) -> u64 {
+        self.request_limit
+    }
+
+    /// Returns the interval, in milliseconds, between the keepalives sent by the Server when
+    /// the stream is idle. This is the effective interval, which may differ from the requested
+    /// one when the Server imposes limits on it.
+    ///
+    /// See also `ConnectionOptions::set_keepalive_interval()`
+    pub fn get_keepalive(&self) -> u64 {
+        self.keepalive
+    }
+
+    /// Returns the keepalive interval, in milliseconds, requested by the client when the session
+    /// was created, or `None` if the Server was left to decide it.
+    pub fn get_requested_keepalive(&self) -> Option<u64> {
+        self.requested_keepalive
+    }
+
+    /// Returns whether the Server granted the keepalive interval requested by the client. When
+    /// no interval was requested, any interval is considered granted.
+    pub fn is_keepalive_granted(&self) -> bool {
+        self.requested_keepalive
+            .is_none_or(|requested| requested == self.keepalive)
+    }
+
+    /// Returns the address to be used for control requests, or `None` if they go to the same
+    /// address used to create the session.
+    pub fn get_control_link(&self) -> Option<&str> {
+        self.control_link.as_deref()
+    }
+
+    /// Returns the name of the Server, as configured on the Server, if received yet.
+    pub fn get_server_name(&self) -> Option<&str> {
+        self.server_name.as_deref()
+    }
+
+    /// Returns the IP address of the client as seen by the Server, if received yet.
+    pub fn get_client_ip(&self) -> Option<&str> {
+        self.client_ip.as_deref()
+    }
+
+    /// Sets the keepalive interval requested by the client on session creation.
+    pub(crate) fn set_requested_keepalive(&mut self, requested_keepalive: u64) {
+        self.requested_keepalive = Some(requested_keepalive);
+    }
+
+    /// Sets the name of the Server, received through SERVNAME.
+    pub(crate) fn set_server_name(&mut self, server_name: &str) {
+        self.server_name = Some(server_name.to_string());
+    }
+
+    /// Sets the IP address of the client, received through CLIENTIP.
+    pub(crate) fn set_client_ip(&mut self, client_ip: &str) {
+        self.client_ip = Some(client_ip.to_string());
+    }
+}
+
+/// Notice of a session replacing one that could not be recovered: the subscriptions of the
+/// client are subscribed again in the new session, so their snapshots are sent again, and the
+/// messages enqueued or journaled are sent again.
+///
+/// See also `ClientListener::on_session_replaced()`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionReplacement {
+    previous_session_id: String,
+    session_id: String,
+    resubscriptions: usize,
+}
+
+impl SessionReplacement {
+    /// Creates a new SessionReplacement.
+    ///
+    /// # Parameters
+    ///
+    /// * `previous_session_id`: the ID of the session replaced.
+    /// * `session_id`: the ID of the new session.
+    /// * `resubscriptions`: the number of subscriptions subscribed again.
+    pub fn new(previous_session_id: &str, session_id: &str, resubscriptions: usize) -> Self {
+        SessionReplacement {
+            previous_session_id: previous_session_id.to_string(),
+            session_id: session_id.to_string(),
+            resubscriptions,
+        }
+    }
+
+    /// Returns the ID of the session replaced.
+    pub fn get_previous_session_id(&self) -> &str {
+        &self.previous_session_id
+    }
+
+    /// Returns the ID of the new session.
+    pub fn get_session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Returns the number of subscriptions subscribed again, whose snapshots, if requested, are
+    /// replayed.
+    pub fn get_resubscriptions(&self) -> usize {
+        self.resubscriptions
+    }
+}
+
+impl fmt::Display for SessionReplacement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Session {} replaced by session {}, resubscribing {} subscriptions",
+            self.previous_session_id, self.session_id, self.resubscriptions
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new() {
+        let mut info = SessionInfo::new("S1a2b3c", 50000, 5000, Some("push2.example.com"));
+        assert_eq!(info.get_session_id(), "S1a2b3c");
+        assert_eq!(info.get_request_limit(), 50000);
+        assert_eq!(info.get_keepalive(), 5000);
+        assert_eq!(info.get_control_link(), Some("push2.example.com"));
+        assert_eq!(info.get_server_name(), None);
+
+        info.set_server_name("Lightstreamer HTTP Server");
+        info.set_client_ip("10.0.0.5");
+        assert_eq!(info.get_server_name(), Some("Lightstreamer HTTP Server"));
+        assert_eq!(info.get_client_ip(), Some("10.0.0.5"));
+    }
+
+    #[test]
+    fn test_keepalive_reconciliation() {
+        let mut info = SessionInfo::new("S1", 50000, 30000, None);
+        assert_eq!(info.get_requested_keepalive(), None);
+        assert!(info.is_keepalive_granted());
+
+        info.set_requested_keepalive(5000);
+        assert_eq!(info.get_requested_keepalive(), Some(5000));
+        assert!(!info.is_keepalive_granted());
+    }
+}
@@ -0,0 +1,344 @@
+use crate::client::compression::InflatingStream;
+use crate::client::http::HttpConnection;
+use crate::utils::{Proxy, ProxyType};
+use futures_util::{Sink, SinkExt, Stream, StreamExt};
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpSocket, TcpStream, lookup_host};
+use tokio_tungstenite::tungstenite::error::{TlsError, UrlError};
+use tokio_tungstenite::tungstenite::handshake::client::Response;
+use tokio_tungstenite::tungstenite::http::Request;
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::{Connector, MaybeTlsStream, WebSocketStream, client_async_with_config};
+
+/// A WebSocket connected to the Lightstreamer Server.
+pub(crate) type ServerWebSocket = WebSocketStream<InflatingStream<MaybeTlsStream<TcpStream>>>;
+
+/// A connection to the Lightstreamer Server, over which the session reads notifications and
+/// writes requests as WebSocket messages, whatever the transport.
+pub(crate) enum ServerConnection {
+    WebSocket(Box<ServerWebSocket>),
+    Http(HttpConnection),
+}
+
+impl Stream for ServerConnection {
+    type Item = Result<Message, WsError>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            ServerConnection::WebSocket(ws_stream) => ws_stream.poll_next_unpin(cx),
+            ServerConnection::Http(connection) => connection.poll_next_unpin(cx),
+        }
+    }
+}
+
+impl Sink<Message> for ServerConnection {
+    type Error = WsError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        match self.get_mut() {
+            ServerConnection::WebSocket(ws_stream) => ws_stream.poll_ready_unpin(cx),
+            ServerConnection::Http(connection) => connection.poll_ready_unpin(cx),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: Message) -> Result<(), WsError> {
+        match self.get_mut() {
+            ServerConnection::WebSocket(ws_stream) => ws_stream.start_send_unpin(message),
+            ServerConnection::Http(connection) => connection.start_send_unpin(message),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        match self.get_mut() {
+            ServerConnection::WebSocket(ws_stream) => ws_stream.poll_flush_unpin(cx),
+            ServerConnection::Http(connection) => connection.poll_flush_unpin(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), WsError>> {
+        match self.get_mut() {
+            ServerConnection::WebSocket(ws_stream) => ws_stream.poll_close_unpin(cx),
+            ServerConnection::Http(connection) => connection.poll_close_unpin(cx),
+        }
+    }
+}
+
+/// Opens a WebSocket with the given handshake request. The messages of the Server are
+/// decompressed if the request offers permessage-deflate.
+///
+/// # Parameters
+///
+/// * `request`: the WebSocket handshake request.
+/// * `local_address`: the local address the connection is bound to, if any; otherwise the
+///   operating system chooses it.
+/// * `connector`: the TLS connector of `wss` connections, if not the default one.
+/// * `server_name`: the name sent through SNI, and checked against the certificate, on `wss`
+///   connections, if not the host of the request.
+/// * `proxy`: the HTTP proxy the connection is tunneled through, if any.
+pub(crate) async fn connect_websocket(
+    request: Request<()>,
+    local_address: Option<IpAddr>,
+    connector: Option<Connector>,
+    server_name: Option<&str>,
+    proxy: Option<&Proxy>,
+) -> Result<(ServerWebSocket, Response), WsError> {
+    let uri = request.uri();
+    let is_secure = uri.scheme_str() == Some("wss");
+    let host = uri.host().ok_or(WsError::Url(UrlError::NoHostName))?;
+    let port = uri.port_u16().unwrap_or(if is_secure { 443 } else { 80 });
+    let compressed = request
+        .headers()
+        .get_all("sec-websocket-extensions")
+        .iter()
+        .any(|value| value.as_bytes().starts_with(b"permessage-deflate"));
+    let stream = connect_stream(
+        host,
+        port,
+        is_secure,
+        local_address,
+        connector,
+        server_name,
+        proxy,
+    )
+    .await?;
+    client_async_with_config(request, InflatingStream::new(stream, compressed), None).await
+}
+
+/// Opens a connection to the given host, through the proxy and with TLS as required, ready for
+/// a WebSocket handshake or an HTTP request.
+///
+/// # Parameters
+///
+/// * `host`: the host to connect to.
+/// * `port`: the port to connect to.
+/// * `is_secure`: whether the connection is protected with TLS.
+/// * `local_address`: the local address the connection is bound to, if any.
+/// * `connector`: the TLS connector, if not the default one.
+/// * `server_name`: the name sent through SNI, and checked against the certificate, if not the
+///   host.
+/// * `proxy`: the HTTP proxy the connection is tunneled through, if any.
+pub(crate) async fn connect_stream(
+    host: &str,
+    port: u16,
+    is_secure: bool,
+    local_address: Option<IpAddr>,
+    connector: Option<Connector>,
+    server_name: Option<&str>,
+    proxy: Option<&Proxy>,
+) -> Result<MaybeTlsStream<TcpStream>, WsError> {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let stream = match proxy {
+        Some(proxy) => {
+            if *proxy.get_proxy_type() != ProxyType::Http {
+                return Err(WsError::Io(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("{:?} proxies are not supported", proxy.get_proxy_type()),
+                )));
+            }
+            let stream = connect_tcp(local_address, proxy.get_host(), proxy.get_port()).await?;
+            connect_tunnel(stream, proxy, host, port).await?
+        }
+        None => connect_tcp(local_address, host, port).await?,
+    };
+    // The TLS layer is set up here, since the decompression of WebSocket messages sits between
+    // it and the WebSocket.
+    let stream = match connector {
+        _ if !is_secure => MaybeTlsStream::Plain(stream),
+        Some(Connector::Plain) => MaybeTlsStream::Plain(stream),
+        connector => {
+            let connector = match connector {
+                Some(Connector::NativeTls(connector)) => connector,
+                _ => native_tls::TlsConnector::new().map_err(TlsError::Native)?,
+            };
+            let stream = tokio_native_tls::TlsConnector::from(connector)
+                .connect(server_name.unwrap_or(host), stream)
+                .await
+                .map_err(TlsError::Native)?;
+            MaybeTlsStream::NativeTls(stream)
+        }
+    };
+    Ok(stream)
+}
+
+/// Opens a TCP connection to the given host, from the given local address if any.
+async fn connect_tcp(
+    local_address: Option<IpAddr>,
+    host: &str,
+    port: u16,
+) -> io::Result<TcpStream> {
+    match local_address {
+        Some(local_address) => connect_from(local_address, host, port).await,
+        None => TcpStream::connect((host, port)).await,
+    }
+}
+
+/// The maximum length of the response of a proxy to a `CONNECT` request.
+const MAX_PROXY_RESPONSE_LENGTH: usize = 8192;
+
+/// Opens a tunnel to the given host through an HTTP proxy, with the `CONNECT` method.
+///
+/// # Parameters
+///
+/// * `stream`: the connection to the proxy.
+/// * `proxy`: the proxy, with its credentials, if any.
+/// * `host`: the host to connect to through the proxy.
+/// * `port`: the port to connect to through the proxy.
+async fn connect_tunnel(
+    mut stream: TcpStream,
+    proxy: &Proxy,
+    host: &str,
+    port: u16,
+) -> io::Result<TcpStream> {
+    let authority = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+    let mut request = format!("CONNECT {0} HTTP/1.1\r\nHost: {0}\r\n", authority);
+    if let Some(authorization) = proxy.get_authorization() {
+        request.push_str(&format!("Proxy-Authorization: {}\r\n", authorization));
+    }
+    request.push_str("\r\n");
+    stream.write_all(request.as_bytes()).await?;
+    // The response is read one byte at a time, not to consume any byte of the tunnel.
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        if response.len() == MAX_PROXY_RESPONSE_LENGTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Proxy response too long",
+            ));
+        }
+        response.push(stream.read_u8().await?);
+    }
+    let response = String::from_utf8_lossy(&response);
+    let status_line = response.lines().next().unwrap_or_default();
+    match status_line.split_whitespace().nth(1) {
+        Some(status) if status.starts_with('2') => Ok(stream),
+        _ => Err(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!(
+                "Proxy {}:{} refused the tunnel to {}: {}",
+                proxy.get_host(),
+                proxy.get_port(),
+                authority,
+                status_line
+            ),
+        )),
+    }
+}
+
+/// Opens a TCP connection to the given host from the given local address, trying in turn all
+/// the addresses of the host belonging to the same family as the local one.
+async fn connect_from(local_address: IpAddr, host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(
+        io::ErrorKind::AddrNotAvailable,
+        format!(
+            "No address of '{}' is reachable from local address {}",
+            host, local_address
+        ),
+    );
+    for remote_address in lookup_host((host, port)).await? {
+        if remote_address.is_ipv4() != local_address.is_ipv4() {
+            continue;
+        }
+        let socket = if local_address.is_ipv4() {
+            TcpSocket::new_v4()?
+        } else {
+            TcpSocket::new_v6()?
+        };
+        socket.bind(SocketAddr::new(local_address, 0))?;
+        match socket.connect(remote_address).await {
+            Ok(stream) => return Ok(stream),
+            Err(err) => last_error = err,
+        }
+    }
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_connect_from_local_address() {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let stream = connect_from(IpAddr::V4(Ipv4Addr::LOCALHOST), "127.0.0.1", port)
+            .await
+            .unwrap();
+        assert_eq!(
+            stream.local_addr().unwrap().ip(),
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        );
+    }
+
+    /// Accepts a connection, answering its `CONNECT` request with the given status line, and
+    /// returns the request followed by the bytes sent through the tunnel.
+    async fn spawn_proxy(status_line: &'static str) -> (u16, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let task = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            while !received.ends_with(b"\r\n\r\n") {
+                received.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(format!("{}\r\nVia: test\r\n\r\n", status_line).as_bytes())
+                .await
+                .unwrap();
+            let _ = stream.read_to_end(&mut received).await;
+            String::from_utf8(received).unwrap()
+        });
+        (port, task)
+    }
+
+    #[tokio::test]
+    async fn test_connect_tunnel_through_proxy() {
+        let (port, proxy_task) = spawn_proxy("HTTP/1.1 200 Connection established").await;
+        let proxy = Proxy::new(
+            ProxyType::Http,
+            "127.0.0.1".to_string(),
+            port,
+            Some("user".to_string()),
+            Some("secret".to_string()),
+        );
+        let stream = connect_tcp(None, "127.0.0.1", port).await.unwrap();
+        let mut tunnel = connect_tunnel(stream, &proxy, "push.example.com", 443)
+            .await
+            .unwrap();
+        tunnel.write_all(b"hello").await.unwrap();
+        drop(tunnel);
+
+        assert_eq!(
+            proxy_task.await.unwrap(),
+            "CONNECT push.example.com:443 HTTP/1.1\r\nHost: push.example.com:443\r\n\
+             Proxy-Authorization: Basic dXNlcjpzZWNyZXQ=\r\n\r\nhello"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_connect_tunnel_refused_by_proxy() {
+        let (port, _proxy_task) = spawn_proxy("HTTP/1.1 407 Proxy Authentication Required").await;
+        let proxy = Proxy::new(ProxyType::Http, "127.0.0.1".to_string(), port, None, None);
+        let stream = connect_tcp(None, "127.0.0.1", port).await.unwrap();
+        let err = connect_tunnel(stream, &proxy, "::1", 80).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+        assert!(err.to_string().contains("tunnel to [::1]:80"));
+        assert!(err.to_string().contains("407"));
+    }
+
+    #[tokio::test]
+    async fn test_connect_from_other_family_fails() {
+        let result = connect_from("::1".parse().unwrap(), "127.0.0.1", 80).await;
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::AddrNotAvailable);
+    }
+}
@@ -0,0 +1,393 @@
+use crate::client::model::ClientStatus;
+use crate::utils::ServerErrorCode;
+use std::collections::VecDeque;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+/// The reason why the status of a `LightstreamerClient` changed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusChangeCause {
+    /// The application called `LightstreamerClient::connect()`.
+    ConnectRequested,
+    /// The client is opening a new session after the previous one ended.
+    Retry {
+        /// The number of the attempt, starting from 1.
+        attempt: u32,
+    },
+    /// The Server confirmed the creation of a session (CONOK).
+    SessionCreated {
+        /// The ID of the session.
+        session_id: String,
+    },
+    /// The Server confirmed the recovery of the session on a new connection (CONOK).
+    SessionRecovered {
+        /// The ID of the session.
+        session_id: String,
+    },
+    /// The attempts to recover the session exceeded `ConnectionOptions::get_session_recovery_timeout()`,
+    /// so the session was abandoned.
+    RecoveryAbandoned,
+    /// The Server refused the creation of a session (CONERR).
+    ConnectionRefused {
+        /// The error code sent by the Server.
+        code: i32,
+        /// The error message sent by the Server.
+        message: String,
+    },
+    /// The Server closed the session (END).
+    SessionEnded {
+        /// The cause code sent by the Server.
+        code: i32,
+        /// The cause message sent by the Server.
+        message: String,
+    },
+    /// A timeout expired, such as the one waiting for keepalives from the Server.
+    Timeout(String),
+    /// The Server closed the connection without ending the session.
+    ConnectionClosed,
+    /// The connection was lost because of a network error, without the session being ended.
+    ConnectionLost(String),
+    /// The session failed because of an error on the client side or on the network.
+    Failure(String),
+    /// The application requested a shutdown.
+    ShutdownRequested,
+}
+
+impl Display for StatusChangeCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            StatusChangeCause::ConnectRequested => write!(f, "connect requested"),
+            StatusChangeCause::Retry { attempt } => write!(f, "retry attempt {}", attempt),
+            StatusChangeCause::SessionCreated { session_id } => {
+                write!(f, "session {} created", session_id)
+            }
+            StatusChangeCause::SessionRecovered { session_id } => {
+                write!(f, "session {} recovered", session_id)
+            }
+            StatusChangeCause::RecoveryAbandoned => write!(f, "session recovery abandoned"),
+            StatusChangeCause::ConnectionRefused { code, message } => {
+                write!(f, "CONERR {}: {}", code, message)
+            }
+            StatusChangeCause::SessionEnded { code, message } => {
+                write!(f, "END {}: {}", code, message)
+            }
+            StatusChangeCause::Timeout(timeout) => write!(f, "{} timeout", timeout),
+            StatusChangeCause::ConnectionClosed => write!(f, "connection closed by server"),
+            StatusChangeCause::ConnectionLost(error) => write!(f, "connection lost: {}", error),
+            StatusChangeCause::Failure(error) => write!(f, "failure: {}", error),
+            StatusChangeCause::ShutdownRequested => write!(f, "shutdown requested"),
+        }
+    }
+}
+
+impl StatusChangeCause {
+    /// Whether the cause is an error, i.e. the reason why a connection or session went down.
+    pub fn is_error(&self) -> bool {
+        matches!(
+            self,
+            StatusChangeCause::ConnectionRefused { .. }
+                | StatusChangeCause::SessionEnded { .. }
+                | StatusChangeCause::Timeout(_)
+                | StatusChangeCause::ConnectionClosed
+                | StatusChangeCause::ConnectionLost(_)
+                | StatusChangeCause::Failure(_)
+        )
+    }
+
+    /// Returns the meaning of the error code of a session refused by the Server (CONERR), or
+    /// `None` for any other cause.
+    pub fn get_error_code(&self) -> Option<ServerErrorCode> {
+        match self {
+            StatusChangeCause::ConnectionRefused { code, .. } => {
+                Some(ServerErrorCode::from_code(*code))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// How an error affected a `LightstreamerClient`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorSeverity {
+    /// The client recovered, or will retry, after the error.
+    Transient,
+    /// The client gave up after the error and will not retry on its own.
+    Fatal,
+}
+
+impl Display for ErrorSeverity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorSeverity::Transient => write!(f, "transient"),
+            ErrorSeverity::Fatal => write!(f, "fatal"),
+        }
+    }
+}
+
+/// The most recent error of a `LightstreamerClient`, as returned by
+/// `LightstreamerClient::last_error()` and `LastErrorHandle::get()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LastError {
+    /// The wall-clock time of the error.
+    pub timestamp: SystemTime,
+    /// Whether the client gave up after the error.
+    pub severity: ErrorSeverity,
+    /// The error, as the cause of the status change it triggered.
+    pub cause: StatusChangeCause,
+}
+
+impl Display for LastError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} error: {}", self.severity, self.cause)
+    }
+}
+
+/// Shared slot holding the most recent error of a `LightstreamerClient`.
+///
+/// Handles are cheap to clone and share the same slot, so the error can be read from another
+/// task while the client is connected.
+///
+/// See also `LightstreamerClient::get_last_error_handle()`
+#[derive(Debug, Clone, Default)]
+pub struct LastErrorHandle {
+    error: Arc<Mutex<Option<LastError>>>,
+}
+
+impl LastErrorHandle {
+    /// Returns the most recent error, or `None` if there was none.
+    pub fn get(&self) -> Option<LastError> {
+        self.error
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /// Records an error, replacing the previous one.
+    pub(crate) fn record(&self, error: LastError) {
+        *self.error.lock().unwrap_or_else(|err| err.into_inner()) = Some(error);
+    }
+}
+
+/// A change in the status of a `LightstreamerClient`, with the time it happened and its cause.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusTransition {
+    /// The wall-clock time of the change.
+    pub timestamp: SystemTime,
+    /// The status entered.
+    pub status: ClientStatus,
+    /// The reason of the change.
+    pub cause: StatusChangeCause,
+}
+
+impl Display for StatusTransition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.status, self.cause)
+    }
+}
+
+/// Bounded history of the status transitions of a `LightstreamerClient`, oldest first, to
+/// reconstruct what happened around a data gap.
+///
+/// Handles are cheap to clone and share the same history, so it can be inspected from another
+/// task while the client is connected.
+///
+/// See also `LightstreamerClient::get_status_history()`
+#[derive(Debug, Clone)]
+pub struct StatusHistory {
+    /// The maximum number of transitions kept.
+    capacity: usize,
+    /// The transitions, oldest first.
+    transitions: Arc<Mutex<VecDeque<StatusTransition>>>,
+}
+
+impl StatusHistory {
+    /// The number of transitions kept by default.
+    pub const DEFAULT_CAPACITY: usize = 64;
+
+    /// Creates an empty history.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity`: the maximum number of transitions kept; older ones are discarded first.
+    pub fn new(capacity: usize) -> Self {
+        StatusHistory {
+            capacity,
+            transitions: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+        }
+    }
+
+    /// Returns the maximum number of transitions kept.
+    pub fn get_capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Records a transition, discarding the oldest one if the history is full.
+    pub(crate) fn record(&self, status: ClientStatus, cause: StatusChangeCause) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut transitions = self
+            .transitions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        if transitions.len() == self.capacity {
+            transitions.pop_front();
+        }
+        transitions.push_back(StatusTransition {
+            timestamp: SystemTime::now(),
+            status,
+            cause,
+        });
+    }
+
+    /// Returns a copy of the transitions, oldest first.
+    pub fn get_transitions(&self) -> Vec<StatusTransition> {
+        let transitions = self
+            .transitions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        transitions.iter().cloned().collect()
+    }
+
+    /// Returns the transitions that happened at or after the given time, oldest first.
+    pub fn get_transitions_since(&self, since: SystemTime) -> Vec<StatusTransition> {
+        self.get_transitions()
+            .into_iter()
+            .filter(|transition| transition.timestamp >= since)
+            .collect()
+    }
+
+    /// Returns the most recent transition, if any.
+    pub fn get_last(&self) -> Option<StatusTransition> {
+        let transitions = self
+            .transitions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner());
+        transitions.back().cloned()
+    }
+
+    /// Discards all the transitions.
+    pub fn clear(&self) {
+        self.transitions
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+}
+
+impl Default for StatusHistory {
+    fn default() -> Self {
+        StatusHistory::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::model::{ConnectionType, DisconnectionType};
+    use std::time::Duration;
+
+    #[test]
+    fn test_keeps_last_transitions() {
+        let history = StatusHistory::new(2);
+        history.record(
+            ClientStatus::Connecting,
+            StatusChangeCause::ConnectRequested,
+        );
+        history.record(
+            ClientStatus::Connected(ConnectionType::WsStreaming),
+            StatusChangeCause::SessionCreated {
+                session_id: "S1".to_string(),
+            },
+        );
+        history.record(
+            ClientStatus::Disconnected(DisconnectionType::NoRetry),
+            StatusChangeCause::SessionEnded {
+                code: 31,
+                message: "Terminated by admin".to_string(),
+            },
+        );
+
+        let transitions = history.get_transitions();
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(
+            transitions[0].status,
+            ClientStatus::Connected(ConnectionType::WsStreaming)
+        );
+        assert_eq!(
+            history.get_last().unwrap().to_string(),
+            "DISCONNECTED (END 31: Terminated by admin)"
+        );
+
+        history.clear();
+        assert!(history.get_last().is_none());
+    }
+
+    #[test]
+    fn test_transitions_since() {
+        let history = StatusHistory::default();
+        history.record(
+            ClientStatus::Connecting,
+            StatusChangeCause::ConnectRequested,
+        );
+        let since = SystemTime::now() + Duration::from_secs(60);
+        assert!(history.get_transitions_since(since).is_empty());
+        assert_eq!(
+            history.get_transitions_since(SystemTime::UNIX_EPOCH).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_clones_share_the_history() {
+        let history = StatusHistory::new(4);
+        let handle = history.clone();
+        history.record(
+            ClientStatus::Stalled,
+            StatusChangeCause::Timeout("keepalive".to_string()),
+        );
+        assert_eq!(
+            handle.get_last().unwrap().to_string(),
+            "STALLED (keepalive timeout)"
+        );
+    }
+
+    #[test]
+    fn test_clones_share_the_last_error() {
+        let last_error = LastErrorHandle::default();
+        let handle = last_error.clone();
+        assert!(handle.get().is_none());
+        last_error.record(LastError {
+            timestamp: SystemTime::now(),
+            severity: ErrorSeverity::Transient,
+            cause: StatusChangeCause::ConnectionClosed,
+        });
+        assert_eq!(handle.get().unwrap().severity, ErrorSeverity::Transient);
+    }
+
+    #[test]
+    fn test_error_causes() {
+        assert!(StatusChangeCause::ConnectionLost("reset".to_string()).is_error());
+        assert!(StatusChangeCause::ConnectionClosed.is_error());
+        assert!(!StatusChangeCause::ShutdownRequested.is_error());
+        assert!(!StatusChangeCause::RecoveryAbandoned.is_error());
+        let error = LastError {
+            timestamp: SystemTime::now(),
+            severity: ErrorSeverity::Fatal,
+            cause: StatusChangeCause::ConnectionRefused {
+                code: 2,
+                message: "Requested Adapter Set not available".to_string(),
+            },
+        };
+        assert_eq!(
+            error.to_string(),
+            "fatal error: CONERR 2: Requested Adapter Set not available"
+        );
+        assert_eq!(
+            error.cause.get_error_code(),
+            Some(ServerErrorCode::AdapterSetUnavailable)
+        );
+        assert_eq!(StatusChangeCause::ConnectionClosed.get_error_code(), None);
+    }
+}
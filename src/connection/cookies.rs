@@ -0,0 +1,278 @@
+use cookie::Cookie;
+use cookie::time::OffsetDateTime;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex};
+use url::Url;
+
+/// A cookie kept by a `CookieStore`, with the host it was received from.
+#[derive(Clone)]
+struct StoredCookie {
+    /// The domain the cookie applies to, lowercase and without leading dot.
+    domain: String,
+    /// Whether the cookie only applies to `domain` itself, and not to its subdomains, since it
+    /// was received without a `Domain` attribute.
+    host_only: bool,
+    /// The path the cookie applies to.
+    path: String,
+    /// The cookie.
+    cookie: Cookie<'static>,
+}
+
+impl StoredCookie {
+    /// Returns whether the cookie has to be sent in a request to the given URL.
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+        let domain_matches = if self.host_only {
+            host == self.domain
+        } else {
+            host == self.domain || host.ends_with(&format!(".{}", self.domain))
+        };
+        let path = url.path();
+        let path_matches = path == self.path
+            || (path.starts_with(&self.path)
+                && (self.path.ends_with('/') || path[self.path.len()..].starts_with('/')));
+        let secure_matches =
+            !self.cookie.secure().unwrap_or(false) || matches!(url.scheme(), "https" | "wss");
+        domain_matches && path_matches && secure_matches && !self.is_expired()
+    }
+
+    /// Returns whether the cookie expired.
+    fn is_expired(&self) -> bool {
+        self.cookie
+            .expires_datetime()
+            .is_some_and(|expires| expires <= OffsetDateTime::now_utc())
+    }
+}
+
+/// Cookie set used to access the Server, which can be shared by several clients.
+///
+/// Each `LightstreamerClient` has its own store by default. Giving the same store to sibling
+/// clients targeting the same cluster (see `LightstreamerClient::set_cookie_store()`) makes the
+/// cookies obtained by one of them, such as load-balancer affinity cookies, apply to all of them.
+/// Handles are cheap to clone and share the same cookies.
+///
+/// Cookies received on the WebSocket handshake and on the responses of the HTTP transports are
+/// added automatically, and the matching ones are sent on every WebSocket handshake and HTTP
+/// request.
+#[derive(Clone, Default)]
+pub struct CookieStore {
+    /// The cookies, in insertion order.
+    cookies: Arc<Mutex<Vec<StoredCookie>>>,
+}
+
+impl CookieStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds cookies received from the given URL, replacing the cookies with the same name,
+    /// domain and path. Cookies already expired, or whose domain is not compatible with the URL,
+    /// are discarded, and remove the cookie they would replace.
+    ///
+    /// # Parameters
+    ///
+    /// * `url`: the URL the cookies were received from.
+    /// * `cookies`: the cookies.
+    pub fn add_cookies(&self, url: &Url, cookies: &[Cookie<'_>]) {
+        let Some(host) = url.host_str().map(str::to_ascii_lowercase) else {
+            return;
+        };
+        let mut stored_cookies = self.cookies.lock().unwrap_or_else(|err| err.into_inner());
+        for cookie in cookies {
+            let (domain, host_only) = match cookie.domain() {
+                Some(domain) => (domain.trim_start_matches('.').to_ascii_lowercase(), false),
+                None => (host.clone(), true),
+            };
+            if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                continue;
+            }
+            let path = match cookie.path() {
+                Some(path) if path.starts_with('/') => path.to_string(),
+                _ => default_path(url),
+            };
+            stored_cookies.retain(|stored| {
+                !(stored.cookie.name() == cookie.name()
+                    && stored.domain == domain
+                    && stored.path == path)
+            });
+            let stored = StoredCookie {
+                domain,
+                host_only,
+                path,
+                cookie: cookie.clone().into_owned(),
+            };
+            let removed = stored.is_expired()
+                || stored
+                    .cookie
+                    .max_age()
+                    .is_some_and(|max_age| max_age.is_zero() || max_age.is_negative());
+            if !removed {
+                stored_cookies.push(stored);
+            }
+        }
+    }
+
+    /// Parses and adds the cookies of the `Set-Cookie` header values received from the given
+    /// URL, ignoring the invalid ones.
+    pub(crate) fn add_set_cookie_headers<'a>(
+        &self,
+        url: &Url,
+        headers: impl IntoIterator<Item = &'a str>,
+    ) {
+        let cookies: Vec<Cookie<'_>> = headers
+            .into_iter()
+            .filter_map(|header| Cookie::parse(header).ok())
+            .collect();
+        self.add_cookies(url, &cookies);
+    }
+
+    /// Returns the cookies to be sent in a request to the given URL.
+    ///
+    /// # Parameters
+    ///
+    /// * `url`: the URL of the request.
+    pub fn get_cookies(&self, url: &Url) -> Vec<Cookie<'static>> {
+        let mut stored_cookies = self.cookies.lock().unwrap_or_else(|err| err.into_inner());
+        stored_cookies.retain(|stored| !stored.is_expired());
+        stored_cookies
+            .iter()
+            .filter(|stored| stored.matches(url))
+            .map(|stored| stored.cookie.clone())
+            .collect()
+    }
+
+    /// Returns the value of the `Cookie` header to be sent in a request to the given URL, if
+    /// any cookie applies to it.
+    pub(crate) fn get_cookie_header(&self, url: &Url) -> Option<String> {
+        let cookies = self.get_cookies(url);
+        if cookies.is_empty() {
+            return None;
+        }
+        Some(
+            cookies
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name(), cookie.value()))
+                .collect::<Vec<String>>()
+                .join("; "),
+        )
+    }
+
+    /// Discards all the cookies.
+    pub fn clear(&self) {
+        self.cookies
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+}
+
+impl Debug for CookieStore {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        // Cookie values may carry credentials: only their number is shown.
+        let count = self
+            .cookies
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .len();
+        f.debug_struct("CookieStore")
+            .field("cookies", &count)
+            .finish()
+    }
+}
+
+/// Returns the default path of the cookies received from the given URL without a `Path`
+/// attribute: the path of the URL up to its last `/`, excluded.
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(pos) => url.path()[..pos].to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).unwrap()
+    }
+
+    #[test]
+    fn test_cookies_are_shared_between_handles() {
+        let store = CookieStore::new();
+        let sibling = store.clone();
+        store.add_set_cookie_headers(
+            &url("wss://push.example.com/lightstreamer"),
+            ["AWSALB=node-2; Path=/", "invalid"],
+        );
+
+        assert_eq!(
+            sibling.get_cookie_header(&url("wss://push.example.com/lightstreamer")),
+            Some("AWSALB=node-2".to_string())
+        );
+        assert_eq!(
+            sibling.get_cookie_header(&url("wss://other.example.com/lightstreamer")),
+            None
+        );
+    }
+
+    #[test]
+    fn test_domain_cookies_apply_to_subdomains() {
+        let store = CookieStore::new();
+        store.add_set_cookie_headers(
+            &url("https://push1.example.com/"),
+            ["affinity=a; Domain=.example.com", "session=s"],
+        );
+
+        assert_eq!(
+            store.get_cookie_header(&url("wss://push2.example.com/lightstreamer")),
+            Some("affinity=a".to_string())
+        );
+        assert_eq!(
+            store.get_cookie_header(&url("wss://push1.example.com/lightstreamer")),
+            Some("affinity=a; session=s".to_string())
+        );
+
+        // Incompatible domains are rejected.
+        store.add_set_cookie_headers(&url("https://push1.example.com/"), ["x=1; Domain=evil.com"]);
+        assert!(store.get_cookies(&url("https://evil.com/")).is_empty());
+    }
+
+    #[test]
+    fn test_cookies_are_replaced_and_removed() {
+        let store = CookieStore::new();
+        let server = url("https://push.example.com/");
+        store.add_set_cookie_headers(&server, ["node=1"]);
+        store.add_set_cookie_headers(&server, ["node=2"]);
+        assert_eq!(store.get_cookie_header(&server), Some("node=2".to_string()));
+
+        store.add_set_cookie_headers(&server, ["node=; Max-Age=0"]);
+        assert_eq!(store.get_cookie_header(&server), None);
+    }
+
+    #[test]
+    fn test_secure_and_path_attributes() {
+        let store = CookieStore::new();
+        store.add_set_cookie_headers(
+            &url("https://push.example.com/"),
+            ["secure=1; Secure", "scoped=1; Path=/lightstreamer"],
+        );
+
+        assert_eq!(
+            store.get_cookie_header(&url("ws://push.example.com/other")),
+            None
+        );
+        assert_eq!(
+            store.get_cookie_header(&url("wss://push.example.com/lightstreamer")),
+            Some("secure=1; scoped=1".to_string())
+        );
+        assert_eq!(
+            store.get_cookie_header(&url("wss://push.example.com/lightstreamerx")),
+            Some("secure=1".to_string())
+        );
+    }
+}
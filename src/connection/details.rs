@@ -1,6 +1,5 @@
 use crate::client::ClientListener;
-use crate::utils::IllegalArgumentException;
-use std::error::Error;
+use crate::utils::{IllegalArgumentException, LightstreamerError};
 use std::fmt::{self, Debug, Formatter};
 
 /// Used by `LightstreamerClient` to provide a basic connection properties data object.
@@ -176,7 +175,7 @@ impl ConnectionDetails {
         adapter_set: Option<&str>,
         user: Option<&str>,
         password: Option<&str>,
-    ) -> Result<ConnectionDetails, Box<dyn Error>> {
+    ) -> Result<ConnectionDetails, LightstreamerError> {
         let mut connection_details = ConnectionDetails::default();
         connection_details.set_server_address(server_address.map(|s| s.to_string()))?;
         connection_details.set_adapter_set(adapter_set.map(|s| s.to_string()));
@@ -291,14 +290,16 @@ impl ConnectionDetails {
     pub fn set_server_address(
         &mut self,
         server_address: Option<String>,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         // Validate the server address
-        if let Some(address) = &server_address {
-            if !address.starts_with("http://") && !address.starts_with("https://") {
-                return Err(IllegalArgumentException::new(
-                    "Invalid server address: must start with http:// or https://",
-                ));
-            }
+        if let Some(address) = &server_address
+            && !address.starts_with("http://")
+            && !address.starts_with("https://")
+        {
+            return Err(IllegalArgumentException::new(
+                "Invalid server address: must start with http:// or https://",
+            )
+            .into());
         }
 
         self.server_address = server_address;
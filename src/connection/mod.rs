@@ -4,8 +4,10 @@
    Date: 16/5/25
 ******************************************************************************/
 
+mod cookies;
 mod details;
 mod options;
 
+pub use self::cookies::CookieStore;
 pub use self::details::ConnectionDetails;
 pub use self::options::ConnectionOptions;
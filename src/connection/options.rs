@@ -1,7 +1,8 @@
-use crate::client::Transport;
-use crate::utils::{IllegalArgumentException, Proxy};
+use crate::client::{EndCauseReaction, Transport};
+use crate::utils::{IllegalArgumentException, LightstreamerError, Proxy};
 use std::collections::HashMap;
 use std::fmt::{self, Debug, Formatter};
+use std::net::IpAddr;
 
 /// Used by LightstreamerClient to provide an extra connection properties data object.
 /// Data struct that contains the policy settings used to connect to a Lightstreamer Server.
@@ -9,17 +10,28 @@ use std::fmt::{self, Debug, Formatter};
 ///
 /// See also `LightstreamerClient`
 pub struct ConnectionOptions {
+    compression_enabled: bool,
     content_length: Option<u64>,
+    control_batching_window: u64,
+    #[cfg(feature = "dangerous-dev")]
+    dangerous_accept_invalid_certificates: bool,
+    end_cause_reactions: HashMap<i32, EndCauseReaction>,
     first_retry_max_delay: u64,
     forced_transport: Option<Transport>,
     http_extra_headers: Option<HashMap<String, String>>,
     http_extra_headers_on_session_creation_only: bool,
+    http_streaming_timeout: u64,
     idle_timeout: u64,
     keepalive_interval: u64,
+    local_address: Option<IpAddr>,
+    max_message_size: Option<usize>,
+    message_queue_size: usize,
     polling_interval: u64,
     proxy: Option<Proxy>,
+    proxy_from_environment: bool,
     real_max_bandwidth: Option<u64>,
     reconnect_timeout: u64,
+    resubscribe_on_oversized_message: bool,
     requested_max_bandwidth: Option<f64>,
     retry_delay: u64,
     reverse_heartbeat_interval: u64,
@@ -27,40 +39,63 @@ pub struct ConnectionOptions {
     session_recovery_timeout: u64,
     slowing_enabled: bool,
     stalled_timeout: u64,
+    subscribe_retries: u32,
+    subscribe_timeout: u64,
+    slow_start_threshold: u64,
     send_sync: bool,
-    _reduce_head: bool,
+    reduce_head: bool,
     supported_diffs: Option<String>,
     polling: bool,
+    transport_probe_timeout: u64,
+    tls_server_name: Option<String>,
     ttl_millis: Option<u64>,
+    ws_streaming_timeout: u64,
 }
 
 impl ConnectionOptions {
     /// Creates a new instance of `ConnectionOptions` with default values.
     pub fn new() -> Self {
         ConnectionOptions {
+            compression_enabled: false,
             content_length: None,
+            control_batching_window: 0,
+            #[cfg(feature = "dangerous-dev")]
+            dangerous_accept_invalid_certificates: false,
+            end_cause_reactions: HashMap::new(),
             first_retry_max_delay: 100,
             forced_transport: None,
             http_extra_headers: None,
             http_extra_headers_on_session_creation_only: false,
+            http_streaming_timeout: 4000,
             idle_timeout: 19000,
             keepalive_interval: 0,
+            local_address: None,
+            max_message_size: None,
+            message_queue_size: 1000,
             polling_interval: 0,
             proxy: None,
+            proxy_from_environment: true,
             real_max_bandwidth: None,
             reconnect_timeout: 3000,
+            resubscribe_on_oversized_message: false,
             requested_max_bandwidth: None,
             retry_delay: 4000,
             reverse_heartbeat_interval: 0,
             session_recovery_timeout: 15000,
             slowing_enabled: false,
             stalled_timeout: 2000,
+            subscribe_retries: 0,
+            subscribe_timeout: 0,
+            slow_start_threshold: 0,
             server_instance_address_ignored: false,
             send_sync: true,
-            _reduce_head: false,
+            reduce_head: false,
             supported_diffs: None,
             polling: false,
+            transport_probe_timeout: 0,
+            tls_server_name: None,
             ttl_millis: None,
+            ws_streaming_timeout: 4000,
         }
     }
 
@@ -237,13 +272,93 @@ impl ConnectionOptions {
         self.reverse_heartbeat_interval
     }
 
-    /// Inquiry method that gets if LS_send_sync is to be sent to the server.
-    /// If set to false, instructs the Server not to send the SYNC notifications on this connection.
-    /// If omitted, the default is true.
+    /// Inquiry method that checks whether the Server is asked to send the SYNC notifications,
+    /// with which the client measures how far behind the Server it is.
+    ///
+    /// # Returns
+    ///
+    /// true if the SYNC notifications are requested.
+    ///
+    /// See also `setSendSync()`
     pub fn get_send_sync(&self) -> bool {
         self.send_sync
     }
 
+    /// Setter method that asks the Server to send, or not, the SYNC notifications, which carry
+    /// the time elapsed since the start of the session, so that the client can measure how far
+    /// behind the Server it is (see `SessionDiagnostics::get_sync_delay()`). Disabling them saves
+    /// a little bandwidth on constrained links.
+    ///
+    /// true (the SYNC notifications are requested).
+    ///
+    /// This value can be set and changed at any time. The supplied value will be used for the
+    /// next connection, through the `LS_send_sync` parameter.
+    ///
+    /// # Parameters
+    ///
+    /// * `send_sync`: true to request the SYNC notifications, false otherwise.
+    pub fn set_send_sync(&mut self, send_sync: bool) {
+        self.send_sync = send_sync;
+    }
+
+    /// Inquiry method that checks whether the Server is asked to reduce the headers of its
+    /// responses.
+    ///
+    /// # Returns
+    ///
+    /// true if reduced headers are requested.
+    ///
+    /// See also `setReduceHead()`
+    pub fn get_reduce_head(&self) -> bool {
+        self.reduce_head
+    }
+
+    /// Setter method that asks the Server to reduce the headers of its HTTP responses to the
+    /// minimum, to save bandwidth on constrained links. The notifications themselves are not
+    /// affected.
+    ///
+    /// false (the headers are not reduced).
+    ///
+    /// This value can be set and changed at any time. The supplied value will be used for the
+    /// next connection, through the `LS_reduce_head` parameter.
+    ///
+    /// # Parameters
+    ///
+    /// * `reduce_head`: true to request reduced headers, false otherwise.
+    pub fn set_reduce_head(&mut self, reduce_head: bool) {
+        self.reduce_head = reduce_head;
+    }
+
+    /// Inquiry method that checks whether the compression of the WebSocket stream is requested.
+    ///
+    /// # Returns
+    ///
+    /// true if the permessage-deflate extension is negotiated.
+    ///
+    /// See also `setCompressionEnabled()`
+    pub fn is_compression_enabled(&self) -> bool {
+        self.compression_enabled
+    }
+
+    /// Setter method that enables the negotiation of the permessage-deflate extension on the
+    /// WebSocket transport, so that the Server compresses the notifications it sends, at the
+    /// cost of some CPU on both sides. This pays off on high-volume subscriptions over
+    /// constrained links. The requests of the client are never compressed.
+    ///
+    /// If the Server does not support the extension, the stream is simply not compressed.
+    ///
+    /// false (the stream is not compressed).
+    ///
+    /// This value can be set and changed at any time. The supplied value will be used for the
+    /// next WebSocket connection.
+    ///
+    /// # Parameters
+    ///
+    /// * `compression_enabled`: true to request a compressed stream, false otherwise.
+    pub fn set_compression_enabled(&mut self, compression_enabled: bool) {
+        self.compression_enabled = compression_enabled;
+    }
+
     /// Inquiry method that gets the maximum time allowed for attempts to recover the current session
     /// upon an interruption, after which a new session will be created. A 0 value also means that
     /// any attempt to recover the current session is prevented in the first place.
@@ -332,14 +447,9 @@ impl ConnectionOptions {
     /// # Raises
     ///
     /// * `IllegalArgumentException`: if a negative or zero value is configured
-    pub fn set_content_length(
-        &mut self,
-        content_length: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    pub fn set_content_length(&mut self, content_length: u64) -> Result<(), LightstreamerError> {
         if content_length == 0 {
-            return Err(IllegalArgumentException::new(
-                "Content length cannot be zero",
-            ));
+            return Err(IllegalArgumentException::new("Content length cannot be zero").into());
         }
 
         self.content_length = Some(content_length);
@@ -373,11 +483,11 @@ impl ConnectionOptions {
     pub fn set_first_retry_max_delay(
         &mut self,
         first_retry_max_delay: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         if first_retry_max_delay == 0 {
-            return Err(IllegalArgumentException::new(
-                "First retry max delay cannot be zero",
-            ));
+            return Err(
+                IllegalArgumentException::new("First retry max delay cannot be zero").into(),
+            );
         }
 
         self.first_retry_max_delay = first_retry_max_delay;
@@ -524,9 +634,9 @@ impl ConnectionOptions {
     /// # Raises
     ///
     /// * `IllegalArgumentException`: if a negative value is configured
-    pub fn set_idle_timeout(&mut self, idle_timeout: u64) -> Result<(), IllegalArgumentException> {
+    pub fn set_idle_timeout(&mut self, idle_timeout: u64) -> Result<(), LightstreamerError> {
         if idle_timeout == 0 {
-            return Err(IllegalArgumentException::new("Idle timeout cannot be zero"));
+            return Err(IllegalArgumentException::new("Idle timeout cannot be zero").into());
         }
 
         self.idle_timeout = idle_timeout;
@@ -544,8 +654,9 @@ impl ConnectionOptions {
     ///
     /// The keepalive interval should be set before calling the `LightstreamerClient.connect()`
     /// method. However, the value can be changed at any time: the supplied value will be used
-    /// for the next streaming connection (either a bind or a brand new session). Note that, after
-    /// a connection, the value may be changed to the one imposed by the Server.
+    /// for the next streaming connection (either a bind or a brand new session). Note that the
+    /// Server may impose a different value: the interval requested through `LS_keepalive_millis`
+    /// and the one granted are available through `LightstreamerClient::get_session_info()`.
     ///
     /// A change to this setting will be notified through a call to `ClientListener.onPropertyChange()`
     /// with argument "keepaliveInterval" on any `ClientListener` listening to the related `LightstreamerClient`.
@@ -564,7 +675,7 @@ impl ConnectionOptions {
     pub fn set_keepalive_interval(
         &mut self,
         keepalive_interval: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         if keepalive_interval == 0 {
             self.keepalive_interval = keepalive_interval;
             return Ok(());
@@ -574,7 +685,7 @@ impl ConnectionOptions {
         {
             return Err(IllegalArgumentException::new(
                 "Keepalive interval should be greater than or equal to stalled timeout and reconnect timeout",
-            ));
+            ).into());
         }
 
         self.keepalive_interval = keepalive_interval;
@@ -626,7 +737,7 @@ impl ConnectionOptions {
     pub fn set_polling_interval(
         &mut self,
         polling_interval: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         if polling_interval == 0 {
             self.polling_interval = polling_interval;
             return Ok(());
@@ -635,7 +746,8 @@ impl ConnectionOptions {
         if polling_interval < self.idle_timeout {
             return Err(IllegalArgumentException::new(
                 "Polling interval should be greater than or equal to idle timeout",
-            ));
+            )
+            .into());
         }
 
         self.polling_interval = polling_interval;
@@ -645,7 +757,11 @@ impl ConnectionOptions {
     /// Setter method that configures the coordinates to a proxy server to be used to connect
     /// to the Lightstreamer Server.
     ///
-    /// None (meaning not to pass through a proxy).
+    /// Only HTTP proxies are supported, through the `CONNECT` method; the connection attempts
+    /// through a SOCKS proxy fail.
+    ///
+    /// None (meaning not to pass through a proxy, unless one is configured through the
+    /// environment, see `setProxyFromEnvironment()`).
     ///
     /// This value can be set and changed at any time. The supplied value will be used for the
     /// next connection attempt.
@@ -660,6 +776,61 @@ impl ConnectionOptions {
         self.proxy = proxy;
     }
 
+    /// Inquiry method that gets the coordinates of the proxy server configured through
+    /// `setProxy()`.
+    ///
+    /// # Returns
+    ///
+    /// The proxy configuration, or `None` if no proxy was configured.
+    ///
+    /// See also `setProxy()`
+    pub fn get_proxy(&self) -> Option<&Proxy> {
+        self.proxy.as_ref()
+    }
+
+    /// Setter method that enables or disables the use of the proxy configured through the
+    /// `HTTP_PROXY`, `HTTPS_PROXY` and `NO_PROXY` environment variables, when no proxy is
+    /// configured through `setProxy()`, so that deployments behind an egress proxy work without
+    /// configuration in code. See `Proxy::from_env()` for how the variables are read.
+    ///
+    /// true (the environment is honored).
+    ///
+    /// This value can be set and changed at any time. The supplied value will be used for the
+    /// next connection attempt.
+    ///
+    /// # Parameters
+    ///
+    /// * `proxy_from_environment`: true to honor the environment, false to connect directly
+    ///   unless a proxy is configured through `setProxy()`.
+    pub fn set_proxy_from_environment(&mut self, proxy_from_environment: bool) {
+        self.proxy_from_environment = proxy_from_environment;
+    }
+
+    /// Inquiry method that checks whether the proxy configured through the environment is used.
+    ///
+    /// # Returns
+    ///
+    /// true if the environment is honored.
+    ///
+    /// See also `setProxyFromEnvironment()`
+    pub fn is_proxy_from_environment(&self) -> bool {
+        self.proxy_from_environment
+    }
+
+    /// Returns the proxy to pass through to connect to the given Server address: the one
+    /// configured through `setProxy()`, or else the one configured through the environment, if
+    /// honored.
+    pub(crate) fn resolve_proxy(
+        &self,
+        server_address: &str,
+    ) -> Result<Option<Proxy>, LightstreamerError> {
+        match &self.proxy {
+            Some(proxy) => Ok(Some(proxy.clone())),
+            None if self.proxy_from_environment => Proxy::from_env(server_address),
+            None => Ok(None),
+        }
+    }
+
     /// Setter method that sets the time the client, after entering "STALLED" status, is allowed
     /// to keep waiting for a keepalive packet or any data on a stream connection, before disconnecting
     /// and trying to reconnect to the Server. The new connection may be either the opening of
@@ -688,11 +859,9 @@ impl ConnectionOptions {
     pub fn set_reconnect_timeout(
         &mut self,
         reconnect_timeout: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         if reconnect_timeout == 0 {
-            return Err(IllegalArgumentException::new(
-                "Reconnect timeout cannot be zero",
-            ));
+            return Err(IllegalArgumentException::new("Reconnect timeout cannot be zero").into());
         }
         self.reconnect_timeout = reconnect_timeout;
         Ok(())
@@ -735,13 +904,14 @@ impl ConnectionOptions {
     pub fn set_requested_max_bandwidth(
         &mut self,
         max_bandwidth: Option<f64>,
-    ) -> Result<(), IllegalArgumentException> {
-        if let Some(bandwidth) = max_bandwidth {
-            if bandwidth <= 0.0 {
-                return Err(IllegalArgumentException::new(
-                    "Maximum bandwidth should be a positive number or 'unlimited'",
-                ));
-            }
+    ) -> Result<(), LightstreamerError> {
+        if let Some(bandwidth) = max_bandwidth
+            && bandwidth <= 0.0
+        {
+            return Err(IllegalArgumentException::new(
+                "Maximum bandwidth should be a positive number or 'unlimited'",
+            )
+            .into());
         }
 
         self.requested_max_bandwidth = max_bandwidth;
@@ -795,9 +965,9 @@ impl ConnectionOptions {
     /// * `IllegalArgumentException`: if a negative or zero value is configured
     ///
     /// See also `setFirstRetryMaxDelay()`
-    pub fn set_retry_delay(&mut self, retry_delay: u64) -> Result<(), IllegalArgumentException> {
+    pub fn set_retry_delay(&mut self, retry_delay: u64) -> Result<(), LightstreamerError> {
         if retry_delay == 0 {
-            return Err(IllegalArgumentException::new("Retry delay cannot be zero"));
+            return Err(IllegalArgumentException::new("Retry delay cannot be zero").into());
         }
 
         self.retry_delay = retry_delay;
@@ -853,7 +1023,7 @@ impl ConnectionOptions {
     pub fn set_reverse_heartbeat_interval(
         &mut self,
         reverse_heartbeat_interval: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         if reverse_heartbeat_interval == 0 {
             self.reverse_heartbeat_interval = reverse_heartbeat_interval;
             return Ok(());
@@ -862,7 +1032,8 @@ impl ConnectionOptions {
         if reverse_heartbeat_interval < self.retry_delay {
             return Err(IllegalArgumentException::new(
                 "Reverse heartbeat interval should be greater than or equal to retry delay",
-            ));
+            )
+            .into());
         }
 
         self.reverse_heartbeat_interval = reverse_heartbeat_interval;
@@ -939,7 +1110,7 @@ impl ConnectionOptions {
     pub fn set_session_recovery_timeout(
         &mut self,
         session_recovery_timeout: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    ) -> Result<(), LightstreamerError> {
         if session_recovery_timeout == 0 {
             self.session_recovery_timeout = session_recovery_timeout;
             return Ok(());
@@ -948,7 +1119,8 @@ impl ConnectionOptions {
         if session_recovery_timeout < self.retry_delay {
             return Err(IllegalArgumentException::new(
                 "Session recovery timeout should be greater than or equal to retry delay",
-            ));
+            )
+            .into());
         }
 
         self.session_recovery_timeout = session_recovery_timeout;
@@ -1007,26 +1179,23 @@ impl ConnectionOptions {
     /// See also `setReconnectTimeout()`
     ///
     /// See also `setKeepaliveInterval()`
-    pub fn set_stalled_timeout(
-        &mut self,
-        stalled_timeout: u64,
-    ) -> Result<(), IllegalArgumentException> {
+    pub fn set_stalled_timeout(&mut self, stalled_timeout: u64) -> Result<(), LightstreamerError> {
         if stalled_timeout == 0 {
-            return Err(IllegalArgumentException::new(
-                "Stalled timeout cannot be zero",
-            ));
+            return Err(IllegalArgumentException::new("Stalled timeout cannot be zero").into());
         }
 
         if stalled_timeout >= self.keepalive_interval {
             return Err(IllegalArgumentException::new(
                 "Stalled timeout should be less than keepalive interval",
-            ));
+            )
+            .into());
         }
 
         if stalled_timeout >= self.reconnect_timeout {
             return Err(IllegalArgumentException::new(
                 "Stalled timeout should be less than reconnect timeout",
-            ));
+            )
+            .into());
         }
 
         self.stalled_timeout = stalled_timeout;
@@ -1115,12 +1284,489 @@ impl ConnectionOptions {
     pub fn set_supported_diffs(&mut self, supported_diffs: Option<String>) {
         self.supported_diffs = supported_diffs;
     }
+
+    /// Inquiry method that gets the time window used to coalesce control requests.
+    ///
+    /// # Returns
+    ///
+    /// The time window (in milliseconds) during which control requests are collected before being
+    /// sent together. 0 means that every control request is sent as soon as it is issued.
+    ///
+    /// See also `setControlBatchingWindow()`
+    pub fn get_control_batching_window(&self) -> u64 {
+        self.control_batching_window
+    }
+
+    /// Setter method that sets the time window used to coalesce control requests (such as
+    /// subscription and unsubscription requests) into a single frame.
+    ///
+    /// When the window is nonzero, the first control request issued starts the window, and all
+    /// the control requests issued until it expires are sent together. This reduces the number
+    /// of frames when many subscriptions are issued at once, at the cost of delaying each request
+    /// by up to the window. Latency-critical applications should keep the window at 0, while a
+    /// few milliseconds (e.g. 10) are usually enough for bulk subscriptions.
+    ///
+    /// 0 (every control request is sent as soon as it is issued).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `control_batching_window`: The time window (in milliseconds) used to coalesce control
+    ///   requests, or 0 to disable batching.
+    pub fn set_control_batching_window(&mut self, control_batching_window: u64) {
+        self.control_batching_window = control_batching_window;
+    }
+
+    /// Inquiry method that gets the reaction of the client when the Server ends a session with
+    /// the given END cause code.
+    ///
+    /// # Parameters
+    ///
+    /// * `code`: The END cause code.
+    ///
+    /// # Returns
+    ///
+    /// The configured reaction, or the default one (see `EndCauseReaction::default_for()`).
+    ///
+    /// See also `setEndCauseReaction()`
+    pub fn get_end_cause_reaction(&self, code: i32) -> EndCauseReaction {
+        self.end_cause_reactions
+            .get(&code)
+            .copied()
+            .unwrap_or_else(|| EndCauseReaction::default_for(code))
+    }
+
+    /// Setter method that sets the reaction of the client when the Server ends a session with
+    /// the given END cause code: open a new session right away, open it after a backoff delay,
+    /// or give up. In every case the cause is notified through `ClientListener.onServerError()`.
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next END
+    /// notification.
+    ///
+    /// # Parameters
+    ///
+    /// * `code`: The END cause code, e.g. `48` (maximum session duration reached).
+    /// * `reaction`: The reaction, or `None` to restore the default one (see
+    ///   `EndCauseReaction::default_for()`).
+    pub fn set_end_cause_reaction(&mut self, code: i32, reaction: Option<EndCauseReaction>) {
+        match reaction {
+            Some(reaction) => self.end_cause_reactions.insert(code, reaction),
+            None => self.end_cause_reactions.remove(&code),
+        };
+    }
+
+    /// Inquiry method that checks if the certificate and host name of the Server are left
+    /// unverified.
+    ///
+    /// Only available with the `dangerous-dev` feature.
+    ///
+    /// # Returns
+    ///
+    /// `true` if invalid certificates are accepted, `false` otherwise.
+    ///
+    /// See also `setDangerousAcceptInvalidCertificates()`
+    #[cfg(feature = "dangerous-dev")]
+    pub fn is_dangerous_accept_invalid_certificates(&self) -> bool {
+        self.dangerous_accept_invalid_certificates
+    }
+
+    /// Setter method that disables the verification of the certificate of the Server, and of
+    /// its host name, on `https` connections, so that local test Servers with self-signed
+    /// certificates can be reached without patching the library.
+    ///
+    /// This removes the protection of TLS against man-in-the-middle attacks: it must never be
+    /// used in production, hence the explicitly named `dangerous-dev` feature required for it.
+    /// Every connection made with the verification disabled is logged as a warning.
+    ///
+    /// false (the certificates are verified).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// connection attempt.
+    ///
+    /// # Parameters
+    ///
+    /// * `accept`: `true` to accept invalid certificates and host names, `false` to verify them.
+    #[cfg(feature = "dangerous-dev")]
+    pub fn set_dangerous_accept_invalid_certificates(&mut self, accept: bool) {
+        self.dangerous_accept_invalid_certificates = accept;
+    }
+
+    /// Inquiry method that gets the name sent through SNI on TLS connections, and checked
+    /// against the certificate of the Server.
+    ///
+    /// # Returns
+    ///
+    /// The server name, or `None` if the host of the server address is used.
+    ///
+    /// See also `setTlsServerName()`
+    pub fn get_tls_server_name(&self) -> Option<&String> {
+        self.tls_server_name.as_ref()
+    }
+
+    /// Setter method that sets the name sent through SNI on `https` connections, and checked
+    /// against the certificate of the Server, independently of the host of the server address.
+    /// This is needed when connecting to the Server by IP address, or through TLS-terminating
+    /// infrastructure that routes the connections by server name. The `Host` header still
+    /// carries the host of the server address.
+    ///
+    /// None (the host of the server address is used).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// connection attempt.
+    ///
+    /// # Parameters
+    ///
+    /// * `server_name`: The server name, or `None` to use the host of the server address.
+    pub fn set_tls_server_name(&mut self, server_name: Option<String>) {
+        self.tls_server_name = server_name;
+    }
+
+    /// Inquiry method that gets the local address outbound connections are bound to.
+    ///
+    /// # Returns
+    ///
+    /// The local address, or `None` if the operating system chooses it.
+    ///
+    /// See also `setLocalAddress()`
+    pub fn get_local_address(&self) -> Option<IpAddr> {
+        self.local_address
+    }
+
+    /// Setter method that binds the connections to the Server to a specific local address, so
+    /// that on multi-homed hosts (e.g. with separate market-data and management network
+    /// interfaces) the traffic of the client goes through the interface owning that address.
+    ///
+    /// Only the addresses of the Server belonging to the same family (IPv4 or IPv6) as the local
+    /// address are tried.
+    ///
+    /// None (the operating system chooses the local address).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// connection attempt.
+    ///
+    /// # Parameters
+    ///
+    /// * `local_address`: The local address to bind to, or `None` to let the operating system
+    ///   choose it.
+    pub fn set_local_address(&mut self, local_address: Option<IpAddr>) {
+        self.local_address = local_address;
+    }
+
+    /// Inquiry method that gets the maximum length of the TLCP messages accepted from the Server.
+    ///
+    /// # Returns
+    ///
+    /// The maximum length (in bytes) of a message, or `None` if messages of any length are
+    /// accepted.
+    ///
+    /// See also `setMaxMessageSize()`
+    pub fn get_max_message_size(&self) -> Option<usize> {
+        self.max_message_size
+    }
+
+    /// Setter method that sets the maximum length of the TLCP messages accepted from the Server,
+    /// such as a single update, to protect the application from unexpectedly large values.
+    ///
+    /// A longer message is discarded and notified through `ClientListener.onOversizedMessage()`,
+    /// while the session goes on. See `setResubscribeOnOversizedMessage()` to resynchronize the
+    /// subscription the message referred to.
+    ///
+    /// None (messages of any length are accepted).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `max_message_size`: The maximum length (in bytes) of a message, or `None` to accept
+    ///   messages of any length.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalArgumentException`: if a zero value is passed.
+    pub fn set_max_message_size(
+        &mut self,
+        max_message_size: Option<usize>,
+    ) -> Result<(), LightstreamerError> {
+        if max_message_size == Some(0) {
+            return Err(
+                IllegalArgumentException::new("Max message size must be greater than 0").into(),
+            );
+        }
+        self.max_message_size = max_message_size;
+        Ok(())
+    }
+
+    /// Inquiry method that gets the maximum number of messages kept waiting for a session.
+    ///
+    /// # Returns
+    ///
+    /// The maximum number of messages in the queue.
+    ///
+    /// See also `setMessageQueueSize()`
+    pub fn get_message_queue_size(&self) -> usize {
+        self.message_queue_size
+    }
+
+    /// Setter method that sets the maximum number of messages sent with
+    /// `enqueue_while_disconnected` that are kept waiting for a session while the client is
+    /// disconnected or retrying. They are sent in order as soon as a session is available.
+    ///
+    /// A message not fitting in the queue is aborted at once, as is any message still queued
+    /// when the client stops trying to connect, through `ClientMessageListener.onAbort()`.
+    ///
+    /// 1000.
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// message queued.
+    ///
+    /// # Parameters
+    ///
+    /// * `message_queue_size`: The maximum number of messages in the queue; with 0, no message
+    ///   is queued.
+    pub fn set_message_queue_size(&mut self, message_queue_size: usize) {
+        self.message_queue_size = message_queue_size;
+    }
+
+    /// Inquiry method that checks whether a subscription is resubscribed when one of its
+    /// messages is discarded for exceeding the maximum length.
+    ///
+    /// # Returns
+    ///
+    /// Whether the subscription is resubscribed.
+    ///
+    /// See also `setResubscribeOnOversizedMessage()`
+    pub fn is_resubscribe_on_oversized_message(&self) -> bool {
+        self.resubscribe_on_oversized_message
+    }
+
+    /// Setter method that sets whether a subscription is resubscribed when one of its messages
+    /// is discarded for exceeding the length set through `setMaxMessageSize()`.
+    ///
+    /// Discarding an update leaves the data of the subscription out of date; resubscribing it
+    /// under a new ID makes the Server send a fresh snapshot, at the cost of an unsubscription
+    /// and subscription events being notified.
+    ///
+    /// false.
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `resubscribe`: `true` to resubscribe the subscription, `false` to keep it as is.
+    pub fn set_resubscribe_on_oversized_message(&mut self, resubscribe: bool) {
+        self.resubscribe_on_oversized_message = resubscribe;
+    }
+
+    /// Inquiry method that gets the timeout of the transport probe performed before connecting.
+    ///
+    /// # Returns
+    ///
+    /// The time (in milliseconds) allowed to the probe to complete a WebSocket handshake, or 0 if
+    /// the probe is disabled.
+    ///
+    /// See also `setTransportProbeTimeout()`
+    pub fn get_transport_probe_timeout(&self) -> u64 {
+        self.transport_probe_timeout
+    }
+
+    /// Setter method that enables a probe, performed before connecting, that tests whether
+    /// WebSockets actually work through the current network path by completing a WSOK handshake
+    /// with the Server.
+    ///
+    /// Some proxies accept the WebSocket upgrade but then break the connection, which makes the
+    /// first connection attempt slow to fail. The outcome of the probe is cached for each Server
+    /// address and shared by all the clients of the process, so that the probe is repeated only
+    /// when the cached outcome expires (see `LightstreamerClient::probe_transport()`). When the
+    /// probe fails, `LightstreamerClient::connect()` fails right away instead of waiting for the
+    /// WebSocket session to break.
+    ///
+    /// 0 (the probe is disabled).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `transport_probe_timeout`: The time (in milliseconds) allowed to the probe, or 0 to
+    ///   disable the probe.
+    pub fn set_transport_probe_timeout(&mut self, transport_probe_timeout: u64) {
+        self.transport_probe_timeout = transport_probe_timeout;
+    }
+
+    /// Inquiry method that gets the time allowed to a connection over WebSocket to confirm the
+    /// session, before falling back to streaming over HTTP.
+    ///
+    /// # Returns
+    ///
+    /// The time (in milliseconds) allowed to the connection, or 0 if the client waits
+    /// indefinitely.
+    ///
+    /// See also `setWsStreamingTimeout()`
+    pub fn get_ws_streaming_timeout(&self) -> u64 {
+        self.ws_streaming_timeout
+    }
+
+    /// Setter method that sets the time allowed to a connection for streaming over WebSocket,
+    /// from the start of the WebSocket handshake to the confirmation of the session, when the
+    /// transport is chosen by the Stream-Sense algorithm (see `setForcedTransport()`). When the
+    /// time expires, or the connection fails earlier, the client falls back to streaming over
+    /// HTTP, on a new connection.
+    ///
+    /// 4000 (4 seconds).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// connection.
+    ///
+    /// # Parameters
+    ///
+    /// * `ws_streaming_timeout`: The time (in milliseconds) allowed to the connection, or 0 to
+    ///   fall back only when the connection fails.
+    pub fn set_ws_streaming_timeout(&mut self, ws_streaming_timeout: u64) {
+        self.ws_streaming_timeout = ws_streaming_timeout;
+    }
+
+    /// Inquiry method that gets the time allowed to a stream connection over HTTP to confirm
+    /// the session, before falling back to polling over HTTP.
+    ///
+    /// # Returns
+    ///
+    /// The time (in milliseconds) allowed to the connection, or 0 if the client waits
+    /// indefinitely.
+    ///
+    /// See also `setHttpStreamingTimeout()`
+    pub fn get_http_streaming_timeout(&self) -> u64 {
+        self.http_streaming_timeout
+    }
+
+    /// Setter method that sets the time allowed to a stream connection over HTTP to deliver the
+    /// confirmation of the session, when the transport is chosen by the Stream-Sense algorithm
+    /// (see `setForcedTransport()`). Some proxies buffer the responses, so that nothing of a
+    /// stream connection ever reaches the client: when the time expires, or the connection fails
+    /// earlier, the client falls back to polling over HTTP, on a new connection.
+    ///
+    /// 4000 (4 seconds).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// connection.
+    ///
+    /// # Parameters
+    ///
+    /// * `http_streaming_timeout`: The time (in milliseconds) allowed to the connection, or 0 to
+    ///   fall back only when the connection fails.
+    pub fn set_http_streaming_timeout(&mut self, http_streaming_timeout: u64) {
+        self.http_streaming_timeout = http_streaming_timeout;
+    }
+
+    /// Inquiry method that gets the time allowed to the Server to confirm or refuse a
+    /// subscription request.
+    ///
+    /// # Returns
+    ///
+    /// The time (in milliseconds) allowed to the Server, or 0 if the client waits indefinitely.
+    ///
+    /// See also `setSubscribeTimeout()`
+    pub fn get_subscribe_timeout(&self) -> u64 {
+        self.subscribe_timeout
+    }
+
+    /// Setter method that sets the time allowed to the Server to answer a subscription request
+    /// with SUBOK or REQERR, so that a subscription whose Data Adapter hangs does not wait
+    /// forever.
+    ///
+    /// When the time expires, the subscription request is retried as configured through
+    /// `setSubscribeRetries()`; once the retries are exhausted, the subscription is unsubscribed
+    /// and fails with a `TimeoutError`, as observed by `Subscription::await_subscribed()`.
+    ///
+    /// 0 (the client waits indefinitely).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscribe_timeout`: The time (in milliseconds) allowed to the Server, or 0 to wait
+    ///   indefinitely.
+    pub fn set_subscribe_timeout(&mut self, subscribe_timeout: u64) {
+        self.subscribe_timeout = subscribe_timeout;
+    }
+
+    /// Inquiry method that gets the number of times a subscription request left without answer
+    /// is retried.
+    ///
+    /// # Returns
+    ///
+    /// The number of retries.
+    ///
+    /// See also `setSubscribeRetries()`
+    pub fn get_subscribe_retries(&self) -> u32 {
+        self.subscribe_retries
+    }
+
+    /// Setter method that sets the number of times a subscription request left without answer
+    /// within the time set through `setSubscribeTimeout()` is retried, by unsubscribing and
+    /// subscribing again under a new ID, before the subscription fails.
+    ///
+    /// 0 (the subscription fails at the first timeout).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `subscribe_retries`: The number of retries.
+    pub fn set_subscribe_retries(&mut self, subscribe_retries: u32) {
+        self.subscribe_retries = subscribe_retries;
+    }
+
+    /// Inquiry method that gets the time within which a subscription is expected to receive its
+    /// first update or end-of-snapshot.
+    ///
+    /// # Returns
+    ///
+    /// The threshold (in milliseconds), or 0 if slow starts are not reported.
+    ///
+    /// See also `setSlowStartThreshold()`
+    pub fn get_slow_start_threshold(&self) -> u64 {
+        self.slow_start_threshold
+    }
+
+    /// Setter method that sets the time within which a subscription is expected to receive its
+    /// first update or end-of-snapshot after its request. A subscription exceeding it is
+    /// reported to `ClientListener.onSlowSubscriptionStart()` and logged as a warning, as it is
+    /// a common sign of a misconfigured Data Adapter.
+    ///
+    /// 0 (slow starts are not reported).
+    ///
+    /// The value can be changed at any time: the supplied value will be used for the next
+    /// session.
+    ///
+    /// # Parameters
+    ///
+    /// * `slow_start_threshold`: The threshold (in milliseconds), or 0 not to report slow
+    ///   starts.
+    pub fn set_slow_start_threshold(&mut self, slow_start_threshold: u64) {
+        self.slow_start_threshold = slow_start_threshold;
+    }
 }
 
 impl Debug for ConnectionOptions {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        f.debug_struct("ConnectionOptions")
+        let mut debug = f.debug_struct("ConnectionOptions");
+        debug
+            .field("compression_enabled", &self.compression_enabled)
             .field("content_length", &self.content_length)
+            .field("control_batching_window", &self.control_batching_window);
+        #[cfg(feature = "dangerous-dev")]
+        debug.field(
+            "dangerous_accept_invalid_certificates",
+            &self.dangerous_accept_invalid_certificates,
+        );
+        debug
+            .field("end_cause_reactions", &self.end_cause_reactions)
             .field("first_retry_max_delay", &self.first_retry_max_delay)
             .field("forced_transport", &self.forced_transport)
             .field("http_extra_headers", &self.http_extra_headers)
@@ -1130,10 +1776,20 @@ impl Debug for ConnectionOptions {
             )
             .field("idle_timeout", &self.idle_timeout)
             .field("keepalive_interval", &self.keepalive_interval)
+            .field("local_address", &self.local_address)
+            .field("max_message_size", &self.max_message_size)
+            .field("message_queue_size", &self.message_queue_size)
             .field("polling_interval", &self.polling_interval)
             .field("proxy", &self.proxy)
+            .field("proxy_from_environment", &self.proxy_from_environment)
+            .field("send_sync", &self.send_sync)
+            .field("reduce_head", &self.reduce_head)
             .field("real_max_bandwidth", &self.real_max_bandwidth)
             .field("reconnect_timeout", &self.reconnect_timeout)
+            .field(
+                "resubscribe_on_oversized_message",
+                &self.resubscribe_on_oversized_message,
+            )
             .field("requested_max_bandwidth", &self.requested_max_bandwidth)
             .field("retry_delay", &self.retry_delay)
             .field(
@@ -1147,6 +1803,13 @@ impl Debug for ConnectionOptions {
             .field("session_recovery_timeout", &self.session_recovery_timeout)
             .field("slowing_enabled", &self.slowing_enabled)
             .field("stalled_timeout", &self.stalled_timeout)
+            .field("subscribe_retries", &self.subscribe_retries)
+            .field("subscribe_timeout", &self.subscribe_timeout)
+            .field("slow_start_threshold", &self.slow_start_threshold)
+            .field("transport_probe_timeout", &self.transport_probe_timeout)
+            .field("tls_server_name", &self.tls_server_name)
+            .field("ws_streaming_timeout", &self.ws_streaming_timeout)
+            .field("http_streaming_timeout", &self.http_streaming_timeout)
             .finish()
     }
 }
@@ -1154,28 +1817,45 @@ impl Debug for ConnectionOptions {
 impl Default for ConnectionOptions {
     fn default() -> Self {
         Self {
+            compression_enabled: false,
             content_length: None,
+            control_batching_window: 0,
+            #[cfg(feature = "dangerous-dev")]
+            dangerous_accept_invalid_certificates: false,
+            end_cause_reactions: HashMap::new(),
             first_retry_max_delay: 0,
             forced_transport: None,
             http_extra_headers: None,
             http_extra_headers_on_session_creation_only: false,
+            http_streaming_timeout: 4000,
             idle_timeout: 19000,
             keepalive_interval: 0,
+            local_address: None,
+            max_message_size: None,
+            message_queue_size: 1000,
             polling_interval: 0,
             proxy: None,
+            proxy_from_environment: true,
             real_max_bandwidth: None,
             reconnect_timeout: 3000,
-            _reduce_head: false,
+            resubscribe_on_oversized_message: false,
+            reduce_head: false,
             requested_max_bandwidth: None,
             retry_delay: 4000,
             reverse_heartbeat_interval: 0,
-            send_sync: false,
+            send_sync: true,
             server_instance_address_ignored: false,
             session_recovery_timeout: 15000,
             slowing_enabled: false,
             stalled_timeout: 2000,
+            subscribe_retries: 0,
+            subscribe_timeout: 0,
+            slow_start_threshold: 0,
             polling: false,
+            transport_probe_timeout: 0,
+            tls_server_name: None,
             ttl_millis: None,
+            ws_streaming_timeout: 4000,
             supported_diffs: None,
         }
     }
@@ -1184,6 +1864,7 @@ impl Default for ConnectionOptions {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::utils::ProxyType;
 
     #[test]
     fn test_new_connection_options() {
@@ -1206,6 +1887,7 @@ mod tests {
         assert!(!options.is_slowing_enabled());
         assert_eq!(options.get_stalled_timeout(), 2000);
         assert!(options.get_send_sync());
+        assert!(!options.is_compression_enabled());
     }
 
     #[test]
@@ -1502,6 +2184,171 @@ mod tests {
         assert!(debug_string.contains("http_extra_headers"));
     }
 
+    #[test]
+    fn test_set_control_batching_window() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_control_batching_window(), 0);
+
+        options.set_control_batching_window(10);
+        assert_eq!(options.get_control_batching_window(), 10);
+
+        options.set_control_batching_window(0);
+        assert_eq!(options.get_control_batching_window(), 0);
+    }
+
+    #[test]
+    fn test_set_end_cause_reaction() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_end_cause_reaction(31), EndCauseReaction::GiveUp);
+        assert_eq!(options.get_end_cause_reaction(41), EndCauseReaction::GiveUp);
+        assert_eq!(
+            options.get_end_cause_reaction(48),
+            EndCauseReaction::Reconnect
+        );
+        assert_eq!(
+            options.get_end_cause_reaction(33),
+            EndCauseReaction::Backoff
+        );
+
+        options.set_end_cause_reaction(31, Some(EndCauseReaction::Backoff));
+        assert_eq!(
+            options.get_end_cause_reaction(31),
+            EndCauseReaction::Backoff
+        );
+        options.set_end_cause_reaction(31, None);
+        assert_eq!(options.get_end_cause_reaction(31), EndCauseReaction::GiveUp);
+    }
+
+    #[cfg(feature = "dangerous-dev")]
+    #[test]
+    fn test_set_dangerous_accept_invalid_certificates() {
+        let mut options = ConnectionOptions::new();
+        assert!(!options.is_dangerous_accept_invalid_certificates());
+
+        options.set_dangerous_accept_invalid_certificates(true);
+        assert!(options.is_dangerous_accept_invalid_certificates());
+        assert!(format!("{:?}", options).contains("dangerous_accept_invalid_certificates: true"));
+    }
+
+    #[test]
+    fn test_set_tls_server_name() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_tls_server_name(), None);
+
+        options.set_tls_server_name(Some("push.example.com".to_string()));
+        assert_eq!(
+            options.get_tls_server_name().map(String::as_str),
+            Some("push.example.com")
+        );
+    }
+
+    #[test]
+    fn test_set_local_address() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_local_address(), None);
+
+        let address: IpAddr = "10.0.0.5".parse().unwrap();
+        options.set_local_address(Some(address));
+        assert_eq!(options.get_local_address(), Some(address));
+
+        options.set_local_address(None);
+        assert_eq!(options.get_local_address(), None);
+    }
+
+    #[test]
+    fn test_set_max_message_size() {
+        let mut options = ConnectionOptions::default();
+        assert_eq!(options.get_max_message_size(), None);
+        assert!(options.set_max_message_size(Some(0)).is_err());
+        options.set_max_message_size(Some(4096)).unwrap();
+        assert_eq!(options.get_max_message_size(), Some(4096));
+        options.set_resubscribe_on_oversized_message(true);
+        assert!(options.is_resubscribe_on_oversized_message());
+    }
+
+    #[test]
+    fn test_set_message_queue_size() {
+        let mut options = ConnectionOptions::default();
+        assert_eq!(options.get_message_queue_size(), 1000);
+        options.set_message_queue_size(0);
+        assert_eq!(options.get_message_queue_size(), 0);
+    }
+
+    #[test]
+    fn test_set_fallback_timeouts() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_ws_streaming_timeout(), 4000);
+        assert_eq!(options.get_http_streaming_timeout(), 4000);
+        options.set_ws_streaming_timeout(1000);
+        options.set_http_streaming_timeout(0);
+        assert_eq!(options.get_ws_streaming_timeout(), 1000);
+        assert_eq!(options.get_http_streaming_timeout(), 0);
+        assert!(format!("{:?}", options).contains("http_streaming_timeout"));
+    }
+
+    #[test]
+    fn test_set_transport_probe_timeout() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_transport_probe_timeout(), 0);
+
+        options.set_transport_probe_timeout(3000);
+        assert_eq!(options.get_transport_probe_timeout(), 3000);
+        assert!(format!("{:?}", options).contains("transport_probe_timeout"));
+    }
+
+    #[test]
+    fn test_set_subscribe_timeout() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_subscribe_timeout(), 0);
+        assert_eq!(options.get_subscribe_retries(), 0);
+
+        options.set_subscribe_timeout(5000);
+        options.set_subscribe_retries(2);
+        assert_eq!(options.get_subscribe_timeout(), 5000);
+        assert_eq!(options.get_subscribe_retries(), 2);
+        assert!(format!("{:?}", options).contains("subscribe_timeout: 5000"));
+    }
+
+    #[test]
+    fn test_resolve_proxy() {
+        let mut options = ConnectionOptions::new();
+        assert!(options.get_proxy().is_none());
+        assert!(options.is_proxy_from_environment());
+
+        options.set_proxy(Some(Proxy::new(
+            ProxyType::Http,
+            "proxy.example.com".to_string(),
+            3128,
+            None,
+            None,
+        )));
+        let proxy = options
+            .resolve_proxy("wss://push.example.com/lightstreamer")
+            .unwrap()
+            .unwrap();
+        assert_eq!(proxy.get_host(), "proxy.example.com");
+
+        options.set_proxy(None);
+        options.set_proxy_from_environment(false);
+        assert!(
+            options
+                .resolve_proxy("wss://push.example.com/lightstreamer")
+                .unwrap()
+                .is_none()
+        );
+        assert!(format!("{:?}", options).contains("proxy_from_environment: false"));
+    }
+
+    #[test]
+    fn test_set_slow_start_threshold() {
+        let mut options = ConnectionOptions::new();
+        assert_eq!(options.get_slow_start_threshold(), 0);
+
+        options.set_slow_start_threshold(2000);
+        assert_eq!(options.get_slow_start_threshold(), 2000);
+        assert!(format!("{:?}", options).contains("slow_start_threshold: 2000"));
+    }
+
     #[test]
     fn test_combined_settings() {
         let mut options = ConnectionOptions::new();
@@ -17,6 +17,7 @@
 //!
 //! - **Connection Management**:
 //!   - Full-duplex WebSocket-based connection mode
+//!   - HTTP streaming and HTTP polling transports, with automatic fallback from WebSockets
 //!   - Automatic reconnection with configurable retry policies
 //!   - Session recovery after temporary disconnections
 //!   - Connection status monitoring and event notifications
@@ -55,8 +56,10 @@
 //!
 //! - Message sending capabilities (MPN)
 //! - Client-side filtering and frequency limitations
-//! - Additional transport mechanisms beyond WebSockets
 //! - Enhanced security features
+//! - TLS session resumption (session tickets, and early data where safe) on reconnections and
+//!   session recoveries, which the `native-tls` backend of the WebSocket transport does not
+//!   expose, so that they do not pay a full handshake on high-latency links
 //!
 //! ## Installation
 //!
@@ -142,7 +145,7 @@
 //!     
 //!     // Add a listener to the subscription (optional)
 //!     let listener = Box::new(MySubscriptionListener);
-//!     subscription.add_listener(listener);
+//!     subscription.add_listener(listener)?;
 //!     
 //!     // Get the subscription sender from the client
 //!     // Note: This method might not exist in the current API, check the documentation
@@ -164,8 +167,8 @@
 //!     // Wait for some time (in a real application, you would wait for a shutdown signal)
 //!     tokio::time::sleep(Duration::from_secs(5)).await;
 //!     
-//!     // Unsubscribe before disconnecting
-//!     LightstreamerClient::unsubscribe(subscription_sender, subscription_id).await;
+//!     // Unsubscribe before disconnecting, waiting for the Server to confirm it
+//!     LightstreamerClient::unsubscribe(subscription_sender, subscription_id).await?;
 //!     
 //!     Ok(())
 //! }
@@ -229,6 +232,29 @@ pub mod utils;
 /// connecting to Lightstreamer servers, managing sessions, and handling client events.
 pub mod client;
 
+/// Module containing the low-level parsing and encoding of TLCP.
+///
+/// This module exposes the parser the client decodes the notifications of the Server with:
+/// `ServerMessage::parse()` turns a single notification into a typed enum, borrowing from the
+/// line where possible, and `ServerMessage` encodes it back into a TLCP line. `parse_frame()`
+/// parses whole frames and `encode_request()` encodes the requests of the client, for tools
+/// such as proxies, test servers and fuzzers; the `tlcp` submodule groups them with the
+/// `ServerMessage` and `ClientRequest` enums.
+///
+/// ```
+/// use lightstreamer_rs::protocol::{ClientRequest, FieldValue, ServerMessage, encode_request, parse_frame};
+///
+/// let messages = parse_frame("PROBE\r\nU,1,1,10.5|#\r\n").unwrap();
+/// assert!(matches!(messages[0], ServerMessage::Probe));
+/// if let ServerMessage::Update(update) = &messages[1] {
+///     assert_eq!(update.values[1], FieldValue::Null);
+/// }
+///
+/// let request = ClientRequest::Heartbeat(vec![]);
+/// assert_eq!(encode_request(&request), "heartbeat\r\n");
+/// ```
+pub mod protocol;
+
 /// Module containing connection-related functionality.
 ///
 /// This module provides types for managing connection details and options.
@@ -0,0 +1,607 @@
+use crate::protocol::notifications::{
+    FieldValue, FrequencyConfiguration, ItemNotification, Overflow, RequestError, SubscriptionOk,
+    Update, decode_value, encode_value, notification_name, parse_conf, parse_cs, parse_eos,
+    parse_number, parse_ov, parse_prog, parse_reqerr, parse_subok, parse_sync, parse_unsub,
+    parse_update, parse_update_in, split_notification,
+};
+use crate::utils::ProtocolError;
+use bumpalo::Bump;
+use std::borrow::Cow;
+use std::fmt;
+
+/// The names of the notifications known to `ServerMessage::parse()`, except `U`.
+const NOTIFICATION_NAMES: [&str; 24] = [
+    "WSOK", "CONOK", "CONERR", "END", "ERROR", "LOOP", "PROBE", "NOOP", "SYNC", "SERVNAME",
+    "CLIENTIP", "CONS", "PROG", "REQOK", "REQERR", "SUBOK", "SUBCMD", "UNSUB", "EOS", "CS", "OV",
+    "CONF", "MSGDONE", "MSGFAIL",
+];
+
+/// The bandwidth granted to a session, as notified by `CONS`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Bandwidth {
+    /// The bandwidth, in kilobits per second.
+    Limited(f64),
+    /// No limit is applied to the bandwidth.
+    Unlimited,
+    /// The bandwidth is not managed by the Server.
+    Unmanaged,
+}
+
+/// A notification sent by the Server on a TLCP session, parsed from a single line.
+///
+/// The parser accepts the notification names in any case, as the Server does; the encoder,
+/// through `Display`, writes them in upper case, so that the line can be fed back to a client,
+/// for instance by a test server or a proxy.
+///
+/// ```
+/// use lightstreamer_rs::protocol::ServerMessage;
+///
+/// let message = ServerMessage::parse("CONOK,S1,50000,5000,*").unwrap();
+/// assert!(matches!(message, ServerMessage::Conok { keepalive: 5000, .. }));
+/// assert_eq!(message.to_string(), "CONOK,S1,50000,5000,*");
+/// ```
+// Updates, by far the most frequent messages, keep their values inline rather than boxed.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ServerMessage<'a> {
+    /// `WSOK`: the WebSocket connection is open.
+    Wsok,
+    /// `CONOK,<session id>,<request limit>,<keepalive>,<control link>`: the session is open.
+    Conok {
+        /// The ID of the session.
+        session_id: Cow<'a, str>,
+        /// The maximum length, in bytes, of the requests accepted by the Server.
+        request_limit: u64,
+        /// The interval, in milliseconds, between keepalives on idle streams.
+        keepalive: u64,
+        /// The address for the control requests, or `None` for the same address (`*`).
+        control_link: Option<Cow<'a, str>>,
+    },
+    /// `CONERR,<code>,<message>`: the session request was refused.
+    Conerr {
+        /// The error code.
+        code: i32,
+        /// The error message, percent-decoded.
+        message: Cow<'a, str>,
+    },
+    /// `END,<code>,<message>`: the session was closed by the Server.
+    End {
+        /// The cause code.
+        code: i32,
+        /// The cause message, percent-decoded.
+        message: Cow<'a, str>,
+    },
+    /// `ERROR,<code>,<message>`: a request on the stream connection was malformed.
+    Error {
+        /// The error code.
+        code: i32,
+        /// The error message, percent-decoded.
+        message: Cow<'a, str>,
+    },
+    /// `LOOP,<delay>`: the stream connection is closed, to be rebound after the given delay,
+    /// in milliseconds.
+    Loop(u64),
+    /// `PROBE`: a keepalive on an idle stream.
+    Probe,
+    /// `NOOP,<anything>`: a notification to be ignored.
+    Noop,
+    /// `SYNC,<seconds>`: the seconds elapsed on the Server since the stream was opened.
+    Sync(u64),
+    /// `SERVNAME,<name>`: the name of the Server.
+    Servname(Cow<'a, str>),
+    /// `CLIENTIP,<address>`: the IP address of the client, as seen by the Server.
+    Clientip(Cow<'a, str>),
+    /// `CONS,<bandwidth>`: the bandwidth granted to the session.
+    Cons(Bandwidth),
+    /// `PROG,<progressive>`: the count of the data notifications sent in the session.
+    Prog(u64),
+    /// `REQOK,<request id>`: a control request was accepted. The ID is missing when the request
+    /// carried none.
+    Reqok(Option<usize>),
+    /// `REQERR,<request id>,<code>,<message>`: a control request was refused.
+    Reqerr(RequestError<'a>),
+    /// `SUBOK` or `SUBCMD`: a subscription was confirmed.
+    Subok(SubscriptionOk),
+    /// `UNSUB,<subscription id>`: a subscription was removed.
+    Unsub(usize),
+    /// `U,<subscription id>,<item pos>,<values>`: an update.
+    Update(Update<'a>),
+    /// `EOS,<subscription id>,<item pos>`: the snapshot of an item is complete.
+    Eos(ItemNotification),
+    /// `CS,<subscription id>,<item pos>`: the snapshot of an item is to be cleared.
+    Cs(ItemNotification),
+    /// `OV,<subscription id>,<item pos>,<lost updates>`: updates were dropped.
+    Ov(Overflow),
+    /// `CONF,<subscription id>,<max frequency>,<filtering>`: the frequency granted to a
+    /// subscription.
+    Conf(FrequencyConfiguration),
+    /// `MSGDONE,<sequence>,<prog>,<response>`: a message was processed.
+    Msgdone {
+        /// The sequence of the message, `*` for unordered messages.
+        sequence: Cow<'a, str>,
+        /// The progressive of the message within its sequence.
+        prog: u64,
+        /// The response of the Metadata Adapter, percent-decoded.
+        response: Cow<'a, str>,
+    },
+    /// `MSGFAIL,<sequence>,<prog>,<code>,<message>`: a message could not be processed.
+    Msgfail {
+        /// The sequence of the message, `*` for unordered messages.
+        sequence: Cow<'a, str>,
+        /// The progressive of the message within its sequence.
+        prog: u64,
+        /// The error code.
+        code: i32,
+        /// The error message, percent-decoded.
+        message: Cow<'a, str>,
+    },
+}
+
+impl<'a> ServerMessage<'a> {
+    /// Parses a notification. Values are borrowed from the line unless they need decoding.
+    ///
+    /// # Parameters
+    ///
+    /// * `line`: The notification, with or without the terminating CRLF.
+    ///
+    /// # Raises
+    ///
+    /// * `ProtocolError`: if the notification is unknown or malformed.
+    pub fn parse(line: &'a str) -> Result<ServerMessage<'a>, ProtocolError> {
+        let line = line.trim();
+        let name = notification_name(line);
+        // Updates, by far the most frequent notifications, skip the lookup of the name.
+        if name.eq_ignore_ascii_case("U") {
+            return Ok(ServerMessage::Update(parse_update(line)?));
+        }
+        // The name is matched in its canonical uppercase form, without copying the line.
+        let name = NOTIFICATION_NAMES
+            .iter()
+            .copied()
+            .find(|known| known.eq_ignore_ascii_case(name))
+            .ok_or_else(|| ProtocolError::new(line, "unknown notification"))?;
+        let message = match name {
+            "WSOK" => ServerMessage::Wsok,
+            "CONOK" => {
+                let arguments = split_notification(line, &["CONOK"], 5)?;
+                let session_id = arguments
+                    .get(1)
+                    .copied()
+                    .filter(|session_id| !session_id.is_empty())
+                    .ok_or_else(|| ProtocolError::new(line, "missing session ID"))?;
+                ServerMessage::Conok {
+                    session_id: Cow::Borrowed(session_id),
+                    request_limit: parse_number(line, &arguments, 2, "request limit")?,
+                    keepalive: parse_number(line, &arguments, 3, "keepalive")?,
+                    control_link: arguments
+                        .get(4)
+                        .copied()
+                        .filter(|link| !link.is_empty() && *link != "*")
+                        .map(Cow::Borrowed),
+                }
+            }
+            "CONERR" | "END" | "ERROR" => {
+                let arguments = split_notification(line, &[name], 3)?;
+                let code = parse_number(line, &arguments, 1, "code")?;
+                let message = decode_value(arguments.get(2).copied().unwrap_or(""));
+                match name {
+                    "CONERR" => ServerMessage::Conerr { code, message },
+                    "END" => ServerMessage::End { code, message },
+                    _ => ServerMessage::Error { code, message },
+                }
+            }
+            "LOOP" => {
+                let arguments = split_notification(line, &["LOOP"], 2)?;
+                ServerMessage::Loop(parse_number(line, &arguments, 1, "delay")?)
+            }
+            "PROBE" => ServerMessage::Probe,
+            "NOOP" => ServerMessage::Noop,
+            "SYNC" => ServerMessage::Sync(parse_sync(line)?),
+            "SERVNAME" | "CLIENTIP" => {
+                let arguments = split_notification(line, &[name], 2)?;
+                let value = decode_value(arguments.get(1).copied().unwrap_or(""));
+                match name {
+                    "SERVNAME" => ServerMessage::Servname(value),
+                    _ => ServerMessage::Clientip(value),
+                }
+            }
+            "CONS" => {
+                let arguments = split_notification(line, &["CONS"], 2)?;
+                ServerMessage::Cons(match arguments.get(1).copied() {
+                    Some(bandwidth) if bandwidth.eq_ignore_ascii_case("unlimited") => {
+                        Bandwidth::Unlimited
+                    }
+                    Some(bandwidth) if bandwidth.eq_ignore_ascii_case("unmanaged") => {
+                        Bandwidth::Unmanaged
+                    }
+                    _ => Bandwidth::Limited(parse_number(line, &arguments, 1, "bandwidth")?),
+                })
+            }
+            "PROG" => ServerMessage::Prog(parse_prog(line)?),
+            "REQOK" => {
+                let arguments = split_notification(line, &["REQOK"], 2)?;
+                ServerMessage::Reqok(match arguments.len() {
+                    1 => None,
+                    _ => Some(parse_number(line, &arguments, 1, "request ID")?),
+                })
+            }
+            "REQERR" => ServerMessage::Reqerr(parse_reqerr(line)?),
+            "SUBOK" | "SUBCMD" => ServerMessage::Subok(parse_subok(line)?),
+            "UNSUB" => ServerMessage::Unsub(parse_unsub(line)?),
+            "EOS" => ServerMessage::Eos(parse_eos(line)?),
+            "CS" => ServerMessage::Cs(parse_cs(line)?),
+            "OV" => ServerMessage::Ov(parse_ov(line)?),
+            "CONF" => ServerMessage::Conf(parse_conf(line)?),
+            "MSGDONE" => {
+                let arguments = split_notification(line, &["MSGDONE"], 4)?;
+                ServerMessage::Msgdone {
+                    sequence: Cow::Borrowed(arguments.get(1).copied().unwrap_or("")),
+                    prog: parse_number(line, &arguments, 2, "progressive")?,
+                    response: decode_value(arguments.get(3).copied().unwrap_or("")),
+                }
+            }
+            "MSGFAIL" => {
+                let arguments = split_notification(line, &["MSGFAIL"], 5)?;
+                ServerMessage::Msgfail {
+                    sequence: Cow::Borrowed(arguments.get(1).copied().unwrap_or("")),
+                    prog: parse_number(line, &arguments, 2, "progressive")?,
+                    code: parse_number(line, &arguments, 3, "error code")?,
+                    message: decode_value(arguments.get(4).copied().unwrap_or("")),
+                }
+            }
+            _ => return Err(ProtocolError::new(line, "unknown notification")),
+        };
+        Ok(message)
+    }
+
+    /// Same as `parse()`, but allocates the transient storage of updates from the given arena,
+    /// which the caller resets once the notifications of a read are dispatched.
+    pub(crate) fn parse_in(
+        line: &'a str,
+        arena: &'a Bump,
+    ) -> Result<ServerMessage<'a>, ProtocolError> {
+        let line = line.trim();
+        if notification_name(line).eq_ignore_ascii_case("U") {
+            return Ok(ServerMessage::Update(parse_update_in(line, arena)?));
+        }
+        Self::parse(line)
+    }
+
+    /// Returns whether the notification counts as a data notification, that is it is included
+    /// in the progressive notified by `PROG` and used for session recovery.
+    pub fn is_data_notification(&self) -> bool {
+        matches!(
+            self,
+            ServerMessage::Subok(_)
+                | ServerMessage::Unsub(_)
+                | ServerMessage::Update(_)
+                | ServerMessage::Eos(_)
+                | ServerMessage::Cs(_)
+                | ServerMessage::Ov(_)
+                | ServerMessage::Conf(_)
+                | ServerMessage::Msgdone { .. }
+                | ServerMessage::Msgfail { .. }
+        )
+    }
+
+    /// Returns the ID of the subscription the notification is about, if any.
+    pub fn subscription_id(&self) -> Option<usize> {
+        match self {
+            ServerMessage::Subok(SubscriptionOk {
+                subscription_id, ..
+            })
+            | ServerMessage::Unsub(subscription_id)
+            | ServerMessage::Update(Update {
+                subscription_id, ..
+            })
+            | ServerMessage::Eos(ItemNotification {
+                subscription_id, ..
+            })
+            | ServerMessage::Cs(ItemNotification {
+                subscription_id, ..
+            })
+            | ServerMessage::Ov(Overflow {
+                subscription_id, ..
+            })
+            | ServerMessage::Conf(FrequencyConfiguration {
+                subscription_id, ..
+            }) => Some(*subscription_id),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Bandwidth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Bandwidth::Limited(bandwidth) => write!(f, "{}", bandwidth),
+            Bandwidth::Unlimited => write!(f, "unlimited"),
+            Bandwidth::Unmanaged => write!(f, "unmanaged"),
+        }
+    }
+}
+
+impl fmt::Display for FieldValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Unchanged => Ok(()),
+            FieldValue::Null => write!(f, "#"),
+            FieldValue::Empty => write!(f, "$"),
+            FieldValue::Value(value) => write!(f, "{}", encode_value(value)),
+            FieldValue::JsonPatch(patch) => write!(f, "^P{}", encode_value(patch)),
+            FieldValue::TlcpDiff(diff) => write!(f, "^T{}", encode_value(diff)),
+        }
+    }
+}
+
+impl fmt::Display for ServerMessage<'_> {
+    /// Encodes the notification as a TLCP line, without the terminating CRLF.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerMessage::Wsok => write!(f, "WSOK"),
+            ServerMessage::Conok {
+                session_id,
+                request_limit,
+                keepalive,
+                control_link,
+            } => write!(
+                f,
+                "CONOK,{},{},{},{}",
+                session_id,
+                request_limit,
+                keepalive,
+                control_link.as_deref().unwrap_or("*")
+            ),
+            ServerMessage::Conerr { code, message } => {
+                write!(f, "CONERR,{},{}", code, encode_value(message))
+            }
+            ServerMessage::End { code, message } => {
+                write!(f, "END,{},{}", code, encode_value(message))
+            }
+            ServerMessage::Error { code, message } => {
+                write!(f, "ERROR,{},{}", code, encode_value(message))
+            }
+            ServerMessage::Loop(delay) => write!(f, "LOOP,{}", delay),
+            ServerMessage::Probe => write!(f, "PROBE"),
+            ServerMessage::Noop => write!(f, "NOOP"),
+            ServerMessage::Sync(seconds) => write!(f, "SYNC,{}", seconds),
+            ServerMessage::Servname(name) => write!(f, "SERVNAME,{}", encode_value(name)),
+            ServerMessage::Clientip(address) => write!(f, "CLIENTIP,{}", encode_value(address)),
+            ServerMessage::Cons(bandwidth) => write!(f, "CONS,{}", bandwidth),
+            ServerMessage::Prog(prog) => write!(f, "PROG,{}", prog),
+            ServerMessage::Reqok(Some(request_id)) => write!(f, "REQOK,{}", request_id),
+            ServerMessage::Reqok(None) => write!(f, "REQOK"),
+            ServerMessage::Reqerr(error) => write!(
+                f,
+                "REQERR,{},{},{}",
+                error.request_id,
+                error.code,
+                encode_value(&error.message)
+            ),
+            ServerMessage::Subok(confirmation) => {
+                match (confirmation.key_pos, confirmation.command_pos) {
+                    (Some(key_pos), Some(command_pos)) => write!(
+                        f,
+                        "SUBCMD,{},{},{},{},{}",
+                        confirmation.subscription_id,
+                        confirmation.items,
+                        confirmation.fields,
+                        key_pos,
+                        command_pos
+                    ),
+                    _ => write!(
+                        f,
+                        "SUBOK,{},{},{}",
+                        confirmation.subscription_id, confirmation.items, confirmation.fields
+                    ),
+                }
+            }
+            ServerMessage::Unsub(subscription_id) => write!(f, "UNSUB,{}", subscription_id),
+            ServerMessage::Update(update) => {
+                write!(f, "U,{},{},", update.subscription_id, update.item_pos)?;
+                for (i, value) in update.values.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "|")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                Ok(())
+            }
+            ServerMessage::Eos(notification) => write!(
+                f,
+                "EOS,{},{}",
+                notification.subscription_id, notification.item_pos
+            ),
+            ServerMessage::Cs(notification) => write!(
+                f,
+                "CS,{},{}",
+                notification.subscription_id, notification.item_pos
+            ),
+            ServerMessage::Ov(overflow) => write!(
+                f,
+                "OV,{},{},{}",
+                overflow.subscription_id, overflow.item_pos, overflow.lost_updates
+            ),
+            ServerMessage::Conf(configuration) => {
+                write!(f, "CONF,{},", configuration.subscription_id)?;
+                match configuration.max_frequency {
+                    Some(frequency) => write!(f, "{}", frequency)?,
+                    None => write!(f, "unlimited")?,
+                }
+                match configuration.filtered {
+                    true => write!(f, ",filtered"),
+                    false => write!(f, ",unfiltered"),
+                }
+            }
+            ServerMessage::Msgdone {
+                sequence,
+                prog,
+                response,
+            } => write!(
+                f,
+                "MSGDONE,{},{},{}",
+                sequence,
+                prog,
+                encode_value(response)
+            ),
+            ServerMessage::Msgfail {
+                sequence,
+                prog,
+                code,
+                message,
+            } => write!(
+                f,
+                "MSGFAIL,{},{},{},{}",
+                sequence,
+                prog,
+                code,
+                encode_value(message)
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_session_messages() {
+        assert_eq!(
+            ServerMessage::parse("conok,S1,50000,5000,push.example.com\r\n").unwrap(),
+            ServerMessage::Conok {
+                session_id: Cow::Borrowed("S1"),
+                request_limit: 50000,
+                keepalive: 5000,
+                control_link: Some(Cow::Borrowed("push.example.com")),
+            }
+        );
+        assert_eq!(
+            ServerMessage::parse("END,31,Closed%20by admin").unwrap(),
+            ServerMessage::End {
+                code: 31,
+                message: Cow::Owned("Closed by admin".to_string()),
+            }
+        );
+        assert_eq!(
+            ServerMessage::parse("CONS,unlimited").unwrap(),
+            ServerMessage::Cons(Bandwidth::Unlimited)
+        );
+        assert_eq!(
+            ServerMessage::parse("REQOK").unwrap(),
+            ServerMessage::Reqok(None)
+        );
+        assert_eq!(
+            ServerMessage::parse("LOOP,0").unwrap(),
+            ServerMessage::Loop(0)
+        );
+        assert_eq!(
+            ServerMessage::parse("WAT,1").unwrap_err().to_string(),
+            "Malformed notification 'WAT,1': unknown notification"
+        );
+        assert!(ServerMessage::parse("CONOK,,50000,5000,*").is_err());
+        // Names are case insensitive.
+        assert!(matches!(
+            ServerMessage::parse("u,1,1,a").unwrap(),
+            ServerMessage::Update(_)
+        ));
+        assert_eq!(
+            ServerMessage::parse("eos,1,2").unwrap(),
+            ServerMessage::Eos(ItemNotification {
+                subscription_id: 1,
+                item_pos: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_data_notifications() {
+        assert!(
+            ServerMessage::parse("U,1,1,a")
+                .unwrap()
+                .is_data_notification()
+        );
+        assert!(
+            ServerMessage::parse("MSGDONE,*,1,")
+                .unwrap()
+                .is_data_notification()
+        );
+        assert!(
+            !ServerMessage::parse("PROG,3")
+                .unwrap()
+                .is_data_notification()
+        );
+    }
+
+    #[test]
+    fn test_subscription_id() {
+        for line in [
+            "SUBCMD,4,1,4,1,2",
+            "U,4,1,a",
+            "EOS,4,1",
+            "OV,4,1,3",
+            "CONF,4,1,filtered",
+        ] {
+            assert_eq!(
+                ServerMessage::parse(line).unwrap().subscription_id(),
+                Some(4),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(
+            ServerMessage::parse("REQERR,4,19,x")
+                .unwrap()
+                .subscription_id(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_encode_round_trip() {
+        for line in [
+            "WSOK",
+            "CONOK,S1,50000,5000,*",
+            "CONERR,60,Client%20version not supported",
+            "ERROR,65,Malformed",
+            "PROBE",
+            "SYNC,30",
+            "SERVNAME,Lightstreamer HTTP Server",
+            "CLIENTIP,10.0.0.5",
+            "CONS,12.5",
+            "PROG,42",
+            "REQOK,7",
+            "REQERR,5,19,Data adapter not found",
+            "SUBOK,1,2,3",
+            "SUBCMD,2,1,4,1,2",
+            "UNSUB,1",
+            "U,1,2,10.5||#|$|^P[]|a%2Cb",
+            "EOS,1,2",
+            "CS,1,2",
+            "OV,1,2,30",
+            "CONF,1,unlimited,filtered",
+            "CONF,1,2.5,unfiltered",
+            "MSGDONE,*,3,ok",
+            "MSGFAIL,orders,4,38,Rejected",
+        ] {
+            assert_eq!(
+                ServerMessage::parse_in(line, &Bump::new()).unwrap(),
+                ServerMessage::parse(line).unwrap(),
+                "{}",
+                line
+            );
+            let encoded = ServerMessage::parse(line).unwrap().to_string();
+            assert_eq!(
+                ServerMessage::parse(&encoded).unwrap(),
+                ServerMessage::parse(line).unwrap(),
+                "{}",
+                line
+            );
+        }
+        assert_eq!(
+            ServerMessage::parse("U,1,2,10.5|^2|#").unwrap().to_string(),
+            "U,1,2,10.5|||#"
+        );
+    }
+}
@@ -0,0 +1,11 @@
+mod message;
+mod notifications;
+pub mod tlcp;
+
+pub use message::{Bandwidth, ServerMessage};
+pub(crate) use notifications::notification_name;
+pub use notifications::{
+    FieldValue, FrequencyConfiguration, ItemNotification, Overflow, RequestError, SubscriptionOk,
+    Update, decode_value, encode_value, split_frame,
+};
+pub use tlcp::{ClientRequest, RequestParams, encode_request, parse_frame};
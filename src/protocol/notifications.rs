@@ -0,0 +1,545 @@
+use crate::subscription::FieldList;
+use crate::utils::ProtocolError;
+use bumpalo::Bump;
+use bumpalo::collections::Vec as BumpVec;
+use std::borrow::Cow;
+
+/// The value of a field, as carried by an update notification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue<'a> {
+    /// The field is unchanged since the previous update of the same item.
+    Unchanged,
+    /// The field is null (`#`).
+    Null,
+    /// The field is an empty string (`$`).
+    Empty,
+    /// The new value of the field, percent-decoded.
+    Value(Cow<'a, str>),
+    /// A JSON Patch to apply to the previous value of the field (`^P`), percent-decoded.
+    JsonPatch(Cow<'a, str>),
+    /// A TLCP-diff to apply to the previous value of the field (`^T`), percent-decoded.
+    TlcpDiff(Cow<'a, str>),
+}
+
+/// An update notification: `U,<subscription id>,<item pos>,<values>`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update<'a> {
+    /// The ID of the subscription, as given in its subscription request.
+    pub subscription_id: usize,
+    /// The 1-based position of the item within the subscription.
+    pub item_pos: usize,
+    /// The values of the fields, one per field of the subscription, in order. Runs of unchanged
+    /// fields (`^<count>`) are expanded. Updates with up to 16 fields are kept inline.
+    pub values: FieldList<FieldValue<'a>>,
+}
+
+/// A subscription confirmation: `SUBOK,<subscription id>,<items>,<fields>`, or
+/// `SUBCMD,<subscription id>,<items>,<fields>,<key pos>,<command pos>` for COMMAND mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubscriptionOk {
+    /// The ID of the subscription.
+    pub subscription_id: usize,
+    /// The number of items of the subscription.
+    pub items: usize,
+    /// The number of fields of the subscription.
+    pub fields: usize,
+    /// The 1-based position of the "key" field, for COMMAND mode.
+    pub key_pos: Option<usize>,
+    /// The 1-based position of the "command" field, for COMMAND mode.
+    pub command_pos: Option<usize>,
+}
+
+/// A notification about an item of a subscription, such as `EOS,<subscription id>,<item pos>`
+/// and `CS,<subscription id>,<item pos>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ItemNotification {
+    /// The ID of the subscription.
+    pub subscription_id: usize,
+    /// The 1-based position of the item within the subscription.
+    pub item_pos: usize,
+}
+
+/// An overflow notification: `OV,<subscription id>,<item pos>,<lost updates>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overflow {
+    /// The ID of the subscription.
+    pub subscription_id: usize,
+    /// The 1-based position of the item within the subscription.
+    pub item_pos: usize,
+    /// The number of updates the Server had to drop.
+    pub lost_updates: u64,
+}
+
+/// A frequency configuration notification:
+/// `CONF,<subscription id>,<max frequency>,(filtered|unfiltered)`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyConfiguration {
+    /// The ID of the subscription.
+    pub subscription_id: usize,
+    /// The maximum update frequency granted, in updates per second, or `None` if unlimited.
+    pub max_frequency: Option<f64>,
+    /// Whether the updates are filtered.
+    pub filtered: bool,
+}
+
+/// A request error: `REQERR,<request id>,<code>,<message>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestError<'a> {
+    /// The ID of the refused request.
+    pub request_id: usize,
+    /// The error code.
+    pub code: i32,
+    /// The error message, percent-decoded.
+    pub message: Cow<'a, str>,
+}
+
+/// Splits a frame received from the Server into its notifications, skipping empty lines.
+///
+/// # Parameters
+///
+/// * `frame`: The frame, made of notifications terminated by CRLF.
+pub fn split_frame(frame: &str) -> impl Iterator<Item = &str> {
+    frame
+        .split("\r\n")
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+}
+
+/// Returns the name of a notification, such as `U` or `SUBOK`, as received.
+pub(crate) fn notification_name(line: &str) -> &str {
+    line.trim().split(',').next().unwrap_or("")
+}
+
+/// Parses an update notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `U` notification.
+pub(super) fn parse_update(line: &str) -> Result<Update<'_>, ProtocolError> {
+    let arguments = split_notification(line, &["U"], 4)?;
+    build_update(line, &arguments, decode_value)
+}
+
+/// Same as `parse_update()`, but allocates the arguments and the decoded values from the given
+/// arena, so that the updates of a read can be released all together.
+pub(super) fn parse_update_in<'a>(
+    line: &'a str,
+    arena: &'a Bump,
+) -> Result<Update<'a>, ProtocolError> {
+    let arguments = split_notification_in(line, &["U"], 4, arena)?;
+    build_update(line, &arguments, |value| decode_value_in(value, arena))
+}
+
+/// Builds an update from the arguments of its notification, decoding the values with `decode`.
+fn build_update<'a>(
+    line: &str,
+    arguments: &[&'a str],
+    decode: impl Fn(&'a str) -> Cow<'a, str>,
+) -> Result<Update<'a>, ProtocolError> {
+    let values = arguments.get(3).copied().unwrap_or("");
+    let mut parsed = FieldList::new();
+    for value in values.split('|') {
+        match value {
+            "" => parsed.push(FieldValue::Unchanged),
+            "#" => parsed.push(FieldValue::Null),
+            "$" => parsed.push(FieldValue::Empty),
+            _ if value.starts_with("^P") => parsed.push(FieldValue::JsonPatch(decode(&value[2..]))),
+            _ if value.starts_with("^T") => parsed.push(FieldValue::TlcpDiff(decode(&value[2..]))),
+            _ if value.starts_with('^') => {
+                let count = value[1..].parse::<usize>().map_err(|_| {
+                    ProtocolError::new(line.trim(), "invalid count of unchanged fields")
+                })?;
+                parsed.extend(std::iter::repeat_n(FieldValue::Unchanged, count));
+            }
+            _ => parsed.push(FieldValue::Value(decode(value))),
+        }
+    }
+    Ok(Update {
+        subscription_id: parse_number(line, arguments, 1, "subscription ID")?,
+        item_pos: parse_number(line, arguments, 2, "item position")?,
+        values: parsed,
+    })
+}
+
+/// Parses a `SUBOK` or `SUBCMD` notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `SUBOK` or `SUBCMD` notification.
+pub(super) fn parse_subok(line: &str) -> Result<SubscriptionOk, ProtocolError> {
+    let arguments = split_notification(line, &["SUBOK", "SUBCMD"], 6)?;
+    let is_command = arguments[0].eq_ignore_ascii_case("SUBCMD");
+    Ok(SubscriptionOk {
+        subscription_id: parse_number(line, &arguments, 1, "subscription ID")?,
+        items: parse_number(line, &arguments, 2, "number of items")?,
+        fields: parse_number(line, &arguments, 3, "number of fields")?,
+        key_pos: match is_command {
+            true => Some(parse_number(line, &arguments, 4, "key position")?),
+            false => None,
+        },
+        command_pos: match is_command {
+            true => Some(parse_number(line, &arguments, 5, "command position")?),
+            false => None,
+        },
+    })
+}
+
+/// Parses an `EOS` (end of snapshot) notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `EOS` notification.
+pub(super) fn parse_eos(line: &str) -> Result<ItemNotification, ProtocolError> {
+    parse_item_notification(line, "EOS")
+}
+
+/// Parses a `CS` (clear snapshot) notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `CS` notification.
+pub(super) fn parse_cs(line: &str) -> Result<ItemNotification, ProtocolError> {
+    parse_item_notification(line, "CS")
+}
+
+/// Parses an `OV` (overflow) notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `OV` notification.
+pub(super) fn parse_ov(line: &str) -> Result<Overflow, ProtocolError> {
+    let arguments = split_notification(line, &["OV"], 4)?;
+    Ok(Overflow {
+        subscription_id: parse_number(line, &arguments, 1, "subscription ID")?,
+        item_pos: parse_number(line, &arguments, 2, "item position")?,
+        lost_updates: parse_number(line, &arguments, 3, "number of lost updates")?,
+    })
+}
+
+/// Parses a `CONF` notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `CONF` notification.
+pub(super) fn parse_conf(line: &str) -> Result<FrequencyConfiguration, ProtocolError> {
+    let arguments = split_notification(line, &["CONF"], 4)?;
+    let max_frequency = match arguments.get(2).copied() {
+        Some(frequency) if frequency.eq_ignore_ascii_case("unlimited") => None,
+        _ => Some(parse_number(line, &arguments, 2, "maximum frequency")?),
+    };
+    let filtered = match arguments.get(3).copied() {
+        Some(filtering) if filtering.eq_ignore_ascii_case("filtered") => true,
+        Some(filtering) if filtering.eq_ignore_ascii_case("unfiltered") => false,
+        _ => return Err(ProtocolError::new(line, "invalid filtering")),
+    };
+    Ok(FrequencyConfiguration {
+        subscription_id: parse_number(line, &arguments, 1, "subscription ID")?,
+        max_frequency,
+        filtered,
+    })
+}
+
+/// Parses an `UNSUB` notification, returning the ID of the subscription.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `UNSUB` notification.
+pub(super) fn parse_unsub(line: &str) -> Result<usize, ProtocolError> {
+    let arguments = split_notification(line, &["UNSUB"], 2)?;
+    parse_number(line, &arguments, 1, "subscription ID")
+}
+
+/// Parses a `PROG` notification, returning the progressive of the data notifications.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `PROG` notification.
+pub(super) fn parse_prog(line: &str) -> Result<u64, ProtocolError> {
+    let arguments = split_notification(line, &["PROG"], 2)?;
+    parse_number(line, &arguments, 1, "progressive")
+}
+
+/// Parses a `SYNC` notification, returning the seconds elapsed on the Server since the stream
+/// connection was opened.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `SYNC` notification.
+pub(super) fn parse_sync(line: &str) -> Result<u64, ProtocolError> {
+    let arguments = split_notification(line, &["SYNC"], 2)?;
+    parse_number(line, &arguments, 1, "elapsed seconds")
+}
+
+/// Parses a `REQERR` notification.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if the line is not a well-formed `REQERR` notification.
+pub(super) fn parse_reqerr(line: &str) -> Result<RequestError<'_>, ProtocolError> {
+    let arguments = split_notification(line, &["REQERR"], 4)?;
+    Ok(RequestError {
+        request_id: parse_number(line, &arguments, 1, "request ID")?,
+        code: parse_number(line, &arguments, 2, "error code")?,
+        message: decode_value(arguments.get(3).copied().unwrap_or("")),
+    })
+}
+
+/// Decodes a percent-encoded value, borrowing it when it contains no escape sequence. Malformed
+/// escape sequences are kept as they are.
+pub fn decode_value(value: &str) -> Cow<'_, str> {
+    if !value.contains('%') {
+        return Cow::Borrowed(value);
+    }
+    let mut decoded = Vec::with_capacity(value.len());
+    decode_bytes(value.as_bytes(), |byte| decoded.push(byte));
+    match String::from_utf8_lossy(&decoded) {
+        Cow::Borrowed(decoded) => Cow::Owned(decoded.to_string()),
+        Cow::Owned(decoded) => Cow::Owned(decoded),
+    }
+}
+
+/// Same as `decode_value()`, but decodes into the given arena rather than a new string.
+fn decode_value_in<'a>(value: &'a str, arena: &'a Bump) -> Cow<'a, str> {
+    if !value.contains('%') {
+        return Cow::Borrowed(value);
+    }
+    let mut decoded = BumpVec::with_capacity_in(value.len(), arena);
+    decode_bytes(value.as_bytes(), |byte| decoded.push(byte));
+    let decoded = decoded.into_bump_slice();
+    match std::str::from_utf8(decoded) {
+        Ok(decoded) => Cow::Borrowed(decoded),
+        Err(_) => Cow::Owned(String::from_utf8_lossy(decoded).into_owned()),
+    }
+}
+
+/// Decodes percent-encoded bytes, passing each decoded byte to `push`.
+fn decode_bytes(bytes: &[u8], mut push: impl FnMut(u8)) {
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = match bytes.get(i + 1..i + 3) {
+            Some([high, low]) if bytes[i] == b'%' => (*high as char)
+                .to_digit(16)
+                .zip((*low as char).to_digit(16)),
+            _ => None,
+        };
+        match escaped {
+            Some((high, low)) => {
+                push((high * 16 + low) as u8);
+                i += 3;
+            }
+            None => {
+                push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Splits a notification into at most `max` arguments, checking its name.
+pub(super) fn split_notification<'a>(
+    line: &'a str,
+    names: &[&str],
+    max: usize,
+) -> Result<Vec<&'a str>, ProtocolError> {
+    let arguments: Vec<&str> = line.trim().splitn(max, ',').collect();
+    check_notification_name(line, &arguments, names)?;
+    Ok(arguments)
+}
+
+/// Same as `split_notification()`, but allocates the arguments from the given arena.
+fn split_notification_in<'a>(
+    line: &'a str,
+    names: &[&str],
+    max: usize,
+    arena: &'a Bump,
+) -> Result<BumpVec<'a, &'a str>, ProtocolError> {
+    let arguments = BumpVec::from_iter_in(line.trim().splitn(max, ','), arena);
+    check_notification_name(line, &arguments, names)?;
+    Ok(arguments)
+}
+
+/// Checks that the first argument of a notification is one of the given names.
+fn check_notification_name(
+    line: &str,
+    arguments: &[&str],
+    names: &[&str],
+) -> Result<(), ProtocolError> {
+    if !names
+        .iter()
+        .any(|name| arguments[0].eq_ignore_ascii_case(name))
+    {
+        return Err(ProtocolError::new(
+            line.trim(),
+            &format!("expected a {} notification", names.join(" or ")),
+        ));
+    }
+    Ok(())
+}
+
+/// Percent-encodes a value to be carried by a notification, escaping the characters that
+/// delimit the arguments and the values, and the ones with a special meaning at the start of a
+/// value. The opposite of `decode_value()`.
+pub fn encode_value(value: &str) -> Cow<'_, str> {
+    let is_special = |(i, c): (usize, char)| {
+        matches!(c, '%' | ',' | '|' | '\r' | '\n') || (i == 0 && matches!(c, '#' | '$' | '^'))
+    };
+    if !value.char_indices().any(is_special) {
+        return Cow::Borrowed(value);
+    }
+    let mut encoded = String::with_capacity(value.len() + 8);
+    for (i, c) in value.char_indices() {
+        if is_special((i, c)) {
+            encoded.push_str(&format!("%{:02X}", c as u8));
+        } else {
+            encoded.push(c);
+        }
+    }
+    Cow::Owned(encoded)
+}
+
+/// Parses the argument at the given position as a number.
+pub(super) fn parse_number<T: std::str::FromStr>(
+    line: &str,
+    arguments: &[&str],
+    pos: usize,
+    name: &str,
+) -> Result<T, ProtocolError> {
+    arguments
+        .get(pos)
+        .and_then(|argument| argument.parse().ok())
+        .ok_or_else(|| ProtocolError::new(line.trim(), &format!("invalid {}", name)))
+}
+
+/// Parses a notification made of a subscription ID and an item position.
+fn parse_item_notification(line: &str, name: &str) -> Result<ItemNotification, ProtocolError> {
+    let arguments = split_notification(line, &[name], 3)?;
+    Ok(ItemNotification {
+        subscription_id: parse_number(line, &arguments, 1, "subscription ID")?,
+        item_pos: parse_number(line, &arguments, 2, "item position")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_update() {
+        let update = parse_update("U,3,1,10.5|^2|#|$|^P%5B%5D|caf%C3%A9|").unwrap();
+        assert_eq!(update.subscription_id, 3);
+        assert_eq!(update.item_pos, 1);
+        assert_eq!(
+            update.values.as_slice(),
+            [
+                FieldValue::Value(Cow::Borrowed("10.5")),
+                FieldValue::Unchanged,
+                FieldValue::Unchanged,
+                FieldValue::Null,
+                FieldValue::Empty,
+                FieldValue::JsonPatch(Cow::Borrowed("[]")),
+                FieldValue::Value(Cow::Borrowed("café")),
+                FieldValue::Unchanged,
+            ]
+        );
+        // Values are borrowed from the line unless they need decoding.
+        assert!(matches!(
+            update.values[0],
+            FieldValue::Value(Cow::Borrowed(_))
+        ));
+        assert!(!update.values.spilled());
+
+        assert!(parse_update("U,x,1,a").is_err());
+        assert!(parse_update("U,1,1,^x").is_err());
+        assert!(parse_update("EOS,1,1").is_err());
+    }
+
+    #[test]
+    fn test_parse_update_in_arena() {
+        let mut arena = Bump::new();
+        let line = "U,3,1,10.5|^2|#|$|^P%5B%5D|caf%C3%A9|";
+        {
+            let update = parse_update_in(line, &arena).unwrap();
+            assert_eq!(update, parse_update(line).unwrap());
+            // Decoded values are borrowed from the arena.
+            assert!(matches!(
+                update.values[6],
+                FieldValue::Value(Cow::Borrowed("café"))
+            ));
+        }
+        arena.reset();
+        assert!(parse_update_in("U,1,1,^x", &arena).is_err());
+        assert!(parse_update_in("EOS,1,1", &arena).is_err());
+    }
+
+    #[test]
+    fn test_parse_subscription_notifications() {
+        assert_eq!(
+            parse_subok("SUBOK,1,2,3\r\n").unwrap(),
+            SubscriptionOk {
+                subscription_id: 1,
+                items: 2,
+                fields: 3,
+                key_pos: None,
+                command_pos: None,
+            }
+        );
+        let subcmd = parse_subok("subcmd,1,2,4,1,2").unwrap();
+        assert_eq!((subcmd.key_pos, subcmd.command_pos), (Some(1), Some(2)));
+        assert!(parse_subok("SUBCMD,1,2,4").is_err());
+        assert_eq!(
+            parse_eos("EOS,2,5").unwrap(),
+            ItemNotification {
+                subscription_id: 2,
+                item_pos: 5,
+            }
+        );
+        assert!(parse_cs("EOS,2,5").is_err());
+        assert_eq!(parse_ov("OV,1,1,12").unwrap().lost_updates, 12);
+        assert_eq!(parse_unsub("UNSUB,7").unwrap(), 7);
+        let conf = parse_conf("CONF,1,unlimited,filtered").unwrap();
+        assert_eq!((conf.max_frequency, conf.filtered), (None, true));
+        assert_eq!(
+            parse_conf("CONF,1,2.5,unfiltered").unwrap().max_frequency,
+            Some(2.5)
+        );
+    }
+
+    #[test]
+    fn test_parse_session_notifications() {
+        assert_eq!(parse_prog("PROG,42").unwrap(), 42);
+        assert_eq!(parse_sync("SYNC,30").unwrap(), 30);
+        assert!(parse_sync("SYNC,soon").is_err());
+        let error = parse_reqerr("REQERR,5,19,Specified%20data adapter, not found").unwrap();
+        assert_eq!(error.request_id, 5);
+        assert_eq!(error.code, 19);
+        assert_eq!(error.message, "Specified data adapter, not found");
+        assert_eq!(
+            parse_reqerr("REQERR,5").unwrap_err().to_string(),
+            "Malformed notification 'REQERR,5': invalid error code"
+        );
+    }
+
+    #[test]
+    fn test_split_frame() {
+        let lines: Vec<&str> = split_frame("PROBE\r\n\r\nU,1,1,a\r\n").collect();
+        assert_eq!(lines, vec!["PROBE", "U,1,1,a"]);
+        assert_eq!(notification_name(lines[1]), "U");
+    }
+
+    #[test]
+    fn test_encode_value() {
+        assert!(matches!(encode_value("10.5"), Cow::Borrowed("10.5")));
+        assert_eq!(encode_value("a,b|c%"), "a%2Cb%7Cc%25");
+        assert_eq!(encode_value("#1$^"), "%231$^");
+        assert_eq!(decode_value(&encode_value("$ line\r\n")), "$ line\r\n");
+    }
+
+    #[test]
+    fn test_decode_value() {
+        assert!(matches!(decode_value("plain"), Cow::Borrowed("plain")));
+        assert_eq!(decode_value("a%7Cb%2C%0D%0A"), "a|b,\r\n");
+        // Malformed escape sequences are kept.
+        assert_eq!(decode_value("100%"), "100%");
+        assert_eq!(decode_value("%zz"), "%zz");
+    }
+}
@@ -0,0 +1,187 @@
+//! Parsing and encoding of TLCP frames, independent of any session, for tools such as proxies,
+//! test servers and fuzzers.
+//!
+//! ```
+//! use lightstreamer_rs::protocol::tlcp::{ClientRequest, ServerMessage, encode_request, parse_frame};
+//!
+//! let messages = parse_frame("PROBE\r\nU,1,1,a|b\r\n").unwrap();
+//! assert_eq!(messages.len(), 2);
+//! assert!(matches!(messages[0], ServerMessage::Probe));
+//!
+//! let request = ClientRequest::Heartbeat(vec![]);
+//! assert_eq!(encode_request(&request), "heartbeat\r\n");
+//! ```
+
+pub use crate::protocol::message::{Bandwidth, ServerMessage};
+use crate::protocol::notifications::split_frame;
+use crate::utils::ProtocolError;
+use std::borrow::Cow;
+
+/// The parameters of a request, as name and value pairs, in order.
+pub type RequestParams<'a> = Vec<(Cow<'a, str>, Cow<'a, str>)>;
+
+/// A request sent by the client on a WebSocket connection: the name of the request, on the
+/// first line, followed by its URL-encoded parameters.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ClientRequest<'a> {
+    /// `wsok`: the opening of the WebSocket connection.
+    Wsok,
+    /// `create_session`: the request of a new session.
+    CreateSession(RequestParams<'a>),
+    /// `bind_session`: the request binding the connection to an existing session.
+    BindSession(RequestParams<'a>),
+    /// `control`: one or more control requests, such as subscriptions, one per line.
+    Control(Vec<RequestParams<'a>>),
+    /// `msg`: one or more messages, one per line.
+    Msg(Vec<RequestParams<'a>>),
+    /// `heartbeat`: a reverse heartbeat, keeping the connection alive.
+    Heartbeat(RequestParams<'a>),
+}
+
+impl ClientRequest<'_> {
+    /// Returns the name of the request, as written on the first line.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClientRequest::Wsok => "wsok",
+            ClientRequest::CreateSession(_) => "create_session",
+            ClientRequest::BindSession(_) => "bind_session",
+            ClientRequest::Control(_) => "control",
+            ClientRequest::Msg(_) => "msg",
+            ClientRequest::Heartbeat(_) => "heartbeat",
+        }
+    }
+
+    /// Parses a request, as received by a Server.
+    ///
+    /// # Parameters
+    ///
+    /// * `frame`: The request, made of lines terminated by CRLF.
+    ///
+    /// # Raises
+    ///
+    /// * `ProtocolError`: if the request is unknown or its parameters are not URL-encoded.
+    pub fn parse(frame: &str) -> Result<ClientRequest<'static>, ProtocolError> {
+        let mut lines = split_frame(frame);
+        let name = lines.next().unwrap_or("");
+        let mut params = Vec::new();
+        for line in lines {
+            let line_params: Vec<(String, String)> = serde_urlencoded::from_str(line)
+                .map_err(|_| ProtocolError::new(line, "malformed request parameters"))?;
+            params.push(
+                line_params
+                    .into_iter()
+                    .map(|(name, value)| (Cow::Owned(name), Cow::Owned(value)))
+                    .collect::<RequestParams>(),
+            );
+        }
+        let request = match name.to_ascii_lowercase().as_str() {
+            "wsok" => ClientRequest::Wsok,
+            "create_session" => {
+                ClientRequest::CreateSession(params.into_iter().flatten().collect())
+            }
+            "bind_session" => ClientRequest::BindSession(params.into_iter().flatten().collect()),
+            "control" => ClientRequest::Control(params),
+            "msg" => ClientRequest::Msg(params),
+            "heartbeat" => ClientRequest::Heartbeat(params.into_iter().flatten().collect()),
+            _ => return Err(ProtocolError::new(name, "unknown request")),
+        };
+        Ok(request)
+    }
+}
+
+/// Parses a frame received from the Server into its notifications.
+///
+/// # Parameters
+///
+/// * `frame`: The frame, made of notifications terminated by CRLF.
+///
+/// # Raises
+///
+/// * `ProtocolError`: if any notification is unknown or malformed.
+pub fn parse_frame(frame: &str) -> Result<Vec<ServerMessage<'_>>, ProtocolError> {
+    split_frame(frame).map(ServerMessage::parse).collect()
+}
+
+/// Encodes a request as the frame sent on a WebSocket connection.
+///
+/// # Parameters
+///
+/// * `request`: The request to encode.
+pub fn encode_request(request: &ClientRequest) -> String {
+    let mut frame = String::from(request.name());
+    let mut push_line = |params: &RequestParams| {
+        frame.push_str("\r\n");
+        // Encoding pairs of strings cannot fail.
+        frame.push_str(&serde_urlencoded::to_string(params).unwrap_or_default());
+    };
+    match request {
+        ClientRequest::Wsok => {}
+        ClientRequest::CreateSession(params)
+        | ClientRequest::BindSession(params)
+        | ClientRequest::Heartbeat(params) => push_line(params),
+        ClientRequest::Control(requests) | ClientRequest::Msg(requests) => {
+            requests.iter().for_each(push_line)
+        }
+    }
+    frame
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params<'a>(pairs: &[(&'a str, &'a str)]) -> RequestParams<'a> {
+        pairs
+            .iter()
+            .map(|(name, value)| (Cow::Borrowed(*name), Cow::Borrowed(*value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_frame() {
+        let messages = parse_frame("CONOK,S1,50000,5000,*\r\nPROBE\r\n\r\nLOOP,0\r\n").unwrap();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[1], ServerMessage::Probe);
+        assert_eq!(messages[2], ServerMessage::Loop(0));
+        assert!(parse_frame("PROBE\r\nWAT\r\n").is_err());
+        assert!(parse_frame("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_encode_request() {
+        let request = ClientRequest::Control(vec![
+            params(&[
+                ("LS_reqId", "1"),
+                ("LS_op", "add"),
+                ("LS_group", "item1 item2"),
+            ]),
+            params(&[("LS_reqId", "2"), ("LS_op", "delete"), ("LS_subId", "1")]),
+        ]);
+        assert_eq!(
+            encode_request(&request),
+            "control\r\nLS_reqId=1&LS_op=add&LS_group=item1+item2\r\nLS_reqId=2&LS_op=delete&LS_subId=1"
+        );
+        assert_eq!(encode_request(&ClientRequest::Wsok), "wsok");
+    }
+
+    #[test]
+    fn test_requests_survive_a_round_trip() {
+        let requests = [
+            ClientRequest::Wsok,
+            ClientRequest::CreateSession(params(&[
+                ("LS_adapter_set", "DEMO"),
+                ("LS_password", "p&ss=word"),
+            ])),
+            ClientRequest::Msg(vec![params(&[("LS_message", "BUY 100")])]),
+            ClientRequest::Heartbeat(Vec::new()),
+        ];
+        for request in requests {
+            assert_eq!(
+                ClientRequest::parse(&encode_request(&request)).unwrap(),
+                request
+            );
+        }
+        assert!(ClientRequest::parse("destroy\r\nLS_op=x").is_err());
+    }
+}
@@ -0,0 +1,147 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// The bandwidth used by a Subscription, as returned by `BandwidthMeter::get_usage()`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct BandwidthUsage {
+    /// The bytes received for the Subscription, including the line terminators.
+    pub bytes: u64,
+    /// The notifications received for the Subscription, such as updates and snapshot events.
+    pub notifications: u64,
+    /// The average rate, in bytes per second, since the first notification.
+    pub average_rate: f64,
+    /// The rate, in bytes per second, over the last complete window of
+    /// `BandwidthMeter::RATE_WINDOW`.
+    pub recent_rate: f64,
+}
+
+/// The counters of a `BandwidthMeter`.
+#[derive(Debug, Default)]
+struct Counters {
+    bytes: u64,
+    notifications: u64,
+    /// When the first notification was received.
+    first_at: Option<Instant>,
+    /// The start of the current rate window.
+    window_start: Option<Instant>,
+    /// The bytes received in the current rate window.
+    window_bytes: u64,
+    /// The rate measured over the last complete window.
+    recent_rate: f64,
+}
+
+/// Meter of the bytes received for a Subscription, attributed through the subscription ID of
+/// each notification, e.g. to charge the teams sharing a feed for their share of the bandwidth.
+///
+/// The meter is a shared handle, obtained through `Subscription::get_bandwidth_meter()`, which
+/// can be kept to read the figures after the Subscription has been handed over to the client.
+/// Figures are cumulative across sessions.
+#[derive(Debug, Clone, Default)]
+pub struct BandwidthMeter {
+    counters: Arc<Mutex<Counters>>,
+}
+
+impl BandwidthMeter {
+    /// The length of the window the recent rate is measured over.
+    pub const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+    /// Returns the bandwidth used so far.
+    pub fn get_usage(&self) -> BandwidthUsage {
+        self.get_usage_at(Instant::now())
+    }
+
+    /// Returns the bandwidth used so far, as of the given time.
+    fn get_usage_at(&self, now: Instant) -> BandwidthUsage {
+        let counters = self.lock();
+        let average_rate = match counters.first_at {
+            Some(first_at) => {
+                let elapsed = now.duration_since(first_at).max(Self::RATE_WINDOW);
+                counters.bytes as f64 / elapsed.as_secs_f64()
+            }
+            None => 0.0,
+        };
+        BandwidthUsage {
+            bytes: counters.bytes,
+            notifications: counters.notifications,
+            average_rate,
+            recent_rate: counters.recent_rate,
+        }
+    }
+
+    /// Clears the figures, e.g. at the start of a new billing period.
+    pub fn reset(&self) {
+        *self.lock() = Counters::default();
+    }
+
+    /// Records a notification of the given length.
+    pub(crate) fn record(&self, bytes: usize) {
+        self.record_at(bytes, Instant::now());
+    }
+
+    /// Records a notification of the given length, received at the given time.
+    fn record_at(&self, bytes: usize, now: Instant) {
+        let mut counters = self.lock();
+        let bytes = bytes as u64;
+        counters.bytes += bytes;
+        counters.notifications += 1;
+        counters.first_at.get_or_insert(now);
+        match counters.window_start {
+            Some(start) if now.duration_since(start) < Self::RATE_WINDOW => {
+                counters.window_bytes += bytes;
+            }
+            Some(start) => {
+                // A window with no notifications at all between the two means no traffic.
+                counters.recent_rate = if now.duration_since(start) < Self::RATE_WINDOW * 2 {
+                    counters.window_bytes as f64 / Self::RATE_WINDOW.as_secs_f64()
+                } else {
+                    0.0
+                };
+                counters.window_start = Some(now);
+                counters.window_bytes = bytes;
+            }
+            None => {
+                counters.window_start = Some(now);
+                counters.window_bytes = bytes;
+            }
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Counters> {
+        self.counters.lock().unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_is_cumulative() {
+        let meter = BandwidthMeter::default();
+        let start = Instant::now();
+        meter.record_at(100, start);
+        meter.record_at(50, start + Duration::from_millis(500));
+        // The next window reports the rate of the previous one.
+        meter.record_at(10, start + Duration::from_millis(1200));
+
+        let usage = meter.get_usage_at(start + Duration::from_secs(2));
+        assert_eq!(usage.bytes, 160);
+        assert_eq!(usage.notifications, 3);
+        assert_eq!(usage.average_rate, 80.0);
+        assert_eq!(usage.recent_rate, 150.0);
+    }
+
+    #[test]
+    fn test_idle_window_resets_recent_rate() {
+        let meter = BandwidthMeter::default();
+        let start = Instant::now();
+        meter.record_at(100, start);
+        meter.record_at(10, start + Duration::from_secs(5));
+        assert_eq!(meter.get_usage().recent_rate, 0.0);
+
+        // Clones share the figures.
+        meter.clone().reset();
+        assert_eq!(meter.get_usage(), BandwidthUsage::default());
+    }
+}
@@ -0,0 +1,208 @@
+use crate::subscription::{Snapshot, Subscription, SubscriptionMode};
+use crate::utils::{LightstreamerError, ValidationError, ValidationProblem};
+
+/// Builder for a `Subscription`, which collects every problem of the configuration and reports
+/// them all together when the Subscription is built, rather than failing at the first one.
+///
+/// # Example
+/// ```
+/// use lightstreamer_rs::subscription::{Subscription, SubscriptionMode};
+///
+/// let error = Subscription::builder(SubscriptionMode::Command)
+///     .items(vec!["portfolio1".to_string()])
+///     .fields(vec!["qty".to_string()])
+///     .max_frequency(-1.0)
+///     .build()
+///     .unwrap_err();
+/// // The missing "key" and "command" fields and the bad frequency are all reported.
+/// assert_eq!(error.get_problems().len(), 3);
+/// ```
+///
+/// See also `Subscription::builder()`
+#[derive(Debug)]
+pub struct SubscriptionBuilder {
+    /// The Subscription being configured.
+    subscription: Subscription,
+    /// The problems reported by the setters so far.
+    problems: Vec<ValidationProblem>,
+}
+
+impl SubscriptionBuilder {
+    /// Creates a builder for a Subscription in the given mode.
+    pub(crate) fn new(mode: SubscriptionMode) -> Self {
+        SubscriptionBuilder {
+            subscription: Subscription::unconfigured(mode),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Records the problem reported by a setter, if any.
+    fn record(mut self, property: &str, result: Result<(), LightstreamerError>) -> Self {
+        if let Err(error) = result {
+            self.problems
+                .push(ValidationProblem::new(property, &error.to_string()));
+        }
+        self
+    }
+
+    /// Sets the "Item List" to be subscribed to. See `Subscription::set_items()`.
+    pub fn items(mut self, items: Vec<String>) -> Self {
+        let result = self.subscription.set_items(items);
+        self.record("items", result)
+    }
+
+    /// Sets the "Item Group" to be subscribed to. See `Subscription::set_item_group()`.
+    pub fn item_group(mut self, group: &str) -> Self {
+        let result = self.subscription.set_item_group(group.to_string());
+        self.record("item_group", result)
+    }
+
+    /// Sets the "Field List" to be subscribed to. See `Subscription::set_fields()`.
+    pub fn fields(mut self, fields: Vec<String>) -> Self {
+        let result = self.subscription.set_fields(fields);
+        self.record("fields", result)
+    }
+
+    /// Sets the "Field Schema" to be subscribed to. See `Subscription::set_field_schema()`.
+    pub fn field_schema(mut self, schema: &str) -> Self {
+        let result = self.subscription.set_field_schema(schema.to_string());
+        self.record("field_schema", result)
+    }
+
+    /// Sets the Data Adapter supplying the items. See `Subscription::set_data_adapter()`.
+    pub fn data_adapter(mut self, adapter: &str) -> Self {
+        let result = self
+            .subscription
+            .set_data_adapter(Some(adapter.to_string()));
+        self.record("data_adapter", result)
+    }
+
+    /// Sets the snapshot preference. See `Subscription::set_requested_snapshot()`.
+    pub fn snapshot(mut self, snapshot: Snapshot) -> Self {
+        let result = self.subscription.set_requested_snapshot(Some(snapshot));
+        self.record("requested_snapshot", result)
+    }
+
+    /// Sets the maximum update frequency, in updates per second. See
+    /// `Subscription::set_requested_max_frequency()`.
+    pub fn max_frequency(mut self, frequency: f64) -> Self {
+        let result = self
+            .subscription
+            .set_requested_max_frequency(Some(frequency));
+        self.record("requested_max_frequency", result)
+    }
+
+    /// Sets the length of the Server buffers. See `Subscription::set_requested_buffer_size()`.
+    pub fn buffer_size(mut self, size: usize) -> Self {
+        let result = self.subscription.set_requested_buffer_size(Some(size));
+        self.record("requested_buffer_size", result)
+    }
+
+    /// Sets the selector filtering the updates. See `Subscription::set_selector()`.
+    pub fn selector(mut self, selector: &str) -> Self {
+        let result = self.subscription.set_selector(Some(selector.to_string()));
+        self.record("selector", result)
+    }
+
+    /// Drops the updates changing no cached value. See
+    /// `Subscription::set_noop_update_suppression_enabled()`.
+    pub fn suppress_noop_updates(mut self) -> Self {
+        let result = self.subscription.set_noop_update_suppression_enabled(true);
+        self.record("noop_update_suppression_enabled", result)
+    }
+
+    /// Sets the priority of the dispatching of the updates. See `Subscription::set_priority()`.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.subscription.set_priority(priority);
+        self
+    }
+
+    /// Builds the Subscription, checking the whole configuration.
+    ///
+    /// # Errors
+    /// Returns a `ValidationError` carrying the problems reported by the setters and the ones
+    /// found by `Subscription::validate()`, a property with a problem reported by its setter not being checked again.
+    pub fn build(mut self) -> Result<Subscription, ValidationError> {
+        if let Err(error) = self.subscription.validate() {
+            let reported: Vec<String> = self
+                .problems
+                .iter()
+                .map(|problem| problem.get_property().to_string())
+                .collect();
+            self.problems.extend(
+                error
+                    .get_problems()
+                    .iter()
+                    .filter(|problem| !reported.iter().any(|p| p == problem.get_property()))
+                    .cloned(),
+            );
+        }
+        ValidationError::check(self.problems)?;
+        Ok(self.subscription)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_valid_configuration_builds() {
+        let subscription = Subscription::builder(SubscriptionMode::Distinct)
+            .items(strings(&["item1", "item2"]))
+            .field_schema("short")
+            .snapshot(Snapshot::Number(10))
+            .max_frequency(2.0)
+            .priority(5)
+            .suppress_noop_updates()
+            .build()
+            .unwrap();
+        assert_eq!(subscription.get_items().unwrap().len(), 2);
+        assert_eq!(subscription.get_priority(), 5);
+        assert!(subscription.is_noop_update_suppression_enabled());
+        assert_eq!(subscription.get_field_schema().unwrap(), "short");
+        assert_eq!(subscription.get_requested_max_frequency(), Some(&2.0));
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let error = Subscription::builder(SubscriptionMode::Merge)
+            .items(strings(&["item 1"]))
+            .snapshot(Snapshot::Number(5))
+            .buffer_size(0)
+            .build()
+            .unwrap_err();
+        let properties: Vec<&str> = error
+            .get_problems()
+            .iter()
+            .map(|problem| problem.get_property())
+            .collect();
+        assert_eq!(
+            properties,
+            vec![
+                "items",
+                "requested_snapshot",
+                "fields",
+                "requested_buffer_size"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_command_mode_requires_key_and_command() {
+        let error = Subscription::builder(SubscriptionMode::Command)
+            .items(strings(&["portfolio"]))
+            .fields(strings(&["key", "qty"]))
+            .build()
+            .unwrap_err();
+        assert_eq!(error.get_problems().len(), 1);
+        assert_eq!(
+            error.get_problems()[0].get_message(),
+            "COMMAND mode requires the 'command' field"
+        );
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Statistics about a value cache, as returned by `Subscription::get_value_cache_metrics()`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheMetrics {
+    /// The number of entries currently cached.
+    pub entries: usize,
+    /// The maximum number of entries, or `None` if the cache is unbounded.
+    pub capacity: Option<usize>,
+    /// The number of entries evicted so far to stay within the capacity.
+    pub evictions: u64,
+}
+
+/// Last-value cache with an optional capacity and least-recently-updated eviction.
+///
+/// Recency is refreshed when an entry is inserted or updated, not when it is read, so that
+/// lookups can be served through a shared reference. When the capacity is exceeded, the entry
+/// that was updated least recently is evicted.
+pub(crate) struct ValueCache<K, V> {
+    /// Cached values, together with the tick of their last update.
+    entries: HashMap<K, (V, u64)>,
+    /// Keys by tick of their last update, oldest first.
+    order: BTreeMap<u64, K>,
+    /// The maximum number of entries, or `None` if unbounded.
+    capacity: Option<usize>,
+    /// Monotonic counter used to order updates.
+    tick: u64,
+    /// The number of entries evicted so far.
+    evictions: u64,
+}
+
+impl<K: Eq + Hash + Clone, V> ValueCache<K, V> {
+    /// Creates an empty cache.
+    ///
+    /// # Parameters
+    /// - `capacity`: the maximum number of entries, or `None` for an unbounded cache.
+    pub(crate) fn new(capacity: Option<usize>) -> Self {
+        ValueCache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            capacity,
+            tick: 0,
+            evictions: 0,
+        }
+    }
+
+    /// Returns the value cached for a key, without refreshing its recency.
+    pub(crate) fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    /// Returns the value cached for a key, inserting the default value if missing, and marks
+    /// the entry as the most recently updated one.
+    pub(crate) fn get_or_insert_default(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        if !self.entries.contains_key(&key) {
+            self.insert(key.clone(), V::default());
+        } else {
+            self.touch(&key);
+        }
+        &mut self.entries.get_mut(&key).expect("entry just inserted").0
+    }
+
+    /// Inserts or replaces the value for a key, marking it as the most recently updated entry
+    /// and evicting the least recently updated ones if the capacity is exceeded.
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        self.tick += 1;
+        if let Some((_, tick)) = self.entries.insert(key.clone(), (value, self.tick)) {
+            self.order.remove(&tick);
+        }
+        self.order.insert(self.tick, key);
+        self.evict();
+    }
+
+    /// Removes the value for a key.
+    pub(crate) fn remove(&mut self, key: &K) -> Option<V> {
+        let (value, tick) = self.entries.remove(key)?;
+        self.order.remove(&tick);
+        Some(value)
+    }
+
+    /// Removes all entries. Eviction statistics are preserved.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// Returns the number of cached entries.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Changes the capacity, evicting entries if the new capacity is exceeded.
+    pub(crate) fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.capacity = capacity;
+        self.evict();
+    }
+
+    /// Returns the statistics of the cache.
+    pub(crate) fn metrics(&self) -> CacheMetrics {
+        CacheMetrics {
+            entries: self.len(),
+            capacity: self.capacity,
+            evictions: self.evictions,
+        }
+    }
+
+    /// Marks an existing entry as the most recently updated one.
+    fn touch(&mut self, key: &K) {
+        if let Some((_, tick)) = self.entries.get_mut(key) {
+            self.order.remove(tick);
+            self.tick += 1;
+            *tick = self.tick;
+            self.order.insert(self.tick, key.clone());
+        }
+    }
+
+    /// Evicts the least recently updated entries until the capacity is respected.
+    fn evict(&mut self) {
+        let Some(capacity) = self.capacity else {
+            return;
+        };
+        while self.entries.len() > capacity {
+            let Some((_, key)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+            self.evictions += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unbounded_cache() {
+        let mut cache = ValueCache::new(None);
+        for i in 0..100 {
+            cache.insert(i, i * 2);
+        }
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.get(&10), Some(&20));
+        assert_eq!(cache.metrics().evictions, 0);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_updated() {
+        let mut cache = ValueCache::new(Some(2));
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // Updating "a" makes "b" the least recently updated entry.
+        cache.insert("a", 3);
+        cache.insert("c", 4);
+
+        assert_eq!(cache.get(&"a"), Some(&3));
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"c"), Some(&4));
+        assert_eq!(
+            cache.metrics(),
+            CacheMetrics {
+                entries: 2,
+                capacity: Some(2),
+                evictions: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_or_insert_default_refreshes_recency() {
+        let mut cache: ValueCache<&str, Vec<u32>> = ValueCache::new(Some(2));
+        cache.get_or_insert_default("a").push(1);
+        cache.get_or_insert_default("b").push(2);
+        cache.get_or_insert_default("a").push(3);
+        cache.get_or_insert_default("c");
+
+        assert_eq!(cache.get(&"a"), Some(&vec![1, 3]));
+        assert_eq!(cache.get(&"b"), None);
+    }
+
+    #[test]
+    fn test_remove_clear_and_set_capacity() {
+        let mut cache = ValueCache::new(None);
+        cache.insert(1, "one");
+        cache.insert(2, "two");
+        cache.insert(3, "three");
+
+        assert_eq!(cache.remove(&2), Some("two"));
+        assert_eq!(cache.remove(&2), None);
+
+        cache.set_capacity(Some(1));
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&3), Some(&"three"));
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.metrics().evictions, 1);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::utils::{IllegalArgumentException, LightstreamerError};
+use std::any::Any;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+
+/// A value decoded by a `FieldCodec`, as stored in `ItemUpdate::decoded_fields`.
+pub type DecodedValue = Arc<dyn Any + Send + Sync>;
+
+/// Decoder of the values of a field into a domain type, such as binary data packed in base64
+/// or an enumeration sent as a code.
+///
+/// Codecs are registered per field on a Subscription through `Subscription::set_field_codec()`
+/// and applied by the client to each update before it reaches the listeners, which read the
+/// decoded values with `ItemUpdate::get_decoded_value()`. The textual values are still
+/// available as usual.
+///
+/// Closures taking the textual value are codecs too:
+///
+/// ```ignore
+/// subscription.set_field_codec("side", |value: &str| match value {
+///     "B" => Ok(Side::Buy),
+///     "S" => Ok(Side::Sell),
+///     other => Err(IllegalArgumentException::new(&format!("Unknown side: {}", other)).into()),
+/// });
+/// ```
+pub trait FieldCodec: Send + Sync {
+    /// The type of the decoded values.
+    type Value: Send + Sync + 'static;
+
+    /// Decodes a value of the field.
+    ///
+    /// # Parameters
+    ///
+    /// * `value`: the textual value of the field, never null.
+    ///
+    /// # Returns
+    ///
+    /// The decoded value, or an error if the value is malformed, in which case the update is
+    /// delivered without decoded value for the field.
+    fn decode(&self, value: &str) -> Result<Self::Value, LightstreamerError>;
+}
+
+impl<F, T> FieldCodec for F
+where
+    F: Fn(&str) -> Result<T, LightstreamerError> + Send + Sync,
+    T: Send + Sync + 'static,
+{
+    type Value = T;
+
+    fn decode(&self, value: &str) -> Result<T, LightstreamerError> {
+        self(value)
+    }
+}
+
+/// Codec decoding binary data packed in standard base64, with or without padding, into a
+/// `Vec<u8>`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Base64Codec;
+
+impl FieldCodec for Base64Codec {
+    type Value = Vec<u8>;
+
+    fn decode(&self, value: &str) -> Result<Vec<u8>, LightstreamerError> {
+        let sextet = |byte: u8| match byte {
+            b'A'..=b'Z' => Some(byte - b'A'),
+            b'a'..=b'z' => Some(byte - b'a' + 26),
+            b'0'..=b'9' => Some(byte - b'0' + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        };
+        let invalid = || {
+            LightstreamerError::from(IllegalArgumentException::new(&format!(
+                "Invalid base64 value: {}",
+                value
+            )))
+        };
+        let data = value.trim_end_matches('=').as_bytes();
+        if data.len() % 4 == 1 {
+            return Err(invalid());
+        }
+        let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+        for chunk in data.chunks(4) {
+            let mut bits: u32 = 0;
+            for (index, &byte) in chunk.iter().enumerate() {
+                bits |= u32::from(sextet(byte).ok_or_else(invalid)?) << (18 - 6 * index);
+            }
+            let decoded = bits.to_be_bytes();
+            bytes.extend_from_slice(&decoded[1..chunk.len()]);
+        }
+        Ok(bytes)
+    }
+}
+
+/// A codec decoding into a `DecodedValue`, whatever the type of its values.
+trait DynFieldCodec: Send + Sync {
+    fn decode_dyn(&self, value: &str) -> Result<DecodedValue, LightstreamerError>;
+}
+
+impl<C: FieldCodec> DynFieldCodec for C {
+    fn decode_dyn(&self, value: &str) -> Result<DecodedValue, LightstreamerError> {
+        self.decode(value)
+            .map(|decoded| Arc::new(decoded) as DecodedValue)
+    }
+}
+
+/// A codec with its value type erased, as registered on a Subscription.
+#[derive(Clone)]
+pub(crate) struct ErasedCodec(Arc<dyn DynFieldCodec>);
+
+impl ErasedCodec {
+    pub(crate) fn new<C: FieldCodec + 'static>(codec: C) -> ErasedCodec {
+        ErasedCodec(Arc::new(codec))
+    }
+
+    pub(crate) fn decode(&self, value: &str) -> Result<DecodedValue, LightstreamerError> {
+        self.0.decode_dyn(value)
+    }
+}
+
+impl Debug for ErasedCodec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("FieldCodec")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_codec() {
+        let codec = Base64Codec;
+        assert_eq!(codec.decode("").unwrap(), b"");
+        assert_eq!(codec.decode("TQ==").unwrap(), b"M");
+        assert_eq!(codec.decode("TWE").unwrap(), b"Ma");
+        assert_eq!(codec.decode("TWFu").unwrap(), b"Man");
+        assert_eq!(codec.decode("AP8B").unwrap(), vec![0x00, 0xff, 0x01]);
+        assert!(codec.decode("TWF").is_ok());
+        assert!(codec.decode("T").is_err());
+        assert!(codec.decode("TW$u").is_err());
+    }
+
+    #[test]
+    fn test_closures_are_codecs() {
+        let codec = ErasedCodec::new(|value: &str| {
+            value
+                .parse::<u8>()
+                .map_err(|err| IllegalArgumentException::new(&err.to_string()).into())
+        });
+        let decoded = codec.decode("42").unwrap();
+        assert_eq!(decoded.downcast_ref::<u8>(), Some(&42));
+        assert!(codec.decode("x").is_err());
+    }
+}
@@ -0,0 +1,153 @@
+use crate::subscription::ItemUpdate;
+use std::collections::HashMap;
+
+/// Name of the field carrying the key in a COMMAND Subscription.
+pub(crate) const KEY_FIELD: &str = "key";
+
+/// Name of the field carrying the command in a COMMAND Subscription.
+pub(crate) const COMMAND_FIELD: &str = "command";
+
+/// Typed interpretation of an update received for a COMMAND Subscription.
+///
+/// In COMMAND mode each update carries a "key" field identifying a row of the table and a
+/// "command" field telling whether the row was added, updated or deleted. Events are delivered
+/// through `SubscriptionListener::on_command_event()`, in addition to the raw `ItemUpdate`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandEvent {
+    /// A new row was added (ADD command).
+    RowAdded {
+        /// The key of the row.
+        key: String,
+        /// The current value of all fields of the row.
+        fields: HashMap<String, Option<String>>,
+    },
+    /// An existing row was updated (UPDATE command).
+    RowUpdated {
+        /// The key of the row.
+        key: String,
+        /// The fields changed by the update, excluding "key" and "command".
+        changed: HashMap<String, String>,
+    },
+    /// A row was deleted (DELETE command).
+    RowDeleted {
+        /// The key of the row.
+        key: String,
+    },
+}
+
+impl CommandEvent {
+    /// Interprets an update of a COMMAND Subscription.
+    ///
+    /// # Parameters
+    /// - `update`: the update to be interpreted. Its fields must include "key" and "command".
+    ///
+    /// # Returns
+    /// The typed event, or `None` if the update does not carry a key and a known command.
+    pub fn from_update(update: &ItemUpdate) -> Option<CommandEvent> {
+        let key = update.get_value(KEY_FIELD)?.to_string();
+        let command = update.get_value(COMMAND_FIELD)?;
+        if command.eq_ignore_ascii_case("ADD") {
+            Some(CommandEvent::RowAdded {
+                key,
+                fields: update.get_fields(),
+            })
+        } else if command.eq_ignore_ascii_case("UPDATE") {
+            let changed = update
+                .changed_fields
+                .iter()
+                .filter(|(name, _)| name.as_str() != KEY_FIELD && name.as_str() != COMMAND_FIELD)
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect();
+            Some(CommandEvent::RowUpdated { key, changed })
+        } else if command.eq_ignore_ascii_case("DELETE") {
+            Some(CommandEvent::RowDeleted { key })
+        } else {
+            None
+        }
+    }
+
+    /// Returns the key of the row the event refers to.
+    pub fn get_key(&self) -> &str {
+        match self {
+            CommandEvent::RowAdded { key, .. }
+            | CommandEvent::RowUpdated { key, .. }
+            | CommandEvent::RowDeleted { key } => key,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_command_update(command: &str, changed: &[(&str, &str)]) -> ItemUpdate {
+        let mut fields = HashMap::new();
+        fields.insert("key".to_string(), Some("row1".to_string()));
+        fields.insert("command".to_string(), Some(command.to_string()));
+        fields.insert("price".to_string(), Some("10".to_string()));
+
+        let changed_fields = changed
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+
+        ItemUpdate {
+            item_name: Some("portfolio".to_string()),
+            item_pos: 1,
+            fields,
+            changed_fields,
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_row_added() {
+        let update = create_command_update("ADD", &[("key", "row1"), ("command", "ADD")]);
+        match CommandEvent::from_update(&update) {
+            Some(CommandEvent::RowAdded { key, fields }) => {
+                assert_eq!(key, "row1");
+                assert_eq!(fields.get("price"), Some(&Some("10".to_string())));
+            }
+            other => panic!("Unexpected event: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_row_updated() {
+        let update = create_command_update("update", &[("command", "update"), ("price", "11")]);
+        let event = CommandEvent::from_update(&update).unwrap();
+        let mut changed = HashMap::new();
+        changed.insert("price".to_string(), "11".to_string());
+        assert_eq!(
+            event,
+            CommandEvent::RowUpdated {
+                key: "row1".to_string(),
+                changed
+            }
+        );
+        assert_eq!(event.get_key(), "row1");
+    }
+
+    #[test]
+    fn test_row_deleted() {
+        let update = create_command_update("DELETE", &[("command", "DELETE")]);
+        assert_eq!(
+            CommandEvent::from_update(&update),
+            Some(CommandEvent::RowDeleted {
+                key: "row1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_not_a_command_update() {
+        let update = create_command_update("CLEAR", &[]);
+        assert_eq!(CommandEvent::from_update(&update), None);
+
+        let mut update = create_command_update("ADD", &[]);
+        update.fields.remove("key");
+        assert_eq!(CommandEvent::from_update(&update), None);
+    }
+}
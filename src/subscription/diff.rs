@@ -0,0 +1,25 @@
+use smallvec::SmallVec;
+
+/// The number of entries kept inline by a `FieldList`, enough for typical subscriptions.
+pub(crate) const INLINE_FIELDS: usize = 16;
+
+/// A list with an entry per field of an update, which only allocates when the update carries
+/// more than `INLINE_FIELDS` fields.
+pub(crate) type FieldList<T> = SmallVec<[T; INLINE_FIELDS]>;
+
+/// A change of a field value, pairing the value held in the Subscription cache before an update
+/// with the one carried by the update.
+///
+/// Changes are delivered through `SubscriptionListener::on_field_changes()`, so that audit and
+/// change-detection logic can rely on the cache of the Subscription instead of keeping its own
+/// copy of the previous values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldChange {
+    /// The name of the field.
+    pub field: String,
+    /// The value before the update, or `None` if no value was known (first update of the item,
+    /// new row of a COMMAND Subscription, or value caching disabled).
+    pub previous_value: Option<String>,
+    /// The value carried by the update.
+    pub new_value: String,
+}
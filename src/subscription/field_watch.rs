@@ -0,0 +1,131 @@
+use crate::subscription::{FieldChange, ItemUpdate, SubscriptionListener};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// A closure called with the changes of a watched field.
+type FieldCallback = Box<dyn Fn(&FieldChange) + Send>;
+
+/// `SubscriptionListener` that watches a single cell of a Subscription, identified by the
+/// position of its item and by the name of its field, and reports its value only when it
+/// changes.
+///
+/// This is meant for alerting rules watching one cell of a big table, which would otherwise
+/// have to filter every update of the Subscription. Updates leaving the value unchanged are not
+/// reported, and neither are the other items and fields.
+///
+/// See also `Subscription::watch_field()` and `Subscription::watch_field_changes()`
+pub struct FieldWatcher {
+    /// The 1-based position of the watched item.
+    item_pos: usize,
+    /// The name of the watched field.
+    field: String,
+    /// The closure receiving the changes.
+    callback: FieldCallback,
+}
+
+impl FieldWatcher {
+    /// Creates a watcher calling the given closure on each change of a field.
+    ///
+    /// # Parameters
+    ///
+    /// * `item_pos`: the 1-based position of the item within the "Item List" or "Item Group".
+    /// * `field`: the name of the field.
+    /// * `callback`: the closure called with each change.
+    pub fn new<F>(item_pos: usize, field: &str, callback: F) -> Self
+    where
+        F: Fn(&FieldChange) + Send + 'static,
+    {
+        FieldWatcher {
+            item_pos,
+            field: field.to_string(),
+            callback: Box::new(callback),
+        }
+    }
+
+    /// Creates a watcher sending each change of a field to the given channel. Changes are
+    /// dropped silently once the receiver is closed.
+    ///
+    /// # Parameters
+    ///
+    /// * `item_pos`: the 1-based position of the item within the "Item List" or "Item Group".
+    /// * `field`: the name of the field.
+    /// * `sender`: the channel receiving the changes.
+    pub fn with_sender(item_pos: usize, field: &str, sender: UnboundedSender<FieldChange>) -> Self {
+        FieldWatcher::new(item_pos, field, move |change| {
+            let _ = sender.send(change.clone());
+        })
+    }
+
+    /// Returns the 1-based position of the watched item.
+    pub fn get_item_pos(&self) -> usize {
+        self.item_pos
+    }
+
+    /// Returns the name of the watched field.
+    pub fn get_field(&self) -> &str {
+        &self.field
+    }
+}
+
+impl SubscriptionListener for FieldWatcher {
+    fn on_item_update(&self, _update: &ItemUpdate) {}
+
+    fn on_field_changes(&self, update: &ItemUpdate, changes: &[FieldChange]) {
+        if update.item_pos != self.item_pos {
+            return;
+        }
+        if let Some(change) = changes.iter().find(|change| change.field == self.field) {
+            (self.callback)(change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn update(item_pos: usize) -> ItemUpdate {
+        ItemUpdate {
+            item_name: None,
+            item_pos,
+            fields: HashMap::new(),
+            changed_fields: HashMap::new(),
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    fn change(field: &str, new_value: &str) -> FieldChange {
+        FieldChange {
+            field: field.to_string(),
+            previous_value: None,
+            new_value: new_value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_only_the_watched_cell_is_reported() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let sink = received.clone();
+        let watcher = FieldWatcher::new(2, "bid", move |change| {
+            sink.lock().unwrap().push(change.new_value.clone());
+        });
+        watcher.on_field_changes(&update(1), &[change("bid", "1")]);
+        watcher.on_field_changes(&update(2), &[change("ask", "2")]);
+        watcher.on_field_changes(&update(2), &[change("ask", "3"), change("bid", "4")]);
+        assert_eq!(*received.lock().unwrap(), vec!["4".to_string()]);
+    }
+
+    #[test]
+    fn test_changes_are_sent_to_the_channel() {
+        let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+        let watcher = FieldWatcher::with_sender(1, "bid", sender);
+        watcher.on_field_changes(&update(1), &[change("bid", "10")]);
+        assert_eq!(receiver.try_recv().unwrap(), change("bid", "10"));
+        drop(receiver);
+        // A closed receiver is not an error.
+        watcher.on_field_changes(&update(1), &[change("bid", "11")]);
+    }
+}
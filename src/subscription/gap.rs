@@ -0,0 +1,103 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::SystemTime;
+
+/// Why updates of a Subscription may have been missed, as reported by a `DataGap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DataGapCause {
+    /// The Server dropped updates of an item because of its buffer limits (OV).
+    Overflow,
+    /// The Server sent data notifications which never reached the client, as found by comparing
+    /// the progressive counts (PROG) after a session recovery.
+    LostNotifications,
+    /// The session could not be recovered after a network drop, so a new session was created
+    /// and the Subscription subscribed again.
+    RecoveryFailed,
+    /// The Server closed the session (END).
+    SessionEnded,
+}
+
+impl Display for DataGapCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DataGapCause::Overflow => write!(f, "overflow"),
+            DataGapCause::LostNotifications => write!(f, "lost notifications"),
+            DataGapCause::RecoveryFailed => write!(f, "recovery failed"),
+            DataGapCause::SessionEnded => write!(f, "session ended"),
+        }
+    }
+}
+
+/// A possible loss of updates of a Subscription, notified through
+/// `SubscriptionListener::on_data_gap()` so that downstream systems can reconcile their data,
+/// e.g. from a snapshot or a historical source, instead of silently missing ticks.
+///
+/// The bounds of the gap are best-effort: the client only knows what the Server reported and
+/// when the connection broke.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataGap {
+    /// Why updates may have been missed.
+    pub cause: DataGapCause,
+    /// The 1-based position of the affected item, or `None` if all the items may be affected.
+    pub item_pos: Option<usize>,
+    /// The number of updates missed, exact for an overflow and an upper bound for lost
+    /// notifications, or `None` if unknown.
+    pub lost_updates: Option<u64>,
+    /// When updates may have started to be missed, such as when the connection dropped, or
+    /// `None` if unknown.
+    pub since: Option<SystemTime>,
+    /// When the gap was detected.
+    pub detected_at: SystemTime,
+}
+
+impl DataGap {
+    /// Creates a gap detected now.
+    pub(crate) fn new(
+        cause: DataGapCause,
+        item_pos: Option<usize>,
+        lost_updates: Option<u64>,
+        since: Option<SystemTime>,
+    ) -> Self {
+        DataGap {
+            cause,
+            item_pos,
+            lost_updates,
+            since,
+            detected_at: SystemTime::now(),
+        }
+    }
+}
+
+impl Display for DataGap {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "data gap ({})", self.cause)?;
+        if let Some(item_pos) = self.item_pos {
+            write!(f, " on item {}", item_pos)?;
+        }
+        if let Some(lost_updates) = self.lost_updates {
+            write!(f, ", {} updates lost", lost_updates)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        let gap = DataGap::new(DataGapCause::Overflow, Some(2), Some(5), None);
+        assert_eq!(
+            gap.to_string(),
+            "data gap (overflow) on item 2, 5 updates lost"
+        );
+        let gap = DataGap::new(
+            DataGapCause::RecoveryFailed,
+            None,
+            None,
+            Some(SystemTime::now()),
+        );
+        assert_eq!(gap.to_string(), "data gap (recovery failed)");
+        assert!(gap.since.unwrap() <= gap.detected_at);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::subscription::DecodedValue;
 use serde::Serialize;
 use std::collections::HashMap;
 
@@ -34,6 +35,33 @@ pub struct ItemUpdate {
     pub changed_fields: HashMap<String, String>,
     /// Flag indicating whether this update is part of a snapshot (initial state) or a real-time update.
     pub is_snapshot: bool,
+    /// The origin of the fields of a two-level COMMAND Subscription. Fields not present in the
+    /// map come from the first-level item. The client does not subscribe to second-level items
+    /// yet, so the map is always empty for now.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub field_sources: HashMap<String, FieldSource>,
+    /// The values decoded by the codecs registered on the Subscription, by field name. Fields
+    /// without codec, null or failing to decode are not present in the map.
+    #[serde(skip)]
+    pub decoded_fields: HashMap<String, DecodedValue>,
+}
+
+/// Origin of a field value in an update of a COMMAND Subscription with two-level behavior.
+///
+/// The row delivered for a key merges the fields of the first-level item with the fields of the
+/// second-level item subscribed to for that key; this tells which of the two supplied a value.
+///
+/// The client does not subscribe to second-level items yet: until it does, every field of the
+/// updates it delivers is `FieldSource::FirstLevel`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub enum FieldSource {
+    /// The value comes from the first-level item.
+    FirstLevel,
+    /// The value comes from a second-level item.
+    SecondLevel {
+        /// The name of the second-level item, i.e. the value of the "key" field.
+        item: String,
+    },
 }
 
 impl ItemUpdate {
@@ -144,6 +172,31 @@ impl ItemUpdate {
         }
     }
 
+    /// Returns the value of the specified field as decoded by the codec registered for it on the
+    /// Subscription.
+    ///
+    /// # Parameters
+    /// - `field_name_or_pos` – The field name or the 1-based position of the field within the
+    ///   "Field List" or "Field Schema".
+    ///
+    /// # Returns
+    /// The decoded value, `None` if the field has no codec, is null, failed to decode or is not
+    /// of type `T`.
+    ///
+    /// # See also
+    /// `Subscription::set_field_codec()`
+    pub fn get_decoded_value<T: 'static>(&self, field_name_or_pos: &str) -> Option<&T> {
+        let value = match field_name_or_pos.parse::<usize>() {
+            Ok(pos) => self
+                .decoded_fields
+                .iter()
+                .find(|(name, _)| self.get_field_position(name) == pos)
+                .map(|(_, value)| value),
+            Err(_) => self.decoded_fields.get(field_name_or_pos),
+        };
+        value.and_then(|value| value.downcast_ref())
+    }
+
     /// Inquiry method that gets the difference between the new value and the previous one as a JSON Patch structure,
     /// provided that the Server has used the JSON Patch format to send this difference, as part of the "delta delivery"
     /// mechanism. This, in turn, requires that:
@@ -229,6 +282,153 @@ impl ItemUpdate {
         }
     }
 
+    /// Inquiry method that tells whether the value of a field comes from the first-level or from
+    /// a second-level item, for COMMAND Subscriptions with two-level behavior enabled.
+    ///
+    /// For Subscriptions without two-level behavior every field is `FieldSource::FirstLevel`, and
+    /// so is it for now for all Subscriptions, as second-level items are not subscribed to yet.
+    ///
+    /// # Parameters
+    /// - `field_name` – The name of the field.
+    ///
+    /// # Returns
+    /// The origin of the field value, or `None` if the field is not part of the update.
+    pub fn get_field_source(&self, field_name: &str) -> Option<FieldSource> {
+        match self.field_sources.get(field_name) {
+            Some(source) => Some(source.clone()),
+            None if self.fields.contains_key(field_name) => Some(FieldSource::FirstLevel),
+            None => None,
+        }
+    }
+
+    /// Serializes the update as a JSON object carrying the item name and position, the snapshot
+    /// flag, the current value of all fields and the changed fields.
+    ///
+    /// Fields without a value are serialized as `null`. This is handy for forwarding updates over
+    /// HTTP or Server-Sent Events, or for structured logging.
+    ///
+    /// # Returns
+    /// The JSON representation of the update, or an error if serialization fails.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(self)
+    }
+
+    /// Returns the current value for the specified field as an exact decimal number.
+    ///
+    /// Meant for price and quantity fields, where converting to `f64` would introduce rounding
+    /// errors. The value is parsed with `parse_decimal()`, which tolerates thousands separators
+    /// and both `.` and `,` as decimal separator.
+    ///
+    /// Only available with the `decimal` feature.
+    ///
+    /// # Parameters
+    /// - `field_name_or_pos` – The field name or the 1-based position of the field within the
+    ///   "Field List" or "Field Schema".
+    ///
+    /// # Returns
+    /// The parsed value, `None` if the field is null or not part of the update, or an error if the
+    /// value is not a number.
+    #[cfg(feature = "decimal")]
+    pub fn get_value_as_decimal(
+        &self,
+        field_name_or_pos: &str,
+    ) -> Result<Option<rust_decimal::Decimal>, crate::utils::LightstreamerError> {
+        self.get_value(field_name_or_pos)
+            .map(crate::utils::parse_decimal)
+            .transpose()
+    }
+
+    /// Returns the current value for the specified field as a timestamp.
+    ///
+    /// Epoch milliseconds, ISO 8601 strings and times of day are recognized; see
+    /// `parse_timestamp()` for the supported formats.
+    ///
+    /// Only available with the `timestamps` feature.
+    ///
+    /// # Parameters
+    /// - `field_name_or_pos` – The field name or the 1-based position of the field within the
+    ///   "Field List" or "Field Schema".
+    /// - `offset` – The offset used for values that don't carry one.
+    ///
+    /// # Returns
+    /// The parsed value, `None` if the field is null or not part of the update, or an error if the
+    /// value is not a timestamp.
+    #[cfg(feature = "timestamps")]
+    pub fn get_value_as_timestamp(
+        &self,
+        field_name_or_pos: &str,
+        offset: time::UtcOffset,
+    ) -> Result<Option<time::OffsetDateTime>, crate::utils::LightstreamerError> {
+        self.get_value(field_name_or_pos)
+            .map(|value| crate::utils::parse_timestamp(value, offset))
+            .transpose()
+    }
+
+    /// Returns the current value for the specified field as a time of day (`HH:MM:SS`, optionally
+    /// with a fractional part).
+    ///
+    /// Only available with the `timestamps` feature.
+    ///
+    /// # Parameters
+    /// - `field_name_or_pos` – The field name or the 1-based position of the field within the
+    ///   "Field List" or "Field Schema".
+    ///
+    /// # Returns
+    /// The parsed value, `None` if the field is null or not part of the update, or an error if the
+    /// value is not a time of day.
+    #[cfg(feature = "timestamps")]
+    pub fn get_value_as_time(
+        &self,
+        field_name_or_pos: &str,
+    ) -> Result<Option<time::Time>, crate::utils::LightstreamerError> {
+        self.get_value(field_name_or_pos)
+            .map(crate::utils::parse_time_of_day)
+            .transpose()
+    }
+
+    /// Returns a copy of the update restricted to the given fields.
+    ///
+    /// Fields not part of the update are ignored. The item name and position and the snapshot
+    /// flag are preserved.
+    ///
+    /// # Parameters
+    /// - `field_names` – The names of the fields to keep.
+    ///
+    /// # Returns
+    /// The projected update.
+    pub fn project<S: AsRef<str>>(&self, field_names: &[S]) -> ItemUpdate {
+        let keep = |name: &String| field_names.iter().any(|field| field.as_ref() == name);
+        ItemUpdate {
+            item_name: self.item_name.clone(),
+            item_pos: self.item_pos,
+            fields: self
+                .fields
+                .iter()
+                .filter(|(name, _)| keep(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            changed_fields: self
+                .changed_fields
+                .iter()
+                .filter(|(name, _)| keep(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+            is_snapshot: self.is_snapshot,
+            field_sources: self
+                .field_sources
+                .iter()
+                .filter(|(name, _)| keep(name))
+                .map(|(name, source)| (name.clone(), source.clone()))
+                .collect(),
+            decoded_fields: self
+                .decoded_fields
+                .iter()
+                .filter(|(name, _)| keep(name))
+                .map(|(name, value)| (name.clone(), value.clone()))
+                .collect(),
+        }
+    }
+
     /// Helper method to get the 1-based position of a field within the field list or field schema.
     ///
     /// # Parameters
@@ -306,6 +506,8 @@ mod tests {
             fields,
             changed_fields,
             is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
         }
     }
 
@@ -383,6 +585,102 @@ mod tests {
         assert_eq!(update.get_value_as_json_patch_if_available("field1"), None);
     }
 
+    #[test]
+    fn test_get_field_source() {
+        let mut update = create_test_item_update();
+        assert_eq!(
+            update.get_field_source("field1"),
+            Some(FieldSource::FirstLevel)
+        );
+        assert_eq!(update.get_field_source("non_existent"), None);
+
+        update.field_sources.insert(
+            "field2".to_string(),
+            FieldSource::SecondLevel {
+                item: "key1".to_string(),
+            },
+        );
+        assert_eq!(
+            update.get_field_source("field2"),
+            Some(FieldSource::SecondLevel {
+                item: "key1".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let update = create_test_item_update();
+        let json: serde_json::Value = serde_json::from_str(&update.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["item_name"], "test_item");
+        assert_eq!(json["item_pos"], 1);
+        assert_eq!(json["is_snapshot"], false);
+        assert_eq!(json["fields"]["field1"], "value1");
+        assert!(json["fields"]["field3"].is_null());
+        assert_eq!(json["changed_fields"]["field2"], "value2");
+        assert!(json["changed_fields"].get("field3").is_none());
+    }
+
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn test_get_value_as_decimal() {
+        use std::str::FromStr;
+
+        let mut item_update = create_test_item_update();
+        item_update
+            .fields
+            .insert("field1".to_string(), Some("1.234,50".to_string()));
+
+        assert_eq!(
+            item_update.get_value_as_decimal("field1").unwrap(),
+            Some(rust_decimal::Decimal::from_str("1234.5").unwrap())
+        );
+        assert_eq!(item_update.get_value_as_decimal("field3").unwrap(), None);
+        assert!(item_update.get_value_as_decimal("field2").is_err());
+    }
+
+    #[cfg(feature = "timestamps")]
+    #[test]
+    fn test_get_value_as_timestamp() {
+        let mut item_update = create_test_item_update();
+        item_update
+            .fields
+            .insert("field1".to_string(), Some("1718000000000".to_string()));
+        item_update
+            .fields
+            .insert("field2".to_string(), Some("08:13:20".to_string()));
+
+        assert_eq!(
+            item_update
+                .get_value_as_timestamp("field1", time::UtcOffset::UTC)
+                .unwrap()
+                .map(|timestamp| timestamp.unix_timestamp()),
+            Some(1_718_000_000)
+        );
+        assert_eq!(
+            item_update.get_value_as_time("field2").unwrap(),
+            Some(time::Time::from_hms(8, 13, 20).unwrap())
+        );
+        assert_eq!(item_update.get_value_as_time("field3").unwrap(), None);
+        assert!(item_update.get_value_as_time("field1").is_err());
+    }
+
+    #[test]
+    fn test_project() {
+        let item_update = create_test_item_update();
+        let projected = item_update.project(&["field2", "field3", "missing"]);
+
+        assert_eq!(projected.get_item_name(), Some("test_item"));
+        assert_eq!(projected.get_item_pos(), 1);
+        assert_eq!(projected.fields.len(), 2);
+        assert_eq!(projected.get_value("field2"), Some("value2"));
+        assert!(projected.fields.contains_key("field3"));
+        assert!(!projected.fields.contains_key("field1"));
+        assert_eq!(projected.get_changed_fields().len(), 1);
+        assert!(projected.is_value_changed("field2"));
+    }
+
     #[test]
     fn test_get_field_position() {
         let update = create_test_item_update();
@@ -0,0 +1,102 @@
+use crate::utils::{IllegalArgumentException, LightstreamerError};
+use std::ops::RangeInclusive;
+
+/// Builds an "Item List" made of a common prefix followed by each number in a range.
+///
+/// For instance `item_range("item", 1..=3)` returns `["item1", "item2", "item3"]`. The positions
+/// of the items within the list match the positions reported by the Server, so the names of the
+/// generated items are available in each `ItemUpdate` through `ItemUpdate::get_item_name()`.
+///
+/// # Parameters
+/// - `prefix`: the prefix shared by all item names.
+/// - `range`: the numbers to be appended to the prefix.
+///
+/// # Returns
+/// The list of item names, in range order.
+pub fn item_range(prefix: &str, range: RangeInclusive<usize>) -> Vec<String> {
+    range.map(|n| format!("{}{}", prefix, n)).collect()
+}
+
+/// Builds an "Item List" by expanding a name template for each number in a range.
+///
+/// The template must contain exactly one placeholder, either `{}` to insert the number as is, or
+/// `{:0N}` to pad it with zeros to a width of `N` digits. For instance
+/// `item_template("stock_{:03}.MI", 1..=2)` returns `["stock_001.MI", "stock_002.MI"]`.
+///
+/// # Parameters
+/// - `template`: the name template.
+/// - `range`: the numbers to be inserted in the template.
+///
+/// # Errors
+/// Returns an `IllegalArgumentException` if the template does not contain exactly one valid
+/// placeholder.
+///
+/// # Returns
+/// The list of item names, in range order.
+pub fn item_template(
+    template: &str,
+    range: RangeInclusive<usize>,
+) -> Result<Vec<String>, LightstreamerError> {
+    let start = template.find('{').ok_or_else(|| {
+        IllegalArgumentException::new("Item template must contain a '{}' placeholder.")
+    })?;
+    let end = template[start..]
+        .find('}')
+        .map(|offset| start + offset)
+        .ok_or_else(|| {
+            IllegalArgumentException::new("Unterminated placeholder in item template.")
+        })?;
+    let (prefix, suffix) = (&template[..start], &template[end + 1..]);
+    if suffix.contains(['{', '}']) {
+        return Err(IllegalArgumentException::new(
+            "Item template must contain exactly one placeholder.",
+        )
+        .into());
+    }
+    let width = match &template[start + 1..end] {
+        "" => 0,
+        spec => spec
+            .strip_prefix(":0")
+            .and_then(|width| width.parse::<usize>().ok())
+            .ok_or_else(|| {
+                IllegalArgumentException::new(&format!(
+                    "Unsupported placeholder '{{{}}}' in item template.",
+                    spec
+                ))
+            })?,
+    };
+    Ok(range
+        .map(|n| format!("{}{:0width$}{}", prefix, n, suffix, width = width))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_item_range() {
+        assert_eq!(item_range("item", 1..=3), vec!["item1", "item2", "item3"]);
+        assert_eq!(item_range("item", 5..=5), vec!["item5"]);
+    }
+
+    #[test]
+    fn test_item_template() {
+        assert_eq!(
+            item_template("stock_{}", 9..=10).unwrap(),
+            vec!["stock_9", "stock_10"]
+        );
+        assert_eq!(
+            item_template("stock_{:03}.MI", 1..=2).unwrap(),
+            vec!["stock_001.MI", "stock_002.MI"]
+        );
+    }
+
+    #[test]
+    fn test_item_template_with_invalid_placeholder() {
+        assert!(item_template("stock", 1..=2).is_err());
+        assert!(item_template("stock_{", 1..=2).is_err());
+        assert!(item_template("stock_{x}", 1..=2).is_err());
+        assert!(item_template("{}_{}", 1..=2).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+
+/// The counters of a `ConsumerLag`.
+#[derive(Debug, Default)]
+struct Counters {
+    depth: AtomicU64,
+    high_water_mark: AtomicU64,
+    /// The gauge the changes of the depth are also reported to, if any.
+    parent: OnceLock<ConsumerLag>,
+}
+
+/// Gauge of the updates received but not yet delivered to a consumer, to alert on a slow
+/// consumer before its queue grows enough to cause drops or exhaust the memory.
+///
+/// The gauge of a Subscription, obtained through `Subscription::get_consumer_lag()`, counts the
+/// updates waiting to be dispatched to its listeners, such as the ones held back by
+/// `Subscription::set_priority()`. The gauge of a `SinkListener` counts the updates queued for
+/// its sink, and can be reported to the gauge of the Subscription through
+/// `SinkListener::with_consumer_lag()`, so that a single gauge covers both.
+///
+/// The gauge is a shared handle, which can be kept after the Subscription has been handed over
+/// to the client.
+#[derive(Debug, Clone, Default)]
+pub struct ConsumerLag {
+    counters: Arc<Counters>,
+}
+
+impl ConsumerLag {
+    /// Returns the number of updates received but not yet delivered.
+    pub fn get_depth(&self) -> u64 {
+        self.counters.depth.load(Ordering::Relaxed)
+    }
+
+    /// Returns the highest depth reached since the gauge was created or its high-water mark
+    /// was last reset.
+    pub fn get_high_water_mark(&self) -> u64 {
+        self.counters.high_water_mark.load(Ordering::Relaxed)
+    }
+
+    /// Resets the high-water mark to the current depth, e.g. at the start of each alerting
+    /// period.
+    pub fn reset_high_water_mark(&self) {
+        self.counters
+            .high_water_mark
+            .store(self.get_depth(), Ordering::Relaxed);
+    }
+
+    /// Also reports the changes of the depth to the given gauge. Only the first parent is kept.
+    pub(crate) fn set_parent(&self, parent: ConsumerLag) {
+        let _ = self.counters.parent.set(parent);
+    }
+
+    /// Records updates received, waiting to be delivered.
+    pub(crate) fn record_enqueued(&self, updates: u64) {
+        let depth = self.counters.depth.fetch_add(updates, Ordering::Relaxed) + updates;
+        self.counters
+            .high_water_mark
+            .fetch_max(depth, Ordering::Relaxed);
+        if let Some(parent) = self.counters.parent.get() {
+            parent.record_enqueued(updates);
+        }
+    }
+
+    /// Records updates delivered, or dropped without being delivered.
+    pub(crate) fn record_delivered(&self, updates: u64) {
+        let _ = self
+            .counters
+            .depth
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |depth| {
+                Some(depth.saturating_sub(updates))
+            });
+        if let Some(parent) = self.counters.parent.get() {
+            parent.record_delivered(updates);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_and_high_water_mark() {
+        let subscription_lag = ConsumerLag::default();
+        let sink_lag = ConsumerLag::default();
+        sink_lag.set_parent(subscription_lag.clone());
+
+        sink_lag.record_enqueued(3);
+        subscription_lag.record_enqueued(1);
+        sink_lag.record_delivered(2);
+        assert_eq!(sink_lag.get_depth(), 1);
+        assert_eq!(sink_lag.get_high_water_mark(), 3);
+        assert_eq!(subscription_lag.get_depth(), 2);
+        assert_eq!(subscription_lag.get_high_water_mark(), 4);
+
+        subscription_lag.reset_high_water_mark();
+        assert_eq!(subscription_lag.get_high_water_mark(), 2);
+        sink_lag.record_delivered(5);
+        assert_eq!(sink_lag.get_depth(), 0);
+    }
+}
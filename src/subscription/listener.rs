@@ -1,4 +1,4 @@
-use crate::subscription::ItemUpdate;
+use crate::subscription::{CommandEvent, DataGap, FieldChange, ItemState, ItemUpdate};
 
 /// Interface to be implemented to listen to Subscription events comprehending notifications
 /// of subscription/unsubscription, updates, errors and others.
@@ -8,6 +8,15 @@ use crate::subscription::ItemUpdate;
 /// has changed. On the other hand, all the notifications for a single LightstreamerClient,
 /// including notifications to ClientListener, SubscriptionListener and ClientMessageListener
 /// will be dispatched by the same thread.
+///
+/// The notifications are dispatched one at a time, with no lock of the client held, so a listener
+/// may call back into the client through a `ClientHandle`, obtained with
+/// `LightstreamerClient::get_handle()`: it can subscribe, unsubscribe, even its own Subscription,
+/// and send messages, and the requests are processed as soon as the notification returns. A
+/// listener must not block or wait for the client, as the client cannot make progress meanwhile.
+///
+/// Listeners must be `Send`; handlers which must run on a given thread, such as the callbacks
+/// of a GUI, can receive the events through a `LocalDispatcher` instead.
 pub trait SubscriptionListener: Send {
     /// Event handler that is called by Lightstreamer each time a request to clear the snapshot
     /// pertaining to an item in the Subscription has been received from the Server.
@@ -131,6 +140,20 @@ pub trait SubscriptionListener: Send {
         unimplemented!("Implement on_end_of_snapshot method for SubscriptionListener.");
     }
 
+    /// Event handler that is called when updates of the Subscription may have been missed, such
+    /// as when the Server dropped updates of an item (OV), when notifications were lost across a
+    /// session recovery, when the session could not be recovered or when the Server closed it.
+    ///
+    /// By implementing this method it is possible to trigger a reconciliation of the data,
+    /// instead of silently missing updates.
+    ///
+    /// # Parameters
+    ///
+    /// - `gap`: the cause of the gap, the affected item, if known, and its best-effort bounds.
+    fn on_data_gap(&mut self, _gap: &DataGap) {
+        // Default implementation does nothing.
+    }
+
     /// Event handler that is called by Lightstreamer to notify that, due to internal resource
     /// limitations, Lightstreamer Server dropped one or more updates for an item in the Subscription.
     /// Such notifications are sent only if the items are delivered in an unfiltered mode; this occurs if the subscription mode is:
@@ -175,6 +198,65 @@ pub trait SubscriptionListener: Send {
         unimplemented!("Implement on_item_update method for SubscriptionListener.");
     }
 
+    /// Event handler that is called for each update of a COMMAND Subscription carrying an ADD,
+    /// UPDATE or DELETE command, right after `on_item_update()` is called with the raw update.
+    ///
+    /// The event spares the listener the interpretation of the "key" and "command" fields.
+    ///
+    /// # Parameters
+    ///
+    /// - `event`: the typed interpretation of the update.
+    ///
+    /// # See also
+    ///
+    /// - `CommandEvent::from_update()`
+    fn on_command_event(&self, _event: &CommandEvent) {
+        // Default implementation does nothing.
+    }
+
+    /// Event handler that is called by Lightstreamer each time an update changes the value of one
+    /// or more fields, right after `on_item_update()`, with the previous and new value of each
+    /// changed field.
+    ///
+    /// Previous values are taken from the cache of the Subscription: if value caching is
+    /// disabled, they are always `None`. Fields whose value is sent again unchanged are not
+    /// reported, and the event is not fired if no field actually changed.
+    ///
+    /// # Parameters
+    ///
+    /// - `update`: the update that caused the changes.
+    /// - `changes`: the changed fields, with their previous and new values.
+    ///
+    /// # See also
+    ///
+    /// - `Subscription::set_value_caching_enabled()`
+    fn on_field_changes(&self, _update: &ItemUpdate, _changes: &[FieldChange]) {
+        // Default implementation does nothing.
+    }
+
+    /// Event handler that is called each time the state of an item in the Subscription changes,
+    /// for instance when its snapshot starts, when it becomes live after the end of the snapshot,
+    /// or when the Server requests to clear it.
+    ///
+    /// # Parameters
+    ///
+    /// - `item_name`: name of the involved item. If the Subscription was initialized using an
+    ///   "Item Group" then a `None` value is supplied.
+    /// - `item_pos`: 1-based position of the item within the "Item List" or "Item Group".
+    /// - `state`: the new state of the item.
+    ///
+    /// # See also
+    ///
+    /// - `Subscription::get_item_state()`
+    fn on_item_state_change(
+        &mut self,
+        _item_name: Option<&str>,
+        _item_pos: usize,
+        _state: ItemState,
+    ) {
+        // Default implementation does nothing.
+    }
+
     /// Event handler that receives a notification when the `SubscriptionListener` instance is
     /// removed from a `Subscription` through `Subscription::remove_listener()`. This is the last
     /// event to be fired on the listener.
@@ -398,6 +480,8 @@ mod tests {
             fields,
             changed_fields,
             is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
         };
 
         listener.on_item_update(&item_update);
@@ -519,6 +603,8 @@ mod tests {
             fields,
             changed_fields,
             is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
         };
 
         listener.on_item_update(&item_update);
@@ -0,0 +1,260 @@
+use crate::subscription::{DataGap, ItemUpdate, SubscriptionListener};
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+
+/// An event of a Subscription, as passed by a `LocalDispatcher` to its handler.
+#[derive(Debug, Clone)]
+pub enum SubscriptionEvent {
+    /// The Subscription was accepted by the Server, see `SubscriptionListener::on_subscription()`.
+    Subscribed,
+    /// The Subscription was removed, see `SubscriptionListener::on_unsubscription()`.
+    Unsubscribed,
+    /// The Subscription was refused by the Server, with the code and message of the error.
+    SubscriptionError {
+        /// The error code sent by the Server.
+        code: i32,
+        /// The description of the error sent by the Server, if any.
+        message: Option<String>,
+    },
+    /// An update of an item was received.
+    ItemUpdate(ItemUpdate),
+    /// The snapshot of an item was fully received.
+    EndOfSnapshot {
+        /// The name of the item, if the Subscription was given an item list.
+        item_name: Option<String>,
+        /// The 1-based position of the item.
+        item_pos: usize,
+    },
+    /// The snapshot of an item has to be cleared.
+    ClearSnapshot {
+        /// The name of the item, if the Subscription was given an item list.
+        item_name: Option<String>,
+        /// The 1-based position of the item.
+        item_pos: usize,
+    },
+    /// The Server dropped updates of an item.
+    ItemLostUpdates {
+        /// The name of the item, if the Subscription was given an item list.
+        item_name: Option<String>,
+        /// The 1-based position of the item.
+        item_pos: usize,
+        /// The number of consecutive updates dropped.
+        lost_updates: u32,
+    },
+    /// Updates may have been missed, see `SubscriptionListener::on_data_gap()`.
+    DataGap(DataGap),
+    /// The maximum frequency granted by the Server changed.
+    RealMaxFrequency(Option<f64>),
+}
+
+/// Listener forwarding the events of a Subscription to a `LocalDispatcher`.
+#[derive(Debug)]
+pub struct LocalListener {
+    sender: UnboundedSender<SubscriptionEvent>,
+}
+
+impl LocalListener {
+    fn forward(&self, event: SubscriptionEvent) {
+        // The dispatcher is only gone if the application is no longer interested.
+        let _ = self.sender.send(event);
+    }
+}
+
+impl SubscriptionListener for LocalListener {
+    fn on_clear_snapshot(&mut self, item_name: Option<&str>, item_pos: usize) {
+        self.forward(SubscriptionEvent::ClearSnapshot {
+            item_name: item_name.map(str::to_string),
+            item_pos,
+        });
+    }
+
+    fn on_end_of_snapshot(&mut self, item_name: Option<&str>, item_pos: usize) {
+        self.forward(SubscriptionEvent::EndOfSnapshot {
+            item_name: item_name.map(str::to_string),
+            item_pos,
+        });
+    }
+
+    fn on_data_gap(&mut self, gap: &DataGap) {
+        self.forward(SubscriptionEvent::DataGap(gap.clone()));
+    }
+
+    fn on_item_lost_updates(
+        &mut self,
+        item_name: Option<&str>,
+        item_pos: usize,
+        lost_updates: u32,
+    ) {
+        self.forward(SubscriptionEvent::ItemLostUpdates {
+            item_name: item_name.map(str::to_string),
+            item_pos,
+            lost_updates,
+        });
+    }
+
+    fn on_item_update(&self, update: &ItemUpdate) {
+        self.forward(SubscriptionEvent::ItemUpdate(update.clone()));
+    }
+
+    fn on_real_max_frequency(&mut self, frequency: Option<f64>) {
+        self.forward(SubscriptionEvent::RealMaxFrequency(frequency));
+    }
+
+    fn on_subscription(&mut self) {
+        self.forward(SubscriptionEvent::Subscribed);
+    }
+
+    fn on_subscription_error(&mut self, code: i32, message: Option<&str>) {
+        self.forward(SubscriptionEvent::SubscriptionError {
+            code,
+            message: message.map(str::to_string),
+        });
+    }
+
+    fn on_unsubscription(&mut self) {
+        self.forward(SubscriptionEvent::Unsubscribed);
+    }
+}
+
+/// Dispatcher of the events of Subscriptions to a handler running on the thread of the
+/// application, which unlike a `SubscriptionListener` does not need to be `Send`.
+///
+/// Listeners are called by the task running the session, which may be spawned on any thread
+/// of a multi-threaded runtime, hence their `Send` bound. GUI applications, whose callbacks must
+/// run on the main thread and often capture `Rc` state, attach the `LocalListener` returned by
+/// `new()` to their Subscriptions instead, and drive the dispatcher from the main thread: either
+/// with `run()`, e.g. in a `tokio::task::LocalSet` of a `current_thread` runtime, or by calling
+/// `dispatch_pending()` from their event loop.
+///
+/// ```ignore
+/// let (dispatcher, listener) = LocalDispatcher::new();
+/// subscription.add_listener(Box::new(listener))?;
+/// let model = Rc::new(RefCell::new(Model::default()));
+/// local_set.spawn_local(dispatcher.run(move |event| model.borrow_mut().apply(event)));
+/// ```
+#[derive(Debug)]
+pub struct LocalDispatcher {
+    receiver: UnboundedReceiver<SubscriptionEvent>,
+    sender: UnboundedSender<SubscriptionEvent>,
+}
+
+impl LocalDispatcher {
+    /// Creates a dispatcher, with a first listener feeding it.
+    pub fn new() -> (LocalDispatcher, LocalListener) {
+        let (sender, receiver) = unbounded_channel();
+        let listener = LocalListener {
+            sender: sender.clone(),
+        };
+        (LocalDispatcher { receiver, sender }, listener)
+    }
+
+    /// Returns another listener feeding the dispatcher, e.g. for another Subscription.
+    pub fn listener(&self) -> LocalListener {
+        LocalListener {
+            sender: self.sender.clone(),
+        }
+    }
+
+    /// Passes the events received so far to the handler, without waiting for more.
+    ///
+    /// # Parameters
+    ///
+    /// * `handler`: The closure receiving the events, in order.
+    ///
+    /// # Returns
+    ///
+    /// The number of events dispatched.
+    pub fn dispatch_pending(&mut self, mut handler: impl FnMut(SubscriptionEvent)) -> usize {
+        let mut dispatched = 0;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(event) => {
+                    handler(event);
+                    dispatched += 1;
+                }
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return dispatched,
+            }
+        }
+    }
+
+    /// Passes the events to the handler as they are received, until all the listeners feeding
+    /// the dispatcher are dropped.
+    ///
+    /// # Parameters
+    ///
+    /// * `handler`: The closure receiving the events, in order.
+    pub async fn run(self, mut handler: impl FnMut(SubscriptionEvent)) {
+        let LocalDispatcher {
+            mut receiver,
+            sender,
+        } = self;
+        // Only the listeners keep the channel open.
+        drop(sender);
+        while let Some(event) = receiver.recv().await {
+            handler(event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    fn update(price: &str) -> ItemUpdate {
+        let mut fields = HashMap::new();
+        fields.insert("price".to_string(), Some(price.to_string()));
+        ItemUpdate {
+            item_name: Some("item1".to_string()),
+            item_pos: 1,
+            fields,
+            changed_fields: HashMap::new(),
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_dispatch_pending_drains_the_events() {
+        let (mut dispatcher, mut listener) = LocalDispatcher::new();
+        listener.on_subscription();
+        listener.on_item_update(&update("1"));
+        dispatcher.listener().on_unsubscription();
+
+        let mut events = Vec::new();
+        assert_eq!(dispatcher.dispatch_pending(|event| events.push(event)), 3);
+        assert!(matches!(events[0], SubscriptionEvent::Subscribed));
+        assert!(
+            matches!(&events[1], SubscriptionEvent::ItemUpdate(update) if update.get_value("price") == Some("1"))
+        );
+        assert!(matches!(events[2], SubscriptionEvent::Unsubscribed));
+        assert_eq!(dispatcher.dispatch_pending(|event| events.push(event)), 0);
+    }
+
+    #[tokio::test(flavor = "current_thread")]
+    async fn test_run_calls_a_local_handler() {
+        let (dispatcher, listener) = LocalDispatcher::new();
+        // Rc is not Send, as in the state of a GUI.
+        let prices = Rc::new(RefCell::new(Vec::new()));
+        let handled = prices.clone();
+        let local_set = tokio::task::LocalSet::new();
+        let task = local_set.spawn_local(dispatcher.run(move |event| {
+            if let SubscriptionEvent::ItemUpdate(update) = event {
+                let price = update.get_value("price").unwrap_or_default().to_string();
+                handled.borrow_mut().push(price);
+            }
+        }));
+        std::thread::spawn(move || {
+            listener.on_item_update(&update("1"));
+            listener.on_item_update(&update("2"));
+        })
+        .join()
+        .unwrap();
+
+        local_set.run_until(task).await.unwrap();
+        assert_eq!(*prices.borrow(), vec!["1", "2"]);
+    }
+}
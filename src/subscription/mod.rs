@@ -3,11 +3,47 @@
    Email: jb@taunais.com
    Date: 16/5/25
 ******************************************************************************/
+mod bandwidth;
+mod builder;
+mod cache;
+mod codec;
+mod command;
+mod diff;
+mod field_watch;
+mod gap;
 mod listener;
+mod local;
 mod model;
+mod projection;
+mod refresh;
+mod schema;
 
 mod item_update;
+mod items;
+mod lag;
+mod sink;
+mod stream;
 
-pub use item_update::ItemUpdate;
+pub use bandwidth::{BandwidthMeter, BandwidthUsage};
+pub use builder::SubscriptionBuilder;
+pub use cache::CacheMetrics;
+pub use codec::{Base64Codec, DecodedValue, FieldCodec};
+pub use command::CommandEvent;
+pub use diff::FieldChange;
+pub(crate) use diff::FieldList;
+pub use field_watch::FieldWatcher;
+pub use gap::{DataGap, DataGapCause};
+pub use item_update::{FieldSource, ItemUpdate};
+pub use items::{item_range, item_template};
+pub use lag::ConsumerLag;
 pub use listener::SubscriptionListener;
-pub use model::{Snapshot, Subscription, SubscriptionMode};
+pub use local::{LocalDispatcher, LocalListener, SubscriptionEvent};
+pub use model::{ItemState, Snapshot, Subscription, SubscriptionMode, SubscriptionState};
+pub use projection::ProjectedListener;
+pub use refresh::SnapshotRefresher;
+pub use schema::{FieldType, FieldValue, NamedSchema, SchemaRegistry};
+pub use sink::{CsvSink, JsonLinesSink, SinkListener, SinkTask, UpdateSink};
+pub use stream::{
+    ConflatePerItem, DebounceField, ItemStream, SampleLatest, SplitByItem, UpdateStream,
+    UpdateStreamExt,
+};
@@ -1,8 +1,29 @@
-use crate::subscription::SubscriptionListener;
+use crate::client::{RequestResponder, request_response};
+use crate::protocol::SubscriptionOk;
+use crate::subscription::bandwidth::BandwidthMeter;
+use crate::subscription::builder::SubscriptionBuilder;
+use crate::subscription::cache::{CacheMetrics, ValueCache};
+use crate::subscription::codec::ErasedCodec;
+use crate::subscription::command::{COMMAND_FIELD, KEY_FIELD};
+use crate::subscription::diff::FieldList;
+use crate::subscription::lag::ConsumerLag;
+use crate::subscription::refresh::SnapshotRefresher;
+use crate::subscription::schema::NamedSchema;
+use crate::subscription::{
+    CommandEvent, DataGap, FieldChange, FieldCodec, FieldWatcher, ItemUpdate, ProjectedListener,
+    SubscriptionListener, UpdateStream,
+};
+use crate::utils::{
+    IllegalArgumentException, IllegalStateException, LightstreamerError, ServerException,
+    SubscriptionStateError, TimeoutError, ValidationError, ValidationProblem,
+};
 use std::collections::HashMap;
-use std::error::Error;
 use std::fmt::{self, Debug, Formatter};
-use tokio::sync::mpsc::{Receiver, Sender, channel};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{Receiver, Sender, UnboundedReceiver, channel, unbounded_channel};
+use tokio::sync::watch;
 
 /// Enum representing the snapshot delivery preferences to be requested to Lightstreamer Server for the items in the Subscription.
 #[derive(Debug, Default)]
@@ -59,6 +80,97 @@ impl fmt::Display for SubscriptionMode {
     }
 }
 
+/// Enum representing the state of a single item within a Subscription.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ItemState {
+    /// The Subscription has not been confirmed by the Server yet. Default value.
+    #[default]
+    Pending,
+    /// The snapshot of the item is being received.
+    SnapshotInProgress,
+    /// The snapshot, if any, is complete and real-time updates are being received.
+    Live,
+    /// The Server requested to clear the snapshot of the item; the next update brings it back
+    /// to `Live`.
+    Cleared,
+}
+
+impl fmt::Display for ItemState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ItemState::Pending => write!(f, "PENDING"),
+            ItemState::SnapshotInProgress => write!(f, "SNAPSHOT-IN-PROGRESS"),
+            ItemState::Live => write!(f, "LIVE"),
+            ItemState::Cleared => write!(f, "CLEARED"),
+        }
+    }
+}
+
+/// Enum representing the stage of the lifecycle a Subscription is in.
+///
+/// A Subscription moves from `Inactive` to `Activating` when handed over to a client, to
+/// `Subscribed` when the Server confirms it, and back to `Inactive` when it is refused or
+/// removed from its client. Once unsubscribed, it is `Unsubscribed` for good.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubscriptionState {
+    /// The Subscription is not in any client and can be configured. Default value.
+    #[default]
+    Inactive,
+    /// The Subscription was handed over to a client and waits for the confirmation of the
+    /// Server.
+    Activating,
+    /// The Subscription was confirmed by the Server and receives updates.
+    Subscribed,
+    /// The Subscription was unsubscribed from its client: it receives no more updates and can
+    /// neither be listened to nor subscribed again.
+    Unsubscribed,
+}
+
+impl fmt::Display for SubscriptionState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SubscriptionState::Inactive => write!(f, "INACTIVE"),
+            SubscriptionState::Activating => write!(f, "ACTIVATING"),
+            SubscriptionState::Subscribed => write!(f, "SUBSCRIBED"),
+            SubscriptionState::Unsubscribed => write!(f, "UNSUBSCRIBED"),
+        }
+    }
+}
+
+/// Outcome of the last subscription request of a Subscription, as notified by the Server.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SubscriptionActivation {
+    /// No answer received from the Server yet.
+    Pending,
+    /// The Server confirmed the subscription through SUBOK or SUBCMD.
+    Subscribed(SubscriptionOk),
+    /// The subscription request failed.
+    Failed(SubscriptionFailure),
+}
+
+/// Why the last subscription request of a Subscription failed.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SubscriptionFailure {
+    /// The Server refused the subscription through REQERR.
+    Refused(ServerException),
+    /// The Server answered neither SUBOK nor REQERR in time.
+    TimedOut(TimeoutError),
+    /// The Subscription was unsubscribed before the outcome was known.
+    Unsubscribed,
+}
+
+impl From<SubscriptionFailure> for LightstreamerError {
+    fn from(failure: SubscriptionFailure) -> Self {
+        match failure {
+            SubscriptionFailure::Refused(error) => error.into(),
+            SubscriptionFailure::TimedOut(error) => error.into(),
+            SubscriptionFailure::Unsubscribed => {
+                SubscriptionStateError::new("await", SubscriptionState::Unsubscribed).into()
+            }
+        }
+    }
+}
+
 /// Struct representing a Subscription to be submitted to a Lightstreamer Server.
 /// It contains subscription details and the listeners needed to process the real-time data.
 pub struct Subscription {
@@ -80,6 +192,10 @@ pub struct Subscription {
     command_second_level_fields: Option<Vec<String>>,
     /// The "Field Schema" to be subscribed to through Lightstreamer Server for the second-level items in a COMMAND Subscription.
     command_second_level_field_schema: Option<String>,
+    /// The maximum update frequency to be requested for the second-level items in a COMMAND Subscription.
+    command_second_level_requested_max_frequency: Option<f64>,
+    /// The length of the queuing buffers to be requested for the second-level items in a COMMAND Subscription.
+    command_second_level_requested_buffer_size: Option<usize>,
     /// The length to be requested to Lightstreamer Server for the internal queuing buffers for the items in the Subscription.
     requested_buffer_size: Option<usize>,
     /// The maximum update frequency to be requested to Lightstreamer Server for all the items in the Subscription.
@@ -90,20 +206,55 @@ pub struct Subscription {
     selector: Option<String>,
     /// A list of SubscriptionListener instances that will receive events from this Subscription.
     listeners: Vec<Box<dyn SubscriptionListener>>,
-    /// A HashMap storing the latest values received for each item/field pair.
-    values: HashMap<(usize, usize), String>,
-    /// A HashMap storing the latest values received for each key/field pair in a COMMAND Subscription.
-    command_values: HashMap<String, HashMap<usize, String>>,
-    /// A flag indicating whether the Subscription is currently active or not.
-    is_active: bool,
-    /// A flag indicating whether the Subscription is currently subscribed to through the server or not.
-    is_subscribed: bool,
+    /// The codecs decoding the values of the fields, by field name.
+    field_codecs: HashMap<String, ErasedCodec>,
+    /// The shared schema the fields were taken from, if any, see `set_schema()`.
+    schema: Option<Arc<NamedSchema>>,
+    /// A cache storing the latest values received for each item, by field position.
+    values: ValueCache<usize, HashMap<usize, String>>,
+    /// A cache storing the latest values received for each item/key pair in a COMMAND Subscription, by field position.
+    command_values: ValueCache<String, HashMap<usize, String>>,
+    /// A flag indicating whether previous values are cached, see `set_value_caching_enabled()`.
+    value_caching_enabled: bool,
+    /// A flag indicating whether updates changing no value are dropped, see
+    /// `set_noop_update_suppression_enabled()`.
+    noop_update_suppression_enabled: bool,
+    /// The stage of the lifecycle the Subscription is in.
+    state: SubscriptionState,
     /// Client assigned subscription ID.
     pub(crate) id: usize,
     /// A channel sender to send the subscription ID to the Lightstreamer client.
     pub(crate) id_sender: Sender<usize>,
     /// A channel receiver to receive the subscription ID from the Lightstreamer client.
     pub(crate) id_receiver: Receiver<usize>,
+    /// Outcome of the last subscription request, observed by `await_subscribed()`.
+    activation: watch::Sender<SubscriptionActivation>,
+    /// Whoever awaits the answer of the Server to the next subscription request, see
+    /// `await_response()`.
+    request_responder: Option<RequestResponder>,
+    /// Number of items in the Subscription, as notified by the Server with SUBOK or SUBCMD.
+    item_count: usize,
+    /// State of each item, by 1-based item position. Items not present are `Pending`.
+    item_states: HashMap<usize, ItemState>,
+    /// Snapshot updates received so far, in order of arrival.
+    snapshot_updates: Vec<ItemUpdate>,
+    /// Complete snapshot of all items (or the error refusing the Subscription), observed by
+    /// `await_snapshot()`.
+    snapshot: watch::Sender<Option<Result<Vec<ItemUpdate>, SubscriptionFailure>>>,
+    /// Meter of the bytes received for the Subscription.
+    bandwidth: BandwidthMeter,
+    /// Gauge of the updates waiting to be dispatched to the listeners.
+    consumer_lag: ConsumerLag,
+    /// The handle requesting a fresh snapshot, see `refresh()`.
+    snapshot_refresher: SnapshotRefresher,
+    /// When the last subscription request was sent, if any.
+    requested_at: Option<Instant>,
+    /// The time from the last subscription request to the first update or end-of-snapshot.
+    time_to_first_update: Option<Duration>,
+    /// A flag indicating whether the slow start of the last request was already reported.
+    slow_start_reported: bool,
+    /// The priority of the dispatching of the updates, see `set_priority()`.
+    priority: i32,
 }
 
 impl Subscription {
@@ -120,36 +271,74 @@ impl Subscription {
         mode: SubscriptionMode,
         items: Option<Vec<String>>,
         fields: Option<Vec<String>>,
-    ) -> Result<Subscription, Box<dyn Error>> {
+    ) -> Result<Subscription, LightstreamerError> {
         if items.is_none() || fields.is_none() {
-            return Err("Items and fields must be provided".to_string().into());
+            return Err(IllegalArgumentException::new("Items and fields must be provided").into());
         }
 
+        let mut subscription = Subscription::unconfigured(mode);
+        subscription.items = items;
+        subscription.fields = fields;
+        Ok(subscription)
+    }
+
+    /// Creates a builder for a Subscription in the given mode, which collects all the problems of
+    /// the configuration instead of failing at the first one.
+    ///
+    /// # Parameters
+    /// - `mode`: The subscription mode for the items, required by Lightstreamer Server.
+    ///
+    /// # See also
+    /// `SubscriptionBuilder.build()`
+    pub fn builder(mode: SubscriptionMode) -> SubscriptionBuilder {
+        SubscriptionBuilder::new(mode)
+    }
+
+    /// Creates a Subscription with no items and no fields yet.
+    pub(crate) fn unconfigured(mode: SubscriptionMode) -> Subscription {
         let (id_sender, id_receiver) = channel(1);
 
-        Ok(Subscription {
+        Subscription {
             mode,
-            items,
+            items: None,
             item_group: None,
-            fields,
+            fields: None,
             field_schema: None,
             data_adapter: None,
             command_second_level_data_adapter: None,
             command_second_level_fields: None,
             command_second_level_field_schema: None,
+            command_second_level_requested_max_frequency: None,
+            command_second_level_requested_buffer_size: None,
             requested_buffer_size: None,
             requested_max_frequency: None,
             requested_snapshot: None,
             selector: None,
             listeners: Vec::new(),
-            values: HashMap::new(),
-            command_values: HashMap::new(),
-            is_active: false,
-            is_subscribed: false,
+            field_codecs: HashMap::new(),
+            schema: None,
+            values: ValueCache::new(None),
+            command_values: ValueCache::new(None),
+            value_caching_enabled: true,
+            noop_update_suppression_enabled: false,
+            state: SubscriptionState::Inactive,
             id: 0,
             id_sender,
             id_receiver,
-        })
+            activation: watch::Sender::new(SubscriptionActivation::Pending),
+            request_responder: None,
+            item_count: 0,
+            item_states: HashMap::new(),
+            snapshot_updates: Vec::new(),
+            snapshot: watch::Sender::new(None),
+            bandwidth: BandwidthMeter::default(),
+            consumer_lag: ConsumerLag::default(),
+            snapshot_refresher: SnapshotRefresher::default(),
+            requested_at: None,
+            time_to_first_update: None,
+            slow_start_reported: false,
+            priority: 0,
+        }
     }
 
     /// Adds a listener that will receive events from the Subscription instance.
@@ -157,15 +346,249 @@ impl Subscription {
     /// The same listener can be added to several different Subscription instances.
     ///
     /// # Lifecycle
-    /// A listener can be added at any time. A call to add a listener already present will be ignored.
+    /// A listener can be added at any time before the Subscription is unsubscribed. A call to add
+    /// a listener already present will be ignored.
     ///
     /// # Parameters
     /// - `listener`: An object that will receive the events as documented in the SubscriptionListener interface.
     ///
+    /// # Errors
+    /// - Returns a `SubscriptionStateError` if the Subscription was unsubscribed.
+    ///
     /// # See also
     /// `removeListener()`
-    pub fn add_listener(&mut self, listener: Box<dyn SubscriptionListener>) {
+    pub fn add_listener(
+        &mut self,
+        listener: Box<dyn SubscriptionListener>,
+    ) -> Result<(), LightstreamerError> {
+        if self.state == SubscriptionState::Unsubscribed {
+            return Err(SubscriptionStateError::new("listen to", self.state).into());
+        }
         self.listeners.push(listener);
+        Ok(())
+    }
+
+    /// Adds a listener that will receive events only for a subset of the fields of the Subscription.
+    ///
+    /// The listener receives an update only if at least one of the given fields changed, and the
+    /// update only carries the given fields. See `ProjectedListener` for details.
+    ///
+    /// # Lifecycle
+    /// A listener can be added at any time before the Subscription is unsubscribed.
+    ///
+    /// # Parameters
+    /// - `fields`: The names of the fields the listener is interested in.
+    /// - `listener`: An object that will receive the events as documented in the SubscriptionListener interface.
+    ///
+    /// # Errors
+    /// - Returns a `SubscriptionStateError` if the Subscription was unsubscribed.
+    pub fn add_listener_for_fields(
+        &mut self,
+        fields: Vec<String>,
+        listener: Box<dyn SubscriptionListener>,
+    ) -> Result<(), LightstreamerError> {
+        self.add_listener(Box::new(ProjectedListener::new(fields, listener)))
+    }
+
+    /// Registers a closure called each time the value of a single field of a single item
+    /// changes, e.g. for an alerting rule watching one cell of a big table.
+    ///
+    /// Updates that leave the value unchanged do not call the closure. Changes are detected
+    /// against the cached values, so with value caching disabled every update carrying the
+    /// field is reported. See `FieldWatcher` for details.
+    ///
+    /// # Lifecycle
+    /// A closure can be registered at any time before the Subscription is unsubscribed.
+    ///
+    /// # Parameters
+    /// - `item_pos`: The 1-based position of the item within the "Item List" or "Item Group".
+    /// - `field`: The name of the field.
+    /// - `callback`: The closure called with each change.
+    ///
+    /// # Errors
+    /// - Returns a `SubscriptionStateError` if the Subscription was unsubscribed.
+    ///
+    /// # See also
+    /// `watch_field_changes()`
+    pub fn watch_field<F>(
+        &mut self,
+        item_pos: usize,
+        field: &str,
+        callback: F,
+    ) -> Result<(), LightstreamerError>
+    where
+        F: Fn(&FieldChange) + Send + 'static,
+    {
+        self.add_listener(Box::new(FieldWatcher::new(item_pos, field, callback)))
+    }
+
+    /// Registers interest in the changes of a single field of a single item, returning a
+    /// dedicated channel receiving them.
+    ///
+    /// The channel is unbounded, as the session task must never wait for the application; it
+    /// can be closed at any time by dropping the receiver.
+    ///
+    /// # Lifecycle
+    /// Interest can be registered at any time before the Subscription is unsubscribed.
+    ///
+    /// # Parameters
+    /// - `item_pos`: The 1-based position of the item within the "Item List" or "Item Group".
+    /// - `field`: The name of the field.
+    ///
+    /// # Returns
+    /// The receiving end of the channel.
+    ///
+    /// # Errors
+    /// - Returns a `SubscriptionStateError` if the Subscription was unsubscribed.
+    ///
+    /// # See also
+    /// `watch_field()`
+    pub fn watch_field_changes(
+        &mut self,
+        item_pos: usize,
+        field: &str,
+    ) -> Result<UnboundedReceiver<FieldChange>, LightstreamerError> {
+        let (sender, receiver) = unbounded_channel();
+        self.add_listener(Box::new(FieldWatcher::with_sender(item_pos, field, sender)))?;
+        Ok(receiver)
+    }
+
+    /// Returns a stream of the updates of the Subscription, to be consumed with
+    /// `while let Some(update) = updates.next().await` instead of implementing a listener.
+    ///
+    /// The stream is fed by a listener dispatched with the other ones. It holds up to
+    /// `capacity` updates in order of arrival; beyond that, the updates are conflated per item
+    /// until the consumer catches up, so that a slow consumer never stalls the session nor
+    /// exhausts the memory. See `UpdateStream` for details.
+    ///
+    /// # Lifecycle
+    /// A stream can be obtained at any time before the Subscription is unsubscribed. It ends when
+    /// the Subscription is dropped.
+    ///
+    /// # Parameters
+    /// - `capacity`: The number of updates held in order of arrival, at least 1.
+    ///
+    /// # Errors
+    /// - Returns a `SubscriptionStateError` if the Subscription was unsubscribed.
+    pub fn updates(&mut self, capacity: usize) -> Result<UpdateStream, LightstreamerError> {
+        let (stream, listener) = UpdateStream::new(capacity);
+        self.add_listener(Box::new(listener))?;
+        Ok(stream)
+    }
+
+    /// Registers a codec decoding the values of a field before the updates reach the listeners,
+    /// which get the decoded values through `ItemUpdate::get_decoded_value()`. A codec already
+    /// registered for the field is replaced.
+    ///
+    /// The codec is called for each update carrying a non-null value for the field; values
+    /// failing to decode are logged and delivered without decoded value.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// Returns an error if the Subscription is currently "active".
+    ///
+    /// # Parameters
+    /// - `field`: The name of the field.
+    /// - `codec`: The codec of the field.
+    ///
+    /// # See also
+    /// `FieldCodec`
+    pub fn set_field_codec<C: FieldCodec + 'static>(
+        &mut self,
+        field: &str,
+        codec: C,
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the field codecs of")?;
+        self.field_codecs
+            .insert(field.to_string(), ErasedCodec::new(codec));
+        Ok(())
+    }
+
+    /// Removes the codec registered for a field, if any.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// Returns an error if the Subscription is currently "active".
+    ///
+    /// # Parameters
+    /// - `field`: The name of the field.
+    pub fn remove_field_codec(&mut self, field: &str) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the field codecs of")?;
+        self.field_codecs.remove(field);
+        Ok(())
+    }
+
+    /// Setter method that takes the fields of the Subscription from a shared schema, typically
+    /// from a `SchemaRegistry`: the field list, in the order of the schema, and the codecs of
+    /// the fields. Codecs registered before for other fields are kept.
+    ///
+    /// The values of the updates are then checked against the types declared by the schema,
+    /// logging the ones not matching, and can be read as typed values through
+    /// `NamedSchema::get_value()`.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// Returns an error if the Subscription is currently "active".
+    ///
+    /// # Parameters
+    /// - `schema`: The schema.
+    pub fn set_schema(&mut self, schema: Arc<NamedSchema>) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the schema of")?;
+        self.set_fields(schema.get_fields())?;
+        for (field, codec) in schema.get_codecs() {
+            self.field_codecs.insert(field.to_string(), codec.clone());
+        }
+        self.schema = Some(schema);
+        Ok(())
+    }
+
+    /// Inquiry method that returns the shared schema the fields of the Subscription were taken
+    /// from, if any.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # See also
+    /// `setSchema()`
+    pub fn get_schema(&self) -> Option<&Arc<NamedSchema>> {
+        self.schema.as_ref()
+    }
+
+    /// Decodes the fields of an update with the registered codecs and checks them against the
+    /// schema, if any, returning the fields failing with their error.
+    pub(crate) fn decode_fields(
+        &self,
+        update: &mut ItemUpdate,
+    ) -> Vec<(String, LightstreamerError)> {
+        let mut errors = Vec::new();
+        if let Some(schema) = &self.schema
+            && let Err(err) = schema.validate_update(update)
+        {
+            for problem in err.get_problems() {
+                errors.push((
+                    problem.get_property().to_string(),
+                    IllegalArgumentException::new(problem.get_message()).into(),
+                ));
+            }
+        }
+        for (field, codec) in &self.field_codecs {
+            let Some(Some(value)) = update.fields.get(field) else {
+                continue;
+            };
+            match codec.decode(value) {
+                Ok(decoded) => {
+                    update.decoded_fields.insert(field.clone(), decoded);
+                }
+                Err(err) => errors.push((field.clone(), err)),
+            }
+        }
+        errors
     }
 
     /// Removes a listener from the Subscription instance so that it will not receive events anymore.
@@ -223,10 +646,8 @@ impl Subscription {
     ///
     /// # Parameters
     /// - `group`: A String to be expanded into an item list by the Metadata Adapter.
-    pub fn set_item_group(&mut self, group: String) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active. This method can only be called while the Subscription instance is in its 'inactive' state.".to_string());
-        }
+    pub fn set_item_group(&mut self, group: String) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the items of")?;
         self.item_group = Some(group);
         Ok(())
     }
@@ -255,13 +676,11 @@ impl Subscription {
     ///
     /// # Parameters
     /// - `items`: An array of items to be subscribed to through the server.
-    pub fn set_items(&mut self, items: Vec<String>) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_items(&mut self, items: Vec<String>) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the items of")?;
         for item in &items {
             if item.contains(" ") || item.parse::<usize>().is_ok() || item.is_empty() {
-                return Err("Invalid item name".to_string());
+                return Err(IllegalArgumentException::new("Invalid item name").into());
             }
         }
         self.items = Some(items);
@@ -292,10 +711,8 @@ impl Subscription {
     ///
     /// # Parameters
     /// - `schema`: A String to be expanded into a field list by the Metadata Adapter.
-    pub fn set_field_schema(&mut self, schema: String) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_field_schema(&mut self, schema: String) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the fields of")?;
         self.field_schema = Some(schema);
         Ok(())
     }
@@ -324,13 +741,11 @@ impl Subscription {
     ///
     /// # Parameters
     /// - `fields`: An array of fields to be subscribed to through the server.
-    pub fn set_fields(&mut self, fields: Vec<String>) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_fields(&mut self, fields: Vec<String>) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the fields of")?;
         for field in &fields {
             if field.contains(" ") || field.is_empty() {
-                return Err("Invalid field name".to_string());
+                return Err(IllegalArgumentException::new("Invalid field name").into());
             }
         }
         self.fields = Some(fields);
@@ -368,10 +783,8 @@ impl Subscription {
     ///
     /// # See also
     /// `ConnectionDetails.setAdapterSet()`
-    pub fn set_data_adapter(&mut self, adapter: Option<String>) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_data_adapter(&mut self, adapter: Option<String>) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the data adapter of")?;
         self.data_adapter = adapter;
         Ok(())
     }
@@ -415,12 +828,10 @@ impl Subscription {
     pub fn set_command_second_level_data_adapter(
         &mut self,
         adapter: Option<String>,
-    ) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the second-level data adapter of")?;
         if self.mode != SubscriptionMode::Command {
-            return Err("Subscription mode is not Command".to_string());
+            return Err(IllegalStateException::new("Subscription mode is not Command").into());
         }
         self.command_second_level_data_adapter = adapter;
         Ok(())
@@ -471,12 +882,10 @@ impl Subscription {
     pub fn set_command_second_level_field_schema(
         &mut self,
         schema: Option<String>,
-    ) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the second-level fields of")?;
         if self.mode != SubscriptionMode::Command {
-            return Err("Subscription mode is not Command".to_string());
+            return Err(IllegalStateException::new("Subscription mode is not Command").into());
         }
         self.command_second_level_field_schema = schema;
         Ok(())
@@ -528,17 +937,15 @@ impl Subscription {
     pub fn set_command_second_level_fields(
         &mut self,
         fields: Option<Vec<String>>,
-    ) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the second-level fields of")?;
         if self.mode != SubscriptionMode::Command {
-            return Err("Subscription mode is not Command".to_string());
+            return Err(IllegalStateException::new("Subscription mode is not Command").into());
         }
         if let Some(ref fields) = fields {
             for field in fields {
                 if field.contains(" ") || field.is_empty() {
-                    return Err("Invalid field name".to_string());
+                    return Err(IllegalArgumentException::new("Invalid field name").into());
                 }
             }
         }
@@ -566,6 +973,108 @@ impl Subscription {
         self.command_second_level_fields.as_ref()
     }
 
+    /// Setter method that sets the maximum update frequency to be requested to Lightstreamer Server for the second-level items of a COMMAND Subscription. It can only be used on COMMAND Subscriptions.
+    ///
+    /// Second-level items are subscribed to in "MERGE" mode, so the same considerations as for `Subscription.setRequestedMaxFrequency()` apply, except that unfiltered dispatching cannot be requested.
+    ///
+    /// Note that the client does not subscribe to second-level items yet: the value is only stored, and returned by `getCommandSecondLevelRequestedMaxFrequency()`, but currently has no effect on the requests sent to the Server.
+    ///
+    /// # Default
+    /// `None`, meaning that the second-level items are subscribed to with the same maximum frequency setting as the first-level items.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// - Returns an error if the Subscription is currently "active".
+    /// - Returns an error if the Subscription mode is not "COMMAND".
+    /// - Returns an error if the specified value is not `None` nor a valid positive number.
+    ///
+    /// # Parameters
+    /// - `freq`: A decimal number, representing the maximum update frequency (expressed in updates per second) for each second-level item, or `None` to inherit the first-level setting.
+    ///
+    /// # See also
+    /// `Subscription.setCommandSecondLevelDataAdapter()`
+    pub fn set_command_second_level_requested_max_frequency(
+        &mut self,
+        freq: Option<f64>,
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the second-level frequency of")?;
+        if self.mode != SubscriptionMode::Command {
+            return Err(IllegalStateException::new("Subscription mode is not Command").into());
+        }
+        if let Some(freq) = freq
+            && (!freq.is_finite() || freq <= 0.0)
+        {
+            return Err(IllegalArgumentException::new("Invalid frequency").into());
+        }
+        self.command_second_level_requested_max_frequency = freq;
+        Ok(())
+    }
+
+    /// Inquiry method that can be used to read the max frequency, configured through `setCommandSecondLevelRequestedMaxFrequency()`, to be requested to the Server for the second-level items.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The max frequency to be requested for the second-level items, or `None` if the first-level setting is inherited or the Subscription mode is not COMMAND.
+    pub fn get_command_second_level_requested_max_frequency(&self) -> Option<&f64> {
+        if self.mode != SubscriptionMode::Command {
+            return None;
+        }
+        self.command_second_level_requested_max_frequency.as_ref()
+    }
+
+    /// Setter method that sets the length to be requested to Lightstreamer Server for the internal queuing buffers of the second-level items of a COMMAND Subscription. It can only be used on COMMAND Subscriptions.
+    ///
+    /// Note that the client does not subscribe to second-level items yet: the value is only stored, and returned by `getCommandSecondLevelRequestedBufferSize()`, but currently has no effect on the requests sent to the Server.
+    ///
+    /// # Default
+    /// `None`, meaning to lean on the Server default for "MERGE" subscriptions, that is a buffer size of 1.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// - Returns an error if the Subscription is currently "active".
+    /// - Returns an error if the Subscription mode is not "COMMAND".
+    /// - Returns an error if the specified value is zero.
+    ///
+    /// # Parameters
+    /// - `size`: An integer number, representing the length of the internal queuing buffers to be used in the Server for each second-level item, or `None` to stick to the Server default.
+    ///
+    /// # See also
+    /// `Subscription.setRequestedBufferSize()`
+    pub fn set_command_second_level_requested_buffer_size(
+        &mut self,
+        size: Option<usize>,
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the second-level buffer size of")?;
+        if self.mode != SubscriptionMode::Command {
+            return Err(IllegalStateException::new("Subscription mode is not Command").into());
+        }
+        if size == Some(0) {
+            return Err(IllegalArgumentException::new("Invalid buffer size").into());
+        }
+        self.command_second_level_requested_buffer_size = size;
+        Ok(())
+    }
+
+    /// Inquiry method that can be used to read the buffer size, configured through `setCommandSecondLevelRequestedBufferSize()`, to be requested to the Server for the second-level items.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The buffer size to be requested for the second-level items, or `None` if the Server default applies or the Subscription mode is not COMMAND.
+    pub fn get_command_second_level_requested_buffer_size(&self) -> Option<&usize> {
+        if self.mode != SubscriptionMode::Command {
+            return None;
+        }
+        self.command_second_level_requested_buffer_size.as_ref()
+    }
+
     /// Setter method that sets the length to be requested to Lightstreamer Server for the internal queuing buffers for the items in the Subscription. A Queuing buffer is used by the Server to accumulate a burst of updates for an item, so that they can all be sent to the client, despite of bandwidth or frequency limits. It can be used only when the subscription mode is MERGE or DISTINCT and unfiltered dispatching has not been requested. Note that the Server may pose an upper limit on the size of its internal buffers.
     ///
     /// # Default
@@ -583,10 +1092,11 @@ impl Subscription {
     ///
     /// # See also
     /// `Subscription.setRequestedMaxFrequency()`
-    pub fn set_requested_buffer_size(&mut self, size: Option<usize>) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_requested_buffer_size(
+        &mut self,
+        size: Option<usize>,
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the buffer size of")?;
         self.requested_buffer_size = size;
         Ok(())
     }
@@ -628,15 +1138,21 @@ impl Subscription {
     ///
     /// # Parameters
     /// - `freq`: A decimal number, representing the maximum update frequency (expressed in updates per second) for each item in the Subscription; for instance, with a setting of 0.5, for each single item, no more than one update every 2 seconds will be received. If the string "unlimited" is supplied, then no frequency limit is requested. It is also possible to supply the string "unfiltered", to ask for unfiltered dispatching, if it is allowed for the items, or a `None` value to stick to the Server default (which currently corresponds to "unlimited"). The check for the string constants is case insensitive.
-    pub fn set_requested_max_frequency(&mut self, freq: Option<f64>) -> Result<(), String> {
-        if self.is_active && self.requested_max_frequency.is_none() {
-            return Err("Subscription is active and current value is unfiltered".to_string());
+    pub fn set_requested_max_frequency(
+        &mut self,
+        freq: Option<f64>,
+    ) -> Result<(), LightstreamerError> {
+        if self.is_active() && self.requested_max_frequency.is_none() {
+            return Err(IllegalStateException::new(
+                "Subscription is active and current value is unfiltered",
+            )
+            .into());
         }
-        if self.is_active && freq.is_none() {
-            return Err("Cannot set unfiltered while active".to_string());
+        if self.is_active() && freq.is_none() {
+            return Err(IllegalStateException::new("Cannot set unfiltered while active").into());
         }
-        if self.is_active && freq.is_none() {
-            return Err("Cannot set None while active".to_string());
+        if self.is_active() && freq.is_none() {
+            return Err(IllegalStateException::new("Cannot set None while active").into());
         }
         self.requested_max_frequency = freq;
         Ok(())
@@ -674,20 +1190,22 @@ impl Subscription {
     ///
     /// # See also
     /// `ItemUpdate.isSnapshot()`
-    pub fn set_requested_snapshot(&mut self, snapshot: Option<Snapshot>) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_requested_snapshot(
+        &mut self,
+        snapshot: Option<Snapshot>,
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the requested snapshot of")?;
         match snapshot {
-            Some(Snapshot::None) => {
-                if self.mode == SubscriptionMode::Raw {
-                    return Err("Cannot request snapshot for Raw mode".to_string());
-                }
+            Some(Snapshot::None) if self.mode == SubscriptionMode::Raw => {
+                return Err(
+                    IllegalStateException::new("Cannot request snapshot for Raw mode").into(),
+                );
             }
-            Some(Snapshot::Number(_)) => {
-                if self.mode != SubscriptionMode::Distinct {
-                    return Err("Cannot specify snapshot length for non-Distinct mode".to_string());
-                }
+            Some(Snapshot::Number(_)) if self.mode != SubscriptionMode::Distinct => {
+                return Err(IllegalStateException::new(
+                    "Cannot specify snapshot length for non-Distinct mode",
+                )
+                .into());
             }
             _ => {}
         }
@@ -719,10 +1237,8 @@ impl Subscription {
     ///
     /// # Parameters
     /// - `selector`: The name of a selector, to be recognized by the Metadata Adapter, or `None` to unset the selector.
-    pub fn set_selector(&mut self, selector: Option<String>) -> Result<(), String> {
-        if self.is_active {
-            return Err("Subscription is active".to_string());
-        }
+    pub fn set_selector(&mut self, selector: Option<String>) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the selector of")?;
         self.selector = selector;
         Ok(())
     }
@@ -738,36 +1254,164 @@ impl Subscription {
         self.selector.as_ref()
     }
 
-    /// Returns the latest value received for the specified item/field pair.
-    ///
-    /// It is suggested to consume real-time data by implementing and adding a proper SubscriptionListener rather than probing this method. In case of COMMAND Subscriptions, the value returned by this method may be misleading, as in COMMAND mode all the keys received, being part of the same item, will overwrite each other; for COMMAND Subscriptions, use `Subscription.getCommandValue()` instead.
-    ///
-    /// Note that internal data is cleared when the Subscription is unsubscribed from.
-    ///
-    /// # Lifecycle
-    /// This method can be called at any time; if called to retrieve a value that has not been received yet, then it will return `None`.
-    ///
-    /// # Errors
-    /// Returns an error if an invalid item name or field name is specified or if the specified item position or field position is out of bounds.
-    ///
-    /// # Parameters
-    /// - `item_pos`: A String representing an item in the configured item list or a Number representing the 1-based position of the item in the specified item group. (In case an item list was specified, passing the item position is also possible).
-    /// - `field_pos`: A String representing a field in the configured field list or a Number representing the 1-based position of the field in the specified field schema. (In case a field list was specified, passing the field position is also possible).
-    ///
-    /// # Returns
-    /// The current value for the specified field of the specified item(possibly `None`), or `None` if no value has been received yet.
-    pub fn get_value(&self, item_pos: usize, field_pos: usize) -> Option<&String> {
-        self.values.get(&(item_pos, field_pos))
-    }
-
-    /// Returns the latest value received for the specified item/key/field combination in a COMMAND Subscription. This method can only be used if the Subscription mode is COMMAND. Subscriptions with two-level behavior are also supported, hence the specified field can be either a first-level or a second-level one.
-    ///
-    /// It is suggested to consume real-time data by implementing and adding a proper SubscriptionListener rather than probing this method.
-    ///
-    /// Note that internal data is cleared when the Subscription is unsubscribed from.
+    /// Checks the whole configuration of the Subscription, reporting all the problems that would
+    /// make the Server refuse it, such as a missing "Item List", a COMMAND "Field List" without
+    /// the "key" and "command" fields or a snapshot preference not allowed by the mode.
     ///
     /// # Lifecycle
-    /// This method can be called at any time; if called to retrieve a value that has not been received yet, then it will return `None`.
+    /// This method can be called at any time.
+    ///
+    /// # Errors
+    /// Returns a `ValidationError` carrying every problem found, each one referring to the
+    /// property involved.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let mut problems = Vec::new();
+        let mut problem = |property: &str, message: &str| {
+            problems.push(ValidationProblem::new(property, message));
+        };
+        match (&self.items, &self.item_group) {
+            (None, None) => problem("items", "no item list or item group is set"),
+            (Some(items), _) if items.is_empty() => problem("items", "the item list is empty"),
+            (Some(items), _) => {
+                for item in items {
+                    if item.contains(' ') || item.parse::<usize>().is_ok() || item.is_empty() {
+                        problem("items", &format!("invalid item name '{}'", item));
+                    }
+                }
+            }
+            (None, Some(group)) if group.is_empty() => {
+                problem("item_group", "the item group is empty")
+            }
+            _ => {}
+        }
+        match (&self.fields, &self.field_schema) {
+            (None, None) => problem("fields", "no field list or field schema is set"),
+            (Some(fields), _) if fields.is_empty() => problem("fields", "the field list is empty"),
+            (Some(fields), _) => {
+                for field in fields {
+                    if field.contains(' ') || field.is_empty() {
+                        problem("fields", &format!("invalid field name '{}'", field));
+                    }
+                }
+                if self.mode == SubscriptionMode::Command {
+                    for required in [KEY_FIELD, COMMAND_FIELD] {
+                        if !fields.iter().any(|field| field == required) {
+                            problem(
+                                "fields",
+                                &format!("COMMAND mode requires the '{}' field", required),
+                            );
+                        }
+                    }
+                }
+            }
+            (None, Some(schema)) if schema.is_empty() => {
+                problem("field_schema", "the field schema is empty")
+            }
+            _ => {}
+        }
+        for (property, frequency) in [
+            ("requested_max_frequency", self.requested_max_frequency),
+            (
+                "command_second_level_requested_max_frequency",
+                self.command_second_level_requested_max_frequency,
+            ),
+        ] {
+            if let Some(frequency) = frequency
+                && (!frequency.is_finite() || frequency <= 0.0)
+            {
+                problem(
+                    property,
+                    &format!(
+                        "{} is not a positive number of updates per second",
+                        frequency
+                    ),
+                );
+            }
+        }
+        if self.mode == SubscriptionMode::Raw && self.requested_max_frequency.is_some() {
+            problem(
+                "requested_max_frequency",
+                "a max frequency cannot be requested in RAW mode",
+            );
+        }
+        for (property, size) in [
+            ("requested_buffer_size", self.requested_buffer_size),
+            (
+                "command_second_level_requested_buffer_size",
+                self.command_second_level_requested_buffer_size,
+            ),
+        ] {
+            if size == Some(0) {
+                problem(property, "the buffer size must be positive");
+            }
+        }
+        match (&self.mode, &self.requested_snapshot) {
+            (SubscriptionMode::Raw, Some(Snapshot::Yes | Snapshot::Number(_))) => problem(
+                "requested_snapshot",
+                "a snapshot cannot be requested in RAW mode",
+            ),
+            (mode, Some(Snapshot::Number(_))) if *mode != SubscriptionMode::Distinct => problem(
+                "requested_snapshot",
+                "a snapshot length can only be requested in DISTINCT mode",
+            ),
+            _ => {}
+        }
+        if self.mode != SubscriptionMode::Command {
+            let second_level = [
+                (
+                    "command_second_level_fields",
+                    self.command_second_level_fields.is_some(),
+                ),
+                (
+                    "command_second_level_field_schema",
+                    self.command_second_level_field_schema.is_some(),
+                ),
+                (
+                    "command_second_level_data_adapter",
+                    self.command_second_level_data_adapter.is_some(),
+                ),
+            ];
+            for (property, set) in second_level {
+                if set {
+                    problem(property, "second-level settings require COMMAND mode");
+                }
+            }
+        }
+        ValidationError::check(problems)
+    }
+
+    /// Returns the latest value received for the specified item/field pair.
+    ///
+    /// It is suggested to consume real-time data by implementing and adding a proper SubscriptionListener rather than probing this method. In case of COMMAND Subscriptions, the value returned by this method may be misleading, as in COMMAND mode all the keys received, being part of the same item, will overwrite each other; for COMMAND Subscriptions, use `Subscription.getCommandValue()` instead.
+    ///
+    /// Note that internal data is cleared when the Subscription is unsubscribed from.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time; if called to retrieve a value that has not been received yet, then it will return `None`.
+    ///
+    /// # Errors
+    /// Returns an error if an invalid item name or field name is specified or if the specified item position or field position is out of bounds.
+    ///
+    /// # Parameters
+    /// - `item_pos`: A String representing an item in the configured item list or a Number representing the 1-based position of the item in the specified item group. (In case an item list was specified, passing the item position is also possible).
+    /// - `field_pos`: A String representing a field in the configured field list or a Number representing the 1-based position of the field in the specified field schema. (In case a field list was specified, passing the field position is also possible).
+    ///
+    /// # Returns
+    /// The current value for the specified field of the specified item(possibly `None`), or `None` if no value has been received yet.
+    pub fn get_value(&self, item_pos: usize, field_pos: usize) -> Option<&String> {
+        self.values
+            .get(&item_pos)
+            .and_then(|fields| fields.get(&field_pos))
+    }
+
+    /// Returns the latest value received for the specified item/key/field combination in a COMMAND Subscription. This method can only be used if the Subscription mode is COMMAND. Subscriptions with two-level behavior are also supported, hence the specified field can be either a first-level or a second-level one.
+    ///
+    /// It is suggested to consume real-time data by implementing and adding a proper SubscriptionListener rather than probing this method.
+    ///
+    /// Note that internal data is cleared when the Subscription is unsubscribed from.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time; if called to retrieve a value that has not been received yet, then it will return `None`.
     ///
     /// # Errors
     /// - Returns an error if an invalid item name or field name is specified or if the specified item position or field position is out of bounds.
@@ -792,6 +1436,353 @@ impl Subscription {
             .and_then(|fields| fields.get(&field_pos))
     }
 
+    /// Setter method that enables or disables the client-side caching of previous values for this
+    /// Subscription.
+    ///
+    /// With caching disabled, no previous values are kept: `getValue()` and `getCommandValue()`
+    /// always return `None`, and each `ItemUpdate` carries only the values sent with that update,
+    /// while the fields left unchanged by the Server are reported as `None` instead of being
+    /// filled in with their previous values. This saves memory and CPU for pure pass-through
+    /// pipelines that only consume `ItemUpdate::get_changed_fields()`.
+    ///
+    /// # Default
+    /// `true`.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// Returns an error if the Subscription is currently "active".
+    ///
+    /// # Parameters
+    /// - `enabled`: `true` to cache previous values, `false` to disable caching.
+    pub fn set_value_caching_enabled(&mut self, enabled: bool) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the value caching of")?;
+        self.value_caching_enabled = enabled;
+        if !enabled {
+            self.values.clear();
+            self.command_values.clear();
+        }
+        Ok(())
+    }
+
+    /// Inquiry method that checks whether previous values are cached for this Subscription.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// `true`/`false` if caching is enabled or not.
+    pub fn is_value_caching_enabled(&self) -> bool {
+        self.value_caching_enabled
+    }
+
+    /// Setter method that enables or disables the suppression of the no-op updates, i.e. the
+    /// real-time updates whose changed fields all carry the values already cached for the item,
+    /// which some Data Adapters emit. Idempotent consumers are then spared the churn of updates
+    /// that change nothing; snapshot updates are always delivered.
+    ///
+    /// The comparison relies on the cached values, so the suppression has no effect when value
+    /// caching is disabled through `setValueCachingEnabled()`.
+    ///
+    /// # Default
+    /// `false`.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Errors
+    /// Returns an error if the Subscription is currently "active".
+    ///
+    /// # Parameters
+    /// - `enabled`: `true` to drop the no-op updates, `false` to deliver them.
+    pub fn set_noop_update_suppression_enabled(
+        &mut self,
+        enabled: bool,
+    ) -> Result<(), LightstreamerError> {
+        self.check_configurable("change the no-op update suppression of")?;
+        self.noop_update_suppression_enabled = enabled;
+        Ok(())
+    }
+
+    /// Inquiry method that checks whether the no-op updates are dropped for this Subscription.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// `true`/`false` if the suppression is enabled or not.
+    pub fn is_noop_update_suppression_enabled(&self) -> bool {
+        self.noop_update_suppression_enabled
+    }
+
+    /// Setter method that bounds the number of rows kept in the last-value caches backing
+    /// `getValue()` and `getCommandValue()`. A row is an item, or an item/key pair for COMMAND
+    /// Subscriptions. When the limit is exceeded, the row updated least recently is evicted, so
+    /// that subscriptions with huge key spaces don't exhaust memory; values of evicted rows are
+    /// no longer returned by the inquiry methods.
+    ///
+    /// # Default
+    /// `None`, meaning that the caches are unbounded.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time. Lowering the capacity evicts rows immediately.
+    ///
+    /// # Errors
+    /// Returns an error if the specified capacity is zero.
+    ///
+    /// # Parameters
+    /// - `capacity`: The maximum number of rows to be cached, or `None` for no limit.
+    pub fn set_value_cache_capacity(
+        &mut self,
+        capacity: Option<usize>,
+    ) -> Result<(), LightstreamerError> {
+        if capacity == Some(0) {
+            return Err(IllegalArgumentException::new("Invalid cache capacity").into());
+        }
+        self.values.set_capacity(capacity);
+        self.command_values.set_capacity(capacity);
+        Ok(())
+    }
+
+    /// Inquiry method that can be used to read the capacity of the last-value caches, configured through `setValueCacheCapacity()`.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The maximum number of rows to be cached, or `None` if the caches are unbounded.
+    pub fn get_value_cache_capacity(&self) -> Option<usize> {
+        self.values.metrics().capacity
+    }
+
+    /// Setter method that sets the priority of the dispatching of the updates of this
+    /// Subscription to its listeners, relative to the other Subscriptions of the same client.
+    ///
+    /// When the client is under load, and several frames received from the Server are waiting to
+    /// be processed, the updates they carry are dispatched in order of priority, highest first,
+    /// so that critical tables (e.g. risk limits) are not delayed by bulky ones (e.g. reference
+    /// data). The updates of a Subscription are still dispatched in order, and lower priority
+    /// updates are never delayed beyond the batch of frames being processed, so they cannot
+    /// starve.
+    ///
+    /// # Default
+    /// 0, the same for all the Subscriptions; in that case, updates are dispatched as soon as
+    /// they are received.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription instance is in its "inactive" state.
+    ///
+    /// # Parameters
+    /// - `priority`: The priority of the Subscription; higher values are dispatched first.
+    pub fn set_priority(&mut self, priority: i32) {
+        self.priority = priority;
+    }
+
+    /// Inquiry method that can be used to read the priority of the dispatching of the updates,
+    /// configured through `setPriority()`.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The priority of the Subscription.
+    pub fn get_priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Inquiry method that returns the meter of the bytes received for this Subscription, such
+    /// as its updates and snapshot events, as a handle that can be kept after the Subscription
+    /// has been handed over to the client.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The bandwidth meter, shared with the Subscription.
+    pub fn get_bandwidth_meter(&self) -> BandwidthMeter {
+        self.bandwidth.clone()
+    }
+
+    /// Inquiry method that returns the gauge of the updates received for this Subscription but
+    /// not yet delivered to its listeners, with its high-water mark, as a handle that can be kept
+    /// after the Subscription has been handed over to the client.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The consumer lag gauge, shared with the Subscription.
+    ///
+    /// # See also
+    /// `SinkListener::with_consumer_lag()`
+    pub fn get_consumer_lag(&self) -> ConsumerLag {
+        self.consumer_lag.clone()
+    }
+
+    /// Requests a fresh snapshot for this Subscription, to recover from a suspected corruption
+    /// of the state kept by the application, without touching the other Subscriptions.
+    ///
+    /// The client resubscribes the Subscription under a new ID, clearing its cached values and
+    /// item states, and the Server sends the snapshot again, as requested through
+    /// `set_requested_snapshot()`; the listeners receive `on_subscription()` again, followed by
+    /// the snapshot.
+    ///
+    /// # Lifecycle
+    /// This method can only be called while the Subscription is active. As the Subscription is
+    /// owned by the client once subscribed, use the handle returned by
+    /// `get_snapshot_refresher()` to request a refresh afterwards.
+    ///
+    /// # Errors
+    /// Returns an error if the Subscription is not active in a client.
+    ///
+    /// # See also
+    /// `SnapshotRefresher.refresh()`
+    pub fn refresh(&self) -> Result<(), LightstreamerError> {
+        self.snapshot_refresher.refresh()
+    }
+
+    /// Inquiry method that returns the handle requesting a fresh snapshot for this Subscription,
+    /// as `refresh()` does, which can be kept after the Subscription has been handed over to the
+    /// client.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The snapshot refresher, shared with the Subscription.
+    pub fn get_snapshot_refresher(&self) -> SnapshotRefresher {
+        self.snapshot_refresher.clone()
+    }
+
+    /// Inquiry method that returns the time from the last subscription request to the first
+    /// update or end-of-snapshot of any item, which tells how long the Data Adapter took to
+    /// start feeding the Subscription.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time; it is measured again on each subscription request,
+    /// e.g. after a reconnection.
+    ///
+    /// # Returns
+    /// The time to the first update, or `None` if no update nor end-of-snapshot was received
+    /// since the last request.
+    ///
+    /// # See also
+    /// `ConnectionOptions.setSlowStartThreshold()`
+    pub fn get_time_to_first_update(&self) -> Option<Duration> {
+        self.time_to_first_update
+    }
+
+    /// Returns when the Subscription exceeds the given slow start threshold, if it is still
+    /// waiting for its first data and was not reported yet.
+    pub(crate) fn get_slow_start_deadline(&self, threshold: Duration) -> Option<Instant> {
+        self.requested_at
+            .filter(|_| self.is_active() && self.time_to_first_update.is_none())
+            .filter(|_| !self.slow_start_reported)
+            .map(|requested_at| requested_at + threshold)
+    }
+
+    /// Marks the slow start of the last request as reported.
+    pub(crate) fn on_slow_start(&mut self) {
+        self.slow_start_reported = true;
+    }
+
+    /// Records the time to the first data received since the last request, if not done yet.
+    fn record_first_data(&mut self) {
+        if self.time_to_first_update.is_none() {
+            self.time_to_first_update =
+                self.requested_at.map(|requested_at| requested_at.elapsed());
+        }
+    }
+
+    /// Records a notification of the given length received for this Subscription.
+    pub(crate) fn record_received_bytes(&self, bytes: usize) {
+        self.bandwidth.record(bytes);
+    }
+
+    /// Inquiry method that returns statistics about the last-value caches: the number of rows
+    /// currently cached, the configured capacity and the number of rows evicted so far.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    ///
+    /// # Returns
+    /// The cache statistics.
+    pub fn get_value_cache_metrics(&self) -> CacheMetrics {
+        let values = self.values.metrics();
+        let command_values = self.command_values.metrics();
+        CacheMetrics {
+            entries: values.entries + command_values.entries,
+            capacity: values.capacity,
+            evictions: values.evictions + command_values.evictions,
+        }
+    }
+
+    /// Returns the 1-based position of a field within the "Field List".
+    fn get_field_pos(&self, field_name: &str) -> Option<usize> {
+        self.fields
+            .as_ref()
+            .and_then(|fields| fields.iter().position(|field| field == field_name))
+            .map(|pos| pos + 1)
+    }
+
+    /// Compares the values carried by an update with the cached ones, returning the fields whose
+    /// value actually changed. Must be called before the cache is updated.
+    fn get_field_changes(&self, update: &ItemUpdate) -> FieldList<FieldChange> {
+        let cached = if self.mode != SubscriptionMode::Command {
+            self.values.get(&update.get_item_pos())
+        } else {
+            update.get_value("key").and_then(|key| {
+                self.command_values
+                    .get(&format!("{}_{}", update.get_item_pos(), key))
+            })
+        };
+        let mut changes: FieldList<FieldChange> = update
+            .changed_fields
+            .iter()
+            .map(|(field, new_value)| FieldChange {
+                field: field.clone(),
+                previous_value: cached
+                    .zip(self.get_field_pos(field))
+                    .and_then(|(values, field_pos)| values.get(&field_pos).cloned()),
+                new_value: new_value.clone(),
+            })
+            .filter(|change| change.previous_value.as_ref() != Some(&change.new_value))
+            .collect();
+        changes.sort_by_key(|change| self.get_field_pos(&change.field));
+        changes
+    }
+
+    /// Stores the values carried by an update in the last-value caches.
+    fn update_value_cache(&mut self, update: &ItemUpdate) {
+        if !self.value_caching_enabled {
+            return;
+        }
+        let item_pos = update.get_item_pos();
+        let changed: FieldList<(usize, String)> = update
+            .changed_fields
+            .iter()
+            .filter_map(|(name, value)| Some((self.get_field_pos(name)?, value.clone())))
+            .collect();
+        if self.mode != SubscriptionMode::Command {
+            self.values.get_or_insert_default(item_pos).extend(changed);
+            return;
+        }
+        let Some(key) = update.get_value("key") else {
+            return;
+        };
+        let cache_key = format!("{}_{}", item_pos, key);
+        match update.get_value("command") {
+            Some(command) if command.eq_ignore_ascii_case("DELETE") => {
+                self.command_values.remove(&cache_key);
+            }
+            _ => self
+                .command_values
+                .get_or_insert_default(cache_key)
+                .extend(changed),
+        }
+    }
+
     /// Inquiry method that checks if the Subscription is currently "active" or not. Most of the Subscription properties cannot be modified if a Subscription is "active".
     ///
     /// The status of a Subscription is changed to "active" through the `LightstreamerClient.subscribe()` method and back to "inactive" through the `LightstreamerClient.unsubscribe()` one.
@@ -808,7 +1799,10 @@ impl Subscription {
     /// # See also
     /// `LightstreamerClient.unsubscribe()`
     pub fn is_active(&self) -> bool {
-        self.is_active
+        matches!(
+            self.state,
+            SubscriptionState::Activating | SubscriptionState::Subscribed
+        )
     }
 
     /// Inquiry method that checks if the Subscription is currently subscribed to through the server or not.
@@ -821,7 +1815,382 @@ impl Subscription {
     /// # Returns
     /// `true`/`false` if the Subscription is subscribed to through the server or not.
     pub fn is_subscribed(&self) -> bool {
-        self.is_subscribed
+        self.state == SubscriptionState::Subscribed
+    }
+
+    /// Inquiry method that gets the stage of the lifecycle the Subscription is in, which
+    /// combines `is_active()` and `is_subscribed()`.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time.
+    pub fn get_state(&self) -> SubscriptionState {
+        self.state
+    }
+
+    /// Checks that the Subscription can be handed over to a client, that is it is not active
+    /// in another one already.
+    ///
+    /// # Errors
+    /// Returns a `SubscriptionStateError` if the Subscription is active.
+    pub(crate) fn check_subscribable(&self) -> Result<(), SubscriptionStateError> {
+        self.check_configurable("subscribe")
+    }
+
+    /// Checks that the Subscription can be configured, that is it is inactive.
+    ///
+    /// # Errors
+    /// Returns a `SubscriptionStateError` describing the refused `operation` otherwise.
+    fn check_configurable(&self, operation: &str) -> Result<(), SubscriptionStateError> {
+        match self.state {
+            SubscriptionState::Inactive => Ok(()),
+            state => Err(SubscriptionStateError::new(operation, state)),
+        }
+    }
+
+    /// Returns a future that resolves when the Server answers the next subscription request:
+    /// with `Ok` on REQOK (or on SUBOK or SUBCMD, if they come first), or with a
+    /// `ServerException` carrying the code and message of the REQERR notification.
+    ///
+    /// Unlike `await_subscribed()`, the future only tells that the request was accepted. Only
+    /// the future obtained last is resolved, and the requests sent on the initiative of the
+    /// client, such as resubscriptions, resolve none.
+    ///
+    /// # Errors
+    /// - Returns an `IllegalStateException` if the request is abandoned before any answer, as
+    ///   when the session ends first.
+    pub fn await_response(
+        &mut self,
+    ) -> impl Future<Output = Result<(), LightstreamerError>> + Send + 'static {
+        let (responder, response) = request_response();
+        self.request_responder = Some(responder);
+        response
+    }
+
+    /// Takes whoever awaits the answer to the subscription request being sent.
+    pub(crate) fn take_request_responder(&mut self) -> Option<RequestResponder> {
+        self.request_responder.take()
+    }
+
+    /// Returns a future that resolves once the Server has confirmed the Subscription, that is
+    /// when the SUBOK (or SUBCMD, for COMMAND mode) notification is received, yielding the
+    /// confirmation with the number of items and fields of the Subscription.
+    ///
+    /// The future does not borrow the Subscription, so it can be obtained before handing the
+    /// Subscription over to `LightstreamerClient::subscribe()` and awaited afterwards, allowing
+    /// startup sequences to wait until data is guaranteed to flow.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time. If the Subscription is already subscribed to, the
+    /// returned future resolves immediately.
+    ///
+    /// # Errors
+    /// - Returns a `ServerException` carrying the code and message of the REQERR notification if
+    ///   the Server refuses the subscription.
+    /// - Returns a `TimeoutError` if the Server does not answer within the subscribe timeout.
+    /// - Returns an `IllegalStateException` if the Subscription is discarded (for instance because
+    ///   the client is dropped) before any answer from the Server.
+    pub fn await_subscribed(
+        &self,
+    ) -> impl Future<Output = Result<SubscriptionOk, LightstreamerError>> + Send + 'static + use<>
+    {
+        let mut activation = self.activation.subscribe();
+        async move {
+            let outcome: SubscriptionActivation = activation
+                .wait_for(|outcome| *outcome != SubscriptionActivation::Pending)
+                .await
+                .map_err(|_| {
+                    IllegalStateException::new(
+                        "Subscription was discarded before being confirmed by the server.",
+                    )
+                })?
+                .clone();
+            match outcome {
+                SubscriptionActivation::Subscribed(confirmation) => Ok(confirmation),
+                SubscriptionActivation::Failed(error) => Err(error.into()),
+                SubscriptionActivation::Pending => unreachable!("waited for an answer"),
+            }
+        }
+    }
+
+    /// Returns a future that resolves once the snapshot of every item in the Subscription has
+    /// been received, yielding the snapshot updates in order of arrival.
+    ///
+    /// The snapshot of an item is complete:
+    /// - in DISTINCT and COMMAND mode, when the end-of-snapshot (EOS) notification is received;
+    /// - in MERGE mode, with the first update, which carries the current value of all fields.
+    ///
+    /// If no snapshot was requested through `set_requested_snapshot()`, or the mode is RAW, the
+    /// future resolves with no updates as soon as the Subscription is confirmed by the Server.
+    ///
+    /// As with `await_subscribed()`, the future does not borrow the Subscription.
+    ///
+    /// # Errors
+    /// - Returns a `ServerException` if the Server refuses the subscription.
+    /// - Returns an `IllegalStateException` if the Subscription is discarded before its snapshot
+    ///   is complete.
+    pub fn await_snapshot(
+        &self,
+    ) -> impl Future<Output = Result<Vec<ItemUpdate>, LightstreamerError>> + Send + 'static {
+        let mut snapshot = self.snapshot.subscribe();
+        async move {
+            let outcome = snapshot
+                .wait_for(|outcome| outcome.is_some())
+                .await
+                .map_err(|_| {
+                    IllegalStateException::new(
+                        "Subscription was discarded before its snapshot was complete.",
+                    )
+                })?
+                .clone();
+            match outcome {
+                Some(Ok(updates)) => Ok(updates),
+                Some(Err(err)) => Err(err.into()),
+                None => unreachable!(),
+            }
+        }
+    }
+
+    /// Inquiry method that returns the name of the item at the given position.
+    ///
+    /// Positions reported by the Server refer to the "Item List", so this is how the positional
+    /// indexes of the updates are mapped back to item names, including names generated with
+    /// `item_range()` or `item_template()`.
+    ///
+    /// # Parameters
+    /// - `item_pos`: 1-based position of the item within the "Item List".
+    ///
+    /// # Returns
+    /// The name of the item, or `None` if the position is out of range or the Subscription was
+    /// initialized using an "Item Group".
+    pub fn get_item_name(&self, item_pos: usize) -> Option<&str> {
+        if self.item_group.is_some() {
+            return None;
+        }
+        self.items
+            .as_ref()
+            .and_then(|items| items.get(item_pos.checked_sub(1)?))
+            .map(|item| item.as_str())
+    }
+
+    /// Inquiry method that returns the state of an item within the Subscription.
+    ///
+    /// Changes of state are notified through `SubscriptionListener::on_item_state_change()`.
+    ///
+    /// # Lifecycle
+    /// This method can be called at any time. Before the Subscription is confirmed by the Server
+    /// all items are `ItemState::Pending`.
+    ///
+    /// # Parameters
+    /// - `item_pos`: 1-based position of the item within the "Item List" or "Item Group".
+    ///
+    /// # Returns
+    /// The current state of the item.
+    pub fn get_item_state(&self, item_pos: usize) -> ItemState {
+        self.item_states.get(&item_pos).copied().unwrap_or_default()
+    }
+
+    /// Inquiry method that checks whether the snapshot of an item has been fully received.
+    ///
+    /// # Parameters
+    /// - `item_pos`: 1-based position of the item within the "Item List" or "Item Group".
+    pub(crate) fn is_snapshot_complete(&self, item_pos: usize) -> bool {
+        matches!(
+            self.get_item_state(item_pos),
+            ItemState::Live | ItemState::Cleared
+        )
+    }
+
+    /// Checks whether snapshot information was requested for the items.
+    fn is_snapshot_requested(&self) -> bool {
+        self.mode != SubscriptionMode::Raw
+            && matches!(
+                self.requested_snapshot,
+                Some(Snapshot::Yes) | Some(Snapshot::Number(_))
+            )
+    }
+
+    /// Changes the state of an item, notifying the listeners if it differs from the current one.
+    fn set_item_state(&mut self, item_pos: usize, state: ItemState) {
+        if self.get_item_state(item_pos) == state {
+            return;
+        }
+        self.item_states.insert(item_pos, state);
+        let item_name = self.get_item_name(item_pos).map(|name| name.to_string());
+        for listener in &mut self.listeners {
+            listener.on_item_state_change(item_name.as_deref(), item_pos, state);
+        }
+    }
+
+    /// Publishes the snapshot to `await_snapshot()` once all items have completed it.
+    fn check_snapshot_complete(&mut self) {
+        if self.is_subscribed()
+            && self.snapshot.borrow().is_none()
+            && (1..=self.item_count).all(|item_pos| self.is_snapshot_complete(item_pos))
+        {
+            self.snapshot
+                .send_replace(Some(Ok(std::mem::take(&mut self.snapshot_updates))));
+        }
+    }
+
+    /// Marks the Subscription as active, upon sending the subscription request to the Server.
+    pub(crate) fn on_subscription_request(&mut self) {
+        self.state = SubscriptionState::Activating;
+        self.item_count = 0;
+        // The snapshot requested now is a fresh one.
+        self.snapshot_refresher.take_request();
+        self.requested_at = Some(Instant::now());
+        self.time_to_first_update = None;
+        self.slow_start_reported = false;
+        let item_positions: Vec<usize> = self.item_states.keys().copied().collect();
+        for item_pos in item_positions {
+            self.set_item_state(item_pos, ItemState::Pending);
+        }
+        self.item_states.clear();
+        self.snapshot_updates.clear();
+        self.values.clear();
+        self.command_values.clear();
+        self.activation
+            .send_replace(SubscriptionActivation::Pending);
+        self.snapshot.send_replace(None);
+    }
+
+    /// Marks the Subscription as removed from its client, to be given to another one, keeping
+    /// its configuration, listeners and cached values.
+    pub(crate) fn on_detach(&mut self) {
+        self.id = 0;
+        // The ID of the last session must not be taken for the one of the next.
+        (self.id_sender, self.id_receiver) = channel(1);
+        self.state = SubscriptionState::Inactive;
+        self.snapshot_refresher.detach();
+    }
+
+    /// Handles the SUBOK or SUBCMD notification confirming the Subscription.
+    ///
+    /// # Parameters
+    /// - `confirmation`: the numbers of items and fields of the Subscription, as notified by the
+    ///   Server.
+    pub(crate) fn on_subscription(&mut self, confirmation: SubscriptionOk) {
+        let item_count = confirmation.items;
+        self.state = SubscriptionState::Subscribed;
+        self.item_count = item_count;
+        self.activation
+            .send_replace(SubscriptionActivation::Subscribed(confirmation));
+        for listener in &mut self.listeners {
+            listener.on_subscription();
+        }
+        let state = if self.is_snapshot_requested() {
+            ItemState::SnapshotInProgress
+        } else {
+            ItemState::Live
+        };
+        for item_pos in 1..=item_count {
+            self.set_item_state(item_pos, state);
+        }
+        self.check_snapshot_complete();
+    }
+
+    /// Handles the REQERR notification refusing the subscription request.
+    pub(crate) fn on_subscription_error(&mut self, error: ServerException) {
+        self.on_subscription_failure(SubscriptionFailure::Refused(error));
+    }
+
+    /// Handles the expiry of the time allowed to the Server to answer the subscription request.
+    pub(crate) fn on_subscription_timeout(&mut self, error: TimeoutError) {
+        self.on_subscription_failure(SubscriptionFailure::TimedOut(error));
+    }
+
+    fn on_subscription_failure(&mut self, failure: SubscriptionFailure) {
+        self.state = SubscriptionState::Inactive;
+        self.snapshot_refresher.detach();
+        self.activation
+            .send_replace(SubscriptionActivation::Failed(failure.clone()));
+        self.snapshot.send_replace(Some(Err(failure)));
+    }
+
+    /// Handles the removal of the Subscription from its client upon unsubscription, failing
+    /// whoever still awaits its confirmation or its snapshot.
+    pub(crate) fn on_unsubscription(&mut self) {
+        self.state = SubscriptionState::Unsubscribed;
+        self.snapshot_refresher.detach();
+        self.activation.send_if_modified(|activation| {
+            let pending = *activation == SubscriptionActivation::Pending;
+            if pending {
+                *activation = SubscriptionActivation::Failed(SubscriptionFailure::Unsubscribed);
+            }
+            pending
+        });
+        self.snapshot.send_if_modified(|snapshot| {
+            let pending = snapshot.is_none();
+            if pending {
+                *snapshot = Some(Err(SubscriptionFailure::Unsubscribed));
+            }
+            pending
+        });
+        for listener in &mut self.listeners {
+            listener.on_unsubscription();
+        }
+    }
+
+    /// Handles an update for an item, dispatching it to the listeners, together with its typed
+    /// interpretation for COMMAND Subscriptions.
+    pub(crate) fn on_item_update(&mut self, update: &ItemUpdate) {
+        self.record_first_data();
+        let item_pos = update.get_item_pos();
+        let changes = if self.listeners.is_empty() {
+            FieldList::new()
+        } else {
+            self.get_field_changes(update)
+        };
+        self.update_value_cache(update);
+        if update.is_snapshot() {
+            self.snapshot_updates.push(update.clone());
+        }
+        // A MERGE snapshot is made of exactly one update; DISTINCT and COMMAND snapshots end
+        // with the EOS notification.
+        if !update.is_snapshot() || self.mode == SubscriptionMode::Merge {
+            self.set_item_state(item_pos, ItemState::Live);
+        }
+        for listener in &self.listeners {
+            listener.on_item_update(update);
+        }
+        if !changes.is_empty() {
+            for listener in &self.listeners {
+                listener.on_field_changes(update, &changes);
+            }
+        }
+        if self.mode == SubscriptionMode::Command
+            && let Some(event) = CommandEvent::from_update(update)
+        {
+            for listener in &self.listeners {
+                listener.on_command_event(&event);
+            }
+        }
+        self.check_snapshot_complete();
+    }
+
+    /// Handles the EOS notification for an item.
+    ///
+    /// # Parameters
+    /// - `item_pos`: 1-based position of the item within the "Item List" or "Item Group".
+    pub(crate) fn on_end_of_snapshot(&mut self, item_pos: usize) {
+        self.record_first_data();
+        self.set_item_state(item_pos, ItemState::Live);
+        self.check_snapshot_complete();
+    }
+
+    /// Notifies the listeners that updates may have been missed.
+    pub(crate) fn on_data_gap(&mut self, gap: &DataGap) {
+        for listener in &mut self.listeners {
+            listener.on_data_gap(gap);
+        }
+    }
+
+    /// Handles the CS notification clearing the snapshot of an item.
+    ///
+    /// # Parameters
+    /// - `item_pos`: 1-based position of the item within the "Item List" or "Item Group".
+    pub(crate) fn on_clear_snapshot(&mut self, item_pos: usize) {
+        self.set_item_state(item_pos, ItemState::Cleared);
     }
 
     /// Returns the position of the "key" field in a COMMAND Subscription.
@@ -838,7 +2207,7 @@ impl Subscription {
     /// # Returns
     /// The 1-based position of the "key" field within the "Field Schema".
     pub fn get_key_position(&self) -> Option<usize> {
-        if self.mode != SubscriptionMode::Command || !self.is_subscribed {
+        if self.mode != SubscriptionMode::Command || !self.is_subscribed() {
             return None;
         }
         if let Some(ref schema) = self.field_schema {
@@ -860,7 +2229,7 @@ impl Subscription {
     /// # Returns
     /// The 1-based position of the "command" field within the "Field Schema".
     pub fn get_command_position(&self) -> Option<usize> {
-        if self.mode != SubscriptionMode::Command || !self.is_subscribed {
+        if self.mode != SubscriptionMode::Command || !self.is_subscribed() {
             return None;
         }
         if let Some(ref schema) = self.field_schema {
@@ -932,12 +2301,34 @@ impl Debug for Subscription {
                 "command_second_level_fields",
                 &self.command_second_level_fields,
             )
+            .field(
+                "command_second_level_requested_max_frequency",
+                &self.command_second_level_requested_max_frequency,
+            )
+            .field(
+                "command_second_level_requested_buffer_size",
+                &self.command_second_level_requested_buffer_size,
+            )
             .field("requested_buffer_size", &self.requested_buffer_size)
             .field("requested_max_frequency", &self.requested_max_frequency)
             .field("requested_snapshot", &self.requested_snapshot)
             .field("selector", &self.selector)
-            .field("is_active", &self.is_active)
-            .field("is_subscribed", &self.is_subscribed)
+            .field(
+                "field_codecs",
+                &self.field_codecs.keys().collect::<Vec<_>>(),
+            )
+            .field(
+                "schema",
+                &self.schema.as_ref().map(|schema| schema.get_name()),
+            )
+            .field("priority", &self.priority)
+            .field("value_caching_enabled", &self.value_caching_enabled)
+            .field(
+                "noop_update_suppression_enabled",
+                &self.noop_update_suppression_enabled,
+            )
+            .field("is_active", &self.is_active())
+            .field("is_subscribed", &self.is_subscribed())
             .finish()
     }
 }
@@ -946,8 +2337,20 @@ impl Debug for Subscription {
 mod tests {
     use super::*;
     use crate::subscription::ItemUpdate;
+    use crate::subscription::diff::INLINE_FIELDS;
     use std::sync::{Arc, Mutex};
 
+    /// A SUBOK notification confirming the given number of items, with a single field.
+    fn confirmation(items: usize) -> SubscriptionOk {
+        SubscriptionOk {
+            subscription_id: 1,
+            items,
+            fields: 1,
+            key_pos: None,
+            command_pos: None,
+        }
+    }
+
     struct MockSubscriptionListener {
         subscription_called: Arc<Mutex<bool>>,
         unsubscription_called: Arc<Mutex<bool>>,
@@ -1025,17 +2428,144 @@ mod tests {
         assert_eq!(subscription.get_listeners().len(), 0);
 
         let listener = Box::new(MockSubscriptionListener::new());
-        subscription.add_listener(listener);
+        subscription.add_listener(listener).unwrap();
         assert_eq!(subscription.get_listeners().len(), 1);
 
         let listener2 = MockSubscriptionListener::new();
         subscription.remove_listener(&listener2);
         assert_eq!(subscription.get_listeners().len(), 1);
 
-        subscription.add_listener(Box::new(listener2));
+        subscription.add_listener(Box::new(listener2)).unwrap();
         assert_eq!(subscription.get_listeners().len(), 2);
     }
 
+    #[test]
+    fn test_add_listener_for_fields() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string(), "field2".to_string()]),
+        )
+        .unwrap();
+        let field1_listener = MockSubscriptionListener::new();
+        let field1_called = field1_listener.item_update_called.clone();
+        let field2_listener = MockSubscriptionListener::new();
+        let field2_called = field2_listener.item_update_called.clone();
+        subscription
+            .add_listener_for_fields(vec!["field1".to_string()], Box::new(field1_listener))
+            .unwrap();
+        subscription
+            .add_listener_for_fields(vec!["field2".to_string()], Box::new(field2_listener))
+            .unwrap();
+
+        let mut update = create_test_update(1, "a", false);
+        update
+            .changed_fields
+            .insert("field1".to_string(), "a".to_string());
+        subscription.on_item_update(&update);
+
+        assert!(*field1_called.lock().unwrap());
+        assert!(!*field2_called.lock().unwrap());
+    }
+
+    #[test]
+    fn test_on_field_changes_reports_previous_values() {
+        struct ChangeListener(Arc<Mutex<Vec<FieldChange>>>);
+
+        impl SubscriptionListener for ChangeListener {
+            fn on_item_update(&self, _update: &ItemUpdate) {}
+
+            fn on_field_changes(&self, _update: &ItemUpdate, changes: &[FieldChange]) {
+                self.0.lock().unwrap().extend_from_slice(changes);
+            }
+        }
+
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        subscription
+            .add_listener(Box::new(ChangeListener(changes.clone())))
+            .unwrap();
+
+        for value in ["a", "a", "b"] {
+            let mut update = create_test_update(1, value, false);
+            update
+                .changed_fields
+                .insert("field1".to_string(), value.to_string());
+            subscription.on_item_update(&update);
+        }
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                FieldChange {
+                    field: "field1".to_string(),
+                    previous_value: None,
+                    new_value: "a".to_string(),
+                },
+                FieldChange {
+                    field: "field1".to_string(),
+                    previous_value: Some("a".to_string()),
+                    new_value: "b".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_field_changes_stay_inline_for_typical_schemas() {
+        let fields: Vec<String> = (1..=INLINE_FIELDS + 1)
+            .map(|pos| format!("field{}", pos))
+            .collect();
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(fields.clone()),
+        )
+        .unwrap();
+        let mut update = create_test_update(1, "a", false);
+        for field in &fields[..INLINE_FIELDS] {
+            update.changed_fields.insert(field.clone(), "1".to_string());
+        }
+        let changes = subscription.get_field_changes(&update);
+        assert_eq!(changes.len(), INLINE_FIELDS);
+        assert!(!changes.spilled());
+
+        update
+            .changed_fields
+            .insert(fields[INLINE_FIELDS].clone(), "1".to_string());
+        assert!(subscription.get_field_changes(&update).spilled());
+    }
+
+    #[test]
+    fn test_watch_field_changes_skips_unchanged_values() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let mut changes = subscription.watch_field_changes(1, "field1").unwrap();
+
+        for value in ["a", "a", "b"] {
+            let mut update = create_test_update(1, value, false);
+            update
+                .changed_fields
+                .insert("field1".to_string(), value.to_string());
+            subscription.on_item_update(&update);
+        }
+
+        assert_eq!(changes.try_recv().unwrap().new_value, "a");
+        let change = changes.try_recv().unwrap();
+        assert_eq!(change.previous_value.as_deref(), Some("a"));
+        assert_eq!(change.new_value, "b");
+        assert!(changes.try_recv().is_err());
+    }
+
     #[test]
     fn test_set_items() {
         let mut subscription = Subscription::new(
@@ -1053,7 +2583,7 @@ mod tests {
             &vec!["new_item1".to_string(), "new_item2".to_string()]
         );
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_items(vec!["another_item".to_string()]);
         assert!(result.is_err());
@@ -1077,7 +2607,7 @@ mod tests {
             &vec!["new_field1".to_string(), "new_field2".to_string()]
         );
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_fields(vec!["another_field".to_string()]);
         assert!(result.is_err());
@@ -1097,7 +2627,7 @@ mod tests {
 
         assert_eq!(subscription.get_item_group().unwrap(), "group1");
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_item_group("another_group".to_string());
         assert!(result.is_err());
@@ -1117,7 +2647,7 @@ mod tests {
 
         assert_eq!(subscription.get_field_schema().unwrap(), "schema1");
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_field_schema("another_schema".to_string());
         assert!(result.is_err());
@@ -1136,7 +2666,7 @@ mod tests {
         assert!(result.is_ok());
         assert_eq!(subscription.get_data_adapter().unwrap(), "adapter1");
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_data_adapter(Some("another_adapter".to_string()));
         assert!(result.is_err());
@@ -1159,7 +2689,7 @@ mod tests {
             _ => panic!("Expected Snapshot::Yes"),
         }
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_requested_snapshot(Some(Snapshot::No));
         assert!(result.is_err());
@@ -1179,7 +2709,7 @@ mod tests {
 
         assert_eq!(subscription.get_requested_buffer_size().unwrap(), &10);
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_requested_buffer_size(Some(20));
         assert!(result.is_err());
@@ -1199,7 +2729,7 @@ mod tests {
 
         assert_eq!(subscription.get_requested_max_frequency().unwrap(), &10.5);
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_requested_max_frequency(Some(20.5));
         assert!(result.is_ok());
@@ -1223,7 +2753,7 @@ mod tests {
 
         assert_eq!(subscription.get_selector().unwrap(), "selector1");
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result = subscription.set_selector(Some("another_selector".to_string()));
         assert!(result.is_err());
@@ -1248,13 +2778,13 @@ mod tests {
             "adapter1"
         );
 
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         let result =
             subscription.set_command_second_level_data_adapter(Some("adapter2".to_string()));
         assert!(result.is_err());
 
-        subscription.is_active = false;
+        subscription.state = SubscriptionState::Inactive;
 
         let result = subscription.set_command_second_level_fields(Some(vec![
             "field1".to_string(),
@@ -1309,6 +2839,38 @@ mod tests {
         assert!(!subscription.is_subscribed());
     }
 
+    #[test]
+    fn test_state_transitions() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        assert_eq!(subscription.get_state(), SubscriptionState::Inactive);
+        assert!(subscription.check_subscribable().is_ok());
+
+        subscription.on_subscription_request();
+        assert_eq!(subscription.get_state(), SubscriptionState::Activating);
+        assert!(subscription.is_active());
+        assert!(!subscription.is_subscribed());
+        let error = subscription.check_subscribable().unwrap_err();
+        assert_eq!(error.get_state(), SubscriptionState::Activating);
+        assert_eq!(
+            error.to_string(),
+            "Cannot subscribe a subscription in state ACTIVATING"
+        );
+        assert!(subscription.set_items(vec!["item2".to_string()]).is_err());
+
+        subscription.on_subscription(confirmation(1));
+        assert_eq!(subscription.get_state(), SubscriptionState::Subscribed);
+        assert!(subscription.is_subscribed());
+
+        subscription.on_detach();
+        assert_eq!(subscription.get_state(), SubscriptionState::Inactive);
+        assert!(subscription.set_items(vec!["item2".to_string()]).is_ok());
+    }
+
     #[test]
     fn test_get_key_position() {
         // Create a COMMAND subscription with field_schema containing key
@@ -1330,7 +2892,7 @@ mod tests {
         assert_eq!(subscription.get_key_position(), None);
 
         // Mark as subscribed
-        subscription.is_subscribed = true;
+        subscription.state = SubscriptionState::Subscribed;
 
         // Now it should return the position of key (0)
         assert_eq!(subscription.get_key_position(), Some(0));
@@ -1344,7 +2906,7 @@ mod tests {
         .unwrap();
 
         non_command_subscription.field_schema = Some("key,field1".to_string());
-        non_command_subscription.is_subscribed = true;
+        non_command_subscription.state = SubscriptionState::Subscribed;
 
         // Should return None for non-COMMAND subscription
         assert_eq!(non_command_subscription.get_key_position(), None);
@@ -1358,7 +2920,7 @@ mod tests {
         .unwrap();
 
         no_key_subscription.field_schema = Some("command,field1".to_string());
-        no_key_subscription.is_subscribed = true;
+        no_key_subscription.state = SubscriptionState::Subscribed;
 
         // Should return None when key field is not present
         assert_eq!(no_key_subscription.get_key_position(), None);
@@ -1385,7 +2947,7 @@ mod tests {
         assert_eq!(subscription.get_command_position(), None);
 
         // Mark as subscribed
-        subscription.is_subscribed = true;
+        subscription.state = SubscriptionState::Subscribed;
 
         // Now it should return the position of command (1)
         assert_eq!(subscription.get_command_position(), Some(1));
@@ -1399,7 +2961,7 @@ mod tests {
         .unwrap();
 
         non_command_subscription.field_schema = Some("command,field1".to_string());
-        non_command_subscription.is_subscribed = true;
+        non_command_subscription.state = SubscriptionState::Subscribed;
 
         // Should return None for non-COMMAND subscription
         assert_eq!(non_command_subscription.get_command_position(), None);
@@ -1413,7 +2975,7 @@ mod tests {
         .unwrap();
 
         no_command_subscription.field_schema = Some("key,field1".to_string());
-        no_command_subscription.is_subscribed = true;
+        no_command_subscription.state = SubscriptionState::Subscribed;
 
         // Should return None when command field is not present
         assert_eq!(no_command_subscription.get_command_position(), None);
@@ -1530,13 +3092,19 @@ mod tests {
         let result = non_command_subscription
             .set_command_second_level_fields(Some(vec!["field1".to_string()]));
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Subscription mode is not Command");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Subscription mode is not Command"
+        );
 
         // Test set_command_second_level_field_schema with invalid subscription mode
         let result = non_command_subscription
             .set_command_second_level_field_schema(Some("field1".to_string()));
         assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Subscription mode is not Command");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Subscription mode is not Command"
+        );
 
         // Test with COMMAND subscription but active
         let mut command_subscription = Subscription::new(
@@ -1547,19 +3115,23 @@ mod tests {
         .unwrap();
 
         // Make the subscription active
-        command_subscription.is_active = true;
+        command_subscription.state = SubscriptionState::Activating;
 
         // Test set_command_second_level_fields with active subscription
         let result =
             command_subscription.set_command_second_level_fields(Some(vec!["field1".to_string()]));
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Subscription is active");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Cannot change the second-level fields of a subscription in state ACTIVATING"
+        );
 
         // Test set_command_second_level_field_schema with active subscription
         let result =
             command_subscription.set_command_second_level_field_schema(Some("field1".to_string()));
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Subscription is active");
+        assert!(matches!(
+            result,
+            Err(LightstreamerError::SubscriptionState(_))
+        ));
     }
 
     #[test]
@@ -1572,12 +3144,15 @@ mod tests {
         .unwrap();
 
         // Make the subscription active
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         // Test set_data_adapter with active subscription
         let result = subscription.set_data_adapter(Some("adapter1".to_string()));
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Subscription is active");
+        let Err(LightstreamerError::SubscriptionState(error)) = result else {
+            panic!("expected a subscription state error, got {:?}", result);
+        };
+        assert_eq!(error.get_operation(), "change the data adapter of");
+        assert_eq!(error.get_state(), SubscriptionState::Activating);
     }
 
     #[test]
@@ -1590,11 +3165,581 @@ mod tests {
         .unwrap();
 
         // Make the subscription active
-        subscription.is_active = true;
+        subscription.state = SubscriptionState::Activating;
 
         // Test set_selector with active subscription
         let result = subscription.set_selector(Some("selector1".to_string()));
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Subscription is active");
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Cannot change the selector of a subscription in state ACTIVATING"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_await_subscribed_resolves_on_subscription() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let listener = MockSubscriptionListener::new();
+        let subscription_called = listener.subscription_called.clone();
+        subscription.add_listener(Box::new(listener)).unwrap();
+
+        let subscribed = subscription.await_subscribed();
+        subscription.on_subscription_request();
+        assert!(subscription.is_active());
+        subscription.on_subscription(confirmation(1));
+
+        let confirmed = subscribed.await.unwrap();
+        assert_eq!(confirmed.items, 1);
+        assert_eq!(confirmed.fields, 1);
+        assert!(subscription.is_subscribed());
+        assert!(*subscription_called.lock().unwrap());
+        // Already subscribed: resolves immediately.
+        assert!(subscription.await_subscribed().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unsubscribed_subscription_refuses_listeners() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let listener = MockSubscriptionListener::new();
+        let unsubscription_called = listener.unsubscription_called.clone();
+        subscription.add_listener(Box::new(listener)).unwrap();
+
+        let subscribed = subscription.await_subscribed();
+        let snapshot = subscription.await_snapshot();
+        subscription.on_subscription_request();
+        subscription.on_unsubscription();
+
+        assert_eq!(subscription.get_state(), SubscriptionState::Unsubscribed);
+        assert!(!subscription.is_active());
+        assert!(*unsubscription_called.lock().unwrap());
+        for result in [subscribed.await.map(|_| ()), snapshot.await.map(|_| ())] {
+            let Err(LightstreamerError::SubscriptionState(error)) = result else {
+                panic!("expected a subscription state error, got {:?}", result);
+            };
+            assert_eq!(error.get_state(), SubscriptionState::Unsubscribed);
+        }
+        assert_eq!(
+            subscription
+                .add_listener(Box::new(MockSubscriptionListener::new()))
+                .unwrap_err()
+                .to_string(),
+            "Cannot listen to a subscription in state UNSUBSCRIBED"
+        );
+        assert!(subscription.updates(8).is_err());
+        assert!(subscription.check_subscribable().is_err());
+        assert!(subscription.set_items(vec!["item2".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_await_subscribed_fails_on_request_error() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        let subscribed = subscription.await_subscribed();
+        subscription.on_subscription_request();
+        subscription.on_subscription_error(ServerException::new(21, "bad Group name"));
+
+        let err = subscribed.await.unwrap_err();
+        let LightstreamerError::Server(server_error) = err else {
+            panic!("expected a server error, got {:?}", err);
+        };
+        assert_eq!(server_error.get_code(), 21);
+        assert_eq!(server_error.get_message(), "bad Group name");
+        assert!(!subscription.is_active());
+    }
+
+    #[tokio::test]
+    async fn test_await_subscribed_fails_when_subscription_dropped() {
+        let subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        let subscribed = subscription.await_subscribed();
+        drop(subscription);
+
+        assert!(subscribed.await.is_err());
+    }
+
+    fn create_test_update(item_pos: usize, value: &str, is_snapshot: bool) -> ItemUpdate {
+        let mut fields = HashMap::new();
+        fields.insert("field1".to_string(), Some(value.to_string()));
+        ItemUpdate {
+            item_name: None,
+            item_pos,
+            fields,
+            changed_fields: HashMap::new(),
+            is_snapshot,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_await_snapshot_distinct_completes_on_end_of_snapshot() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Distinct,
+            Some(vec!["item1".to_string(), "item2".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .set_requested_snapshot(Some(Snapshot::Number(2)))
+            .unwrap();
+
+        let mut snapshot = Box::pin(subscription.await_snapshot());
+        subscription.on_subscription_request();
+        subscription.on_subscription(confirmation(2));
+        subscription.on_item_update(&create_test_update(1, "a", true));
+        subscription.on_item_update(&create_test_update(2, "b", true));
+        subscription.on_end_of_snapshot(1);
+        assert!(!subscription.is_snapshot_complete(2));
+        assert!(
+            futures_util::FutureExt::now_or_never(snapshot.as_mut()).is_none(),
+            "snapshot must not complete before EOS for every item"
+        );
+        subscription.on_end_of_snapshot(2);
+        subscription.on_item_update(&create_test_update(1, "c", false));
+
+        let updates = snapshot.await.unwrap();
+        assert_eq!(updates.len(), 2);
+        assert_eq!(updates[0].get_value("field1"), Some("a"));
+        assert_eq!(updates[1].get_value("field1"), Some("b"));
+    }
+
+    #[tokio::test]
+    async fn test_await_snapshot_merge_completes_with_first_update() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .set_requested_snapshot(Some(Snapshot::Yes))
+            .unwrap();
+
+        let snapshot = subscription.await_snapshot();
+        subscription.on_subscription_request();
+        subscription.on_subscription(confirmation(1));
+        subscription.on_item_update(&create_test_update(1, "a", true));
+
+        let updates = snapshot.await.unwrap();
+        assert_eq!(updates.len(), 1);
+        assert!(subscription.is_snapshot_complete(1));
+    }
+
+    #[test]
+    fn test_time_to_first_update() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        let threshold = Duration::from_millis(100);
+        assert_eq!(subscription.get_slow_start_deadline(threshold), None);
+
+        subscription.on_subscription_request();
+        subscription.on_subscription(confirmation(1));
+        let deadline = subscription.get_slow_start_deadline(threshold).unwrap();
+        assert!(deadline > Instant::now());
+        std::thread::sleep(Duration::from_millis(5));
+        subscription.on_item_update(&create_test_update(1, "a", false));
+        let time_to_first_update = subscription.get_time_to_first_update().unwrap();
+        assert!(time_to_first_update >= Duration::from_millis(5));
+        assert_eq!(subscription.get_slow_start_deadline(threshold), None);
+        // Only the first data counts.
+        subscription.on_item_update(&create_test_update(1, "b", false));
+        assert_eq!(
+            subscription.get_time_to_first_update(),
+            Some(time_to_first_update)
+        );
+
+        // Measured again on each request, and reported once.
+        subscription.on_subscription_request();
+        assert_eq!(subscription.get_time_to_first_update(), None);
+        assert!(subscription.get_slow_start_deadline(threshold).is_some());
+        subscription.on_slow_start();
+        assert_eq!(subscription.get_slow_start_deadline(threshold), None);
+        subscription.on_end_of_snapshot(1);
+        assert!(subscription.get_time_to_first_update().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_await_snapshot_without_requested_snapshot() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        let snapshot = subscription.await_snapshot();
+        subscription.on_subscription_request();
+        subscription.on_subscription(confirmation(1));
+
+        assert!(snapshot.await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_await_snapshot_fails_on_request_error() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Distinct,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        let snapshot = subscription.await_snapshot();
+        subscription.on_subscription_request();
+        subscription.on_subscription_error(ServerException::new(23, "bad Schema name"));
+
+        let err = snapshot.await.unwrap_err();
+        assert_eq!(err.to_string(), "Server error 23: bad Schema name");
+    }
+
+    #[test]
+    fn test_item_state_transitions() {
+        struct StateRecorder(Arc<Mutex<Vec<String>>>);
+
+        impl SubscriptionListener for StateRecorder {
+            fn on_item_update(&self, _update: &ItemUpdate) {}
+
+            fn on_item_state_change(
+                &mut self,
+                item_name: Option<&str>,
+                item_pos: usize,
+                state: ItemState,
+            ) {
+                self.0.lock().unwrap().push(format!(
+                    "{}:{}:{}",
+                    item_name.unwrap_or(""),
+                    item_pos,
+                    state
+                ));
+            }
+        }
+
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Distinct,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        subscription
+            .set_requested_snapshot(Some(Snapshot::Yes))
+            .unwrap();
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        subscription
+            .add_listener(Box::new(StateRecorder(changes.clone())))
+            .unwrap();
+
+        assert_eq!(subscription.get_item_state(1), ItemState::Pending);
+        subscription.on_subscription_request();
+        subscription.on_subscription(confirmation(1));
+        assert_eq!(
+            subscription.get_item_state(1),
+            ItemState::SnapshotInProgress
+        );
+        subscription.on_item_update(&create_test_update(1, "a", true));
+        assert_eq!(
+            subscription.get_item_state(1),
+            ItemState::SnapshotInProgress
+        );
+        subscription.on_end_of_snapshot(1);
+        assert_eq!(subscription.get_item_state(1), ItemState::Live);
+        subscription.on_clear_snapshot(1);
+        assert_eq!(subscription.get_item_state(1), ItemState::Cleared);
+        subscription.on_item_update(&create_test_update(1, "b", false));
+        assert_eq!(subscription.get_item_state(1), ItemState::Live);
+
+        assert_eq!(
+            *changes.lock().unwrap(),
+            vec![
+                "item1:1:SNAPSHOT-IN-PROGRESS",
+                "item1:1:LIVE",
+                "item1:1:CLEARED",
+                "item1:1:LIVE",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_item_state_live_without_snapshot() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string(), "item2".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        subscription.on_subscription_request();
+        subscription.on_subscription(confirmation(2));
+        assert_eq!(subscription.get_item_state(1), ItemState::Live);
+        assert_eq!(subscription.get_item_state(2), ItemState::Live);
+        assert_eq!(subscription.get_item_state(3), ItemState::Pending);
+
+        subscription.on_subscription_request();
+        assert_eq!(subscription.get_item_state(1), ItemState::Pending);
+    }
+
+    #[test]
+    fn test_get_item_name() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(crate::subscription::item_range("item", 1..=3)),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        assert_eq!(subscription.get_item_name(1), Some("item1"));
+        assert_eq!(subscription.get_item_name(3), Some("item3"));
+        assert_eq!(subscription.get_item_name(0), None);
+        assert_eq!(subscription.get_item_name(4), None);
+
+        subscription.set_item_group("group1".to_string()).unwrap();
+        assert_eq!(subscription.get_item_name(1), None);
+    }
+
+    #[test]
+    fn test_command_events_dispatched() {
+        struct CommandRecorder(Arc<Mutex<Vec<CommandEvent>>>);
+
+        impl SubscriptionListener for CommandRecorder {
+            fn on_item_update(&self, _update: &ItemUpdate) {}
+
+            fn on_command_event(&self, event: &CommandEvent) {
+                self.0.lock().unwrap().push(event.clone());
+            }
+        }
+
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Command,
+            Some(vec!["portfolio".to_string()]),
+            Some(vec!["key".to_string(), "command".to_string()]),
+        )
+        .unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        subscription
+            .add_listener(Box::new(CommandRecorder(events.clone())))
+            .unwrap();
+
+        let mut fields = HashMap::new();
+        fields.insert("key".to_string(), Some("row1".to_string()));
+        fields.insert("command".to_string(), Some("DELETE".to_string()));
+        subscription.on_item_update(&ItemUpdate {
+            item_name: Some("portfolio".to_string()),
+            item_pos: 1,
+            fields,
+            changed_fields: HashMap::new(),
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        });
+
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![CommandEvent::RowDeleted {
+                key: "row1".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_command_second_level_frequency_and_buffer_size() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Command,
+            Some(vec!["portfolio".to_string()]),
+            Some(vec!["key".to_string(), "command".to_string()]),
+        )
+        .unwrap();
+
+        assert!(
+            subscription
+                .set_command_second_level_data_adapter(Some("QUOTES".to_string()))
+                .is_ok()
+        );
+        assert!(
+            subscription
+                .set_command_second_level_requested_max_frequency(Some(2.5))
+                .is_ok()
+        );
+        assert!(
+            subscription
+                .set_command_second_level_requested_buffer_size(Some(10))
+                .is_ok()
+        );
+        assert_eq!(
+            subscription.get_command_second_level_data_adapter(),
+            Some(&"QUOTES".to_string())
+        );
+        assert_eq!(
+            subscription.get_command_second_level_requested_max_frequency(),
+            Some(&2.5)
+        );
+        assert_eq!(
+            subscription.get_command_second_level_requested_buffer_size(),
+            Some(&10)
+        );
+
+        assert!(
+            subscription
+                .set_command_second_level_requested_max_frequency(Some(0.0))
+                .is_err()
+        );
+        assert!(
+            subscription
+                .set_command_second_level_requested_buffer_size(Some(0))
+                .is_err()
+        );
+
+        subscription.state = SubscriptionState::Activating;
+        assert_eq!(
+            subscription
+                .set_command_second_level_requested_buffer_size(None)
+                .unwrap_err()
+                .to_string(),
+            "Cannot change the second-level buffer size of a subscription in state ACTIVATING"
+        );
+    }
+
+    #[test]
+    fn test_command_second_level_options_require_command_mode() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+
+        assert!(
+            subscription
+                .set_command_second_level_requested_max_frequency(Some(1.0))
+                .is_err()
+        );
+        assert!(
+            subscription
+                .set_command_second_level_requested_buffer_size(Some(1))
+                .is_err()
+        );
+        assert_eq!(
+            subscription.get_command_second_level_requested_max_frequency(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_value_cache_populated_from_updates() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string(), "item2".to_string()]),
+            Some(vec!["field0".to_string(), "field1".to_string()]),
+        )
+        .unwrap();
+
+        let mut update = create_test_update(1, "a", false);
+        update
+            .changed_fields
+            .insert("field1".to_string(), "a".to_string());
+        subscription.on_item_update(&update);
+
+        assert_eq!(subscription.get_value(1, 2), Some(&"a".to_string()));
+        assert_eq!(subscription.get_value(1, 1), None);
+        assert_eq!(subscription.get_value(2, 2), None);
+    }
+
+    #[test]
+    fn test_bounded_command_value_cache() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Command,
+            Some(vec!["item1".to_string()]),
+            Some(vec![
+                "key".to_string(),
+                "command".to_string(),
+                "field1".to_string(),
+            ]),
+        )
+        .unwrap();
+        assert!(subscription.set_value_cache_capacity(Some(0)).is_err());
+        subscription.set_value_cache_capacity(Some(2)).unwrap();
+
+        let command_update = |key: &str, command: &str, value: &str| {
+            let mut fields = HashMap::new();
+            fields.insert("key".to_string(), Some(key.to_string()));
+            fields.insert("command".to_string(), Some(command.to_string()));
+            fields.insert("field1".to_string(), Some(value.to_string()));
+            let changed_fields = fields
+                .iter()
+                .map(|(name, value)| (name.clone(), value.clone().unwrap()))
+                .collect();
+            ItemUpdate {
+                item_name: Some("item1".to_string()),
+                item_pos: 1,
+                fields,
+                changed_fields,
+                is_snapshot: false,
+                field_sources: HashMap::new(),
+                decoded_fields: HashMap::new(),
+            }
+        };
+
+        subscription.on_item_update(&command_update("k1", "ADD", "1"));
+        subscription.on_item_update(&command_update("k2", "ADD", "2"));
+        subscription.on_item_update(&command_update("k3", "ADD", "3"));
+        assert_eq!(subscription.get_command_value(1, "k1", 3), None);
+        assert_eq!(
+            subscription.get_command_value(1, "k3", 3),
+            Some(&"3".to_string())
+        );
+
+        subscription.on_item_update(&command_update("k2", "DELETE", "2"));
+        assert_eq!(subscription.get_command_value(1, "k2", 3), None);
+
+        let metrics = subscription.get_value_cache_metrics();
+        assert_eq!(metrics.entries, 1);
+        assert_eq!(metrics.capacity, Some(2));
+        assert_eq!(metrics.evictions, 1);
+        assert_eq!(subscription.get_value_cache_capacity(), Some(2));
+    }
+
+    #[test]
+    fn test_value_caching_disabled() {
+        let mut subscription = Subscription::new(
+            SubscriptionMode::Merge,
+            Some(vec!["item1".to_string()]),
+            Some(vec!["field1".to_string()]),
+        )
+        .unwrap();
+        assert!(subscription.is_value_caching_enabled());
+        subscription.set_value_caching_enabled(false).unwrap();
+        assert!(!subscription.is_value_caching_enabled());
+
+        let mut update = create_test_update(1, "a", false);
+        update
+            .changed_fields
+            .insert("field1".to_string(), "a".to_string());
+        subscription.on_item_update(&update);
+
+        assert_eq!(subscription.get_value(1, 1), None);
+        assert_eq!(subscription.get_value_cache_metrics().entries, 0);
+
+        subscription.state = SubscriptionState::Activating;
+        assert!(subscription.set_value_caching_enabled(true).is_err());
     }
 }
@@ -0,0 +1,237 @@
+use crate::subscription::diff::FieldList;
+use crate::subscription::{
+    CommandEvent, DataGap, FieldChange, ItemState, ItemUpdate, SubscriptionListener,
+};
+
+/// `SubscriptionListener` wrapper that restricts a listener to a subset of the fields of a
+/// Subscription.
+///
+/// The wrapped listener receives an update only if at least one of the selected fields changed,
+/// and the update it receives only carries the selected fields (see `ItemUpdate::project()`).
+/// This way several components interested in different fields can share a single Subscription
+/// instead of subscribing the same items several times.
+///
+/// All the other events are forwarded unchanged. COMMAND events are forwarded only when they
+/// add or delete a row, or when they change one of the selected fields.
+///
+/// ```ignore
+/// subscription.add_listener(Box::new(ProjectedListener::new(
+///     vec!["bid".to_string(), "ask".to_string()],
+///     Box::new(QuoteListener::new()),
+/// )))?;
+/// ```
+pub struct ProjectedListener {
+    /// The names of the fields the listener is interested in.
+    fields: Vec<String>,
+    /// The listener receiving the projected events.
+    listener: Box<dyn SubscriptionListener>,
+}
+
+impl ProjectedListener {
+    /// Creates a new projection of the given listener.
+    ///
+    /// # Parameters
+    ///
+    /// * `fields`: the names of the fields the listener is interested in.
+    /// * `listener`: the listener receiving the projected events.
+    pub fn new(fields: Vec<String>, listener: Box<dyn SubscriptionListener>) -> Self {
+        ProjectedListener { fields, listener }
+    }
+
+    /// Returns the names of the fields the listener is interested in.
+    pub fn get_fields(&self) -> &[String] {
+        &self.fields
+    }
+
+    /// Returns whether an update changed at least one of the selected fields.
+    fn is_relevant(&self, update: &ItemUpdate) -> bool {
+        self.fields
+            .iter()
+            .any(|field| update.changed_fields.contains_key(field))
+    }
+}
+
+impl SubscriptionListener for ProjectedListener {
+    fn on_clear_snapshot(&mut self, item_name: Option<&str>, item_pos: usize) {
+        self.listener.on_clear_snapshot(item_name, item_pos);
+    }
+
+    fn on_command_second_level_item_lost_updates(&mut self, lost_updates: u32, key: &str) {
+        self.listener
+            .on_command_second_level_item_lost_updates(lost_updates, key);
+    }
+
+    fn on_command_second_level_subscription_error(
+        &mut self,
+        code: i32,
+        message: Option<&str>,
+        key: &str,
+    ) {
+        self.listener
+            .on_command_second_level_subscription_error(code, message, key);
+    }
+
+    fn on_end_of_snapshot(&mut self, item_name: Option<&str>, item_pos: usize) {
+        self.listener.on_end_of_snapshot(item_name, item_pos);
+    }
+
+    fn on_data_gap(&mut self, gap: &DataGap) {
+        self.listener.on_data_gap(gap);
+    }
+
+    fn on_item_lost_updates(
+        &mut self,
+        item_name: Option<&str>,
+        item_pos: usize,
+        lost_updates: u32,
+    ) {
+        self.listener
+            .on_item_lost_updates(item_name, item_pos, lost_updates);
+    }
+
+    fn on_item_update(&self, update: &ItemUpdate) {
+        if self.is_relevant(update) {
+            self.listener.on_item_update(&update.project(&self.fields));
+        }
+    }
+
+    fn on_field_changes(&self, update: &ItemUpdate, changes: &[FieldChange]) {
+        let changes: FieldList<FieldChange> = changes
+            .iter()
+            .filter(|change| self.fields.contains(&change.field))
+            .cloned()
+            .collect();
+        if !changes.is_empty() {
+            self.listener
+                .on_field_changes(&update.project(&self.fields), &changes);
+        }
+    }
+
+    fn on_command_event(&self, event: &CommandEvent) {
+        let relevant = match event {
+            CommandEvent::RowUpdated { changed, .. } => {
+                self.fields.iter().any(|field| changed.contains_key(field))
+            }
+            CommandEvent::RowAdded { .. } | CommandEvent::RowDeleted { .. } => true,
+        };
+        if relevant {
+            self.listener.on_command_event(event);
+        }
+    }
+
+    fn on_item_state_change(&mut self, item_name: Option<&str>, item_pos: usize, state: ItemState) {
+        self.listener
+            .on_item_state_change(item_name, item_pos, state);
+    }
+
+    fn on_listen_end(&mut self) {
+        self.listener.on_listen_end();
+    }
+
+    fn on_listen_start(&mut self) {
+        self.listener.on_listen_start();
+    }
+
+    fn on_real_max_frequency(&mut self, frequency: Option<f64>) {
+        self.listener.on_real_max_frequency(frequency);
+    }
+
+    fn on_subscription(&mut self) {
+        self.listener.on_subscription();
+    }
+
+    fn on_subscription_error(&mut self, code: i32, message: Option<&str>) {
+        self.listener.on_subscription_error(code, message);
+    }
+
+    fn on_unsubscription(&mut self) {
+        self.listener.on_unsubscription();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct RecordingListener {
+        updates: Arc<Mutex<Vec<ItemUpdate>>>,
+        events: Arc<Mutex<Vec<CommandEvent>>>,
+    }
+
+    impl SubscriptionListener for RecordingListener {
+        fn on_item_update(&self, update: &ItemUpdate) {
+            self.updates.lock().unwrap().push(update.clone());
+        }
+
+        fn on_command_event(&self, event: &CommandEvent) {
+            self.events.lock().unwrap().push(event.clone());
+        }
+    }
+
+    fn create_test_item_update(changed: &[(&str, &str)]) -> ItemUpdate {
+        let mut fields = HashMap::new();
+        fields.insert("bid".to_string(), Some("10".to_string()));
+        fields.insert("ask".to_string(), Some("11".to_string()));
+        fields.insert("volume".to_string(), Some("500".to_string()));
+        ItemUpdate {
+            item_name: Some("item1".to_string()),
+            item_pos: 1,
+            fields,
+            changed_fields: changed
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    /// Returns a projection on "bid" and "ask", together with a handle on what it records.
+    fn create_projected_listener() -> (ProjectedListener, RecordingListener) {
+        let recorded = RecordingListener::default();
+        let listener = ProjectedListener::new(
+            vec!["bid".to_string(), "ask".to_string()],
+            Box::new(recorded.clone()),
+        );
+        (listener, recorded)
+    }
+
+    #[test]
+    fn test_updates_of_other_fields_are_filtered_out() {
+        let (listener, recorded) = create_projected_listener();
+        listener.on_item_update(&create_test_item_update(&[("volume", "500")]));
+        listener.on_item_update(&create_test_item_update(&[
+            ("bid", "10"),
+            ("volume", "500"),
+        ]));
+
+        let updates = recorded.updates.lock().unwrap();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].fields.len(), 2);
+        assert_eq!(updates[0].get_value("bid"), Some("10"));
+        assert!(!updates[0].fields.contains_key("volume"));
+        assert_eq!(updates[0].get_changed_fields().len(), 1);
+    }
+
+    #[test]
+    fn test_command_events_are_filtered() {
+        let (listener, recorded) = create_projected_listener();
+        listener.on_command_event(&CommandEvent::RowUpdated {
+            key: "k1".to_string(),
+            changed: HashMap::from([("volume".to_string(), "1".to_string())]),
+        });
+        listener.on_command_event(&CommandEvent::RowUpdated {
+            key: "k1".to_string(),
+            changed: HashMap::from([("ask".to_string(), "12".to_string())]),
+        });
+        listener.on_command_event(&CommandEvent::RowDeleted {
+            key: "k1".to_string(),
+        });
+
+        assert_eq!(recorded.events.lock().unwrap().len(), 2);
+    }
+}
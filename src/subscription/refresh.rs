@@ -0,0 +1,94 @@
+use crate::utils::{IllegalStateException, LightstreamerError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Handle requesting a fresh snapshot for a Subscription, obtained through
+/// `Subscription::get_snapshot_refresher()`, which can be kept after the Subscription has been
+/// handed over to the client.
+///
+/// A refresh resubscribes the Subscription under a new ID, which clears its cached values and
+/// item states and has the Server send the snapshot again, without touching the other
+/// Subscriptions of the session. It is meant to recover from a suspected corruption of the state
+/// kept by the application.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotRefresher {
+    state: Arc<RefreshState>,
+}
+
+#[derive(Debug, Default)]
+struct RefreshState {
+    /// Whether a refresh was requested and not yet performed.
+    requested: AtomicBool,
+    /// The signal waking the client the Subscription is active in, if any.
+    client: Mutex<Option<Arc<Notify>>>,
+}
+
+impl SnapshotRefresher {
+    /// Requests a fresh snapshot for the Subscription.
+    ///
+    /// The Subscription is resubscribed as soon as the client processes the request. If it is
+    /// not subscribed to yet, the snapshot in progress already is a fresh one, and the request
+    /// is dropped. Several requests made before the refresh are merged into one.
+    ///
+    /// # Raises
+    ///
+    /// * `IllegalStateException`: if the Subscription is not active in a client.
+    pub fn refresh(&self) -> Result<(), LightstreamerError> {
+        let client = self.state.client.lock().unwrap();
+        let Some(notify) = client.as_ref() else {
+            return Err(IllegalStateException::new(
+                "Subscription is not active. This method can only be called while the Subscription instance is in its 'active' state.",
+            ).into());
+        };
+        self.state.requested.store(true, Ordering::Release);
+        notify.notify_one();
+        Ok(())
+    }
+
+    /// Returns whether a refresh was requested and not yet performed.
+    pub fn is_refresh_pending(&self) -> bool {
+        self.state.requested.load(Ordering::Acquire)
+    }
+
+    /// Links the Subscription to the client it is active in, woken on each request.
+    pub(crate) fn attach(&self, notify: Arc<Notify>) {
+        *self.state.client.lock().unwrap() = Some(notify);
+    }
+
+    /// Unlinks the Subscription from its client, dropping any pending request.
+    pub(crate) fn detach(&self) {
+        *self.state.client.lock().unwrap() = None;
+        self.take_request();
+    }
+
+    /// Returns whether a refresh was requested, clearing the request.
+    pub(crate) fn take_request(&self) -> bool {
+        self.state.requested.swap(false, Ordering::AcqRel)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refresh_requires_an_active_subscription() {
+        let refresher = SnapshotRefresher::default();
+        assert!(refresher.refresh().is_err());
+        assert!(!refresher.is_refresh_pending());
+
+        let notify = Arc::new(Notify::new());
+        refresher.attach(notify.clone());
+        refresher.clone().refresh().unwrap();
+        refresher.refresh().unwrap();
+        assert!(refresher.is_refresh_pending());
+        assert!(refresher.take_request());
+        assert!(!refresher.take_request());
+
+        refresher.refresh().unwrap();
+        refresher.detach();
+        assert!(!refresher.is_refresh_pending());
+        assert!(refresher.refresh().is_err());
+    }
+}
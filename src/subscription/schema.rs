@@ -0,0 +1,431 @@
+use crate::subscription::codec::ErasedCodec;
+use crate::subscription::{FieldCodec, ItemUpdate, Subscription, SubscriptionMode};
+use crate::utils::{
+    IllegalArgumentException, LightstreamerError, ValidationError, ValidationProblem,
+};
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, RwLock};
+
+/// The type of the values of a field of a `NamedSchema`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldType {
+    /// Any text.
+    Text,
+    /// A signed integer, read as `i64`.
+    Integer,
+    /// A number, possibly with a fractional part, read as `f64`.
+    Number,
+    /// `true` or `false`, also accepted as `1` or `0`.
+    Boolean,
+}
+
+impl FieldType {
+    /// Parses a value of this type.
+    fn parse(&self, value: &str) -> Result<FieldValue, IllegalArgumentException> {
+        let invalid = || IllegalArgumentException::new(&format!("'{}' is not {}", value, self));
+        match self {
+            FieldType::Text => Ok(FieldValue::Text(value.to_string())),
+            FieldType::Integer => value
+                .trim()
+                .parse()
+                .map(FieldValue::Integer)
+                .map_err(|_| invalid()),
+            FieldType::Number => value
+                .trim()
+                .parse()
+                .map(FieldValue::Number)
+                .map_err(|_| invalid()),
+            FieldType::Boolean => match value.trim() {
+                "true" | "1" => Ok(FieldValue::Boolean(true)),
+                "false" | "0" => Ok(FieldValue::Boolean(false)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldType::Text => write!(f, "a text"),
+            FieldType::Integer => write!(f, "an integer"),
+            FieldType::Number => write!(f, "a number"),
+            FieldType::Boolean => write!(f, "a boolean"),
+        }
+    }
+}
+
+/// A value of a field, typed as declared by its `NamedSchema`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldValue {
+    /// The value of a `FieldType::Text` field.
+    Text(String),
+    /// The value of a `FieldType::Integer` field.
+    Integer(i64),
+    /// The value of a `FieldType::Number` field.
+    Number(f64),
+    /// The value of a `FieldType::Boolean` field.
+    Boolean(bool),
+}
+
+/// A field of a `NamedSchema`.
+#[derive(Debug, Clone)]
+struct SchemaField {
+    name: String,
+    field_type: FieldType,
+    codec: Option<ErasedCodec>,
+}
+
+/// A field schema defined once and shared by many Subscriptions through a `SchemaRegistry`: the
+/// names of the fields in the order of their positions, the type of their values and their
+/// codecs, if any.
+///
+/// ```ignore
+/// let quote = NamedSchema::new("quote")
+///     .field("last_price", FieldType::Number)
+///     .field("volume", FieldType::Integer)
+///     .field_with_codec("book", FieldType::Text, Base64Codec);
+/// ```
+#[derive(Debug, Clone)]
+pub struct NamedSchema {
+    name: String,
+    fields: Vec<SchemaField>,
+}
+
+impl NamedSchema {
+    /// Creates an empty schema with the given name.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`: the name the schema is registered and referenced with.
+    pub fn new(name: &str) -> NamedSchema {
+        NamedSchema {
+            name: name.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Adds a field, at the position following the last one.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`: the name of the field.
+    /// * `field_type`: the type of the values of the field.
+    pub fn field(mut self, name: &str, field_type: FieldType) -> NamedSchema {
+        self.fields.push(SchemaField {
+            name: name.to_string(),
+            field_type,
+            codec: None,
+        });
+        self
+    }
+
+    /// Adds a field decoded by a codec, at the position following the last one.
+    ///
+    /// # Parameters
+    ///
+    /// * `name`: the name of the field.
+    /// * `field_type`: the type of the textual values of the field.
+    /// * `codec`: the codec registered for the field on the Subscriptions using the schema.
+    pub fn field_with_codec<C: FieldCodec + 'static>(
+        mut self,
+        name: &str,
+        field_type: FieldType,
+        codec: C,
+    ) -> NamedSchema {
+        self.fields.push(SchemaField {
+            name: name.to_string(),
+            field_type,
+            codec: Some(ErasedCodec::new(codec)),
+        });
+        self
+    }
+
+    /// Returns the name of the schema.
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the names of the fields, in the order of their positions, as the field list of
+    /// the Subscriptions using the schema.
+    pub fn get_fields(&self) -> Vec<String> {
+        self.fields.iter().map(|field| field.name.clone()).collect()
+    }
+
+    /// Returns the 1-based position of a field, if part of the schema.
+    pub fn get_position(&self, field: &str) -> Option<usize> {
+        self.fields
+            .iter()
+            .position(|schema_field| schema_field.name == field)
+            .map(|index| index + 1)
+    }
+
+    /// Returns the type of a field, if part of the schema.
+    pub fn get_field_type(&self, field: &str) -> Option<FieldType> {
+        self.find(field).map(|schema_field| schema_field.field_type)
+    }
+
+    /// Returns the value of a field of an update, typed as declared by the schema.
+    ///
+    /// # Parameters
+    ///
+    /// * `update`: the update, from a Subscription using the schema.
+    /// * `field`: the name of the field.
+    ///
+    /// # Returns
+    ///
+    /// The typed value, `None` if the field is null or not part of the update, or an error if
+    /// the field is not part of the schema or the value is not of its type.
+    pub fn get_value(
+        &self,
+        update: &ItemUpdate,
+        field: &str,
+    ) -> Result<Option<FieldValue>, LightstreamerError> {
+        let schema_field = self.find(field).ok_or_else(|| {
+            IllegalArgumentException::new(&format!(
+                "Field '{}' is not part of schema '{}'",
+                field, self.name
+            ))
+        })?;
+        update
+            .fields
+            .get(field)
+            .and_then(|value| value.as_deref())
+            .map(|value| schema_field.field_type.parse(value))
+            .transpose()
+            .map_err(LightstreamerError::from)
+    }
+
+    /// Checks that the values of an update are of the types declared by the schema.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ValidationError` with a problem for each field whose value is not of its type.
+    pub fn validate_update(&self, update: &ItemUpdate) -> Result<(), ValidationError> {
+        let problems = self
+            .fields
+            .iter()
+            .filter_map(|schema_field| {
+                let value = update.fields.get(&schema_field.name)?.as_deref()?;
+                let err = schema_field.field_type.parse(value).err()?;
+                Some(ValidationProblem::new(&schema_field.name, &err.to_string()))
+            })
+            .collect();
+        ValidationError::check(problems)
+    }
+
+    /// Returns the codecs of the fields having one, by field name.
+    pub(crate) fn get_codecs(&self) -> impl Iterator<Item = (&str, &ErasedCodec)> {
+        self.fields.iter().filter_map(|schema_field| {
+            schema_field
+                .codec
+                .as_ref()
+                .map(|codec| (schema_field.name.as_str(), codec))
+        })
+    }
+
+    fn find(&self, field: &str) -> Option<&SchemaField> {
+        self.fields
+            .iter()
+            .find(|schema_field| schema_field.name == field)
+    }
+}
+
+/// Registry of the field schemas of an application, defined once and referenced by name by
+/// many Subscriptions, so that their field lists, typed accessors and validation stay
+/// consistent.
+///
+/// The registry is a shared handle, which can be cloned and passed to the modules of the
+/// application creating Subscriptions.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    schemas: Arc<RwLock<HashMap<String, Arc<NamedSchema>>>>,
+}
+
+impl SchemaRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> SchemaRegistry {
+        SchemaRegistry::default()
+    }
+
+    /// Registers a schema under its name.
+    ///
+    /// # Parameters
+    ///
+    /// * `schema`: the schema, with at least one field.
+    ///
+    /// # Returns
+    ///
+    /// The registered schema, as shared with the Subscriptions using it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `IllegalArgumentException` if a schema with the same name is already
+    /// registered, or the schema has no fields or repeats a field.
+    pub fn register(&self, schema: NamedSchema) -> Result<Arc<NamedSchema>, LightstreamerError> {
+        if schema.fields.is_empty() {
+            return Err(IllegalArgumentException::new(&format!(
+                "Schema '{}' has no fields",
+                schema.name
+            ))
+            .into());
+        }
+        for (index, field) in schema.fields.iter().enumerate() {
+            if schema.fields[..index].iter().any(|f| f.name == field.name) {
+                return Err(IllegalArgumentException::new(&format!(
+                    "Schema '{}' repeats field '{}'",
+                    schema.name, field.name
+                ))
+                .into());
+            }
+        }
+        let mut schemas = self.schemas.write().unwrap_or_else(|err| err.into_inner());
+        if schemas.contains_key(&schema.name) {
+            return Err(IllegalArgumentException::new(&format!(
+                "Schema '{}' is already registered",
+                schema.name
+            ))
+            .into());
+        }
+        let schema = Arc::new(schema);
+        schemas.insert(schema.name.clone(), schema.clone());
+        Ok(schema)
+    }
+
+    /// Returns the schema registered under the given name, if any.
+    pub fn get(&self, name: &str) -> Option<Arc<NamedSchema>> {
+        self.schemas
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .get(name)
+            .cloned()
+    }
+
+    /// Returns the names of the registered schemas, sorted.
+    pub fn get_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .schemas
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .keys()
+            .cloned()
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// Creates a Subscription to the given items with the fields of a registered schema.
+    ///
+    /// # Parameters
+    ///
+    /// * `mode`: the subscription mode.
+    /// * `items`: the names of the items.
+    /// * `schema_name`: the name of the schema.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no schema is registered under the given name, or the Subscription
+    /// cannot be created.
+    ///
+    /// See also `Subscription::set_schema()`
+    pub fn create_subscription(
+        &self,
+        mode: SubscriptionMode,
+        items: Vec<String>,
+        schema_name: &str,
+    ) -> Result<Subscription, LightstreamerError> {
+        let schema = self.get(schema_name).ok_or_else(|| {
+            IllegalArgumentException::new(&format!("Schema '{}' is not registered", schema_name))
+        })?;
+        let mut subscription = Subscription::new(mode, Some(items), Some(schema.get_fields()))?;
+        subscription.set_schema(schema)?;
+        Ok(subscription)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::subscription::Base64Codec;
+
+    fn quote() -> NamedSchema {
+        NamedSchema::new("quote")
+            .field("last_price", FieldType::Number)
+            .field("volume", FieldType::Integer)
+            .field("open", FieldType::Boolean)
+            .field_with_codec("book", FieldType::Text, Base64Codec)
+    }
+
+    fn update(values: &[(&str, &str)]) -> ItemUpdate {
+        ItemUpdate {
+            item_name: Some("item1".to_string()),
+            item_pos: 1,
+            fields: values
+                .iter()
+                .map(|(name, value)| (name.to_string(), Some(value.to_string())))
+                .collect(),
+            changed_fields: HashMap::new(),
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn test_registry_shares_the_schemas() {
+        let registry = SchemaRegistry::new();
+        registry.register(quote()).unwrap();
+        assert!(registry.register(quote()).is_err());
+        assert!(registry.register(NamedSchema::new("empty")).is_err());
+        assert_eq!(registry.get_names(), vec!["quote"]);
+
+        let subscription = registry
+            .create_subscription(SubscriptionMode::Merge, vec!["item1".to_string()], "quote")
+            .unwrap();
+        assert_eq!(
+            subscription.get_fields(),
+            Some(&vec![
+                "last_price".to_string(),
+                "volume".to_string(),
+                "open".to_string(),
+                "book".to_string()
+            ])
+        );
+        assert_eq!(subscription.get_schema().unwrap().get_name(), "quote");
+        assert!(
+            registry
+                .create_subscription(SubscriptionMode::Merge, vec!["item1".to_string()], "trade")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_typed_values_and_validation() {
+        let schema = quote();
+        assert_eq!(schema.get_position("volume"), Some(2));
+        assert_eq!(schema.get_field_type("open"), Some(FieldType::Boolean));
+
+        let valid = update(&[("last_price", "10.5"), ("volume", "300"), ("open", "1")]);
+        assert_eq!(
+            schema.get_value(&valid, "last_price").unwrap(),
+            Some(FieldValue::Number(10.5))
+        );
+        assert_eq!(
+            schema.get_value(&valid, "open").unwrap(),
+            Some(FieldValue::Boolean(true))
+        );
+        assert_eq!(schema.get_value(&valid, "book").unwrap(), None);
+        assert!(schema.get_value(&valid, "bid").is_err());
+        assert!(schema.validate_update(&valid).is_ok());
+
+        let invalid = update(&[("last_price", "n/a"), ("volume", "1.5")]);
+        let err = schema.validate_update(&invalid).unwrap_err();
+        let properties: Vec<&str> = err
+            .get_problems()
+            .iter()
+            .map(|problem| problem.get_property())
+            .collect();
+        assert_eq!(properties, vec!["last_price", "volume"]);
+    }
+}
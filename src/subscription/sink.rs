@@ -0,0 +1,545 @@
+use crate::subscription::{ConsumerLag, ItemUpdate, SubscriptionListener};
+use std::error::Error;
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, timeout_at};
+
+/// Destination for item updates, such as a file, a socket or another system.
+///
+/// Implementations receive every update in order through `deliver()`. Sinks are driven
+/// asynchronously, so they are usually attached to a `Subscription` through a `SinkListener`,
+/// which forwards the updates received by the listener to a task owning the sink.
+///
+/// Implementations can be written with `async fn`:
+///
+/// ```ignore
+/// struct CountingSink(usize);
+///
+/// impl UpdateSink for CountingSink {
+///     async fn deliver(&mut self, _update: &ItemUpdate) -> Result<(), Box<dyn Error + Send + Sync>> {
+///         self.0 += 1;
+///         Ok(())
+///     }
+/// }
+/// ```
+pub trait UpdateSink {
+    /// Delivers an update to the sink.
+    ///
+    /// # Parameters
+    ///
+    /// * `update`: the update to be delivered.
+    fn deliver(
+        &mut self,
+        update: &ItemUpdate,
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send;
+
+    /// Delivers a batch of updates to the sink, as driven by `SinkListener::spawn_batched()`, so
+    /// that e.g. a database sink can write them in a single statement.
+    ///
+    /// The default implementation delivers the updates one by one.
+    ///
+    /// # Parameters
+    ///
+    /// * `updates`: the updates to be delivered, in order.
+    fn deliver_batch(
+        &mut self,
+        updates: &[ItemUpdate],
+    ) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send
+    where
+        Self: Send,
+    {
+        async move {
+            for update in updates {
+                self.deliver(update).await?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Flushes any buffered output. Called once the sink is no longer receiving updates.
+    ///
+    /// The default implementation does nothing.
+    fn flush(&mut self) -> impl Future<Output = Result<(), Box<dyn Error + Send + Sync>>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// Sink writing each update as one JSON object per line ("JSON lines").
+///
+/// Every line carries the item name and position, the snapshot flag, the current value of all
+/// fields and the changed fields, as produced by `ItemUpdate::to_json()`.
+pub struct JsonLinesSink<W> {
+    writer: W,
+}
+
+impl<W: AsyncWrite + Unpin + Send> JsonLinesSink<W> {
+    /// Creates a new sink writing to the given writer.
+    ///
+    /// # Parameters
+    ///
+    /// * `writer`: the destination of the JSON lines. Wrap it in a `tokio::io::BufWriter` to
+    ///   avoid one write per update.
+    pub fn new(writer: W) -> Self {
+        JsonLinesSink { writer }
+    }
+
+    /// Consumes the sink, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> UpdateSink for JsonLinesSink<W> {
+    async fn deliver(&mut self, update: &ItemUpdate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let line = update.to_json()? + "\n";
+        self.writer.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Sink writing updates as CSV rows.
+///
+/// The first row is a header made of `item_name`, `item_pos`, `is_snapshot` followed by the
+/// configured field names. Each update then produces one row with the current value of every
+/// field; fields without a value are left empty. Values containing commas, quotes or line breaks
+/// are quoted.
+pub struct CsvSink<W> {
+    writer: W,
+    fields: Vec<String>,
+    header_written: bool,
+}
+
+impl<W: AsyncWrite + Unpin + Send> CsvSink<W> {
+    /// Creates a new sink writing to the given writer.
+    ///
+    /// # Parameters
+    ///
+    /// * `writer`: the destination of the CSV rows. Wrap it in a `tokio::io::BufWriter` to
+    ///   avoid one write per update.
+    /// * `fields`: the field names to be written, in column order. Usually the "Field List" of
+    ///   the subscription.
+    pub fn new(writer: W, fields: Vec<String>) -> Self {
+        CsvSink {
+            writer,
+            fields,
+            header_written: false,
+        }
+    }
+
+    /// Consumes the sink, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Quotes a CSV value if needed.
+    fn escape(value: &str) -> String {
+        if value.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", value.replace('"', "\"\""))
+        } else {
+            value.to_string()
+        }
+    }
+
+    /// Builds the CSV row for an update.
+    fn format_row(&self, update: &ItemUpdate) -> String {
+        let mut columns = vec![
+            Self::escape(update.get_item_name().unwrap_or("")),
+            update.get_item_pos().to_string(),
+            update.is_snapshot().to_string(),
+        ];
+        for field in &self.fields {
+            columns.push(Self::escape(
+                update
+                    .fields
+                    .get(field)
+                    .and_then(|value| value.as_deref())
+                    .unwrap_or(""),
+            ));
+        }
+        columns.join(",") + "\n"
+    }
+}
+
+impl<W: AsyncWrite + Unpin + Send> UpdateSink for CsvSink<W> {
+    async fn deliver(&mut self, update: &ItemUpdate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        if !self.header_written {
+            let mut header = vec![
+                "item_name".to_string(),
+                "item_pos".to_string(),
+                "is_snapshot".to_string(),
+            ];
+            header.extend(self.fields.iter().map(|field| Self::escape(field)));
+            self.writer
+                .write_all((header.join(",") + "\n").as_bytes())
+                .await?;
+            self.header_written = true;
+        }
+        let row = self.format_row(update);
+        self.writer.write_all(row.as_bytes()).await?;
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Sink forwarding the batches of updates to a channel, so that they can be consumed as a
+/// stream, e.g. through `SinkListener::spawn_batched()`.
+impl UpdateSink for UnboundedSender<Vec<ItemUpdate>> {
+    async fn deliver(&mut self, update: &ItemUpdate) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.deliver_batch(std::slice::from_ref(update)).await
+    }
+
+    async fn deliver_batch(
+        &mut self,
+        updates: &[ItemUpdate],
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.send(updates.to_vec())?;
+        Ok(())
+    }
+}
+
+/// Handle of the task driving a sink, resolving to the first error returned by the sink.
+pub type SinkTask = JoinHandle<Result<(), Box<dyn Error + Send + Sync>>>;
+
+/// `SubscriptionListener` that forwards every update to an `UpdateSink` driven by its own task.
+///
+/// Listener callbacks are synchronous, so updates are queued on an unbounded channel and
+/// delivered to the sink in order by the spawned task. The task ends, flushing the sink, once
+/// the listener is dropped (for instance when the subscription is removed from the client).
+/// The number of updates queued for the sink is tracked by the gauge returned by
+/// `get_consumer_lag()`.
+pub struct SinkListener {
+    sender: UnboundedSender<ItemUpdate>,
+    lag: ConsumerLag,
+}
+
+impl SinkListener {
+    /// Spawns a task driving the given sink and returns the listener feeding it, together with
+    /// the handle of the task.
+    ///
+    /// The task must be spawned from within a Tokio runtime. It completes with the first error
+    /// returned by the sink, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `sink`: the sink to receive the updates.
+    ///
+    /// See also `spawn_on()`
+    pub fn spawn<S>(sink: S) -> (SinkListener, SinkTask)
+    where
+        S: UpdateSink + Send + 'static,
+    {
+        Self::spawn_on(sink, &Handle::current())
+    }
+
+    /// Spawns a task driving the given sink on the given runtime, which can be called from
+    /// outside of it, and returns the listener feeding it, together with the handle of the task.
+    ///
+    /// # Parameters
+    ///
+    /// * `sink`: the sink to receive the updates.
+    /// * `runtime`: the handle of the runtime to spawn the task on.
+    pub fn spawn_on<S>(mut sink: S, runtime: &Handle) -> (SinkListener, SinkTask)
+    where
+        S: UpdateSink + Send + 'static,
+    {
+        let (sender, mut receiver) = unbounded_channel::<ItemUpdate>();
+        let lag = ConsumerLag::default();
+        let delivered = lag.clone();
+        let handle = runtime.spawn(async move {
+            while let Some(update) = receiver.recv().await {
+                sink.deliver(&update).await?;
+                delivered.record_delivered(1);
+            }
+            sink.flush().await
+        });
+        (SinkListener { sender, lag }, handle)
+    }
+
+    /// Spawns a task driving the given sink with batches of updates, through
+    /// `UpdateSink::deliver_batch()`, and returns the listener feeding it, together with the
+    /// handle of the task. This saves the per-update overhead of consumers such as databases.
+    ///
+    /// A batch is delivered once it holds `max_updates` updates, or `max_delay` after its first
+    /// update was received, whichever comes first, so that a quiet subscription does not hold
+    /// its updates back indefinitely.
+    ///
+    /// The task must be spawned from within a Tokio runtime. It completes with the first error
+    /// returned by the sink, if any.
+    ///
+    /// # Parameters
+    ///
+    /// * `sink`: the sink to receive the batches.
+    /// * `max_updates`: the maximum number of updates of a batch, at least 1.
+    /// * `max_delay`: the longest time an update waits for its batch to be delivered.
+    pub fn spawn_batched<S>(
+        sink: S,
+        max_updates: usize,
+        max_delay: Duration,
+    ) -> (SinkListener, SinkTask)
+    where
+        S: UpdateSink + Send + 'static,
+    {
+        Self::spawn_batched_on(sink, max_updates, max_delay, &Handle::current())
+    }
+
+    /// Spawns a task driving the given sink with batches of updates on the given runtime, as
+    /// `spawn_batched()` does.
+    ///
+    /// # Parameters
+    ///
+    /// * `sink`: the sink to receive the batches.
+    /// * `max_updates`: the maximum number of updates of a batch, at least 1.
+    /// * `max_delay`: the longest time an update waits for its batch to be delivered.
+    /// * `runtime`: the handle of the runtime to spawn the task on.
+    pub fn spawn_batched_on<S>(
+        mut sink: S,
+        max_updates: usize,
+        max_delay: Duration,
+        runtime: &Handle,
+    ) -> (SinkListener, SinkTask)
+    where
+        S: UpdateSink + Send + 'static,
+    {
+        let max_updates = max_updates.max(1);
+        let (sender, mut receiver) = unbounded_channel::<ItemUpdate>();
+        let lag = ConsumerLag::default();
+        let delivered = lag.clone();
+        let handle = runtime.spawn(async move {
+            let mut batch = Vec::with_capacity(max_updates);
+            let mut closed = false;
+            while !closed {
+                // A batch starts with its first update.
+                let Some(update) = receiver.recv().await else {
+                    break;
+                };
+                batch.push(update);
+                let deadline = Instant::now() + max_delay;
+                while batch.len() < max_updates {
+                    match timeout_at(deadline, receiver.recv()).await {
+                        Ok(Some(update)) => batch.push(update),
+                        Ok(None) => {
+                            closed = true;
+                            break;
+                        }
+                        Err(_) => break,
+                    }
+                }
+                sink.deliver_batch(&batch).await?;
+                delivered.record_delivered(batch.len() as u64);
+                batch.clear();
+            }
+            sink.flush().await
+        });
+        (SinkListener { sender, lag }, handle)
+    }
+
+    /// Also reports the number of updates queued for the sink to the given gauge, typically the
+    /// one of the Subscription the listener is added to, so that a single gauge covers the whole
+    /// path of its updates. Only the first gauge given is kept.
+    ///
+    /// # Parameters
+    ///
+    /// * `lag`: the gauge to report to, e.g. from `Subscription::get_consumer_lag()`.
+    pub fn with_consumer_lag(self, lag: ConsumerLag) -> SinkListener {
+        self.lag.set_parent(lag);
+        self
+    }
+
+    /// Returns the gauge of the updates queued for the sink and not yet delivered, with its
+    /// high-water mark, as a handle that can be kept after the listener is added to a
+    /// Subscription.
+    pub fn get_consumer_lag(&self) -> ConsumerLag {
+        self.lag.clone()
+    }
+}
+
+impl SubscriptionListener for SinkListener {
+    fn on_item_update(&self, update: &ItemUpdate) {
+        self.lag.record_enqueued(1);
+        // The receiver is only gone if the sink failed; the error is reported by the task.
+        if self.sender.send(update.clone()).is_err() {
+            self.lag.record_delivered(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn create_test_item_update(price: &str) -> ItemUpdate {
+        let mut fields = HashMap::new();
+        fields.insert("price".to_string(), Some(price.to_string()));
+        fields.insert("name".to_string(), Some("ACME, Inc.".to_string()));
+        fields.insert("volume".to_string(), None);
+
+        let mut changed_fields = HashMap::new();
+        changed_fields.insert("price".to_string(), price.to_string());
+
+        ItemUpdate {
+            item_name: Some("item1".to_string()),
+            item_pos: 1,
+            fields,
+            changed_fields,
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_json_lines_sink() {
+        let mut sink = JsonLinesSink::new(Vec::new());
+        sink.deliver(&create_test_item_update("10.5"))
+            .await
+            .unwrap();
+        sink.deliver(&create_test_item_update("11.0"))
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+
+        let first: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(first["item_name"], "item1");
+        assert_eq!(first["item_pos"], 1);
+        assert_eq!(first["fields"]["price"], "10.5");
+        assert_eq!(first["changed_fields"]["price"], "10.5");
+    }
+
+    #[tokio::test]
+    async fn test_csv_sink() {
+        let fields = vec![
+            "name".to_string(),
+            "price".to_string(),
+            "volume".to_string(),
+        ];
+        let mut sink = CsvSink::new(Vec::new(), fields);
+        sink.deliver(&create_test_item_update("10.5"))
+            .await
+            .unwrap();
+        sink.deliver(&create_test_item_update("11.0"))
+            .await
+            .unwrap();
+
+        let output = String::from_utf8(sink.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "item_name,item_pos,is_snapshot,name,price,volume");
+        assert_eq!(lines[1], "item1,1,false,\"ACME, Inc.\",10.5,");
+        assert_eq!(lines[2], "item1,1,false,\"ACME, Inc.\",11.0,");
+    }
+
+    #[test]
+    fn test_csv_escape() {
+        assert_eq!(CsvSink::<Vec<u8>>::escape("plain"), "plain");
+        assert_eq!(CsvSink::<Vec<u8>>::escape("a,b"), "\"a,b\"");
+        assert_eq!(
+            CsvSink::<Vec<u8>>::escape("say \"hi\""),
+            "\"say \"\"hi\"\"\""
+        );
+    }
+
+    #[tokio::test]
+    async fn test_sink_listener_forwards_updates() {
+        struct CollectingSink(tokio::sync::mpsc::UnboundedSender<String>);
+
+        impl UpdateSink for CollectingSink {
+            async fn deliver(
+                &mut self,
+                update: &ItemUpdate,
+            ) -> Result<(), Box<dyn Error + Send + Sync>> {
+                self.0
+                    .send(update.get_value("price").unwrap().to_string())?;
+                Ok(())
+            }
+        }
+
+        let (sender, mut receiver) = unbounded_channel();
+        let (listener, handle) = SinkListener::spawn(CollectingSink(sender));
+        listener.on_item_update(&create_test_item_update("1"));
+        listener.on_item_update(&create_test_item_update("2"));
+        drop(listener);
+
+        handle.await.unwrap().unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), "1");
+        assert_eq!(receiver.recv().await.unwrap(), "2");
+    }
+
+    #[tokio::test]
+    async fn test_batched_sink_listener_bounds_the_batches() {
+        let (sender, mut batches) = unbounded_channel::<Vec<ItemUpdate>>();
+        let (listener, handle) = SinkListener::spawn_batched(sender, 2, Duration::from_millis(20));
+        let prices = |batch: Vec<ItemUpdate>| -> Vec<String> {
+            batch
+                .iter()
+                .map(|update| update.get_value("price").unwrap().to_string())
+                .collect()
+        };
+
+        // Full batches are delivered at once.
+        for price in ["1", "2", "3"] {
+            listener.on_item_update(&create_test_item_update(price));
+        }
+        assert_eq!(prices(batches.recv().await.unwrap()), vec!["1", "2"]);
+        // Incomplete ones once the delay has elapsed.
+        let started = Instant::now();
+        assert_eq!(prices(batches.recv().await.unwrap()), vec!["3"]);
+        assert!(started.elapsed() >= Duration::from_millis(10));
+
+        listener.on_item_update(&create_test_item_update("4"));
+        drop(listener);
+        assert_eq!(prices(batches.recv().await.unwrap()), vec!["4"]);
+        handle.await.unwrap().unwrap();
+    }
+
+    /// Sink delivering one update per permit of its semaphore.
+    struct GatedSink(std::sync::Arc<tokio::sync::Semaphore>);
+
+    impl UpdateSink for GatedSink {
+        async fn deliver(
+            &mut self,
+            _update: &ItemUpdate,
+        ) -> Result<(), Box<dyn Error + Send + Sync>> {
+            self.0.acquire().await?.forget();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sink_listener_reports_its_queue_depth() {
+        let gate = std::sync::Arc::new(tokio::sync::Semaphore::new(0));
+        let subscription_lag = ConsumerLag::default();
+        let (listener, handle) = SinkListener::spawn(GatedSink(gate.clone()));
+        let listener = listener.with_consumer_lag(subscription_lag.clone());
+        let sink_lag = listener.get_consumer_lag();
+
+        for price in ["1", "2", "3"] {
+            listener.on_item_update(&create_test_item_update(price));
+        }
+        assert_eq!(sink_lag.get_depth(), 3);
+        assert_eq!(subscription_lag.get_depth(), 3);
+
+        gate.add_permits(3);
+        drop(listener);
+        handle.await.unwrap().unwrap();
+        assert_eq!(sink_lag.get_depth(), 0);
+        assert_eq!(subscription_lag.get_depth(), 0);
+        assert_eq!(subscription_lag.get_high_water_mark(), 3);
+    }
+}
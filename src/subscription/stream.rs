@@ -0,0 +1,602 @@
+use crate::subscription::{ItemUpdate, SubscriptionListener};
+use futures_util::Stream;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender, unbounded_channel};
+use tokio::time::{Instant, Interval, MissedTickBehavior, Sleep, interval_at, sleep_until};
+
+/// Merges a newer update of an item into the previous one, keeping the fields changed by
+/// either of them, the newer values winning.
+fn merge_update(previous: &mut ItemUpdate, mut update: ItemUpdate) {
+    for (field, value) in previous.changed_fields.drain() {
+        update.changed_fields.entry(field).or_insert(value);
+    }
+    *previous = update;
+}
+
+/// The latest update of each item, in order of first arrival.
+#[derive(Debug, Default)]
+struct LatestPerItem {
+    order: VecDeque<usize>,
+    updates: HashMap<usize, ItemUpdate>,
+}
+
+impl LatestPerItem {
+    fn push(&mut self, update: ItemUpdate) {
+        match self.updates.get_mut(&update.item_pos) {
+            Some(previous) => merge_update(previous, update),
+            None => {
+                self.order.push_back(update.item_pos);
+                self.updates.insert(update.item_pos, update);
+            }
+        }
+    }
+
+    fn pop(&mut self) -> Option<ItemUpdate> {
+        let item_pos = self.order.pop_front()?;
+        self.updates.remove(&item_pos)
+    }
+}
+
+/// Combinators shaping streams of updates, such as the ones received through a channel fed by
+/// a listener, along the items of a Subscription.
+///
+/// Conflated, debounced and sampled updates carry the latest values of their item, and the
+/// fields changed by any of the updates they replace, so that no change goes unnoticed. The
+/// time-based combinators must be polled within a Tokio runtime.
+pub trait UpdateStreamExt: Stream<Item = ItemUpdate> + Sized {
+    /// Conflates the updates of the same item which are waiting to be consumed, so that a slow
+    /// consumer only gets the latest state of each item instead of falling behind.
+    fn conflate_per_item(self) -> ConflatePerItem<Self>
+    where
+        Self: Unpin,
+    {
+        ConflatePerItem {
+            inner: self,
+            buffer: LatestPerItem::default(),
+            done: false,
+        }
+    }
+
+    /// Holds the updates of an item changing the given field until the field has not changed
+    /// for the given time, delivering only the latest one, e.g. to ignore a quote flickering
+    /// before it settles. The updates of the other items, and the ones not changing the field,
+    /// are delivered at once, unless a change of the same item is held.
+    ///
+    /// # Parameters
+    ///
+    /// * `field`: The name of the field.
+    /// * `quiet`: The time without changes of the field after which an update is delivered.
+    fn debounce_field(self, field: &str, quiet: Duration) -> DebounceField<Self>
+    where
+        Self: Unpin,
+    {
+        DebounceField {
+            inner: self,
+            field: field.to_string(),
+            quiet,
+            held: HashMap::new(),
+            ready: VecDeque::new(),
+            sleep: None,
+            done: false,
+        }
+    }
+
+    /// Delivers, once per period, the latest update of each item updated during the period, e.g.
+    /// to refresh a display at a fixed rate whatever the rate of the updates.
+    ///
+    /// # Parameters
+    ///
+    /// * `period`: The sampling period.
+    fn sample_latest(self, period: Duration) -> SampleLatest<Self>
+    where
+        Self: Unpin,
+    {
+        SampleLatest {
+            inner: self,
+            period,
+            interval: None,
+            buffer: LatestPerItem::default(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Splits the updates by item, yielding a stream of the updates of each item the first time
+    /// the item is updated, e.g. to process the items concurrently.
+    ///
+    /// The item streams are fed while the returned stream is polled, so it has to be polled
+    /// until it ends; an item stream which is dropped no longer receives updates.
+    fn split_by_item(self) -> SplitByItem<Self>
+    where
+        Self: Unpin,
+    {
+        SplitByItem {
+            inner: self,
+            senders: HashMap::new(),
+        }
+    }
+}
+
+impl<S: Stream<Item = ItemUpdate>> UpdateStreamExt for S {}
+
+/// Stream returned by `UpdateStreamExt::conflate_per_item()`.
+#[derive(Debug)]
+pub struct ConflatePerItem<S> {
+    inner: S,
+    buffer: LatestPerItem,
+    done: bool,
+}
+
+impl<S: Stream<Item = ItemUpdate> + Unpin> Stream for ConflatePerItem<S> {
+    type Item = ItemUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ItemUpdate>> {
+        let this = self.get_mut();
+        while !this.done {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(update)) => this.buffer.push(update),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        match this.buffer.pop() {
+            Some(update) => Poll::Ready(Some(update)),
+            None if this.done => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+/// Stream returned by `UpdateStreamExt::debounce_field()`.
+#[derive(Debug)]
+pub struct DebounceField<S> {
+    inner: S,
+    field: String,
+    quiet: Duration,
+    /// The updates held, by item, with the time they can be delivered.
+    held: HashMap<usize, (ItemUpdate, Instant)>,
+    ready: VecDeque<ItemUpdate>,
+    sleep: Option<Pin<Box<Sleep>>>,
+    done: bool,
+}
+
+impl<S> DebounceField<S> {
+    fn push(&mut self, update: ItemUpdate) {
+        let changes_field = update.changed_fields.contains_key(&self.field);
+        match self.held.get_mut(&update.item_pos) {
+            Some((held, deadline)) => {
+                merge_update(held, update);
+                if changes_field {
+                    *deadline = Instant::now() + self.quiet;
+                }
+            }
+            None if changes_field => {
+                let deadline = Instant::now() + self.quiet;
+                self.held.insert(update.item_pos, (update, deadline));
+            }
+            None => self.ready.push_back(update),
+        }
+    }
+
+    /// Moves the updates held until the given time to the ready ones, in order of deadline.
+    fn release(&mut self, until: Option<Instant>) {
+        let mut released: Vec<(usize, Instant)> = self
+            .held
+            .iter()
+            .filter(|(_, (_, deadline))| until.is_none_or(|until| *deadline <= until))
+            .map(|(item_pos, (_, deadline))| (*item_pos, *deadline))
+            .collect();
+        released.sort_by_key(|(_, deadline)| *deadline);
+        for (item_pos, _) in released {
+            if let Some((update, _)) = self.held.remove(&item_pos) {
+                self.ready.push_back(update);
+            }
+        }
+    }
+}
+
+impl<S: Stream<Item = ItemUpdate> + Unpin> Stream for DebounceField<S> {
+    type Item = ItemUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ItemUpdate>> {
+        let this = self.get_mut();
+        loop {
+            while !this.done {
+                match Pin::new(&mut this.inner).poll_next(cx) {
+                    Poll::Ready(Some(update)) => this.push(update),
+                    Poll::Ready(None) => {
+                        // Nothing can change the held updates anymore.
+                        this.done = true;
+                        this.release(None);
+                    }
+                    Poll::Pending => break,
+                }
+            }
+            if let Some(update) = this.ready.pop_front() {
+                return Poll::Ready(Some(update));
+            }
+            if this.done {
+                return Poll::Ready(None);
+            }
+            let Some(earliest) = this.held.values().map(|(_, deadline)| *deadline).min() else {
+                return Poll::Pending;
+            };
+            if earliest <= Instant::now() {
+                this.release(Some(earliest));
+                continue;
+            }
+            let sleep = this
+                .sleep
+                .get_or_insert_with(|| Box::pin(sleep_until(earliest)));
+            sleep.as_mut().reset(earliest);
+            if sleep.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Stream returned by `UpdateStreamExt::sample_latest()`.
+#[derive(Debug)]
+pub struct SampleLatest<S> {
+    inner: S,
+    period: Duration,
+    /// The sampling clock, started at the first poll.
+    interval: Option<Interval>,
+    buffer: LatestPerItem,
+    ready: VecDeque<ItemUpdate>,
+    done: bool,
+}
+
+impl<S: Stream<Item = ItemUpdate> + Unpin> Stream for SampleLatest<S> {
+    type Item = ItemUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ItemUpdate>> {
+        let this = self.get_mut();
+        let period = this.period;
+        let interval = this.interval.get_or_insert_with(|| {
+            let mut interval = interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+        while !this.done {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(update)) => this.buffer.push(update),
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        loop {
+            if let Some(update) = this.ready.pop_front() {
+                return Poll::Ready(Some(update));
+            }
+            if this.done {
+                // The last sample is delivered without waiting for the end of the period.
+                return Poll::Ready(this.buffer.pop());
+            }
+            match interval.poll_tick(cx) {
+                Poll::Ready(_) => {
+                    while let Some(update) = this.buffer.pop() {
+                        this.ready.push_back(update);
+                    }
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// Stream returned by `UpdateStreamExt::split_by_item()`, yielding a stream per item.
+#[derive(Debug)]
+pub struct SplitByItem<S> {
+    inner: S,
+    senders: HashMap<usize, UnboundedSender<ItemUpdate>>,
+}
+
+impl<S: Stream<Item = ItemUpdate> + Unpin> Stream for SplitByItem<S> {
+    type Item = ItemStream;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ItemStream>> {
+        let this = self.get_mut();
+        loop {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(update)) => {
+                    if let Some(sender) = this.senders.get(&update.item_pos) {
+                        // The item stream may have been dropped, if no longer needed.
+                        let _ = sender.send(update);
+                        continue;
+                    }
+                    let (sender, receiver) = unbounded_channel();
+                    let item_stream = ItemStream {
+                        item_pos: update.item_pos,
+                        item_name: update.item_name.clone(),
+                        receiver,
+                    };
+                    this.senders.insert(update.item_pos, sender.clone());
+                    let _ = sender.send(update);
+                    return Poll::Ready(Some(item_stream));
+                }
+                Poll::Ready(None) => {
+                    // The item streams end too.
+                    this.senders.clear();
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+    }
+}
+
+/// The updates of a single item, as yielded by `UpdateStreamExt::split_by_item()`.
+#[derive(Debug)]
+pub struct ItemStream {
+    item_pos: usize,
+    item_name: Option<String>,
+    receiver: UnboundedReceiver<ItemUpdate>,
+}
+
+impl ItemStream {
+    /// Returns the 1-based position of the item.
+    pub fn get_item_pos(&self) -> usize {
+        self.item_pos
+    }
+
+    /// Returns the name of the item, if the Subscription was given an item list.
+    pub fn get_item_name(&self) -> Option<&str> {
+        self.item_name.as_deref()
+    }
+}
+
+impl Stream for ItemStream {
+    type Item = ItemUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ItemUpdate>> {
+        self.get_mut().receiver.poll_recv(cx)
+    }
+}
+
+/// The updates shared between an `UpdateStream` and the listener feeding it.
+#[derive(Debug, Default)]
+struct UpdateQueue {
+    /// The updates waiting to be consumed, in order of arrival.
+    queued: VecDeque<ItemUpdate>,
+    /// The updates received while `queued` is full, conflated per item.
+    overflow: LatestPerItem,
+    /// The number of updates `queued` can hold.
+    capacity: usize,
+    /// The task waiting for an update, if any.
+    waker: Option<Waker>,
+    /// Whether the listener is gone, so that no update will follow the queued ones.
+    closed: bool,
+}
+
+/// Stream of the updates of a Subscription, returned by `Subscription::updates()`.
+///
+/// The stream holds a bounded number of updates in order of arrival. When the consumer falls
+/// behind and the bound is reached, the session task is never made to wait: the further
+/// updates are conflated per item, as by `UpdateStreamExt::conflate_per_item()`, and delivered
+/// once the queued ones are consumed, so that a slow consumer gets the latest state of each
+/// item with bounded memory. The stream ends once the Subscription is dropped, for instance
+/// when it is removed from its client.
+#[derive(Debug)]
+pub struct UpdateStream {
+    queue: Arc<Mutex<UpdateQueue>>,
+}
+
+impl UpdateStream {
+    /// Creates a stream together with the listener feeding it.
+    ///
+    /// # Parameters
+    ///
+    /// * `capacity`: the number of updates held in order of arrival, at least 1.
+    pub(crate) fn new(capacity: usize) -> (UpdateStream, UpdateStreamListener) {
+        let queue = Arc::new(Mutex::new(UpdateQueue {
+            capacity: capacity.max(1),
+            ..UpdateQueue::default()
+        }));
+        (
+            UpdateStream {
+                queue: queue.clone(),
+            },
+            UpdateStreamListener { queue },
+        )
+    }
+}
+
+impl Stream for UpdateStream {
+    type Item = ItemUpdate;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<ItemUpdate>> {
+        let mut queue = self.queue.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(update) = queue.queued.pop_front() {
+            return Poll::Ready(Some(update));
+        }
+        if let Some(update) = queue.overflow.pop() {
+            return Poll::Ready(Some(update));
+        }
+        if queue.closed {
+            return Poll::Ready(None);
+        }
+        queue.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// `SubscriptionListener` feeding an `UpdateStream`, added by `Subscription::updates()`.
+#[derive(Debug)]
+pub(crate) struct UpdateStreamListener {
+    queue: Arc<Mutex<UpdateQueue>>,
+}
+
+impl SubscriptionListener for UpdateStreamListener {
+    fn on_item_update(&self, update: &ItemUpdate) {
+        let mut queue = self.queue.lock().unwrap_or_else(|err| err.into_inner());
+        // Once updates overflow, the later ones are conflated too, not to reorder an item.
+        if queue.queued.len() < queue.capacity && queue.overflow.order.is_empty() {
+            queue.queued.push_back(update.clone());
+        } else {
+            queue.overflow.push(update.clone());
+        }
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Drop for UpdateStreamListener {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap_or_else(|err| err.into_inner());
+        queue.closed = true;
+        if let Some(waker) = queue.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::StreamExt;
+    use futures_util::stream;
+
+    fn update(item_pos: usize, changes: &[(&str, &str)]) -> ItemUpdate {
+        let changed_fields: HashMap<String, String> = changes
+            .iter()
+            .map(|(field, value)| (field.to_string(), value.to_string()))
+            .collect();
+        ItemUpdate {
+            item_name: Some(format!("item{}", item_pos)),
+            item_pos,
+            fields: changed_fields
+                .iter()
+                .map(|(field, value)| (field.clone(), Some(value.clone())))
+                .collect(),
+            changed_fields,
+            is_snapshot: false,
+            field_sources: HashMap::new(),
+            decoded_fields: HashMap::new(),
+        }
+    }
+
+    /// A stream fed through a channel, which stays open while the sender is alive.
+    fn channel_stream() -> (
+        UnboundedSender<ItemUpdate>,
+        impl Stream<Item = ItemUpdate> + Unpin,
+    ) {
+        let (sender, receiver) = unbounded_channel();
+        let stream = stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|update| (update, receiver))
+        });
+        (sender, Box::pin(stream))
+    }
+
+    #[tokio::test]
+    async fn test_update_stream_conflates_beyond_its_capacity() {
+        let (mut stream, listener) = UpdateStream::new(2);
+        listener.on_item_update(&update(1, &[("bid", "1")]));
+        listener.on_item_update(&update(2, &[("bid", "5")]));
+        // The queue is full: the next updates are conflated per item.
+        listener.on_item_update(&update(1, &[("bid", "2")]));
+        listener.on_item_update(&update(3, &[("bid", "7")]));
+        listener.on_item_update(&update(1, &[("ask", "3")]));
+        drop(listener);
+
+        let received: Vec<ItemUpdate> = (&mut stream).collect().await;
+        let values: Vec<(usize, Option<&str>)> = received
+            .iter()
+            .map(|update| (update.item_pos, update.get_value("bid")))
+            .collect();
+        assert_eq!(
+            values,
+            vec![(1, Some("1")), (2, Some("5")), (1, None), (3, Some("7"))]
+        );
+        assert_eq!(received[2].get_changed_fields().len(), 2);
+        assert!(stream.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_stream_wakes_the_consumer() {
+        let (mut stream, listener) = UpdateStream::new(4);
+        let consumer = tokio::spawn(async move { stream.next().await });
+        tokio::task::yield_now().await;
+        listener.on_item_update(&update(1, &[("bid", "1")]));
+        let received = consumer.await.unwrap().unwrap();
+        assert_eq!(received.get_value("bid"), Some("1"));
+    }
+
+    #[tokio::test]
+    async fn test_conflate_per_item_keeps_the_latest_update() {
+        let updates = stream::iter(vec![
+            update(1, &[("bid", "1"), ("ask", "2")]),
+            update(2, &[("bid", "5")]),
+            update(1, &[("bid", "3")]),
+        ]);
+        let conflated: Vec<ItemUpdate> = updates.conflate_per_item().collect().await;
+        assert_eq!(conflated.len(), 2);
+        assert_eq!(conflated[0].item_pos, 1);
+        assert_eq!(conflated[0].get_value("bid"), Some("3"));
+        // The changes of the replaced update are kept.
+        assert_eq!(conflated[0].get_changed_fields().len(), 2);
+        assert_eq!(conflated[1].item_pos, 2);
+    }
+
+    #[tokio::test]
+    async fn test_debounce_field_waits_for_the_field_to_settle() {
+        let (sender, updates) = channel_stream();
+        let mut debounced = updates.debounce_field("bid", Duration::from_millis(30));
+        sender.send(update(1, &[("bid", "1")])).unwrap();
+        sender.send(update(1, &[("bid", "2")])).unwrap();
+        sender.send(update(2, &[("ask", "7")])).unwrap();
+
+        // The other updates are not held.
+        let first = debounced.next().await.unwrap();
+        assert_eq!(first.item_pos, 2);
+        let started = Instant::now();
+        let second = debounced.next().await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(20));
+        assert_eq!(second.item_pos, 1);
+        assert_eq!(second.get_value("bid"), Some("2"));
+
+        drop(sender);
+        assert!(debounced.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sample_latest_delivers_once_per_period() {
+        let (sender, updates) = channel_stream();
+        let mut sampled = updates.sample_latest(Duration::from_millis(20));
+        for bid in ["1", "2", "3"] {
+            sender.send(update(1, &[("bid", bid)])).unwrap();
+        }
+
+        let sample = sampled.next().await.unwrap();
+        assert_eq!(sample.get_value("bid"), Some("3"));
+        sender.send(update(1, &[("bid", "4")])).unwrap();
+        drop(sender);
+        assert_eq!(sampled.next().await.unwrap().get_value("bid"), Some("4"));
+        assert!(sampled.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_split_by_item_yields_a_stream_per_item() {
+        let updates = stream::iter(vec![
+            update(1, &[("bid", "1")]),
+            update(2, &[("bid", "5")]),
+            update(1, &[("bid", "2")]),
+        ]);
+        let item_streams: Vec<ItemStream> = updates.split_by_item().collect().await;
+        assert_eq!(item_streams.len(), 2);
+        assert_eq!(item_streams[0].get_item_name(), Some("item1"));
+        assert_eq!(item_streams[1].get_item_pos(), 2);
+
+        let mut item_streams = item_streams.into_iter();
+        let first: Vec<ItemUpdate> = item_streams.next().unwrap().collect().await;
+        assert_eq!(first.len(), 2);
+        assert_eq!(first[1].get_value("bid"), Some("2"));
+    }
+}
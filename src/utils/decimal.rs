@@ -0,0 +1,112 @@
+use crate::utils::{IllegalArgumentException, LightstreamerError};
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Parses a numeric field value into an exact decimal, tolerating the most common locale
+/// formats.
+///
+/// The following conventions are accepted:
+/// - an optional leading `+` or `-` sign and surrounding whitespace;
+/// - spaces, non-breaking spaces, apostrophes and underscores as thousands separators
+///   (`1 234.5`, `1'234.5`);
+/// - both `.` and `,` as decimal separator. When both appear, the last one is the decimal
+///   separator and the other one groups thousands (`1,234.56`, `1.234,56`). When only one of
+///   them appears, it is a thousands separator if it occurs more than once (`1,234,567`),
+///   otherwise it is the decimal separator (`12,5`);
+/// - scientific notation (`1.5e-3`).
+///
+/// # Parameters
+/// - `text` – The value to be parsed.
+///
+/// # Returns
+/// The parsed value, or an `IllegalArgumentException` if the text is not a number.
+pub fn parse_decimal(text: &str) -> Result<Decimal, LightstreamerError> {
+    let invalid = || {
+        LightstreamerError::from(IllegalArgumentException::new(&format!(
+            "Invalid decimal value '{}'",
+            text
+        )))
+    };
+
+    let compact: String = text
+        .trim()
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '\u{a0}' | '\u{202f}' | '\'' | '_'))
+        .collect();
+    let compact = compact.strip_prefix('+').unwrap_or(&compact);
+    if compact.is_empty() {
+        return Err(invalid());
+    }
+
+    let last_dot = compact.rfind('.');
+    let last_comma = compact.rfind(',');
+    let (decimal_separator, grouping_separator) = match (last_dot, last_comma) {
+        (Some(dot), Some(comma)) if dot > comma => (Some('.'), Some(',')),
+        (Some(_), Some(_)) => (Some(','), Some('.')),
+        (Some(_), None) if compact.matches('.').count() > 1 => (None, Some('.')),
+        (Some(_), None) => (Some('.'), None),
+        (None, Some(_)) if compact.matches(',').count() > 1 => (None, Some(',')),
+        (None, Some(_)) => (Some(','), None),
+        (None, None) => (None, None),
+    };
+
+    let mut normalized = String::with_capacity(compact.len());
+    for c in compact.chars() {
+        if Some(c) == grouping_separator {
+            continue;
+        }
+        normalized.push(if Some(c) == decimal_separator { '.' } else { c });
+    }
+
+    if normalized.contains(['e', 'E']) {
+        Decimal::from_scientific(&normalized).map_err(|_| invalid())
+    } else {
+        Decimal::from_str(&normalized).map_err(|_| invalid())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decimal(text: &str) -> Decimal {
+        Decimal::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_parse_plain_values() {
+        assert_eq!(parse_decimal("1234.56").unwrap(), decimal("1234.56"));
+        assert_eq!(parse_decimal(" -0.1 ").unwrap(), decimal("-0.1"));
+        assert_eq!(parse_decimal("+42").unwrap(), decimal("42"));
+        // No binary floating point rounding.
+        assert_eq!(
+            parse_decimal("0.1").unwrap() + parse_decimal("0.2").unwrap(),
+            decimal("0.3")
+        );
+    }
+
+    #[test]
+    fn test_parse_locale_formats() {
+        assert_eq!(parse_decimal("1,234.56").unwrap(), decimal("1234.56"));
+        assert_eq!(parse_decimal("1.234,56").unwrap(), decimal("1234.56"));
+        assert_eq!(parse_decimal("1 234,56").unwrap(), decimal("1234.56"));
+        assert_eq!(parse_decimal("1'234.56").unwrap(), decimal("1234.56"));
+        assert_eq!(parse_decimal("12,5").unwrap(), decimal("12.5"));
+        assert_eq!(parse_decimal("1,234,567").unwrap(), decimal("1234567"));
+        assert_eq!(parse_decimal("1.234.567").unwrap(), decimal("1234567"));
+    }
+
+    #[test]
+    fn test_parse_scientific_notation() {
+        assert_eq!(parse_decimal("1.5e-3").unwrap(), decimal("0.0015"));
+        assert_eq!(parse_decimal("2E2").unwrap(), decimal("200"));
+    }
+
+    #[test]
+    fn test_parse_invalid_values() {
+        assert!(parse_decimal("").is_err());
+        assert!(parse_decimal("  ").is_err());
+        assert!(parse_decimal("abc").is_err());
+        assert!(parse_decimal("1.2.3,4,5").is_err());
+    }
+}
@@ -1,5 +1,7 @@
+use crate::subscription::SubscriptionState;
 use std::error::Error;
 use std::fmt;
+use std::time::Duration;
 
 /// Exception thrown when an illegal or inappropriate argument is passed to a method.
 ///
@@ -70,11 +72,741 @@ impl Error for IllegalStateException {
     }
 }
 
+/// The meaning of an error code sent by Lightstreamer Server in a CONERR, REQERR or ERROR
+/// notification, so that errors can be told apart without parsing their descriptions.
+///
+/// Other notifications, such as END and MSGFAIL, give some of the same codes different
+/// meanings, so their codes are not to be converted to this type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ServerErrorCode {
+    /// 1: the user/password check failed.
+    AuthenticationFailed,
+    /// 2: the requested Adapter Set is not available.
+    AdapterSetUnavailable,
+    /// 7: the maximum number of sessions allowed by the license was reached.
+    LicensedSessionLimitReached,
+    /// 8: the maximum number of sessions configured on the Server was reached.
+    ConfiguredSessionLimitReached,
+    /// 9: the maximum load configured on the Server was reached.
+    ServerLoadLimitReached,
+    /// 10: new sessions are temporarily blocked on the Server.
+    SessionsBlocked,
+    /// 11: streaming is not allowed by the license, only polling is.
+    StreamingNotLicensed,
+    /// 17: the Data Adapter of a subscription is unknown, or no default one is configured.
+    DataAdapterUnavailable,
+    /// 19: the subscription of a request was not found.
+    SubscriptionNotFound,
+    /// 21: the group (the item list) of a subscription is not valid.
+    InvalidGroup,
+    /// 22: the group of a subscription is not valid for its schema.
+    GroupNotAllowedForSchema,
+    /// 23: the schema (the field list) of a subscription is not valid.
+    InvalidSchema,
+    /// 24: the mode of a subscription is not allowed for one of its items.
+    ModeNotAllowed,
+    /// 25: the selector of a subscription is not valid.
+    InvalidSelector,
+    /// 26: unfiltered dispatching is not allowed by the license for one of the items.
+    UnfilteredDispatchingNotLicensed,
+    /// 27: unfiltered dispatching is not supported for one of the items.
+    UnfilteredDispatchingNotSupported,
+    /// 28: unfiltered dispatching is not allowed by the Metadata Adapter.
+    UnfilteredDispatchingNotAllowed,
+    /// 29: the RAW mode is not allowed by the license.
+    RawModeNotLicensed,
+    /// 30: subscriptions are not allowed by the license.
+    SubscriptionsNotLicensed,
+    /// 60: this version of the client is not allowed by the license.
+    ClientVersionNotLicensed,
+    /// 61: the Server could not parse a request of the client.
+    MalformedRequest,
+    /// 65: an unexpected error occurred on the Server while processing a request.
+    UnexpectedError,
+    /// 66: the Metadata Adapter threw an unexpected exception.
+    MetadataAdapterError,
+    /// 68: the Server could not fulfill a request because of an internal error.
+    InternalError,
+    /// 70: the port of the Server address cannot be used.
+    UnusablePort,
+    /// 71: this kind of client is not allowed by the license.
+    ClientNotLicensed,
+    /// A code of 0 or less: the Metadata Adapter refused the request, with a code of its own.
+    MetadataAdapterRefusal(i32),
+    /// Any other code.
+    Other(i32),
+}
+
+impl ServerErrorCode {
+    /// Returns the meaning of the given code.
+    ///
+    /// # Arguments
+    /// * `code` - The error code sent by the Server
+    pub fn from_code(code: i32) -> ServerErrorCode {
+        match code {
+            1 => ServerErrorCode::AuthenticationFailed,
+            2 => ServerErrorCode::AdapterSetUnavailable,
+            7 => ServerErrorCode::LicensedSessionLimitReached,
+            8 => ServerErrorCode::ConfiguredSessionLimitReached,
+            9 => ServerErrorCode::ServerLoadLimitReached,
+            10 => ServerErrorCode::SessionsBlocked,
+            11 => ServerErrorCode::StreamingNotLicensed,
+            17 => ServerErrorCode::DataAdapterUnavailable,
+            19 => ServerErrorCode::SubscriptionNotFound,
+            21 => ServerErrorCode::InvalidGroup,
+            22 => ServerErrorCode::GroupNotAllowedForSchema,
+            23 => ServerErrorCode::InvalidSchema,
+            24 => ServerErrorCode::ModeNotAllowed,
+            25 => ServerErrorCode::InvalidSelector,
+            26 => ServerErrorCode::UnfilteredDispatchingNotLicensed,
+            27 => ServerErrorCode::UnfilteredDispatchingNotSupported,
+            28 => ServerErrorCode::UnfilteredDispatchingNotAllowed,
+            29 => ServerErrorCode::RawModeNotLicensed,
+            30 => ServerErrorCode::SubscriptionsNotLicensed,
+            60 => ServerErrorCode::ClientVersionNotLicensed,
+            61 => ServerErrorCode::MalformedRequest,
+            65 => ServerErrorCode::UnexpectedError,
+            66 => ServerErrorCode::MetadataAdapterError,
+            68 => ServerErrorCode::InternalError,
+            70 => ServerErrorCode::UnusablePort,
+            71 => ServerErrorCode::ClientNotLicensed,
+            code if code <= 0 => ServerErrorCode::MetadataAdapterRefusal(code),
+            code => ServerErrorCode::Other(code),
+        }
+    }
+
+    /// Returns the error code sent by the Server.
+    pub fn get_code(&self) -> i32 {
+        match *self {
+            ServerErrorCode::AuthenticationFailed => 1,
+            ServerErrorCode::AdapterSetUnavailable => 2,
+            ServerErrorCode::LicensedSessionLimitReached => 7,
+            ServerErrorCode::ConfiguredSessionLimitReached => 8,
+            ServerErrorCode::ServerLoadLimitReached => 9,
+            ServerErrorCode::SessionsBlocked => 10,
+            ServerErrorCode::StreamingNotLicensed => 11,
+            ServerErrorCode::DataAdapterUnavailable => 17,
+            ServerErrorCode::SubscriptionNotFound => 19,
+            ServerErrorCode::InvalidGroup => 21,
+            ServerErrorCode::GroupNotAllowedForSchema => 22,
+            ServerErrorCode::InvalidSchema => 23,
+            ServerErrorCode::ModeNotAllowed => 24,
+            ServerErrorCode::InvalidSelector => 25,
+            ServerErrorCode::UnfilteredDispatchingNotLicensed => 26,
+            ServerErrorCode::UnfilteredDispatchingNotSupported => 27,
+            ServerErrorCode::UnfilteredDispatchingNotAllowed => 28,
+            ServerErrorCode::RawModeNotLicensed => 29,
+            ServerErrorCode::SubscriptionsNotLicensed => 30,
+            ServerErrorCode::ClientVersionNotLicensed => 60,
+            ServerErrorCode::MalformedRequest => 61,
+            ServerErrorCode::UnexpectedError => 65,
+            ServerErrorCode::MetadataAdapterError => 66,
+            ServerErrorCode::InternalError => 68,
+            ServerErrorCode::UnusablePort => 70,
+            ServerErrorCode::ClientNotLicensed => 71,
+            ServerErrorCode::MetadataAdapterRefusal(code) | ServerErrorCode::Other(code) => code,
+        }
+    }
+
+    /// Returns whether the same request may succeed if sent again later, because the error
+    /// is caused by a temporary condition of the Server, such as its load, rather than by the
+    /// request itself, the configuration or the license.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            ServerErrorCode::LicensedSessionLimitReached
+                | ServerErrorCode::ConfiguredSessionLimitReached
+                | ServerErrorCode::ServerLoadLimitReached
+                | ServerErrorCode::SessionsBlocked
+                | ServerErrorCode::UnexpectedError
+                | ServerErrorCode::InternalError
+        )
+    }
+}
+
+impl From<i32> for ServerErrorCode {
+    fn from(code: i32) -> Self {
+        ServerErrorCode::from_code(code)
+    }
+}
+
+/// Exception raised when Lightstreamer Server refuses a request.
+///
+/// This exception carries the error code and the description sent by the Server, for instance
+/// in a REQERR notification for a subscription request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerException {
+    code: i32,
+    message: String,
+    request: Option<String>,
+}
+
+impl ServerException {
+    /// Creates a new ServerException with the code and message sent by the Server.
+    ///
+    /// # Arguments
+    /// * `code` - The error code sent by the Server
+    /// * `message` - The description of the error sent by the Server
+    ///
+    /// # Returns
+    /// A new ServerException instance
+    pub fn new(code: i32, message: &str) -> ServerException {
+        ServerException {
+            code,
+            message: message.to_string(),
+            request: None,
+        }
+    }
+
+    /// Sets the description of the request refused by the Server, such as the items of a
+    /// subscription, so that the error tells what was being attempted.
+    ///
+    /// # Arguments
+    /// * `request` - The description of the refused request
+    ///
+    /// # Returns
+    /// The ServerException, with the description of the request
+    pub fn with_request(mut self, request: &str) -> ServerException {
+        self.request = Some(request.to_string());
+        self
+    }
+
+    /// Returns the error code sent by the Server.
+    pub fn get_code(&self) -> i32 {
+        self.code
+    }
+
+    /// Returns the meaning of the error code sent by the Server.
+    pub fn get_error_code(&self) -> ServerErrorCode {
+        ServerErrorCode::from_code(self.code)
+    }
+
+    /// Returns the description of the error sent by the Server.
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the description of the request refused by the Server, if known.
+    pub fn get_request(&self) -> Option<&str> {
+        self.request.as_deref()
+    }
+}
+
+impl fmt::Display for ServerException {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Server error {}: {}", self.code, self.message)?;
+        if let Some(request) = &self.request {
+            write!(f, " (refused request: {})", request)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ServerException {}
+
+/// Error notified when the Server sends a TLCP message longer than the limit configured through
+/// `ConnectionOptions::set_max_message_size()`. The message is discarded instead of ending the
+/// session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OversizedMessageError {
+    size: usize,
+    limit: usize,
+    subscription_id: Option<usize>,
+    resubscribed: bool,
+}
+
+impl OversizedMessageError {
+    /// Creates a new OversizedMessageError.
+    ///
+    /// # Arguments
+    /// * `size` - The length of the message, in bytes
+    /// * `limit` - The configured limit, in bytes
+    /// * `subscription_id` - The subscription the message referred to, if any
+    /// * `resubscribed` - Whether the subscription was resubscribed to resynchronize its data
+    ///
+    /// # Returns
+    /// A new OversizedMessageError instance
+    pub fn new(
+        size: usize,
+        limit: usize,
+        subscription_id: Option<usize>,
+        resubscribed: bool,
+    ) -> OversizedMessageError {
+        OversizedMessageError {
+            size,
+            limit,
+            subscription_id,
+            resubscribed,
+        }
+    }
+
+    /// Returns the length of the message, in bytes.
+    pub fn get_size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the configured limit, in bytes.
+    pub fn get_limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the ID of the subscription the message referred to, if it was a subscription
+    /// notification, such as an update.
+    pub fn get_subscription_id(&self) -> Option<usize> {
+        self.subscription_id
+    }
+
+    /// Returns whether the subscription was resubscribed, as configured through
+    /// `ConnectionOptions::set_resubscribe_on_oversized_message()`.
+    pub fn is_resubscribed(&self) -> bool {
+        self.resubscribed
+    }
+}
+
+impl fmt::Display for OversizedMessageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Discarded message of {} bytes, exceeding the limit of {} bytes",
+            self.size, self.limit
+        )?;
+        if let Some(subscription_id) = self.subscription_id {
+            write!(f, " (subscription {})", subscription_id)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for OversizedMessageError {}
+
+/// Error returned by the parsing functions of the `protocol` module when a notification is
+/// malformed or is not of the expected kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProtocolError {
+    line: String,
+    reason: String,
+}
+
+impl ProtocolError {
+    /// Creates a new ProtocolError.
+    ///
+    /// # Arguments
+    /// * `line` - The notification that could not be parsed
+    /// * `reason` - What is wrong with the notification
+    ///
+    /// # Returns
+    /// A new ProtocolError instance
+    pub fn new(line: &str, reason: &str) -> ProtocolError {
+        ProtocolError {
+            line: line.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Returns the notification that could not be parsed.
+    pub fn get_line(&self) -> &str {
+        &self.line
+    }
+
+    /// Returns what is wrong with the notification.
+    pub fn get_reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+impl fmt::Display for ProtocolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Malformed notification '{}': {}", self.line, self.reason)
+    }
+}
+
+impl Error for ProtocolError {}
+
+/// Error notified when the Server does not answer a request in time, such as a subscription
+/// request left without SUBOK or REQERR because its Data Adapter hangs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutError {
+    operation: String,
+    timeout: Duration,
+}
+
+impl TimeoutError {
+    /// Creates a new TimeoutError.
+    ///
+    /// # Arguments
+    /// * `operation` - The description of the request left without answer
+    /// * `timeout` - The time waited for the answer
+    ///
+    /// # Returns
+    /// A new TimeoutError instance
+    pub fn new(operation: &str, timeout: Duration) -> TimeoutError {
+        TimeoutError {
+            operation: operation.to_string(),
+            timeout,
+        }
+    }
+
+    /// Returns the description of the request left without answer.
+    pub fn get_operation(&self) -> &str {
+        &self.operation
+    }
+
+    /// Returns the time waited for the answer.
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+}
+
+impl fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "No answer to {} within {} ms",
+            self.operation,
+            self.timeout.as_millis()
+        )
+    }
+}
+
+impl Error for TimeoutError {}
+
+/// Error raised when an operation is requested on a Subscription in a stage of its lifecycle
+/// that does not allow it, such as handing over to a client a Subscription already active.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubscriptionStateError {
+    operation: String,
+    state: SubscriptionState,
+}
+
+impl SubscriptionStateError {
+    /// Creates a new SubscriptionStateError.
+    ///
+    /// # Arguments
+    /// * `operation` - The description of the refused operation
+    /// * `state` - The state of the Subscription when the operation was requested
+    ///
+    /// # Returns
+    /// A new SubscriptionStateError instance
+    pub fn new(operation: &str, state: SubscriptionState) -> SubscriptionStateError {
+        SubscriptionStateError {
+            operation: operation.to_string(),
+            state,
+        }
+    }
+
+    /// Returns the description of the refused operation.
+    pub fn get_operation(&self) -> &str {
+        &self.operation
+    }
+
+    /// Returns the state of the Subscription when the operation was requested.
+    pub fn get_state(&self) -> SubscriptionState {
+        self.state
+    }
+}
+
+impl fmt::Display for SubscriptionStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Cannot {} a subscription in state {}",
+            self.operation, self.state
+        )
+    }
+}
+
+impl Error for SubscriptionStateError {}
+
+impl From<SubscriptionStateError> for IllegalStateException {
+    fn from(error: SubscriptionStateError) -> Self {
+        IllegalStateException::new(&error.to_string())
+    }
+}
+
+/// Warning notified when a subscription receives no update, nor end-of-snapshot, within the
+/// threshold configured through `ConnectionOptions::set_slow_start_threshold()` of its request,
+/// a common sign of a misconfigured Data Adapter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlowStartWarning {
+    subscription: String,
+    threshold: Duration,
+}
+
+impl SlowStartWarning {
+    /// Creates a new SlowStartWarning.
+    ///
+    /// # Arguments
+    /// * `subscription` - The description of the slow subscription
+    /// * `threshold` - The configured threshold
+    ///
+    /// # Returns
+    /// A new SlowStartWarning instance
+    pub fn new(subscription: &str, threshold: Duration) -> SlowStartWarning {
+        SlowStartWarning {
+            subscription: subscription.to_string(),
+            threshold,
+        }
+    }
+
+    /// Returns the description of the slow subscription.
+    pub fn get_subscription(&self) -> &str {
+        &self.subscription
+    }
+
+    /// Returns the configured threshold.
+    pub fn get_threshold(&self) -> Duration {
+        self.threshold
+    }
+}
+
+impl fmt::Display for SlowStartWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "No data for {} within {} ms of its request",
+            self.subscription,
+            self.threshold.as_millis()
+        )
+    }
+}
+
+/// A problem found while validating a configuration, as part of a `ValidationError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationProblem {
+    property: String,
+    message: String,
+}
+
+impl ValidationProblem {
+    /// Creates a new ValidationProblem for the given property.
+    ///
+    /// # Arguments
+    /// * `property` - The name of the property, such as `items` or `max_frequency`
+    /// * `message` - The description of the problem
+    ///
+    /// # Returns
+    /// A new ValidationProblem instance
+    pub fn new(property: &str, message: &str) -> ValidationProblem {
+        ValidationProblem {
+            property: property.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Returns the name of the property the problem refers to.
+    pub fn get_property(&self) -> &str {
+        &self.property
+    }
+
+    /// Returns the description of the problem.
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for ValidationProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.property, self.message)
+    }
+}
+
+/// Error returned when a configuration is not valid, carrying all the problems found rather than
+/// the first one only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    problems: Vec<ValidationProblem>,
+}
+
+impl ValidationError {
+    /// Creates a new ValidationError with the given problems.
+    ///
+    /// # Arguments
+    /// * `problems` - The problems found, in the order they were found
+    ///
+    /// # Returns
+    /// A new ValidationError instance
+    pub fn new(problems: Vec<ValidationProblem>) -> ValidationError {
+        ValidationError { problems }
+    }
+
+    /// Returns the problems found, in the order they were found.
+    pub fn get_problems(&self) -> &[ValidationProblem] {
+        &self.problems
+    }
+
+    /// Returns `Ok(())` if no problem was found, or the error carrying them otherwise.
+    pub(crate) fn check(problems: Vec<ValidationProblem>) -> Result<(), ValidationError> {
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError::new(problems))
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let problems: Vec<String> = self.problems.iter().map(|p| p.to_string()).collect();
+        write!(f, "Invalid configuration: {}", problems.join("; "))
+    }
+}
+
+impl Error for ValidationError {}
+
+/// Single error type of the public API, covering all the failures of the client.
+///
+/// Each variant wraps the specific error type, which can be matched on for details, such as the
+/// code of a `ServerException`; the other error types of the crate convert into it through
+/// `From`, so that `?` works across APIs. Errors from the network, such as I/O and WebSocket
+/// errors, are reported as `Transport`.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum LightstreamerError {
+    /// An illegal or inappropriate argument was passed to a method.
+    #[error(transparent)]
+    IllegalArgument(#[from] IllegalArgumentException),
+    /// A method was invoked at an illegal or inappropriate time.
+    #[error(transparent)]
+    IllegalState(#[from] IllegalStateException),
+    /// An operation is not allowed in the current state of a Subscription.
+    #[error(transparent)]
+    SubscriptionState(#[from] SubscriptionStateError),
+    /// A configuration is not valid.
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    /// The connection to the Server failed or was lost.
+    #[error("Transport failure: {0}")]
+    Transport(#[source] Box<dyn Error + Send + Sync>),
+    /// The Server sent a malformed notification.
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+    /// The Server refused a request.
+    #[error(transparent)]
+    Server(#[from] ServerException),
+    /// The Server did not answer a request in time.
+    #[error(transparent)]
+    Timeout(#[from] TimeoutError),
+    /// A message exceeded the configured maximum size.
+    #[error(transparent)]
+    OversizedMessage(#[from] OversizedMessageError),
+}
+
+impl LightstreamerError {
+    /// Wraps an error that is not among the error types of the crate, such as an I/O or
+    /// WebSocket error, as a `Transport` error.
+    ///
+    /// # Arguments
+    /// * `error` - The error to wrap
+    pub fn transport<E>(error: E) -> LightstreamerError
+    where
+        E: Into<Box<dyn Error + Send + Sync>>,
+    {
+        LightstreamerError::Transport(error.into())
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for LightstreamerError {
+    /// Recovers the error type of the crate behind a boxed error, so that errors propagated
+    /// through internal layers keep their variant. Other errors are reported as `Transport`.
+    fn from(error: Box<dyn Error + Send + Sync>) -> Self {
+        macro_rules! recover {
+            ($error:ident, $($variant:ident($kind:ty)),+) => {
+                $(
+                    let $error = match $error.downcast::<$kind>() {
+                        Ok(error) => return LightstreamerError::$variant(*error),
+                        Err(error) => error,
+                    };
+                )+
+            };
+        }
+        let error = match error.downcast::<LightstreamerError>() {
+            Ok(error) => return *error,
+            Err(error) => error,
+        };
+        recover!(
+            error,
+            IllegalArgument(IllegalArgumentException),
+            IllegalState(IllegalStateException),
+            SubscriptionState(SubscriptionStateError),
+            Validation(ValidationError),
+            Protocol(ProtocolError),
+            Server(ServerException),
+            Timeout(TimeoutError),
+            OversizedMessage(OversizedMessageError)
+        );
+        LightstreamerError::Transport(error)
+    }
+}
+
+impl From<std::io::Error> for LightstreamerError {
+    fn from(error: std::io::Error) -> Self {
+        LightstreamerError::Transport(Box::new(error))
+    }
+}
+
+impl From<tokio_tungstenite::tungstenite::Error> for LightstreamerError {
+    fn from(error: tokio_tungstenite::tungstenite::Error) -> Self {
+        LightstreamerError::Transport(Box::new(error))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::error::Error;
 
+    #[test]
+    fn test_lightstreamer_error_keeps_the_error_type() {
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(ServerException::new(19, "Not found"));
+        let error = LightstreamerError::from(boxed);
+        assert!(matches!(&error, LightstreamerError::Server(server) if server.get_code() == 19));
+        assert_eq!(
+            error.to_string(),
+            ServerException::new(19, "Not found").to_string()
+        );
+
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(IllegalStateException::new("Gone"));
+        assert!(matches!(
+            LightstreamerError::from(boxed),
+            LightstreamerError::IllegalState(_)
+        ));
+
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let error = LightstreamerError::from(io);
+        assert!(matches!(error, LightstreamerError::Transport(_)));
+        assert_eq!(error.to_string(), "Transport failure: reset");
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn test_oversized_message_error_display() {
+        let error = OversizedMessageError::new(2048, 1024, Some(3), true);
+        assert_eq!(
+            error.to_string(),
+            "Discarded message of 2048 bytes, exceeding the limit of 1024 bytes (subscription 3)"
+        );
+        assert!(error.is_resubscribed());
+        let error = OversizedMessageError::new(2048, 1024, None, false);
+        assert_eq!(error.get_subscription_id(), None);
+        assert!(!error.to_string().contains("subscription"));
+    }
+
+    #[test]
+    fn test_validation_error_carries_all_problems() {
+        let error = ValidationError::new(vec![
+            ValidationProblem::new("items", "no item list or item group is set"),
+            ValidationProblem::new("max_frequency", "must be a positive number"),
+        ]);
+        assert_eq!(error.get_problems().len(), 2);
+        assert_eq!(error.get_problems()[1].get_property(), "max_frequency");
+        assert_eq!(
+            error.to_string(),
+            "Invalid configuration: items: no item list or item group is set; max_frequency: must be a positive number"
+        );
+        assert!(ValidationError::check(Vec::new()).is_ok());
+    }
+
     // Test trait implementations for IllegalArgumentException
     #[test]
     fn test_illegal_argument_exception_creation() {
@@ -121,6 +853,52 @@ mod tests {
         assert_eq!(description, error_msg);
     }
 
+    // Test trait implementations for ServerException
+    #[test]
+    fn test_server_exception_creation() {
+        let exception = ServerException::new(21, "bad Group name");
+
+        assert_eq!(exception.get_code(), 21);
+        assert_eq!(exception.get_message(), "bad Group name");
+        assert_eq!(exception.to_string(), "Server error 21: bad Group name");
+        assert_eq!(exception.get_request(), None);
+        assert_eq!(exception.get_error_code(), ServerErrorCode::InvalidGroup);
+
+        let exception = exception.with_request("subscription 1 to items [item1] in MERGE mode");
+        assert_eq!(
+            exception.to_string(),
+            "Server error 21: bad Group name (refused request: subscription 1 to items [item1] in MERGE mode)"
+        );
+
+        let boxed_error: Box<dyn Error + Send + Sync> = Box::new(exception.clone());
+        assert_eq!(
+            boxed_error.downcast_ref::<ServerException>(),
+            Some(&exception)
+        );
+    }
+
+    #[test]
+    fn test_server_error_codes() {
+        for code in -3..100 {
+            assert_eq!(ServerErrorCode::from_code(code).get_code(), code);
+        }
+        assert_eq!(
+            ServerErrorCode::from(1),
+            ServerErrorCode::AuthenticationFailed
+        );
+        assert_eq!(
+            ServerErrorCode::from(0),
+            ServerErrorCode::MetadataAdapterRefusal(0)
+        );
+        assert_eq!(ServerErrorCode::from(-5).get_code(), -5);
+        assert_eq!(ServerErrorCode::from(99), ServerErrorCode::Other(99));
+        assert!(ServerErrorCode::from(9).is_retryable());
+        assert!(ServerErrorCode::from(68).is_retryable());
+        assert!(!ServerErrorCode::from(2).is_retryable());
+        assert!(!ServerErrorCode::from(11).is_retryable());
+        assert!(!ServerErrorCode::from(-1).is_retryable());
+    }
+
     // Test error propagation with ? operator
     #[test]
     fn test_error_propagation() {
@@ -0,0 +1,84 @@
+//! The logging macros used throughout the library: those of `tracing` when the `logging`
+//! feature is enabled, and macros discarding their arguments otherwise.
+
+#[cfg(feature = "logging")]
+pub(crate) use tracing::{debug, error, info, trace, warn};
+
+/// The verbosity of a log message, as passed to `LightstreamerClient::make_log()`.
+#[cfg(feature = "logging")]
+pub use tracing::Level;
+
+/// The verbosity of a log message, as passed to `LightstreamerClient::make_log()`, mirroring
+/// the levels of `tracing` for builds without the `logging` feature.
+#[cfg(not(feature = "logging"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Level(LevelInner);
+
+#[cfg(not(feature = "logging"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LevelInner {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+#[cfg(not(feature = "logging"))]
+impl Level {
+    /// The "trace" level.
+    pub const TRACE: Level = Level(LevelInner::Trace);
+    /// The "debug" level.
+    pub const DEBUG: Level = Level(LevelInner::Debug);
+    /// The "info" level.
+    pub const INFO: Level = Level(LevelInner::Info);
+    /// The "warn" level.
+    pub const WARN: Level = Level(LevelInner::Warn);
+    /// The "error" level.
+    pub const ERROR: Level = Level(LevelInner::Error);
+}
+
+/// Discards a log message, still type-checking its arguments so that the values computed only
+/// to be logged do not trigger unused warnings.
+#[cfg(not(feature = "logging"))]
+macro_rules! discard_log {
+    ($format:literal $(, $argument:expr)* $(,)?) => {
+        if false {
+            let _ = format_args!($format $(, $argument)*);
+        }
+    };
+    ($value:expr) => {
+        let _ = &$value;
+    };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! discard_trace {
+    ($($argument:tt)*) => { $crate::utils::logging::discard_log!($($argument)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! discard_debug {
+    ($($argument:tt)*) => { $crate::utils::logging::discard_log!($($argument)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! discard_info {
+    ($($argument:tt)*) => { $crate::utils::logging::discard_log!($($argument)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! discard_warn {
+    ($($argument:tt)*) => { $crate::utils::logging::discard_log!($($argument)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+macro_rules! discard_error {
+    ($($argument:tt)*) => { $crate::utils::logging::discard_log!($($argument)*) };
+}
+
+#[cfg(not(feature = "logging"))]
+pub(crate) use {
+    discard_debug as debug, discard_error as error, discard_info as info, discard_log,
+    discard_trace as trace, discard_warn as warn,
+};
@@ -4,17 +4,41 @@
    Date: 16/5/25
 ******************************************************************************/
 
+#[cfg(feature = "decimal")]
+mod decimal;
 /// Module containing custom error types used throughout the library.
 ///
 /// This module provides specialized error types for handling different error scenarios,
 /// such as illegal arguments and illegal states.
 pub mod error;
 mod proxy;
+#[cfg(all(windows, feature = "windows-service"))]
+mod service;
+#[cfg(feature = "timestamps")]
+mod timestamp;
 mod util;
 
+#[cfg(feature = "logging")]
 mod logger;
+/// The logging macros, backed by `tracing` when the `logging` feature is enabled.
+pub(crate) mod logging;
 
-pub use error::{IllegalArgumentException, IllegalStateException};
+#[cfg(feature = "decimal")]
+pub use decimal::parse_decimal;
+pub use error::{
+    IllegalArgumentException, IllegalStateException, LightstreamerError, OversizedMessageError,
+    ProtocolError, ServerErrorCode, ServerException, SlowStartWarning, SubscriptionStateError,
+    TimeoutError, ValidationError, ValidationProblem,
+};
+#[cfg(feature = "logging")]
 pub use logger::{setup_logger, setup_logger_with_level};
-pub use proxy::Proxy;
-pub use util::{clean_message, parse_arguments, setup_signal_hook};
+pub use logging::Level;
+pub use proxy::{Proxy, ProxyType};
+#[cfg(all(windows, feature = "windows-service"))]
+pub use service::{ServiceStatusReporter, setup_service_control_hook};
+#[cfg(feature = "timestamps")]
+pub use timestamp::{parse_time_of_day, parse_timestamp};
+#[cfg(feature = "signals")]
+pub use util::setup_signal_hook;
+#[allow(deprecated)]
+pub use util::{clean_message, parse_arguments};
@@ -0,0 +1,149 @@
+use crate::utils::logging::info;
+use std::ffi::c_void;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+use tokio::sync::Notify;
+use windows_sys::Win32::Foundation::{ERROR_CALL_NOT_IMPLEMENTED, NO_ERROR};
+use windows_sys::Win32::System::Services::{
+    RegisterServiceCtrlHandlerExW, SERVICE_ACCEPT_PRESHUTDOWN, SERVICE_ACCEPT_SHUTDOWN,
+    SERVICE_ACCEPT_STOP, SERVICE_CONTROL_INTERROGATE, SERVICE_CONTROL_PRESHUTDOWN,
+    SERVICE_CONTROL_SHUTDOWN, SERVICE_CONTROL_STOP, SERVICE_RUNNING, SERVICE_STATUS,
+    SERVICE_STATUS_CURRENT_STATE, SERVICE_STATUS_HANDLE, SERVICE_STOP_PENDING, SERVICE_STOPPED,
+    SERVICE_WIN32_OWN_PROCESS, SetServiceStatus,
+};
+
+/// Reporter of the status of a Windows service to the Service Control Manager, as returned by
+/// `setup_service_control_hook()`.
+#[derive(Debug)]
+pub struct ServiceStatusReporter {
+    handle: SERVICE_STATUS_HANDLE,
+    /// The progress of the pending stop, increased at each report.
+    checkpoint: AtomicU32,
+}
+
+// The status handle is not tied to the thread that registered it.
+unsafe impl Send for ServiceStatusReporter {}
+unsafe impl Sync for ServiceStatusReporter {}
+
+impl ServiceStatusReporter {
+    /// Reports the service as running, accepting the stop and shutdown requests.
+    pub fn report_running(&self) -> io::Result<()> {
+        self.report(
+            SERVICE_RUNNING,
+            SERVICE_ACCEPT_STOP | SERVICE_ACCEPT_SHUTDOWN | SERVICE_ACCEPT_PRESHUTDOWN,
+            Duration::ZERO,
+        )
+    }
+
+    /// Reports the service as stopping, e.g. while the client closes its session.
+    ///
+    /// # Arguments
+    ///
+    /// * `wait_hint` - The time the stop is expected to take before the next report.
+    pub fn report_stop_pending(&self, wait_hint: Duration) -> io::Result<()> {
+        self.report(SERVICE_STOP_PENDING, 0, wait_hint)
+    }
+
+    /// Reports the service as stopped, once `LightstreamerClient::connect()` has returned.
+    pub fn report_stopped(&self) -> io::Result<()> {
+        self.report(SERVICE_STOPPED, 0, Duration::ZERO)
+    }
+
+    fn report(
+        &self,
+        state: SERVICE_STATUS_CURRENT_STATE,
+        controls_accepted: u32,
+        wait_hint: Duration,
+    ) -> io::Result<()> {
+        let checkpoint = match state {
+            SERVICE_STOP_PENDING => self.checkpoint.fetch_add(1, Ordering::Relaxed) + 1,
+            _ => 0,
+        };
+        let status = SERVICE_STATUS {
+            dwServiceType: SERVICE_WIN32_OWN_PROCESS,
+            dwCurrentState: state,
+            dwControlsAccepted: controls_accepted,
+            dwWin32ExitCode: NO_ERROR,
+            dwServiceSpecificExitCode: 0,
+            dwCheckPoint: checkpoint,
+            dwWaitHint: u32::try_from(wait_hint.as_millis()).unwrap_or(u32::MAX),
+        };
+        // SAFETY: the handle was returned by the Service Control Manager and never closed.
+        match unsafe { SetServiceStatus(self.handle, &status) } {
+            0 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Handles the requests of the Service Control Manager, with the shutdown signal as context.
+unsafe extern "system" fn handle_control(
+    control: u32,
+    _event_type: u32,
+    _event_data: *mut c_void,
+    context: *mut c_void,
+) -> u32 {
+    match control {
+        SERVICE_CONTROL_STOP | SERVICE_CONTROL_SHUTDOWN | SERVICE_CONTROL_PRESHUTDOWN => {
+            // SAFETY: the context is the shutdown signal leaked at registration.
+            let shutdown_signal = unsafe { &*(context as *const Notify) };
+            info!(
+                "Received stop request {} from the Service Control Manager",
+                control
+            );
+            shutdown_signal.notify_one();
+            NO_ERROR
+        }
+        // Status queries are answered by the status last reported.
+        SERVICE_CONTROL_INTERROGATE => NO_ERROR,
+        _ => ERROR_CALL_NOT_IMPLEMENTED,
+    }
+}
+
+/// Sets up the handler of the requests of the Windows Service Control Manager, notifying the
+/// shutdown signal when the service is asked to stop, or the system shuts down, so that feed
+/// daemons packaged as Windows services stop cleanly, as they would on Ctrl+C.
+///
+/// It must be called from the service main function, as started by `StartServiceCtrlDispatcherW`
+/// (e.g. through the `windows-service` crate). The returned reporter tells the Service Control
+/// Manager how the service is doing: running once set up, stop pending once the shutdown signal
+/// is notified, and stopped once `LightstreamerClient::connect()` returns.
+///
+/// The handler stays registered, and the shutdown signal alive, until the process exits.
+///
+/// # Arguments
+///
+/// * `service_name` - The name the service is installed with.
+/// * `shutdown_signal` - An Arc<Notify> that will be notified when the service is asked to stop.
+///
+/// # Errors
+///
+/// Returns the error of the Service Control Manager if the handler cannot be registered, such
+/// as when not running as a service.
+pub fn setup_service_control_hook(
+    service_name: &str,
+    shutdown_signal: Arc<Notify>,
+) -> io::Result<ServiceStatusReporter> {
+    let name: Vec<u16> = service_name.encode_utf16().chain(Some(0)).collect();
+    let context = Arc::into_raw(shutdown_signal);
+    // SAFETY: the name is NUL-terminated and the context outlives the registration.
+    let handle = unsafe {
+        RegisterServiceCtrlHandlerExW(
+            name.as_ptr(),
+            Some(handle_control),
+            context as *const c_void,
+        )
+    };
+    if handle.is_null() {
+        let error = io::Error::last_os_error();
+        // SAFETY: the context was not registered, so it is released exactly once.
+        drop(unsafe { Arc::from_raw(context) });
+        return Err(error);
+    }
+    Ok(ServiceStatusReporter {
+        handle,
+        checkpoint: AtomicU32::new(0),
+    })
+}
@@ -0,0 +1,142 @@
+use crate::utils::{IllegalArgumentException, LightstreamerError};
+use time::format_description::well_known::Rfc3339;
+use time::macros::format_description;
+use time::{Date, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+/// Parses a time of day in the `HH:MM:SS` format, optionally followed by a fractional part
+/// (`HH:MM:SS.mmm`), as commonly sent by Lightstreamer Data Adapters.
+///
+/// # Parameters
+/// - `text` – The value to be parsed.
+///
+/// # Returns
+/// The parsed time, or an `IllegalArgumentException` if the text is not a valid time of day.
+pub fn parse_time_of_day(text: &str) -> Result<Time, LightstreamerError> {
+    let text = text.trim();
+    let parsed = if text.contains('.') {
+        Time::parse(
+            text,
+            format_description!("[hour]:[minute]:[second].[subsecond]"),
+        )
+    } else {
+        Time::parse(text, format_description!("[hour]:[minute]:[second]"))
+    };
+    parsed.map_err(|err| {
+        IllegalArgumentException::new(&format!("Invalid time of day '{}': {}", text, err)).into()
+    })
+}
+
+/// Parses a timestamp in one of the formats commonly sent by Lightstreamer Data Adapters:
+/// - milliseconds since the Unix epoch (`1718000000000`);
+/// - RFC 3339 / ISO 8601 with an offset (`2024-06-10T06:13:20Z`, `2024-06-10T08:13:20+02:00`);
+/// - ISO 8601 without an offset (`2024-06-10T08:13:20`, `2024-06-10 08:13:20.250`), which is
+///   interpreted in the given offset;
+/// - a time of day (`08:13:20`), which is interpreted as today's time in the given offset.
+///
+/// # Parameters
+/// - `text` – The value to be parsed.
+/// - `offset` – The offset of the timezone used by the Data Adapter for values that don't carry
+///   one, such as `UtcOffset::UTC`.
+///
+/// # Returns
+/// The parsed timestamp, or an `IllegalArgumentException` if the text matches none of the
+/// supported formats.
+pub fn parse_timestamp(
+    text: &str,
+    offset: UtcOffset,
+) -> Result<OffsetDateTime, LightstreamerError> {
+    let text = text.trim();
+    let invalid = || {
+        LightstreamerError::from(IllegalArgumentException::new(&format!(
+            "Invalid timestamp '{}'",
+            text
+        )))
+    };
+
+    // Epoch milliseconds.
+    if !text.is_empty()
+        && text
+            .strip_prefix('-')
+            .unwrap_or(text)
+            .chars()
+            .all(|c| c.is_ascii_digit())
+    {
+        let millis = text.parse::<i128>().map_err(|_| invalid())?;
+        return OffsetDateTime::from_unix_timestamp_nanos(millis * 1_000_000)
+            .map_err(|_| invalid());
+    }
+
+    // Time of day only.
+    if !text.contains('-') {
+        let time = parse_time_of_day(text).map_err(|_| invalid())?;
+        let today = OffsetDateTime::now_utc().to_offset(offset).date();
+        return Ok(PrimitiveDateTime::new(today, time).assume_offset(offset));
+    }
+
+    if let Ok(timestamp) = OffsetDateTime::parse(text, &Rfc3339) {
+        return Ok(timestamp);
+    }
+
+    // ISO 8601 without an offset, with either `T` or a space between date and time.
+    let (date, time) = text.split_once(['T', ' ']).ok_or_else(invalid)?;
+    let date =
+        Date::parse(date, format_description!("[year]-[month]-[day]")).map_err(|_| invalid())?;
+    let time = parse_time_of_day(time).map_err(|_| invalid())?;
+    Ok(PrimitiveDateTime::new(date, time).assume_offset(offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{datetime, offset, time};
+
+    #[test]
+    fn test_parse_time_of_day() {
+        assert_eq!(parse_time_of_day("08:13:20").unwrap(), time!(08:13:20));
+        assert_eq!(
+            parse_time_of_day(" 08:13:20.250 ").unwrap(),
+            time!(08:13:20.250)
+        );
+        assert!(parse_time_of_day("8h13").is_err());
+        assert!(parse_time_of_day("25:00:00").is_err());
+    }
+
+    #[test]
+    fn test_parse_epoch_millis() {
+        assert_eq!(
+            parse_timestamp("1718000000250", UtcOffset::UTC).unwrap(),
+            datetime!(2024-06-10 06:13:20.250 UTC)
+        );
+    }
+
+    #[test]
+    fn test_parse_iso_timestamps() {
+        assert_eq!(
+            parse_timestamp("2024-06-10T08:13:20+02:00", UtcOffset::UTC).unwrap(),
+            datetime!(2024-06-10 06:13:20 UTC)
+        );
+        assert_eq!(
+            parse_timestamp("2024-06-10T06:13:20Z", offset!(+2)).unwrap(),
+            datetime!(2024-06-10 06:13:20 UTC)
+        );
+        // Values without an offset are interpreted in the given one.
+        assert_eq!(
+            parse_timestamp("2024-06-10 08:13:20.5", offset!(+2)).unwrap(),
+            datetime!(2024-06-10 06:13:20.5 UTC)
+        );
+    }
+
+    #[test]
+    fn test_parse_time_of_day_timestamp() {
+        let timestamp = parse_timestamp("08:13:20", offset!(+2)).unwrap();
+        assert_eq!(timestamp.offset(), offset!(+2));
+        assert_eq!(timestamp.time(), time!(08:13:20));
+    }
+
+    #[test]
+    fn test_parse_invalid_timestamps() {
+        assert!(parse_timestamp("", UtcOffset::UTC).is_err());
+        assert!(parse_timestamp("yesterday", UtcOffset::UTC).is_err());
+        assert!(parse_timestamp("2024-13-10T08:13:20", UtcOffset::UTC).is_err());
+    }
+}
@@ -1,8 +1,12 @@
+#[cfg(feature = "signals")]
+use crate::utils::logging::info;
+#[cfg(feature = "signals")]
 use std::sync::Arc;
+#[cfg(feature = "signals")]
 use tokio::sync::Notify;
-use tracing::info;
 
 /// Clean the message from newlines and carriage returns and convert it to lowercase. Also remove all brackets.
+#[deprecated(note = "use the parser of `protocol::tlcp` instead")]
 pub fn clean_message(text: &str) -> String {
     let mut result = String::new();
     let mut inside_braces = false;
@@ -54,12 +58,13 @@ pub fn clean_message(text: &str) -> String {
 /// # Implementation Notes:
 /// - This function correctly handles string slices without unnecessary dereferencing.
 /// - Whitespace trimming is applied to each argument to ensure clean parsing.
+#[deprecated(note = "use the parser of `protocol::tlcp` instead")]
 pub fn parse_arguments(input: &str) -> Vec<&str> {
     let mut arguments = Vec::new();
     let mut start = 0;
     let mut in_brackets = 0; // Tracks nesting level for curly braces
 
-    for (i, c) in input.chars().enumerate() {
+    for (i, c) in input.char_indices() {
         match c {
             '{' => in_brackets += 1,
             '}' => in_brackets -= 1,
@@ -104,10 +109,15 @@ pub fn parse_arguments(input: &str) -> Vec<&str> {
 /// - **Unix/Linux**: Handles SIGINT and SIGTERM signals
 /// - **Windows**: Handles Ctrl+C and Ctrl+Break events
 ///
+/// This is a convenience for applications without signal handling of their own: hosts that
+/// already handle signals can stop the client through `LightstreamerClient::connect_with_shutdown()`
+/// instead, and build without the `signals` feature.
+///
+#[cfg(feature = "signals")]
 pub async fn setup_signal_hook(shutdown_signal: Arc<Notify>) {
     // Use ctrlc crate for cross-platform signal handling
     let shutdown_clone = Arc::clone(&shutdown_signal);
-    
+
     // Set up the signal handler - this works on both Unix and Windows
     ctrlc::set_handler(move || {
         info!("Received termination signal, initiating graceful shutdown...");
@@ -117,6 +127,7 @@ pub async fn setup_signal_hook(shutdown_signal: Arc<Notify>) {
 }
 
 #[cfg(test)]
+#[allow(deprecated)]
 mod tests {
     use super::*;
 